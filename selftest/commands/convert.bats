@@ -0,0 +1,24 @@
+#!/usr/bin/env bats
+
+greet() {
+  echo "hello $1"
+}
+
+setup() {
+  cd "$BATS_TEST_TMPDIR"
+}
+
+@test "greets by name" {
+  run greet world
+  [ "$status" -eq 0 ]
+  [ "$output" = "hello world" ]
+}
+
+@test "asserts lines and exit code" {
+  echo "content" > file.txt
+  [ -f file.txt ]
+  run bash -c 'cat file.txt; echo more; exit 3'
+  [ "$status" -eq 3 ]
+  [ "${lines[0]}" = "content" ]
+  [[ "$output" =~ more ]]
+}
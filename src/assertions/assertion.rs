@@ -0,0 +1,89 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::fmt::Debug;
+use std::fmt::Display;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::output::Output;
+
+/// The prefix of a line in the output block of a testcase, that contains an
+/// [`Assertion`] instead of an output expectation
+pub const ASSERTION_PREFIX: &str = "@assert ";
+
+/// Assertion implements checks of a testcase that are evaluated after its
+/// execution and that are not about its output, like `@assert file_exists out.txt`
+pub trait Assertion: AssertionClone + Debug + Send {
+    /// What kind (type name) the assertion has
+    fn kind(&self) -> &'static str;
+
+    /// The arguments of the assertion, as written after its kind
+    fn arguments(&self) -> &str;
+
+    /// Returns the reason why the given output of the execution does not
+    /// satisfy the assertion, if it does not
+    fn check(&self, output: &Output) -> Result<(), String>;
+
+    /// The path of a file that the assertion refers to, whose digest must be
+    /// captured right after the execution (see [`Output::file_digests`])
+    fn file_path(&self) -> Option<&str> {
+        None
+    }
+
+    /// Whether the assertion checks the exit code, which then replaces the
+    /// default expectation of a zero exit code
+    fn asserts_exit_code(&self) -> bool {
+        false
+    }
+}
+
+impl Display for Box<dyn Assertion> {
+    /// The assertion as it is written in a test document
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{ASSERTION_PREFIX}{} {}", self.kind(), self.arguments())
+    }
+}
+
+impl PartialEq<Box<dyn Assertion>> for Box<dyn Assertion> {
+    fn eq(&self, other: &Box<dyn Assertion>) -> bool {
+        format!("{self}") == format!("{other}")
+    }
+}
+
+impl Clone for Box<dyn Assertion> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+impl Serialize for Box<dyn Assertion> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+pub trait AssertionClone {
+    fn clone_box(&self) -> Box<dyn Assertion>;
+}
+
+impl<T: 'static + Assertion + Clone> AssertionClone for T {
+    fn clone_box(&self) -> Box<dyn Assertion> {
+        Box::new(self.clone())
+    }
+}
+
+pub trait AssertionMaker {
+    fn make(arguments: &str) -> Result<Box<dyn Assertion>>;
+}
+
+/// Constructor function for [`Assertion`] implementations
+pub type MakeAssertion = fn(&str) -> Result<Box<dyn Assertion>>;
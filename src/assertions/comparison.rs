@@ -0,0 +1,137 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+
+/// Comparison of an actual value with expected values, as written in the
+/// arguments of an assertion:
+///
+/// ```bnf
+/// <comparison> ::= <operator> <value> | "in" <value> ".." <value> | "in" <value> "..=" <value>
+///   <operator> ::= "==" | "!=" | "<" | "<=" | ">" | ">="
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum Comparison<T> {
+    Equal(T),
+    NotEqual(T),
+    Less(T),
+    LessOrEqual(T),
+    Greater(T),
+    GreaterOrEqual(T),
+
+    /// Range that excludes the upper bound (`in 0..2`)
+    Range(T, T),
+
+    /// Range that includes the upper bound (`in 0..=2`)
+    RangeInclusive(T, T),
+}
+
+impl<T: PartialOrd> Comparison<T> {
+    /// Parse the comparison from the arguments of an assertion, using the
+    /// given function to parse the values
+    pub fn parse(arguments: &str, parse_value: impl Fn(&str) -> Result<T>) -> Result<Self> {
+        let arguments = arguments.trim();
+        if let Some(range) = arguments.strip_prefix("in ") {
+            let range = range.trim();
+            let (from, to, inclusive) = match range.split_once("..=") {
+                Some((from, to)) => (from, to, true),
+                None => match range.split_once("..") {
+                    Some((from, to)) => (from, to, false),
+                    None => bail!("expected range `<from>..<to>` in `{arguments}`"),
+                },
+            };
+            let (from, to) = (parse_value(from.trim())?, parse_value(to.trim())?);
+            return Ok(if inclusive {
+                Self::RangeInclusive(from, to)
+            } else {
+                Self::Range(from, to)
+            });
+        }
+
+        // longer operators first, so that `<=` is not read as `<`
+        for operator in ["==", "!=", "<=", ">=", "<", ">"] {
+            if let Some(value) = arguments.strip_prefix(operator) {
+                let value = parse_value(value.trim())
+                    .with_context(|| format!("invalid value in `{arguments}`"))?;
+                return Ok(match operator {
+                    "==" => Self::Equal(value),
+                    "!=" => Self::NotEqual(value),
+                    "<=" => Self::LessOrEqual(value),
+                    ">=" => Self::GreaterOrEqual(value),
+                    "<" => Self::Less(value),
+                    _ => Self::Greater(value),
+                });
+            }
+        }
+        bail!("expected comparison like `< <value>` or `in <from>..<to>`, got `{arguments}`")
+    }
+
+    /// Whether the actual value is as expected
+    pub fn matches(&self, actual: &T) -> bool {
+        match self {
+            Self::Equal(value) => actual == value,
+            Self::NotEqual(value) => actual != value,
+            Self::Less(value) => actual < value,
+            Self::LessOrEqual(value) => actual <= value,
+            Self::Greater(value) => actual > value,
+            Self::GreaterOrEqual(value) => actual >= value,
+            Self::Range(from, to) => actual >= from && actual < to,
+            Self::RangeInclusive(from, to) => actual >= from && actual <= to,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Comparison;
+
+    fn parse(arguments: &str) -> Comparison<i32> {
+        Comparison::parse(arguments, |value| Ok(value.parse()?))
+            .unwrap_or_else(|err| panic!("parse `{arguments}`: {err}"))
+    }
+
+    #[test]
+    fn test_parse_and_match() {
+        let tests = vec![
+            ("== 1", Comparison::Equal(1), vec![1], vec![0, 2]),
+            ("!= 1", Comparison::NotEqual(1), vec![0, 2], vec![1]),
+            ("< 1", Comparison::Less(1), vec![0], vec![1, 2]),
+            ("<= 1", Comparison::LessOrEqual(1), vec![0, 1], vec![2]),
+            ("> 1", Comparison::Greater(1), vec![2], vec![0, 1]),
+            (">=1", Comparison::GreaterOrEqual(1), vec![1, 2], vec![0]),
+            ("in 0..2", Comparison::Range(0, 2), vec![0, 1], vec![2]),
+            (
+                "in 0..=2",
+                Comparison::RangeInclusive(0, 2),
+                vec![0, 1, 2],
+                vec![3],
+            ),
+        ];
+        for (arguments, expected, matching, not_matching) in tests {
+            let comparison = parse(arguments);
+            assert_eq!(expected, comparison, "from `{arguments}`");
+            for value in matching {
+                assert!(comparison.matches(&value), "`{arguments}` matches {value}");
+            }
+            for value in not_matching {
+                assert!(
+                    !comparison.matches(&value),
+                    "`{arguments}` does not match {value}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for arguments in ["", "1", "in 1", "< one", "in 0..two"] {
+            Comparison::<i32>::parse(arguments, |value| Ok(value.parse()?)).expect_err(arguments);
+        }
+    }
+}
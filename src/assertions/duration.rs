@@ -0,0 +1,83 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::time::Duration;
+
+use anyhow::Result;
+
+use super::assertion::Assertion;
+use super::assertion::AssertionMaker;
+use super::comparison::Comparison;
+use crate::output::Output;
+
+/// Compares how long the execution took (wall clock), which is written as
+/// `@assert duration < 2s` with durations like `500ms`, `2s` or `1m 30s`
+#[derive(Clone, Debug, PartialEq)]
+pub struct DurationAssertion {
+    arguments: String,
+    comparison: Comparison<Duration>,
+}
+
+impl Assertion for DurationAssertion {
+    fn kind(&self) -> &'static str {
+        "duration"
+    }
+
+    fn arguments(&self) -> &str {
+        &self.arguments
+    }
+
+    fn check(&self, output: &Output) -> Result<(), String> {
+        match output.duration {
+            Some(duration) if self.comparison.matches(&duration) => Ok(()),
+            Some(duration) => Err(format!(
+                "execution took {}",
+                humantime::format_duration(Duration::from_millis(duration.as_millis() as u64))
+            )),
+            None => Err("duration of the execution was not measured".to_string()),
+        }
+    }
+}
+
+impl AssertionMaker for DurationAssertion {
+    fn make(arguments: &str) -> Result<Box<dyn Assertion>> {
+        Ok(Box::new(Self {
+            arguments: arguments.to_string(),
+            comparison: Comparison::parse(arguments, |value| {
+                Ok(humantime::parse_duration(value)?)
+            })?,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::DurationAssertion;
+    use crate::assertions::assertion::AssertionMaker;
+    use crate::output::Output;
+
+    fn output(duration: Duration) -> Output {
+        Output {
+            duration: Some(duration),
+            ..("", "", Some(0)).into()
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let assertion = DurationAssertion::make("< 2s").expect("make assertion");
+        assertion
+            .check(&output(Duration::from_millis(1500)))
+            .expect("shorter than 2s");
+        assert_eq!(
+            Err("execution took 2s 500ms".to_string()),
+            assertion.check(&output(Duration::from_millis(2500)))
+        );
+    }
+}
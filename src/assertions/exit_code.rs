@@ -0,0 +1,77 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use anyhow::Result;
+
+use super::assertion::Assertion;
+use super::assertion::AssertionMaker;
+use super::comparison::Comparison;
+use crate::output::ExitStatus;
+use crate::output::Output;
+
+/// Compares the exit code of the execution, which is written as
+/// `@assert exit_code in 0..2` or `@assert exit_code != 1`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExitCodeAssertion {
+    arguments: String,
+    comparison: Comparison<i32>,
+}
+
+impl Assertion for ExitCodeAssertion {
+    fn kind(&self) -> &'static str {
+        "exit_code"
+    }
+
+    fn arguments(&self) -> &str {
+        &self.arguments
+    }
+
+    fn check(&self, output: &Output) -> Result<(), String> {
+        match output.exit_code {
+            ExitStatus::Code(_) | ExitStatus::Signal(_) => {
+                let exit_code = output.exit_code.as_code();
+                if self.comparison.matches(&exit_code) {
+                    Ok(())
+                } else {
+                    Err(format!("exit code is {exit_code}"))
+                }
+            }
+            ref status => Err(format!("execution ended without exit code ({status})")),
+        }
+    }
+
+    fn asserts_exit_code(&self) -> bool {
+        true
+    }
+}
+
+impl AssertionMaker for ExitCodeAssertion {
+    fn make(arguments: &str) -> Result<Box<dyn Assertion>> {
+        Ok(Box::new(Self {
+            arguments: arguments.to_string(),
+            comparison: Comparison::parse(arguments, |value| Ok(value.parse()?))?,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExitCodeAssertion;
+    use crate::assertions::assertion::AssertionMaker;
+
+    #[test]
+    fn test_check() {
+        let assertion = ExitCodeAssertion::make("in 0..2").expect("make assertion");
+        assertion
+            .check(&("", "", Some(1)).into())
+            .expect("exit code in range");
+        assert_eq!(
+            Err("exit code is 2".to_string()),
+            assertion.check(&("", "", Some(2)).into())
+        );
+    }
+}
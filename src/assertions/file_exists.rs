@@ -0,0 +1,78 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use anyhow::Result;
+use anyhow::bail;
+
+use super::assertion::Assertion;
+use super::assertion::AssertionMaker;
+use crate::output::Output;
+
+/// Asserts that the execution left a (readable) file at the given path, which
+/// is relative to the work directory, and is written as
+/// `@assert file_exists out.txt`
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileExistsAssertion {
+    path: String,
+}
+
+impl Assertion for FileExistsAssertion {
+    fn kind(&self) -> &'static str {
+        "file_exists"
+    }
+
+    fn arguments(&self) -> &str {
+        &self.path
+    }
+
+    fn check(&self, output: &Output) -> Result<(), String> {
+        match output.file_digests.get(&self.path) {
+            Some(Some(_)) => Ok(()),
+            _ => Err(format!("file {} does not exist", self.path)),
+        }
+    }
+
+    fn file_path(&self) -> Option<&str> {
+        Some(&self.path)
+    }
+}
+
+impl AssertionMaker for FileExistsAssertion {
+    fn make(arguments: &str) -> Result<Box<dyn Assertion>> {
+        if arguments.is_empty() {
+            bail!("missing path of file_exists assertion")
+        }
+        Ok(Box::new(Self {
+            path: arguments.to_string(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::FileExistsAssertion;
+    use crate::assertions::assertion::AssertionMaker;
+    use crate::output::Output;
+
+    #[test]
+    fn test_check() {
+        let assertion = FileExistsAssertion::make("out.txt").expect("make assertion");
+        let output = |digest: Option<&str>| Output {
+            file_digests: BTreeMap::from([("out.txt".to_string(), digest.map(String::from))]),
+            ..("", "", Some(0)).into()
+        };
+        assertion
+            .check(&output(Some("abc")))
+            .expect("file was produced");
+        assert_eq!(
+            Err("file out.txt does not exist".to_string()),
+            assertion.check(&output(None))
+        );
+    }
+}
@@ -0,0 +1,13 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+pub mod assertion;
+pub mod comparison;
+pub mod duration;
+pub mod exit_code;
+pub mod file_exists;
+pub mod registry;
@@ -0,0 +1,103 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use anyhow::anyhow;
+
+use super::assertion::ASSERTION_PREFIX;
+use super::assertion::Assertion;
+use super::assertion::AssertionMaker;
+use super::assertion::MakeAssertion;
+use super::duration::DurationAssertion;
+use super::exit_code::ExitCodeAssertion;
+use super::file_exists::FileExistsAssertion;
+
+/// Registry for [`Assertion`] constructors, that is used to parse the
+/// `@assert` lines of testcases
+pub struct AssertionRegistry {
+    makers: HashMap<String, MakeAssertion>,
+}
+
+impl AssertionRegistry {
+    pub fn new() -> Self {
+        Self {
+            makers: HashMap::new(),
+        }
+    }
+
+    /// File an [`Assertion`] constructor under given names
+    pub fn register(&mut self, maker: MakeAssertion, names: &[&str]) -> &mut Self {
+        for name in names {
+            self.makers.insert(name.to_string(), maker);
+        }
+        self
+    }
+
+    /// Construct an [`Assertion`] of the given kind (=name)
+    pub fn make(&self, kind: &str, arguments: &str) -> Result<Box<dyn Assertion>> {
+        if let Some(maker) = self.makers.get(kind) {
+            maker(arguments)
+        } else {
+            Err(anyhow!("no assertion maker for `{}` registered", kind))
+        }
+    }
+
+    /// Construct an [`Assertion`] from its line in a test document, with the
+    /// form `@assert <kind> <arguments>`, or return `None` if the line does
+    /// not contain an assertion
+    pub fn parse(&self, line: &str) -> Option<Result<Box<dyn Assertion>>> {
+        let assertion = line.strip_prefix(ASSERTION_PREFIX)?.trim();
+        let (kind, arguments) = assertion
+            .split_once(char::is_whitespace)
+            .unwrap_or((assertion, ""));
+        Some(self.make(kind, arguments.trim()))
+    }
+}
+
+impl Default for AssertionRegistry {
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry
+            .register(DurationAssertion::make, &["duration"])
+            .register(ExitCodeAssertion::make, &["exit_code"])
+            .register(FileExistsAssertion::make, &["file_exists"]);
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AssertionRegistry;
+
+    #[test]
+    fn test_parse() {
+        let registry = AssertionRegistry::default();
+        for line in [
+            "@assert duration < 2s",
+            "@assert exit_code in 0..2",
+            "@assert file_exists out.txt",
+        ] {
+            let assertion = registry
+                .parse(line)
+                .expect("line is an assertion")
+                .unwrap_or_else(|err| panic!("make from `{line}`: {err}"));
+            assert_eq!(line, assertion.to_string());
+        }
+        assert!(registry.parse("@assertion").is_none());
+        assert!(registry.parse("output").is_none());
+        registry
+            .parse("@assert unknown foo")
+            .expect("line is an assertion")
+            .expect_err("unknown assertion kind");
+        registry
+            .parse("@assert exit_code is zero")
+            .expect("line is an assertion")
+            .expect_err("invalid arguments");
+    }
+}
@@ -0,0 +1,232 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use clap::Parser;
+use clap::ValueEnum;
+use dialoguer::console::style;
+use scrut::config::DocumentConfig;
+use scrut::config::Shell;
+use scrut::config::TestCaseConfig;
+use scrut::executors::context::ContextBuilder;
+use scrut::executors::error::ExecutionError;
+use scrut::executors::executor::Executor;
+use scrut::executors::stateful_executor::StatefulExecutor;
+use scrut::output::ExitStatus;
+use scrut::output::Output;
+use scrut::parsers::parser::ParserType;
+
+use super::root::GlobalSharedParameters;
+use crate::utils::ParsedTestFile;
+use crate::utils::ProgressWriter;
+use crate::utils::ProjectConfig;
+use crate::utils::Statistics;
+use crate::utils::TestCaseBenchmark;
+use crate::utils::TestEnvironment;
+use crate::utils::apply_allow_recursion;
+use crate::utils::canonical_shell;
+use crate::utils::get_log_level;
+use crate::utils::kill_detached_processes;
+use crate::utils::make_runner_generator;
+use crate::utils::render_benchmarks_json;
+use crate::utils::render_benchmarks_pretty;
+use crate::utils::stage_fixtures;
+
+/// Output format of benchmarks
+#[derive(Debug, Clone, PartialEq, ValueEnum)]
+pub enum BenchRenderer {
+    Pretty,
+    Json,
+}
+
+/// Measure how long the testcases in test files or directories take
+///
+/// Each test document is executed repeatedly, every time in a fresh work
+/// directory, and the wall time of each testcase is recorded. Outputs are not
+/// validated. Reported are mean, median and 95th percentile per testcase.
+#[derive(Debug, Parser)]
+pub struct Args {
+    /// Path to test files or directories
+    #[clap(required = true)]
+    paths: Vec<PathBuf>,
+
+    /// How many times each test document is executed to measure it
+    #[clap(long, short = 'n', default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    iterations: u32,
+
+    /// How many times each test document is executed before it is measured
+    #[clap(long, default_value_t = 1)]
+    warmup: u32,
+
+    /// Which renderer to use for the benchmarks, with `json` being meant for
+    /// further machine processing
+    #[clap(long, short, value_enum, default_value = "pretty")]
+    renderer: BenchRenderer,
+
+    #[clap(flatten)]
+    global: GlobalSharedParameters,
+}
+
+impl Args {
+    pub(crate) fn run(&self) -> Result<()> {
+        let paths = self.paths.iter().map(|p| p as &Path).collect::<Vec<_>>();
+        let project_config = ProjectConfig::find_any(&paths)?;
+        let documents = super::test::Args::for_paths(self.paths.clone(), self.global.clone())
+            .find_documents(&paths, project_config.as_ref())?
+            .into_iter()
+            .flat_map(ParsedTestFile::per_locale)
+            .collect::<Vec<_>>();
+
+        let pw = ProgressWriter::try_new(
+            documents.len() as u64 * (self.warmup + self.iterations) as u64,
+            get_log_level() <= tracing::Level::WARN,
+            self.global.no_color || !console::colors_enabled(),
+        )?;
+        pw.println(format!(
+            "🔎 Found {} test document(s)",
+            style(documents.len()).bold()
+        ));
+
+        let mut benchmarks = vec![];
+        for document in &documents {
+            benchmarks.extend(self.bench_document(&pw, document)?);
+        }
+        pw.finish_and_clear();
+
+        print!(
+            "{}",
+            match self.renderer {
+                BenchRenderer::Pretty => render_benchmarks_pretty(&benchmarks),
+                BenchRenderer::Json => render_benchmarks_json(&benchmarks)?,
+            }
+        );
+        Ok(())
+    }
+
+    /// Executes the document repeatedly and returns the benchmark of each of
+    /// its testcases
+    fn bench_document(
+        &self,
+        pw: &ProgressWriter,
+        document: &ParsedTestFile,
+    ) -> Result<Vec<TestCaseBenchmark>> {
+        let name = document.display_name();
+        let config = document
+            .config
+            .with_overrides_from(&self.global.to_document_config());
+        let testcase_config = self.global.to_testcase_config();
+        let mut durations = vec![vec![]; document.testcases.len()];
+
+        for iteration in 0..self.warmup + self.iterations {
+            pw.inc(1);
+            pw.set_message(format!(
+                "⏱️ {} ({}/{})",
+                style(&name).yellow(),
+                iteration + 1,
+                self.warmup + self.iterations,
+            ));
+            let outputs = self.execute_document(pw, document, &config, &testcase_config)?;
+            let Some(outputs) = outputs else {
+                pw.println(format!(
+                    "⏩ {}: skipped, because a testcase ended in its skip document code",
+                    style(&name).blue(),
+                ));
+                pw.inc((self.warmup + self.iterations - iteration - 1) as u64);
+                return Ok(vec![]);
+            };
+            if iteration < self.warmup {
+                continue;
+            }
+            for (index, output) in outputs.into_iter().enumerate() {
+                if output.exit_code == ExitStatus::Detached {
+                    continue;
+                }
+                if let Some(duration) = output.duration {
+                    durations[index].push(duration);
+                }
+            }
+        }
+
+        Ok(document
+            .testcases
+            .iter()
+            .zip(durations)
+            .map(|(testcase, durations)| TestCaseBenchmark {
+                location: name.clone(),
+                line_number: testcase.line_number,
+                title: testcase.title.clone(),
+                shell_expression: testcase.shell_expression.clone(),
+                iterations: durations.len(),
+                statistics: Statistics::from_durations(&durations),
+            })
+            .collect())
+    }
+
+    /// Executes all testcases of the document in a fresh environment and
+    /// returns their outputs, which end early if execution is aborted, or
+    /// nothing if the document is skipped
+    fn execute_document(
+        &self,
+        pw: &ProgressWriter,
+        document: &ParsedTestFile,
+        config: &DocumentConfig,
+        testcase_config: &TestCaseConfig,
+    ) -> Result<Option<Vec<Output>>> {
+        let shell_path = canonical_shell(config.shell.as_ref().and_then(Shell::path))?;
+        let mut test_environment = TestEnvironment::new(
+            &shell_path,
+            self.global.work_directory.as_deref(),
+            self.global.keep_temporary_directories,
+        )?;
+        let cram_compat = document.parser_type == ParserType::Cram || self.global.cram_compat;
+        let (test_work_directory, mut env_vars) =
+            test_environment.init_test_file(&document.path, cram_compat)?;
+        stage_fixtures(&document.path, &test_work_directory, config)?;
+        env_vars.extend(document.locale_environment());
+        let env_vars = BTreeMap::from_iter(env_vars.iter().map(|(k, v)| (k as &str, v as &str)));
+        let testcases = document
+            .testcases
+            .iter()
+            .map(|testcase| {
+                let mut testcase = testcase.clone();
+                testcase.config = testcase
+                    .config
+                    .with_overrides_from(testcase_config)
+                    .with_environment(&env_vars);
+                apply_allow_recursion(&mut testcase.config);
+                testcase
+            })
+            .collect::<Vec<_>>();
+
+        // the stateful executor measures each testcase, also for Cram
+        let executor = StatefulExecutor::new(make_runner_generator(&test_environment.shell));
+        let context = ContextBuilder::default()
+            .work_directory(test_work_directory)
+            .temp_directory(test_environment.tmp_directory.as_path_buf())
+            .file(document.path.clone())
+            .config(config.clone())
+            .build()
+            .context("failed to build execution context")?;
+        let outputs = executor.execute_all(&testcases.iter().collect::<Vec<_>>(), &context);
+        kill_detached_processes(pw, &context.detached_processes)?;
+        let outputs = match outputs {
+            Ok(outputs) => outputs,
+            Err(ExecutionError::Timeout(_, outputs) | ExecutionError::Failed(_, outputs)) => {
+                outputs
+            }
+            Err(ExecutionError::Skipped(_)) => return Ok(None),
+            Err(err) => bail!("failing in {:?}: {}", document.path, err),
+        };
+        Ok(Some(outputs))
+    }
+}
@@ -0,0 +1,171 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::fs;
+use std::io::IsTerminal;
+use std::io::stdout;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use clap::Parser;
+use dialoguer::console::style;
+use scrut::escaping::strip_colors;
+use scrut::importers::importer::ImporterType;
+use scrut::parsers::parser::ParserType;
+
+use super::root::GlobalSharedParameters;
+use crate::utils::confirm;
+
+/// Convert test files of other test frameworks into Scrut Markdown documents
+#[derive(Debug, Parser)]
+pub struct Args {
+    /// Path to test files or directories containing test files (with the file
+    /// extension of the framework, e.g. `.bats`)
+    #[clap(required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Test framework the test files are written for
+    #[clap(long, value_enum)]
+    from: ImporterType,
+
+    /// Directory to write the converted documents into. Defaults to the
+    /// directory of each test file.
+    #[clap(long, short)]
+    output_directory: Option<PathBuf>,
+
+    /// Overwrite existing documents without asking
+    #[clap(long, short = 'y')]
+    assume_yes: bool,
+
+    #[clap(flatten)]
+    global: GlobalSharedParameters,
+}
+
+impl Args {
+    pub(crate) fn run(&self) -> Result<()> {
+        let importer = self.from.importer();
+        let files = self.find_test_files()?;
+        if files.is_empty() {
+            println!(
+                "👋 No {} test files found in {:?}. Stopping.",
+                self.from, &self.paths
+            );
+            return Ok(());
+        }
+
+        let mut output = vec![];
+        let (mut count_converted, mut count_attentions) = (0, 0);
+        for path in files {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("read test file {}", path.display()))?;
+            let document = importer
+                .import(&content)
+                .with_context(|| format!("convert {} test file {}", self.from, path.display()))?;
+            let file_name = path.file_name().map_or_else(
+                || path.to_string_lossy().to_string(),
+                |name| name.to_string_lossy().to_string(),
+            );
+            let generated = document.to_markdown(&file_name)?;
+
+            let output_path = self
+                .output_directory
+                .as_ref()
+                .map_or_else(|| path.clone(), |directory| directory.join(&file_name))
+                .with_extension(ParserType::Markdown.file_extension());
+            if !self.assume_yes && output_path.exists() {
+                let confirmed = confirm(
+                    &format!(
+                        "Overwrite existing document {}?",
+                        style(output_path.to_string_lossy()).blue()
+                    ),
+                    false,
+                    self.global.no_color,
+                )?;
+                if !confirmed {
+                    output.push(format!(
+                        "👎 {}: keep as-is, chosen not to overwrite document",
+                        style(output_path.to_string_lossy()).red()
+                    ));
+                    continue;
+                }
+            }
+            if let Some(directory) = output_path.parent() {
+                fs::create_dir_all(directory)
+                    .with_context(|| format!("create directory {}", directory.display()))?;
+            }
+            fs::write(&output_path, generated)
+                .with_context(|| format!("write document {}", output_path.display()))?;
+            count_converted += 1;
+
+            let attentions = document.attentions();
+            count_attentions += attentions.len();
+            output.push(format!(
+                "🌟 {}: converted {} testcase(s) into {}",
+                style(path.to_string_lossy()).green(),
+                document.testcase_count(),
+                style(output_path.to_string_lossy()).blue(),
+            ));
+            for attention in attentions {
+                output.push(format!(
+                    "  ⚠️ {}:{}: {}",
+                    style(path.to_string_lossy()).yellow(),
+                    attention.line_number,
+                    attention.reason
+                ));
+            }
+        }
+        output.push(format!(
+            "{}: {} document(s) converted, {} construct(s) need manual attention",
+            style("Result").underlined(),
+            count_converted,
+            count_attentions,
+        ));
+
+        let mut output = output.join("\n");
+        if self.global.no_color || !stdout().is_terminal() {
+            output = strip_colors(&output)?;
+        }
+        println!("{}", output);
+        Ok(())
+    }
+
+    /// Returns all given files and all files with the extension of the test
+    /// framework in the given directories (recursively), sorted by path
+    fn find_test_files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = vec![];
+        for path in &self.paths {
+            if path.is_dir() {
+                self.find_test_files_within(path, &mut files)?;
+            } else {
+                files.push(path.clone());
+            }
+        }
+        Ok(files)
+    }
+
+    fn find_test_files_within(&self, directory: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        let mut entries = fs::read_dir(directory)
+            .with_context(|| format!("list test files in directory {}", directory.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()
+            .with_context(|| format!("list test files in directory {}", directory.display()))?;
+        entries.sort();
+        for path in entries {
+            if path.is_dir() {
+                self.find_test_files_within(&path, files)?;
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension == self.from.file_extension())
+            {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+}
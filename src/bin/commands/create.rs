@@ -19,8 +19,11 @@ use dialoguer::console::style;
 use scrut::config::DocumentConfig;
 use scrut::config::TestCaseConfig;
 use scrut::executors::bash_script_executor::BashScriptExecutor;
+use scrut::executors::cmd_runner::is_cmd;
 use scrut::executors::context::ContextBuilder;
 use scrut::executors::executor::Executor;
+use scrut::executors::powershell_runner::POWERSHELL_PROMPT;
+use scrut::executors::powershell_runner::is_powershell;
 use scrut::generators::cram::CramTestCaseGenerator;
 use scrut::generators::generator::TestCaseGenerator;
 use scrut::generators::markdown::MarkdownTestCaseGenerator;
@@ -33,6 +36,7 @@ use crate::utils::ProgressWriter;
 use crate::utils::TestEnvironment;
 use crate::utils::canonical_shell;
 use crate::utils::get_log_level;
+use crate::utils::make_executor;
 
 /// Create tests from provided shell expression
 #[derive(Debug, Parser)]
@@ -72,7 +76,16 @@ impl Args {
             self.shell_expression.join(" ")
         };
         let shell_path = canonical_shell(self.global.shell.as_ref().map(|p| p as &Path))?;
-        let executor = BashScriptExecutor::new(&shell_path);
+        let powershell = is_powershell(&shell_path);
+        let executor: Box<dyn Executor> = if powershell || is_cmd(&shell_path) {
+            make_executor(
+                &shell_path,
+                self.format == ParserType::Cram,
+                &DocumentConfig::empty(),
+            )?
+        } else {
+            Box::new(BashScriptExecutor::new(&shell_path))
+        };
 
         // init output
         let pw: ProgressWriter = ProgressWriter::try_new(
@@ -139,8 +152,12 @@ impl Args {
         let testcase = TestCase {
             title: self.title.clone(),
             shell_expression: expression,
+            template_arguments: None,
+            interaction: vec![],
             expectations: vec![],
+            variants: vec![],
             exit_code: None,
+            assertions: vec![],
             line_number: 0,
             config: testcase_config.without_environment(&env_vars),
         };
@@ -149,6 +166,9 @@ impl Args {
         // generate testcase document
         let generator: Box<dyn TestCaseGenerator> = match self.format {
             ParserType::Cram => Box::<CramTestCaseGenerator>::default(),
+            ParserType::Markdown if powershell => {
+                Box::new(MarkdownTestCaseGenerator::default().with_prompt(POWERSHELL_PROMPT))
+            }
             ParserType::Markdown => Box::<MarkdownTestCaseGenerator>::default(),
         };
         let generated = generator
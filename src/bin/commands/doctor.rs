@@ -0,0 +1,101 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use clap::Parser;
+use serde::Serialize;
+
+use super::root::GlobalSharedParameters;
+use crate::utils::CAPABILITIES;
+use crate::utils::Capability;
+use crate::utils::FEATURES;
+use crate::utils::Feature;
+use crate::utils::canonical_shell;
+
+/// Print the version and the available capabilities of this build
+#[derive(Debug, Parser)]
+pub struct Args {
+    /// Print the capabilities as JSON, for machine processing
+    #[clap(long)]
+    json: bool,
+
+    #[clap(flatten)]
+    global: GlobalSharedParameters,
+}
+
+/// The capabilities of this build, as reported by the doctor command
+#[derive(Debug, Serialize)]
+struct Capabilities {
+    version: &'static str,
+    features: Vec<Feature>,
+    capabilities: Vec<Capability>,
+    shell: Option<PathBuf>,
+    docker: Option<PathBuf>,
+}
+
+impl Args {
+    pub(crate) fn run(&self) -> Result<()> {
+        print_capabilities(self.global.shell.as_deref(), self.json)
+    }
+}
+
+/// Prints the version and the capabilities of this build, with the given
+/// shell or the default one, as `scrut doctor` and `scrut --version --json` do
+pub(crate) fn print_capabilities(shell: Option<&Path>, json: bool) -> Result<()> {
+    let shell = canonical_shell(shell);
+    let capabilities = Capabilities {
+        version: crate::VERSION,
+        features: FEATURES.to_vec(),
+        capabilities: CAPABILITIES.to_vec(),
+        shell: shell.as_ref().ok().cloned(),
+        docker: which::which("docker").ok(),
+    };
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&capabilities).context("render capabilities")?
+        );
+        return Ok(());
+    }
+
+    println!("scrut {}", capabilities.version);
+    println!();
+    println!("Features:");
+    for feature in &capabilities.features {
+        println!(
+            "  {} {}: {}",
+            if feature.enabled { "✅" } else { "❌" },
+            feature.name,
+            feature.description
+        );
+    }
+    println!();
+    println!("Built-in:");
+    for capability in &capabilities.capabilities {
+        println!(
+            "  {} {}: {}",
+            if capability.available { "✅" } else { "❌" },
+            capability.name,
+            capability.description
+        );
+    }
+    println!();
+    match shell {
+        Ok(shell) => println!("Shell: {}", shell.display()),
+        Err(err) => println!("Shell: ❌ {err:#}"),
+    }
+    match capabilities.docker {
+        Some(docker) => println!("Docker: {}", docker.display()),
+        None => println!("Docker: ❌ `docker` not found in PATH"),
+    }
+    Ok(())
+}
@@ -0,0 +1,155 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+use clap::Parser;
+use dialoguer::console::style;
+
+use super::root::GlobalSharedParameters;
+use super::test::ValidationFailedError;
+use crate::utils::FileParser;
+use crate::utils::PROJECT_CONFIG_FILE_NAMES;
+use crate::utils::ProjectConfig;
+use crate::utils::has_header;
+use crate::utils::markdown_languages;
+use crate::utils::with_header;
+
+/// Check that test documents begin with a header, and insert it where it is
+/// missing
+///
+/// The header (e.g. a copyright or ownership notice) is expected after the
+/// front-matter of a document, if it has any. Trailing whitespace of its lines
+/// is ignored when checking and normalized when fixing.
+#[derive(Debug, Parser)]
+pub struct Args {
+    /// Path to test files or directories, defaults to the current directory
+    paths: Vec<PathBuf>,
+
+    /// File with the header that all test documents must begin with. Defaults
+    /// to the `header` of the project configuration.
+    #[clap(long, value_name = "PATH")]
+    header_file: Option<PathBuf>,
+
+    /// For markdown format: Language annotations that are considered test cases.
+    /// Defaults to the `markdown_languages` of the project configuration, if
+    /// any, or `scrut` otherwise.
+    #[clap(long, hide = true, num_args = 1..)]
+    markdown_languages: Vec<String>,
+
+    /// Glob match that identifies cram files
+    #[clap(long, default_value = "*.{t,cram}")]
+    match_cram: String,
+
+    /// Glob match that identifies markdown files
+    #[clap(long, default_value = "*.{md,markdown,scrut}")]
+    match_markdown: String,
+
+    /// Insert or normalize the header in test documents, instead of failing.
+    /// Same as `scrut fmt`.
+    #[clap(long)]
+    fix: bool,
+
+    #[clap(flatten)]
+    global: GlobalSharedParameters,
+}
+
+impl Args {
+    /// Checks the headers, as `scrut lint` does
+    pub(crate) fn run(&self) -> Result<()> {
+        self.run_with(self.fix)
+    }
+
+    /// Inserts or normalizes the headers, as `scrut fmt` does
+    pub(crate) fn run_fix(&self) -> Result<()> {
+        self.run_with(true)
+    }
+
+    fn run_with(&self, fix: bool) -> Result<()> {
+        let paths = if self.paths.is_empty() {
+            vec![Path::new(".")]
+        } else {
+            self.paths.iter().map(|p| p as &Path).collect::<Vec<_>>()
+        };
+        let header_file = self
+            .header_file
+            .as_ref()
+            .map(|path| {
+                fs::read_to_string(path)
+                    .with_context(|| format!("read header from {}", path.display()))
+            })
+            .transpose()?;
+
+        let project_config = ProjectConfig::find_any(&paths)?;
+        let markdown_languages =
+            markdown_languages(&self.markdown_languages, project_config.as_ref());
+        let markdown_languages = &markdown_languages.iter().map(|s| &**s).collect::<Vec<_>>();
+        let parser = FileParser::new(&self.match_markdown, &self.match_cram, markdown_languages)
+            .context("create file parser")?;
+        let documents = parser.find_and_parse("lint", &paths, self.global.cram_compat)?;
+
+        let current_directory = std::env::current_dir().context("get current directory")?;
+        let (mut count_invalid, mut count_fixed) = (0, 0);
+        for document in &documents {
+            let display_path = document
+                .path
+                .strip_prefix(&current_directory)
+                .unwrap_or(&document.path);
+            let header = match &header_file {
+                Some(header) => header.clone(),
+                None => ProjectConfig::find(&document.path)?
+                    .and_then(|(_, config)| config.header)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "no `--header-file` given and no `header` configured in a {} file for {}",
+                            PROJECT_CONFIG_FILE_NAMES[0],
+                            display_path.display()
+                        )
+                    })?,
+            };
+
+            let fixed = with_header(&document.content, &header);
+            if fixed == document.content {
+                continue;
+            }
+            if !fix {
+                count_invalid += 1;
+                println!(
+                    "❌ {}: {}",
+                    style(display_path.display()).red(),
+                    if has_header(&document.content, &header) {
+                        "header is not normalized"
+                    } else {
+                        "header is missing"
+                    }
+                );
+                continue;
+            }
+            fs::write(&document.path, fixed)
+                .with_context(|| format!("write {}", display_path.display()))?;
+            count_fixed += 1;
+            println!("✍️ {}: fixed header", style(display_path.display()).green());
+        }
+
+        println!(
+            "Result: {} document(s) checked: {} with invalid header and {} fixed",
+            documents.len(),
+            count_invalid,
+            count_fixed
+        );
+        if count_invalid > 0 {
+            Err(anyhow!(ValidationFailedError))
+        } else {
+            Ok(())
+        }
+    }
+}
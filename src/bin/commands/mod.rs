@@ -5,7 +5,13 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+pub mod bench;
+pub mod convert;
 pub mod create;
+pub mod doctor;
+pub mod lint;
+pub mod review;
 pub mod root;
 pub mod test;
+pub mod tui;
 pub mod update;
@@ -0,0 +1,202 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use clap::Parser;
+use dialoguer::console::Term;
+use dialoguer::console::style;
+use scrut::testcase::TestCase;
+
+use super::root::GlobalSharedParameters;
+use crate::utils::DocumentChanges;
+use crate::utils::FileParser;
+use crate::utils::ProjectConfig;
+use crate::utils::changed_files;
+use crate::utils::confirm;
+use crate::utils::markdown_languages;
+use crate::utils::stage_file;
+
+/// Amount of unchanged lines that are shown before and after each change
+const SURROUNDING_LINES: usize = 3;
+
+/// Review changes of test documents, as made by `scrut update`, and accept
+/// or revert them one by one
+///
+/// Reviewed are all test documents that have unstaged changes in git. Each
+/// change is printed along with the testcase it belongs to. Reverted changes
+/// are restored to the content of the git index, accepted changes are kept
+/// and staged, so that they are not reviewed again.
+#[derive(Debug, Parser)]
+pub struct Args {
+    /// Path to test files or directories, defaults to the current directory
+    paths: Vec<PathBuf>,
+
+    /// For markdown format: Language annotations that are considered test cases.
+    /// Defaults to the `markdown_languages` of the project configuration, if
+    /// any, or `scrut` otherwise.
+    #[clap(long, hide = true, num_args = 1..)]
+    markdown_languages: Vec<String>,
+
+    /// Glob match that identifies cram files
+    #[clap(long, default_value = "*.{t,cram}")]
+    match_cram: String,
+
+    /// Glob match that identifies markdown files
+    #[clap(long, default_value = "*.{md,markdown,scrut}")]
+    match_markdown: String,
+
+    /// Accept all changes without asking
+    #[clap(long, conflicts_with = "revert_all")]
+    accept_all: bool,
+
+    /// Revert all changes without asking
+    #[clap(long)]
+    revert_all: bool,
+
+    #[clap(flatten)]
+    global: GlobalSharedParameters,
+}
+
+impl Args {
+    pub(crate) fn run(&self) -> Result<()> {
+        let paths = if self.paths.is_empty() {
+            vec![Path::new(".")]
+        } else {
+            self.paths.iter().map(|p| p as &Path).collect::<Vec<_>>()
+        };
+        if !self.accept_all && !self.revert_all && !Term::stderr().is_term() {
+            bail!("interactive review requires a terminal, use --accept-all or --revert-all");
+        }
+
+        let project_config = ProjectConfig::find_any(&paths)?;
+        let markdown_languages =
+            markdown_languages(&self.markdown_languages, project_config.as_ref());
+        let markdown_languages = &markdown_languages.iter().map(|s| &**s).collect::<Vec<_>>();
+        let parser = FileParser::new(&self.match_markdown, &self.match_cram, markdown_languages)
+            .context("create file parser")?;
+
+        let current_directory = std::env::current_dir().context("get current directory")?;
+        let changed = changed_files(&paths)?
+            .into_iter()
+            .filter(|file| parser.accept(&file.path))
+            .collect::<Vec<_>>();
+        if changed.is_empty() {
+            println!("👋 No changed test documents found. Stopping.");
+            return Ok(());
+        }
+
+        let (mut count_accepted, mut count_reverted) = (0, 0);
+        for file in changed {
+            let display_path = file
+                .path
+                .strip_prefix(&current_directory)
+                .unwrap_or(&file.path)
+                .to_path_buf();
+            let updated = fs::read_to_string(&file.path)
+                .with_context(|| format!("read {}", display_path.display()))?;
+            let changes = DocumentChanges::new(&file.original, &updated);
+
+            // the updated document may not be valid, in which case the
+            // changes are reviewed without testcase context
+            let testcases = parser
+                .find_and_parse("review", &[&file.path], self.global.cram_compat)
+                .map(|parsed| {
+                    parsed
+                        .into_iter()
+                        .flat_map(|parsed| parsed.testcases)
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+
+            println!(
+                "{}",
+                style(format!("--- {}", display_path.display())).bold()
+            );
+            println!(
+                "{}",
+                style(format!("+++ {}", display_path.display())).bold()
+            );
+            let mut accepted = vec![];
+            for hunk in &changes.hunks {
+                let context = testcase_context(&testcases, hunk.updated.start + 1);
+                let rendered = changes.render_hunk(hunk, SURROUNDING_LINES, &context);
+                print!("{}", self.colorize(&rendered));
+
+                let accept = if self.accept_all {
+                    true
+                } else if self.revert_all {
+                    false
+                } else {
+                    confirm("Accept this change?", true, self.global.no_color)?
+                };
+                if accept {
+                    count_accepted += 1;
+                } else {
+                    count_reverted += 1;
+                }
+                accepted.push(accept);
+            }
+
+            if accepted.iter().any(|accept| !accept) {
+                fs::write(&file.path, changes.apply(&accepted))
+                    .with_context(|| format!("write {}", display_path.display()))?;
+            }
+            if accepted.iter().any(|accept| *accept) {
+                stage_file(&file.path)?;
+            }
+        }
+
+        println!(
+            "Result: {} change(s) reviewed: {} accepted and {} reverted",
+            count_accepted + count_reverted,
+            count_accepted,
+            count_reverted
+        );
+        Ok(())
+    }
+
+    /// Colors removed lines red and added lines green
+    fn colorize(&self, hunk: &str) -> String {
+        if self.global.no_color {
+            return hunk.to_string();
+        }
+        hunk.split_inclusive('\n')
+            .map(|line| match line.chars().next() {
+                Some('-') => style(line).red().to_string(),
+                Some('+') => style(line).green().to_string(),
+                Some('@') => style(line).cyan().to_string(),
+                _ => line.to_string(),
+            })
+            .collect()
+    }
+}
+
+/// Describes the testcase that contains the given (1-based) line by its
+/// title, or its shell expression if it has no title
+fn testcase_context(testcases: &[TestCase], line: usize) -> String {
+    testcases
+        .iter()
+        .rev()
+        .find(|testcase| testcase.line_number <= line)
+        .map(|testcase| {
+            if testcase.title.is_empty() {
+                format!(
+                    "$ {}",
+                    testcase.shell_expression.lines().next().unwrap_or_default()
+                )
+            } else {
+                format!("# {}", testcase.title.lines().next().unwrap_or_default())
+            }
+        })
+        .unwrap_or_default()
+}
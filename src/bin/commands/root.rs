@@ -5,43 +5,75 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::path::Path;
 use std::path::PathBuf;
 use std::time::Duration;
 
 use clap::Parser;
 use clap::Subcommand;
 use clap::ValueEnum;
+use scrut::config::ConfigOverride;
 use scrut::config::DocumentConfig;
 use scrut::config::OutputStreamControl;
 use scrut::config::TestCaseConfig;
+use scrut::diff_algorithm::DiffAlgorithm;
 use scrut::escaping::Escaper;
 use scrut::parsers::parser::ParserType;
+use serde::Deserialize;
 
+use crate::utils::require_feature;
+
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Subcommand)]
 pub(crate) enum Commands {
+    Bench(super::bench::Args),
+    Convert(super::convert::Args),
     Create(super::create::Args),
+    Doctor(super::doctor::Args),
+    /// Insert or normalize the header of test documents, same as `lint --fix`
+    Fmt(super::lint::Args),
+    Lint(super::lint::Args),
+    Review(super::review::Args),
     Test(super::test::Args),
+    Tui(super::tui::Args),
     Update(super::update::Args),
 }
 
 impl Commands {
     pub(crate) fn run(&self) -> anyhow::Result<()> {
         match &self {
+            Commands::Bench(cmd) => cmd.run(),
+            Commands::Convert(cmd) => cmd.run(),
             Commands::Create(cmd) => cmd.run(),
+            Commands::Doctor(cmd) => cmd.run(),
+            Commands::Fmt(cmd) => cmd.run_fix(),
+            Commands::Lint(cmd) => cmd.run(),
+            Commands::Review(cmd) => cmd.run(),
             Commands::Test(cmd) => cmd.run(),
+            Commands::Tui(cmd) => cmd.run(),
             Commands::Update(cmd) => cmd.run(),
         }
     }
 }
 
 /// Supported scrut output format
-#[derive(Debug, Clone, ValueEnum)]
+#[derive(Debug, Clone, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ScrutRenderer {
     Auto,
     Pretty,
     Diff,
     Json,
     Yaml,
+
+    /// Like `pretty`, but without any progress output while running and with
+    /// the full diff of each failing testcase, for CI logs
+    Summary,
+
+    /// Like `pretty`, preceded by GitHub Actions workflow commands that
+    /// annotate each failed expectation in the test document. Used instead of
+    /// `auto` when running in GitHub Actions.
+    Github,
 }
 
 #[derive(Parser, Debug)]
@@ -70,6 +102,20 @@ pub(crate) struct GlobalParameters {
     #[clap(long, overrides_with = "keep_output_crlf", global = true)]
     pub(crate) no_keep_output_crlf: bool,
 
+    /// Replace the home directory and the name of the user with `~` and
+    /// `$USER` in outputs of shell expressions, so that expectations that
+    /// are created or updated hold for all users. Same as setting the
+    /// `anonymize_user` configuration in all test cases.
+    #[clap(long, global = true)]
+    pub(crate) anonymize_user: bool,
+
+    /// Algorithm that aligns output expectations with the lines of output,
+    /// which determines how mismatches are presented. `patience` and
+    /// `histogram` keep reordered blocks of output together. Same as setting
+    /// the `diff_algorithm` configuration in all test cases.
+    #[clap(long, value_enum, global = true)]
+    pub(crate) diff_algorithm: Option<DiffAlgorithm>,
+
     /// Optional output escaping mode. If not set then defaults to escaping
     /// all non-printable unicode characters for Scrut Markdown tests and
     /// all non-printable ASCII characters for Cram tests.
@@ -99,20 +145,47 @@ pub(crate) struct GlobalParameters {
     #[clap(long, alias = "no-colour", global = true)]
     pub(crate) no_color: bool,
 
-    /// Specify the logging level.
-    #[cfg(feature = "logging")]
-    #[clap(long, global = true, value_enum, default_value_t = logging::LogLevel::default())]
-    pub(crate) log_level: logging::LogLevel,
+    /// Override any per-document or per-testcase configuration, as if it
+    /// was set in all test documents. Values are parsed as YAML. Nested
+    /// values can be addressed with dots. Can be provided multiple times.
+    /// Example: `--config total_timeout=10m --config environment.FOO=bar`
+    #[clap(long = "config", value_name = "KEY=VALUE", global = true)]
+    pub(crate) config_overrides: Vec<ConfigOverride>,
+
+    /// Load environment variables for all testcases from a dotenv file with
+    /// `KEY=VALUE` lines, as if it was set as `env_file` in all test documents.
+    /// Can be provided multiple times.
+    #[clap(long = "env-file", value_name = "PATH", value_parser = load_env_file, global = true)]
+    pub(crate) env_files: Vec<DocumentConfig>,
+
+    /// Specify the logging level. Defaults to `warn`. Requires the `logging`
+    /// feature.
+    #[clap(long, global = true, value_enum)]
+    pub(crate) log_level: Option<logging::LogLevel>,
 }
 
-#[cfg(feature = "logging")]
 impl GlobalParameters {
+    /// Initializes logging, if compiled with the `logging` feature. Fails if
+    /// a log level is requested, but the feature is not available.
     pub fn init_logging(&self) -> anyhow::Result<()> {
-        logging::init_logging(&self.log_level, self.no_color)
+        if !cfg!(feature = "logging") {
+            if self.log_level.is_some() {
+                require_feature("logging", "`--log-level`")?;
+            }
+            return Ok(());
+        }
+        logging::init_logging(&self.log_level.clone().unwrap_or_default(), self.no_color)
     }
 }
 
-#[derive(Parser, Debug, Default)]
+/// Parses the arguments of a subcommand without the main command, which
+/// provides the global parameters that the subcommand refers to
+pub(crate) fn parse_subcommand<T: Parser>(args: &[&str]) -> T {
+    let command = <GlobalParameters as clap::Args>::augment_args(T::command());
+    T::from_arg_matches(&command.get_matches_from(args)).expect("valid subcommand arguments")
+}
+
+#[derive(Parser, Debug, Clone, Default)]
 pub(crate) struct GlobalSharedParameters {
     #[clap(from_global)]
     pub(crate) cram_compat: bool,
@@ -127,6 +200,12 @@ pub(crate) struct GlobalSharedParameters {
     #[clap(from_global)]
     pub(crate) no_keep_output_crlf: bool,
 
+    #[clap(from_global)]
+    pub(crate) anonymize_user: bool,
+
+    #[clap(from_global)]
+    pub(crate) diff_algorithm: Option<DiffAlgorithm>,
+
     #[clap(from_global)]
     pub(crate) shell: Option<PathBuf>,
 
@@ -145,9 +224,23 @@ pub(crate) struct GlobalSharedParameters {
     #[clap(from_global)]
     pub(crate) no_color: bool,
 
-    #[cfg(feature = "logging")]
     #[clap(from_global)]
-    pub(crate) log_level: logging::LogLevel,
+    pub(crate) config_overrides: Vec<ConfigOverride>,
+
+    #[clap(from_global)]
+    pub(crate) env_files: Vec<DocumentConfig>,
+
+    #[clap(from_global)]
+    pub(crate) log_level: Option<logging::LogLevel>,
+}
+
+/// Reads the environment variables of the dotenv file at the given path
+fn load_env_file(path: &str) -> anyhow::Result<DocumentConfig> {
+    DocumentConfig {
+        env_file: Some(path.into()),
+        ..Default::default()
+    }
+    .with_env_file_loaded(Path::new(""))
 }
 
 impl GlobalSharedParameters {
@@ -155,13 +248,16 @@ impl GlobalSharedParameters {
     pub(crate) fn to_document_config(&self) -> DocumentConfig {
         let mut config = DocumentConfig::empty();
         if let Some(ref value) = self.shell {
-            config.shell = Some(value.clone())
+            config.shell = Some(value.clone().into())
         }
         if let Some(value) = self.timeout_seconds {
             config.total_timeout = Some(Duration::from_secs(value))
         }
+        for env_file in &self.env_files {
+            config = config.with_overrides_from(env_file);
+        }
 
-        config
+        ConfigOverride::apply_document(&self.config_overrides, config)
     }
 
     /// Translates global shared parameters into (defaults for) per-test configuration
@@ -184,7 +280,15 @@ impl GlobalSharedParameters {
             config.keep_crlf = Some(true)
         }
 
-        config
+        if self.anonymize_user {
+            config.anonymize_user = Some(true)
+        }
+
+        if self.diff_algorithm.is_some() {
+            config.diff_algorithm = self.diff_algorithm
+        }
+
+        ConfigOverride::apply_testcase(&self.config_overrides, config)
     }
 
     pub(crate) fn output_escaping(&self, parser: Option<ParserType>) -> Escaper {
@@ -197,17 +301,34 @@ impl GlobalSharedParameters {
     }
 }
 
-#[cfg(feature = "logging")]
+pub(crate) use logging::redirect_warnings;
+
 mod logging {
     use std::env;
+    use std::fmt::Debug;
     use std::fmt::Display;
     use std::fmt::Formatter;
     use std::io;
+    use std::sync::Mutex;
 
     use anyhow::Context;
     use clap::ValueEnum;
+    use tracing::Event;
+    use tracing::Level;
+    use tracing::Subscriber;
+    use tracing::field::Field;
+    use tracing::field::Visit;
     use tracing_subscriber::EnvFilter;
-    use tracing_subscriber::FmtSubscriber;
+    use tracing_subscriber::Layer;
+    use tracing_subscriber::fmt;
+    use tracing_subscriber::layer;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+
+    /// Receives the messages of logged warnings and errors
+    pub(crate) type WarningSink = Box<dyn Fn(String) + Send>;
+
+    static WARNING_SINK: Mutex<Option<WarningSink>> = Mutex::new(None);
 
     #[derive(Debug, Clone, ValueEnum, Default)]
     pub enum LogLevel {
@@ -243,13 +364,66 @@ mod logging {
             .parse(&log_level)
             .with_context(|| format!("invalid log level `{log_level}` provided"))?;
 
-        FmtSubscriber::builder()
-            .with_ansi(!no_color && console::colors_enabled())
-            .with_writer(io::stderr)
-            .with_env_filter(filter)
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(
+                fmt::layer()
+                    .with_ansi(!no_color && console::colors_enabled())
+                    .with_writer(|| -> Box<dyn io::Write> {
+                        if is_redirected() {
+                            Box::new(io::sink())
+                        } else {
+                            Box::new(io::stderr())
+                        }
+                    }),
+            )
+            .with(RedirectLayer)
             .init();
         Ok(())
     }
+
+    /// Sends the messages of all logged warnings and errors to the sink,
+    /// instead of writing the log to STDERR, until it is unset again. Used
+    /// while another UI owns the terminal.
+    pub(crate) fn redirect_warnings(sink: Option<WarningSink>) {
+        *WARNING_SINK.lock().expect("warning sink lock") = sink;
+    }
+
+    fn is_redirected() -> bool {
+        WARNING_SINK.lock().is_ok_and(|sink| sink.is_some())
+    }
+
+    /// Forwards the messages of warnings and errors to the sink of
+    /// [`redirect_warnings`], if any
+    struct RedirectLayer;
+
+    impl<S: Subscriber> Layer<S> for RedirectLayer {
+        fn on_event(&self, event: &Event<'_>, _: layer::Context<'_, S>) {
+            if *event.metadata().level() > Level::WARN {
+                return;
+            }
+            let Ok(sink) = WARNING_SINK.lock() else {
+                return;
+            };
+            if let Some(sink) = sink.as_ref() {
+                let mut message = MessageVisitor::default();
+                event.record(&mut message);
+                sink(message.0);
+            }
+        }
+    }
+
+    /// Extracts the message of a logged event
+    #[derive(Default)]
+    struct MessageVisitor(String);
+
+    impl Visit for MessageVisitor {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            if field.name() == "message" {
+                self.0 = format!("{value:?}");
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -274,6 +448,20 @@ mod tests {
                     ..DocumentConfig::empty()
                 },
             ),
+            (
+                GlobalSharedParameters {
+                    shell: Some("other-shell".into()),
+                    config_overrides: vec![
+                        "shell=config-shell".parse().unwrap(),
+                        "keep_crlf=true".parse().unwrap(),
+                    ],
+                    ..Default::default()
+                },
+                DocumentConfig {
+                    shell: Some("config-shell".into()),
+                    ..DocumentConfig::empty()
+                },
+            ),
         ];
 
         for (params, expected) in tests {
@@ -325,6 +513,30 @@ mod tests {
                     ..TestCaseConfig::empty()
                 },
             ),
+            (
+                GlobalSharedParameters {
+                    anonymize_user: true,
+                    ..Default::default()
+                },
+                TestCaseConfig {
+                    anonymize_user: Some(true),
+                    ..TestCaseConfig::empty()
+                },
+            ),
+            (
+                GlobalSharedParameters {
+                    keep_output_crlf: true,
+                    config_overrides: vec![
+                        "keep_crlf=false".parse().unwrap(),
+                        "total_timeout=1m".parse().unwrap(),
+                    ],
+                    ..Default::default()
+                },
+                TestCaseConfig {
+                    keep_crlf: Some(false),
+                    ..TestCaseConfig::empty()
+                },
+            ),
         ];
 
         for (idx, (params, expected)) in tests.into_iter().enumerate() {
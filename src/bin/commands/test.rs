@@ -6,6 +6,8 @@
  */
 
 use std::collections::BTreeMap;
+use std::env;
+use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -17,51 +19,90 @@ use clap::Parser as ClapParser;
 use dialoguer::console::style;
 use humantime::format_duration;
 use scrut::config::DEFAULT_SKIP_DOCUMENT_CODE;
+use scrut::config::DeprecationDate;
+use scrut::config::DiffContext;
 use scrut::config::DocumentConfig;
+use scrut::config::EmptyTestsPolicy;
+use scrut::config::Shell;
 use scrut::config::TestCaseConfig;
+use scrut::config::WorkDirectoryKeep;
 use scrut::executors::context::ContextBuilder;
 use scrut::executors::error::ExecutionError;
 use scrut::executors::error::ExecutionTimeout;
 use scrut::outcome::Outcome;
 use scrut::output::ExitStatus;
-use scrut::parsers::markdown::DEFAULT_MARKDOWN_LANGUAGES;
+use scrut::output::Output;
 use scrut::parsers::parser::ParserType;
 use scrut::renderers::diff::DiffRenderer;
+use scrut::renderers::github::GithubRenderer;
 use scrut::renderers::pretty::DEFAULT_MULTILINE_MATCHED_LINES;
 use scrut::renderers::pretty::DEFAULT_SURROUNDING_LINES;
+use scrut::renderers::pretty::DiffGranularity;
+use scrut::renderers::pretty::DiffStyle;
 use scrut::renderers::pretty::PrettyColorRenderer;
 use scrut::renderers::pretty::PrettyMonochromeRenderer;
 use scrut::renderers::renderer::Renderer;
 use scrut::renderers::structured::JsonRenderer;
 use scrut::renderers::structured::YamlRenderer;
+use scrut::rules::variables::Variables;
 use scrut::testcase::TestCase;
 use scrut::testcase::TestCaseError;
 use tracing::debug;
 use tracing::debug_span;
 use tracing::info;
 use tracing::trace;
+use tracing::warn;
 
 use super::root::GlobalSharedParameters;
 use super::root::ScrutRenderer;
+use super::update::generate_update;
+use crate::utils::ArtifactsDirectory;
+use crate::utils::DEFAULT_ARTIFACTS_DIRECTORY;
+use crate::utils::DirectoryFixtures;
 use crate::utils::FileParser;
+use crate::utils::FixtureReferences;
+use crate::utils::LastRun;
+use crate::utils::ParsedTestFile;
 use crate::utils::ProgressWriter;
+use crate::utils::ProjectConfig;
+use crate::utils::Sandbox;
+use crate::utils::SharedWorkDirectory;
+use crate::utils::StatusStream;
 use crate::utils::TestEnvironment;
+use crate::utils::TestPaths;
+use crate::utils::UNDO_DIRECTORY;
+use crate::utils::UpdateJournal;
+use crate::utils::Workspace;
+use crate::utils::apply_allow_recursion;
+use crate::utils::assure_not_recursive;
 use crate::utils::canonical_shell;
+use crate::utils::changed_paths_since_head;
 use crate::utils::debug_testcases;
 use crate::utils::get_log_level;
-use crate::utils::kill_detached_process;
+use crate::utils::impacted_paths;
+use crate::utils::kill_detached_processes;
 use crate::utils::make_executor;
+use crate::utils::markdown_languages;
+use crate::utils::select_impacted;
+use crate::utils::stage_fixtures;
 
 #[derive(Debug, thiserror::Error)]
 #[error("validation failed")]
 pub struct ValidationFailedError;
 
 /// Run tests from files or directories
-#[derive(Debug, ClapParser)]
+#[derive(Debug, Clone, ClapParser)]
 pub struct Args {
     /// Path to test files or directories
     test_file_paths: Vec<PathBuf>,
 
+    /// Run all suites of a workspace, as defined in a `scrut.workspace.yaml`
+    /// file. Accepts the path to the file or to the directory that contains
+    /// it. If no path is given, the file is looked for in the current
+    /// directory and all its parent directories.
+    #[clap(long, conflicts_with = "test_file_paths")]
+    workspace: Option<Option<PathBuf>>,
+
     /// Optional list of paths to test files which are prepended to each test
     /// file in execution. Think: shared test bootstrap.
     /// This is NOT meant to be used from the command line, aside from
@@ -84,10 +125,17 @@ pub struct Args {
     #[clap(long)]
     debug: bool,
 
-    /// For markdown format: Language annotations that are considered test cases
-    #[clap(long, hide = true, default_values = DEFAULT_MARKDOWN_LANGUAGES, num_args = 1..)]
+    /// For markdown format: Language annotations that are considered test cases.
+    /// Defaults to the `markdown_languages` of the project configuration, if
+    /// any, or `scrut` otherwise.
+    #[clap(long, hide = true, num_args = 1..)]
     markdown_languages: Vec<String>,
 
+    /// Parse all test documents in this format, instead of determining it from
+    /// the `format` in their front-matter, their file name or their content
+    #[clap(long, value_enum)]
+    format: Option<ParserType>,
+
     /// Glob match that identifies cram files
     #[clap(long, default_value = "*.{t,cram}")]
     match_cram: String,
@@ -97,10 +145,12 @@ pub struct Args {
     match_markdown: String,
 
     /// Which renderer to use for generating the result, with `diff` being the
-    /// best choice for human consumption and `json` or `yaml` for further
-    /// machine processing.
-    #[clap(long, short, default_value = "auto", value_enum)]
-    renderer: ScrutRenderer,
+    /// best choice for human consumption, `summary` for CI logs and `json`
+    /// or `yaml` for further machine processing. Defaults to the `renderer`
+    /// of the project configuration, if any, or `auto` otherwise, which is
+    /// `github` when running in GitHub Actions.
+    #[clap(long, short, value_enum)]
+    renderer: Option<ScrutRenderer>,
 
     /// Per default, renderers that provide line numbers use relative numbers within
     /// the test case / the output of the execution. Setting this flag changes that
@@ -114,61 +164,476 @@ pub struct Args {
     #[clap(long, default_value_t = DEFAULT_MULTILINE_MATCHED_LINES)]
     max_multiline_matched_lines: usize,
 
+    /// How the pretty renderer presents mismatching output: `unified` lists
+    /// expectations and output lines below each other, `side-by-side` shows
+    /// them in two columns
+    #[clap(long, value_enum, default_value_t)]
+    diff_style: DiffStyle,
+
+    /// Which parts of a mismatching expectation and the output line it is
+    /// paired up with the pretty renderer highlights: the whole `line`, or
+    /// only the `word`s or `char`acters that differ
+    #[clap(long, value_enum, default_value_t)]
+    diff_granularity: DiffGranularity,
+
+    /// How many matching lines the pretty renderer shows around mismatches
+    /// (default: 5), or `full` to show all lines of failing testcases.
+    /// Overrides the `diff_context` configuration of testcases.
+    #[clap(long, value_name = "LINES|full")]
+    diff_context: Option<DiffContext>,
+
     /// Increase output verbosity, print out information that is not warning or errors
     #[clap(long)]
     verbose: bool,
 
+    /// Execute each test document a second time, in a fresh work directory,
+    /// and fail all testcases whose output (STDOUT, STDERR and exit code)
+    /// differs between both executions, regardless of whether the
+    /// expectations match. Use this to assure tests are safe to `update`.
+    #[clap(long, conflicts_with = "work_directory")]
+    audit_determinism: bool,
+
+    /// Execute test documents with failing testcases up to this many times
+    /// again, each time in a fresh work directory. Testcases that pass in a
+    /// retry count as passed, but are reported as flaky. Documents that are
+    /// stopped by a timeout or `fail_fast` are not retried.
+    #[clap(long, default_value_t = 0, conflicts_with_all = ["audit_determinism", "work_directory"])]
+    retries: usize,
+
+    /// Fail the run if more than this many testcases pass only on retry (see
+    /// `--retries`), so that retries do not hide tests that become flaky
+    #[clap(long, requires = "retries")]
+    flake_budget: Option<usize>,
+
+    /// Fail the run if any testcase is skipped, e.g. by exiting with the
+    /// `skip_document_code`, so that tests cannot silently stop running
+    #[clap(long)]
+    fail_on_skipped: bool,
+
+    /// Fail the run if any testcase that is expected to fail (see the
+    /// `xfail` testcase configuration) passes, so that fixed bugs are noticed
+    #[clap(long)]
+    fail_on_xpass: bool,
+
+    /// Fail the run if any file in the `fixture_directories` of the project
+    /// configuration is not referenced by a test document, or if a test
+    /// document references a file that does not exist. Only meaningful if
+    /// all test documents of the project are run.
+    #[clap(long)]
+    check_fixtures: bool,
+
+    /// Rewrite the expectations of failed testcases in their test documents,
+    /// as `update --replace` would, while the run is rendered and fails as
+    /// usual. Testcases that are `frozen` keep their expectations. Use
+    /// `update --undo` to restore the documents.
+    #[clap(long)]
+    update_failing: bool,
+
+    /// Shell command that selects which of the found test documents to run,
+    /// based on the changed source files. It receives the changed files on
+    /// STDIN and prints the paths of the test documents to run on STDOUT,
+    /// both one path per line. If the command fails, all test documents run.
+    #[clap(long)]
+    impact_cmd: Option<String>,
+
+    /// Changed source files that are passed to the `--impact-cmd`. Defaults
+    /// to all files that are changed compared to the last commit of the git
+    /// repository, including untracked files.
+    #[clap(long, num_args = 1.., requires = "impact_cmd")]
+    changed_files: Vec<PathBuf>,
+
+    /// Run only the testcases that failed in the last run, and those before
+    /// them in the same document, as recorded in `.scrut/last-run.json` in
+    /// the directory of the project configuration, or in the current
+    /// directory outside of projects. All test documents run if no failed
+    /// testcases are recorded.
+    #[clap(long, conflicts_with = "update_failing")]
+    rerun_failed: bool,
+
+    /// Write progress events as newline-delimited JSON to this file descriptor
+    /// (e.g. `3`) or file path, for tools that render live progress. Human
+    /// readable output is not affected.
+    #[clap(long, value_name = "FD_OR_PATH")]
+    status_fd: Option<String>,
+
+    /// Show a continuously updated progress bar, with the elapsed time, the
+    /// amount of failures so far and the currently running testcase. Falls
+    /// back to plain output if STDERR is not a terminal.
+    #[clap(long)]
+    progress: bool,
+
+    /// Do not remove the work directory of test documents with failing
+    /// testcases, as if they were configured with `workdir: {keep:
+    /// on-failure}`, and print its path in the output of the failed testcases,
+    /// so that the files they produced can be inspected
+    #[clap(long)]
+    keep_failed_workdirs: bool,
+
+    /// Directory into which the files that match the `artifacts` patterns of
+    /// test documents with failing testcases are copied, each into a
+    /// directory named like the test document. Defaults to `.scrut/artifacts`
+    /// in the current directory.
+    #[clap(long, value_name = "PATH")]
+    artifacts_dir: Option<PathBuf>,
+
+    /// Refuse to run test documents that contain commands, which are denied
+    /// in the sandbox (e.g. `sudo`, `curl` or `rm -rf /`) or not allowed by
+    /// the `sandbox` project configuration, unless they are trusted. Use it
+    /// to run test documents from untrusted sources.
+    #[clap(long)]
+    sandbox: bool,
+
+    /// Test document, or directory containing test documents, that is
+    /// trusted to run in the sandbox without being checked. Can be provided
+    /// multiple times.
+    #[clap(long, value_name = "PATH", requires = "sandbox")]
+    trust: Vec<PathBuf>,
+
+    /// Whether to hide the progress bar, e.g. when another UI is shown
+    #[clap(skip)]
+    hide_progress: bool,
+
     #[clap(flatten)]
     global: GlobalSharedParameters,
 }
 
+/// Outcomes of running a set of test documents
+#[derive(Default)]
+pub(super) struct TestRun {
+    pub(super) outcomes: Vec<Outcome>,
+    count_success: usize,
+    count_skipped: usize,
+    count_failed: usize,
+    count_detached: usize,
+    count_flaky: usize,
+    count_xfailed: usize,
+    count_xpassed: usize,
+    count_fixture_problems: usize,
+}
+
 impl Args {
     pub(crate) fn run(&self) -> Result<()> {
-        // init parser and determine suffices to look for
-        let markdown_languages = &self
-            .markdown_languages
+        if let Some(ref workspace) = self.workspace {
+            return self.run_workspace(workspace.as_deref());
+        }
+
+        let test_file_paths = self
+            .test_file_paths
             .iter()
-            .map(|s| &**s)
+            .map(|p| p as &Path)
             .collect::<Vec<_>>();
+        let project_config = ProjectConfig::find_any(&test_file_paths)?;
+        let status = self.open_status_stream()?;
+        let run = self.execute(&test_file_paths, project_config.as_ref(), status.as_ref())?;
+        match LastRun::find_file(&test_file_paths) {
+            Ok(path) => save_last_run(&path, LastRun::from_outcomes(&run.outcomes)),
+            Err(err) => warn!("failed to record the failed testcases of the run: {err:#}"),
+        }
+
+        // finally render all outcomes of testcase validations
+        let renderer = self.make_renderer(&self.renderer_type(project_config.as_ref()));
+        info!(
+            success = run.count_success,
+            skipped = run.count_skipped,
+            failed = run.count_failed,
+            detached = run.count_detached,
+            flaky = run.count_flaky,
+            xfailed = run.count_xfailed,
+            xpassed = run.count_xpassed,
+        );
+        print!(
+            "{}",
+            renderer.render(&run.outcomes.iter().collect::<Vec<_>>())?
+        );
+
+        if run.count_failed > 0 || self.fails_strict_gates(&run) {
+            Err(anyhow!(ValidationFailedError))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Runs all suites of the workspace, each with its own configuration
+    /// overrides, and renders the outcomes in a section per suite
+    fn run_workspace(&self, path: Option<&Path>) -> Result<()> {
+        let path = Workspace::find(path)?;
+        let workspace = Workspace::load(&path)?;
+
+        let status = self.open_status_stream()?;
+        let mut runs = vec![];
+        let mut last_runs = BTreeMap::<_, LastRun>::new();
+        for suite in &workspace.suites {
+            let mut args = self.clone();
+            args.global.config_overrides = suite.config_overrides()?;
+            args.global
+                .config_overrides
+                .extend(self.global.config_overrides.clone());
+            let project_config = ProjectConfig::find(&suite.path)?.map(|(_, config)| config);
+            let run = args
+                .execute(&[&suite.path], project_config.as_ref(), status.as_ref())
+                .with_context(|| format!("run suite {}", suite.name()))?;
+            // suites of the same project record their failed testcases together
+            last_runs
+                .entry(LastRun::find_file(&[&suite.path])?)
+                .or_default()
+                .merge(LastRun::from_outcomes(&run.outcomes));
+            runs.push((suite, run));
+        }
+
+        let renderer_type =
+            self.renderer_type(ProjectConfig::find(&path)?.map(|(_, c)| c).as_ref());
+        let renderer = self.make_renderer(&renderer_type);
+        let mut total = TestRun::default();
+        for (suite, run) in runs {
+            info!(
+                suite = suite.name(),
+                success = run.count_success,
+                skipped = run.count_skipped,
+                failed = run.count_failed,
+                detached = run.count_detached,
+                flaky = run.count_flaky,
+                xfailed = run.count_xfailed,
+                xpassed = run.count_xpassed,
+            );
+
+            // structured output is rendered as a whole, with the suite of
+            // each testcase being available in its annotations
+            if !matches!(renderer_type, ScrutRenderer::Json | ScrutRenderer::Yaml) {
+                println!("{}", style(format!("# Suite: {}", suite.name())).bold());
+                print!(
+                    "{}",
+                    renderer.render(&run.outcomes.iter().collect::<Vec<_>>())?
+                );
+                println!();
+            }
+            total.count_success += run.count_success;
+            total.count_skipped += run.count_skipped;
+            total.count_failed += run.count_failed;
+            total.count_detached += run.count_detached;
+            total.count_flaky += run.count_flaky;
+            total.count_xfailed += run.count_xfailed;
+            total.count_xpassed += run.count_xpassed;
+            total.count_fixture_problems += run.count_fixture_problems;
+            total.outcomes.extend(run.outcomes);
+        }
+        for (path, last_run) in last_runs {
+            save_last_run(&path, last_run);
+        }
+
+        if matches!(renderer_type, ScrutRenderer::Json | ScrutRenderer::Yaml) {
+            print!(
+                "{}",
+                renderer.render(&total.outcomes.iter().collect::<Vec<_>>())?
+            );
+        } else {
+            println!(
+                "Workspace: {} suite(s) with {} testcase(s): {} succeeded, {} failed and {} skipped",
+                workspace.suites.len(),
+                total.outcomes.len(),
+                total.count_success,
+                total.count_failed,
+                total.count_skipped,
+            );
+        }
+
+        if total.count_failed > 0 || self.fails_strict_gates(&total) {
+            Err(anyhow!(ValidationFailedError))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the arguments to run the test documents in the given paths,
+    /// without a progress bar, as the interactive `tui` command does
+    pub(super) fn for_paths(paths: Vec<PathBuf>, global: GlobalSharedParameters) -> Self {
+        let mut args: Self = super::root::parse_subcommand(&["test"]);
+        args.test_file_paths = paths;
+        args.global = global;
+        args.hide_progress = true;
+        args
+    }
+
+    /// Returns all test documents in the given paths, without the documents
+    /// that set up or tear down directories
+    pub(super) fn find_documents(
+        &self,
+        test_file_paths: &[&Path],
+        project_config: Option<&ProjectConfig>,
+    ) -> Result<Vec<ParsedTestFile>> {
+        let markdown_languages = markdown_languages(&self.markdown_languages, project_config);
+        let markdown_languages = &markdown_languages.iter().map(|s| &**s).collect::<Vec<_>>();
         let parser = FileParser::new(&self.match_markdown, &self.match_cram, markdown_languages)
-            .context("create file parser")?;
+            .context("create file parser")?
+            .with_format(self.format)
+            .with_template_vars(self.global.to_document_config().vars);
+        let test_paths = TestPaths::resolve(test_file_paths, project_config)?;
+        let search_paths = test_paths.search_paths();
+        let search_paths = search_paths.iter().map(|p| p as &Path).collect::<Vec<_>>();
+        let tests = parser.find_and_parse("test", &search_paths, self.global.cram_compat)?;
+        Ok(DirectoryFixtures::extract(test_paths.select(tests)?).1)
+    }
 
-        let tests = parser.find_and_parse(
-            "test",
-            &self
-                .test_file_paths
-                .iter()
-                .map(|p| p as &Path)
-                .collect::<Vec<_>>(),
-            self.global.cram_compat,
-        )?;
+    /// Runs all test documents in the given paths and returns the outcomes
+    pub(super) fn execute(
+        &self,
+        test_file_paths: &[&Path],
+        project_config: Option<&ProjectConfig>,
+        status: Option<&StatusStream>,
+    ) -> Result<TestRun> {
+        // init parser and determine suffices to look for
+        let markdown_languages = markdown_languages(&self.markdown_languages, project_config);
+        let markdown_languages = &markdown_languages.iter().map(|s| &**s).collect::<Vec<_>>();
+        let parser = FileParser::new(&self.match_markdown, &self.match_cram, markdown_languages)
+            .context("create file parser")?
+            .with_format(self.format)
+            .with_template_vars(self.global.to_document_config().vars);
+
+        let test_paths = TestPaths::resolve(test_file_paths, project_config)?;
+        let search_paths = test_paths.search_paths();
+        let search_paths = search_paths.iter().map(|p| p as &Path).collect::<Vec<_>>();
+        let tests = parser.find_and_parse("test", &search_paths, self.global.cram_compat)?;
+        let tests = test_paths.select(tests)?;
+        assure_not_recursive(tests.iter().map(|test| test.path.as_path()))?;
+        let count_fixture_problems = if self.check_fixtures {
+            self.check_fixture_references(&tests, project_config)?
+        } else {
+            0
+        };
+        let (mut fixtures, tests) = DirectoryFixtures::extract(tests);
+        let sandbox = if self.sandbox {
+            Some(
+                Sandbox::new(
+                    &project_config
+                        .map(|config| config.sandbox.clone())
+                        .unwrap_or_default(),
+                    project_config.map(|config| &config.document),
+                    &self.trust,
+                )?
+                .with_command_line_shell(self.global.shell.as_deref()),
+            )
+        } else {
+            None
+        };
+        if let Some(ref sandbox) = sandbox {
+            for document in fixtures.documents() {
+                sandbox
+                    .check_document(&document.path, &document.config, &document.testcases)
+                    .map_err(|reason| {
+                        anyhow!(
+                            "refusing to run {} in the sandbox, because {}",
+                            document.path.display(),
+                            reason
+                        )
+                    })?;
+            }
+        }
+        let count_found = tests.len();
+        let tests = self.select_impacted_tests(tests)?;
+        let count_impacted = tests.len();
+        let last_run = self.load_last_run(test_file_paths)?;
+        let tests = match last_run {
+            Some(ref last_run) => last_run.select_failed(tests),
+            None => tests,
+        };
+
+        // documents that configure locales are run once per locale
+        let tests = tests
+            .into_iter()
+            .flat_map(ParsedTestFile::per_locale)
+            .collect::<Vec<_>>();
 
         // initiate outputs
         let mut outcomes = vec![];
         let (mut count_success, mut count_skipped, mut count_failed, mut count_detached) =
             (0, 0, 0, 0);
+        let (mut count_flaky, mut count_xfailed, mut count_xpassed) = (0, 0, 0);
 
         // load configuration from command line
         let document_config = self.to_document_config();
         let testcase_config = self.to_testcase_config();
         let current_directory = std::env::current_dir().context("get current directory")?;
+        let mut journal = if self.update_failing {
+            Some(UpdateJournal::load(Path::new(UNDO_DIRECTORY))?)
+        } else {
+            None
+        };
 
         let pw = ProgressWriter::try_new(
             tests.len() as u64,
-            get_log_level() <= tracing::Level::WARN,
+            !self.hide_progress && get_log_level() <= tracing::Level::WARN,
             self.global.no_color || !console::colors_enabled(),
         )?;
+        let pw = if self.renderer_type(project_config) == ScrutRenderer::Summary {
+            pw.quiet()
+        } else if self.progress {
+            pw.with_live_status()?
+        } else {
+            pw
+        };
         pw.println(format!(
             "🔎 Found {} test document(s)",
-            style(tests.len()).bold()
+            style(count_found).bold()
         ));
+        if count_impacted != count_found {
+            pw.println(format!(
+                "🎯 Selected {} test document(s) that are impacted by the changes",
+                style(count_impacted).bold()
+            ));
+        }
+        if last_run.is_some() {
+            pw.println(format!(
+                "🔁 Selected {} test document(s) with testcases that failed in the last run",
+                style(tests.len()).bold()
+            ));
+        }
+
+        if let Some(status) = status {
+            status.run_started(tests.len());
+        }
+
+        // set up directories once, before any of their documents run
+        collect_fixture_outcomes(
+            fixtures.set_up(&pw, &self.global)?,
+            &mut outcomes,
+            &mut count_success,
+            &mut count_failed,
+            &mut count_skipped,
+        );
 
+        let mut shared_work_directory = SharedWorkDirectory::default();
+        let artifacts_directory = ArtifactsDirectory::new(
+            self.artifacts_dir
+                .as_deref()
+                .unwrap_or(Path::new(DEFAULT_ARTIFACTS_DIRECTORY)),
+        );
         for mut test in tests {
+            let name = test.display_name();
+            pw.set_failures(count_failed);
             pw.inc(1);
-            pw.set_message(format!(
-                "👀 {}",
-                style(test.path.to_string_lossy()).yellow()
-            ));
+            pw.set_message(format!("👀 {}", style(&name).yellow()));
+            if let Some(status) = status {
+                status.document_started(&test.path, test.testcases.len(), &outcomes);
+            }
+
+            // documents in directories whose setup failed cannot run
+            if let Some(directory) = fixtures.failed_setup(&test.path) {
+                count_skipped += 1;
+                pw.println(format!(
+                    "⏩ {}: skipped, because setup of {} failed",
+                    style(&name).blue(),
+                    directory.display(),
+                ));
+                let escaping = self.global.output_escaping(Some(test.parser_type));
+                outcomes.extend(test.testcases.iter().map(|testcase| Outcome {
+                    location: Some(test.path.display().to_string()),
+                    testcase: testcase.clone(),
+                    output: ("", "", None).into(),
+                    escaping: escaping.clone(),
+                    format: test.parser_type,
+                    result: Err(TestCaseError::Skipped),
+                }));
+                continue;
+            }
 
             // prefix append and prepend in document config with directory where test is
             let test_directory = &test.path.parent().unwrap_or(&current_directory);
@@ -178,10 +643,39 @@ impl Args {
             // compile configuration from test file and parameters
             let config: DocumentConfig = test.config.with_overrides_from(&document_config);
 
+            // deprecated documents warn, and fail without being executed once
+            // they are past their sunset
+            if let Some(deprecation) = &config.deprecated {
+                let today = DeprecationDate::today();
+                if deprecation.is_failing(&today) {
+                    count_failed += test.testcases.len();
+                    pw.println(format!("❌ {}: {}", style(&name).red(), deprecation));
+                    let escaping = self.global.output_escaping(Some(test.parser_type));
+                    outcomes.extend(test.testcases.iter().map(|testcase| Outcome {
+                        location: Some(test.path.display().to_string()),
+                        testcase: testcase.clone(),
+                        output: ("", "", None).into(),
+                        escaping: escaping.clone(),
+                        format: test.parser_type,
+                        result: Err(TestCaseError::InternalError(anyhow!(
+                            "document is {deprecation}"
+                        ))),
+                    }));
+                    continue;
+                } else if deprecation.is_expired(&today) {
+                    pw.warn(format!("⚠️ {}: {}", style(&name).yellow(), deprecation));
+                }
+            }
+
             // initialize environment in which test will run
-            let shell_path = canonical_shell(config.shell.as_ref().map(|p| p as &Path))?;
-            let mut test_environment = TestEnvironment::new(
+            let shell_path = canonical_shell(config.shell.as_ref().and_then(Shell::path))?;
+            let mut workdir = config.get_workdir();
+            if self.keep_failed_workdirs && workdir.keep == WorkDirectoryKeep::Never {
+                workdir.keep = WorkDirectoryKeep::OnFailure;
+            }
+            let mut test_environment = shared_work_directory.environment(
                 &shell_path,
+                &workdir,
                 self.global.work_directory.as_deref(),
                 self.global.keep_temporary_directories,
             )?;
@@ -230,10 +724,41 @@ impl Args {
             testcases.extend(test.testcases.clone());
             testcases.extend(append_tests.iter().flat_map(|test| test.testcases.clone()));
 
+            // untrusted documents with denied commands are not executed
+            if let Some(ref sandbox) = sandbox {
+                if let Err(reason) = sandbox.check_document(&test.path, &test.config, &testcases) {
+                    count_failed += testcases.len();
+                    pw.println(format!(
+                        "🚫 {}: refused to run in the sandbox, because {}",
+                        style(&name).red(),
+                        reason
+                    ));
+                    let escaping = self.global.output_escaping(Some(test.parser_type));
+                    outcomes.extend(testcases.iter().map(|testcase| Outcome {
+                        location: Some(test.path.display().to_string()),
+                        testcase: testcase.clone(),
+                        output: ("", "", None).into(),
+                        escaping: escaping.clone(),
+                        format: test.parser_type,
+                        result: Err(TestCaseError::InternalError(anyhow!(
+                            "refused to run in the sandbox, because {reason}"
+                        ))),
+                    }));
+                    continue;
+                }
+            }
+
             // setup testing environment
             let cram_compat = test.parser_type == ParserType::Cram || self.global.cram_compat;
-            let (test_work_directory, env_vars) =
+            let (test_work_directory, test_env_vars) =
                 test_environment.init_test_file(&test.path, cram_compat)?;
+            stage_fixtures(&test.path, &test_work_directory, &config)?;
+            let mut env_vars = fixtures
+                .environment(&test.path)
+                .into_iter()
+                .collect::<Vec<_>>();
+            env_vars.extend(test_env_vars);
+            env_vars.extend(test.locale_environment());
 
             // update testcase configuration from command line parameters
             let env_vars =
@@ -245,28 +770,35 @@ impl Args {
                         .config
                         .with_overrides_from(&testcase_config)
                         .with_environment(&env_vars);
+                    apply_allow_recursion(&mut testcase.config);
                     trace!(testcase = %&testcase, "running test case");
                     testcase as &TestCase
                 })
                 .collect::<Vec<_>>();
 
             // get the appropriate or requested executor
-            let executor = make_executor(&test_environment.shell, cram_compat)?;
+            let executor = make_executor(&test_environment.shell, cram_compat, &config)?;
 
             // determine output escaping
             let escaping = self.global.output_escaping(Some(test.parser_type));
 
             // run all testcases from the file and gather output ..
-            let outputs = executor.execute_all(
-                testcases.as_slice(),
-                &ContextBuilder::default()
-                    .work_directory(PathBuf::from(&test_work_directory))
-                    .temp_directory(test_environment.tmp_directory.as_path_buf())
-                    .file(test.path.clone())
-                    .config(config.clone())
-                    .build()
-                    .context("failed to build execution context")?,
-            );
+            let context = ContextBuilder::default()
+                .work_directory(PathBuf::from(&test_work_directory))
+                .temp_directory(test_environment.tmp_directory.as_path_buf())
+                .file(test.path.clone())
+                .config(config.clone())
+                .notifier(pw.notifier(&name))
+                .build()
+                .context("failed to build execution context")?;
+            let (count_failed_before, count_outcomes_before) = (count_failed, outcomes.len());
+            let outputs = executor.execute_all(testcases.as_slice(), &context);
+
+            // .. and clean up what was detached, however the execution ended
+            kill_detached_processes(&pw, &context.detached_processes)?;
+
+            // values that testcases capture, which later testcases refer to
+            let mut variables = Variables::new();
             match outputs {
                 // test execution failed ...
                 Err(err) => match err {
@@ -283,13 +815,12 @@ impl Args {
                         }));
                         pw.println(format!(
                             "⏩ {}: skipped, because testcase #{} ended in exit code {}",
-                            style(test.path.to_string_lossy()).blue(),
+                            style(&name).blue(),
                             idx + 1,
                             testcases.get(idx).map_or(DEFAULT_SKIP_DOCUMENT_CODE, |t| t
                                 .config
                                 .get_skip_document_code())
                         ));
-                        continue;
                     }
 
                     // ... because test timed out
@@ -308,7 +839,7 @@ impl Args {
                                 if matches!(output.exit_code, ExitStatus::Timeout(_)) {
                                     Err(TestCaseError::Timeout)
                                 } else {
-                                    testcase.validate(output)
+                                    testcase.validate_with_variables(output, &mut variables)
                                 }
                             },
                         );
@@ -324,14 +855,13 @@ impl Args {
                         };
                         pw.println(format!(
                             "⌛️ {}: execution timed out after {} at {}",
-                            style(test.path.to_string_lossy()).red(),
+                            style(&name).red(),
                             timeout.map_or_else(
                                 || "<undef>".to_string(), // this should never happen
                                 |t| format_duration(t).to_string()
                             ),
                             location,
                         ));
-                        continue;
                     }
 
                     // ... because test failed with fail_fast enabled
@@ -346,15 +876,16 @@ impl Args {
                             &mut count_success,
                             &mut count_failed,
                             &mut count_skipped,
-                            |output, testcase| testcase.validate(output),
+                            |output, testcase| {
+                                testcase.validate_with_variables(output, &mut variables)
+                            },
                         );
 
                         pw.println(format!(
                             "⚡ {}: stopped at testcase #{} due to fail_fast",
-                            style(test.path.to_string_lossy()).red(),
+                            style(&name).red(),
                             idx + 1,
                         ));
-                        continue;
                     }
 
                     // ... because of a final error
@@ -367,33 +898,151 @@ impl Args {
                         debug_testcases(&test.testcases, &test.path, &outputs);
                     }
 
+                    // .. to optionally repeat the execution in a fresh
+                    //    environment, to audit whether the output is deterministic
+                    let repeated_outputs = if self.audit_determinism {
+                        Some(self.execute_repeated(
+                            &pw,
+                            &shell_path,
+                            &test,
+                            &testcases,
+                            &config,
+                            cram_compat,
+                        )?)
+                    } else {
+                        None
+                    };
+
                     // .. to compare the outputs with testcases and gather that
                     //    outcome for later rendering
-                    let (mut failed, mut success) = (0, 0);
-                    for (testcase, output) in testcases.into_iter().zip(outputs.into_iter()) {
+                    let (mut document_outcomes, mut detached_outcomes) = (vec![], vec![]);
+                    for (index, (testcase, output)) in testcases.iter().zip(outputs).enumerate() {
                         if output.exit_code == ExitStatus::Detached {
                             count_detached += 1;
-                            if let Some(ref detached_process) = output.detached_process {
-                                kill_detached_process(&pw, detached_process)?;
-                            }
+                            detached_outcomes.push((
+                                index,
+                                Outcome {
+                                    location: Some(test.path.display().to_string()),
+                                    testcase: (*testcase).clone(),
+                                    output,
+                                    escaping: escaping.clone(),
+                                    format: test.parser_type,
+                                    result: Ok(()),
+                                },
+                            ));
                             continue;
                         }
 
-                        let result = testcase.validate(&output);
-                        if result.is_err() {
-                            failed += 1;
-                        } else {
-                            success += 1;
+                        let mut result = testcase.validate_with_variables(&output, &mut variables);
+                        if result.is_ok() && testcase.asserts_nothing() {
+                            match testcase.config.allow_empty_tests {
+                                Some(EmptyTestsPolicy::Fail) => {
+                                    result = Err(TestCaseError::NoAssertions)
+                                }
+                                Some(EmptyTestsPolicy::Warn) => pw.println(format!(
+                                    "⚠️ {}: testcase #{} in line {} asserts nothing",
+                                    style(&name).yellow(),
+                                    index + 1,
+                                    testcase.line_number,
+                                )),
+                                Some(EmptyTestsPolicy::Allow) | None => {}
+                            }
+                        }
+                        if let Some(ref repeated_outputs) = repeated_outputs {
+                            let repeated = repeated_outputs
+                                .get(index)
+                                .cloned()
+                                .unwrap_or_else(|| ("", "", None).into());
+                            if repeated != output {
+                                result = Err(TestCaseError::NonDeterministic(Box::new(repeated)));
+                            }
+                        }
+                        if testcase.config.xfail == Some(true) {
+                            if result.is_ok() {
+                                count_xpassed += 1;
+                                pw.warn(format!(
+                                    "⚠️ {}: testcase #{} in line {} passed, but is expected to fail",
+                                    style(&name).yellow(),
+                                    index + 1,
+                                    testcase.line_number,
+                                ));
+                            } else {
+                                count_xfailed += 1;
+                                result = Ok(());
+                                if self.verbose {
+                                    pw.println(format!(
+                                        "🤞 {}: testcase #{} in line {} failed as expected",
+                                        style(&name).blue(),
+                                        index + 1,
+                                        testcase.line_number,
+                                    ));
+                                }
+                            }
+                        }
+                        if self.verbose {
+                            if let Some(usage) = output.resource_usage {
+                                pw.println(format!(
+                                    "📊 {}: testcase #{} in line {} used {}",
+                                    style(&name).blue(),
+                                    index + 1,
+                                    testcase.line_number,
+                                    usage,
+                                ));
+                            }
+                        }
+                        document_outcomes.push((
+                            index,
+                            Outcome {
+                                location: Some(test.path.display().to_string()),
+                                testcase: (*testcase).clone(),
+                                output,
+                                escaping: escaping.clone(),
+                                format: test.parser_type,
+                                result,
+                            },
+                        ));
+                    }
+
+                    // .. to retry the document while testcases fail, with
+                    //    those that pass in a retry counting as flaky
+                    count_flaky += self.retry_failed(
+                        &pw,
+                        &name,
+                        &shell_path,
+                        &test,
+                        &testcases,
+                        &config,
+                        cram_compat,
+                        &mut document_outcomes,
+                    )?;
+
+                    let failed = document_outcomes
+                        .iter()
+                        .filter(|(_, outcome)| outcome.result.is_err())
+                        .count();
+                    if let Some(ref mut journal) = journal {
+                        if failed > 0 {
+                            let mut all_outcomes = document_outcomes
+                                .iter()
+                                .chain(&detached_outcomes)
+                                .collect::<Vec<_>>();
+                            all_outcomes.sort_by_key(|(index, _)| *index);
+                            self.update_failing_testcases(
+                                &pw,
+                                &name,
+                                &test,
+                                &config,
+                                &all_outcomes
+                                    .into_iter()
+                                    .map(|(_, outcome)| outcome)
+                                    .collect::<Vec<_>>(),
+                                markdown_languages,
+                                journal,
+                            )?;
                         }
-                        outcomes.push(Outcome {
-                            location: Some(test.path.display().to_string()),
-                            testcase: testcase.clone(),
-                            output,
-                            escaping: escaping.clone(),
-                            format: test.parser_type,
-                            result,
-                        });
                     }
+                    let success = document_outcomes.len() - failed;
+                    outcomes.extend(document_outcomes.into_iter().map(|(_, outcome)| outcome));
                     count_failed += failed;
                     count_success += success;
                     let total = failed + success;
@@ -401,7 +1050,7 @@ impl Args {
                     if failed > 0 {
                         pw.println(format!(
                             "❌ {}: failed {} out of {} testcase{}",
-                            style(test.path.to_string_lossy()).red(),
+                            style(&name).red(),
                             style(failed).red().bold(),
                             style(total).bold(),
                             if total == 1 { "" } else { "s" },
@@ -409,25 +1058,127 @@ impl Args {
                     } else if self.verbose {
                         pw.println(format!(
                             "✅ {}: passed {} testcase{}",
-                            style(test.path.to_string_lossy()).green(),
+                            style(&name).green(),
                             style(success).green().bold(),
                             if success == 1 { "" } else { "s" },
                         ));
                     }
                 }
             }
+
+            // collect the artifacts of failed testcases, before the work
+            // directory is removed
+            let failed = count_failed > count_failed_before;
+            if failed && !config.artifacts.is_empty() {
+                match artifacts_directory.collect(
+                    &test.path,
+                    &test_work_directory,
+                    &config.artifacts,
+                ) {
+                    Ok(artifacts) => {
+                        pw.println(format!(
+                            "📦 {}: collected {} artifact(s)",
+                            style(&name).blue(),
+                            artifacts.len(),
+                        ));
+                        outcomes[count_outcomes_before..]
+                            .iter_mut()
+                            .filter(|outcome| outcome.result.is_err())
+                            .for_each(|outcome| outcome.output.artifacts = artifacts.clone());
+                    }
+                    Err(err) => pw.println(format!(
+                        "⚠️ {}: failed to collect artifacts: {err:#}",
+                        style(&name).yellow(),
+                    )),
+                }
+            }
+
+            // keep the work directory for inspection, if configured so, and
+            // point the failed testcases to it
+            if shared_work_directory.keep(&mut test_environment, &workdir, failed) {
+                pw.println(format!(
+                    "📁 {}: kept work directory {}",
+                    style(&name).blue(),
+                    test_work_directory.display(),
+                ));
+                outcomes[count_outcomes_before..]
+                    .iter_mut()
+                    .filter(|outcome| outcome.result.is_err())
+                    .for_each(|outcome| {
+                        outcome.output.work_directory = Some(test_work_directory.clone())
+                    });
+            }
+        }
+        if let Some(directory) = shared_work_directory.finish() {
+            pw.println(format!(
+                "📁 kept shared work directory {}",
+                directory.display()
+            ));
+        }
+
+        if let Some(status) = status {
+            status.run_finished(&outcomes);
         }
+
+        // tear down directories once, after all their documents ran
+        collect_fixture_outcomes(
+            fixtures.tear_down(&pw, &self.global)?,
+            &mut outcomes,
+            &mut count_success,
+            &mut count_failed,
+            &mut count_skipped,
+        );
         pw.println("");
         pw.finish_and_clear();
 
-        // finally render all outcomes of testcase validations
-        let renderer: Box<dyn Renderer> = match self.renderer {
-            ScrutRenderer::Auto | ScrutRenderer::Pretty => {
+        Ok(TestRun {
+            outcomes,
+            count_success,
+            count_skipped,
+            count_failed,
+            count_detached,
+            count_flaky,
+            count_xfailed,
+            count_xpassed,
+            count_fixture_problems,
+        })
+    }
+
+    /// Returns the renderer provided on the command line, falling back to the
+    /// one of the project configuration and then to `auto`
+    fn renderer_type(&self, project_config: Option<&ProjectConfig>) -> ScrutRenderer {
+        let renderer = self
+            .renderer
+            .clone()
+            .or_else(|| project_config.and_then(|config| config.renderer.clone()))
+            .unwrap_or(ScrutRenderer::Auto);
+
+        // annotate failures in pull requests, when running in GitHub Actions
+        if renderer == ScrutRenderer::Auto
+            && env::var("GITHUB_ACTIONS").is_ok_and(|value| value == "true")
+        {
+            ScrutRenderer::Github
+        } else {
+            renderer
+        }
+    }
+
+    fn make_renderer(&self, renderer: &ScrutRenderer) -> Box<dyn Renderer> {
+        match renderer {
+            ScrutRenderer::Auto | ScrutRenderer::Pretty | ScrutRenderer::Summary => {
+                // the summary shows the full diff, as it is all there is
+                let full = *renderer == ScrutRenderer::Summary;
                 let color_renderer = PrettyColorRenderer {
-                    max_surrounding_lines: DEFAULT_SURROUNDING_LINES,
+                    max_surrounding_lines: if full { 0 } else { DEFAULT_SURROUNDING_LINES },
                     absolute_line_numbers: self.absolute_line_numbers,
                     summarize: true,
-                    max_multiline_matched_lines: self.max_multiline_matched_lines,
+                    max_multiline_matched_lines: if full {
+                        usize::MAX
+                    } else {
+                        self.max_multiline_matched_lines
+                    },
+                    diff_style: self.diff_style,
+                    diff_granularity: self.diff_granularity,
                 };
                 if !self.global.no_color && console::colors_enabled() {
                     Box::new(color_renderer)
@@ -435,24 +1186,309 @@ impl Args {
                     Box::new(PrettyMonochromeRenderer::new(color_renderer))
                 }
             }
+            ScrutRenderer::Github => Box::new(GithubRenderer::new(
+                self.make_renderer(&ScrutRenderer::Pretty),
+            )),
             ScrutRenderer::Diff => Box::<DiffRenderer>::default(),
             ScrutRenderer::Json => Box::<JsonRenderer>::default(),
             ScrutRenderer::Yaml => Box::<YamlRenderer>::default(),
+        }
+    }
+
+    /// Executes the already configured testcases of a document once more, in
+    /// a fresh test environment, and returns the outputs of all testcases
+    /// that were executed
+    fn execute_repeated(
+        &self,
+        pw: &ProgressWriter,
+        shell_path: &Path,
+        test: &ParsedTestFile,
+        testcases: &[&TestCase],
+        config: &DocumentConfig,
+        cram_compat: bool,
+    ) -> Result<Vec<Output>> {
+        let mut test_environment = TestEnvironment::new(
+            shell_path,
+            self.global.work_directory.as_deref(),
+            self.global.keep_temporary_directories,
+        )?;
+        let (test_work_directory, mut env_vars) =
+            test_environment.init_test_file(&test.path, cram_compat)?;
+        stage_fixtures(&test.path, &test_work_directory, config)?;
+        env_vars.extend(test.locale_environment());
+        let env_vars = BTreeMap::from_iter(env_vars.iter().map(|(k, v)| (k as &str, v as &str)));
+        let testcases = testcases
+            .iter()
+            .map(|testcase| {
+                let mut testcase = (*testcase).clone();
+                testcase.config = testcase.config.with_environment(&env_vars);
+                apply_allow_recursion(&mut testcase.config);
+                testcase
+            })
+            .collect::<Vec<_>>();
+
+        let executor = make_executor(&test_environment.shell, cram_compat, config)?;
+        let context = ContextBuilder::default()
+            .work_directory(test_work_directory)
+            .temp_directory(test_environment.tmp_directory.as_path_buf())
+            .file(test.path.clone())
+            .config(config.clone())
+            .build()
+            .context("failed to build execution context")?;
+        let outputs = executor.execute_all(&testcases.iter().collect::<Vec<_>>(), &context);
+        kill_detached_processes(pw, &context.detached_processes)?;
+        let outputs = match outputs {
+            Ok(outputs) => outputs,
+            Err(ExecutionError::Timeout(_, outputs) | ExecutionError::Failed(_, outputs)) => {
+                outputs
+            }
+            Err(ExecutionError::Skipped(_)) => vec![],
+            Err(err) => bail!("failing in repeated execution of {:?}: {}", test.path, err),
         };
+        Ok(outputs)
+    }
 
-        info!(
-            success = count_success,
-            skipped = count_skipped,
-            failed = count_failed,
-            detached = count_detached,
-        );
-        print!("{}", renderer.render(&outcomes.iter().collect::<Vec<_>>())?);
+    /// Executes the document again, in a fresh environment, up to `--retries`
+    /// times while any of its testcases fail and replaces the outcomes of
+    /// the failed testcases that pass in a retry. Returns the amount of
+    /// testcases that passed only on retry.
+    #[allow(clippy::too_many_arguments)]
+    fn retry_failed(
+        &self,
+        pw: &ProgressWriter,
+        name: &str,
+        shell_path: &Path,
+        test: &ParsedTestFile,
+        testcases: &[&TestCase],
+        config: &DocumentConfig,
+        cram_compat: bool,
+        outcomes: &mut [(usize, Outcome)],
+    ) -> Result<usize> {
+        let mut flaky = 0;
+        for attempt in 1..=self.retries {
+            // testcases without assertions fail by policy, not by chance
+            let failed = outcomes
+                .iter_mut()
+                .filter(|(_, outcome)| {
+                    matches!(outcome.result, Err(ref err) if !matches!(err, TestCaseError::NoAssertions))
+                })
+                .collect::<Vec<_>>();
+            if failed.is_empty() {
+                break;
+            }
 
-        if count_failed > 0 {
-            Err(anyhow!(ValidationFailedError))
+            let outputs =
+                self.execute_repeated(pw, shell_path, test, testcases, config, cram_compat)?;
+            // validate in order, so that the variables that preceding
+            // testcases capture are available to the failed ones
+            let mut variables = Variables::new();
+            let passed = testcases
+                .iter()
+                .zip(&outputs)
+                .map(|(testcase, output)| {
+                    testcase
+                        .validate_with_variables(output, &mut variables)
+                        .is_ok()
+                })
+                .collect::<Vec<_>>();
+            for (index, outcome) in failed {
+                let Some(output) = outputs.get(*index) else {
+                    continue;
+                };
+                if passed.get(*index) == Some(&true) {
+                    pw.println(format!(
+                        "🔁 {}: testcase #{} in line {} passed only on retry #{}",
+                        style(name).yellow(),
+                        *index + 1,
+                        outcome.testcase.line_number,
+                        attempt,
+                    ));
+                    outcome.output = output.clone();
+                    outcome.result = Ok(());
+                    flaky += 1;
+                }
+            }
+        }
+        Ok(flaky)
+    }
+
+    /// Whether the run fails any of the gates that are stricter than failing
+    /// on failed testcases (`--flake-budget`, `--fail-on-skipped` and
+    /// `--fail-on-xpass`), which are reported if so
+    fn fails_strict_gates(&self, run: &TestRun) -> bool {
+        let mut failed = false;
+        if let Some(budget) = self.flake_budget {
+            if run.count_flaky > budget {
+                eprintln!(
+                    "🔁 {} testcase(s) passed only on retry, which exceeds the flake budget of {}",
+                    style(run.count_flaky).red().bold(),
+                    budget,
+                );
+                failed = true;
+            }
+        }
+        let skipped = run
+            .outcomes
+            .iter()
+            .filter(|outcome| matches!(outcome.result, Err(TestCaseError::Skipped)))
+            .count();
+        if self.fail_on_skipped && skipped > 0 {
+            eprintln!(
+                "⏩ {} testcase(s) were skipped, which fails the run due to --fail-on-skipped",
+                style(skipped).red().bold(),
+            );
+            failed = true;
+        }
+        if self.fail_on_xpass && run.count_xpassed > 0 {
+            eprintln!(
+                "⚠️ {} testcase(s) passed, but are expected to fail, which fails the run due to --fail-on-xpass",
+                style(run.count_xpassed).red().bold(),
+            );
+            failed = true;
+        }
+        if run.count_fixture_problems > 0 {
+            eprintln!(
+                "🗑️ {} fixture file(s) are unreferenced or missing, which fails the run due to --check-fixtures",
+                style(run.count_fixture_problems).red().bold(),
+            );
+            failed = true;
+        }
+        failed
+    }
+
+    /// Rewrites the expectations of the failed testcases of the document, from
+    /// the given outcomes of all its testcases, unless the document is not
+    /// written as it is run
+    #[allow(clippy::too_many_arguments)]
+    fn update_failing_testcases(
+        &self,
+        pw: &ProgressWriter,
+        name: &str,
+        test: &ParsedTestFile,
+        config: &DocumentConfig,
+        outcomes: &[&Outcome],
+        markdown_languages: &[&str],
+        journal: &mut UpdateJournal,
+    ) -> Result<()> {
+        let unsupported = if !config.prepend.is_empty() || !config.append.is_empty() {
+            Some("'prepend' or 'append' are")
+        } else if test.locale.is_some() {
+            Some("'locales' are")
+        } else if config.template.is_some() {
+            Some("'template' is")
         } else {
-            Ok(())
+            None
+        };
+        if let Some(unsupported) = unsupported {
+            pw.warn(format!(
+                "⏩ {}: not updated, because {} currently not supported in update",
+                style(name).yellow(),
+                unsupported,
+            ));
+            return Ok(());
         }
+
+        let updated = match generate_update(test, outcomes, markdown_languages) {
+            Ok(updated) => updated,
+            Err(err) => {
+                pw.warn(format!(
+                    "⏩ {}: not updated, because {:#}",
+                    style(name).yellow(),
+                    err,
+                ));
+                return Ok(());
+            }
+        };
+
+        // e.g. if only frozen testcases failed
+        if updated == test.content {
+            return Ok(());
+        }
+        journal.record(&test.path)?;
+        journal.save(Path::new(UNDO_DIRECTORY))?;
+        fs::write(&test.path, &updated)
+            .with_context(|| format!("overwrite existing document in {:?}", test.path))?;
+        pw.warn(format!(
+            "✍️ {}: overwritten document with updated expectations of failed testcases",
+            style(name).green()
+        ));
+        Ok(())
+    }
+
+    /// Prints the files in the fixture directories, that no test document
+    /// references, and the referenced files that do not exist, and returns
+    /// their amount
+    fn check_fixture_references(
+        &self,
+        tests: &[ParsedTestFile],
+        project_config: Option<&ProjectConfig>,
+    ) -> Result<usize> {
+        let fixture_directories = project_config
+            .map(|config| config.fixture_directories.as_slice())
+            .unwrap_or_default();
+        let references = FixtureReferences::check(tests, fixture_directories)?;
+        for path in &references.unreferenced {
+            eprintln!(
+                "🗑️ {}: not referenced by any test document",
+                style(path.display()).yellow()
+            );
+        }
+        for reference in &references.missing {
+            eprintln!(
+                "❓ {}:{}: references {}, which does not exist",
+                reference.document.display(),
+                reference.line_number,
+                style(reference.path.display()).yellow()
+            );
+        }
+        Ok(references.count())
+    }
+
+    /// Opens the stream for progress events, if requested with `--status-fd`
+    fn open_status_stream(&self) -> Result<Option<StatusStream>> {
+        self.status_fd
+            .as_deref()
+            .map(StatusStream::open)
+            .transpose()
+    }
+
+    /// Returns the test documents that the `--impact-cmd` selects, if any, or
+    /// all test documents if it fails
+    fn select_impacted_tests(&self, tests: Vec<ParsedTestFile>) -> Result<Vec<ParsedTestFile>> {
+        let Some(ref command) = self.impact_cmd else {
+            return Ok(tests);
+        };
+        let shell_path = canonical_shell(self.global.shell.as_ref().map(|p| p as &Path))?;
+        let selected = if self.changed_files.is_empty() {
+            changed_paths_since_head()
+        } else {
+            Ok(self.changed_files.clone())
+        }
+        .and_then(|changed| {
+            debug!(?changed, "determining impacted test documents");
+            impacted_paths(&shell_path, command, &changed)
+        });
+        match selected {
+            Ok(selected) => Ok(select_impacted(tests, &selected)),
+            Err(err) => {
+                warn!("running all test documents, because impact analysis failed: {err:#}");
+                Ok(tests)
+            }
+        }
+    }
+
+    /// Returns the failed testcases of the last run of the given test paths,
+    /// if `--rerun-failed` is requested and any are recorded
+    fn load_last_run(&self, test_file_paths: &[&Path]) -> Result<Option<LastRun>> {
+        if !self.rerun_failed {
+            return Ok(None);
+        }
+        let path = LastRun::find_file(test_file_paths)?;
+        let last_run = LastRun::load(&path)?.filter(|last_run| !last_run.failed.is_empty());
+        if last_run.is_none() {
+            warn!("running all test documents, because no testcases failed in the last run");
+        }
+        Ok(last_run)
     }
 
     /// Translates command line arguments into a document config, that has only
@@ -472,12 +1508,17 @@ impl Args {
     /// Translates command line arguments into a testcase config, that has only
     /// values set which are provided by the user.
     fn to_testcase_config(&self) -> TestCaseConfig {
-        self.global.to_testcase_config()
+        let mut config = self.global.to_testcase_config();
+        if self.diff_context.is_some() {
+            config.diff_context = self.diff_context;
+        }
+        config
     }
 }
 
 /// Helper function to handle early termination cases (timeout, fail_fast).
 /// Validates outputs that were collected, marks remaining tests as skipped.
+#[allow(clippy::too_many_arguments)]
 fn handle_early_termination<F>(
     outputs: &[scrut::output::Output],
     testcases: &[&TestCase],
@@ -535,6 +1576,33 @@ fn handle_early_termination<F>(
     }
 }
 
+/// Adds the outcomes of directory setup or teardown documents to all outcomes
+fn collect_fixture_outcomes(
+    fixture_outcomes: Vec<Outcome>,
+    outcomes: &mut Vec<Outcome>,
+    count_success: &mut usize,
+    count_failed: &mut usize,
+    count_skipped: &mut usize,
+) {
+    for outcome in fixture_outcomes {
+        match outcome.result {
+            Ok(_) => *count_success += 1,
+            Err(TestCaseError::Skipped) => *count_skipped += 1,
+            Err(_) => *count_failed += 1,
+        }
+        outcomes.push(outcome);
+    }
+}
+
+/// Records the failed testcases of the run in the given file for
+/// `--rerun-failed`, which is not worth failing the run for if it is not
+/// possible
+fn save_last_run(path: &Path, last_run: LastRun) {
+    if let Err(err) = last_run.save(path) {
+        warn!("failed to record the failed testcases of the run: {err:#}");
+    }
+}
+
 fn prefix_with_directory(prefix: &Path, paths: &[PathBuf]) -> Vec<PathBuf> {
     paths
         .iter()
@@ -0,0 +1,744 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+use clap::Parser;
+use dialoguer::console::Term;
+use dialoguer::console::strip_ansi_codes;
+use ratatui::DefaultTerminal;
+use ratatui::Frame;
+use ratatui::crossterm::event;
+use ratatui::crossterm::event::Event;
+use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::layout::Constraint;
+use ratatui::layout::Layout;
+use ratatui::style::Color;
+use ratatui::style::Modifier;
+use ratatui::style::Style;
+use ratatui::text::Line;
+use ratatui::text::Span;
+use ratatui::widgets::Block;
+use ratatui::widgets::List;
+use ratatui::widgets::ListItem;
+use ratatui::widgets::ListState;
+use ratatui::widgets::Paragraph;
+use scrut::outcome::Outcome;
+use scrut::renderers::pretty::PrettyColorRenderer;
+use scrut::renderers::pretty::PrettyMonochromeRenderer;
+use scrut::renderers::renderer::Renderer;
+use scrut::testcase::TestCaseError;
+
+use super::root::GlobalSharedParameters;
+use super::root::redirect_warnings;
+use crate::utils::ParsedTestFile;
+use crate::utils::ProjectConfig;
+use crate::utils::StatusStream;
+
+/// Time to wait for key presses, before checking on running tests
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Run tests in an interactive terminal UI
+///
+/// Shows all test documents with their testcases and the live status of each
+/// while they run. The failure of the selected testcase is shown next to it.
+///
+/// Keys: `↑`/`↓` (or `k`/`j`) select, `PgUp`/`PgDn` scroll the details, `r`
+/// re-runs the selected document, `R` all documents, `e` opens the document
+/// in `$EDITOR` at the selected testcase, `u` accepts the update of the
+/// selected document (as `scrut update --replace`) and `q` quits.
+#[derive(Debug, Parser)]
+pub struct Args {
+    /// Path to test files or directories, defaults to the current directory
+    paths: Vec<PathBuf>,
+
+    #[clap(flatten)]
+    global: GlobalSharedParameters,
+}
+
+impl Args {
+    pub(crate) fn run(&self) -> Result<()> {
+        if !Term::stdout().is_term() {
+            bail!("interactive test runner requires a terminal, use `scrut test` instead");
+        }
+        let paths = if self.paths.is_empty() {
+            vec![PathBuf::from(".")]
+        } else {
+            self.paths.clone()
+        };
+        let path_refs = paths.iter().map(|p| p as &Path).collect::<Vec<_>>();
+        let project_config = ProjectConfig::find_any(&path_refs)?;
+        let documents = super::test::Args::for_paths(paths.clone(), self.global.clone())
+            .find_documents(&path_refs, project_config.as_ref())?;
+        if documents.is_empty() {
+            println!("👋 No test documents found in {:?}. Stopping.", &paths);
+            return Ok(());
+        }
+
+        // warnings of runs and updates are shown in the dashboard, instead of
+        // being written over it
+        let (warning_sender, warnings) = mpsc::channel();
+        redirect_warnings(Some(Box::new(move |warning| {
+            let _ = warning_sender.send(strip_ansi_codes(&warning).into_owned());
+        })));
+        let mut dashboard = Dashboard::new(&documents);
+        let mut terminal = ratatui::init();
+        let result = self.run_dashboard(&mut terminal, &mut dashboard, &warnings, paths);
+        ratatui::restore();
+        redirect_warnings(None);
+        result
+    }
+
+    /// Runs all test documents and handles key presses until the user quits
+    fn run_dashboard(
+        &self,
+        terminal: &mut DefaultTerminal,
+        dashboard: &mut Dashboard,
+        warnings: &Receiver<String>,
+        paths: Vec<PathBuf>,
+    ) -> Result<()> {
+        let mut running = Some(self.start_run(dashboard, paths.clone()));
+        loop {
+            if let Some(ref receiver) = running {
+                if dashboard.receive(receiver) {
+                    running = None;
+                }
+            }
+            if let Some(warning) = warnings.try_iter().last() {
+                dashboard.warning = Some(warning);
+            }
+            terminal.draw(|frame| dashboard.draw(frame))?;
+
+            if !event::poll(POLL_INTERVAL)? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Down | KeyCode::Char('j') => dashboard.select_next(),
+                KeyCode::Up | KeyCode::Char('k') => dashboard.select_previous(),
+                KeyCode::PageDown => dashboard.scroll_detail(10),
+                KeyCode::PageUp => dashboard.scroll_detail(-10),
+                _ if running.is_some() => {
+                    dashboard.message = "Tests are still running".into();
+                }
+                KeyCode::Char('r') => {
+                    let path = dashboard.selected_document().path.clone();
+                    running = Some(self.start_run(dashboard, vec![path]));
+                }
+                KeyCode::Char('R') => {
+                    running = Some(self.start_run(dashboard, paths.clone()));
+                }
+                KeyCode::Char('e') => {
+                    let (path, line) = dashboard.selected_location();
+                    ratatui::restore();
+                    let result = open_editor(&path, line);
+                    *terminal = ratatui::init();
+                    dashboard.message = match result {
+                        Ok(_) => format!("Edited {}", path.display()),
+                        Err(err) => format!("{err:#}"),
+                    };
+                }
+                KeyCode::Char('u') => {
+                    let path = dashboard.selected_document().path.clone();
+                    let result =
+                        super::update::Args::for_replacing(vec![path.clone()], self.global.clone())
+                            .run();
+                    match result {
+                        Ok(_) => running = Some(self.start_run(dashboard, vec![path])),
+                        Err(err) => dashboard.message = format!("{err:#}"),
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Runs the test documents in the given paths in the background
+    fn start_run(&self, dashboard: &mut Dashboard, paths: Vec<PathBuf>) -> Receiver<RunMessage> {
+        dashboard.reset(&paths);
+        let (sender, receiver) = mpsc::channel();
+        let global = self.global.clone();
+        std::thread::spawn(move || {
+            let status = StatusStream::new(Box::new(StatusLines(sender.clone(), vec![])));
+            let path_refs = paths.iter().map(|p| p as &Path).collect::<Vec<_>>();
+            let result = ProjectConfig::find_any(&path_refs).and_then(|project_config| {
+                super::test::Args::for_paths(paths.clone(), global).execute(
+                    &path_refs,
+                    project_config.as_ref(),
+                    Some(&status),
+                )
+            });
+            let _ = sender.send(RunMessage::Finished(
+                result
+                    .map(|run| run.outcomes)
+                    .map_err(|err| format!("{err:#}")),
+            ));
+        });
+        receiver
+    }
+}
+
+/// Opens the document in the editor of the user, at the given line
+fn open_editor(path: &Path, line: usize) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".into());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().context("empty editor command")?;
+    let status = Command::new(program)
+        .args(parts)
+        .arg(format!("+{line}"))
+        .arg(path)
+        .status()
+        .with_context(|| format!("open editor {editor}"))?;
+    if !status.success() {
+        return Err(anyhow!("editor {editor} failed with {status}"));
+    }
+    Ok(())
+}
+
+/// Messages from a run of test documents in the background
+enum RunMessage {
+    /// A line of the progress stream (see [`StatusStream`])
+    Status(String),
+
+    /// The outcomes of all testcases, or what went wrong
+    Finished(std::result::Result<Vec<Outcome>, String>),
+}
+
+/// Sends each line of the progress stream to the dashboard
+struct StatusLines(Sender<RunMessage>, Vec<u8>);
+
+impl Write for StatusLines {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.1.extend_from_slice(buf);
+        while let Some(index) = self.1.iter().position(|b| *b == b'\n') {
+            let line = self.1.drain(..=index).collect::<Vec<_>>();
+            let line = String::from_utf8_lossy(&line).trim_end().to_string();
+            self.0
+                .send(RunMessage::Status(line))
+                .map_err(|_| std::io::ErrorKind::BrokenPipe)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    Skipped,
+}
+
+impl Status {
+    fn from_result(result: &str) -> Self {
+        match result {
+            "succeeded" => Self::Succeeded,
+            "skipped" => Self::Skipped,
+            _ => Self::Failed,
+        }
+    }
+
+    fn symbol(&self) -> Span<'static> {
+        match self {
+            Self::Pending => Span::styled("·", Style::default().fg(Color::DarkGray)),
+            Self::Running => Span::styled("…", Style::default().fg(Color::Yellow)),
+            Self::Succeeded => Span::styled("✔", Style::default().fg(Color::Green)),
+            Self::Failed => Span::styled("✘", Style::default().fg(Color::Red)),
+            Self::Skipped => Span::styled("-", Style::default().fg(Color::Blue)),
+        }
+    }
+}
+
+struct TestcaseRow {
+    line_number: usize,
+    title: String,
+    status: Status,
+    outcome: Option<Outcome>,
+}
+
+struct DocumentRow {
+    path: PathBuf,
+    status: Status,
+    testcases: Vec<TestcaseRow>,
+}
+
+impl DocumentRow {
+    /// Derives the status of the document from the status of its testcases
+    fn finish(&mut self) {
+        let statuses = self.testcases.iter().map(|t| t.status).collect::<Vec<_>>();
+        self.status = if statuses.contains(&Status::Failed) {
+            Status::Failed
+        } else if !statuses.is_empty() && statuses.iter().all(|s| *s == Status::Skipped) {
+            Status::Skipped
+        } else {
+            Status::Succeeded
+        };
+    }
+}
+
+/// The state of the terminal UI: the tree of documents and testcases with
+/// their status, and which of them is selected
+struct Dashboard {
+    documents: Vec<DocumentRow>,
+
+    /// Rows of the tree, as the index of the document and, for testcase
+    /// rows, the index of the testcase in the document
+    rows: Vec<(usize, Option<usize>)>,
+    selected: usize,
+    detail_scroll: u16,
+    message: String,
+
+    /// The last warning or error that was logged since the last run started
+    warning: Option<String>,
+}
+
+impl Dashboard {
+    fn new(documents: &[ParsedTestFile]) -> Self {
+        let mut dashboard = Self {
+            documents: vec![],
+            rows: vec![],
+            selected: 0,
+            detail_scroll: 0,
+            message: String::new(),
+            warning: None,
+        };
+        for document in documents {
+            // documents with multiple locales are shown once
+            if dashboard.documents.iter().any(|d| d.path == document.path) {
+                continue;
+            }
+            let index = dashboard.documents.len();
+            dashboard.rows.push((index, None));
+            dashboard.documents.push(DocumentRow {
+                path: document.path.clone(),
+                status: Status::Pending,
+                testcases: document
+                    .testcases
+                    .iter()
+                    .enumerate()
+                    .map(|(testcase_index, testcase)| {
+                        dashboard.rows.push((index, Some(testcase_index)));
+                        TestcaseRow {
+                            line_number: testcase.line_number,
+                            title: if testcase.title.is_empty() {
+                                testcase
+                                    .shell_expression
+                                    .lines()
+                                    .next()
+                                    .unwrap_or("")
+                                    .into()
+                            } else {
+                                testcase.title.clone()
+                            },
+                            status: Status::Pending,
+                            outcome: None,
+                        }
+                    })
+                    .collect(),
+            });
+        }
+        dashboard
+    }
+
+    fn document_mut(&mut self, path: &str) -> Option<&mut DocumentRow> {
+        self.documents
+            .iter_mut()
+            .find(|document| document.path.to_string_lossy() == path)
+    }
+
+    /// Marks all documents in the paths as pending
+    fn reset(&mut self, paths: &[PathBuf]) {
+        for document in &mut self.documents {
+            if paths.iter().any(|path| document.path.starts_with(path)) {
+                document.status = Status::Pending;
+                for testcase in &mut document.testcases {
+                    testcase.status = Status::Pending;
+                    testcase.outcome = None;
+                }
+            }
+        }
+        self.message = "Running …".into();
+        self.warning = None;
+    }
+
+    /// Applies all pending messages of the run and returns whether the run
+    /// is finished
+    fn receive(&mut self, receiver: &Receiver<RunMessage>) -> bool {
+        loop {
+            match receiver.try_recv() {
+                Ok(RunMessage::Status(line)) => self.apply_status(&line),
+                Ok(RunMessage::Finished(Ok(outcomes))) => {
+                    self.apply_outcomes(outcomes);
+                    return true;
+                }
+                Ok(RunMessage::Finished(Err(err))) => {
+                    self.message = err;
+                    return true;
+                }
+                Err(mpsc::TryRecvError::Empty) => return false,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.message = "Tests stopped unexpectedly".into();
+                    return true;
+                }
+            }
+        }
+    }
+
+    /// Applies an event of the progress stream (see [`StatusStream`])
+    fn apply_status(&mut self, line: &str) {
+        let Ok(event) = serde_json::from_str::<serde_json::Value>(line) else {
+            return;
+        };
+        let path = event["path"].as_str().unwrap_or_default();
+        match event["event"].as_str() {
+            Some("document_started") => {
+                if let Some(document) = self.document_mut(path) {
+                    document.status = Status::Running;
+                    for testcase in &mut document.testcases {
+                        testcase.status = Status::Running;
+                    }
+                }
+            }
+            Some("testcase_finished") => {
+                let line_number = event["line_number"].as_u64().unwrap_or_default() as usize;
+                let status = Status::from_result(event["result"].as_str().unwrap_or_default());
+                if let Some(testcase) = self.document_mut(path).and_then(|document| {
+                    document
+                        .testcases
+                        .iter_mut()
+                        .find(|testcase| testcase.line_number == line_number)
+                }) {
+                    testcase.status = status;
+                }
+            }
+            Some("document_finished") => {
+                if let Some(document) = self.document_mut(path) {
+                    document.finish();
+                }
+            }
+            Some("progress") => {
+                self.message = format!(
+                    "Running … {} of {} document(s)",
+                    event["completed"], event["total"]
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// Attaches the outcomes of a finished run to their testcases
+    fn apply_outcomes(&mut self, outcomes: Vec<Outcome>) {
+        let (mut failed, total) = (0, outcomes.len());
+        for outcome in outcomes {
+            let location = outcome.location.clone().unwrap_or_default();
+            let Some(document) = self.document_mut(&location) else {
+                continue;
+            };
+            let Some(testcase) = document
+                .testcases
+                .iter_mut()
+                .find(|testcase| testcase.line_number == outcome.testcase.line_number)
+            else {
+                continue;
+            };
+            testcase.status = match outcome.result {
+                Ok(_) => Status::Succeeded,
+                Err(TestCaseError::Skipped) => Status::Skipped,
+                Err(_) => {
+                    failed += 1;
+                    Status::Failed
+                }
+            };
+            testcase.outcome = Some(outcome);
+            document.finish();
+        }
+        self.message = format!("Finished {total} testcase(s), of which {failed} failed");
+    }
+
+    fn select_next(&mut self) {
+        if self.selected + 1 < self.rows.len() {
+            self.selected += 1;
+            self.detail_scroll = 0;
+        }
+    }
+
+    fn select_previous(&mut self) {
+        if self.selected > 0 {
+            self.selected -= 1;
+            self.detail_scroll = 0;
+        }
+    }
+
+    fn scroll_detail(&mut self, lines: i32) {
+        self.detail_scroll = (self.detail_scroll as i32 + lines).max(0) as u16;
+    }
+
+    fn selected_document(&self) -> &DocumentRow {
+        &self.documents[self.rows[self.selected].0]
+    }
+
+    fn selected_testcase(&self) -> Option<&TestcaseRow> {
+        let (document, testcase) = self.rows[self.selected];
+        testcase.map(|testcase| &self.documents[document].testcases[testcase])
+    }
+
+    /// Returns the path of the selected document and the line of the
+    /// selected testcase, or of the first failed testcase of the document
+    fn selected_location(&self) -> (PathBuf, usize) {
+        let document = self.selected_document();
+        let testcase = self.selected_testcase().or_else(|| {
+            document
+                .testcases
+                .iter()
+                .find(|testcase| testcase.status == Status::Failed)
+        });
+        (
+            document.path.clone(),
+            testcase.map_or(1, |testcase| testcase.line_number),
+        )
+    }
+
+    /// Returns the details of the selected row: the rendered failure of a
+    /// testcase, or a summary of a document
+    fn detail(&self) -> String {
+        let document = self.selected_document();
+        let Some(testcase) = self.selected_testcase() else {
+            let count = |status| {
+                document
+                    .testcases
+                    .iter()
+                    .filter(|testcase| testcase.status == status)
+                    .count()
+            };
+            return format!(
+                "{}\n\n{} testcase(s): {} succeeded, {} failed and {} skipped",
+                document.path.display(),
+                document.testcases.len(),
+                count(Status::Succeeded),
+                count(Status::Failed),
+                count(Status::Skipped),
+            );
+        };
+        match testcase.outcome {
+            Some(ref outcome) if outcome.result.is_err() => {
+                let renderer = PrettyMonochromeRenderer::new(PrettyColorRenderer {
+                    summarize: false,
+                    ..Default::default()
+                });
+                renderer
+                    .render(&[outcome])
+                    .unwrap_or_else(|err| format!("{err:#}"))
+            }
+            Some(ref outcome) => format!(
+                "{}:{}\n\n$ {}\n\nsucceeded",
+                document.path.display(),
+                testcase.line_number,
+                outcome.testcase.shell_expression
+            ),
+            None => format!(
+                "{}:{}\n\n{:?}",
+                document.path.display(),
+                testcase.line_number,
+                testcase.status
+            ),
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [tree, detail] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+
+        let items = self
+            .rows
+            .iter()
+            .map(|(document, testcase)| {
+                let document = &self.documents[*document];
+                ListItem::new(match testcase {
+                    None => Line::from(vec![
+                        document.status.symbol(),
+                        Span::raw(format!(" {}", document.path.display())),
+                    ]),
+                    Some(testcase) => {
+                        let testcase = &document.testcases[*testcase];
+                        Line::from(vec![
+                            Span::raw("  "),
+                            testcase.status.symbol(),
+                            Span::raw(format!(" {}: {}", testcase.line_number, testcase.title)),
+                        ])
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(Block::bordered().title(" Tests "))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(list, tree, &mut state);
+
+        frame.render_widget(
+            Paragraph::new(self.detail())
+                .block(Block::bordered().title(" Details "))
+                .scroll((self.detail_scroll, 0)),
+            detail,
+        );
+
+        let help = "↑/↓ select · r re-run · R re-run all · e edit · u accept update · q quit";
+        let mut footer_text = vec![self.message.as_str()];
+        footer_text.extend(self.warning.as_deref());
+        footer_text.push(help);
+        footer_text.retain(|text| !text.is_empty());
+        frame.render_widget(
+            Paragraph::new(footer_text.join(" · "))
+                .style(Style::default().add_modifier(Modifier::DIM)),
+            footer,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use scrut::config::DocumentConfig;
+    use scrut::escaping::Escaper;
+    use scrut::outcome::Outcome;
+    use scrut::parsers::parser::ParserType;
+    use scrut::testcase::TestCase;
+    use scrut::testcase::TestCaseError;
+
+    use super::Dashboard;
+    use super::Status;
+    use crate::utils::ParsedTestFile;
+
+    fn document(path: &str, lines: &[usize]) -> ParsedTestFile {
+        ParsedTestFile {
+            path: path.into(),
+            content: String::new(),
+            parser_type: ParserType::Markdown,
+            testcases: lines
+                .iter()
+                .map(|line_number| TestCase {
+                    title: format!("Testcase {line_number}"),
+                    shell_expression: "echo hello".into(),
+                    line_number: *line_number,
+                    ..Default::default()
+                })
+                .collect(),
+            config: DocumentConfig::default(),
+            locale: None,
+        }
+    }
+
+    fn dashboard() -> Dashboard {
+        Dashboard::new(&[document("a.md", &[3, 9]), document("b.md", &[5])])
+    }
+
+    #[test]
+    fn test_dashboard_rows() {
+        let mut dashboard = dashboard();
+        assert_eq!(
+            vec![
+                (0, None),
+                (0, Some(0)),
+                (0, Some(1)),
+                (1, None),
+                (1, Some(0))
+            ],
+            dashboard.rows
+        );
+        dashboard.select_previous();
+        assert_eq!(0, dashboard.selected);
+        for _ in 0..10 {
+            dashboard.select_next();
+        }
+        assert_eq!(4, dashboard.selected);
+        assert_eq!((PathBuf::from("b.md"), 5), dashboard.selected_location());
+    }
+
+    #[test]
+    fn test_dashboard_applies_status_events() {
+        let mut dashboard = dashboard();
+        dashboard.apply_status(r#"{"event":"document_started","path":"a.md","index":0}"#);
+        assert_eq!(Status::Running, dashboard.documents[0].status);
+        assert_eq!(Status::Pending, dashboard.documents[1].status);
+        dashboard.apply_status(
+            r#"{"event":"testcase_finished","path":"a.md","line_number":3,"result":"succeeded"}"#,
+        );
+        dashboard.apply_status(
+            r#"{"event":"testcase_finished","path":"a.md","line_number":9,"result":"failed"}"#,
+        );
+        dashboard.apply_status(r#"{"event":"document_finished","path":"a.md","index":0}"#);
+        assert_eq!(Status::Failed, dashboard.documents[0].status);
+        assert_eq!(
+            vec![Status::Succeeded, Status::Failed],
+            dashboard.documents[0]
+                .testcases
+                .iter()
+                .map(|testcase| testcase.status)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!((PathBuf::from("a.md"), 9), dashboard.selected_location());
+    }
+
+    #[test]
+    fn test_dashboard_shows_failure_of_outcome() {
+        let mut dashboard = dashboard();
+        let testcase = dashboard.documents[0].testcases[1].line_number;
+        dashboard.apply_outcomes(vec![Outcome {
+            location: Some("a.md".into()),
+            output: ("", "", Some(1)).into(),
+            testcase: TestCase {
+                title: "Testcase 9".into(),
+                shell_expression: "echo hello".into(),
+                line_number: testcase,
+                ..Default::default()
+            },
+            escaping: Escaper::default(),
+            format: ParserType::Markdown,
+            result: Err(TestCaseError::InvalidExitCode {
+                actual: 1,
+                expected: 0.into(),
+            }),
+        }]);
+        assert_eq!(Status::Failed, dashboard.documents[0].status);
+        dashboard.select_next();
+        dashboard.select_next();
+        assert!(
+            dashboard.detail().contains("unexpected exit code"),
+            "failure is rendered: {}",
+            dashboard.detail()
+        );
+    }
+}
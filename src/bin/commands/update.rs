@@ -20,7 +20,9 @@ use dialoguer::console;
 use dialoguer::console::style;
 use scrut::config::DEFAULT_SKIP_DOCUMENT_CODE;
 use scrut::config::DocumentConfig;
+use scrut::config::Shell;
 use scrut::config::TestCaseConfig;
+use scrut::config::TreeAssertion;
 use scrut::escaping::strip_colors;
 use scrut::executors::context::ContextBuilder;
 use scrut::executors::error::ExecutionError;
@@ -31,41 +33,59 @@ use scrut::generators::generator::UpdateGenerator;
 use scrut::generators::markdown::MarkdownTestCaseGenerator;
 use scrut::generators::markdown::MarkdownUpdateGenerator;
 use scrut::outcome::Outcome;
-use scrut::parsers::markdown::DEFAULT_MARKDOWN_LANGUAGES;
+use scrut::output::DirectoryTree;
+use scrut::output::ProducedFile;
+use scrut::output::directory_tree_changes;
 use scrut::parsers::parser::ParserType;
 use scrut::renderers::pretty::DEFAULT_MULTILINE_MATCHED_LINES;
 use scrut::renderers::pretty::DEFAULT_SURROUNDING_LINES;
 use scrut::renderers::pretty::PrettyColorRenderer;
 use scrut::renderers::pretty::PrettyMonochromeRenderer;
 use scrut::renderers::renderer::Renderer;
+use scrut::rules::variables::Variables;
 use scrut::testcase::TestCase;
 
 use super::root::GlobalSharedParameters;
+use crate::utils::DirectoryFixtures;
 use crate::utils::FileParser;
 use crate::utils::ParsedTestFile;
 use crate::utils::ProgressWriter;
-use crate::utils::TestEnvironment;
+use crate::utils::ProjectConfig;
+use crate::utils::SharedWorkDirectory;
+use crate::utils::UNDO_DIRECTORY;
+use crate::utils::UpdateJournal;
+use crate::utils::apply_allow_recursion;
+use crate::utils::assure_not_recursive;
 use crate::utils::canonical_shell;
 use crate::utils::confirm;
 use crate::utils::debug_testcases;
 use crate::utils::get_log_level;
 use crate::utils::make_executor;
+use crate::utils::markdown_languages;
+use crate::utils::stage_fixtures;
 
 /// Re-run all testcases in given file(s) and update the output expectations
 #[derive(Debug, Parser)]
 pub struct Args {
     /// Path to test files or directories
-    #[clap(required = true)]
+    #[clap(required_unless_present = "undo")]
     paths: Vec<PathBuf>,
 
     /// Whether to print out debug output - use only
     #[clap(long)]
     debug: bool,
 
-    /// For markdown format: Language annotations that are considered test cases
-    #[clap(long, hide = true, default_values = DEFAULT_MARKDOWN_LANGUAGES, num_args=1..)]
+    /// For markdown format: Language annotations that are considered test cases.
+    /// Defaults to the `markdown_languages` of the project configuration, if
+    /// any, or `scrut` otherwise.
+    #[clap(long, hide = true, num_args = 1..)]
     markdown_languages: Vec<String>,
 
+    /// Parse all test documents in this format, instead of determining it from
+    /// the `format` in their front-matter, their file name or their content
+    #[clap(long, value_enum)]
+    format: Option<ParserType>,
+
     /// What suffix to add to thew newly created file (will overwrite already
     /// existing files!)
     #[clap(long, short, default_value = ".new")]
@@ -113,26 +133,61 @@ pub struct Args {
     #[clap(long)]
     verbose: bool,
 
+    /// Restore the documents that previous updates wrote to their contents
+    /// before the update, instead of updating. If paths are given, then only
+    /// documents within them are restored.
+    #[clap(long, conflicts_with_all = ["convert", "replace"])]
+    undo: bool,
+
+    /// Whether to hide the progress bar, e.g. when another UI is shown
+    #[clap(skip)]
+    hide_progress: bool,
+
+    /// Whether to print neither the changes nor the summary, e.g. when
+    /// another UI is shown
+    #[clap(skip)]
+    quiet: bool,
+
     #[clap(flatten)]
     global: GlobalSharedParameters,
 }
 
 impl Args {
+    /// Returns the arguments to replace the test documents in the given paths
+    /// with their updated contents without asking, as the `tui` command does
+    pub(super) fn for_replacing(paths: Vec<PathBuf>, global: GlobalSharedParameters) -> Self {
+        let mut args: Self =
+            super::root::parse_subcommand(&["update", "--replace", "--assume-yes", "."]);
+        args.paths = paths;
+        args.global = global;
+        args.hide_progress = true;
+        args.quiet = true;
+        args
+    }
+
     pub(crate) fn run(&self) -> Result<()> {
+        let paths = self.paths.iter().map(|p| p as &Path).collect::<Vec<_>>();
+        if self.undo {
+            return self.undo_updates(&paths);
+        }
+        let project_config = ProjectConfig::find_any(&paths)?;
+
         // init parser and determine suffices to look for
-        let markdown_languages = &self
-            .markdown_languages
-            .iter()
-            .map(|s| &**s)
-            .collect::<Vec<_>>();
-        let parser = FileParser::new(&self.match_markdown, &self.match_cram, markdown_languages)
-            .context("create file parser")?;
-
-        let tests = parser.find_and_parse(
-            "test",
-            &self.paths.iter().map(|p| p as &Path).collect::<Vec<_>>(),
-            self.global.cram_compat,
-        )?;
+        let markdown_languages =
+            markdown_languages(&self.markdown_languages, project_config.as_ref());
+        let markdown_language_refs = &markdown_languages.iter().map(|s| &**s).collect::<Vec<_>>();
+        let parser = FileParser::new(
+            &self.match_markdown,
+            &self.match_cram,
+            markdown_language_refs,
+        )
+        .context("create file parser")?
+        .with_format(self.format)
+        .with_template_vars(self.global.to_document_config().vars);
+
+        let tests = parser.find_and_parse("test", &paths, self.global.cram_compat)?;
+        assure_not_recursive(tests.iter().map(|test| test.path.as_path()))?;
+        let (mut fixtures, tests) = DirectoryFixtures::extract(tests);
 
         if tests.is_empty() {
             println!("👋 No test documents found in {:?}. Stopping.", &self.paths);
@@ -141,16 +196,22 @@ impl Args {
 
         let document_config = self.to_document_config();
         let testcase_config = self.to_testcase_config();
+        let undo_directory = Path::new(UNDO_DIRECTORY);
+        let mut journal = UpdateJournal::load(undo_directory)?;
 
         let pw = ProgressWriter::try_new(
             tests.len() as u64,
-            get_log_level() <= tracing::Level::WARN,
+            !self.hide_progress && get_log_level() <= tracing::Level::WARN,
             self.global.no_color || !console::colors_enabled(),
         )?;
 
         // iterate each test file
         pw.println(format!("🔎 Found {} test document(s)", tests.len()));
         let (mut count_updated, mut count_unchanged, mut count_skipped) = (0, 0, 0);
+
+        // set up directories once, before any of their documents run
+        fixtures.set_up(&pw, &self.global)?;
+        let mut shared_work_directory = SharedWorkDirectory::default();
         for mut test in tests {
             pw.inc(1);
             pw.set_message(format!(
@@ -159,14 +220,26 @@ impl Args {
             ));
 
             let config = test.config.with_overrides_from(&document_config);
-            let shell_path = canonical_shell(config.shell.as_ref().map(|p| p as &Path))?;
+            let shell_path = canonical_shell(config.shell.as_ref().and_then(Shell::path))?;
 
-            let mut test_environment = TestEnvironment::new(
+            let mut test_environment = shared_work_directory.environment(
                 &shell_path,
+                &config.get_workdir(),
                 self.global.work_directory.as_deref(),
                 self.global.keep_temporary_directories,
             )?;
 
+            // documents in directories whose setup failed cannot run
+            if let Some(directory) = fixtures.failed_setup(&test.path) {
+                count_skipped += 1;
+                pw.println(format!(
+                    "⏩ {}: skipped, because setup of {} failed",
+                    style(test.path.to_string_lossy()).blue(),
+                    directory.display(),
+                ));
+                continue;
+            }
+
             // must have test-cases to continue
             if test.testcases.is_empty() {
                 count_skipped += 1;
@@ -187,10 +260,36 @@ impl Args {
                 continue;
             }
 
+            // updating the expectations of one locale would overwrite those of the others
+            if !config.locales.is_empty() {
+                count_skipped += 1;
+                pw.println(format!(
+                    "⏩ {}: skipped, because 'locales' are currently not supported in update",
+                    style(test.path.to_string_lossy()).blue()
+                ));
+                continue;
+            }
+
+            // the expectations are in the template, not in the rendered document
+            if config.template.is_some() {
+                count_skipped += 1;
+                pw.println(format!(
+                    "⏩ {}: skipped, because 'template' is currently not supported in update",
+                    style(test.path.to_string_lossy()).blue()
+                ));
+                continue;
+            }
+
             // setup test file environment ..
             let cram_compat = test.parser_type == ParserType::Cram;
-            let (test_work_directory, env_vars) =
+            let (test_work_directory, test_env_vars) =
                 test_environment.init_test_file(&test.path, cram_compat)?;
+            stage_fixtures(&test.path, &test_work_directory, &config)?;
+            let mut env_vars = fixtures
+                .environment(&test.path)
+                .into_iter()
+                .collect::<Vec<_>>();
+            env_vars.extend(test_env_vars);
 
             // extract testcases and update with config from parameters
             let env_vars =
@@ -203,12 +302,13 @@ impl Args {
                         .config
                         .with_overrides_from(&testcase_config)
                         .with_environment(&env_vars);
+                    apply_allow_recursion(&mut testcase.config);
                     testcase as &TestCase
                 })
                 .collect::<Vec<_>>();
 
             // get the appropriate or requested executor
-            let executor = make_executor(&test_environment.shell, cram_compat)?;
+            let executor = make_executor(&test_environment.shell, cram_compat, &config)?;
 
             // execute the tests to use the updated result to update the test file
             let execution_result = executor.execute_all(
@@ -218,6 +318,7 @@ impl Args {
                     .temp_directory(test_environment.tmp_directory.as_path_buf())
                     .file(test.path.clone())
                     .config(test.config.with_overrides_from(&document_config))
+                    .notifier(pw.notifier(&test.path.to_string_lossy()))
                     .build()
                     .context("failed to build execution context")?,
             );
@@ -244,11 +345,11 @@ impl Args {
 
                 // test execution succeeded
                 Ok(outputs) => {
-                    let mut outcomes = vec![];
+                    let (mut outcomes, mut variables) = (vec![], Variables::new());
 
                     // take test execution output, run validation and store all outcomes ...
                     for (testcase, output) in test.testcases.iter().zip(outputs.iter()) {
-                        let result = testcase.validate(output);
+                        let result = testcase.validate_with_variables(output, &mut variables);
                         let mut testcase = testcase.to_owned();
                         testcase.config = testcase.config.without_environment(&env_vars);
                         outcomes.push(Outcome {
@@ -265,13 +366,70 @@ impl Args {
                         debug_testcases(&test.testcases, &test.path, &outputs);
                     }
 
+                    // .. and write the output that golden files did not match to them
+                    let document_directory = test.path.parent().unwrap_or(Path::new(""));
+                    for (path, contents) in outcomes.iter().flat_map(Outcome::golden_file_updates) {
+                        let output_path = self.updated_path(&document_directory.join(path));
+                        if !self.assume_yes && output_path.exists() {
+                            let confirmed = pw.suspend(|| {
+                                confirm(
+                                    &format!(
+                                        "Overwrite existing golden file {}?",
+                                        style(output_path.to_string_lossy()).blue()
+                                    ),
+                                    false,
+                                    self.global.no_color,
+                                )
+                            })?;
+                            if !confirmed {
+                                continue;
+                            }
+                        }
+                        journal.record(&output_path)?;
+                        journal.save(undo_directory)?;
+                        fs::write(&output_path, &contents)
+                            .with_context(|| format!("write golden file {:?}", output_path))?;
+                        pw.println(format!(
+                            "✍️ {}: golden file written to {}",
+                            style(test.path.to_string_lossy()).green(),
+                            style(output_path.to_string_lossy()).blue()
+                        ));
+                    }
+
+                    // .. and the directories that golden directories did not match to them
+                    for (tree, produced) in outcomes.iter().flat_map(Outcome::golden_tree_updates) {
+                        let output_path = self.updated_path(&document_directory.join(&tree.golden));
+                        if !self.assume_yes && output_path.exists() {
+                            let confirmed = pw.suspend(|| {
+                                confirm(
+                                    &format!(
+                                        "Overwrite existing golden directory {}?",
+                                        style(output_path.to_string_lossy()).blue()
+                                    ),
+                                    false,
+                                    self.global.no_color,
+                                )
+                            })?;
+                            if !confirmed {
+                                continue;
+                            }
+                        }
+                        write_golden_tree(&output_path, tree, produced, &mut journal)?;
+                        journal.save(undo_directory)?;
+                        pw.println(format!(
+                            "✍️ {}: golden directory written to {}",
+                            style(test.path.to_string_lossy()).green(),
+                            style(output_path.to_string_lossy()).blue()
+                        ));
+                    }
+
                     // .. and create an updated content (either from actual update or conversion)
                     let outcomes = &outcomes.iter().collect::<Vec<_>>();
                     let is_conversion = self.convert.is_some_and(|c| c != test.parser_type);
                     let (updated, output_type) = if is_conversion {
-                        self.convert_test(&test, outcomes)
+                        self.convert_test(&test, outcomes, &markdown_languages)
                     } else {
-                        self.update_test(&test, outcomes)
+                        self.update_test(&test, outcomes, markdown_language_refs)
                     }?;
 
                     // .. without changes -> next plz
@@ -295,15 +453,8 @@ impl Args {
                             .map_or(&test.path as &Path, Path::new)
                             .to_path_buf();
                         stripped_path.with_extension(output_type.file_extension())
-                    } else if self.replace {
-                        test.path.clone()
                     } else {
-                        let mut extension = vec![self.output_suffix.clone()];
-                        if let Some(ext) = test.path.extension() {
-                            extension.push(ext.to_string_lossy().to_string())
-                        }
-                        extension.reverse();
-                        test.path.clone().with_extension(extension.join(""))
+                        self.updated_path(&test.path)
                     };
 
                     // always ask, in case the file exists
@@ -332,6 +483,8 @@ impl Args {
                     }
 
                     count_updated += 1;
+                    journal.record(&output_path)?;
+                    journal.save(undo_directory)?;
                     fs::write(&output_path, &updated).with_context(|| {
                         format!("overwrite existing document in {:?}", test.path)
                     })?;
@@ -350,6 +503,16 @@ impl Args {
                 }
             }
         }
+
+        if let Some(directory) = shared_work_directory.finish() {
+            pw.println(format!(
+                "📁 kept shared work directory {}",
+                directory.display()
+            ));
+        }
+
+        // tear down directories once, after all their documents ran
+        fixtures.tear_down(&pw, &self.global)?;
         pw.println("");
         pw.finish_and_clear();
 
@@ -358,30 +521,68 @@ impl Args {
         Ok(())
     }
 
+    /// Returns the path that the updated contents of the file in the given
+    /// path are written to: the path itself, if files are replaced, or
+    /// otherwise the path with the output suffix
+    fn updated_path(&self, path: &Path) -> PathBuf {
+        if self.replace {
+            return path.to_path_buf();
+        }
+        let mut extension = vec![self.output_suffix.clone()];
+        if let Some(ext) = path.extension() {
+            extension.push(ext.to_string_lossy().to_string())
+        }
+        extension.reverse();
+        path.with_extension(extension.join(""))
+    }
+
+    fn undo_updates(&self, paths: &[&Path]) -> Result<()> {
+        let undo_directory = Path::new(UNDO_DIRECTORY);
+        let mut journal = UpdateJournal::load(undo_directory)?;
+        let restored = journal.undo(paths)?;
+        if restored.is_empty() {
+            println!("👋 No updated documents to restore found. Stopping.");
+            return Ok(());
+        }
+        journal.save(undo_directory)?;
+
+        let current_directory = std::env::current_dir().context("get current directory")?;
+        let mut output = vec![];
+        for (path, deleted) in &restored {
+            let path = path.strip_prefix(&current_directory).unwrap_or(path);
+            output.push(if *deleted {
+                format!(
+                    "🗑️ {}: removed document, which did not exist before the update",
+                    style(path.to_string_lossy()).green()
+                )
+            } else {
+                format!(
+                    "↩️ {}: restored document contents from before the update",
+                    style(path.to_string_lossy()).green()
+                )
+            });
+        }
+        output.push(format!(
+            "{}: {} document(s) restored",
+            style("Result").underlined(),
+            restored.len()
+        ));
+
+        let mut output = output.join("\n");
+        if self.global.no_color || !stdout().is_terminal() {
+            output = strip_colors(&output)?;
+        }
+        println!("{}", output);
+        Ok(())
+    }
+
     fn update_test(
         &self,
         test: &ParsedTestFile,
         outcomes: &[&Outcome],
+        markdown_languages: &[&str],
     ) -> Result<(String, ParserType)> {
-        let generator: Box<dyn UpdateGenerator> = match test.parser_type {
-            ParserType::Markdown => Box::new(MarkdownUpdateGenerator::new(
-                &self
-                    .markdown_languages
-                    .iter()
-                    .map(|s| s as &str)
-                    .collect::<Vec<_>>(),
-            )),
-            ParserType::Cram => Box::<CramUpdateGenerator>::default(),
-        };
-
-        let generated = generator
-            .generate_update(&test.content, outcomes)
-            .with_context(|| {
-                format!(
-                    "generating update for testcases in document {:?}",
-                    test.path
-                )
-            })?;
+        let generated = generate_update(test, outcomes, markdown_languages)?;
         Ok((generated, test.parser_type))
     }
 
@@ -389,12 +590,13 @@ impl Args {
         &self,
         test: &ParsedTestFile,
         outcomes: &[&Outcome],
+        markdown_languages: &[String],
     ) -> Result<(String, ParserType)> {
         let (generator, parser_type): (Box<dyn TestCaseGenerator>, ParserType) =
             match test.parser_type {
                 ParserType::Markdown => (Box::<CramTestCaseGenerator>::default(), ParserType::Cram),
                 ParserType::Cram => (
-                    Box::new(MarkdownTestCaseGenerator::new(&self.markdown_languages[0])),
+                    Box::new(MarkdownTestCaseGenerator::new(&markdown_languages[0])),
                     ParserType::Markdown,
                 ),
             };
@@ -410,11 +612,15 @@ impl Args {
     }
 
     fn print_changes(&self, outcomes: &[&Outcome]) {
+        if self.quiet {
+            return;
+        }
         let color_renderer = PrettyColorRenderer {
             max_surrounding_lines: DEFAULT_SURROUNDING_LINES,
             absolute_line_numbers: self.absolute_line_numbers,
             summarize: false,
             max_multiline_matched_lines: self.max_multiline_matched_lines,
+            ..Default::default()
         };
         let diff: Box<dyn Renderer> = if self.global.no_color {
             Box::new(PrettyMonochromeRenderer::new(color_renderer))
@@ -447,6 +653,9 @@ impl Args {
     }
 
     fn print_summary(&self, updated: usize, skipped: usize, unchanged: usize) -> Result<()> {
+        if self.quiet {
+            return Ok(());
+        }
         let mut summary = self.render_summary(updated, skipped, unchanged);
         if self.global.no_color || !stdout().is_terminal() {
             summary = strip_colors(&summary)?;
@@ -463,3 +672,68 @@ impl Args {
         self.global.to_testcase_config()
     }
 }
+
+/// Returns the contents of the test document with the expectations of its
+/// testcases updated from the given outcomes, one for each testcase
+pub(super) fn generate_update(
+    test: &ParsedTestFile,
+    outcomes: &[&Outcome],
+    markdown_languages: &[&str],
+) -> Result<String> {
+    let generator: Box<dyn UpdateGenerator> = match test.parser_type {
+        ParserType::Markdown => Box::new(MarkdownUpdateGenerator::new(markdown_languages)),
+        ParserType::Cram => Box::<CramUpdateGenerator>::default(),
+    };
+    generator
+        .generate_update(&test.content, outcomes)
+        .with_context(|| {
+            format!(
+                "generating update for testcases in document {:?}",
+                test.path
+            )
+        })
+}
+
+/// Makes the golden directory at the given path contain the same (not
+/// ignored) files and directories as the produced directory. Files that are
+/// written or removed are recorded in the journal first.
+fn write_golden_tree(
+    path: &Path,
+    tree: &TreeAssertion,
+    produced: &DirectoryTree,
+    journal: &mut UpdateJournal,
+) -> Result<()> {
+    let current = match tree.read(path) {
+        ProducedFile::Tree(current) => current,
+        _ => DirectoryTree::new(),
+    };
+    fs::create_dir_all(path).with_context(|| format!("create golden directory {:?}", path))?;
+    for (entry, state) in directory_tree_changes(&current, produced) {
+        let entry_path = path.join(&entry);
+        if let Some(ProducedFile::File(_)) = current.get(&entry) {
+            journal.record(&entry_path)?;
+            fs::remove_file(&entry_path)
+                .with_context(|| format!("remove golden file {:?}", entry_path))?;
+        }
+        match state {
+            ProducedFile::Directory => fs::create_dir_all(&entry_path)
+                .with_context(|| format!("create golden directory {:?}", entry_path))?,
+            ProducedFile::File(contents) => {
+                if entry_path.is_dir() {
+                    fs::remove_dir_all(&entry_path)
+                        .with_context(|| format!("remove golden directory {:?}", entry_path))?;
+                }
+                journal.record(&entry_path)?;
+                fs::write(&entry_path, contents)
+                    .with_context(|| format!("write golden file {:?}", entry_path))?;
+            }
+            // directories that still contain ignored files are kept
+            _ if entry_path.is_dir() && fs::read_dir(&entry_path)?.next().is_none() => {
+                fs::remove_dir(&entry_path)
+                    .with_context(|| format!("remove golden directory {:?}", entry_path))?
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
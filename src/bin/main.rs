@@ -13,7 +13,9 @@ mod utils;
 use std::env;
 use std::process::ExitCode;
 
+use clap::CommandFactory;
 use clap::Parser;
+use clap::error::ErrorKind;
 use commands::root::Commands;
 use commands::root::GlobalParameters;
 use commands::test::ValidationFailedError;
@@ -22,10 +24,24 @@ use tracing::error;
 include!(concat!(env!("OUT_DIR"), "/version.rs"));
 
 #[derive(Debug, Parser)]
-#[clap(about = "A testing toolkit to scrutinize CLI applications", version = VERSION)]
+#[clap(
+    about = "A testing toolkit to scrutinize CLI applications",
+    version = VERSION,
+    disable_version_flag = true,
+    arg_required_else_help = true
+)]
 struct Args {
     #[clap(subcommand)]
-    commands: Commands,
+    commands: Option<Commands>,
+
+    /// Print version
+    #[clap(long, short = 'V')]
+    version: bool,
+
+    /// With `--version`: print the version and the capabilities of this build
+    /// as JSON, as `doctor --json` does
+    #[clap(long, requires = "version")]
+    json: bool,
 
     #[clap(flatten)]
     global: GlobalParameters,
@@ -35,16 +51,28 @@ pub fn main() -> ExitCode {
     // init_logging();
     let app = Args::parse();
 
-    #[cfg(feature = "logging")]
     if let Err(err) = app.global.init_logging() {
-        panic!("Failed to initialize logging: {:?}", err);
+        eprintln!("Error: {:?}", err);
+        return 1.into();
     }
 
-    if let Err(err) = app.commands.run() {
+    let result = match app.commands {
+        _ if app.version => print_version(app.json),
+        Some(commands) => commands.run(),
+        None => Args::command()
+            .error(ErrorKind::MissingSubcommand, "a subcommand is required")
+            .exit(),
+    };
+    if let Err(err) = result {
         match err.downcast_ref::<ValidationFailedError>() {
             Some(_) => 50.into(),
             None => {
-                error!("Error: {:?}", err);
+                // without logging, errors would otherwise go unreported
+                if cfg!(feature = "logging") {
+                    error!("Error: {:?}", err);
+                } else {
+                    eprintln!("Error: {:?}", err);
+                }
                 1.into()
             }
         }
@@ -52,3 +80,13 @@ pub fn main() -> ExitCode {
         ExitCode::SUCCESS
     }
 }
+
+/// Prints the version, or with `json` also the capabilities of this build
+fn print_version(json: bool) -> anyhow::Result<()> {
+    if json {
+        commands::doctor::print_capabilities(None, true)
+    } else {
+        println!("scrut {VERSION}");
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::fs;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use globset::GlobBuilder;
+use globset::GlobSet;
+use globset::GlobSetBuilder;
+
+/// Directory, relative to the current directory, into which `scrut test`
+/// collects the artifacts of failed test documents, unless `--artifacts-dir`
+/// is provided
+pub(crate) const DEFAULT_ARTIFACTS_DIRECTORY: &str = ".scrut/artifacts";
+
+/// The directory of a run, into which the files that match the `artifacts`
+/// patterns of failed test documents are copied, so that they outlive the
+/// work directory (e.g. to be uploaded by CI)
+pub(crate) struct ArtifactsDirectory {
+    directory: PathBuf,
+}
+
+impl ArtifactsDirectory {
+    pub fn new(directory: &Path) -> Self {
+        Self {
+            directory: directory.to_path_buf(),
+        }
+    }
+
+    /// Copies all files in the work directory, whose path relative to the
+    /// work directory matches any of the glob patterns, into a directory of
+    /// the test document within the artifacts directory. Returns the paths of
+    /// the copies.
+    pub fn collect(
+        &self,
+        document: &Path,
+        work_directory: &Path,
+        patterns: &[String],
+    ) -> Result<Vec<PathBuf>> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            builder.add(
+                GlobBuilder::new(pattern)
+                    .literal_separator(true)
+                    .build()
+                    .with_context(|| format!("invalid artifacts pattern {pattern:?}"))?,
+            );
+        }
+        let matcher = builder.build().context("build artifacts patterns")?;
+
+        let target = self.directory.join(document_directory(document));
+        let mut artifacts = vec![];
+        for path in matching_files(work_directory, Path::new(""), &matcher)? {
+            let artifact = target.join(&path);
+            if let Some(parent) = artifact.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("create artifacts directory {}", parent.display()))?;
+            }
+            fs::copy(work_directory.join(&path), &artifact)
+                .with_context(|| format!("copy artifact {}", path.display()))?;
+            artifacts.push(artifact);
+        }
+        Ok(artifacts)
+    }
+}
+
+/// Returns the directory, relative to the artifacts directory, that the
+/// artifacts of the test document are collected in, which mirrors the path of
+/// the document without any root or parent components
+fn document_directory(document: &Path) -> PathBuf {
+    document
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
+/// Returns the paths, relative to the work directory, of all files in the
+/// given sub directory that match. Symbolic links to directories are not
+/// followed.
+fn matching_files(
+    work_directory: &Path,
+    sub_directory: &Path,
+    matcher: &GlobSet,
+) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let directory = work_directory.join(sub_directory);
+    let entries = fs::read_dir(&directory)
+        .with_context(|| format!("list work directory {}", directory.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let path = sub_directory.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            files.extend(matching_files(work_directory, &path, matcher)?);
+        } else if matcher.is_match(&path) && work_directory.join(&path).is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    use tempfile::TempDir;
+
+    use super::ArtifactsDirectory;
+
+    #[test]
+    fn test_collect_copies_matching_files() {
+        let work_directory = TempDir::new().expect("create work directory");
+        for path in ["logs/app.log", "logs/app.txt", "core.123", "sub/core.456"] {
+            let path = work_directory.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).expect("create directory");
+            fs::write(&path, path.file_name().unwrap().as_encoded_bytes()).expect("write file");
+        }
+        let artifacts_directory = TempDir::new().expect("create artifacts directory");
+
+        let artifacts = ArtifactsDirectory::new(artifacts_directory.path())
+            .collect(
+                Path::new("../tests/test.md"),
+                work_directory.path(),
+                &["logs/*.log".into(), "core*".into()],
+            )
+            .expect("collect artifacts");
+
+        let document_directory = artifacts_directory.path().join("tests/test.md");
+        assert_eq!(
+            vec![
+                document_directory.join("core.123"),
+                document_directory.join("logs/app.log"),
+            ],
+            artifacts
+        );
+        assert_eq!(
+            "app.log",
+            fs::read_to_string(&artifacts[1]).expect("read artifact")
+        );
+    }
+
+    #[test]
+    fn test_collect_rejects_invalid_pattern() {
+        let work_directory = TempDir::new().expect("create work directory");
+        let result = ArtifactsDirectory::new(&PathBuf::from("artifacts")).collect(
+            Path::new("test.md"),
+            work_directory.path(),
+            &["logs/[".into()],
+        );
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,212 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Serialize;
+use serde::Serializer;
+
+/// Wall time statistics of the measured executions of a testcase
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct Statistics {
+    #[serde(rename = "mean_ms", serialize_with = "serialize_millis")]
+    pub(crate) mean: Duration,
+    #[serde(rename = "median_ms", serialize_with = "serialize_millis")]
+    pub(crate) median: Duration,
+    #[serde(rename = "p95_ms", serialize_with = "serialize_millis")]
+    pub(crate) p95: Duration,
+    #[serde(rename = "min_ms", serialize_with = "serialize_millis")]
+    pub(crate) min: Duration,
+    #[serde(rename = "max_ms", serialize_with = "serialize_millis")]
+    pub(crate) max: Duration,
+}
+
+impl Statistics {
+    /// Computes the statistics of the given wall times, if any. The median of
+    /// an even amount of wall times is the mean of both middle values, the
+    /// 95th percentile uses the nearest rank.
+    pub(crate) fn from_durations(durations: &[Duration]) -> Option<Self> {
+        if durations.is_empty() {
+            return None;
+        }
+        let mut sorted = durations.to_vec();
+        sorted.sort();
+        let count = sorted.len();
+        let median = if count % 2 == 0 {
+            (sorted[count / 2 - 1] + sorted[count / 2]) / 2
+        } else {
+            sorted[count / 2]
+        };
+        let p95_rank = (count * 95).div_ceil(100).max(1);
+        Some(Self {
+            mean: sorted.iter().sum::<Duration>() / count as u32,
+            median,
+            p95: sorted[p95_rank - 1],
+            min: sorted[0],
+            max: sorted[count - 1],
+        })
+    }
+}
+
+/// Benchmark of a single testcase
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct TestCaseBenchmark {
+    pub(crate) location: String,
+    pub(crate) line_number: usize,
+    pub(crate) title: String,
+    pub(crate) shell_expression: String,
+
+    /// Amount of measured executions, which excludes warmup executions and
+    /// executions that did not reach the testcase
+    pub(crate) iterations: usize,
+
+    /// Statistics of the measured executions, if there are any
+    #[serde(flatten)]
+    pub(crate) statistics: Option<Statistics>,
+}
+
+/// Renders the benchmarks as a table per test document
+pub(crate) fn render_benchmarks_pretty(benchmarks: &[TestCaseBenchmark]) -> String {
+    let mut output = String::new();
+    let mut location = None;
+    for benchmark in benchmarks {
+        if location != Some(&benchmark.location) {
+            if location.is_some() {
+                output.push('\n');
+            }
+            location = Some(&benchmark.location);
+            let _ = writeln!(output, "{}", benchmark.location);
+            let _ = writeln!(
+                output,
+                "  {:>6}  {:>10}  {:>10}  {:>10}  {:>5}  TESTCASE",
+                "LINE", "MEAN", "MEDIAN", "P95", "RUNS"
+            );
+        }
+        let name = if benchmark.title.is_empty() {
+            benchmark.shell_expression.lines().next().unwrap_or("")
+        } else {
+            &benchmark.title
+        };
+        let (mean, median, p95) = match benchmark.statistics {
+            Some(ref statistics) => (
+                format_millis(statistics.mean),
+                format_millis(statistics.median),
+                format_millis(statistics.p95),
+            ),
+            None => ("-".into(), "-".into(), "-".into()),
+        };
+        let _ = writeln!(
+            output,
+            "  {:>6}  {:>10}  {:>10}  {:>10}  {:>5}  {}",
+            benchmark.line_number, mean, median, p95, benchmark.iterations, name
+        );
+    }
+    output
+}
+
+/// Renders the benchmarks as a JSON array
+pub(crate) fn render_benchmarks_json(benchmarks: &[TestCaseBenchmark]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(benchmarks)? + "\n")
+}
+
+fn format_millis(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
+
+fn serialize_millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Statistics;
+    use super::TestCaseBenchmark;
+    use super::render_benchmarks_json;
+    use super::render_benchmarks_pretty;
+
+    fn millis(values: &[u64]) -> Vec<Duration> {
+        values.iter().map(|v| Duration::from_millis(*v)).collect()
+    }
+
+    #[test]
+    fn test_statistics_from_durations() {
+        assert_eq!(None, Statistics::from_durations(&[]));
+        assert_eq!(
+            Some(Statistics {
+                mean: Duration::from_millis(5),
+                median: Duration::from_millis(5),
+                p95: Duration::from_millis(5),
+                min: Duration::from_millis(5),
+                max: Duration::from_millis(5),
+            }),
+            Statistics::from_durations(&millis(&[5]))
+        );
+        assert_eq!(
+            Some(Statistics {
+                mean: Duration::from_millis(25),
+                median: Duration::from_millis(25),
+                p95: Duration::from_millis(40),
+                min: Duration::from_millis(10),
+                max: Duration::from_millis(40),
+            }),
+            Statistics::from_durations(&millis(&[40, 10, 30, 20]))
+        );
+
+        let durations = millis(&(1..=100).collect::<Vec<_>>());
+        let statistics = Statistics::from_durations(&durations).expect("statistics");
+        assert_eq!(Duration::from_micros(50500), statistics.mean);
+        assert_eq!(Duration::from_micros(50500), statistics.median);
+        assert_eq!(Duration::from_millis(95), statistics.p95);
+    }
+
+    fn benchmarks() -> Vec<TestCaseBenchmark> {
+        vec![
+            TestCaseBenchmark {
+                location: "tests/a.md".into(),
+                line_number: 5,
+                title: "Say hello".into(),
+                shell_expression: "echo hello".into(),
+                iterations: 2,
+                statistics: Statistics::from_durations(&millis(&[10, 20])),
+            },
+            TestCaseBenchmark {
+                location: "tests/a.md".into(),
+                line_number: 12,
+                title: "".into(),
+                shell_expression: "sleep 1\necho done".into(),
+                iterations: 0,
+                statistics: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_render_benchmarks_pretty() {
+        assert_eq!(
+            "tests/a.md
+    LINE        MEAN      MEDIAN         P95   RUNS  TESTCASE
+       5     15.00ms     15.00ms     20.00ms      2  Say hello
+      12           -           -           -      0  sleep 1
+",
+            render_benchmarks_pretty(&benchmarks())
+        );
+    }
+
+    #[test]
+    fn test_render_benchmarks_json() {
+        let rendered = render_benchmarks_json(&benchmarks()).expect("render json");
+        let parsed = serde_json::from_str::<serde_json::Value>(&rendered).expect("parse json");
+        assert_eq!(15.0, parsed[0]["mean_ms"]);
+        assert_eq!(20.0, parsed[0]["p95_ms"]);
+        assert_eq!(2, parsed[0]["iterations"]);
+        assert_eq!(None, parsed[1].get("mean_ms"));
+    }
+}
@@ -13,12 +13,22 @@ use std::path::PathBuf;
 use anyhow::Context;
 use anyhow::Result;
 use anyhow::anyhow;
+use anyhow::bail;
+use scrut::config::DocumentConfig;
+use scrut::config::TestCaseConfig;
+use scrut::config::WorkDirectoryConfig;
+use scrut::config::WorkDirectoryKeep;
+use scrut::config::WorkDirectoryScope;
 use scrut::executors::DEFAULT_SHELL;
 use tempfile::TempDir;
 use tracing::debug;
 
 use super::namer::UniqueNamer;
 
+/// Environment variable that lists the paths of the test documents that are
+/// run by the `scrut` executions, which (transitively) started this one
+pub const INSIDE_RUN_VARIABLE: &str = "SCRUT_INSIDE_RUN";
+
 /// A directory within a test environment
 pub enum EnvironmentDirectory {
     /// A temporary directory, that will be cleaned up after is is not in use anymore
@@ -160,6 +170,24 @@ impl TestEnvironment {
 
         Ok((per_file.build_work_directory()?, per_file.build_env_vars()?))
     }
+
+    /// Keeps the temporary work directory after the environment is dropped,
+    /// instead of removing it. Returns whether the work directory was going
+    /// to be removed, which is not the case for directories that are kept
+    /// anyway or are provided by the user.
+    pub fn keep(&mut self) -> bool {
+        let placeholder = EnvironmentDirectory::UserProvided(self.work_directory.as_path_buf());
+        match std::mem::replace(&mut self.work_directory, placeholder) {
+            EnvironmentDirectory::Ephemeral(temp) => {
+                self.work_directory = EnvironmentDirectory::Kept(temp.keep());
+                true
+            }
+            directory => {
+                self.work_directory = directory;
+                false
+            }
+        }
+    }
 }
 
 impl Debug for TestEnvironment {
@@ -187,6 +215,87 @@ impl Drop for TestEnvironment {
     }
 }
 
+/// The work directory, that all documents of an execution that are configured
+/// with `workdir: {scope: shared}` share. It is created when it is first used
+/// and removed after the execution, unless any document keeps it.
+#[derive(Default)]
+pub struct SharedWorkDirectory {
+    directory: Option<TempDir>,
+    keep: bool,
+}
+
+impl SharedWorkDirectory {
+    /// Returns the test environment for a document with the given work
+    /// directory configuration, which is set up in the shared work directory
+    /// if the document is configured so, and no work directory is provided
+    pub fn environment(
+        &mut self,
+        shell: &Path,
+        workdir: &WorkDirectoryConfig,
+        provided_work_directory: Option<&Path>,
+        keep_temporary_directories: bool,
+    ) -> Result<TestEnvironment> {
+        if workdir.scope != WorkDirectoryScope::Shared || provided_work_directory.is_some() {
+            return TestEnvironment::new(
+                shell,
+                provided_work_directory,
+                keep_temporary_directories,
+            );
+        }
+        let directory = match self.directory {
+            Some(ref directory) => directory,
+            None => self.directory.insert(
+                TempDir::with_prefix("shared.")
+                    .context("create temporary shared work directory")?,
+            ),
+        };
+        self.keep |= keep_temporary_directories;
+        TestEnvironment::new(shell, Some(directory.path()), false)
+    }
+
+    /// Keeps the work directory of the test environment, if the work
+    /// directory configuration requires it, and returns whether it does. The
+    /// shared work directory is instead kept once the execution finished (see
+    /// [`Self::finish`]).
+    pub fn keep(
+        &mut self,
+        test_environment: &mut TestEnvironment,
+        workdir: &WorkDirectoryConfig,
+        failed: bool,
+    ) -> bool {
+        let keep = match workdir.keep {
+            WorkDirectoryKeep::Always => true,
+            WorkDirectoryKeep::OnFailure => failed,
+            WorkDirectoryKeep::Never => false,
+        };
+        if !keep {
+            return false;
+        }
+        let work_directory = test_environment.work_directory.as_path_buf();
+        if self
+            .directory
+            .as_ref()
+            .is_some_and(|directory| directory.path() == work_directory)
+        {
+            self.keep = true;
+            return true;
+        }
+        test_environment.keep()
+    }
+
+    /// Removes the shared work directory, unless it is kept, in which case
+    /// its path is returned
+    pub fn finish(self) -> Option<PathBuf> {
+        let directory = self.directory?;
+        if self.keep {
+            Some(directory.keep())
+        } else {
+            debug!("cleaning up shared work directory {:?}", directory.path());
+            None
+        }
+    }
+}
+
 /// The environment per file, that builds on the [`TestEnvironment`]
 struct TestFileEnvironment<'a> {
     test_environment: &'a mut TestEnvironment,
@@ -237,6 +346,10 @@ impl TestFileEnvironment<'_> {
             ("COLUMNS".to_string(), "80".to_string()),
             ("CDPATH".to_string(), "".to_string()),
             ("GREP_OPTIONS".to_string(), "".to_string()),
+            (
+                INSIDE_RUN_VARIABLE.to_string(),
+                self.build_inside_run()?.to_string_lossy().to_string(),
+            ),
         ];
         if self.cram_compat {
             env_vars.push((
@@ -248,6 +361,71 @@ impl TestFileEnvironment<'_> {
         }
         Ok(env_vars)
     }
+
+    /// Returns the test documents that are run by all `scrut` executions
+    /// that started this one, with the current test document appended
+    fn build_inside_run(&self) -> Result<std::ffi::OsString> {
+        let mut running = running_documents();
+        running.push(self.test_file_directory.join(self.test_file_name));
+        std::env::join_paths(running).context("join paths of running test documents")
+    }
+}
+
+/// Returns the paths of the test documents, that are run by the `scrut`
+/// executions, which (transitively) started this one
+fn running_documents() -> Vec<PathBuf> {
+    std::env::var_os(INSIDE_RUN_VARIABLE)
+        .map(|value| {
+            std::env::split_paths(&value)
+                .filter(|path| !path.as_os_str().is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Fails if any of the given test documents is already run by a `scrut`
+/// execution that (transitively) started this one, as running it again would
+/// recurse until the system runs out of processes
+pub fn assure_not_recursive<'a>(paths: impl IntoIterator<Item = &'a Path>) -> Result<()> {
+    let running = running_documents();
+    if running.is_empty() {
+        return Ok(());
+    }
+    for path in paths {
+        let (directory, file) = split_path_abs(path)?;
+        let path = directory.join(file);
+        if running.contains(&path) {
+            bail!(
+                "refusing to run {}, because a testcase in it runs scrut on it, which would recurse infinitely. Set `allow_recursion: true` on the testcase, if it is intended.",
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Removes the marker of the running test document from the environment of a
+/// testcase, that is configured to `allow_recursion`
+pub fn apply_allow_recursion(config: &mut TestCaseConfig) {
+    if config.allow_recursion == Some(true) {
+        config.environment.remove(INSIDE_RUN_VARIABLE);
+    }
+}
+
+/// Copies the `fixtures` of the test document into its work directory, before
+/// its testcases run
+pub fn stage_fixtures(
+    test_file_path: &Path,
+    test_work_directory: &Path,
+    config: &DocumentConfig,
+) -> Result<()> {
+    if config.fixtures.is_empty() {
+        return Ok(());
+    }
+    let (test_file_directory, _) = split_path_abs(test_file_path)?;
+    config
+        .stage_fixtures(&test_file_directory, test_work_directory)
+        .with_context(|| format!("stage fixtures of {}", test_file_path.display()))
 }
 
 fn create_random_sub_directory(
@@ -454,6 +632,7 @@ mod tests {
             "LANG",
             "LANGUAGE",
             "LC_ALL",
+            "SCRUT_INSIDE_RUN",
             "TESTDIR",
             "TESTFILE",
             "TESTSHELL",
@@ -8,15 +8,53 @@
 use std::path::Path;
 
 use anyhow::Result;
+use anyhow::bail;
+use scrut::config::DocumentConfig;
+use scrut::config::ExecutorBackend;
 use scrut::executors::bash_runner::BashRunner;
 use scrut::executors::bash_script_executor::BashScriptExecutor;
+use scrut::executors::cmd_runner::CmdRunner;
+use scrut::executors::cmd_runner::is_cmd;
+use scrut::executors::docker_executor::DockerExecutor;
 use scrut::executors::executor::Executor;
+use scrut::executors::powershell_runner::PowerShellRunner;
+use scrut::executors::powershell_runner::is_powershell;
 use scrut::executors::stateful_executor::StatefulExecutor;
+use scrut::executors::stateful_executor::StatefulExecutorRunnerGenerator;
 
-pub(crate) fn make_executor(shell: &Path, cram_compat: bool) -> Result<Box<dyn Executor>> {
-    Ok(if cram_compat {
+pub(crate) fn make_executor(
+    shell: &Path,
+    cram_compat: bool,
+    config: &DocumentConfig,
+) -> Result<Box<dyn Executor>> {
+    Ok(if config.executor == Some(ExecutorBackend::Docker) {
+        if cram_compat {
+            bail!("the docker executor is not supported for Cram documents");
+        }
+        if is_powershell(shell) || is_cmd(shell) {
+            bail!("only bash is supported by the docker executor");
+        }
+        let Some(ref docker) = config.docker else {
+            bail!("the docker executor requires the `docker` configuration");
+        };
+        Box::new(DockerExecutor::new(shell, docker))
+    } else if cram_compat {
+        if is_powershell(shell) || is_cmd(shell) {
+            bail!("only bash is supported for Cram documents");
+        }
         Box::new(BashScriptExecutor::new(shell))
     } else {
-        Box::new(StatefulExecutor::new(BashRunner::stateful_generator(shell)))
+        Box::new(StatefulExecutor::new(make_runner_generator(shell)))
     })
 }
+
+/// Returns the generator of the runners, that run testcases with the shell
+pub(crate) fn make_runner_generator(shell: &Path) -> StatefulExecutorRunnerGenerator {
+    if is_powershell(shell) {
+        PowerShellRunner::stateful_generator(shell)
+    } else if is_cmd(shell) {
+        CmdRunner::stateful_generator(shell)
+    } else {
+        BashRunner::stateful_generator(shell)
+    }
+}
@@ -0,0 +1,101 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use serde::Serialize;
+
+/// An optional capability of Scrut, that is enabled at compile time with a
+/// cargo feature of the same name
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Feature {
+    /// Name of the cargo feature
+    pub name: &'static str,
+
+    /// What the feature provides
+    pub description: &'static str,
+
+    /// Whether Scrut was compiled with the feature
+    pub enabled: bool,
+}
+
+/// All optional features that Scrut can be compiled with
+pub(crate) const FEATURES: &[Feature] = &[Feature {
+    name: "logging",
+    description: "Log output on STDERR, controlled with `--log-level`",
+    enabled: cfg!(feature = "logging"),
+}];
+
+/// A capability of Scrut, that is compiled in without a cargo feature, but
+/// that depends on the platform or on programs available at runtime
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Capability {
+    /// Name of the capability
+    pub name: &'static str,
+
+    /// What the capability provides and what it requires
+    pub description: &'static str,
+
+    /// Whether the capability is available on the platform Scrut was
+    /// compiled for
+    pub available: bool,
+}
+
+/// All capabilities that are compiled in without a cargo feature
+pub(crate) const CAPABILITIES: &[Capability] = &[
+    Capability {
+        name: "pty",
+        description: "Run testcases in a pseudo-terminal with `tty` and `interaction` (Linux and macOS only)",
+        available: cfg!(any(target_os = "linux", target_os = "macos")),
+    },
+    Capability {
+        name: "docker",
+        description: "Run test documents in a container with `docker` (requires the `docker` program)",
+        available: true,
+    },
+];
+
+/// Error that is returned if something requires a feature that Scrut was
+/// compiled without
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "{usage} requires the `{feature}` feature, but scrut was compiled without it (rebuild with `--features {feature}`)"
+)]
+pub(crate) struct MissingFeatureError {
+    pub feature: &'static str,
+    pub usage: String,
+}
+
+/// Returns an error naming the feature, if Scrut was compiled without it
+pub(crate) fn require_feature(
+    feature: &'static str,
+    usage: &str,
+) -> Result<(), MissingFeatureError> {
+    match FEATURES.iter().find(|candidate| candidate.name == feature) {
+        Some(candidate) if candidate.enabled => Ok(()),
+        _ => Err(MissingFeatureError {
+            feature,
+            usage: usage.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::require_feature;
+
+    #[test]
+    fn test_require_feature() {
+        assert_eq!(
+            require_feature("logging", "`--log-level`").is_ok(),
+            cfg!(feature = "logging")
+        );
+        let err = require_feature("unknown", "`--unknown`").expect_err("unknown feature");
+        assert_eq!(
+            err.to_string(),
+            "`--unknown` requires the `unknown` feature, but scrut was compiled without it (rebuild with `--features unknown`)"
+        );
+    }
+}
@@ -5,6 +5,7 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
@@ -19,6 +20,7 @@ use globset::GlobMatcher;
 use scrut::config::DocumentConfig;
 use scrut::config::TestCaseConfig;
 use scrut::expectation::ExpectationMaker;
+use scrut::newline::count_line_endings;
 use scrut::newline::replace_crlf;
 use scrut::parsers::cram::CramParser;
 use scrut::parsers::cram::DEFAULT_CRAM_INDENTION;
@@ -30,13 +32,18 @@ use scrut::rules::registry::RuleRegistry;
 use scrut::rules::rule::RuleMaker;
 use scrut::testcase::TestCase;
 use tracing::debug;
+use tracing::warn;
+
+use super::ProjectConfig;
 
 /// A utility to parse files or directories using the correct parser [`Parser`] automatically by
-/// their file name matching either supported Markdown or Cram file names.
+/// their file name matching either supported Markdown or Cram file names, or by their content.
 pub struct FileParser<'a> {
     match_cram: GlobMatcher,
     match_markdown: GlobMatcher,
     markdown_languages: &'a [&'a str],
+    format: Option<ParserType>,
+    template_vars: BTreeMap<String, serde_yaml::Value>,
 }
 
 impl<'a> FileParser<'a> {
@@ -54,9 +61,25 @@ impl<'a> FileParser<'a> {
                 .context("create cram matcher")?
                 .compile_matcher(),
             markdown_languages,
+            format: None,
+            template_vars: BTreeMap::new(),
         })
     }
 
+    /// Builder setter for the format that all documents are parsed in,
+    /// regardless of their file name or content
+    pub fn with_format(mut self, format: Option<ParserType>) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Builder setter for variables of document templates, that take
+    /// precedence over the `vars` of the documents
+    pub fn with_template_vars(mut self, vars: BTreeMap<String, serde_yaml::Value>) -> Self {
+        self.template_vars = vars;
+        self
+    }
+
     /// Parses all provided paths recursively and retuns all found files with test cases
     pub fn find_and_parse(
         &self,
@@ -69,55 +92,110 @@ impl<'a> FileParser<'a> {
             .with_context(|| format!("read contents from {} document path(s)", name))?;
         let mut result = vec![];
         for (test_file_path, test_file_content) in contents {
-            let (parser_type, parser) = self.parser(&test_file_path, cram_compat)?;
-            let (config, testcases) = parser.parse(&test_file_content).with_context(|| {
+            let project_config = ProjectConfig::find(&test_file_path)?.map(|(_, config)| config);
+            let (parser_type, parser) = self.parser(
+                &test_file_path,
+                &test_file_content,
+                cram_compat,
+                project_config.as_ref().map(|config| &config.document),
+            )?;
+            let (config, mut testcases) = parser.parse(&test_file_content).with_context(|| {
                 format!(
                     "Failed to parse {} from {:?} with {} parser",
                     name, &test_file_path, parser_type
                 )
             })?;
+            let document_directory = test_file_path.parent().unwrap_or(Path::new(""));
+            testcases
+                .iter_mut()
+                .for_each(|testcase| testcase.resolve_document_paths(document_directory));
             result.push(ParsedTestFile {
                 path: Path::new(&test_file_path).into(),
                 content: test_file_content,
                 parser_type,
                 testcases,
                 config,
+                locale: None,
             });
         }
 
         Ok(result)
     }
 
-    /// Returns the appropiately configured document [`Parser`]
-    fn parser(&self, path: &Path, cram_compat: bool) -> Result<(ParserType, Box<dyn Parser>)> {
-        if self.match_markdown.is_match(path) {
+    /// Returns the format of the document, which is in order of precedence:
+    /// the explicitly provided format, the `format` in the front-matter of the
+    /// document, the format whose file name pattern matches the path or the
+    /// format that is detected from the content
+    fn format(&self, path: &Path, content: &str) -> Result<ParserType> {
+        self.format
+            .or_else(|| ParserType::from_front_matter(content))
+            .or_else(|| {
+                if self.match_markdown.is_match(path) {
+                    Some(ParserType::Markdown)
+                } else if self.match_cram.is_match(path) {
+                    Some(ParserType::Cram)
+                } else {
+                    None
+                }
+            })
+            .or_else(|| ParserType::detect(content, self.markdown_languages))
+            .ok_or_else(|| {
+                anyhow!(
+                    "cannot determine format of {:?}, use `--format` or set `format` in the front-matter",
+                    path
+                )
+            })
+    }
+
+    /// Returns the appropiately configured document [`Parser`], that applies
+    /// the document configuration of the project (if any) as defaults
+    fn parser(
+        &self,
+        path: &Path,
+        content: &str,
+        cram_compat: bool,
+        project_config: Option<&DocumentConfig>,
+    ) -> Result<(ParserType, Box<dyn Parser>)> {
+        let format = self.format(path, content)?;
+        if format == ParserType::Markdown {
+            let mut document_config = DocumentConfig::default_markdown();
+            if let Some(project_config) = project_config {
+                document_config = document_config.with_overrides_from(project_config);
+            }
             Ok((
                 ParserType::Markdown,
-                Box::new(MarkdownParser::new(
-                    make_expectation_maker(cram_compat),
-                    self.markdown_languages,
-                    if cram_compat {
-                        Some(TestCaseConfig::default_cram())
-                    } else {
-                        None
-                    },
-                )),
+                Box::new(
+                    MarkdownParser::new(
+                        make_expectation_maker(cram_compat),
+                        self.markdown_languages,
+                        if cram_compat {
+                            Some(TestCaseConfig::default_cram())
+                        } else {
+                            None
+                        },
+                    )
+                    .with_base_document_config(document_config)
+                    .with_document_directory(path.parent().unwrap_or(Path::new("")))
+                    .with_template_vars(self.template_vars.clone()),
+                ),
             ))
-        } else if self.match_cram.is_match(path) {
+        } else {
+            let mut document_config = DocumentConfig::default_cram();
+            if let Some(project_config) = project_config {
+                document_config = document_config.with_overrides_from(project_config);
+            }
             Ok((
                 ParserType::Cram,
-                Box::new(CramParser::new(
-                    make_expectation_maker(true),
-                    DEFAULT_CRAM_INDENTION,
-                )),
+                Box::new(
+                    CramParser::new(make_expectation_maker(true), DEFAULT_CRAM_INDENTION)
+                        .with_base_document_config(document_config),
+                ),
             ))
-        } else {
-            Err(anyhow!("no parser found that matches {:?}", path))
         }
     }
 
     /// Returns true if the provided path matches either the Markdown or the cram file pattern
-    fn accept<P: AsRef<Path>>(&self, path: P) -> bool {
+    pub fn accept<P: AsRef<Path>>(&self, path: P) -> bool {
         self.match_markdown.is_match(path.as_ref()) || self.match_cram.is_match(path.as_ref())
     }
 
@@ -136,8 +214,18 @@ impl<'a> FileParser<'a> {
         Ok(result)
     }
 
-    /// Reads test file (or directories, depth-first) at provided path and returns their contents
+    /// Reads test file (or directories, depth-first) at provided path and returns their contents.
+    /// Files within directories must match the file name patterns, while explicitly provided
+    /// files are always read, so that their format can be determined from their content.
     fn read_test_contents<P: AsRef<Path>>(&self, path: P) -> Result<Vec<(PathBuf, String)>> {
+        self.read_test_contents_within(path, true)
+    }
+
+    fn read_test_contents_within<P: AsRef<Path>>(
+        &self,
+        path: P,
+        explicit: bool,
+    ) -> Result<Vec<(PathBuf, String)>> {
         let mut result = vec![];
 
         let attrs = fs::metadata(path.as_ref()).context("read metadata from path")?;
@@ -145,10 +233,10 @@ impl<'a> FileParser<'a> {
             let paths = fs::read_dir(path).context("list tests documents in directory")?;
             for entry in paths {
                 let path = entry?.path();
-                let sub = self.read_test_contents(&path)?;
+                let sub = self.read_test_contents_within(&path, false)?;
                 result.extend(sub);
             }
-        } else if self.accept(path.as_ref()) {
+        } else if explicit || self.accept(path.as_ref()) {
             let name = path.as_ref().into();
             let contents = read_file(path)?;
             result.push((name, contents));
@@ -171,6 +259,13 @@ fn make_expectation_maker(cram_compat: bool) -> Arc<ExpectationMaker> {
 fn read_file<P: AsRef<Path>>(path: P) -> Result<String> {
     debug!(test_file = %path.as_ref().display(), "reading test document");
     let contents = fs::read(&path).context("read contents from test document")?;
+    let (crlf, lf) = count_line_endings(&contents);
+    if crlf > 0 && lf > 0 {
+        warn!(
+            "test document `{}` mixes line endings: {crlf} line(s) end in CRLF and {lf} in LF, which editors and version control may convert inconsistently",
+            path.as_ref().display()
+        );
+    }
     let contents = replace_crlf(&contents[..]);
     String::from_utf8(contents.into()).with_context(|| {
         format!(
@@ -181,19 +276,85 @@ fn read_file<P: AsRef<Path>>(path: P) -> Result<String> {
 }
 
 /// The parsed instances of a test file that [`FileParser`] creates
+#[derive(Clone)]
 pub struct ParsedTestFile {
     pub path: PathBuf,
     pub content: String,
     pub parser_type: ParserType,
     pub testcases: Vec<TestCase>,
     pub config: DocumentConfig,
+
+    /// The locale the document is run in, if it is run once per locale
+    pub locale: Option<String>,
+}
+
+impl ParsedTestFile {
+    /// Expands a document, that configures `locales`, into one document per
+    /// locale, which contain only the testcases that run in that locale.
+    /// Documents without `locales` are returned as-is.
+    pub fn per_locale(self) -> Vec<Self> {
+        if self.config.locales.is_empty() {
+            return vec![self];
+        }
+        self.config
+            .locales
+            .iter()
+            .map(|locale| {
+                let testcases = self
+                    .testcases
+                    .iter()
+                    .filter(|testcase| testcase.config.runs_in_locale(locale))
+                    .map(|testcase| {
+                        let mut testcase = testcase.clone();
+                        testcase.title = if testcase.title.is_empty() {
+                            format!("(locale {locale})")
+                        } else {
+                            format!("{} (locale {locale})", testcase.title)
+                        };
+                        testcase
+                    })
+                    .collect();
+                Self {
+                    testcases,
+                    locale: Some(locale.clone()),
+                    ..self.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the environment variables that select the locale of the
+    /// document, if it is run in a specific locale
+    pub fn locale_environment(&self) -> Vec<(String, String)> {
+        match self.locale {
+            Some(ref locale) => ["LANG", "LANGUAGE", "LC_ALL"]
+                .iter()
+                .map(|key| (key.to_string(), locale.clone()))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Returns the path of the document, followed by the locale, if any
+    pub fn display_name(&self) -> String {
+        match self.locale {
+            Some(ref locale) => format!("{} ({locale})", self.path.display()),
+            None => self.path.display().to_string(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::path::Path;
 
+    use scrut::config::DocumentConfig;
+    use scrut::config::TestCaseConfig;
+    use scrut::parsers::parser::ParserType;
+    use scrut::testcase::TestCase;
+
     use super::FileParser;
+    use super::ParsedTestFile;
 
     #[test]
     fn test_make_parser_generator() {
@@ -210,9 +371,93 @@ mod tests {
             );
 
             let (parser_type, _) = provider
-                .parser(Path::new(file_name), false)
+                .parser(Path::new(file_name), "", false, None)
                 .expect("generate parser");
             assert_eq!(expect, &format!("{}", parser_type));
         }
     }
+
+    #[test]
+    fn test_format_precedence() {
+        let markdown = "```foo\n$ echo\n```\n";
+        let cram = "  $ echo\n";
+        let front_matter = "---\nformat: cram\n---\n```foo\n$ echo\n```\n";
+        let tests = vec![
+            (None, "file.t", markdown, Some(ParserType::Cram)),
+            (None, "file.txt", markdown, Some(ParserType::Markdown)),
+            (None, "file.txt", cram, Some(ParserType::Cram)),
+            (None, "file.md", front_matter, Some(ParserType::Cram)),
+            (
+                Some(ParserType::Markdown),
+                "file.t",
+                front_matter,
+                Some(ParserType::Markdown),
+            ),
+            (None, "file.txt", "no tests\n", None),
+        ];
+
+        for (format, file_name, content, expect) in tests {
+            let provider = FileParser::new("*.md", "*.t", &["foo", "bar"])
+                .expect("create parser provider")
+                .with_format(format);
+            assert_eq!(
+                provider.format(Path::new(file_name), content).ok(),
+                expect,
+                "format of {file_name} with {content:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parsed_test_file_per_locale() {
+        let testcase = |title: &str, locales: &[&str]| TestCase {
+            title: title.into(),
+            config: TestCaseConfig {
+                locales: locales.iter().map(|l| l.to_string()).collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let parsed = ParsedTestFile {
+            path: "test.md".into(),
+            content: String::new(),
+            parser_type: ParserType::Markdown,
+            testcases: vec![testcase("all", &[]), testcase("", &["de_DE.UTF-8"])],
+            config: DocumentConfig {
+                locales: vec!["en_US.UTF-8".into(), "de_DE.UTF-8".into()],
+                ..Default::default()
+            },
+            locale: None,
+        };
+
+        let expanded = parsed.clone().per_locale();
+        assert_eq!(expanded.len(), 2);
+        assert_eq!(expanded[0].locale.as_deref(), Some("en_US.UTF-8"));
+        assert_eq!(expanded[0].display_name(), "test.md (en_US.UTF-8)");
+        assert_eq!(
+            expanded[0]
+                .testcases
+                .iter()
+                .map(|t| t.title.as_str())
+                .collect::<Vec<_>>(),
+            vec!["all (locale en_US.UTF-8)"]
+        );
+        assert_eq!(
+            expanded[1]
+                .testcases
+                .iter()
+                .map(|t| t.title.as_str())
+                .collect::<Vec<_>>(),
+            vec!["all (locale de_DE.UTF-8)", "(locale de_DE.UTF-8)"]
+        );
+
+        let plain = ParsedTestFile {
+            config: DocumentConfig::default(),
+            ..parsed
+        }
+        .per_locale();
+        assert_eq!(plain.len(), 1);
+        assert_eq!(plain[0].locale, None);
+        assert_eq!(plain[0].display_name(), "test.md");
+    }
 }
@@ -0,0 +1,436 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use dialoguer::console::style;
+use scrut::config::OutputStreamControl;
+use scrut::config::Shell;
+use scrut::config::TestCaseConfig;
+use scrut::executors::context::ContextBuilder;
+use scrut::executors::detached::DetachedProcesses;
+use scrut::executors::error::ExecutionError;
+use scrut::outcome::Outcome;
+use scrut::output::ExitStatus;
+use scrut::output::Output;
+use scrut::parsers::parser::ParserType;
+use scrut::testcase::TestCase;
+use scrut::testcase::TestCaseError;
+use tracing::debug;
+
+use super::ParsedTestFile;
+use super::ProgressWriter;
+use super::TestEnvironment;
+use super::canonical_shell;
+use super::kill_detached_processes;
+use super::make_executor;
+use super::stage_fixtures;
+use crate::commands::root::GlobalSharedParameters;
+
+/// File name (without extension) of documents that set up a directory
+pub(crate) const SETUP_DOCUMENT_NAME: &str = "__setup__";
+
+/// File name (without extension) of documents that tear down a directory
+pub(crate) const TEARDOWN_DOCUMENT_NAME: &str = "__teardown__";
+
+/// Environment variables that change with every execution and are therefore
+/// not considered exported by a setup document
+const VOLATILE_ENVIRONMENT_VARIABLES: &[&str] = &["_", "OLDPWD", "PWD", "SHLVL"];
+
+/// Documents named `__setup__` and `__teardown__` (with any supported
+/// extension) that run once before, respectively after, all test documents in
+/// the same directory and its children. Environment variables that are
+/// exported in a setup document are provided to all those test documents.
+#[derive(Default)]
+pub(crate) struct DirectoryFixtures {
+    setups: Vec<ParsedTestFile>,
+    teardowns: Vec<ParsedTestFile>,
+
+    /// Exported environment of each successfully executed setup, by directory
+    environments: Vec<(PathBuf, BTreeMap<String, String>)>,
+
+    /// Directories whose setup failed
+    failed: Vec<PathBuf>,
+
+    /// Environments of executed setups, that are kept until tear down, so
+    /// that their temporary directories can be used in the tests
+    test_environments: Vec<TestEnvironment>,
+
+    /// Detached processes started in setups, that live until tear down (or
+    /// until the fixtures are dropped)
+    detached_processes: DetachedProcesses,
+}
+
+/// Result of executing a single setup or teardown document
+struct FixtureExecution {
+    outcomes: Vec<Outcome>,
+    failed: bool,
+    exported: BTreeMap<String, String>,
+}
+
+impl DirectoryFixtures {
+    /// Returns all setup and teardown documents
+    pub fn documents(&self) -> impl Iterator<Item = &ParsedTestFile> {
+        self.setups.iter().chain(&self.teardowns)
+    }
+
+    /// Separates setup and teardown documents from the test documents
+    pub fn extract(documents: Vec<ParsedTestFile>) -> (Self, Vec<ParsedTestFile>) {
+        let mut fixtures = Self::default();
+        let mut tests = vec![];
+        for document in documents {
+            match document_name(&document.path) {
+                Some(SETUP_DOCUMENT_NAME) => fixtures.setups.push(document),
+                Some(TEARDOWN_DOCUMENT_NAME) => fixtures.teardowns.push(document),
+                _ => tests.push(document),
+            }
+        }
+
+        // parent directories are set up before and torn down after their children
+        fixtures
+            .setups
+            .sort_by_key(|document| document.path.components().count());
+        fixtures
+            .teardowns
+            .sort_by_key(|document| std::cmp::Reverse(document.path.components().count()));
+        (fixtures, tests)
+    }
+
+    /// Executes all setup documents, parents before children, and returns
+    /// the outcomes of their testcases
+    pub fn set_up(
+        &mut self,
+        pw: &ProgressWriter,
+        global: &GlobalSharedParameters,
+    ) -> Result<Vec<Outcome>> {
+        let mut outcomes = vec![];
+        for setup in self.setups.clone() {
+            let directory = fixture_directory(&setup.path);
+            if let Some(failed) = self.failed_setup(&setup.path) {
+                pw.println(format!(
+                    "⏩ {}: skipped, because setup of {} failed",
+                    style(setup.path.to_string_lossy()).blue(),
+                    failed.display(),
+                ));
+                self.failed.push(directory);
+                continue;
+            }
+
+            let environment = self.environment(&setup.path);
+            let execution = self.execute(global, &setup, &environment, true)?;
+            outcomes.extend(execution.outcomes);
+            if execution.failed {
+                pw.println(format!(
+                    "❌ {}: setup failed, skipping all documents in {}",
+                    style(setup.path.to_string_lossy()).red(),
+                    directory.display(),
+                ));
+                self.failed.push(directory);
+            } else {
+                debug!(path = %setup.path.display(), exported = ?&execution.exported, "setup succeeded");
+                self.environments.push((directory, execution.exported));
+            }
+        }
+        Ok(outcomes)
+    }
+
+    /// Executes all teardown documents, children before parents, stops all
+    /// processes that were detached in setups and returns the outcomes of the
+    /// teardown testcases
+    pub fn tear_down(
+        &mut self,
+        pw: &ProgressWriter,
+        global: &GlobalSharedParameters,
+    ) -> Result<Vec<Outcome>> {
+        let mut outcomes = vec![];
+        for teardown in self.teardowns.clone() {
+            if let Some(failed) = self.failed_setup(&teardown.path) {
+                pw.println(format!(
+                    "⏩ {}: skipped, because setup of {} failed",
+                    style(teardown.path.to_string_lossy()).blue(),
+                    failed.display(),
+                ));
+                continue;
+            }
+            let environment = self.environment(&teardown.path);
+            let execution = self.execute(global, &teardown, &environment, false)?;
+            if execution.failed {
+                pw.println(format!(
+                    "❌ {}: teardown failed",
+                    style(teardown.path.to_string_lossy()).red(),
+                ));
+            }
+            outcomes.extend(execution.outcomes);
+        }
+
+        kill_detached_processes(pw, &self.detached_processes)?;
+        self.test_environments.clear();
+        Ok(outcomes)
+    }
+
+    /// Returns the environment variables that the setups of the directory of
+    /// the given document, and of all its parent directories, exported
+    pub fn environment(&self, path: &Path) -> BTreeMap<String, String> {
+        self.environments
+            .iter()
+            .filter(|(directory, _)| path_in_directory(path, directory))
+            .flat_map(|(_, environment)| environment.clone())
+            .collect()
+    }
+
+    /// Returns the directory whose setup failed, if the given document is
+    /// contained within it
+    pub fn failed_setup(&self, path: &Path) -> Option<&Path> {
+        self.failed
+            .iter()
+            .find(|directory| path_in_directory(path, directory))
+            .map(|directory| directory.as_path())
+    }
+
+    fn execute(
+        &mut self,
+        global: &GlobalSharedParameters,
+        document: &ParsedTestFile,
+        environment: &BTreeMap<String, String>,
+        capture_environment: bool,
+    ) -> Result<FixtureExecution> {
+        let config = document
+            .config
+            .with_overrides_from(&global.to_document_config());
+        let shell_path = canonical_shell(config.shell.as_ref().and_then(Shell::path))?;
+        let mut test_environment = TestEnvironment::new(
+            &shell_path,
+            global.work_directory.as_deref(),
+            global.keep_temporary_directories,
+        )?;
+        let cram_compat = document.parser_type == ParserType::Cram || global.cram_compat;
+        let (work_directory, env_vars) =
+            test_environment.init_test_file(&document.path, cram_compat)?;
+        stage_fixtures(&document.path, &work_directory, &config)?;
+
+        // the inherited environment of parent setups must not override the
+        // variables that identify this document
+        let mut variables = environment.clone();
+        variables.extend(env_vars);
+        let variables = variables
+            .iter()
+            .map(|(k, v)| (k as &str, v as &str))
+            .collect::<BTreeMap<_, _>>();
+        let testcase_config = global.to_testcase_config();
+        let mut testcases = document
+            .testcases
+            .iter()
+            .map(|testcase| {
+                let mut testcase = testcase.clone();
+                testcase.config = testcase
+                    .config
+                    .with_overrides_from(&testcase_config)
+                    .with_environment(&variables);
+                testcase
+            })
+            .collect::<Vec<_>>();
+        if capture_environment {
+            let capture = TestCase {
+                shell_expression: "env -0".into(),
+                config: TestCaseConfig {
+                    output_stream: Some(OutputStreamControl::Stdout),
+                    ..Default::default()
+                }
+                .with_environment(&variables),
+                ..Default::default()
+            };
+            testcases.insert(0, capture.clone());
+            testcases.push(capture);
+        }
+
+        let executor = make_executor(&test_environment.shell, cram_compat, &config)?;
+        let context = ContextBuilder::default()
+            .work_directory(work_directory)
+            .temp_directory(test_environment.tmp_directory.as_path_buf())
+            .file(document.path.clone())
+            .config(config)
+            .build()
+            .context("failed to build execution context")?;
+        let result = executor.execute_all(&testcases.iter().collect::<Vec<_>>(), &context);
+
+        // processes that setups detach live until tear down
+        for detached_process in context.detached_processes.take() {
+            self.detached_processes.track(detached_process);
+        }
+        let (mut outputs, mut failed) = match result {
+            Ok(outputs) => (outputs, false),
+            Err(ExecutionError::Skipped(_)) => (vec![], false),
+            Err(ExecutionError::Timeout(_, outputs) | ExecutionError::Failed(_, outputs)) => {
+                (outputs, true)
+            }
+            Err(err) => bail!("failing in {:?}: {}", document.path, err),
+        };
+
+        let mut exported = BTreeMap::new();
+        if capture_environment && !failed && outputs.len() == testcases.len() {
+            let after = outputs.pop().map(|output| parse_environment(&output));
+            let before = parse_environment(&outputs.remove(0));
+            for (key, value) in after.unwrap_or_default() {
+                if !VOLATILE_ENVIRONMENT_VARIABLES.contains(&key.as_str())
+                    && before.get(&key) != Some(&value)
+                {
+                    exported.insert(key, value);
+                }
+            }
+        } else if capture_environment && !outputs.is_empty() {
+            outputs.remove(0);
+        }
+
+        let escaping = global.output_escaping(Some(document.parser_type));
+        let executed = outputs.len();
+        let mut outcomes = vec![];
+        for (testcase, output) in document.testcases.iter().zip(outputs) {
+            if output.exit_code == ExitStatus::Detached {
+                continue;
+            }
+            let result = testcase.validate(&output);
+            failed |= result.is_err();
+            outcomes.push(Outcome {
+                location: Some(document.path.display().to_string()),
+                testcase: testcase.clone(),
+                output,
+                escaping: escaping.clone(),
+                format: document.parser_type,
+                result,
+            });
+        }
+        outcomes.extend(
+            document
+                .testcases
+                .iter()
+                .skip(executed)
+                .map(|testcase| Outcome {
+                    location: Some(document.path.display().to_string()),
+                    testcase: testcase.clone(),
+                    output: ("", "", None).into(),
+                    escaping: escaping.clone(),
+                    format: document.parser_type,
+                    result: Err(TestCaseError::Skipped),
+                }),
+        );
+
+        self.test_environments.push(test_environment);
+        Ok(FixtureExecution {
+            outcomes,
+            failed,
+            exported,
+        })
+    }
+}
+
+/// Returns the file name of the document without extension
+fn document_name(path: &Path) -> Option<&str> {
+    path.file_stem().and_then(|name| name.to_str())
+}
+
+/// Returns the directory that a setup or teardown document applies to
+fn fixture_directory(path: &Path) -> PathBuf {
+    path.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+/// Returns true if the document at the path is located within the directory
+/// or any of its children
+fn path_in_directory(path: &Path, directory: &Path) -> bool {
+    path.parent()
+        .is_some_and(|parent| parent.starts_with(directory))
+}
+
+/// Parses the output of `env -0` into environment variables
+fn parse_environment(output: &Output) -> BTreeMap<String, String> {
+    output
+        .stdout
+        .to_bytes()
+        .split(|byte| *byte == 0)
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            entry
+                .split_once('=')
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use scrut::config::DocumentConfig;
+    use scrut::parsers::parser::ParserType;
+
+    use super::DirectoryFixtures;
+    use super::ParsedTestFile;
+    use super::path_in_directory;
+
+    fn document(path: &str) -> ParsedTestFile {
+        ParsedTestFile {
+            path: path.into(),
+            content: String::new(),
+            parser_type: ParserType::Markdown,
+            testcases: vec![],
+            config: DocumentConfig::default(),
+            locale: None,
+        }
+    }
+
+    #[test]
+    fn test_extract_fixtures() {
+        let (fixtures, tests) = DirectoryFixtures::extract(vec![
+            document("suite/nested/__teardown__.md"),
+            document("suite/nested/__setup__.md"),
+            document("suite/test.md"),
+            document("suite/__setup__.md"),
+            document("suite/__teardown__.md"),
+            document("suite/__setup__.other.md"),
+        ]);
+        let paths = |documents: &[ParsedTestFile]| {
+            documents
+                .iter()
+                .map(|document| document.path.display().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            paths(&fixtures.setups),
+            vec!["suite/__setup__.md", "suite/nested/__setup__.md"]
+        );
+        assert_eq!(
+            paths(&fixtures.teardowns),
+            vec!["suite/nested/__teardown__.md", "suite/__teardown__.md"]
+        );
+        assert_eq!(
+            paths(&tests),
+            vec!["suite/test.md", "suite/__setup__.other.md"]
+        );
+    }
+
+    #[test]
+    fn test_path_in_directory() {
+        let tests = [
+            ("suite/test.md", "suite", true),
+            ("suite/nested/test.md", "suite", true),
+            ("suite/test.md", "suite/nested", false),
+            ("suites/test.md", "suite", false),
+            ("test.md", "", true),
+        ];
+        for (path, directory, expected) in tests {
+            assert_eq!(
+                path_in_directory(Path::new(path), Path::new(directory)),
+                expected,
+                "{path} in {directory}"
+            );
+        }
+    }
+}
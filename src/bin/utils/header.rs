@@ -0,0 +1,94 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+/// Returns whether the test document begins with the header, after its
+/// front-matter (if any). Trailing whitespace of the lines is ignored.
+pub(crate) fn has_header(content: &str, header: &str) -> bool {
+    let (_, body) = split_front_matter(content);
+    let mut lines = body.lines();
+    header_lines(header).all(|expected| {
+        lines
+            .next()
+            .is_some_and(|line| line.trim_end() == expected.trim_end())
+    })
+}
+
+/// Returns the test document with the header, which is inserted after the
+/// front-matter (if any) and followed by an empty line. If the document
+/// begins with the header already, it is normalized to the exact header.
+pub(crate) fn with_header(content: &str, header: &str) -> String {
+    let (front_matter, body) = split_front_matter(content);
+    let rest = if has_header(content, header) {
+        body.split_inclusive('\n')
+            .skip(header_lines(header).count())
+            .collect::<String>()
+    } else {
+        format!("\n{body}")
+    };
+    let header = header_lines(header).collect::<Vec<_>>().join("\n");
+    format!("{front_matter}{header}\n{rest}")
+}
+
+fn header_lines(header: &str) -> std::str::Lines<'_> {
+    header.trim_end_matches(['\r', '\n']).lines()
+}
+
+/// Splits the test document into its front-matter, including the lines that
+/// delimit it, and the remainder
+fn split_front_matter(content: &str) -> (&str, &str) {
+    let mut lines = content.split_inclusive('\n');
+    if lines.next().is_none_or(|line| line.trim_end() != "---") {
+        return ("", content);
+    }
+    let mut offset = 0;
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        offset += line.len();
+        if index > 0 && line.trim_end() == "---" {
+            return content.split_at(offset);
+        }
+    }
+    ("", content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::has_header;
+    use super::with_header;
+
+    const HEADER: &str = "<!--\nCopyright (c) Example\n-->\n";
+
+    #[test]
+    fn test_has_header() {
+        assert!(has_header(
+            "<!--\nCopyright (c) Example\n-->\n\n# Test\n",
+            HEADER
+        ));
+        assert!(has_header(
+            "---\nshell: bash\n---\n<!--  \nCopyright (c) Example\n-->\n",
+            HEADER
+        ));
+        assert!(!has_header("# Test\n", HEADER));
+        assert!(!has_header("<!--\nCopyright (c) Other\n-->\n", HEADER));
+        assert!(!has_header("<!--\nCopyright (c) Example\n", HEADER));
+    }
+
+    #[test]
+    fn test_with_header() {
+        assert_eq!(
+            "<!--\nCopyright (c) Example\n-->\n\n# Test\n",
+            with_header("# Test\n", HEADER)
+        );
+        assert_eq!(
+            "---\nshell: bash\n---\n<!--\nCopyright (c) Example\n-->\n\n# Test\n",
+            with_header("---\nshell: bash\n---\n# Test\n", HEADER)
+        );
+        assert_eq!(
+            "<!--\nCopyright (c) Example\n-->\n# Test\n",
+            with_header("<!--  \nCopyright (c) Example\t\n-->\n# Test\n", HEADER)
+        );
+    }
+}
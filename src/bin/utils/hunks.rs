@@ -0,0 +1,153 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::ops::Range;
+
+use similar::Algorithm;
+use similar::DiffOp;
+use similar::capture_diff_slices;
+
+/// A contiguous change between two versions of the same document, that can
+/// be individually accepted or reverted
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Hunk {
+    /// Index range of the changed lines in the original document
+    pub original: Range<usize>,
+
+    /// Index range of the changed lines in the updated document
+    pub updated: Range<usize>,
+}
+
+/// Changes between two versions of the same document, split into [`Hunk`]s
+pub(crate) struct DocumentChanges<'a> {
+    pub original: Vec<&'a str>,
+    pub updated: Vec<&'a str>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl<'a> DocumentChanges<'a> {
+    /// Computes the line-wise changes between both versions
+    pub fn new(original: &'a str, updated: &'a str) -> Self {
+        let original = original.split_inclusive('\n').collect::<Vec<_>>();
+        let updated = updated.split_inclusive('\n').collect::<Vec<_>>();
+        let mut hunks: Vec<Hunk> = vec![];
+        for op in capture_diff_slices(Algorithm::Myers, &original, &updated) {
+            if matches!(op, DiffOp::Equal { .. }) {
+                continue;
+            }
+            let (original_range, updated_range) = (op.old_range(), op.new_range());
+            match hunks.last_mut() {
+                Some(last) if last.original.end == original_range.start => {
+                    last.original.end = original_range.end;
+                    last.updated.end = updated_range.end;
+                }
+                _ => hunks.push(Hunk {
+                    original: original_range,
+                    updated: updated_range,
+                }),
+            }
+        }
+        Self {
+            original,
+            updated,
+            hunks,
+        }
+    }
+
+    /// Renders the hunk in unified diff format, with the given amount of
+    /// unchanged lines before and after the change. The header line ends in
+    /// the provided context.
+    pub fn render_hunk(&self, hunk: &Hunk, surrounding: usize, context: &str) -> String {
+        let before = hunk.original.start.saturating_sub(surrounding)..hunk.original.start;
+        let after = hunk.original.end..(hunk.original.end + surrounding).min(self.original.len());
+        let mut output = format!(
+            "@@ -{},{} +{},{} @@ {}\n",
+            before.start + 1,
+            before.len() + hunk.original.len() + after.len(),
+            hunk.updated.start - before.len() + 1,
+            before.len() + hunk.updated.len() + after.len(),
+            context,
+        );
+        let mut push = |prefix: char, lines: &[&str]| {
+            for line in lines {
+                output.push(prefix);
+                output.push_str(line);
+                if !line.ends_with('\n') {
+                    output.push_str("\n\\ No newline at end of file\n");
+                }
+            }
+        };
+        push(' ', &self.original[before]);
+        push('-', &self.original[hunk.original.clone()]);
+        push('+', &self.updated[hunk.updated.clone()]);
+        push(' ', &self.original[after]);
+        output
+    }
+
+    /// Returns the document with only the changes of the accepted hunks
+    /// applied, in order of [`Self::hunks`]
+    pub fn apply(&self, accepted: &[bool]) -> String {
+        let mut output = String::new();
+        let mut cursor = 0;
+        for (hunk, accepted) in self.hunks.iter().zip(accepted) {
+            output.extend(self.original[cursor..hunk.original.start].iter().copied());
+            if *accepted {
+                output.extend(self.updated[hunk.updated.clone()].iter().copied());
+            } else {
+                output.extend(self.original[hunk.original.clone()].iter().copied());
+            }
+            cursor = hunk.original.end;
+        }
+        output.extend(self.original[cursor..].iter().copied());
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DocumentChanges;
+    use super::Hunk;
+
+    const ORIGINAL: &str = "a\nb\nc\nd\ne\nf\ng\n";
+    const UPDATED: &str = "a\nB\nc\nd\ne\nf\nG\nh\n";
+
+    #[test]
+    fn test_hunks() {
+        let changes = DocumentChanges::new(ORIGINAL, UPDATED);
+        assert_eq!(
+            changes.hunks,
+            vec![
+                Hunk {
+                    original: 1..2,
+                    updated: 1..2,
+                },
+                Hunk {
+                    original: 6..7,
+                    updated: 6..8,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_hunk() {
+        let changes = DocumentChanges::new(ORIGINAL, UPDATED);
+        assert_eq!(
+            changes.render_hunk(&changes.hunks[1], 2, "the context"),
+            "@@ -5,3 +5,4 @@ the context\n e\n f\n-g\n+G\n+h\n",
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let changes = DocumentChanges::new(ORIGINAL, UPDATED);
+        assert_eq!(changes.apply(&[true, true]), UPDATED);
+        assert_eq!(changes.apply(&[false, false]), ORIGINAL);
+        assert_eq!(changes.apply(&[false, true]), "a\nb\nc\nd\ne\nf\nG\nh\n");
+        assert_eq!(changes.apply(&[true, false]), "a\nB\nc\nd\ne\nf\ng\n");
+    }
+}
@@ -0,0 +1,158 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::io::ErrorKind;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+
+use super::ParsedTestFile;
+
+/// Executes the impact command, which receives the changed source files on
+/// STDIN and prints the test documents that must run on STDOUT, both one
+/// path per line
+pub(crate) fn impacted_paths(
+    shell: &Path,
+    command: &str,
+    changed: &[PathBuf],
+) -> Result<Vec<PathBuf>> {
+    let mut child = Command::new(shell)
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .with_context(|| format!("execute impact command `{}`", command))?;
+
+    let input = changed
+        .iter()
+        .map(|path| format!("{}\n", path.display()))
+        .collect::<String>();
+    let mut stdin = child.stdin.take().context("open STDIN of impact command")?;
+
+    // the command may not read all (or any) of its input, which is fine
+    let writer = std::thread::spawn(move || match stdin.write_all(input.as_bytes()) {
+        Err(err) if err.kind() != ErrorKind::BrokenPipe => Err(err),
+        _ => Ok(()),
+    });
+    let output = child
+        .wait_with_output()
+        .context("wait for impact command")?;
+    writer
+        .join()
+        .map_err(|_| anyhow!("write changed files to impact command"))?
+        .context("write changed files to impact command")?;
+    if !output.status.success() {
+        bail!("impact command `{}` failed with {}", command, output.status);
+    }
+    Ok(String::from_utf8(output.stdout)
+        .context("impact command output is not utf-8 encoded")?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Returns the test documents that are in the selected paths, which are
+/// compared in their canonical form
+pub(crate) fn select_impacted(
+    documents: Vec<ParsedTestFile>,
+    selected: &[PathBuf],
+) -> Vec<ParsedTestFile> {
+    let selected = selected
+        .iter()
+        .map(|path| canonical(path))
+        .collect::<Vec<_>>();
+    documents
+        .into_iter()
+        .filter(|document| selected.contains(&canonical(&document.path)))
+        .collect()
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use scrut::config::DocumentConfig;
+    use scrut::parsers::parser::ParserType;
+
+    use super::ParsedTestFile;
+    use super::impacted_paths;
+    use super::select_impacted;
+
+    fn document(path: &str) -> ParsedTestFile {
+        ParsedTestFile {
+            path: path.into(),
+            content: String::new(),
+            parser_type: ParserType::Markdown,
+            testcases: vec![],
+            config: DocumentConfig::default(),
+            locale: None,
+        }
+    }
+
+    #[test]
+    fn test_select_impacted() {
+        let selected = select_impacted(
+            vec![
+                document("tests/foo.md"),
+                document("tests/bar.md"),
+                document("tests/baz.md"),
+            ],
+            &["tests/baz.md".into(), "tests/foo.md".into()],
+        );
+        assert_eq!(
+            vec![PathBuf::from("tests/foo.md"), PathBuf::from("tests/baz.md")],
+            selected
+                .into_iter()
+                .map(|document| document.path)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_impacted_paths() {
+        let paths = impacted_paths(
+            &PathBuf::from("sh"),
+            "sed -e 's#^src/#tests/#' -e 's#\\.rs$#.md#'",
+            &["src/foo.rs".into(), "src/bar.rs".into()],
+        )
+        .expect("impact command succeeds");
+        assert_eq!(
+            vec![PathBuf::from("tests/foo.md"), PathBuf::from("tests/bar.md")],
+            paths
+        );
+
+        assert_eq!(
+            vec![PathBuf::from("tests/all.md")],
+            impacted_paths(
+                &PathBuf::from("sh"),
+                "echo tests/all.md",
+                &["src/foo.rs".into()]
+            )
+            .expect("impact command that ignores its input succeeds"),
+        );
+        assert!(
+            impacted_paths(&PathBuf::from("sh"), "exit 1", &[]).is_err(),
+            "failing impact command"
+        );
+    }
+}
@@ -0,0 +1,197 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Directory, relative to the current directory, in which `scrut update`
+/// keeps the journal that allows to undo its changes
+pub(crate) const UNDO_DIRECTORY: &str = ".scrut/undo";
+
+/// Name of the journal file within the [`UNDO_DIRECTORY`]
+const JOURNAL_FILE_NAME: &str = "journal.yaml";
+
+/// Records the contents of documents before `scrut update` wrote them, so that
+/// they can be restored later on. Only the last state before an update is kept
+/// per document.
+#[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub(crate) struct UpdateJournal {
+    /// Contents of the written documents, by their absolute path, before they
+    /// were written, or `None` if they did not exist
+    pub documents: BTreeMap<PathBuf, Option<String>>,
+}
+
+impl UpdateJournal {
+    /// Reads the journal from the given undo directory, which is empty if no
+    /// journal was written yet
+    pub fn load(directory: &Path) -> Result<Self> {
+        let path = directory.join(JOURNAL_FILE_NAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("read update journal from {}", path.display()))?;
+        serde_yaml::from_str(&content)
+            .with_context(|| format!("parse update journal from {}", path.display()))
+    }
+
+    /// Writes the journal into the given undo directory, which is created if
+    /// it does not exist. The directory is ignored by git.
+    pub fn save(&self, directory: &Path) -> Result<()> {
+        fs::create_dir_all(directory)
+            .with_context(|| format!("create undo directory {}", directory.display()))?;
+        let gitignore = directory.join(".gitignore");
+        if !gitignore.exists() {
+            fs::write(&gitignore, "# created by scrut\n*\n")
+                .with_context(|| format!("write {}", gitignore.display()))?;
+        }
+        let path = directory.join(JOURNAL_FILE_NAME);
+        let content = serde_yaml::to_string(self).context("render update journal")?;
+        fs::write(&path, content)
+            .with_context(|| format!("write update journal to {}", path.display()))
+    }
+
+    /// Records the current content of the document at the given path, before
+    /// it is written
+    pub fn record(&mut self, path: &Path) -> Result<()> {
+        let path = std::path::absolute(path)
+            .with_context(|| format!("resolve absolute path of {}", path.display()))?;
+        let content = if path.exists() {
+            Some(
+                fs::read_to_string(&path)
+                    .with_context(|| format!("read document {}", path.display()))?,
+            )
+        } else {
+            None
+        };
+        self.documents.insert(path, content);
+        Ok(())
+    }
+
+    /// Restores all recorded documents that are within the given paths (or all
+    /// if no paths are given) and removes them from the journal. Documents that
+    /// did not exist before the update are deleted. Returns the restored paths
+    /// and whether they were deleted.
+    pub fn undo(&mut self, paths: &[&Path]) -> Result<Vec<(PathBuf, bool)>> {
+        let paths = paths
+            .iter()
+            .map(|path| {
+                std::path::absolute(path)
+                    .with_context(|| format!("resolve absolute path of {}", path.display()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let selected = self
+            .documents
+            .keys()
+            .filter(|document| paths.is_empty() || paths.iter().any(|p| document.starts_with(p)))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut restored = vec![];
+        for path in selected {
+            let content = self.documents.remove(&path).flatten();
+            let deleted = match content {
+                Some(content) => {
+                    // the directory of a removed golden file may be gone
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)
+                            .with_context(|| format!("create directory {}", parent.display()))?;
+                    }
+                    fs::write(&path, content)
+                        .with_context(|| format!("restore document {}", path.display()))?;
+                    false
+                }
+                None => {
+                    if path.exists() {
+                        fs::remove_file(&path)
+                            .with_context(|| format!("remove document {}", path.display()))?;
+                    }
+                    true
+                }
+            };
+            restored.push((path, deleted));
+        }
+        Ok(restored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::UpdateJournal;
+
+    #[test]
+    fn test_record_save_load_and_undo() {
+        let root = TempDir::with_prefix("journal.").expect("create temporary directory");
+        let undo_directory = root.path().join("undo");
+        let existing = root.path().join("existing.md");
+        let created = root.path().join("created.md");
+        fs::write(&existing, "original\n").expect("write existing document");
+
+        let mut journal = UpdateJournal::default();
+        journal.record(&existing).expect("record existing");
+        journal.record(&created).expect("record created");
+        journal.save(&undo_directory).expect("save journal");
+        fs::write(&existing, "updated\n").expect("update existing document");
+        fs::write(&created, "created\n").expect("create document");
+
+        let mut journal = UpdateJournal::load(&undo_directory).expect("load journal");
+        assert_eq!(journal.documents.len(), 2);
+        let restored = journal.undo(&[]).expect("undo");
+        assert_eq!(
+            restored,
+            vec![(created.clone(), true), (existing.clone(), false)]
+        );
+        assert!(!created.exists());
+        assert_eq!(
+            fs::read_to_string(&existing).expect("read existing"),
+            "original\n"
+        );
+        assert!(journal.documents.is_empty());
+    }
+
+    #[test]
+    fn test_undo_only_within_paths() {
+        let root = TempDir::with_prefix("journal.").expect("create temporary directory");
+        let (first, second) = (root.path().join("a/test.md"), root.path().join("b/test.md"));
+        for path in [&first, &second] {
+            fs::create_dir_all(path.parent().expect("has parent")).expect("create directory");
+            fs::write(path, "original\n").expect("write document");
+        }
+
+        let mut journal = UpdateJournal::default();
+        journal.record(&first).expect("record first");
+        journal.record(&second).expect("record second");
+        fs::write(&first, "updated\n").expect("update first");
+        fs::write(&second, "updated\n").expect("update second");
+
+        let restored = journal
+            .undo(&[&root.path().join("a")])
+            .expect("undo within path");
+        assert_eq!(restored, vec![(first.clone(), false)]);
+        assert_eq!(
+            fs::read_to_string(&first).expect("read first"),
+            "original\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&second).expect("read second"),
+            "updated\n"
+        );
+        assert_eq!(journal.documents.len(), 1);
+    }
+}
@@ -6,15 +6,24 @@
  */
 
 use anyhow::Result;
+use scrut::executors::detached::DetachedProcesses;
 use scrut::output::DetachedProcess;
 
 use crate::utils::ProgressWriter;
 
-#[cfg(unix)]
-pub(crate) fn kill_detached_process(
+/// Cleans up all tracked detached processes, that were started in executions
+pub(crate) fn kill_detached_processes(
     pw: &ProgressWriter,
-    detached_process: &DetachedProcess,
+    detached_processes: &DetachedProcesses,
 ) -> Result<()> {
+    for detached_process in detached_processes.take() {
+        kill_detached_process(pw, &detached_process)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn kill_detached_process(pw: &ProgressWriter, detached_process: &DetachedProcess) -> Result<()> {
     if detached_process.signal.is_off() {
         pw.println(format!(
             "ℹ️ Cleanup of detached process disabled, ignoring PID {}",
@@ -24,14 +33,10 @@ pub(crate) fn kill_detached_process(
     }
     let signal = detached_process.signal.clone().to_nix()?;
     pw.println(format!(
-        "🗑️ Sending {signal} to detached process with PID {}",
+        "🗑️ Sending {signal} to process group of detached process with PID {}",
         detached_process.pid
     ));
-    let result = nix::sys::signal::kill(
-        nix::unistd::Pid::from_raw(detached_process.pid as i32),
-        signal,
-    );
-    if let Err(err) = result {
+    if let Err(err) = detached_process.kill() {
         pw.println(format!(
             "❌ Failed to kill detached process with PID {}: {}",
             detached_process.pid, err
@@ -41,13 +46,23 @@ pub(crate) fn kill_detached_process(
 }
 
 #[cfg(windows)]
-pub(crate) fn kill_detached_process(
-    pw: &ProgressWriter,
-    detached_process: &DetachedProcess,
-) -> Result<()> {
+fn kill_detached_process(pw: &ProgressWriter, detached_process: &DetachedProcess) -> Result<()> {
+    if detached_process.signal.is_off() {
+        pw.println(format!(
+            "ℹ️ Cleanup of detached process disabled, ignoring PID {}",
+            detached_process.pid
+        ));
+        return Ok(());
+    }
     pw.println(format!(
-        "⚠️ Windows support for reaping detached processes not implemented. Ignoring process with PID {}",
-        detached_process.pid,
+        "🗑️ Terminating job object of detached process with PID {}",
+        detached_process.pid
     ));
+    if let Err(err) = detached_process.kill() {
+        pw.println(format!(
+            "❌ Failed to kill detached process with PID {}: {}",
+            detached_process.pid, err
+        ));
+    }
     Ok(())
 }
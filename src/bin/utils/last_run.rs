@@ -0,0 +1,293 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use scrut::outcome::Outcome;
+use scrut::testcase::TestCaseError;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::ParsedTestFile;
+use super::ProjectConfig;
+
+/// File, relative to the directory of the project configuration (or the
+/// current directory), in which `scrut test` keeps the failed testcases of the
+/// last run, for `scrut test --rerun-failed`
+pub(crate) const LAST_RUN_FILE: &str = ".scrut/last-run.json";
+
+/// The failed testcases of the last run of `scrut test`
+#[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub(crate) struct LastRun {
+    /// Line numbers of the failed testcases, by the absolute path of their
+    /// test document
+    pub failed: BTreeMap<PathBuf, BTreeSet<usize>>,
+}
+
+impl LastRun {
+    /// Returns the file that records the last run of the given test paths,
+    /// which is located in the directory of the first project configuration
+    /// found for any of them, or in the current directory if there is none
+    pub fn find_file(paths: &[&Path]) -> Result<PathBuf> {
+        for path in paths {
+            if let Some(directory) = ProjectConfig::find_file(path)?
+                .as_deref()
+                .and_then(Path::parent)
+            {
+                return Ok(directory.join(LAST_RUN_FILE));
+            }
+        }
+        Ok(PathBuf::from(LAST_RUN_FILE))
+    }
+
+    /// Collects the testcases that failed from the outcomes of a run. Skipped
+    /// testcases are not considered failed.
+    pub fn from_outcomes(outcomes: &[Outcome]) -> Self {
+        let mut failed = BTreeMap::<_, BTreeSet<_>>::new();
+        for outcome in outcomes {
+            let Some(ref location) = outcome.location else {
+                continue;
+            };
+            if matches!(outcome.result, Ok(()) | Err(TestCaseError::Skipped)) {
+                continue;
+            }
+            let path = Path::new(location);
+            failed
+                .entry(std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()))
+                .or_default()
+                .insert(outcome.testcase.line_number);
+        }
+        Self { failed }
+    }
+
+    /// Adds the failed testcases of another run, e.g. of another suite
+    pub fn merge(&mut self, other: Self) {
+        for (path, line_numbers) in other.failed {
+            self.failed.entry(path).or_default().extend(line_numbers);
+        }
+    }
+
+    /// Reads the last run from the given file, or returns `None` if no run
+    /// was recorded yet
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("read last run from {}", path.display()))?;
+        serde_json::from_str(&content)
+            .map(Some)
+            .with_context(|| format!("parse last run from {}", path.display()))
+    }
+
+    /// Writes the last run into the given file, whose directory is created if
+    /// it does not exist. The directory is ignored by git.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(directory) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(directory)
+                .with_context(|| format!("create directory {}", directory.display()))?;
+            let gitignore = directory.join(".gitignore");
+            if !gitignore.exists() {
+                fs::write(&gitignore, "# created by scrut\n*\n")
+                    .with_context(|| format!("write {}", gitignore.display()))?;
+            }
+        }
+        let content = serde_json::to_string_pretty(self).context("render last run")?;
+        fs::write(path, content + "\n")
+            .with_context(|| format!("write last run to {}", path.display()))
+    }
+
+    /// Returns the test documents that had failed testcases in the last run,
+    /// with their testcases up to and including the last failed one. The
+    /// testcases before a failed one are kept, as it may depend on the shell
+    /// state or files they set up.
+    pub fn select_failed(&self, documents: Vec<ParsedTestFile>) -> Vec<ParsedTestFile> {
+        documents
+            .into_iter()
+            .filter_map(|mut document| {
+                let path =
+                    std::path::absolute(&document.path).unwrap_or_else(|_| document.path.clone());
+                let failed = self.failed.get(&path)?;
+                let last_failed = document
+                    .testcases
+                    .iter()
+                    .map(|testcase| testcase.line_number)
+                    .filter(|line_number| failed.contains(line_number))
+                    .max()?;
+                document
+                    .testcases
+                    .retain(|testcase| testcase.line_number <= last_failed);
+                Some(document)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::collections::BTreeSet;
+    use std::path::PathBuf;
+
+    use anyhow::anyhow;
+    use scrut::config::DocumentConfig;
+    use scrut::escaping::Escaper;
+    use scrut::outcome::Outcome;
+    use scrut::parsers::parser::ParserType;
+    use scrut::testcase::Result as TestCaseResult;
+    use scrut::testcase::TestCase;
+    use scrut::testcase::TestCaseError;
+    use tempfile::TempDir;
+
+    use super::LastRun;
+    use super::ParsedTestFile;
+
+    fn testcase(line_number: usize) -> TestCase {
+        TestCase {
+            line_number,
+            ..Default::default()
+        }
+    }
+
+    fn outcome(location: &str, line_number: usize, result: TestCaseResult<()>) -> Outcome {
+        Outcome {
+            location: Some(location.to_string()),
+            output: ("", "", Some(0)).into(),
+            testcase: testcase(line_number),
+            format: ParserType::Markdown,
+            escaping: Escaper::default(),
+            result,
+        }
+    }
+
+    #[test]
+    fn test_from_outcomes() {
+        let root = TempDir::with_prefix("last-run.").expect("create temporary directory");
+        let location = |name: &str| root.path().join(name).display().to_string();
+        let last_run = LastRun::from_outcomes(&[
+            outcome(&location("a.md"), 3, Ok(())),
+            outcome(
+                &location("a.md"),
+                7,
+                Err(TestCaseError::InternalError(anyhow!("failed"))),
+            ),
+            outcome(&location("b.md"), 2, Err(TestCaseError::Skipped)),
+            outcome(&location("c.md"), 5, Err(TestCaseError::Timeout)),
+        ]);
+        assert_eq!(
+            BTreeMap::from([
+                (root.path().join("a.md"), BTreeSet::from([7])),
+                (root.path().join("c.md"), BTreeSet::from([5])),
+            ]),
+            last_run.failed
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut last_run = LastRun {
+            failed: BTreeMap::from([("a.md".into(), BTreeSet::from([3]))]),
+        };
+        last_run.merge(LastRun {
+            failed: BTreeMap::from([
+                ("a.md".into(), BTreeSet::from([7])),
+                ("b.md".into(), BTreeSet::from([2])),
+            ]),
+        });
+        assert_eq!(
+            BTreeMap::from([
+                ("a.md".into(), BTreeSet::from([3, 7])),
+                ("b.md".into(), BTreeSet::from([2])),
+            ]),
+            last_run.failed
+        );
+    }
+
+    #[test]
+    fn test_find_file() {
+        let root = TempDir::with_prefix("last-run.").expect("create temporary directory");
+        let tests = root.path().join("tests");
+        std::fs::create_dir(&tests).expect("create tests directory");
+        assert_eq!(
+            PathBuf::from(".scrut/last-run.json"),
+            LastRun::find_file(&[&tests]).expect("find without project")
+        );
+
+        std::fs::write(root.path().join(".scrut.yaml"), "{}\n").expect("write project config");
+        assert_eq!(
+            root.path().join(".scrut").join("last-run.json"),
+            LastRun::find_file(&[&tests]).expect("find with project")
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let root = TempDir::with_prefix("last-run.").expect("create temporary directory");
+        let path = root.path().join(".scrut").join("last-run.json");
+        assert_eq!(None, LastRun::load(&path).expect("load missing last run"));
+
+        let last_run = LastRun {
+            failed: BTreeMap::from([(root.path().join("a.md"), BTreeSet::from([3, 7]))]),
+        };
+        last_run.save(&path).expect("save last run");
+        assert_eq!(
+            Some(last_run),
+            LastRun::load(&path).expect("load saved last run")
+        );
+        assert!(root.path().join(".scrut").join(".gitignore").exists());
+    }
+
+    #[test]
+    fn test_select_failed() {
+        let root = TempDir::with_prefix("last-run.").expect("create temporary directory");
+        let document = |name: &str, line_numbers: &[usize]| ParsedTestFile {
+            path: root.path().join(name),
+            content: String::new(),
+            parser_type: ParserType::Markdown,
+            testcases: line_numbers.iter().map(|line| testcase(*line)).collect(),
+            config: DocumentConfig::default(),
+            locale: None,
+        };
+        let last_run = LastRun {
+            failed: BTreeMap::from([
+                (root.path().join("a.md"), BTreeSet::from([7])),
+                (root.path().join("b.md"), BTreeSet::from([99])),
+                (root.path().join("d.md"), BTreeSet::from([4, 9])),
+            ]),
+        };
+        let selected = last_run.select_failed(vec![
+            document("a.md", &[3, 7, 11]),
+            document("b.md", &[2]),
+            document("c.md", &[5]),
+            document("d.md", &[1, 4, 9, 12]),
+        ]);
+        assert_eq!(
+            vec![
+                (root.path().join("a.md"), vec![3, 7]),
+                (root.path().join("d.md"), vec![1, 4, 9]),
+            ],
+            selected
+                .into_iter()
+                .map(|document| (
+                    document.path,
+                    document
+                        .testcases
+                        .iter()
+                        .map(|testcase| testcase.line_number)
+                        .collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+}
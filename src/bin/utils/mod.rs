@@ -5,17 +5,49 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+mod artifacts;
+mod benchmark;
 mod debug;
 mod environment;
 mod executorutil;
+mod features;
 mod file_parser;
+mod fixtures;
+mod header;
+mod hunks;
+mod impact;
+mod journal;
 mod kill;
+mod last_run;
 mod namer;
+mod project;
+mod references;
+mod sandbox;
+mod status;
+mod suite_alias;
 mod ui;
+mod vcs;
+mod workspace;
 
+pub(crate) use artifacts::*;
+pub(crate) use benchmark::*;
 pub(crate) use debug::*;
 pub(crate) use environment::*;
 pub(crate) use executorutil::*;
+pub(crate) use features::*;
 pub(crate) use file_parser::*;
+pub(crate) use fixtures::*;
+pub(crate) use header::*;
+pub(crate) use hunks::*;
+pub(crate) use impact::*;
+pub(crate) use journal::*;
 pub(crate) use kill::*;
+pub(crate) use last_run::*;
+pub(crate) use project::*;
+pub(crate) use references::*;
+pub(crate) use sandbox::*;
+pub(crate) use status::*;
+pub(crate) use suite_alias::*;
 pub(crate) use ui::*;
+pub(crate) use vcs::*;
+pub(crate) use workspace::*;
@@ -0,0 +1,215 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use scrut::config::DocumentConfig;
+use scrut::parsers::markdown::DEFAULT_MARKDOWN_LANGUAGES;
+use serde::Deserialize;
+use tracing::debug;
+
+use super::SandboxConfig;
+use super::SuiteAlias;
+use crate::commands::root::ScrutRenderer;
+
+/// Names of the project configuration file, in order of precedence, that
+/// are looked for in each directory when walking up from a test path
+pub(crate) const PROJECT_CONFIG_FILE_NAMES: &[&str] = &[".scrut.yaml", ".scrut.yml"];
+
+/// Project-wide configuration, that is read from the nearest `.scrut.yaml`
+/// file found by walking up the directory tree from a test path.
+///
+/// The document configuration provides the defaults that the front-matter of
+/// each test document is applied on top of. Command line parameters override
+/// both.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub(crate) struct ProjectConfig {
+    /// Defaults for the per-document (and via `defaults` per-testcase) configuration
+    #[serde(flatten)]
+    pub document: DocumentConfig,
+
+    /// Default renderer, if not provided on the command line
+    pub renderer: Option<ScrutRenderer>,
+
+    /// Default Markdown languages, if not provided on the command line
+    pub markdown_languages: Vec<String>,
+
+    /// Directories with golden files and fixtures, that `--check-fixtures`
+    /// reports files of, which no test document references
+    pub fixture_directories: Vec<PathBuf>,
+
+    /// Commands that are allowed or denied, and documents that are trusted,
+    /// when running with `--sandbox`
+    pub sandbox: SandboxConfig,
+
+    /// Named selections of testcases, that are run with `scrut test @<name>`
+    pub suites: BTreeMap<String, SuiteAlias>,
+
+    /// Header that all test documents must begin with, after their
+    /// front-matter, like a copyright or ownership notice (see `scrut lint`)
+    pub header: Option<String>,
+}
+
+impl ProjectConfig {
+    /// Reads the project configuration from the given file. Paths in `append`,
+    /// `prepend`, `extends`, `env_file`, `fixture_directories`, trusted
+    /// paths of the `sandbox` and paths of `suites` are relative to the
+    /// directory the file is located in.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("read project configuration from {}", path.display()))?;
+        let mut config: Self = serde_yaml::from_str(&content)
+            .with_context(|| format!("parse project configuration from {}", path.display()))?;
+        if let Some(directory) = path.parent() {
+            config.document.append = prefix_paths(directory, &config.document.append);
+            config.document.prepend = prefix_paths(directory, &config.document.prepend);
+            config.fixture_directories = prefix_paths(directory, &config.fixture_directories);
+            config.sandbox.trusted = prefix_paths(directory, &config.sandbox.trusted);
+            for suite in config.suites.values_mut() {
+                suite.paths = prefix_paths(directory, &suite.paths);
+            }
+            config.document = config
+                .document
+                .with_extends_resolved(directory)
+                .with_context(|| format!("resolve extends of {}", path.display()))?
+                .with_env_file_loaded(directory)
+                .with_context(|| format!("load env file of {}", path.display()))?;
+        }
+        Ok(config)
+    }
+
+    /// Walks up from the given path (a test document or a directory containing
+    /// test documents) and returns the first project configuration found
+    pub fn find(path: &Path) -> Result<Option<(PathBuf, Self)>> {
+        let Some(path) = Self::find_file(path)? else {
+            return Ok(None);
+        };
+        debug!(path = %path.display(), "using project configuration");
+        let config = Self::load(&path)?;
+        Ok(Some((path, config)))
+    }
+
+    /// Walks up from the given path (a test document or a directory containing
+    /// test documents) and returns the path of the first project configuration
+    /// file found, without reading it
+    pub fn find_file(path: &Path) -> Result<Option<PathBuf>> {
+        let path = std::path::absolute(path)
+            .with_context(|| format!("resolve absolute path of {}", path.display()))?;
+        let start = if path.is_dir() {
+            path.as_path()
+        } else {
+            match path.parent() {
+                Some(parent) => parent,
+                None => return Ok(None),
+            }
+        };
+        for directory in start.ancestors() {
+            for name in PROJECT_CONFIG_FILE_NAMES {
+                let candidate = directory.join(name);
+                if candidate.is_file() {
+                    return Ok(Some(candidate));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the first project configuration that is found for any of the
+    /// given paths, which provides the defaults of command line parameters
+    pub fn find_any(paths: &[&Path]) -> Result<Option<Self>> {
+        for path in paths {
+            if let Some((_, config)) = Self::find(path)? {
+                return Ok(Some(config));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Returns the Markdown languages provided on the command line, falling back
+/// to those of the project configuration and then to the built-in default
+pub(crate) fn markdown_languages(
+    provided: &[String],
+    project_config: Option<&ProjectConfig>,
+) -> Vec<String> {
+    if !provided.is_empty() {
+        return provided.to_vec();
+    }
+    match project_config {
+        Some(config) if !config.markdown_languages.is_empty() => config.markdown_languages.clone(),
+        _ => DEFAULT_MARKDOWN_LANGUAGES
+            .iter()
+            .map(|language| language.to_string())
+            .collect(),
+    }
+}
+
+fn prefix_paths(prefix: &Path, paths: &[PathBuf]) -> Vec<PathBuf> {
+    paths.iter().map(|path| prefix.join(path)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use tempfile::TempDir;
+
+    use super::ProjectConfig;
+    use crate::commands::root::ScrutRenderer;
+
+    #[test]
+    fn test_find_nearest_project_config() {
+        let root = TempDir::with_prefix("project.").expect("create temporary directory");
+        let nested = root.path().join("suite").join("nested");
+        fs::create_dir_all(&nested).expect("create nested directory");
+        fs::write(
+            root.path().join(".scrut.yaml"),
+            "total_timeout: 1m\nrenderer: diff\ndefaults:\n  timeout: 5s\nprepend:\n- setup.md\nfixture_directories:\n- fixtures\nsandbox:\n  trusted:\n  - examples\nsuites:\n  smoke:\n    paths:\n    - tests/smoke/**\n    tags_exclude:\n    - slow\n",
+        )
+        .expect("write project configuration");
+
+        let (path, config) = ProjectConfig::find(&nested.join("test.md"))
+            .expect("find project configuration")
+            .expect("project configuration exists");
+        assert_eq!(path, root.path().join(".scrut.yaml"));
+        assert_eq!(config.document.total_timeout, Some(Duration::from_secs(60)));
+        assert_eq!(
+            config.document.defaults.timeout,
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(config.document.prepend, vec![root.path().join("setup.md")]);
+        assert_eq!(
+            config.fixture_directories,
+            vec![root.path().join("fixtures")]
+        );
+        assert_eq!(config.sandbox.trusted, vec![root.path().join("examples")]);
+        assert_eq!(
+            config.suites["smoke"].paths,
+            vec![root.path().join("tests/smoke/**")]
+        );
+        assert_eq!(
+            config.suites["smoke"].tags_exclude,
+            vec!["slow".to_string()]
+        );
+        assert!(matches!(config.renderer, Some(ScrutRenderer::Diff)));
+
+        fs::write(nested.join(".scrut.yaml"), "shell: zsh\n").expect("write nested configuration");
+        let (path, config) = ProjectConfig::find(&nested)
+            .expect("find project configuration")
+            .expect("project configuration exists");
+        assert_eq!(path, nested.join(".scrut.yaml"));
+        assert_eq!(config.document.shell, Some("zsh".into()));
+        assert_eq!(config.document.total_timeout, None);
+    }
+}
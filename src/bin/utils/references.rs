@@ -0,0 +1,249 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use lazy_static::lazy_static;
+use regex::Regex;
+use scrut::config::TestCaseStdin;
+
+use super::ParsedTestFile;
+
+lazy_static! {
+    /// Matches paths below the directory of the test document in shell
+    /// expressions, e.g. `$TESTDIR/fixtures/input.json`
+    static ref TESTDIR_PATH: Regex =
+        Regex::new(r#"\$\{?TESTDIR\}?"?/([^\s"'`;:|&()<>]+)"#).expect("valid TESTDIR path expression");
+}
+
+/// A file that a testcase of a test document references
+#[derive(Debug, PartialEq)]
+pub(crate) struct FileReference {
+    pub document: PathBuf,
+    pub line_number: usize,
+    pub path: PathBuf,
+}
+
+impl Display for FileReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}",
+            self.document.display(),
+            self.line_number,
+            self.path.display()
+        )
+    }
+}
+
+/// Result of cross-checking the files in the fixture directories with the
+/// files that the test documents reference
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct FixtureReferences {
+    /// Files in the fixture directories that no test document references
+    pub unreferenced: Vec<PathBuf>,
+
+    /// References of test documents to files that do not exist
+    pub missing: Vec<FileReference>,
+}
+
+impl FixtureReferences {
+    /// Checks the references of the given test documents. A file in one of the
+    /// fixture directories counts as referenced, if a test document references
+    /// it (or a directory below the fixture directory containing it) via
+    /// `$TESTDIR`, `prepend`, `append`, `fixtures` or `stdin`, or if any test
+    /// document mentions its file name.
+    pub fn check(tests: &[ParsedTestFile], fixture_directories: &[PathBuf]) -> Result<Self> {
+        let mut result = Self::default();
+        let mut referenced = BTreeSet::new();
+        for test in tests {
+            referenced.insert(canonical(&test.path));
+            for reference in references(test) {
+                match fs::canonicalize(&reference.path) {
+                    Ok(path) => {
+                        referenced.insert(path);
+                    }
+                    Err(_) => result.missing.push(reference),
+                }
+            }
+        }
+
+        // referencing a fixture directory itself (e.g. `cd $TESTDIR/fixtures`)
+        // does not reference all the files in it
+        let fixture_directories_canonical = fixture_directories
+            .iter()
+            .map(|directory| canonical(directory))
+            .collect::<Vec<_>>();
+        referenced.retain(|reference| {
+            !fixture_directories_canonical
+                .iter()
+                .any(|directory| directory.starts_with(reference))
+        });
+
+        for directory in fixture_directories {
+            for file in list_files(directory)? {
+                let canonical = canonical(&file);
+                if referenced
+                    .iter()
+                    .any(|reference| canonical.starts_with(reference))
+                {
+                    continue;
+                }
+                let name = file.file_name().unwrap_or_default().to_string_lossy();
+                if tests.iter().any(|test| test.content.contains(&*name)) {
+                    continue;
+                }
+                result.unreferenced.push(file);
+            }
+        }
+        Ok(result)
+    }
+
+    /// The amount of unreferenced and missing files
+    pub fn count(&self) -> usize {
+        self.unreferenced.len() + self.missing.len()
+    }
+}
+
+/// Returns the files that the given test document references. Paths in shell
+/// expressions, that contain variables or glob patterns, are skipped.
+fn references(test: &ParsedTestFile) -> Vec<FileReference> {
+    let directory = test.path.parent().unwrap_or_else(|| Path::new(""));
+    let mut references = test
+        .config
+        .prepend
+        .iter()
+        .chain(test.config.append.iter())
+        .chain(test.config.fixtures.iter().map(|fixture| &fixture.source))
+        .map(|path| FileReference {
+            document: test.path.clone(),
+            line_number: 1,
+            path: directory.join(path),
+        })
+        .collect::<Vec<_>>();
+    for testcase in &test.testcases {
+        if let Some(TestCaseStdin::File { ref file }) = testcase.config.stdin {
+            references.push(FileReference {
+                document: test.path.clone(),
+                line_number: testcase.line_number,
+                path: directory.join(file),
+            });
+        }
+        for capture in TESTDIR_PATH.captures_iter(&testcase.shell_expression) {
+            let path = &capture[1];
+            if path.contains(['$', '*', '?', '[', '{']) {
+                continue;
+            }
+            references.push(FileReference {
+                document: test.path.clone(),
+                line_number: testcase.line_number,
+                path: directory.join(path),
+            });
+        }
+    }
+    references
+}
+
+/// Returns all files in the given directory and its sub-directories
+fn list_files(directory: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let entries = fs::read_dir(directory)
+        .with_context(|| format!("list fixture directory {}", directory.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(list_files(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use scrut::config::DocumentConfig;
+    use scrut::parsers::parser::ParserType;
+    use scrut::testcase::TestCase;
+    use tempfile::TempDir;
+
+    use super::FileReference;
+    use super::FixtureReferences;
+    use crate::utils::ParsedTestFile;
+
+    fn document(path: &Path, shell_expressions: &[&str]) -> ParsedTestFile {
+        ParsedTestFile {
+            path: path.to_path_buf(),
+            content: shell_expressions.join("\n"),
+            parser_type: ParserType::Markdown,
+            testcases: shell_expressions
+                .iter()
+                .enumerate()
+                .map(|(index, shell_expression)| TestCase {
+                    shell_expression: shell_expression.to_string(),
+                    line_number: index + 1,
+                    ..Default::default()
+                })
+                .collect(),
+            config: DocumentConfig::default(),
+            locale: None,
+        }
+    }
+
+    #[test]
+    fn test_check_fixture_references() {
+        let root = TempDir::with_prefix("references.").expect("create temporary directory");
+        let fixtures = root.path().join("fixtures");
+        fs::create_dir_all(fixtures.join("tree")).expect("create fixture directories");
+        for name in [
+            "input.json",
+            "mentioned.txt",
+            "stale.txt",
+            "tree/nested.txt",
+        ] {
+            fs::write(fixtures.join(name), "").expect("write fixture");
+        }
+
+        let test = document(
+            &root.path().join("test.md"),
+            &[
+                "cat \"$TESTDIR\"/fixtures/input.json",
+                "cp -r ${TESTDIR}/fixtures/tree .",
+                "cd $TESTDIR/fixtures && cat mentioned.txt",
+                "cat $TESTDIR/fixtures/missing.json $TESTDIR/fixtures/$NAME",
+            ],
+        );
+        let references = FixtureReferences::check(&[test], std::slice::from_ref(&fixtures))
+            .expect("check references");
+        assert_eq!(
+            FixtureReferences {
+                unreferenced: vec![fixtures.join("stale.txt")],
+                missing: vec![FileReference {
+                    document: root.path().join("test.md"),
+                    line_number: 4,
+                    path: root.path().join("fixtures/missing.json"),
+                }],
+            },
+            references
+        );
+        assert_eq!(2, references.count());
+    }
+}
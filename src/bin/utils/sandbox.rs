@@ -0,0 +1,589 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::iter::Peekable;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::Chars;
+
+use anyhow::Context;
+use anyhow::Result;
+use regex::Regex;
+use scrut::config::DockerConfig;
+use scrut::config::DocumentConfig;
+use scrut::config::ExecutorBackend;
+use scrut::config::Shell;
+use scrut::executors::cmd_runner::is_cmd;
+use scrut::executors::powershell_runner::is_powershell;
+use scrut::testcase::TestCase;
+use serde::Deserialize;
+
+/// Patterns of commands that are always refused in the sandbox: gaining
+/// privileges, accessing the network and deleting the root or home directory
+pub(crate) const DEFAULT_DENIED_COMMANDS: &[&str] = &[
+    r"^(sudo|su|doas|pkexec)(\s|$)",
+    r"^(curl|wget|nc|ncat|netcat|socat|telnet|ssh|scp|sftp|ftp|rsync)(\s|$)",
+    r"^rm\s+(-\S+\s+)*(/|/\*|~|~/|\$HOME/?|\$\{HOME\}/?)(\s|$)",
+];
+
+/// Words that precede the actual command in a simple command, like keywords
+/// of compound commands and commands that run other commands
+const COMMAND_PREFIXES: &[&str] = &[
+    "!", "{", "}", "builtin", "command", "do", "done", "elif", "else", "env", "esac", "exec", "fi",
+    "if", "nice", "nohup", "then", "time", "until", "while", "xargs",
+];
+
+/// Keywords that start the head of a compound command, which is not a command
+/// itself (e.g. `for name in words`)
+const COMPOUND_HEADS: &[&str] = &["case", "for", "select"];
+
+/// Project configuration of the sandbox (see `test --sandbox`)
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct SandboxConfig {
+    /// Patterns of commands that are allowed. If any are given, every
+    /// command must match one of them.
+    pub allow: Vec<String>,
+
+    /// Patterns of commands that are refused, in addition to the
+    /// [`DEFAULT_DENIED_COMMANDS`]
+    pub deny: Vec<String>,
+
+    /// Test documents, or directories containing test documents, that are
+    /// executed without being checked
+    pub trusted: Vec<PathBuf>,
+}
+
+/// Checks the shell expressions of test documents before they are executed,
+/// to refuse running untrusted documents that contain denied commands
+pub(crate) struct Sandbox {
+    allow: Vec<Regex>,
+    deny: Vec<Regex>,
+    trusted: Vec<PathBuf>,
+
+    /// The shell of the project configuration, which documents may use
+    shell: Option<Shell>,
+
+    /// The executor of the project configuration, which documents may use
+    executor: Option<ExecutorBackend>,
+
+    /// The container of the project configuration, which documents may use
+    docker: Option<DockerConfig>,
+
+    /// The shell given on the command line, which all documents run with
+    command_line_shell: Option<PathBuf>,
+}
+
+impl Sandbox {
+    /// Create from the project configuration, with the document configuration
+    /// it provides, and additionally trusted paths
+    pub fn new(
+        config: &SandboxConfig,
+        project: Option<&DocumentConfig>,
+        trusted: &[PathBuf],
+    ) -> Result<Self> {
+        let compile = |pattern: &str| {
+            Regex::new(pattern).with_context(|| format!("invalid sandbox pattern `{pattern}`"))
+        };
+        Ok(Self {
+            allow: config
+                .allow
+                .iter()
+                .map(|pattern| compile(pattern))
+                .collect::<Result<_>>()?,
+            deny: DEFAULT_DENIED_COMMANDS
+                .iter()
+                .copied()
+                .chain(config.deny.iter().map(|pattern| pattern.as_str()))
+                .map(compile)
+                .collect::<Result<_>>()?,
+            trusted: config
+                .trusted
+                .iter()
+                .chain(trusted)
+                .map(|path| std::path::absolute(path).unwrap_or_else(|_| path.clone()))
+                .collect(),
+            shell: project.and_then(|project| project.shell.clone()),
+            executor: project.and_then(|project| project.executor),
+            docker: project.and_then(|project| project.docker.clone()),
+            command_line_shell: None,
+        })
+    }
+
+    /// Use the shell of the command line, that overrides the shell of all
+    /// documents
+    pub fn with_command_line_shell(mut self, shell: Option<&Path>) -> Self {
+        self.command_line_shell = shell.map(Path::to_path_buf);
+        self
+    }
+
+    /// Whether the test document at the given path is trusted, because it or
+    /// a directory that contains it is
+    pub fn is_trusted(&self, path: &Path) -> bool {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        self.trusted.iter().any(|trusted| path.starts_with(trusted))
+    }
+
+    /// Returns why a test document, with the given configuration and
+    /// testcases, must not be executed, if it must not. The commands of custom
+    /// expectation rules are checked like the shell expressions, as they are
+    /// executed as well.
+    pub fn check_document(
+        &self,
+        path: &Path,
+        config: &DocumentConfig,
+        testcases: &[TestCase],
+    ) -> Result<(), String> {
+        if self.is_trusted(path) {
+            return Ok(());
+        }
+        if let Some(shell) = config
+            .shell
+            .as_ref()
+            .filter(|shell| Some(*shell) != self.shell.as_ref())
+        {
+            return Err(format!("it configures the shell {shell}"));
+        }
+
+        // commands are split and matched as POSIX shell commands, which
+        // PowerShell and cmd commands are not
+        if let Some(shell) = self
+            .command_line_shell
+            .as_deref()
+            .or_else(|| config.shell.as_ref().or(self.shell.as_ref())?.path())
+            .filter(|shell| is_powershell(shell) || is_cmd(shell))
+        {
+            return Err(format!(
+                "it runs with {}, whose commands cannot be checked",
+                shell.display()
+            ));
+        }
+
+        // interpreters of testcases run their shell expressions, which cannot
+        // be checked, unless they are the shell of the project
+        if let Some(shell) = config
+            .defaults
+            .shell
+            .iter()
+            .chain(
+                testcases
+                    .iter()
+                    .filter_map(|testcase| testcase.config.shell.as_ref()),
+            )
+            .find(|shell| Some(*shell) != self.shell.as_ref())
+        {
+            return Err(format!("it configures the shell {shell} for testcases"));
+        }
+
+        // containers can mount any path of the host
+        if let Some(executor) = config
+            .executor
+            .filter(|executor| *executor != ExecutorBackend::Local)
+            .filter(|executor| Some(*executor) != self.executor)
+        {
+            return Err(format!("it configures the executor {executor}"));
+        }
+        if let Some(docker) = config
+            .docker
+            .as_ref()
+            .filter(|docker| Some(*docker) != self.docker.as_ref())
+        {
+            return Err(format!("it configures the container {}", docker.image));
+        }
+        config
+            .before_each
+            .iter()
+            .chain(&config.after_each)
+            .chain(config.rules.values().map(|rule| &rule.command))
+            .chain(testcases.iter().map(|testcase| &testcase.shell_expression))
+            .try_for_each(|expression| self.check(expression))
+    }
+
+    /// Returns why the shell expression must not be executed, if it must not
+    pub fn check(&self, expression: &str) -> Result<(), String> {
+        for command in simple_commands(expression) {
+            if let Some(pattern) = self.deny.iter().find(|pattern| pattern.is_match(&command)) {
+                return Err(format!("`{command}` matches denied pattern `{pattern}`"));
+            }
+            if !self.allow.is_empty()
+                && !self.allow.iter().any(|pattern| pattern.is_match(&command))
+            {
+                return Err(format!("`{command}` matches no allowed pattern"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the simple commands of a shell expression, which are separated by
+/// control operators (`;`, `&&`, `|`, ..) and new lines, including those in
+/// sub-shells and command substitutions, without leading variable
+/// assignments and prefixes
+fn simple_commands(expression: &str) -> Vec<String> {
+    let mut commands = vec![];
+    parse_commands(&mut expression.chars().peekable(), None, &mut commands);
+    commands
+}
+
+/// Parses simple commands into the given list, until the end of the
+/// expression or the given terminator (outside of quotes) is reached
+fn parse_commands(
+    chars: &mut Peekable<Chars>,
+    terminator: Option<char>,
+    commands: &mut Vec<String>,
+) {
+    let mut current = String::new();
+    let (mut in_single, mut in_double) = (false, false);
+    while let Some(c) = chars.next() {
+        match c {
+            _ if in_single => {
+                in_single = c != '\'';
+                current.push(c);
+            }
+            '\\' => {
+                current.push(c);
+                current.extend(chars.next());
+            }
+            '\'' if !in_double => {
+                in_single = true;
+                current.push(c);
+            }
+            '"' => {
+                in_double = !in_double;
+                current.push(c);
+            }
+
+            // command substitutions are executed, even in double quotes
+            '$' if chars.peek() == Some(&'(') => {
+                chars.next();
+                parse_commands(chars, Some(')'), commands);
+                current.push_str("$()");
+            }
+            '`' if terminator != Some('`') => {
+                parse_commands(chars, Some('`'), commands);
+                current.push_str("``");
+            }
+            _ if Some(c) == terminator && !in_double => break,
+            _ if in_double => current.push(c),
+            '(' => {
+                commands.extend(strip_command_prefixes(&current));
+                current.clear();
+                parse_commands(chars, Some(')'), commands);
+            }
+            ')' | ';' | '&' | '|' | '\n' => {
+                commands.extend(strip_command_prefixes(&current));
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+    commands.extend(strip_command_prefixes(&current));
+}
+
+/// Returns the command without leading variable assignments and prefixes
+/// (see [`COMMAND_PREFIXES`]), or `None` if nothing remains
+fn strip_command_prefixes(command: &str) -> Option<String> {
+    let mut words = command.split_whitespace().peekable();
+    while let Some(word) = words.peek() {
+        let is_assignment = word.split_once('=').is_some_and(|(name, _)| {
+            !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        });
+        if !is_assignment && !COMMAND_PREFIXES.contains(word) {
+            break;
+        }
+        words.next();
+    }
+    if words
+        .peek()
+        .is_some_and(|word| COMPOUND_HEADS.contains(word))
+    {
+        return None;
+    }
+    let command = words.collect::<Vec<_>>().join(" ");
+    (!command.is_empty()).then_some(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    use scrut::config::CustomRule;
+    use scrut::config::DockerConfig;
+    use scrut::config::DocumentConfig;
+    use scrut::config::ExecutorBackend;
+    use scrut::config::Shell;
+    use scrut::config::TestCaseConfig;
+    use scrut::testcase::TestCase;
+
+    use super::Sandbox;
+    use super::SandboxConfig;
+    use super::simple_commands;
+
+    #[test]
+    fn test_simple_commands() {
+        assert_eq!(
+            vec![
+                "echo 'a; b' \"c | d\"",
+                "grep x",
+                "date",
+                "echo \"$()\"",
+                "sudo ls",
+                "curl x",
+                "cat ``",
+                "ls",
+                ":",
+                "echo ${HOME}",
+                "rm $f",
+            ],
+            simple_commands(
+                "FOO=bar echo 'a; b' \"c | d\" | grep x && echo \"$(date)\"; env sudo ls\n(cat `curl x`) || if ls; then :; fi\n{ echo ${HOME}; }\nfor f in *; do case $f in a) rm $f;; esac; done"
+            )
+        );
+    }
+
+    #[test]
+    fn test_check_denied() {
+        let sandbox = Sandbox::new(&SandboxConfig::default(), None, &[]).expect("create sandbox");
+        for expression in [
+            "echo hello",
+            "rm -rf ./build",
+            "cat /etc/hosts | grep localhost",
+            "echo 'sudo make me a sandwich'",
+        ] {
+            sandbox
+                .check(expression)
+                .unwrap_or_else(|err| panic!("`{expression}` is allowed: {err}"));
+        }
+        for expression in [
+            "sudo ls",
+            "echo $(curl -s example.com)",
+            "rm -rf /",
+            "rm -rf ~",
+            "true && wget example.com",
+            "FOO=bar ssh host",
+        ] {
+            sandbox
+                .check(expression)
+                .expect_err(&format!("`{expression}` is denied"));
+        }
+    }
+
+    #[test]
+    fn test_check_allowed() {
+        let sandbox = Sandbox::new(
+            &SandboxConfig {
+                allow: vec![r"^(echo|grep)\b".into()],
+                deny: vec![r"^echo forbidden".into()],
+                ..Default::default()
+            },
+            None,
+            &[],
+        )
+        .expect("create sandbox");
+        sandbox.check("echo foo | grep foo").expect("allowed");
+        assert_eq!(
+            Err("`cat foo` matches no allowed pattern".to_string()),
+            sandbox.check("echo foo | cat foo")
+        );
+        assert_eq!(
+            Err("`echo forbidden` matches denied pattern `^echo forbidden`".to_string()),
+            sandbox.check("echo forbidden")
+        );
+    }
+
+    #[test]
+    fn test_check_document() {
+        let sandbox = Sandbox::new(
+            &SandboxConfig {
+                trusted: vec![PathBuf::from("trusted")],
+                ..Default::default()
+            },
+            Some(&DocumentConfig {
+                shell: Some("/bin/bash".into()),
+                ..Default::default()
+            }),
+            &[PathBuf::from("other/trusted.md")],
+        )
+        .expect("create sandbox");
+        let testcases = vec![TestCase {
+            shell_expression: "echo".into(),
+            ..Default::default()
+        }];
+        let config = DocumentConfig {
+            before_each: Some("sudo true".into()),
+            ..Default::default()
+        };
+        for path in ["trusted/a.md", "trusted/nested/b.md", "other/trusted.md"] {
+            sandbox
+                .check_document(path.as_ref(), &config, &testcases)
+                .unwrap_or_else(|err| panic!("{path} is trusted: {err}"));
+        }
+        assert_eq!(
+            Err("`sudo true` matches denied pattern `^(sudo|su|doas|pkexec)(\\s|$)`".to_string()),
+            sandbox.check_document("untrusted.md".as_ref(), &config, &testcases)
+        );
+        assert_eq!(
+            Err("it configures the shell /bin/zsh".to_string()),
+            sandbox.check_document(
+                "untrusted.md".as_ref(),
+                &DocumentConfig {
+                    shell: Some("/bin/zsh".into()),
+                    ..Default::default()
+                },
+                &testcases
+            )
+        );
+        sandbox
+            .check_document(
+                "untrusted.md".as_ref(),
+                &DocumentConfig {
+                    shell: Some("/bin/bash".into()),
+                    ..Default::default()
+                },
+                &testcases,
+            )
+            .expect("shell of the project is allowed");
+        assert_eq!(
+            Err("it configures the shell python3 -c for testcases".to_string()),
+            sandbox.check_document(
+                "untrusted.md".as_ref(),
+                &DocumentConfig::default(),
+                &[TestCase {
+                    config: TestCaseConfig {
+                        shell: Some(Shell::Interpreter(vec!["python3".into(), "-c".into()])),
+                        ..Default::default()
+                    },
+                    shell_expression: "import os; os.system('sudo true')".into(),
+                    ..Default::default()
+                }]
+            )
+        );
+        assert_eq!(
+            Err("it configures the shell /bin/zsh for testcases".to_string()),
+            sandbox.check_document(
+                "untrusted.md".as_ref(),
+                &DocumentConfig {
+                    defaults: TestCaseConfig {
+                        shell: Some("/bin/zsh".into()),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                &testcases
+            )
+        );
+        assert_eq!(
+            Err("`wget example.com` matches denied pattern `^(curl|wget|nc|ncat|netcat|socat|telnet|ssh|scp|sftp|ftp|rsync)(\\s|$)`".to_string()),
+            sandbox.check_document(
+                "untrusted.md".as_ref(),
+                &DocumentConfig {
+                    rules: BTreeMap::from([(
+                        "remote".to_string(),
+                        CustomRule {
+                            command: "wget example.com".into(),
+                        },
+                    )]),
+                    ..Default::default()
+                },
+                &testcases
+            )
+        );
+    }
+
+    #[test]
+    fn test_check_document_powershell() {
+        let testcases = vec![TestCase {
+            shell_expression: "Invoke-WebRequest https://example.com".into(),
+            ..Default::default()
+        }];
+        let sandbox = Sandbox::new(
+            &SandboxConfig::default(),
+            Some(&DocumentConfig {
+                shell: Some("pwsh".into()),
+                ..Default::default()
+            }),
+            &[],
+        )
+        .expect("create sandbox");
+        assert_eq!(
+            Err("it runs with pwsh, whose commands cannot be checked".to_string()),
+            sandbox.check_document(
+                "untrusted.md".as_ref(),
+                &DocumentConfig::default(),
+                &testcases
+            )
+        );
+        let sandbox = Sandbox::new(&SandboxConfig::default(), None, &[])
+            .expect("create sandbox")
+            .with_command_line_shell(Some("C:/Windows/System32/cmd.exe".as_ref()));
+        assert_eq!(
+            Err(
+                "it runs with C:/Windows/System32/cmd.exe, whose commands cannot be checked"
+                    .to_string()
+            ),
+            sandbox.check_document(
+                "untrusted.md".as_ref(),
+                &DocumentConfig::default(),
+                &testcases
+            )
+        );
+    }
+
+    #[test]
+    fn test_check_document_executor() {
+        let docker = DockerConfig {
+            image: "ubuntu:24.04".into(),
+            ..Default::default()
+        };
+        let testcases = vec![TestCase {
+            shell_expression: "echo".into(),
+            ..Default::default()
+        }];
+        let config = DocumentConfig {
+            executor: Some(ExecutorBackend::Docker),
+            docker: Some(docker.clone()),
+            ..Default::default()
+        };
+
+        let sandbox = Sandbox::new(&SandboxConfig::default(), None, &[]).expect("create sandbox");
+        assert_eq!(
+            Err("it configures the executor docker".to_string()),
+            sandbox.check_document("untrusted.md".as_ref(), &config, &testcases)
+        );
+        sandbox
+            .check_document(
+                "untrusted.md".as_ref(),
+                &DocumentConfig {
+                    executor: Some(ExecutorBackend::Local),
+                    ..Default::default()
+                },
+                &testcases,
+            )
+            .expect("local executor is allowed");
+
+        let sandbox =
+            Sandbox::new(&SandboxConfig::default(), Some(&config), &[]).expect("create sandbox");
+        sandbox
+            .check_document("untrusted.md".as_ref(), &config, &testcases)
+            .expect("container of the project is allowed");
+        assert_eq!(
+            Err("it configures the container ubuntu:24.04".to_string()),
+            sandbox.check_document(
+                "untrusted.md".as_ref(),
+                &DocumentConfig {
+                    docker: Some(DockerConfig {
+                        mounts: vec!["/:/host".into()],
+                        ..docker
+                    }),
+                    ..Default::default()
+                },
+                &testcases
+            )
+        );
+    }
+}
@@ -0,0 +1,317 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use anyhow::Context;
+use anyhow::Result;
+use scrut::outcome::Outcome;
+use scrut::testcase::TestCaseError;
+use serde::Serialize;
+use tracing::debug;
+
+/// An event of the progress stream, which is written as a single line of JSON
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum StatusEvent<'a> {
+    RunStarted {
+        documents: usize,
+    },
+    DocumentStarted {
+        path: &'a Path,
+        index: usize,
+        testcases: usize,
+    },
+    TestcaseFinished {
+        path: &'a Path,
+        line_number: usize,
+        title: &'a str,
+        result: &'static str,
+    },
+    DocumentFinished {
+        path: &'a Path,
+        index: usize,
+        elapsed_ms: u128,
+    },
+    Progress {
+        completed: usize,
+        total: usize,
+        succeeded: usize,
+        failed: usize,
+        skipped: usize,
+        eta_ms: Option<u128>,
+    },
+    RunFinished {
+        documents: usize,
+        succeeded: usize,
+        failed: usize,
+        skipped: usize,
+        elapsed_ms: u128,
+    },
+}
+
+/// The test document that is currently running
+struct RunningDocument {
+    path: PathBuf,
+    index: usize,
+    started: Instant,
+
+    /// Index of the first outcome of the document
+    first_outcome: usize,
+}
+
+struct StatusState {
+    writer: Box<dyn Write + Send>,
+    documents: usize,
+    started: Instant,
+    running: Option<RunningDocument>,
+}
+
+/// Writes newline-delimited JSON progress events to a file (descriptor), so
+/// that wrapping tools can render progress without parsing the human output
+pub(crate) struct StatusStream(Mutex<StatusState>);
+
+impl StatusStream {
+    /// Opens the stream to either a file descriptor number (e.g. `3`) or a
+    /// path to a file, which is truncated
+    pub(crate) fn open(target: &str) -> Result<Self> {
+        let file = match target.parse::<u32>() {
+            Ok(fd) => open_descriptor(fd)?,
+            Err(_) => {
+                File::create(target).with_context(|| format!("create status file {}", target))?
+            }
+        };
+        Ok(Self::new(Box::new(file)))
+    }
+
+    /// Creates the stream to write to the given writer
+    pub(crate) fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self(Mutex::new(StatusState {
+            writer,
+            documents: 0,
+            started: Instant::now(),
+            running: None,
+        }))
+    }
+
+    /// Starts a run of the given amount of test documents
+    pub(crate) fn run_started(&self, documents: usize) {
+        let mut state = self.0.lock().expect("status lock");
+        state.documents = documents;
+        state.started = Instant::now();
+        state.emit(&StatusEvent::RunStarted { documents });
+    }
+
+    /// Starts the next test document, which finishes the previous one
+    pub(crate) fn document_started(&self, path: &Path, testcases: usize, outcomes: &[Outcome]) {
+        let mut state = self.0.lock().expect("status lock");
+        let index = match state.finish_document(outcomes) {
+            Some(previous) => previous + 1,
+            None => 0,
+        };
+        state.emit(&StatusEvent::DocumentStarted {
+            path,
+            index,
+            testcases,
+        });
+        state.running = Some(RunningDocument {
+            path: path.to_path_buf(),
+            index,
+            started: Instant::now(),
+            first_outcome: outcomes.len(),
+        });
+    }
+
+    /// Finishes the last test document and the run
+    pub(crate) fn run_finished(&self, outcomes: &[Outcome]) {
+        let mut state = self.0.lock().expect("status lock");
+        state.finish_document(outcomes);
+        let (succeeded, failed, skipped) = count_results(outcomes);
+        let (documents, elapsed) = (state.documents, state.started.elapsed());
+        state.emit(&StatusEvent::RunFinished {
+            documents,
+            succeeded,
+            failed,
+            skipped,
+            elapsed_ms: elapsed.as_millis(),
+        });
+    }
+}
+
+impl StatusState {
+    /// Emits the results of the running document, if any, and returns its index
+    fn finish_document(&mut self, outcomes: &[Outcome]) -> Option<usize> {
+        let running = self.running.take()?;
+        for outcome in outcomes.iter().skip(running.first_outcome) {
+            self.emit(&StatusEvent::TestcaseFinished {
+                path: &running.path,
+                line_number: outcome.testcase.line_number,
+                title: &outcome.testcase.title,
+                result: result_name(outcome),
+            });
+        }
+        self.emit(&StatusEvent::DocumentFinished {
+            path: &running.path,
+            index: running.index,
+            elapsed_ms: running.started.elapsed().as_millis(),
+        });
+
+        let completed = running.index + 1;
+        let (succeeded, failed, skipped) = count_results(outcomes);
+        let eta_ms = (completed < self.documents).then(|| {
+            self.started.elapsed().as_millis() / completed as u128
+                * (self.documents - completed) as u128
+        });
+        self.emit(&StatusEvent::Progress {
+            completed,
+            total: self.documents,
+            succeeded,
+            failed,
+            skipped,
+            eta_ms,
+        });
+        Some(running.index)
+    }
+
+    /// Writes the event, without failing the run if that is not possible
+    fn emit(&mut self, event: &StatusEvent) {
+        let result = serde_json::to_string(event)
+            .map_err(anyhow::Error::from)
+            .and_then(|line| {
+                writeln!(self.writer, "{}", line)?;
+                self.writer.flush()?;
+                Ok(())
+            });
+        if let Err(err) = result {
+            debug!("cannot write status event {:?}: {}", event, err);
+        }
+    }
+}
+
+fn result_name(outcome: &Outcome) -> &'static str {
+    match outcome.result {
+        Ok(_) => "succeeded",
+        Err(TestCaseError::Skipped) => "skipped",
+        Err(_) => "failed",
+    }
+}
+
+/// Returns the amount of succeeded, failed and skipped outcomes
+fn count_results(outcomes: &[Outcome]) -> (usize, usize, usize) {
+    outcomes.iter().fold(
+        (0, 0, 0),
+        |(succeeded, failed, skipped), outcome| match result_name(outcome) {
+            "succeeded" => (succeeded + 1, failed, skipped),
+            "skipped" => (succeeded, failed, skipped + 1),
+            _ => (succeeded, failed + 1, skipped),
+        },
+    )
+}
+
+#[cfg(unix)]
+fn open_descriptor(fd: u32) -> Result<File> {
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(format!("/dev/fd/{fd}"))
+        .with_context(|| format!("open status file descriptor {fd}"))
+}
+
+#[cfg(not(unix))]
+fn open_descriptor(fd: u32) -> Result<File> {
+    anyhow::bail!("status file descriptor {fd} is not supported on this platform, use a path")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use scrut::escaping::Escaper;
+    use scrut::outcome::Outcome;
+    use scrut::parsers::parser::ParserType;
+    use scrut::testcase::TestCase;
+    use scrut::testcase::TestCaseError;
+
+    use super::StatusStream;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn outcome(line_number: usize, result: Result<(), TestCaseError>) -> Outcome {
+        Outcome {
+            location: None,
+            testcase: TestCase {
+                title: "title".into(),
+                line_number,
+                ..Default::default()
+            },
+            output: ("", "").into(),
+            escaping: Escaper::default(),
+            format: ParserType::Markdown,
+            result,
+        }
+    }
+
+    #[test]
+    fn test_status_events() {
+        let buffer = Buffer::default();
+        let status = StatusStream::new(Box::new(buffer.clone()));
+        let mut outcomes = vec![];
+        status.run_started(2);
+        status.document_started("a.md".as_ref(), 2, &outcomes);
+        outcomes.push(outcome(3, Ok(())));
+        outcomes.push(outcome(8, Err(TestCaseError::Skipped)));
+        status.document_started("b.md".as_ref(), 1, &outcomes);
+        outcomes.push(outcome(5, Err(TestCaseError::Timeout)));
+        status.run_finished(&outcomes);
+
+        let written = String::from_utf8(buffer.0.lock().unwrap().clone()).expect("utf-8");
+        let events = written
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).expect("json line"))
+            .map(|mut event| {
+                // remove timings, which differ between executions
+                let object = event.as_object_mut().expect("object");
+                object.remove("elapsed_ms");
+                object.remove("eta_ms");
+                event.to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                r#"{"documents":2,"event":"run_started"}"#,
+                r#"{"event":"document_started","index":0,"path":"a.md","testcases":2}"#,
+                r#"{"event":"testcase_finished","line_number":3,"path":"a.md","result":"succeeded","title":"title"}"#,
+                r#"{"event":"testcase_finished","line_number":8,"path":"a.md","result":"skipped","title":"title"}"#,
+                r#"{"event":"document_finished","index":0,"path":"a.md"}"#,
+                r#"{"completed":1,"event":"progress","failed":0,"skipped":1,"succeeded":1,"total":2}"#,
+                r#"{"event":"document_started","index":1,"path":"b.md","testcases":1}"#,
+                r#"{"event":"testcase_finished","line_number":5,"path":"b.md","result":"failed","title":"title"}"#,
+                r#"{"event":"document_finished","index":1,"path":"b.md"}"#,
+                r#"{"completed":2,"event":"progress","failed":1,"skipped":1,"succeeded":1,"total":2}"#,
+                r#"{"documents":2,"event":"run_finished","failed":1,"skipped":1,"succeeded":1}"#,
+            ],
+            events
+        );
+    }
+}
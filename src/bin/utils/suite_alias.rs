@@ -0,0 +1,352 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use globset::Glob;
+use globset::GlobSet;
+use globset::GlobSetBuilder;
+use scrut::testcase::TestCase;
+use serde::Deserialize;
+
+use super::ParsedTestFile;
+use super::ProjectConfig;
+
+/// Prefix of test paths, that refer to a suite alias of the project
+/// configuration instead of a file or directory (e.g. `@smoke`)
+pub(crate) const SUITE_ALIAS_PREFIX: &str = "@";
+
+/// Named selection of testcases, that is defined in the `suites` of the
+/// project configuration and run with `scrut test @<name>`
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct SuiteAlias {
+    /// Glob patterns of the test documents to select (e.g. `tests/smoke/**`),
+    /// or paths of test documents or directories containing test documents
+    pub paths: Vec<PathBuf>,
+
+    /// Tags of which testcases must have at least one, if any are given
+    pub tags: Vec<String>,
+
+    /// Tags of which testcases must have none
+    #[serde(alias = "tags-exclude")]
+    pub tags_exclude: Vec<String>,
+}
+
+impl SuiteAlias {
+    /// Returns the directories (or documents) that contain all documents the
+    /// paths select, which is the part of each path before the first glob
+    /// pattern, relative to the current directory if within it
+    pub fn roots(&self) -> Vec<PathBuf> {
+        let current_directory = std::env::current_dir().unwrap_or_default();
+        self.paths
+            .iter()
+            .map(|path| {
+                let root = path
+                    .components()
+                    .take_while(|component| {
+                        !component
+                            .as_os_str()
+                            .to_string_lossy()
+                            .contains(['*', '?', '[', '{'])
+                    })
+                    .collect::<PathBuf>();
+                match root.strip_prefix(&current_directory) {
+                    Ok(relative) if relative.as_os_str().is_empty() => PathBuf::from("."),
+                    Ok(relative) => relative.to_path_buf(),
+                    Err(_) => root,
+                }
+            })
+            .collect()
+    }
+
+    /// Whether the tags of the testcase, which are its annotations, are
+    /// selected. A tag `slow` matches an annotation named `slow`, whatever its
+    /// value, and a tag `speed=slow` an annotation `speed` with value `slow`.
+    pub fn is_selected(&self, testcase: &TestCase) -> bool {
+        let has_tag = |tag: &String| {
+            let annotations = &testcase.config.annotations;
+            match tag.split_once('=') {
+                Some((name, value)) => annotations.get(name).is_some_and(|v| v == value),
+                None => annotations.contains_key(tag),
+            }
+        };
+        (self.tags.is_empty() || self.tags.iter().any(has_tag))
+            && !self.tags_exclude.iter().any(has_tag)
+    }
+
+    /// Whether the paths select the document at the given (absolute) path,
+    /// with the globs compiled from the paths
+    fn selects_path(&self, globs: &GlobSet, path: &Path) -> bool {
+        globs.is_match(path) || self.paths.iter().any(|selected| path.starts_with(selected))
+    }
+
+    fn globs(&self) -> Result<GlobSet> {
+        let mut builder = GlobSetBuilder::new();
+        for path in &self.paths {
+            let pattern = path.to_string_lossy();
+            builder.add(
+                Glob::new(&pattern).with_context(|| format!("invalid suite path `{pattern}`"))?,
+            );
+        }
+        builder.build().context("build suite paths")
+    }
+}
+
+/// The paths that `scrut test` is run with, in which suite aliases (`@<name>`)
+/// are resolved with the project configuration
+pub(crate) struct TestPaths<'a> {
+    /// Paths of test documents, or directories containing test documents, of
+    /// which all testcases are selected
+    pub explicit: Vec<PathBuf>,
+
+    /// Suite aliases, that select documents and testcases
+    pub aliases: Vec<&'a SuiteAlias>,
+}
+
+impl<'a> TestPaths<'a> {
+    /// Resolves the suite aliases in the given paths, which must be defined in
+    /// the given project configuration
+    pub fn resolve(paths: &[&Path], project_config: Option<&'a ProjectConfig>) -> Result<Self> {
+        let mut test_paths = Self {
+            explicit: vec![],
+            aliases: vec![],
+        };
+        for path in paths {
+            let Some(name) = path
+                .to_str()
+                .and_then(|path| path.strip_prefix(SUITE_ALIAS_PREFIX))
+            else {
+                test_paths.explicit.push(path.to_path_buf());
+                continue;
+            };
+            let suites = project_config.map(|config| &config.suites);
+            match suites.and_then(|suites| suites.get(name)) {
+                Some(alias) => test_paths.aliases.push(alias),
+                None => bail!(
+                    "suite alias `{}` is not defined in the `suites` of the project configuration (defined are: {})",
+                    path.display(),
+                    suites
+                        .into_iter()
+                        .flat_map(|suites| suites.keys())
+                        .map(|name| format!("{SUITE_ALIAS_PREFIX}{name}"))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ),
+            }
+        }
+        Ok(test_paths)
+    }
+
+    /// Returns the paths in which test documents are looked for
+    pub fn search_paths(&self) -> Vec<PathBuf> {
+        self.explicit
+            .iter()
+            .cloned()
+            .chain(self.aliases.iter().flat_map(|alias| alias.roots()))
+            .collect()
+    }
+
+    /// Returns the documents in the explicit paths and those that any suite
+    /// alias selects, with only the testcases that any of them selects
+    pub fn select(&self, documents: Vec<ParsedTestFile>) -> Result<Vec<ParsedTestFile>> {
+        if self.aliases.is_empty() {
+            return Ok(documents);
+        }
+        let absolute = |path: &Path| std::path::absolute(path).unwrap_or_else(|_| path.into());
+        let explicit = self
+            .explicit
+            .iter()
+            .map(|path| absolute(path))
+            .collect::<Vec<_>>();
+        let globs = self
+            .aliases
+            .iter()
+            .map(|alias| alias.globs())
+            .collect::<Result<Vec<_>>>()?;
+
+        // the search paths of aliases may overlap with each other
+        let mut seen = BTreeSet::new();
+        Ok(documents
+            .into_iter()
+            .filter_map(|mut document| {
+                let path = absolute(&document.path);
+                if !seen.insert(path.clone()) {
+                    return None;
+                }
+                if explicit.iter().any(|explicit| path.starts_with(explicit)) {
+                    return Some(document);
+                }
+                let selecting = self
+                    .aliases
+                    .iter()
+                    .zip(&globs)
+                    .filter(|(alias, globs)| alias.selects_path(globs, &path))
+                    .map(|(alias, _)| alias)
+                    .collect::<Vec<_>>();
+                document
+                    .testcases
+                    .retain(|testcase| selecting.iter().any(|alias| alias.is_selected(testcase)));
+                (!document.testcases.is_empty()).then_some(document)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    use scrut::config::DocumentConfig;
+    use scrut::config::TestCaseConfig;
+    use scrut::parsers::parser::ParserType;
+    use scrut::testcase::TestCase;
+
+    use super::ParsedTestFile;
+    use super::ProjectConfig;
+    use super::SuiteAlias;
+    use super::TestPaths;
+
+    fn testcase(line_number: usize, annotations: &[(&str, &str)]) -> TestCase {
+        TestCase {
+            line_number,
+            config: TestCaseConfig {
+                annotations: annotations
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect::<BTreeMap<_, _>>(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn document(path: &str, testcases: Vec<TestCase>) -> ParsedTestFile {
+        ParsedTestFile {
+            path: path.into(),
+            content: String::new(),
+            parser_type: ParserType::Markdown,
+            testcases,
+            config: DocumentConfig::default(),
+            locale: None,
+        }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_roots() {
+        let alias = SuiteAlias {
+            paths: vec![
+                "/project/tests/smoke/**".into(),
+                "/project/tests/*/quick.md".into(),
+                "/project/tests/extra.md".into(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![
+                PathBuf::from("/project/tests/smoke"),
+                PathBuf::from("/project/tests"),
+                PathBuf::from("/project/tests/extra.md"),
+            ],
+            alias.roots()
+        );
+    }
+
+    #[test]
+    fn test_resolve() {
+        let config = ProjectConfig {
+            suites: BTreeMap::from([("smoke".to_string(), SuiteAlias::default())]),
+            ..Default::default()
+        };
+        let test_paths =
+            TestPaths::resolve(&[Path::new("@smoke"), Path::new("tests")], Some(&config))
+                .expect("resolve test paths");
+        assert_eq!(vec![PathBuf::from("tests")], test_paths.explicit);
+        assert_eq!(vec![&config.suites["smoke"]], test_paths.aliases);
+
+        let err = TestPaths::resolve(&[Path::new("@unknown")], Some(&config))
+            .err()
+            .expect("unknown alias fails");
+        assert_eq!(
+            "suite alias `@unknown` is not defined in the `suites` of the project configuration (defined are: @smoke)",
+            err.to_string()
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_select() {
+        let alias = SuiteAlias {
+            paths: vec!["/project/tests/smoke/**".into(), "/project/extra".into()],
+            tags_exclude: vec!["slow".into(), "owner=nobody".into()],
+            ..Default::default()
+        };
+        let test_paths = TestPaths {
+            explicit: vec!["/project/explicit".into()],
+            aliases: vec![&alias],
+        };
+        let selected = test_paths
+            .select(vec![
+                document(
+                    "/project/tests/smoke/a.md",
+                    vec![
+                        testcase(3, &[]),
+                        testcase(7, &[("slow", "")]),
+                        testcase(11, &[("owner", "nobody")]),
+                        testcase(15, &[("owner", "somebody")]),
+                    ],
+                ),
+                document(
+                    "/project/tests/smoke/b.md",
+                    vec![testcase(3, &[("slow", "")])],
+                ),
+                document("/project/tests/other/c.md", vec![testcase(3, &[])]),
+                document("/project/extra/d.md", vec![testcase(3, &[])]),
+                document("/project/extra/d.md", vec![testcase(3, &[])]),
+                document("/project/explicit/e.md", vec![testcase(3, &[("slow", "")])]),
+            ])
+            .expect("select documents");
+        assert_eq!(
+            vec![
+                (PathBuf::from("/project/tests/smoke/a.md"), vec![3, 15]),
+                (PathBuf::from("/project/extra/d.md"), vec![3]),
+                (PathBuf::from("/project/explicit/e.md"), vec![3]),
+            ],
+            selected
+                .into_iter()
+                .map(|document| (
+                    document.path,
+                    document
+                        .testcases
+                        .iter()
+                        .map(|testcase| testcase.line_number)
+                        .collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_is_selected_by_tags() {
+        let alias = SuiteAlias {
+            tags: vec!["smoke".into(), "speed=fast".into()],
+            ..Default::default()
+        };
+        assert!(alias.is_selected(&testcase(1, &[("smoke", "")])));
+        assert!(alias.is_selected(&testcase(1, &[("speed", "fast")])));
+        assert!(!alias.is_selected(&testcase(1, &[("speed", "slow")])));
+        assert!(!alias.is_selected(&testcase(1, &[])));
+    }
+}
@@ -12,11 +12,14 @@ use anyhow::Result;
 use dialoguer::Confirm;
 use dialoguer::console::Term;
 use dialoguer::console::strip_ansi_codes;
+use dialoguer::console::style;
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::theme::SimpleTheme;
 use dialoguer::theme::Theme;
 use indicatif::ProgressBar;
 use indicatif::ProgressStyle;
+use scrut::executors::context::ProgressNotice;
+use scrut::executors::context::ProgressNotifier;
 use tracing::debug;
 use tracing::error;
 use tracing::info;
@@ -53,6 +56,18 @@ pub(crate) fn progress_bar(size: u64) -> Result<ProgressBar> {
     Ok(pb)
 }
 
+/// Style of a progress bar that is continuously redrawn, with the elapsed
+/// time and the amount of failures (in the prefix) so far
+pub(crate) fn live_progress_style(size: u64) -> Result<ProgressStyle> {
+    let len: usize = size.to_string().len();
+    Ok(ProgressStyle::with_template(&format!(
+        "{{spinner:.blue}} [{{elapsed:>4}}] [{{pos:>{}}}/{{len:>{}}}]▕{{bar:.blue}}▏{{prefix:.red.bold}}{{msg}}",
+        len, len,
+    ))
+    .context("create live progress bar style")?
+    .progress_chars("█▉▊▋▌▍▎▏  "))
+}
+
 macro_rules! has_prefix {
     ($var:expr, $prefix:expr) => {
         {
@@ -89,9 +104,12 @@ macro_rules! log {
 /// Use-case: We want to show progress bars in the CLI but we don't want to show them if user
 ///           wants increased log messages (info-level or above)
 /// Use-case: Testing. ProgressBar does not create well testable output, logs do.
+#[derive(Clone)]
 pub(crate) struct ProgressWriter {
     pb: Option<ProgressBar>,
     no_color: bool,
+    live: bool,
+    quiet: bool,
 }
 
 impl ProgressWriter {
@@ -101,10 +119,50 @@ impl ProgressWriter {
         } else {
             None
         };
-        Ok(Self { pb, no_color })
+        Ok(Self {
+            pb,
+            no_color,
+            live: false,
+            quiet: false,
+        })
+    }
+
+    /// Neither shows a progress bar nor prints any messages, so that only the
+    /// rendered result is printed
+    pub fn quiet(mut self) -> Self {
+        self.pb = None;
+        self.live = false;
+        self.quiet = true;
+        self
+    }
+
+    /// Continuously redraws the progress bar, if any, with the elapsed time,
+    /// the amount of failures and the currently running testcase, so that
+    /// long running documents do not look frozen
+    pub fn with_live_status(mut self) -> Result<Self> {
+        if let Some(pb) = &self.pb {
+            pb.set_style(live_progress_style(pb.length().unwrap_or_default())?);
+            if !is_in_scrut_test() {
+                pb.enable_steady_tick(std::time::Duration::from_millis(100));
+            }
+            self.live = true;
+        }
+        Ok(self)
+    }
+
+    /// Shows the amount of failed testcases so far, if the status is live
+    pub fn set_failures(&self, failures: usize) {
+        if let (Some(pb), true) = (&self.pb, self.live) {
+            if failures > 0 {
+                pb.set_prefix(format!("❌ {failures} "));
+            }
+        }
     }
 
     pub fn println<S: AsRef<str>>(&self, msg: S) {
+        if self.quiet {
+            return;
+        }
         let msg = self.render(msg.as_ref());
         if let Some(pb) = &self.pb {
             pb.println(msg);
@@ -120,6 +178,9 @@ impl ProgressWriter {
     }
 
     pub fn set_message<S: AsRef<str>>(&self, msg: S) {
+        if self.quiet {
+            return;
+        }
         let msg = self.render(msg.as_ref()).into_owned();
         if let Some(pb) = &self.pb {
             if !is_in_scrut_test() {
@@ -130,6 +191,35 @@ impl ProgressWriter {
         }
     }
 
+    /// Returns a notifier that prints notices about still running executions
+    /// of testcases in the named document
+    pub fn notifier(&self, name: &str) -> ProgressNotifier {
+        let pw = self.clone();
+        let name = name.to_string();
+        ProgressNotifier::new(move |notice| match notice {
+            ProgressNotice::TestCaseStarted { .. } => {
+                if pw.live {
+                    pw.set_message(format!("👀 {}: {}", style(&name).yellow(), notice))
+                }
+            }
+            ProgressNotice::TimeoutApproaching { .. } => {
+                pw.warn(format!("⌛️ {}: {}", style(&name).yellow(), notice))
+            }
+        })
+    }
+
+    /// Like [`ProgressWriter::println`], but also visible if the progress bar
+    /// is hidden (e.g. in CI, where the output is not a terminal)
+    pub fn warn<S: AsRef<str>>(&self, msg: S) {
+        if self.quiet {
+            return;
+        }
+        match &self.pb {
+            Some(pb) if !pb.is_hidden() => pb.println(self.render(msg.as_ref())),
+            _ => warn!("{}", self.render(msg.as_ref())),
+        }
+    }
+
     pub fn finish_and_clear(&self) {
         if let Some(pb) = &self.pb {
             pb.finish_and_clear();
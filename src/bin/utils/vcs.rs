@@ -0,0 +1,105 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+
+/// A file that has changes in the working tree of a git repository, which
+/// are not yet staged
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ChangedFile {
+    /// Absolute path to the file
+    pub path: PathBuf,
+
+    /// Content of the file in the git index, which is the content before the
+    /// (unstaged) changes were made
+    pub original: String,
+}
+
+/// Returns all files within the given paths that have unstaged changes in the
+/// git repository of the current directory
+pub(crate) fn changed_files(paths: &[&Path]) -> Result<Vec<ChangedFile>> {
+    let toplevel = git(&["rev-parse", "--show-toplevel"]).context("find git repository")?;
+    let toplevel = PathBuf::from(toplevel.trim_end());
+
+    let mut args = vec!["diff", "--name-only", "-z", "--"];
+    let paths = paths
+        .iter()
+        .map(|path| path.to_string_lossy())
+        .collect::<Vec<_>>();
+    args.extend(paths.iter().map(|path| path.as_ref()));
+    let names = git(&args).context("list changed files")?;
+
+    names
+        .split('\0')
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            let original = git(&[
+                "-C",
+                &toplevel.to_string_lossy(),
+                "show",
+                &format!(":{name}"),
+            ])
+            .with_context(|| format!("read original content of {name}"))?;
+            Ok(ChangedFile {
+                path: toplevel.join(name),
+                original,
+            })
+        })
+        .collect()
+}
+
+/// Returns all files that are changed compared to the last commit of the git
+/// repository of the current directory, including untracked files
+pub(crate) fn changed_paths_since_head() -> Result<Vec<PathBuf>> {
+    let toplevel = git(&["rev-parse", "--show-toplevel"]).context("find git repository")?;
+    let toplevel = PathBuf::from(toplevel.trim_end());
+    let changed = git(&["diff", "--name-only", "-z", "HEAD"]).context("list changed files")?;
+    let untracked = git(&[
+        "-C",
+        &toplevel.to_string_lossy(),
+        "ls-files",
+        "--others",
+        "--exclude-standard",
+        "-z",
+    ])
+    .context("list untracked files")?;
+    Ok(changed
+        .split('\0')
+        .chain(untracked.split('\0'))
+        .filter(|name| !name.is_empty())
+        .map(|name| toplevel.join(name))
+        .collect())
+}
+
+/// Stages the current content of the given file in the git index
+pub(crate) fn stage_file(path: &Path) -> Result<()> {
+    git(&["add", "--", &path.to_string_lossy()])
+        .with_context(|| format!("stage {}", path.display()))?;
+    Ok(())
+}
+
+/// Runs git with the given arguments and returns what it printed to STDOUT
+fn git(args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .context("execute git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    String::from_utf8(output.stdout).context("git output is not utf-8 encoded")
+}
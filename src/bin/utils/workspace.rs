@@ -0,0 +1,192 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+use scrut::config::ConfigOverride;
+use scrut::config::TestCaseConfig;
+use serde::Deserialize;
+
+/// Name of the workspace file, that is looked for when walking up from the
+/// current directory or in a provided directory
+pub(crate) const WORKSPACE_FILE_NAME: &str = "scrut.workspace.yaml";
+
+/// A workspace bundles multiple suites of test documents, each with their
+/// own configuration, so that they can be run with a single invocation.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Workspace {
+    /// The suites of the workspace, in order of execution
+    pub suites: Vec<WorkspaceSuite>,
+}
+
+/// A single suite of test documents within a [`Workspace`]
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct WorkspaceSuite {
+    /// Name of the suite, which defaults to the path as provided
+    pub name: Option<String>,
+
+    /// Path to a test document or a directory containing test documents,
+    /// relative to the workspace file
+    pub path: PathBuf,
+
+    /// Configuration overrides in the form `key=value`, that are applied as
+    /// if they were provided with `--config` (which still takes precedence)
+    pub config: Vec<String>,
+
+    /// Annotations that all testcases of the suite are labeled with
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl Workspace {
+    /// Reads the workspace from the given file. Suite paths are made relative
+    /// to the directory the file is located in.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("read workspace from {}", path.display()))?;
+        let mut workspace: Self = serde_yaml::from_str(&content)
+            .with_context(|| format!("parse workspace from {}", path.display()))?;
+        if workspace.suites.is_empty() {
+            bail!("workspace {} does not define any suites", path.display());
+        }
+        let directory = path.parent().unwrap_or(Path::new(""));
+        for suite in workspace.suites.iter_mut() {
+            if suite.name.is_none() {
+                suite.name = Some(suite.path.display().to_string());
+            }
+            suite.path = directory.join(&suite.path);
+        }
+        Ok(workspace)
+    }
+
+    /// Returns the path of the workspace file. The provided path is either the
+    /// workspace file itself or a directory that contains it. If no path is
+    /// provided then the workspace file is looked for in the current directory
+    /// and all of its parents.
+    pub fn find(path: Option<&Path>) -> Result<PathBuf> {
+        if let Some(path) = path {
+            let path = if path.is_dir() {
+                path.join(WORKSPACE_FILE_NAME)
+            } else {
+                path.to_path_buf()
+            };
+            if !path.is_file() {
+                bail!("workspace file {} does not exist", path.display());
+            }
+            return Ok(path);
+        }
+        let current_directory = std::env::current_dir().context("get current directory")?;
+        current_directory
+            .ancestors()
+            .map(|directory| directory.join(WORKSPACE_FILE_NAME))
+            .find(|candidate| candidate.is_file())
+            .ok_or_else(|| {
+                anyhow!(
+                    "no {} found in {} or any parent directory",
+                    WORKSPACE_FILE_NAME,
+                    current_directory.display()
+                )
+            })
+    }
+}
+
+impl WorkspaceSuite {
+    /// Returns the name of the suite, or its path if no name was provided
+    pub fn name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| self.path.display().to_string())
+    }
+
+    /// Returns the configuration overrides of the suite, including all of
+    /// its annotations and an additional `suite` annotation with its name
+    pub fn config_overrides(&self) -> Result<Vec<ConfigOverride>> {
+        let mut overrides = self
+            .config
+            .iter()
+            .map(|value| {
+                value
+                    .parse()
+                    .with_context(|| format!("config of suite {}", self.name()))
+            })
+            .collect::<Result<Vec<ConfigOverride>>>()?;
+        let mut annotations = BTreeMap::from([("suite".to_string(), self.name())]);
+        annotations.extend(self.annotations.clone());
+        overrides.push(ConfigOverride::TestCase(TestCaseConfig {
+            annotations,
+            ..Default::default()
+        }));
+        Ok(overrides)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::time::Duration;
+
+    use scrut::config::ConfigOverride;
+    use scrut::config::DocumentConfig;
+    use scrut::config::TestCaseConfig;
+    use tempfile::TempDir;
+
+    use super::WORKSPACE_FILE_NAME;
+    use super::Workspace;
+
+    #[test]
+    fn test_load_workspace() {
+        let root = TempDir::with_prefix("workspace.").expect("create temporary directory");
+        fs::write(
+            root.path().join(WORKSPACE_FILE_NAME),
+            "suites:\n- path: cli\n- name: api\n  path: services/api\n  config:\n  - total_timeout=1m\n  annotations:\n    team: backend\n",
+        )
+        .expect("write workspace");
+
+        let path = Workspace::find(Some(root.path())).expect("find workspace");
+        assert_eq!(path, root.path().join(WORKSPACE_FILE_NAME));
+        let workspace = Workspace::load(&path).expect("load workspace");
+        assert_eq!(workspace.suites.len(), 2);
+        assert_eq!(workspace.suites[0].path, root.path().join("cli"));
+        assert_eq!(workspace.suites[0].name(), "cli");
+        assert_eq!(workspace.suites[1].name(), "api");
+        assert_eq!(
+            workspace.suites[1]
+                .config_overrides()
+                .expect("parse config overrides"),
+            vec![
+                ConfigOverride::Document(DocumentConfig {
+                    total_timeout: Some(Duration::from_secs(60)),
+                    ..Default::default()
+                }),
+                ConfigOverride::TestCase(TestCaseConfig {
+                    annotations: BTreeMap::from([
+                        ("suite".to_string(), "api".to_string()),
+                        ("team".to_string(), "backend".to_string()),
+                    ]),
+                    ..Default::default()
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_workspace_without_suites_fails() {
+        let root = TempDir::with_prefix("workspace.").expect("create temporary directory");
+        let path = root.path().join(WORKSPACE_FILE_NAME);
+        fs::write(&path, "suites: []\n").expect("write workspace");
+        assert!(Workspace::load(&path).is_err());
+    }
+}
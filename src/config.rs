@@ -5,13 +5,27 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::borrow::Cow;
 use std::collections::BTreeMap;
+use std::ffi::OsString;
 use std::fmt;
 use std::fmt::Display;
+use std::fs;
 use std::marker::PhantomData;
+use std::path::Path;
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::time::Duration;
 
+use anyhow::Context;
+use anyhow::anyhow;
+use anyhow::bail;
+use globset::GlobBuilder;
+use globset::GlobSet;
+use globset::GlobSetBuilder;
+use icu_normalizer::ComposingNormalizerBorrowed;
+use icu_normalizer::DecomposingNormalizerBorrowed;
+use regex::bytes::RegexBuilder;
 use serde::Deserialize;
 use serde::Deserializer;
 use serde::Serialize;
@@ -20,6 +34,13 @@ use serde::de;
 use serde::de::MapAccess;
 use serde::de::Visitor;
 
+use crate::diff_algorithm::DiffAlgorithm;
+use crate::output::DirectoryTree;
+use crate::output::Output;
+use crate::output::ProducedFile;
+use crate::output::directory_tree_changes;
+use crate::parsers::parser::ParserType;
+use crate::parsers::template::DocumentTemplate;
 use crate::signal::KillSignal;
 
 /// The default total (per-document) timeout in seconds
@@ -28,10 +49,58 @@ pub const DEFAULT_DOCUMENT_TIMEOUT: u64 = 900;
 /// The exit code that any test execution can return to skip all tests in one document
 pub const DEFAULT_SKIP_DOCUMENT_CODE: i32 = 80;
 
+/// The default time in seconds that processes are given to end after they
+/// received the [`TestCaseConfig::timeout_signal`], before they are killed
+pub const DEFAULT_TIMEOUT_GRACE: u64 = 5;
+
+/// The default time in seconds to wait for the conditions of a
+/// [`TestCaseWait`], if it has no timeout
+pub const DEFAULT_WAIT_TIMEOUT: u64 = 30;
+
+/// Placeholder in [`DocumentConfig::command_template`] for the arguments of
+/// the shorthand line of a testcase
+pub const COMMAND_TEMPLATE_ARGUMENTS: &str = "{args}";
+
+/// Placeholder in [`DocumentConfig::command_template`] for the directory of
+/// the test document, which is rendered as `${TESTDIR}`
+pub const COMMAND_TEMPLATE_FIXTURE: &str = "{fixture}";
+
+/// The environment variables that testcases start with, if the document
+/// configures [`DocumentConfig::hermetic_env`], besides `HOME` and `PATH`
+pub const HERMETIC_ENVIRONMENT: &[(&str, &str)] = &[
+    ("COLUMNS", "80"),
+    ("LANG", "C"),
+    ("LC_ALL", "C"),
+    ("TZ", "GMT"),
+];
+
+/// The `PATH` that testcases start with, if the document configures
+/// [`DocumentConfig::hermetic_env`]
+#[cfg(not(windows))]
+pub const HERMETIC_PATH: &str = "/usr/local/bin:/usr/bin:/bin:/usr/sbin:/sbin";
+
+/// The `PATH` that testcases start with, if the document configures
+/// [`DocumentConfig::hermetic_env`], relative to `SystemRoot`
+#[cfg(windows)]
+pub const HERMETIC_PATH: &str =
+    r"%SystemRoot%\System32;%SystemRoot%;%SystemRoot%\System32\WindowsPowerShell\v1.0";
+
+/// Environment variables that Windows requires to start processes, which are
+/// always passed through, if the document configures
+/// [`DocumentConfig::hermetic_env`]
+#[cfg(windows)]
+const HERMETIC_WINDOWS_PASSTHROUGH: &[&str] =
+    &["COMSPEC", "PATHEXT", "SYSTEMDRIVE", "SYSTEMROOT", "WINDIR"];
+
 /// Configuration for the scope of a whole document, that may contain multiple testcases
 #[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(default)]
 pub struct DocumentConfig {
+    /// Shell snippet that is run after every testcase, in the same shell
+    /// session. Its output is discarded and its exit code is ignored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_each: Option<String>,
+
     /// Include these paths in order, as if they were part of this file. All tests
     /// within the appended paths are appended to the tests defined in this file.
     /// Use-case is common/shared test tear-down. Paths must be relative to the
@@ -39,10 +108,112 @@ pub struct DocumentConfig {
     #[serde(skip_serializing_if = "<[_]>::is_empty")]
     pub append: Vec<PathBuf>,
 
+    /// Glob patterns of files in the work directory (e.g. `logs/*.log`), that
+    /// are collected into the artifacts directory if any testcase of the
+    /// document fails
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub artifacts: Vec<String>,
+
+    /// Shell snippet that is run before every testcase, in the same shell
+    /// session. Its output is discarded and its exit code is ignored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before_each: Option<String>,
+
+    /// Shell expression that is common to all testcases of the document, which
+    /// then only provide what is different with a shorthand line (`% <args>`)
+    /// that replaces the `{args}` in the template. A `{fixture}` is replaced
+    /// with the directory of the test document.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command_template: Option<String>,
+
+    /// The directory all testcases of the document are run in, unless they
+    /// configure their own `cwd` (see [`TestCaseConfig::cwd`])
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "WorkingDirectory::parse"
+    )]
+    pub cwd: Option<WorkingDirectory>,
+
     /// Defaults for per-test configurations
     #[serde(skip_serializing_if = "TestCaseConfig::is_empty")]
     pub defaults: TestCaseConfig,
 
+    /// Retires the document on schedule: running it after the given date
+    /// prints a warning, and from the optional `fail_after` date on, it fails
+    /// without being executed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<Deprecation>,
+
+    /// The container, that testcases run in with `executor: docker`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub docker: Option<DockerConfig>,
+
+    /// Path to a dotenv file with `KEY=VALUE` lines, that provide environment
+    /// variables for all testcases of the document, as if they were set in
+    /// `environment` (which takes precedence). Relative paths are resolved
+    /// against the directory of the file that contains the `env_file`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<PathBuf>,
+
+    /// Names of environment variables of the environment Scrut is run in,
+    /// that are passed through to the testcases with `hermetic_env`
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub env_passthrough: Vec<String>,
+
+    /// Environment variables that are set for all testcases of the document.
+    /// Testcases can override them with their own `environment`. Can also be
+    /// written as `env`.
+    #[serde(alias = "env", skip_serializing_if = "BTreeMap::is_empty")]
+    pub environment: BTreeMap<String, String>,
+
+    /// The backend, that runs the testcases: on the host (`local`, default)
+    /// or in a container (`docker`), as configured with `docker`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub executor: Option<ExecutorBackend>,
+
+    /// Path to a shared YAML configuration file, that provides defaults for
+    /// this configuration. The file may itself extend another file. Relative
+    /// paths are resolved against the directory of the file that contains the
+    /// `extends`, which is also the base of `append` and `prepend` paths in
+    /// the extended file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends: Option<PathBuf>,
+
+    /// Files and directories, relative to the test document, that are copied
+    /// into the work directory before the testcases run (see [`Fixture`])
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub fixtures: Vec<Fixture>,
+
+    /// Format of the document, which takes precedence over the format that
+    /// is determined by the file name or the content of the document. This
+    /// allows documents with unconventional file names to be parsed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<ParserType>,
+
+    /// Whether testcases start from a minimal environment, instead of that
+    /// of Scrut: only the [`HERMETIC_ENVIRONMENT`], with `HOME` set to the
+    /// work directory and `PATH` set to [`HERMETIC_PATH`], along with the
+    /// variables of `env_passthrough`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hermetic_env: Option<bool>,
+
+    /// Run the whole document once per listed locale, with `LC_ALL`, `LANG` and
+    /// `LANGUAGE` set to the respective locale. Testcases can be restricted to
+    /// a subset of the locales with their own `locales` configuration.
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub locales: Vec<String>,
+
+    /// Snippets of expectation lines, by name, that expectations of all
+    /// testcases refer to as `%name%`, as in `ts: '\d{4}-\d{2}-\d{2}.* (regex)'`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub macros: BTreeMap<String, String>,
+
+    /// Replacements that are applied in order to the output of all testcases,
+    /// before it is validated or written back by `update`. Use them to scrub
+    /// volatile output, like timestamps or process IDs, in a single place.
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub normalizers: Vec<OutputNormalizer>,
+
     /// Include these paths in order, as if they were part of this file. All tests
     /// within the prepend paths are prepended to the tests defined in this file.
     /// Use-case is common/shared test setup. Paths must be relative to the
@@ -50,10 +221,22 @@ pub struct DocumentConfig {
     #[serde(skip_serializing_if = "<[_]>::is_empty")]
     pub prepend: Vec<PathBuf>,
 
-    /// The path to the shell. If a full path is not provided, then the command
-    /// must be in $PATH.
+    /// Custom kinds of expectations, by their name, as in `(mycheck)`, that
+    /// run an external command to decide whether a line of output matches
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub rules: BTreeMap<String, CustomRule>,
+
+    /// The shell that runs the shell expressions: either the path to a shell
+    /// or an interpreter with arguments (see [`Shell`]). If a full path is
+    /// not provided, then the command must be in $PATH.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub shell: Option<PathBuf>,
+    pub shell: Option<Shell>,
+
+    /// Template engine that the document is rendered with before it is
+    /// parsed, so that families of similar testcases can be generated from
+    /// `vars`. Templates are rendered for Markdown documents only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<DocumentTemplate>,
 
     /// Timeout for the executions of all tests.
     #[serde(
@@ -62,6 +245,22 @@ pub struct DocumentConfig {
         serialize_with = "render_duration_opt"
     )]
     pub total_timeout: Option<Duration>,
+
+    /// Percentage of its timeout that a running testcase can consume, before
+    /// a warning that it is about to time out is reported
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_warning: Option<u8>,
+
+    /// Variables that are available when the document is rendered as a
+    /// `template`. Variables of the document take precedence over those of
+    /// the project configuration, which can be overridden on the command line.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub vars: BTreeMap<String, serde_yaml::Value>,
+
+    /// How the work directory, that the testcases run in, is shared and
+    /// whether it is kept after the execution (see [`WorkDirectoryConfig`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workdir: Option<WorkDirectoryConfig>,
 }
 
 impl DocumentConfig {
@@ -96,13 +295,38 @@ impl DocumentConfig {
     pub fn is_empty(&self) -> bool {
         self.shell.is_none()
             && self.total_timeout.is_none()
+            && self.timeout_warning.is_none()
             && self.prepend.is_empty()
             && self.append.is_empty()
+            && self.artifacts.is_empty()
+            && self.before_each.is_none()
+            && self.after_each.is_none()
+            && self.command_template.is_none()
+            && self.cwd.is_none()
+            && self.locales.is_empty()
+            && self.normalizers.is_empty()
+            && self.macros.is_empty()
+            && self.rules.is_empty()
+            && self.extends.is_none()
+            && self.fixtures.is_empty()
+            && self.format.is_none()
+            && self.hermetic_env.is_none()
+            && self.env_file.is_none()
+            && self.env_passthrough.is_empty()
+            && self.environment.is_empty()
             && self.defaults.is_empty()
+            && self.deprecated.is_none()
+            && self.docker.is_none()
+            && self.executor.is_none()
+            && self.template.is_none()
+            && self.vars.is_empty()
+            && self.workdir.is_none()
     }
 
     /// Returns a new instance that fills in unset values from the provided defaults.
-    /// Values for `append` and `prepend` are extended, not overwritten.
+    /// Values for `append`, `prepend` and `normalizers` are extended, not
+    /// overwritten, values for `environment`, `macros`, `rules` and `vars`
+    /// are merged.
     pub fn with_defaults_from(&self, defaults: &Self) -> Self {
         // append is added at the end ..
         let mut append = defaults.append.clone();
@@ -113,11 +337,90 @@ impl DocumentConfig {
         prepend.extend(defaults.prepend.clone());
 
         Self {
+            after_each: self
+                .after_each
+                .clone()
+                .or_else(|| defaults.after_each.clone()),
             append,
+            artifacts: defaults
+                .artifacts
+                .iter()
+                .chain(self.artifacts.iter())
+                .cloned()
+                .collect(),
+            before_each: self
+                .before_each
+                .clone()
+                .or_else(|| defaults.before_each.clone()),
+            command_template: self
+                .command_template
+                .clone()
+                .or_else(|| defaults.command_template.clone()),
+            cwd: self.cwd.clone().or_else(|| defaults.cwd.clone()),
+            macros: defaults
+                .macros
+                .clone()
+                .into_iter()
+                .chain(self.macros.clone())
+                .collect(),
+            normalizers: defaults
+                .normalizers
+                .iter()
+                .chain(self.normalizers.iter())
+                .cloned()
+                .collect(),
             prepend,
+            rules: defaults
+                .rules
+                .clone()
+                .into_iter()
+                .chain(self.rules.clone())
+                .collect(),
             defaults: self.defaults.with_defaults_from(&defaults.defaults),
+            deprecated: self
+                .deprecated
+                .clone()
+                .or_else(|| defaults.deprecated.clone()),
+            docker: self.docker.clone().or_else(|| defaults.docker.clone()),
+            env_file: self.env_file.clone().or_else(|| defaults.env_file.clone()),
+            env_passthrough: defaults
+                .env_passthrough
+                .iter()
+                .chain(self.env_passthrough.iter())
+                .cloned()
+                .collect(),
+            environment: defaults
+                .environment
+                .clone()
+                .into_iter()
+                .chain(self.environment.clone())
+                .collect(),
+            executor: self.executor.or(defaults.executor),
+            extends: self.extends.clone().or_else(|| defaults.extends.clone()),
+            fixtures: defaults
+                .fixtures
+                .iter()
+                .chain(self.fixtures.iter())
+                .cloned()
+                .collect(),
+            format: self.format.or(defaults.format),
+            hermetic_env: self.hermetic_env.or(defaults.hermetic_env),
+            locales: if self.locales.is_empty() {
+                defaults.locales.clone()
+            } else {
+                self.locales.clone()
+            },
             shell: self.shell.clone().or_else(|| defaults.shell.clone()),
+            template: self.template.or(defaults.template),
             total_timeout: self.total_timeout.or(defaults.total_timeout),
+            timeout_warning: self.timeout_warning.or(defaults.timeout_warning),
+            vars: defaults
+                .vars
+                .clone()
+                .into_iter()
+                .chain(self.vars.clone())
+                .collect(),
+            workdir: self.workdir.or(defaults.workdir),
         }
     }
 
@@ -126,6 +429,253 @@ impl DocumentConfig {
     pub fn with_overrides_from(&self, overrides: &Self) -> Self {
         overrides.with_defaults_from(self)
     }
+
+    /// Returns the shell expression for a testcase that is given with the
+    /// provided arguments in a shorthand line, from the `command_template`
+    pub fn render_command_template(&self, arguments: &str) -> anyhow::Result<String> {
+        let Some(template) = &self.command_template else {
+            bail!("shorthand line requires a `command_template` in the document configuration");
+        };
+        if !template.contains(COMMAND_TEMPLATE_ARGUMENTS) {
+            bail!(
+                "command_template `{}` does not contain `{}`",
+                template,
+                COMMAND_TEMPLATE_ARGUMENTS
+            );
+        }
+        Ok(template
+            .replace(COMMAND_TEMPLATE_FIXTURE, "${TESTDIR}")
+            .replace(COMMAND_TEMPLATE_ARGUMENTS, arguments))
+    }
+
+    /// Returns the environment that the processes of testcases start from,
+    /// before their `environment` is applied: either that of Scrut, or, with
+    /// `hermetic_env`, the minimal environment with `HOME` set to the given
+    /// work directory and the `env_passthrough` variables
+    pub fn base_environment(&self, work_directory: &Path) -> Vec<(OsString, OsString)> {
+        if self.hermetic_env != Some(true) {
+            return std::env::vars_os().collect();
+        }
+        let mut environment = HERMETIC_ENVIRONMENT
+            .iter()
+            .map(|(key, value)| (OsString::from(key), OsString::from(value)))
+            .collect::<BTreeMap<_, _>>();
+        environment.insert("HOME".into(), work_directory.into());
+        #[cfg(not(windows))]
+        environment.insert("PATH".into(), HERMETIC_PATH.into());
+        #[cfg(windows)]
+        {
+            let root = std::env::var("SystemRoot").unwrap_or_else(|_| r"C:\Windows".into());
+            environment.insert(
+                "PATH".into(),
+                HERMETIC_PATH.replace("%SystemRoot%", &root).into(),
+            );
+        }
+        #[cfg(windows)]
+        let passthrough = HERMETIC_WINDOWS_PASSTHROUGH
+            .iter()
+            .copied()
+            .chain(self.env_passthrough.iter().map(String::as_str));
+        #[cfg(not(windows))]
+        let passthrough = self.env_passthrough.iter().map(String::as_str);
+        for name in passthrough {
+            if let Some(value) = std::env::var_os(name) {
+                environment.insert(name.into(), value);
+            }
+        }
+        environment.into_iter().collect()
+    }
+
+    /// Returns the configured lifecycle of the work directory, or the default
+    pub fn get_workdir(&self) -> WorkDirectoryConfig {
+        self.workdir.unwrap_or_default()
+    }
+
+    /// Copies all `fixtures` from the directory of the test document into the
+    /// work directory
+    pub fn stage_fixtures(
+        &self,
+        document_directory: &Path,
+        work_directory: &Path,
+    ) -> anyhow::Result<()> {
+        for fixture in &self.fixtures {
+            fixture
+                .stage(document_directory, work_directory, &self.vars)
+                .with_context(|| format!("stage fixture {:?}", fixture.source))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the defaults for all testcases of the document, which includes
+    /// the document-wide `environment` variables and `cwd`
+    pub fn testcase_defaults(&self) -> TestCaseConfig {
+        let mut defaults = self.defaults.clone();
+        defaults.environment.extend(self.environment.clone());
+        if self.cwd.is_some() {
+            defaults.cwd = self.cwd.clone();
+        }
+        defaults
+    }
+
+    /// Returns the output with all `normalizers` applied to STDOUT and STDERR
+    pub fn normalize_output(&self, output: Output) -> anyhow::Result<Output> {
+        if self.normalizers.is_empty() {
+            return Ok(output);
+        }
+        let normalizers = self
+            .normalizers
+            .iter()
+            .map(|normalizer| {
+                RegexBuilder::new(&normalizer.pattern)
+                    .multi_line(true)
+                    .build()
+                    .map(|pattern| (pattern, normalizer.replacement.as_bytes()))
+                    .with_context(|| format!("invalid normalizer pattern `{}`", normalizer.pattern))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let normalize = |stream: &[u8]| {
+            normalizers
+                .iter()
+                .fold(stream.to_vec(), |normalized, (pattern, replacement)| {
+                    pattern.replace_all(&normalized, *replacement).into_owned()
+                })
+                .into()
+        };
+        Ok(Output {
+            stdout: normalize(&output.stdout.to_bytes()),
+            stderr: normalize(&output.stderr.to_bytes()),
+            ..output
+        })
+    }
+
+    /// Returns a new instance that fills in unset values from the file in
+    /// `extends` (and the files that one extends, recursively). Relative
+    /// paths are resolved against the provided directory.
+    pub fn with_extends_resolved(&self, directory: &Path) -> anyhow::Result<Self> {
+        self.resolve_extends(directory, &mut vec![])
+    }
+
+    fn resolve_extends(&self, directory: &Path, seen: &mut Vec<PathBuf>) -> anyhow::Result<Self> {
+        let extends = match self.extends {
+            Some(ref extends) => extends,
+            None => return Ok(self.clone()),
+        };
+        let path = std::path::absolute(directory.join(extends))
+            .with_context(|| format!("resolve path of extended {}", extends.display()))?;
+        if seen.contains(&path) {
+            bail!("circular extends of configuration {}", path.display());
+        }
+        seen.push(path.clone());
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("read extended configuration from {}", path.display()))?;
+        let mut extended: Self = serde_yaml::from_str(&content)
+            .with_context(|| format!("parse extended configuration from {}", path.display()))?;
+        let extended_directory = path.parent().unwrap_or(directory);
+        extended.append = prefix_paths(extended_directory, &extended.append);
+        extended.prepend = prefix_paths(extended_directory, &extended.prepend);
+        extended.env_file = extended
+            .env_file
+            .map(|env_file| extended_directory.join(env_file));
+        for fixture in &mut extended.fixtures {
+            fixture.source = extended_directory.join(&fixture.source);
+        }
+        let extended = extended.resolve_extends(extended_directory, seen)?;
+
+        let mut config = self.with_defaults_from(&extended);
+        config.extends = None;
+        Ok(config)
+    }
+
+    /// Returns a new instance that has the variables of the file in `env_file`
+    /// added to its `environment`, where they do not override existing values.
+    /// Relative paths are resolved against the provided directory.
+    pub fn with_env_file_loaded(&self, directory: &Path) -> anyhow::Result<Self> {
+        let env_file = match self.env_file {
+            Some(ref env_file) => directory.join(env_file),
+            None => return Ok(self.clone()),
+        };
+        let content = fs::read_to_string(&env_file)
+            .with_context(|| format!("read env file {}", env_file.display()))?;
+        let mut environment = parse_env_file(&content)
+            .with_context(|| format!("parse env file {}", env_file.display()))?;
+        environment.extend(self.environment.clone());
+
+        let mut config = self.clone();
+        config.env_file = None;
+        config.environment = environment;
+        Ok(config)
+    }
+}
+
+/// Parses the contents of a dotenv file into environment variables. Each line
+/// is of the form `KEY=VALUE`, optionally prefixed with `export`. Empty lines
+/// and lines starting with `#` are ignored. Values can be enclosed in single
+/// or double quotes, otherwise anything after ` #` is considered a comment.
+pub fn parse_env_file(content: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut environment = BTreeMap::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("line {}: expected `KEY=VALUE`", index + 1))?;
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            bail!("line {}: invalid variable name `{}`", index + 1, key);
+        }
+        let value = value.trim();
+        let value = if let Some(quoted) = unquote(value, '"') {
+            unescape_double_quoted(quoted)
+        } else if let Some(quoted) = unquote(value, '\'') {
+            quoted.to_string()
+        } else {
+            value
+                .split_once(" #")
+                .map_or(value, |(value, _)| value)
+                .trim_end()
+                .to_string()
+        };
+        environment.insert(key.to_string(), value);
+    }
+    Ok(environment)
+}
+
+/// Resolves the escape sequences `\n`, `\"` and `\\` of a double quoted
+/// value in one pass, so that an escaped backslash does not escape the
+/// character that follows it. Other backslashes are kept.
+fn unescape_double_quoted(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some(escaped @ ('"' | '\\')) => unescaped.push(escaped),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+fn unquote(value: &str, quote: char) -> Option<&str> {
+    value
+        .strip_prefix(quote)
+        .and_then(|value| value.strip_suffix(quote))
+}
+
+fn prefix_paths(prefix: &Path, paths: &[PathBuf]) -> Vec<PathBuf> {
+    paths.iter().map(|path| prefix.join(path)).collect()
 }
 
 impl Display for DocumentConfig {
@@ -139,7 +689,7 @@ fn is_none_or_default_timeout(timeout: &Option<Duration>) -> bool {
     if let Some(timeout) = timeout {
         timeout.as_secs() == DEFAULT_DOCUMENT_TIMEOUT
     } else {
-        false
+        true
     }
 }
 
@@ -167,617 +717,3964 @@ impl Display for OutputStreamControl {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
-pub struct TestCaseWait {
-    /// How long to wait for the test to run.
-    #[serde(
-        deserialize_with = "parse_duration",
-        serialize_with = "render_duration"
-    )]
-    pub timeout: Duration,
+/// How the output of testcases is compared with their expectations
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputEncoding {
+    /// Output is text, that is compared line by line with the expectations.
+    /// Characters that are not printable, or bytes that are not valid UTF-8,
+    /// are written as escape sequences.
+    #[default]
+    Utf8Lossy,
 
-    /// If set then the wait will end early once the path exists
-    pub path: Option<PathBuf>,
+    /// Output is compared as raw bytes, which are written as hex dump lines
+    /// like `00000000  89 50 4e 47  |.PNG|`, without CRLF or ANSI escape
+    /// sequences being removed
+    Bytes,
+
+    /// Output is compared as raw bytes by its SHA-256 digest, which is written
+    /// as a single `(sha256:<hex>)` expectation
+    Hash,
 }
 
-impl TestCaseWait {
-    /// Deserialize from either scalar (only timeout) or map
-    fn parse<'de, D>(deserializer: D) -> Result<Option<TestCaseWait>, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        struct TestCaseWaitParser(PhantomData<fn() -> Option<TestCaseWait>>);
+impl Display for OutputEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Utf8Lossy => "utf8-lossy",
+            Self::Bytes => "bytes",
+            Self::Hash => "hash",
+        };
+        write!(f, "{name}")
+    }
+}
 
-        impl<'de> Visitor<'de> for TestCaseWaitParser {
-            type Value = Option<TestCaseWait>;
+/// How bytes of the output of testcases that are not valid UTF-8 are handled
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InvalidUtf8 {
+    /// Invalid bytes are replaced with the Unicode replacement character `�`
+    Replace,
 
-            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("string or map")
-            }
+    /// Invalid bytes are replaced with `\xNN` escape sequences and backslashes
+    /// with `\\`, so that the original output can be restored from the text
+    Escape,
 
-            fn visit_str<E>(self, value: &str) -> Result<Option<TestCaseWait>, E>
-            where
-                E: de::Error,
-            {
-                let timeout = humantime::parse_duration(value).map_err(de::Error::custom)?;
-                Ok(Some(TestCaseWait {
-                    timeout,
-                    path: None,
-                }))
-            }
+    /// Output that contains invalid bytes fails the testcase
+    Error,
+}
 
-            fn visit_map<M>(self, map: M) -> Result<Option<TestCaseWait>, M::Error>
-            where
-                M: MapAccess<'de>,
-            {
-                let wait = TestCaseWait::deserialize(de::value::MapAccessDeserializer::new(map))?;
-                Ok(Some(wait))
-            }
+impl Display for InvalidUtf8 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Replace => "replace",
+            Self::Escape => "escape",
+            Self::Error => "error",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The Unicode normalization form that the output of testcases and their
+/// expectations are brought into before they are compared
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnicodeNormalization {
+    /// Canonical composition, e.g. `é` as the single character U+00E9
+    Nfc,
+
+    /// Canonical decomposition, e.g. `é` as `e` followed by U+0301, as macOS
+    /// returns file names
+    Nfd,
+
+    /// Output and expectations are compared as they are
+    None,
+}
+
+impl UnicodeNormalization {
+    /// Returns the text in this normalization form
+    pub fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            Self::Nfc => ComposingNormalizerBorrowed::new_nfc().normalize(text),
+            Self::Nfd => DecomposingNormalizerBorrowed::new_nfd().normalize(text),
+            Self::None => Cow::Borrowed(text),
         }
+    }
 
-        deserializer.deserialize_any(TestCaseWaitParser(PhantomData))
+    /// Returns the bytes with all valid UTF-8 in this normalization form, while
+    /// bytes that are not valid UTF-8 are kept as they are
+    pub fn normalize_bytes<'a>(&self, bytes: &'a [u8]) -> Cow<'a, [u8]> {
+        if let Ok(text) = std::str::from_utf8(bytes) {
+            return match self.normalize(text) {
+                Cow::Borrowed(_) => Cow::Borrowed(bytes),
+                Cow::Owned(normalized) => Cow::Owned(normalized.into_bytes()),
+            };
+        }
+        let mut normalized = Vec::with_capacity(bytes.len());
+        for chunk in bytes.utf8_chunks() {
+            normalized.extend_from_slice(self.normalize(chunk.valid()).as_bytes());
+            normalized.extend_from_slice(chunk.invalid());
+        }
+        Cow::Owned(normalized)
     }
 }
 
-impl Display for TestCaseWait {
+impl Display for UnicodeNormalization {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let out = serde_json::to_string(&self).map_err(|_| std::fmt::Error)?;
-        write!(f, "{}", out)
+        let name = match self {
+            Self::Nfc => "nfc",
+            Self::Nfd => "nfd",
+            Self::None => "none",
+        };
+        write!(f, "{name}")
     }
 }
 
-/// Configuration for the scope of a single [`crate::testcase::TestCase`]
-#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
-#[serde(default)]
-pub struct TestCaseConfig {
-    /// Tell Scrut that the shell expression of this test will detach itself, so
-    /// Scrut will not consider this a test (i.e. no output or exit code evaluation).
-    /// Purpose is to allow the user to detach a command (like
-    /// `nohup some-command &`) that is doing something asynchronous (e.g.
-    /// starting a server to which the tested CLI is a client).
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub detached: Option<bool>,
+/// Replaces all matches of a regular expression in the output of testcases
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct OutputNormalizer {
+    /// Regular expression, in which `^` and `$` match at the start and end of
+    /// each line
+    pub pattern: String,
 
-    /// Kill signal to send to the detached process after test execution on unix systems
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub detached_kill_signal: Option<KillSignal>,
+    /// What the matches are replaced with, which can refer to capture groups
+    /// (`$1`, `${name}`). Matches are removed if empty.
+    #[serde(default)]
+    pub replacement: String,
+}
 
-    /// If true, stops execution of the entire test document immediately if this
-    /// test case fails for any reason (exit status, snapshot validation, etc).
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub fail_fast: Option<bool>,
+/// A custom kind of expectation, whose command receives a line of output on
+/// STDIN and the expression of the expectation as its argument, and which
+/// matches if the command exits with code 0
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomRule {
+    /// Shell expression, that is run in the directory of the test document
+    pub command: String,
+}
 
-    /// A set of environment variable names and values that will be explicitly set
-    /// for the test.
-    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
-    pub environment: BTreeMap<String, String>,
+/// Deprecation of a test document, as in
+/// `deprecated: {after: 2025-06-30, fail_after: 2025-12-31, reason: "…"}`
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Deprecation {
+    /// Date after which running the document prints a warning
+    pub after: DeprecationDate,
 
-    /// Whether CRLF should be translated to LF (=false) or whether CR needs to
-    /// be explicitly handled (=true).
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub keep_crlf: Option<bool>,
+    /// Date after which the document fails, instead of being executed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fail_after: Option<DeprecationDate>,
 
-    /// Which output stream to choose when applying output expectations:
-    /// - `stdout`: All expectations apply to what is printed on STDOUT
-    /// - `stderr`: All expectations apply to what is printed on STDERR
-    /// - `combined`: STDOUT and STDERR will combined into a single stream where all expectations are applied on
-    /// - `marked` (todo): User marks which expectations are intended for which stream explicitly
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub output_stream: Option<OutputStreamControl>,
+    /// Why the document is deprecated, or what replaces it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
 
-    /// The exit code, that if returned by any test, leads to skipping of the whole file.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub skip_document_code: Option<i32>,
+impl Deprecation {
+    /// Whether the document is past the date after which it warns
+    pub fn is_expired(&self, today: &DeprecationDate) -> bool {
+        today > &self.after
+    }
 
-    /// Whether to strip ANSI escape sequences from the tested output before validation
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub strip_ansi_escaping: Option<bool>,
+    /// Whether the document is past the date after which it fails
+    pub fn is_failing(&self, today: &DeprecationDate) -> bool {
+        self.fail_after.as_ref().is_some_and(|date| today > date)
+    }
+}
 
-    /// A max execution time a test can run before it is considered failed (and
-    /// will be aborted).
-    #[serde(
-        skip_serializing_if = "Option::is_none",
-        deserialize_with = "parse_duration_opt",
-        serialize_with = "render_duration_opt"
-    )]
-    pub timeout: Option<Duration>,
+impl Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deprecated after {}", self.after)?;
+        if let Some(fail_after) = &self.fail_after {
+            write!(f, " and failing after {fail_after}")?;
+        }
+        if let Some(reason) = &self.reason {
+            write!(f, ": {reason}")?;
+        }
+        Ok(())
+    }
+}
 
-    /// Sleep for some time before starting this test (i.e. continuing with testing).
-    /// If path is provided, then wait will be aborted (and the testing continues)
-    /// as soon as path exists and the test will fail if it does not show up
-    /// within duration. The wait time does not count against timeout(), but
-    /// against total_timeout(). To be used in conjunction with detached().
-    #[serde(
-        skip_serializing_if = "Option::is_none",
-        deserialize_with = "TestCaseWait::parse"
-    )]
-    pub wait: Option<TestCaseWait>,
+/// Calendar date (in UTC) of a [`Deprecation`], written as `YYYY-MM-DD`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DeprecationDate {
+    year: u16,
+    month: u8,
+    day: u8,
 }
 
-impl TestCaseConfig {
-    /// Returns instance with all values set to [`None`]
-    pub fn empty() -> Self {
-        Self::default() // TODO: review - default may soon not be empty anymore
+impl DeprecationDate {
+    /// Returns the current date in UTC
+    pub fn today() -> Self {
+        let now = humantime::format_rfc3339_seconds(std::time::SystemTime::now()).to_string();
+        now[..10]
+            .parse()
+            .expect("RFC 3339 timestamp starts with date")
     }
+}
 
-    /// Returns default testcase configuration for Markdown documents
-    ///
-    /// Currently:
-    /// - Output Stream: STDOUT
-    /// - Skip Code: 80
-    ///
-    /// TODO: change the default output stream to Combined (adjust all uses!)
-    pub fn default_markdown() -> Self {
-        Self {
-            output_stream: Some(OutputStreamControl::Stdout),
-            skip_document_code: Some(DEFAULT_SKIP_DOCUMENT_CODE),
-            detached_kill_signal: Some(KillSignal::default()),
-            ..Default::default()
-        }
-    }
+impl FromStr for DeprecationDate {
+    type Err = anyhow::Error;
 
-    /// Returns default testcase configuration for Cram documents
-    ///
-    /// Currently:
-    /// - Output Stream: Combined STDOUT and STDERR
-    /// - Skip Code: 80
-    /// - Keep CRLF in output
-    pub fn default_cram() -> Self {
-        Self {
-            output_stream: Some(OutputStreamControl::Combined),
-            keep_crlf: Some(true),
-            skip_document_code: Some(DEFAULT_SKIP_DOCUMENT_CODE),
-            ..Default::default()
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow!("invalid date `{s}`, expected `YYYY-MM-DD`");
+        let mut parts = s.splitn(3, '-');
+        let (Some(year), Some(month), Some(day)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return Err(invalid());
         }
-    }
 
-    /// Returns true if none the configuration parameters are set
-    pub fn is_empty(&self) -> bool {
-        self.output_stream.is_none()
-            && self.keep_crlf.is_none()
-            && self.timeout.is_none()
-            && self.detached.is_none()
-            && self.fail_fast.is_none()
-            && self.wait.is_none()
-            && self.skip_document_code.is_none()
-            && self.strip_ansi_escaping.is_none()
-            && self.environment.is_empty()
+        // validates the day of the month, including leap years
+        humantime::parse_rfc3339(&format!("{s}T00:00:00Z")).map_err(|_| invalid())?;
+        Ok(Self {
+            year: year.parse().map_err(|_| invalid())?,
+            month: month.parse().map_err(|_| invalid())?,
+            day: day.parse().map_err(|_| invalid())?,
+        })
     }
+}
 
-    /// Returns a new instance that fills in unset values from the provided defaults
-    pub fn with_defaults_from(&self, defaults: &Self) -> Self {
-        Self {
-            output_stream: self
-                .output_stream
-                .clone()
-                .or_else(|| defaults.output_stream.clone()),
-            keep_crlf: self.keep_crlf.or(defaults.keep_crlf),
-            timeout: self.timeout.or(defaults.timeout),
-            environment: self
-                .environment
-                .clone()
-                .into_iter()
-                .chain(defaults.environment.clone())
-                .collect(),
-            detached: self.detached.or(defaults.detached),
-            detached_kill_signal: self
-                .detached_kill_signal
-                .clone()
-                .or_else(|| defaults.detached_kill_signal.clone()),
-            fail_fast: self.fail_fast.or(defaults.fail_fast),
-            wait: self.wait.clone().or_else(|| defaults.wait.clone()),
-            skip_document_code: self.skip_document_code.or(defaults.skip_document_code),
-            strip_ansi_escaping: self.strip_ansi_escaping.or(defaults.strip_ansi_escaping),
-        }
+impl Display for DeprecationDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
     }
+}
 
-    /// Returns a new instance that is overridden with provided (set) values
-    pub fn with_overrides_from(&self, overrides: &Self) -> Self {
-        overrides.with_defaults_from(self)
+impl Serialize for DeprecationDate {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
     }
+}
 
-    /// Returns a new configuration that contains the provided environment variables
-    pub fn with_environment(&self, environment: &BTreeMap<&str, &str>) -> Self {
-        let mut config = self.clone();
-        for (key, value) in environment {
-            config.environment.insert((*key).into(), (*value).into());
-        }
-        config
+impl<'de> Deserialize<'de> for DeprecationDate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
     }
+}
 
-    /// Return new configuration that does not contain the provided environment
-    /// variables - unless they have a different value.
-    pub fn without_environment(&self, environment: &BTreeMap<&str, &str>) -> Self {
-        let mut config = self.clone();
-        for (key, value) in environment {
-            if config.environment.get(*key) == Some(&value.to_string()) {
-                config.environment.remove(*key);
-            }
+/// The backend, that runs the testcases of a document
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutorBackend {
+    /// Testcases run on the host, in the work directory
+    #[default]
+    Local,
+
+    /// Testcases run in a container, that is created from the image of the
+    /// [`DockerConfig`] for each document
+    Docker,
+}
+
+impl Display for ExecutorBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local => write!(f, "local"),
+            Self::Docker => write!(f, "docker"),
         }
-        config
     }
+}
 
-    /// Returns what makes this configuration different from another one.
-    /// Will be equal to [`Self::empty()`] if they are equal.
-    pub fn diff(&self, other: &Self) -> Self {
-        let mut diff = Self::empty();
-        if self.output_stream != other.output_stream {
-            diff.output_stream = self.output_stream.clone();
-        }
-        if self.keep_crlf != other.keep_crlf {
-            diff.keep_crlf = self.keep_crlf;
-        }
-        if self.timeout != other.timeout {
-            diff.timeout = self.timeout;
-        }
-        if self.detached != other.detached {
-            diff.detached = self.detached;
-        }
-        if self.fail_fast != other.fail_fast {
-            diff.fail_fast = self.fail_fast;
-        }
-        if self.skip_document_code != other.skip_document_code {
-            diff.skip_document_code = self.skip_document_code;
-        }
-        if self.strip_ansi_escaping != other.strip_ansi_escaping {
-            diff.strip_ansi_escaping = self.strip_ansi_escaping;
-        }
-        if self.wait != other.wait {
-            diff.wait = self.wait.clone();
-        }
+/// The container, in which the testcases of a document run with the `docker`
+/// [`ExecutorBackend`], as in `docker: {image: "ubuntu:24.04"}`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DockerConfig {
+    /// The image, that the container is created from
+    pub image: String,
 
-        // difference here is: all env vars that are set in self, but not in other
-        // and all that env vars that have different values in self than in other
-        if self.environment != other.environment {
-            let mut env_diff = self.environment.clone();
-            for (k, v) in other.environment.iter() {
-                if env_diff.get(k) != Some(v) {
-                    env_diff.remove(k);
-                }
-            }
-            diff.environment = env_diff;
-        }
+    /// Volumes, that are mounted into the container, as
+    /// `<host path>:<container path>[:ro]`. Relative host paths are resolved
+    /// against the directory of the test document.
+    #[serde(default, skip_serializing_if = "<[_]>::is_empty")]
+    pub mounts: Vec<String>,
 
-        diff
-    }
+    /// Environment variables, that are set in the container
+    #[serde(default, alias = "env", skip_serializing_if = "BTreeMap::is_empty")]
+    pub environment: BTreeMap<String, String>,
+}
 
-    /// Serde YAML does not support one-line-formatted YAML and currently Scrut
-    /// supports only parsing one-line-formatted YAML.
-    /// This implementation provides a least-effort
-    pub fn to_yaml_one_liner(&self) -> String {
-        let mut output = vec![];
-        if let Some(ref value) = self.output_stream {
-            output.push(format!(
-                "output_stream: {}",
-                value.to_string().to_lowercase()
-            ));
-        }
-        if let Some(value) = self.keep_crlf {
-            output.push(format!("keep_crlf: {}", value))
-        }
-        if let Some(value) = self.timeout {
-            output.push(format!("timeout: {}", humantime::format_duration(value)))
-        }
-        if let Some(value) = self.detached {
-            output.push(format!("detached: {}", value))
-        }
-        if let Some(value) = self.fail_fast {
-            output.push(format!("fail_fast: {}", value))
-        }
-        if let Some(value) = self.skip_document_code {
-            output.push(format!("skip_document_code: {}", value))
-        }
-        if let Some(value) = self.strip_ansi_escaping {
-            output.push(format!("strip_ansi_escaping: {}", value))
-        }
-        if let Some(ref wait) = self.wait {
-            let duration = humantime::format_duration(wait.timeout).to_string();
-            if let Some(ref path) = wait.path {
-                output.push(format!(
-                    "wait: {{timeout: {}, path: {}}}",
-                    duration,
-                    path.to_string_lossy(),
-                ))
-            } else {
-                output.push(format!("wait: {}", duration))
+/// The lifecycle of the work directory of a document, as in
+/// `workdir: {scope: per-testcase, keep: on-failure}`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkDirectoryConfig {
+    /// Which testcases share a work directory
+    #[serde(default)]
+    pub scope: WorkDirectoryScope,
+
+    /// When the work directory is kept after the execution, instead of being
+    /// removed
+    #[serde(default)]
+    pub keep: WorkDirectoryKeep,
+}
+
+/// Which testcases share a work directory
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WorkDirectoryScope {
+    /// All testcases of a document share a work directory of their own
+    #[default]
+    PerDocument,
+
+    /// Each testcase runs in a fresh directory of its own, within the work
+    /// directory of the document
+    PerTestcase,
+
+    /// All documents of an execution, that are configured so, share a
+    /// single work directory
+    Shared,
+}
+
+/// When the work directory is kept after the execution
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WorkDirectoryKeep {
+    /// The work directory is removed (unless `--keep-temporary-directories`)
+    #[default]
+    Never,
+
+    /// The work directory is kept, if any testcase failed
+    OnFailure,
+
+    /// The work directory is always kept
+    Always,
+}
+
+/// How testcases that assert nothing are handled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmptyTestsPolicy {
+    /// Testcases without assertions are allowed
+    Allow,
+
+    /// Testcases without assertions are reported as a warning
+    Warn,
+
+    /// Testcases without assertions fail
+    Fail,
+}
+
+impl EmptyTestsPolicy {
+    /// Deserialize from either boolean (allow or fail) or `warn`
+    fn parse<'de, D>(deserializer: D) -> Result<Option<EmptyTestsPolicy>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct EmptyTestsPolicyParser(PhantomData<fn() -> Option<EmptyTestsPolicy>>);
+
+        impl Visitor<'_> for EmptyTestsPolicyParser {
+            type Value = Option<EmptyTestsPolicy>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("boolean or `warn`")
             }
-        }
-        if !self.environment.is_empty() {
-            let mut envvars = vec![];
-            for (key, value) in self.environment.iter() {
-                // TODO: this will bereak break if the value contains double quotes => use `quote-string` crate?
-                envvars.push(format!("{}: \"{}\"", key, value))
+
+            fn visit_bool<E>(self, value: bool) -> Result<Option<EmptyTestsPolicy>, E>
+            where
+                E: de::Error,
+            {
+                Ok(Some(if value {
+                    EmptyTestsPolicy::Allow
+                } else {
+                    EmptyTestsPolicy::Fail
+                }))
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Option<EmptyTestsPolicy>, E>
+            where
+                E: de::Error,
+            {
+                match value {
+                    "warn" => Ok(Some(EmptyTestsPolicy::Warn)),
+                    _ => Err(de::Error::invalid_value(de::Unexpected::Str(value), &self)),
+                }
             }
-            output.push(format!("environment: {{{}}}", envvars.join(", ")));
         }
-        format!("{{{}}}", output.join(", "))
-    }
 
-    pub fn get_skip_document_code(&self) -> i32 {
-        self.skip_document_code
-            .unwrap_or(DEFAULT_SKIP_DOCUMENT_CODE)
+        deserializer.deserialize_any(EmptyTestsPolicyParser(PhantomData))
     }
+}
 
-    pub fn get_fail_fast(&self) -> bool {
-        self.fail_fast.unwrap_or(false)
+impl Serialize for EmptyTestsPolicy {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Allow => serializer.serialize_bool(true),
+            Self::Warn => serializer.serialize_str("warn"),
+            Self::Fail => serializer.serialize_bool(false),
+        }
     }
 }
 
-impl Display for TestCaseConfig {
+impl Display for EmptyTestsPolicy {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let out = serde_json::to_string(&self).map_err(|_| std::fmt::Error)?;
-        write!(f, "{}", out)
+        match self {
+            Self::Allow => write!(f, "true"),
+            Self::Warn => write!(f, "warn"),
+            Self::Fail => write!(f, "false"),
+        }
     }
 }
 
-fn parse_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let value: String = Deserialize::deserialize(deserializer)?;
-    let duration = humantime::parse_duration(&value).map_err(de::Error::custom)?;
-    Ok(duration)
-}
+/// An amount of memory, in bytes, that is written as an integer (bytes) or
+/// as a string with a decimal (`KB`, `MB`, `GB`) or binary (`KiB`, `MiB`,
+/// `GiB`) unit, e.g. `512MiB`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ByteSize(pub u64);
 
-fn parse_duration_opt<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let value: String = Deserialize::deserialize(deserializer)?;
-    if value.is_empty() || value == "null" {
-        return Ok(None);
+impl ByteSize {
+    const UNITS: [(&'static str, u64); 7] = [
+        ("GiB", 1 << 30),
+        ("MiB", 1 << 20),
+        ("KiB", 1 << 10),
+        ("GB", 1_000_000_000),
+        ("MB", 1_000_000),
+        ("KB", 1_000),
+        ("B", 1),
+    ];
+
+    /// Deserialize from either an integer or a string with unit
+    fn parse<'de, D>(deserializer: D) -> Result<Option<ByteSize>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ByteSizeParser(PhantomData<fn() -> Option<ByteSize>>);
+
+        impl Visitor<'_> for ByteSizeParser {
+            type Value = Option<ByteSize>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("amount of bytes, optionally with unit (e.g. `512MiB`)")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Option<ByteSize>, E>
+            where
+                E: de::Error,
+            {
+                Ok(Some(ByteSize(value)))
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Option<ByteSize>, E>
+            where
+                E: de::Error,
+            {
+                value
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(value), &self))
+            }
+        }
+
+        deserializer.deserialize_any(ByteSizeParser(PhantomData))
     }
-    let duration = humantime::parse_duration(&value).map_err(de::Error::custom)?;
-    Ok(Some(duration))
 }
 
-fn render_duration<S>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    let value = humantime::format_duration(*value).to_string();
-    serializer.serialize_str(&value)
+impl FromStr for ByteSize {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        let value = value.trim();
+        let (amount, factor) = Self::UNITS
+            .iter()
+            .find_map(|(unit, factor)| {
+                value
+                    .strip_suffix(unit)
+                    .map(|amount| (amount.trim_end(), *factor))
+            })
+            .unwrap_or((value, 1));
+        let amount = amount
+            .parse::<u64>()
+            .with_context(|| format!("invalid amount of bytes `{value}`"))?;
+        amount
+            .checked_mul(factor)
+            .map(ByteSize)
+            .ok_or_else(|| anyhow!("amount of bytes `{value}` is too large"))
+    }
 }
 
-fn render_duration_opt<S>(value: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    let value = if let Some(value) = value {
-        humantime::format_duration(*value).to_string()
-    } else {
-        "null".to_string()
-    };
-    serializer.serialize_str(&value)
+impl Display for ByteSize {
+    /// Renders with the largest binary unit that represents the size exactly
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (unit, factor) = Self::UNITS
+            .iter()
+            .filter(|(unit, _)| unit.ends_with("iB") || *unit == "B")
+            .find(|(_, factor)| self.0 > 0 && self.0 % factor == 0)
+            .unwrap_or(&("B", 1));
+        write!(f, "{}{unit}", self.0 / factor)
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use std::collections::BTreeMap;
-    use std::path::PathBuf;
-    use std::time::Duration;
+impl Serialize for ByteSize {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// How many matching lines surround the mismatches in the failure output of
+/// a testcase, which is written as an integer or as `full` for all lines
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffContext {
+    /// At most this many matching lines before and after each mismatch
+    Lines(usize),
+
+    /// All lines of the testcase
+    Full,
+}
+
+impl DiffContext {
+    /// Deserialize from either an integer or `full`
+    fn parse<'de, D>(deserializer: D) -> Result<Option<DiffContext>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct DiffContextParser(PhantomData<fn() -> Option<DiffContext>>);
+
+        impl Visitor<'_> for DiffContextParser {
+            type Value = Option<DiffContext>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("amount of lines or `full`")
+            }
+
+            fn visit_u64<E>(self, value: u64) -> Result<Option<DiffContext>, E>
+            where
+                E: de::Error,
+            {
+                Ok(Some(DiffContext::Lines(value as usize)))
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Option<DiffContext>, E>
+            where
+                E: de::Error,
+            {
+                value
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(value), &self))
+            }
+        }
+
+        deserializer.deserialize_any(DiffContextParser(PhantomData))
+    }
+}
+
+impl FromStr for DiffContext {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        match value.trim() {
+            "full" => Ok(Self::Full),
+            lines => lines.parse().map(Self::Lines).with_context(|| {
+                format!("invalid diff context `{value}`, expected amount of lines or `full`")
+            }),
+        }
+    }
+}
+
+impl Display for DiffContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lines(lines) => write!(f, "{lines}"),
+            Self::Full => write!(f, "full"),
+        }
+    }
+}
+
+impl Serialize for DiffContext {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Lines(lines) => serializer.serialize_u64(*lines as u64),
+            Self::Full => serializer.serialize_str("full"),
+        }
+    }
+}
+
+/// Dimensions of a terminal, in characters
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TerminalSize {
+    /// Number of characters per line
+    pub columns: u16,
+
+    /// Number of lines
+    pub rows: u16,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TestCaseWait {
+    /// How long to wait for the test to run.
+    #[serde(
+        default = "default_wait_timeout",
+        deserialize_with = "parse_duration",
+        serialize_with = "render_duration"
+    )]
+    pub timeout: Duration,
+
+    /// If set then the wait will end early once the path exists
+    pub path: Option<PathBuf>,
+
+    /// If set then the wait will end early once a TCP connection to the port
+    /// on `localhost` can be established
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+
+    /// If set then the wait will end early once a `GET` request to the
+    /// (`http://`) URL is answered with the expected `status`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<String>,
+
+    /// The HTTP status code that ends the wait for `http`. Any successful
+    /// status (`2xx`) does, if it is not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+}
+
+fn default_wait_timeout() -> Duration {
+    Duration::from_secs(DEFAULT_WAIT_TIMEOUT)
+}
+
+impl TestCaseWait {
+    /// Deserialize from either scalar (only timeout) or map
+    fn parse<'de, D>(deserializer: D) -> Result<Option<TestCaseWait>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct TestCaseWaitParser(PhantomData<fn() -> Option<TestCaseWait>>);
+
+        impl<'de> Visitor<'de> for TestCaseWaitParser {
+            type Value = Option<TestCaseWait>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("string or map")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Option<TestCaseWait>, E>
+            where
+                E: de::Error,
+            {
+                let timeout = humantime::parse_duration(value).map_err(de::Error::custom)?;
+                Ok(Some(TestCaseWait {
+                    timeout,
+                    path: None,
+                    port: None,
+                    http: None,
+                    status: None,
+                }))
+            }
+
+            fn visit_map<M>(self, map: M) -> Result<Option<TestCaseWait>, M::Error>
+            where
+                M: MapAccess<'de>,
+            {
+                let wait = TestCaseWait::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                if let Some(ref url) = wait.http {
+                    if !url.starts_with("http://") {
+                        return Err(de::Error::custom(format!(
+                            "wait for `{url}` is not supported, only `http://` URLs are"
+                        )));
+                    }
+                }
+                if wait.status.is_some() && wait.http.is_none() {
+                    return Err(de::Error::custom("wait for `status` requires `http`"));
+                }
+                Ok(Some(wait))
+            }
+        }
+
+        deserializer.deserialize_any(TestCaseWaitParser(PhantomData))
+    }
+}
+
+impl Display for TestCaseWait {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let out = serde_json::to_string(&self).map_err(|_| std::fmt::Error)?;
+        write!(f, "{}", out)
+    }
+}
+
+/// The standard input a testcase is provided with
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum TestCaseStdin {
+    /// The literal content
+    Content(String),
+
+    /// A file from which the content is read. Relative paths are resolved
+    /// against the directory of the test document.
+    File { file: PathBuf },
+}
+
+impl TestCaseStdin {
+    /// Deserialize from either scalar (content) or map (file)
+    fn parse<'de, D>(deserializer: D) -> Result<Option<TestCaseStdin>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct TestCaseStdinParser(PhantomData<fn() -> Option<TestCaseStdin>>);
+
+        impl<'de> Visitor<'de> for TestCaseStdinParser {
+            type Value = Option<TestCaseStdin>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("string or map with `file`")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Option<TestCaseStdin>, E>
+            where
+                E: de::Error,
+            {
+                Ok(Some(TestCaseStdin::Content(value.to_string())))
+            }
+
+            fn visit_map<M>(self, map: M) -> Result<Option<TestCaseStdin>, M::Error>
+            where
+                M: MapAccess<'de>,
+            {
+                #[derive(Deserialize)]
+                #[serde(deny_unknown_fields)]
+                struct StdinFile {
+                    file: PathBuf,
+                }
+                let stdin = StdinFile::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                Ok(Some(TestCaseStdin::File { file: stdin.file }))
+            }
+        }
+
+        deserializer.deserialize_any(TestCaseStdinParser(PhantomData))
+    }
+
+    /// Returns the content that is provided as standard input, reading it
+    /// from the file relative to the given directory, if configured so
+    pub fn read(&self, directory: &Path) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Content(content) => Ok(content.as_bytes().to_vec()),
+            Self::File { file } => {
+                let path = directory.join(file);
+                fs::read(&path).with_context(|| format!("read stdin file {:?}", path))
+            }
+        }
+    }
+}
+
+/// The working directory a testcase is run in
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum WorkingDirectory {
+    /// A path, that relative paths are resolved against the work directory
+    Work(PathBuf),
+
+    /// A path, that relative paths are resolved against the directory of the
+    /// test document (`{document: path}`)
+    Document { document: PathBuf },
+}
+
+impl WorkingDirectory {
+    /// Deserialize from either scalar (work directory) or map (document)
+    fn parse<'de, D>(deserializer: D) -> Result<Option<WorkingDirectory>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct WorkingDirectoryParser(PhantomData<fn() -> Option<WorkingDirectory>>);
+
+        impl<'de> Visitor<'de> for WorkingDirectoryParser {
+            type Value = Option<WorkingDirectory>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("string or map with `document`")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Option<WorkingDirectory>, E>
+            where
+                E: de::Error,
+            {
+                Ok(Some(WorkingDirectory::Work(value.into())))
+            }
+
+            fn visit_map<M>(self, map: M) -> Result<Option<WorkingDirectory>, M::Error>
+            where
+                M: MapAccess<'de>,
+            {
+                #[derive(Deserialize)]
+                #[serde(deny_unknown_fields)]
+                struct DocumentDirectory {
+                    document: PathBuf,
+                }
+                let directory =
+                    DocumentDirectory::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                Ok(Some(WorkingDirectory::Document {
+                    document: directory.document,
+                }))
+            }
+        }
+
+        deserializer.deserialize_any(WorkingDirectoryParser(PhantomData))
+    }
+
+    /// Returns the path of the working directory, resolving relative paths
+    /// against the given work directory or the given directory of the test
+    /// document
+    pub fn resolve(&self, work_directory: &Path, document_directory: &Path) -> PathBuf {
+        match self {
+            Self::Work(path) => work_directory.join(path),
+            Self::Document { document } => document_directory.join(document),
+        }
+    }
+}
+
+/// Checks of the files in the work directory, that are evaluated after the
+/// shell expression ran, so that its side effects can be asserted without
+/// printing them into the output (see [`TestCaseConfig::assert_files`]).
+/// Paths are relative to the work directory.
+#[derive(Clone, Debug, Default, Derivative, Deserialize, Serialize)]
+#[derivative(PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct FileAssertions {
+    /// Paths that must exist. Paths that end in `/` must be directories.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exists: Vec<String>,
+
+    /// Paths of files or directories that must not exist
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub absent: Vec<String>,
+
+    /// Paths of files and the contents they must have: either literally, or,
+    /// if the value starts with `@`, those of the golden file at the path
+    /// that follows, which is relative to the test document
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub matches: BTreeMap<String, String>,
+
+    /// Paths of directories and the golden directories, whose structure and
+    /// file contents they must have
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub trees: BTreeMap<String, TreeAssertion>,
+
+    /// The contents of the golden files, once they were loaded from the
+    /// directory of the test document, or `None` for those that do not exist
+    #[serde(skip)]
+    #[derivative(PartialEq = "ignore")]
+    golden_files: BTreeMap<String, Option<Vec<u8>>>,
+
+    /// The golden directories of the `trees`, by the path of the directory
+    /// they are compared with, once they were loaded from the directory of
+    /// the test document, or `None` for those that do not exist
+    #[serde(skip)]
+    #[derivative(PartialEq = "ignore")]
+    golden_trees: BTreeMap<String, Option<DirectoryTree>>,
+}
+
+impl FileAssertions {
+    pub fn is_empty(&self) -> bool {
+        self.exists.is_empty()
+            && self.absent.is_empty()
+            && self.matches.is_empty()
+            && self.trees.is_empty()
+    }
+
+    /// Returns all paths, relative to the work directory, that are checked,
+    /// except for those of the `trees`
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.exists
+            .iter()
+            .chain(&self.absent)
+            .chain(self.matches.keys())
+            .map(String::as_str)
+    }
+
+    /// Loads the contents of the golden files that `matches` refer to, and
+    /// the golden directories of the `trees`, from the directory of the test
+    /// document
+    pub fn load_golden_files(&mut self, document_directory: &Path) {
+        self.golden_trees = self
+            .trees
+            .iter()
+            .map(|(path, tree)| {
+                let golden = match tree.read(&document_directory.join(&tree.golden)) {
+                    ProducedFile::Tree(golden) => Some(golden),
+                    _ => None,
+                };
+                (path.clone(), golden)
+            })
+            .collect();
+        self.golden_files = self
+            .matches
+            .values()
+            .filter_map(|expected| expected.strip_prefix('@'))
+            .map(|golden| {
+                (
+                    golden.to_string(),
+                    fs::read(document_directory.join(golden)).ok(),
+                )
+            })
+            .collect();
+    }
+
+    /// Returns the failed checks, as the check and the reason it failed, for
+    /// the files that the execution produced
+    pub fn check(&self, produced_files: &BTreeMap<String, ProducedFile>) -> Vec<(String, String)> {
+        let produced = |path: &str| produced_files.get(path).unwrap_or(&ProducedFile::Missing);
+        let mut failures = vec![];
+        for path in &self.exists {
+            let reason = match produced(path) {
+                ProducedFile::Missing => format!("{path} does not exist"),
+                ProducedFile::File(_) if path.ends_with('/') => {
+                    format!("{path} is not a directory")
+                }
+                _ => continue,
+            };
+            failures.push((format!("assert_files.exists {path}"), reason));
+        }
+        for path in &self.absent {
+            if produced(path) != &ProducedFile::Missing {
+                failures.push((
+                    format!("assert_files.absent {path}"),
+                    format!("{path} exists"),
+                ));
+            }
+        }
+        for (path, expected) in &self.matches {
+            let reason = match (produced(path), self.expected_contents(expected)) {
+                (ProducedFile::Missing, _) => format!("{path} does not exist"),
+                (ProducedFile::Directory | ProducedFile::Tree(_), _) => {
+                    format!("{path} is a directory")
+                }
+                (_, None) => format!("golden file {} does not exist", &expected[1..]),
+                (ProducedFile::File(contents), Some(expected)) if contents != expected => {
+                    format!("contents of {path} differ")
+                }
+                _ => continue,
+            };
+            failures.push((format!("assert_files.matches {path}: {expected}"), reason));
+        }
+        for (path, tree) in &self.trees {
+            let golden = self.golden_trees.get(path).and_then(Option::as_ref);
+            let reason = match (produced(path), golden) {
+                (ProducedFile::Missing, _) => format!("{path} does not exist"),
+                (ProducedFile::File(_), _) => format!("{path} is not a directory"),
+                (_, None) => format!("golden directory {} does not exist", tree.golden),
+                (ProducedFile::Tree(produced), Some(golden)) => {
+                    let differences = directory_tree_changes(golden, produced)
+                        .into_iter()
+                        .map(|(entry, state)| match state {
+                            ProducedFile::Missing => format!("missing {entry}"),
+                            _ if golden.contains_key(&entry) => format!("different {entry}"),
+                            _ => format!("unexpected {entry}"),
+                        })
+                        .collect::<Vec<_>>();
+                    if differences.is_empty() {
+                        continue;
+                    }
+                    format!(
+                        "{path} differs from {}: {}",
+                        tree.golden,
+                        differences.join(", ")
+                    )
+                }
+                _ => continue,
+            };
+            failures.push((
+                format!("assert_files.trees {path}: {}", tree.golden),
+                reason,
+            ));
+        }
+        failures
+    }
+
+    /// Returns the paths of golden files, relative to the test document, that
+    /// the produced files did not match, and the contents they are updated
+    /// with
+    pub fn golden_file_updates(
+        &self,
+        produced_files: &BTreeMap<String, ProducedFile>,
+    ) -> Vec<(String, Vec<u8>)> {
+        self.matches
+            .iter()
+            .filter_map(|(path, expected)| {
+                let golden = expected.strip_prefix('@')?;
+                match produced_files.get(path) {
+                    Some(ProducedFile::File(contents))
+                        if self.expected_contents(expected) != Some(contents) =>
+                    {
+                        Some((golden.to_string(), contents.clone()))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the `trees` whose golden directory the produced directory did
+    /// not match, along with the produced directory it is updated with
+    pub fn golden_tree_updates<'a>(
+        &'a self,
+        produced_files: &'a BTreeMap<String, ProducedFile>,
+    ) -> Vec<(&'a TreeAssertion, &'a DirectoryTree)> {
+        self.trees
+            .iter()
+            .filter_map(|(path, tree)| match produced_files.get(path) {
+                Some(ProducedFile::Tree(produced))
+                    if self.golden_trees.get(path).and_then(Option::as_ref) != Some(produced) =>
+                {
+                    Some((tree, produced))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the expected contents of a file, as given in `matches`, or
+    /// `None` if the golden file does not exist (or was not loaded)
+    fn expected_contents<'a>(&'a self, expected: &'a str) -> Option<&'a [u8]> {
+        match expected.strip_prefix('@') {
+            Some(golden) => self.golden_files.get(golden)?.as_deref(),
+            None => Some(expected.as_bytes()),
+        }
+    }
+}
+
+/// A golden directory, that a directory in the work directory is compared
+/// with (see [`FileAssertions::trees`]). Given as a path, it is the path of
+/// the golden directory. Given as a map, paths can be `ignore`d in both.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TreeAssertion {
+    /// Path of the golden directory, relative to the test document
+    pub golden: String,
+
+    /// Glob patterns of paths, relative to the compared directories, that are
+    /// ignored, along with everything within them. Patterns are also matched
+    /// against the names of files and directories at any depth.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<String>,
+}
+
+impl TreeAssertion {
+    /// Returns the state of the directory at the given path, with all files
+    /// and directories within that are not ignored
+    pub fn read(&self, path: &Path) -> ProducedFile {
+        let ignore = self
+            .ignore_matcher()
+            .expect("ignore patterns are validated when parsed");
+        ProducedFile::read_tree(path, &ignore)
+    }
+
+    fn ignore_matcher(&self) -> anyhow::Result<GlobSet> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &self.ignore {
+            builder.add(
+                GlobBuilder::new(pattern)
+                    .literal_separator(true)
+                    .build()
+                    .with_context(|| format!("invalid ignore pattern {pattern:?}"))?,
+            );
+        }
+        builder.build().context("build ignore patterns")
+    }
+}
+
+impl<'de> Deserialize<'de> for TreeAssertion {
+    /// Deserialize from either scalar (golden) or map
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct TreeAssertionParser;
+
+        impl<'de> Visitor<'de> for TreeAssertionParser {
+            type Value = TreeAssertion;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("string or map with `golden`")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<TreeAssertion, E>
+            where
+                E: de::Error,
+            {
+                Ok(TreeAssertion {
+                    golden: value.into(),
+                    ignore: vec![],
+                })
+            }
+
+            fn visit_map<M>(self, map: M) -> Result<TreeAssertion, M::Error>
+            where
+                M: MapAccess<'de>,
+            {
+                #[derive(Deserialize)]
+                #[serde(deny_unknown_fields)]
+                struct TreeAssertionMap {
+                    golden: String,
+                    #[serde(default)]
+                    ignore: Vec<String>,
+                }
+                let tree =
+                    TreeAssertionMap::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                let tree = TreeAssertion {
+                    golden: tree.golden,
+                    ignore: tree.ignore,
+                };
+                tree.ignore_matcher()
+                    .map_err(|err| de::Error::custom(format!("{err:#}")))?;
+                Ok(tree)
+            }
+        }
+
+        deserializer.deserialize_any(TreeAssertionParser)
+    }
+}
+
+/// A file or directory, that is copied from the directory of the test
+/// document into the work directory before the testcases run. Given as a path,
+/// it is copied under its file name. Given as a map, the `target` path within
+/// the work directory can be chosen and files can be rendered as a `template`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Fixture {
+    /// Path of the file or directory, relative to the test document
+    pub source: PathBuf,
+
+    /// Path within the work directory, that the fixture is copied to.
+    /// Defaults to the file name of the `source`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<PathBuf>,
+
+    /// Template engine that the copied files are rendered with. The `vars` of
+    /// the document are available, as well as `TESTDIR` (the directory of the
+    /// test document) and `WORKDIR` (the work directory).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<DocumentTemplate>,
+}
+
+impl<'de> Deserialize<'de> for Fixture {
+    /// Deserialize from either scalar (source) or map
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct FixtureParser;
+
+        impl<'de> Visitor<'de> for FixtureParser {
+            type Value = Fixture;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("string or map with `source`")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Fixture, E>
+            where
+                E: de::Error,
+            {
+                Ok(Fixture {
+                    source: value.into(),
+                    target: None,
+                    template: None,
+                })
+            }
+
+            fn visit_map<M>(self, map: M) -> Result<Fixture, M::Error>
+            where
+                M: MapAccess<'de>,
+            {
+                #[derive(Deserialize)]
+                #[serde(deny_unknown_fields)]
+                struct FixtureMap {
+                    source: PathBuf,
+                    #[serde(default)]
+                    target: Option<PathBuf>,
+                    #[serde(default)]
+                    template: Option<DocumentTemplate>,
+                }
+                let fixture = FixtureMap::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                Ok(Fixture {
+                    source: fixture.source,
+                    target: fixture.target,
+                    template: fixture.template,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(FixtureParser)
+    }
+}
+
+impl Fixture {
+    /// Copies the fixture from the directory of the test document into the
+    /// work directory, rendering files as configured
+    pub fn stage(
+        &self,
+        document_directory: &Path,
+        work_directory: &Path,
+        vars: &BTreeMap<String, serde_yaml::Value>,
+    ) -> anyhow::Result<()> {
+        let target = match self.target {
+            Some(ref target) => target.as_path(),
+            None => Path::new(
+                self.source
+                    .file_name()
+                    .ok_or_else(|| anyhow!("source has no file name"))?,
+            ),
+        };
+        if target
+            .components()
+            .any(|component| !matches!(component, std::path::Component::Normal(_)))
+        {
+            bail!("target {target:?} must be a path within the work directory");
+        }
+
+        let mut vars = vars.clone();
+        for (name, path) in [("TESTDIR", document_directory), ("WORKDIR", work_directory)] {
+            vars.entry(name.to_string())
+                .or_insert_with(|| path.to_string_lossy().to_string().into());
+        }
+        self.copy(
+            &document_directory.join(&self.source),
+            &work_directory.join(target),
+            &vars,
+        )
+    }
+
+    fn copy(
+        &self,
+        source: &Path,
+        target: &Path,
+        vars: &BTreeMap<String, serde_yaml::Value>,
+    ) -> anyhow::Result<()> {
+        if source.is_dir() {
+            fs::create_dir_all(target).with_context(|| format!("create directory {target:?}"))?;
+            let entries =
+                fs::read_dir(source).with_context(|| format!("list directory {source:?}"))?;
+            for entry in entries {
+                let name = entry?.file_name();
+                self.copy(&source.join(&name), &target.join(&name), vars)?;
+            }
+            return Ok(());
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("create directory {parent:?}"))?;
+        }
+        match self.template {
+            Some(ref template) => {
+                let content =
+                    fs::read_to_string(source).with_context(|| format!("read {source:?}"))?;
+                let rendered = template
+                    .render(&content, vars)
+                    .with_context(|| format!("render {source:?}"))?;
+                fs::write(target, rendered).with_context(|| format!("write {target:?}"))?;
+            }
+            None => {
+                fs::copy(source, target)
+                    .with_context(|| format!("copy {source:?} to {target:?}"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The shell that runs the shell expressions of testcases
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum Shell {
+    /// Path to a shell (`bash`, PowerShell or `cmd`), that runs all shell
+    /// expressions of a document in sessions that share their state
+    Path(PathBuf),
+
+    /// An arbitrary interpreter with its arguments, that is started anew for
+    /// each shell expression, which is appended as the last argument (e.g.
+    /// `["python3", "-c"]`)
+    Interpreter(Vec<String>),
+}
+
+impl Shell {
+    /// Returns the path to the shell, unless it is an interpreter
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Path(path) => Some(path),
+            Self::Interpreter(_) => None,
+        }
+    }
+
+    /// Returns the program and arguments of the interpreter, if it is one
+    pub fn interpreter(&self) -> Option<&[String]> {
+        match self {
+            Self::Path(_) => None,
+            Self::Interpreter(command) => Some(command),
+        }
+    }
+}
+
+impl From<&str> for Shell {
+    fn from(value: &str) -> Self {
+        Self::Path(value.into())
+    }
+}
+
+impl From<PathBuf> for Shell {
+    fn from(value: PathBuf) -> Self {
+        Self::Path(value)
+    }
+}
+
+impl Display for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "{}", path.display()),
+            Self::Interpreter(command) => write!(f, "{}", command.join(" ")),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Shell {
+    /// Deserialize from either scalar (path) or sequence (interpreter)
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ShellParser;
+
+        impl<'de> Visitor<'de> for ShellParser {
+            type Value = Shell;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("path to a shell or list of interpreter and arguments")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Shell, E>
+            where
+                E: de::Error,
+            {
+                Ok(Shell::Path(value.into()))
+            }
+
+            fn visit_seq<A>(self, seq: A) -> Result<Shell, A::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                let command =
+                    Vec::<String>::deserialize(de::value::SeqAccessDeserializer::new(seq))?;
+                if command.is_empty() || command[0].is_empty() {
+                    return Err(de::Error::custom("interpreter must not be empty"));
+                }
+                Ok(Shell::Interpreter(command))
+            }
+        }
+
+        deserializer.deserialize_any(ShellParser)
+    }
+}
+
+/// Configuration for the scope of a single [`crate::testcase::TestCase`]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TestCaseConfig {
+    /// What to do with testcases that assert nothing, because they have
+    /// neither output expectations nor an expected exit code: `true` allows
+    /// them, `false` fails them and `warn` reports them as a warning
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "EmptyTestsPolicy::parse"
+    )]
+    pub allow_empty_tests: Option<EmptyTestsPolicy>,
+
+    /// Whether the testcase may run `scrut` on the test document it is
+    /// defined in, which is refused otherwise, to prevent infinite recursion
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_recursion: Option<bool>,
+
+    /// Arbitrary key/value labels (e.g. `component: auth`) that are carried
+    /// into all report formats, so that failures can be grouped downstream.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+
+    /// Whether to replace the home directory and the name of the user that
+    /// runs the test with the stable placeholders `~` and `$USER` in the
+    /// output before validation, so that tests pass for all users
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anonymize_user: Option<bool>,
+
+    /// Checks of the files in the work directory after the shell expression
+    /// ran: paths that must `exist`, paths that must be `absent` and files
+    /// whose contents must `match`, literally or those of a golden file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assert_files: Option<FileAssertions>,
+
+    /// Whether to report all failures of the testcase at once: an unexpected
+    /// exit code then does not hide mismatching output, so that both can be
+    /// fixed (or updated) in a single pass
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collect_all_failures: Option<bool>,
+
+    /// The directory the shell expression is run in: a path relative to the
+    /// work directory, or relative to the directory of the test document
+    /// (`{document: path}`). Changes of the directory in the shell expression
+    /// are not kept for subsequent testcases.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "WorkingDirectory::parse"
+    )]
+    pub cwd: Option<WorkingDirectory>,
+
+    /// Tell Scrut that the shell expression of this test will detach itself, so
+    /// Scrut will not consider this a test (i.e. no output or exit code evaluation).
+    /// Purpose is to allow the user to detach a command (like
+    /// `nohup some-command &`) that is doing something asynchronous (e.g.
+    /// starting a server to which the tested CLI is a client).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detached: Option<bool>,
+
+    /// Kill signal to send to the detached process after test execution on unix systems
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detached_kill_signal: Option<KillSignal>,
+
+    /// Algorithm that aligns the output expectations with the lines of output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff_algorithm: Option<DiffAlgorithm>,
+
+    /// How many matching lines surround the mismatches in the failure output
+    /// of the pretty renderer, or `full` to show all lines of the testcase
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "DiffContext::parse"
+    )]
+    pub diff_context: Option<DiffContext>,
+
+    /// If true, stops execution of the entire test document immediately if this
+    /// test case fails for any reason (exit status, snapshot validation, etc).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fail_fast: Option<bool>,
+
+    /// A set of environment variable names and values that will be explicitly set
+    /// for the test. Values can reference variables of the environment Scrut
+    /// is run in with `${NAME}`. Can also be written as `env`.
+    #[serde(alias = "env", skip_serializing_if = "BTreeMap::is_empty")]
+    pub environment: BTreeMap<String, String>,
+
+    /// The signal that the shell expression is expected to be terminated by,
+    /// instead of ending with an exit code. Takes precedence over the exit
+    /// code expectation of the testcase.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_signal: Option<KillSignal>,
+
+    /// Whether the expectations of the testcase are kept as they are written,
+    /// instead of being rewritten by `update` or `test --update-failing`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frozen: Option<bool>,
+
+    /// How bytes of the output that are not valid UTF-8 are handled. Unless
+    /// set, they are kept as they are and written as escape sequences.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invalid_utf8: Option<InvalidUtf8>,
+
+    /// Whether CRLF should be translated to LF (=false) or whether CR needs to
+    /// be explicitly handled (=true).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_crlf: Option<bool>,
+
+    /// Restricts the testcase to these locales, if the document is run once
+    /// per locale (see [`DocumentConfig::locales`]). Runs in all locales if empty.
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub locales: Vec<String>,
+
+    /// A max execution time of a succeeding test, after which it is considered
+    /// failed. Unlike the timeout, the execution is not aborted.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "parse_duration_opt",
+        serialize_with = "render_duration_opt"
+    )]
+    pub max_duration: Option<Duration>,
+
+    /// The max amount of output (STDOUT and STDERR combined) that the
+    /// execution may produce, e.g. `100MiB`. Executions that exceed it are
+    /// ended early and fail, instead of capturing all output in memory.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "ByteSize::parse"
+    )]
+    pub max_output_size: Option<ByteSize>,
+
+    /// The max resident set size that the largest process of a succeeding
+    /// test may reach, after which it is considered failed, e.g. `256MiB`
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "ByteSize::parse"
+    )]
+    pub max_rss: Option<ByteSize>,
+
+    /// Whether CRLF in the output is translated to LF, even if
+    /// [`TestCaseConfig::keep_crlf`] is set, so that expectations are the same
+    /// on Windows and Unix
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalize_crlf: Option<bool>,
+
+    /// Whether to normalize paths in the output before validation, so that
+    /// tests pass on all platforms: backslashes are replaced with slashes,
+    /// drive letters are removed and the work directory prefix is stripped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalize_paths: Option<bool>,
+
+    /// How the output is compared with the expectations:
+    /// - `utf8-lossy`: Output is text, that is compared line by line (default)
+    /// - `bytes`: Output is compared as raw bytes, that are written as hex dump
+    /// - `hash`: Output is compared by its SHA-256 digest
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_encoding: Option<OutputEncoding>,
+
+    /// Which output stream to choose when applying output expectations:
+    /// - `stdout`: All expectations apply to what is printed on STDOUT
+    /// - `stderr`: All expectations apply to what is printed on STDERR
+    /// - `combined`: STDOUT and STDERR will combined into a single stream where all expectations are applied on
+    /// - `marked` (todo): User marks which expectations are intended for which stream explicitly
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_stream: Option<OutputStreamControl>,
+
+    /// Secrets that are replaced with `[REDACTED]` in the output of the
+    /// testcase, before it is validated, rendered or written back by `update`.
+    /// Entries that are valid environment variable names (e.g. `API_TOKEN`)
+    /// redact the value of that variable, all other entries are regular
+    /// expressions (use e.g. `(?:API_TOKEN)` to redact a literal name).
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub redact: Vec<String>,
+
+    /// Re-execute the shell expression until its output matches the
+    /// expectations, or until this much time has passed, for commands that
+    /// test eventually-consistent state. Only the last attempt is validated.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "parse_duration_opt",
+        serialize_with = "render_duration_opt"
+    )]
+    pub retry: Option<Duration>,
+
+    /// The exit code, that if returned by any test, leads to skipping of the whole file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_document_code: Option<i32>,
+
+    /// An interpreter with arguments (e.g. `["python3", "-c"]`), that runs
+    /// the shell expression of the testcase instead of the shell of the
+    /// document. It does not share state with other testcases.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<Shell>,
+
+    /// The standard input the shell expression is provided with, either the
+    /// literal content or a file (`{file: path}`) it is read from
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "TestCaseStdin::parse"
+    )]
+    pub stdin: Option<TestCaseStdin>,
+
+    /// Whether to strip ANSI escape sequences (colors, cursor movement, ..) from
+    /// the tested output before validation. Can also be written as `strip_ansi`.
+    #[serde(alias = "strip_ansi", skip_serializing_if = "Option::is_none")]
+    pub strip_ansi_escaping: Option<bool>,
+
+    /// The size of the terminal the test is run in, which is provided in the
+    /// `COLUMNS` and `LINES` environment variables (taking precedence over
+    /// `environment`), so that output that is wrapped or truncated to the
+    /// terminal width does not depend on the terminal Scrut runs in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub terminal_size: Option<TerminalSize>,
+
+    /// A max execution time a test can run before it is considered failed (and
+    /// will be aborted).
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "parse_duration_opt",
+        serialize_with = "render_duration_opt"
+    )]
+    pub timeout: Option<Duration>,
+
+    /// How long the processes of a test that ran into its timeout are given
+    /// to end after they received the `timeout_signal`, before they are
+    /// killed
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "parse_duration_opt",
+        serialize_with = "render_duration_opt"
+    )]
+    pub timeout_grace: Option<Duration>,
+
+    /// The signal that is sent to the processes of a test that ran into its
+    /// timeout, so that they can clean up before they are killed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_signal: Option<KillSignal>,
+
+    /// Whether to run the shell expression attached to a pseudo-terminal, so
+    /// that programs which behave differently when their output is a terminal
+    /// (progress bars, colors, prompts) can be tested as a user sees them.
+    /// STDOUT and STDERR are combined, as they are in a terminal.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tty: Option<bool>,
+
+    /// The Unicode normalization form that output and expectations are brought
+    /// into before they are compared, so that e.g. file names that macOS
+    /// returns decomposed match expectations that are written composed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unicode_normalization: Option<UnicodeNormalization>,
+
+    /// Sleep for some time before starting this test (i.e. continuing with testing).
+    /// If path is provided, then wait will be aborted (and the testing continues)
+    /// as soon as path exists and the test will fail if it does not show up
+    /// within duration. The wait time does not count against timeout(), but
+    /// against total_timeout(). To be used in conjunction with detached().
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "TestCaseWait::parse"
+    )]
+    pub wait: Option<TestCaseWait>,
+
+    /// Whether the testcase is expected to fail, e.g. because it documents a
+    /// known bug: its failure does not fail the run and it passing is
+    /// reported as unexpected (see `--fail-on-xpass`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xfail: Option<bool>,
+}
+
+impl TestCaseConfig {
+    /// Returns instance with all values set to [`None`]
+    pub fn empty() -> Self {
+        Self::default() // TODO: review - default may soon not be empty anymore
+    }
+
+    /// Returns default testcase configuration for Markdown documents
+    ///
+    /// Currently:
+    /// - Output Stream: STDOUT
+    /// - Skip Code: 80
+    ///
+    /// TODO: change the default output stream to Combined (adjust all uses!)
+    pub fn default_markdown() -> Self {
+        Self {
+            output_stream: Some(OutputStreamControl::Stdout),
+            skip_document_code: Some(DEFAULT_SKIP_DOCUMENT_CODE),
+            detached_kill_signal: Some(KillSignal::default()),
+            ..Default::default()
+        }
+    }
+
+    /// Returns default testcase configuration for Cram documents
+    ///
+    /// Currently:
+    /// - Output Stream: Combined STDOUT and STDERR
+    /// - Skip Code: 80
+    /// - Keep CRLF in output
+    pub fn default_cram() -> Self {
+        Self {
+            output_stream: Some(OutputStreamControl::Combined),
+            keep_crlf: Some(true),
+            skip_document_code: Some(DEFAULT_SKIP_DOCUMENT_CODE),
+            ..Default::default()
+        }
+    }
+
+    /// Returns true if none the configuration parameters are set
+    pub fn is_empty(&self) -> bool {
+        self.output_stream.is_none()
+            && self.output_encoding.is_none()
+            && self.keep_crlf.is_none()
+            && self.normalize_crlf.is_none()
+            && self.invalid_utf8.is_none()
+            && self.timeout.is_none()
+            && self.timeout_grace.is_none()
+            && self.timeout_signal.is_none()
+            && self.detached.is_none()
+            && self.diff_algorithm.is_none()
+            && self.diff_context.is_none()
+            && self.fail_fast.is_none()
+            && self.frozen.is_none()
+            && self.collect_all_failures.is_none()
+            && self.cwd.is_none()
+            && self.wait.is_none()
+            && self.xfail.is_none()
+            && self.stdin.is_none()
+            && self.skip_document_code.is_none()
+            && self.shell.is_none()
+            && self.strip_ansi_escaping.is_none()
+            && self.terminal_size.is_none()
+            && self.tty.is_none()
+            && self.unicode_normalization.is_none()
+            && self.environment.is_empty()
+            && self.expected_signal.is_none()
+            && self.locales.is_empty()
+            && self.max_duration.is_none()
+            && self.max_output_size.is_none()
+            && self.max_rss.is_none()
+            && self.normalize_paths.is_none()
+            && self.anonymize_user.is_none()
+            && self.assert_files.is_none()
+            && self.allow_empty_tests.is_none()
+            && self.allow_recursion.is_none()
+            && self.redact.is_empty()
+            && self.retry.is_none()
+            && self.annotations.is_empty()
+    }
+
+    /// Returns a new instance that fills in unset values from the provided defaults
+    pub fn with_defaults_from(&self, defaults: &Self) -> Self {
+        Self {
+            annotations: defaults
+                .annotations
+                .clone()
+                .into_iter()
+                .chain(self.annotations.clone())
+                .collect(),
+            output_stream: self
+                .output_stream
+                .clone()
+                .or_else(|| defaults.output_stream.clone()),
+            output_encoding: self.output_encoding.or(defaults.output_encoding),
+            keep_crlf: self.keep_crlf.or(defaults.keep_crlf),
+            normalize_crlf: self.normalize_crlf.or(defaults.normalize_crlf),
+            invalid_utf8: self.invalid_utf8.or(defaults.invalid_utf8),
+            locales: if self.locales.is_empty() {
+                defaults.locales.clone()
+            } else {
+                self.locales.clone()
+            },
+            timeout: self.timeout.or(defaults.timeout),
+            timeout_grace: self.timeout_grace.or(defaults.timeout_grace),
+            timeout_signal: self
+                .timeout_signal
+                .clone()
+                .or_else(|| defaults.timeout_signal.clone()),
+            max_duration: self.max_duration.or(defaults.max_duration),
+            max_output_size: self.max_output_size.or(defaults.max_output_size),
+            max_rss: self.max_rss.or(defaults.max_rss),
+            retry: self.retry.or(defaults.retry),
+            environment: defaults
+                .environment
+                .clone()
+                .into_iter()
+                .chain(self.environment.clone())
+                .collect(),
+            detached: self.detached.or(defaults.detached),
+            detached_kill_signal: self
+                .detached_kill_signal
+                .clone()
+                .or_else(|| defaults.detached_kill_signal.clone()),
+            diff_algorithm: self.diff_algorithm.or(defaults.diff_algorithm),
+            diff_context: self.diff_context.or(defaults.diff_context),
+            expected_signal: self
+                .expected_signal
+                .clone()
+                .or_else(|| defaults.expected_signal.clone()),
+            fail_fast: self.fail_fast.or(defaults.fail_fast),
+            frozen: self.frozen.or(defaults.frozen),
+            collect_all_failures: self.collect_all_failures.or(defaults.collect_all_failures),
+            cwd: self.cwd.clone().or_else(|| defaults.cwd.clone()),
+            normalize_paths: self.normalize_paths.or(defaults.normalize_paths),
+            anonymize_user: self.anonymize_user.or(defaults.anonymize_user),
+            assert_files: self
+                .assert_files
+                .clone()
+                .or_else(|| defaults.assert_files.clone()),
+            allow_empty_tests: self.allow_empty_tests.or(defaults.allow_empty_tests),
+            allow_recursion: self.allow_recursion.or(defaults.allow_recursion),
+            redact: defaults.redact.iter().chain(self.redact.iter()).fold(
+                vec![],
+                |mut redact, entry| {
+                    if !redact.contains(entry) {
+                        redact.push(entry.clone());
+                    }
+                    redact
+                },
+            ),
+            wait: self.wait.clone().or_else(|| defaults.wait.clone()),
+            xfail: self.xfail.or(defaults.xfail),
+            stdin: self.stdin.clone().or_else(|| defaults.stdin.clone()),
+            skip_document_code: self.skip_document_code.or(defaults.skip_document_code),
+            shell: self.shell.clone().or_else(|| defaults.shell.clone()),
+            strip_ansi_escaping: self.strip_ansi_escaping.or(defaults.strip_ansi_escaping),
+            terminal_size: self.terminal_size.or(defaults.terminal_size),
+            tty: self.tty.or(defaults.tty),
+            unicode_normalization: self
+                .unicode_normalization
+                .or(defaults.unicode_normalization),
+        }
+    }
+
+    /// Returns a new instance that is overridden with provided (set) values
+    pub fn with_overrides_from(&self, overrides: &Self) -> Self {
+        overrides.with_defaults_from(self)
+    }
+
+    /// Returns true if the testcase is to be run in the given locale
+    pub fn runs_in_locale(&self, locale: &str) -> bool {
+        self.locales.is_empty() || self.locales.iter().any(|l| l == locale)
+    }
+
+    /// Returns a new configuration that contains the provided environment variables
+    pub fn with_environment(&self, environment: &BTreeMap<&str, &str>) -> Self {
+        let mut config = self.clone();
+        for (key, value) in environment {
+            config.environment.insert((*key).into(), (*value).into());
+        }
+        config
+    }
+
+    /// Return new configuration that does not contain the provided environment
+    /// variables - unless they have a different value.
+    pub fn without_environment(&self, environment: &BTreeMap<&str, &str>) -> Self {
+        let mut config = self.clone();
+        for (key, value) in environment {
+            if config.environment.get(*key) == Some(&value.to_string()) {
+                config.environment.remove(*key);
+            }
+        }
+        config
+    }
+
+    /// Returns the environment variables with all `${NAME}` references in their
+    /// values replaced by the value of the variable `NAME` of the environment
+    /// Scrut is run in (or the empty string, if it is not set). Use `$${NAME}`
+    /// to keep a literal `${NAME}`. The `terminal_size` is provided as
+    /// `COLUMNS` and `LINES`.
+    pub fn interpolated_environment(&self) -> BTreeMap<String, String> {
+        let mut environment = self
+            .environment
+            .iter()
+            .map(|(key, value)| {
+                (
+                    key.clone(),
+                    interpolate(value, |name| std::env::var(name).ok()),
+                )
+            })
+            .collect::<BTreeMap<_, _>>();
+        if let Some(size) = self.terminal_size {
+            environment.insert("COLUMNS".to_string(), size.columns.to_string());
+            environment.insert("LINES".to_string(), size.rows.to_string());
+        }
+        environment
+    }
+
+    /// Returns what makes this configuration different from another one.
+    /// Will be equal to [`Self::empty()`] if they are equal.
+    pub fn diff(&self, other: &Self) -> Self {
+        let mut diff = Self::empty();
+        if self.output_stream != other.output_stream {
+            diff.output_stream = self.output_stream.clone();
+        }
+        if self.output_encoding != other.output_encoding {
+            diff.output_encoding = self.output_encoding;
+        }
+        if self.keep_crlf != other.keep_crlf {
+            diff.keep_crlf = self.keep_crlf;
+        }
+        if self.normalize_crlf != other.normalize_crlf {
+            diff.normalize_crlf = self.normalize_crlf;
+        }
+        if self.invalid_utf8 != other.invalid_utf8 {
+            diff.invalid_utf8 = self.invalid_utf8;
+        }
+        if self.timeout != other.timeout {
+            diff.timeout = self.timeout;
+        }
+        if self.timeout_grace != other.timeout_grace {
+            diff.timeout_grace = self.timeout_grace;
+        }
+        if self.timeout_signal != other.timeout_signal {
+            diff.timeout_signal = self.timeout_signal.clone();
+        }
+        if self.max_duration != other.max_duration {
+            diff.max_duration = self.max_duration;
+        }
+        if self.max_output_size != other.max_output_size {
+            diff.max_output_size = self.max_output_size;
+        }
+        if self.max_rss != other.max_rss {
+            diff.max_rss = self.max_rss;
+        }
+        if self.retry != other.retry {
+            diff.retry = self.retry;
+        }
+        if self.detached != other.detached {
+            diff.detached = self.detached;
+        }
+        if self.detached_kill_signal != other.detached_kill_signal {
+            diff.detached_kill_signal = self.detached_kill_signal.clone();
+        }
+        if self.diff_algorithm != other.diff_algorithm {
+            diff.diff_algorithm = self.diff_algorithm;
+        }
+        if self.diff_context != other.diff_context {
+            diff.diff_context = self.diff_context;
+        }
+        if self.expected_signal != other.expected_signal {
+            diff.expected_signal = self.expected_signal.clone();
+        }
+        if self.collect_all_failures != other.collect_all_failures {
+            diff.collect_all_failures = self.collect_all_failures;
+        }
+        if self.cwd != other.cwd {
+            diff.cwd = self.cwd.clone();
+        }
+        if self.fail_fast != other.fail_fast {
+            diff.fail_fast = self.fail_fast;
+        }
+        if self.frozen != other.frozen {
+            diff.frozen = self.frozen;
+        }
+        if self.anonymize_user != other.anonymize_user {
+            diff.anonymize_user = self.anonymize_user;
+        }
+        if self.assert_files != other.assert_files {
+            diff.assert_files = self.assert_files.clone();
+        }
+        if self.allow_empty_tests != other.allow_empty_tests {
+            diff.allow_empty_tests = self.allow_empty_tests;
+        }
+        if self.allow_recursion != other.allow_recursion {
+            diff.allow_recursion = self.allow_recursion;
+        }
+        if self.normalize_paths != other.normalize_paths {
+            diff.normalize_paths = self.normalize_paths;
+        }
+        if self.skip_document_code != other.skip_document_code {
+            diff.skip_document_code = self.skip_document_code;
+        }
+        if self.shell != other.shell {
+            diff.shell = self.shell.clone();
+        }
+        if self.strip_ansi_escaping != other.strip_ansi_escaping {
+            diff.strip_ansi_escaping = self.strip_ansi_escaping;
+        }
+        if self.terminal_size != other.terminal_size {
+            diff.terminal_size = self.terminal_size;
+        }
+        if self.tty != other.tty {
+            diff.tty = self.tty;
+        }
+        if self.unicode_normalization != other.unicode_normalization {
+            diff.unicode_normalization = self.unicode_normalization;
+        }
+        if self.stdin != other.stdin {
+            diff.stdin = self.stdin.clone();
+        }
+        if self.wait != other.wait {
+            diff.wait = self.wait.clone();
+        }
+        if self.xfail != other.xfail {
+            diff.xfail = self.xfail;
+        }
+        if self.locales != other.locales {
+            diff.locales = self.locales.clone();
+        }
+        if self.redact != other.redact {
+            diff.redact = self
+                .redact
+                .iter()
+                .filter(|entry| !other.redact.contains(entry))
+                .cloned()
+                .collect();
+        }
+
+        // difference here is: all env vars that are set in self, but not in other
+        // and all that env vars that have different values in self than in other
+        if self.environment != other.environment {
+            let mut env_diff = self.environment.clone();
+            for (k, v) in other.environment.iter() {
+                if env_diff.get(k) == Some(v) {
+                    env_diff.remove(k);
+                }
+            }
+            diff.environment = env_diff;
+        }
+        if self.annotations != other.annotations {
+            let mut annotations_diff = self.annotations.clone();
+            for (k, v) in other.annotations.iter() {
+                if annotations_diff.get(k) == Some(v) {
+                    annotations_diff.remove(k);
+                }
+            }
+            diff.annotations = annotations_diff;
+        }
+
+        diff
+    }
+
+    /// Serde YAML does not support one-line-formatted YAML and currently Scrut
+    /// supports only parsing one-line-formatted YAML.
+    /// This implementation provides a least-effort
+    pub fn to_yaml_one_liner(&self) -> String {
+        let mut output = vec![];
+        if let Some(ref value) = self.output_stream {
+            output.push(format!(
+                "output_stream: {}",
+                value.to_string().to_lowercase()
+            ));
+        }
+        if let Some(value) = self.output_encoding {
+            output.push(format!("output_encoding: {}", value))
+        }
+        if let Some(value) = self.keep_crlf {
+            output.push(format!("keep_crlf: {}", value))
+        }
+        if let Some(value) = self.normalize_crlf {
+            output.push(format!("normalize_crlf: {}", value))
+        }
+        if let Some(value) = self.invalid_utf8 {
+            output.push(format!("invalid_utf8: {}", value))
+        }
+        if let Some(value) = self.timeout {
+            output.push(format!("timeout: {}", humantime::format_duration(value)))
+        }
+        if let Some(value) = self.timeout_grace {
+            output.push(format!(
+                "timeout_grace: {}",
+                humantime::format_duration(value)
+            ))
+        }
+        if let Some(ref value) = self.timeout_signal {
+            output.push(format!(
+                "timeout_signal: {}",
+                value.to_string().to_lowercase()
+            ))
+        }
+        if let Some(value) = self.max_duration {
+            output.push(format!(
+                "max_duration: {}",
+                humantime::format_duration(value)
+            ))
+        }
+        if let Some(value) = self.max_output_size {
+            output.push(format!("max_output_size: {}", value))
+        }
+        if let Some(value) = self.max_rss {
+            output.push(format!("max_rss: {}", value))
+        }
+        if let Some(value) = self.retry {
+            output.push(format!("retry: {}", humantime::format_duration(value)))
+        }
+        if let Some(value) = self.detached {
+            output.push(format!("detached: {}", value))
+        }
+        if let Some(ref value) = self.detached_kill_signal {
+            output.push(format!(
+                "detached_kill_signal: {}",
+                value.to_string().to_lowercase()
+            ))
+        }
+        if let Some(value) = self.diff_algorithm {
+            output.push(format!("diff_algorithm: {}", value))
+        }
+        if let Some(value) = self.diff_context {
+            output.push(format!("diff_context: {}", value))
+        }
+        if let Some(ref value) = self.expected_signal {
+            output.push(format!(
+                "expected_signal: {}",
+                value.to_string().to_lowercase()
+            ))
+        }
+        if let Some(value) = self.fail_fast {
+            output.push(format!("fail_fast: {}", value))
+        }
+        if let Some(value) = self.frozen {
+            output.push(format!("frozen: {}", value))
+        }
+        if let Some(value) = self.collect_all_failures {
+            output.push(format!("collect_all_failures: {}", value))
+        }
+        if let Some(value) = self.anonymize_user {
+            output.push(format!("anonymize_user: {}", value))
+        }
+        if let Some(ref assert_files) = self.assert_files {
+            let mut checks = vec![];
+            if !assert_files.exists.is_empty() {
+                let paths = assert_files.exists.iter().map(|path| quoted(path));
+                checks.push(format!(
+                    "exists: [{}]",
+                    paths.collect::<Vec<_>>().join(", ")
+                ));
+            }
+            if !assert_files.absent.is_empty() {
+                let paths = assert_files.absent.iter().map(|path| quoted(path));
+                checks.push(format!(
+                    "absent: [{}]",
+                    paths.collect::<Vec<_>>().join(", ")
+                ));
+            }
+            if !assert_files.matches.is_empty() {
+                let matches = assert_files
+                    .matches
+                    .iter()
+                    .map(|(path, expected)| format!("{}: {}", quoted(path), quoted(expected)));
+                checks.push(format!(
+                    "matches: {{{}}}",
+                    matches.collect::<Vec<_>>().join(", ")
+                ));
+            }
+            if !assert_files.trees.is_empty() {
+                let trees = assert_files.trees.iter().map(|(path, tree)| {
+                    if tree.ignore.is_empty() {
+                        format!("{}: {}", quoted(path), quoted(&tree.golden))
+                    } else {
+                        let ignore = tree.ignore.iter().map(|pattern| quoted(pattern));
+                        format!(
+                            "{}: {{golden: {}, ignore: [{}]}}",
+                            quoted(path),
+                            quoted(&tree.golden),
+                            ignore.collect::<Vec<_>>().join(", ")
+                        )
+                    }
+                });
+                checks.push(format!(
+                    "trees: {{{}}}",
+                    trees.collect::<Vec<_>>().join(", ")
+                ));
+            }
+            output.push(format!("assert_files: {{{}}}", checks.join(", ")))
+        }
+        if let Some(value) = self.allow_empty_tests {
+            output.push(format!("allow_empty_tests: {}", value))
+        }
+        if let Some(value) = self.allow_recursion {
+            output.push(format!("allow_recursion: {}", value))
+        }
+        if let Some(value) = self.normalize_paths {
+            output.push(format!("normalize_paths: {}", value))
+        }
+        if let Some(value) = self.skip_document_code {
+            output.push(format!("skip_document_code: {}", value))
+        }
+        match self.stdin {
+            Some(TestCaseStdin::Content(ref content)) => {
+                output.push(format!("stdin: {}", quoted(content)))
+            }
+            Some(TestCaseStdin::File { ref file }) => output.push(format!(
+                "stdin: {{file: {}}}",
+                quoted(&file.to_string_lossy())
+            )),
+            None => {}
+        }
+        match self.cwd {
+            Some(WorkingDirectory::Work(ref path)) => {
+                output.push(format!("cwd: {}", quoted(&path.to_string_lossy())))
+            }
+            Some(WorkingDirectory::Document { ref document }) => output.push(format!(
+                "cwd: {{document: {}}}",
+                quoted(&document.to_string_lossy())
+            )),
+            None => {}
+        }
+        match self.shell {
+            Some(Shell::Path(ref path)) => {
+                output.push(format!("shell: {}", quoted(&path.to_string_lossy())))
+            }
+            Some(Shell::Interpreter(ref command)) => output.push(format!(
+                "shell: [{}]",
+                command
+                    .iter()
+                    .map(|part| quoted(part))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            None => {}
+        }
+        if let Some(value) = self.strip_ansi_escaping {
+            output.push(format!("strip_ansi_escaping: {}", value))
+        }
+        if let Some(value) = self.terminal_size {
+            output.push(format!(
+                "terminal_size: {{columns: {}, rows: {}}}",
+                value.columns, value.rows
+            ))
+        }
+        if let Some(value) = self.tty {
+            output.push(format!("tty: {}", value))
+        }
+        if let Some(value) = self.unicode_normalization {
+            output.push(format!("unicode_normalization: {}", value))
+        }
+        if let Some(ref wait) = self.wait {
+            let mut conditions = vec![];
+            if let Some(ref path) = wait.path {
+                conditions.push(format!("path: {}", quoted(&path.to_string_lossy())));
+            }
+            if let Some(port) = wait.port {
+                conditions.push(format!("port: {}", port));
+            }
+            if let Some(ref url) = wait.http {
+                conditions.push(format!("http: {}", quoted(url)));
+            }
+            if let Some(status) = wait.status {
+                conditions.push(format!("status: {}", status));
+            }
+            let duration = humantime::format_duration(wait.timeout).to_string();
+            if conditions.is_empty() {
+                output.push(format!("wait: {}", duration))
+            } else {
+                output.push(format!(
+                    "wait: {{timeout: {}, {}}}",
+                    duration,
+                    conditions.join(", ")
+                ))
+            }
+        }
+        if let Some(value) = self.xfail {
+            output.push(format!("xfail: {}", value))
+        }
+        if !self.locales.is_empty() {
+            output.push(format!("locales: [{}]", self.locales.join(", ")))
+        }
+        if !self.redact.is_empty() {
+            let redact = self
+                .redact
+                .iter()
+                .map(|entry| format!("'{}'", entry.replace('\'', "''")))
+                .collect::<Vec<_>>();
+            output.push(format!("redact: [{}]", redact.join(", ")))
+        }
+        if !self.environment.is_empty() {
+            let mut envvars = vec![];
+            for (key, value) in self.environment.iter() {
+                envvars.push(format!("{}: {}", key, quoted(value)))
+            }
+            output.push(format!("environment: {{{}}}", envvars.join(", ")));
+        }
+        if !self.annotations.is_empty() {
+            let annotations = self
+                .annotations
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key, quoted(value)))
+                .collect::<Vec<_>>();
+            output.push(format!("annotations: {{{}}}", annotations.join(", ")));
+        }
+        format!("{{{}}}", output.join(", "))
+    }
+
+    pub fn get_skip_document_code(&self) -> i32 {
+        self.skip_document_code
+            .unwrap_or(DEFAULT_SKIP_DOCUMENT_CODE)
+    }
+
+    pub fn get_timeout_grace(&self) -> Duration {
+        self.timeout_grace
+            .unwrap_or(Duration::from_secs(DEFAULT_TIMEOUT_GRACE))
+    }
+
+    pub fn get_timeout_signal(&self) -> KillSignal {
+        self.timeout_signal.clone().unwrap_or_default()
+    }
+
+    pub fn get_fail_fast(&self) -> bool {
+        self.fail_fast.unwrap_or(false)
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.unwrap_or(false)
+    }
+}
+
+/// Renders the value as a double-quoted YAML string. JSON strings are valid
+/// YAML, so their escaping of quotes, backslashes and control characters is
+/// used.
+fn quoted(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
+
+/// Replaces all `${NAME}` references in the value with what the lookup returns
+/// for `NAME`, or the empty string if it returns nothing. A `$${NAME}` is
+/// rendered as the literal `${NAME}`.
+fn interpolate(value: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut output = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let (before, reference) = rest.split_at(start);
+        let end = match reference.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+        if let Some(before) = before.strip_suffix('$') {
+            output.push_str(before);
+            output.push_str(&reference[..=end]);
+        } else {
+            output.push_str(before);
+            output.push_str(&lookup(&reference[2..end]).unwrap_or_default());
+        }
+        rest = &reference[end + 1..];
+    }
+    output.push_str(rest);
+    output
+}
+
+impl Display for TestCaseConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let out = serde_json::to_string(&self).map_err(|_| std::fmt::Error)?;
+        write!(f, "{}", out)
+    }
+}
+
+/// A single configuration value, provided in the form `key=value`, that
+/// overrides either the [`DocumentConfig`] or the [`TestCaseConfig`]. The key
+/// can address nested values with dots (e.g. `environment.FOO=bar`). The value
+/// is parsed as YAML, the same way as front-matter, and falls back to a plain
+/// string if that does not yield a valid configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(clippy::large_enum_variant)]
+pub enum ConfigOverride {
+    Document(DocumentConfig),
+    TestCase(TestCaseConfig),
+}
+
+impl ConfigOverride {
+    /// Applies all document configuration overrides, in order, on top of the
+    /// provided configuration
+    pub fn apply_document(overrides: &[Self], config: DocumentConfig) -> DocumentConfig {
+        overrides.iter().fold(config, |config, value| match value {
+            Self::Document(overrides) => config.with_overrides_from(overrides),
+            Self::TestCase(_) => config,
+        })
+    }
+
+    /// Applies all testcase configuration overrides, in order, on top of the
+    /// provided configuration
+    pub fn apply_testcase(overrides: &[Self], config: TestCaseConfig) -> TestCaseConfig {
+        overrides.iter().fold(config, |config, value| match value {
+            Self::TestCase(overrides) => config.with_overrides_from(overrides),
+            Self::Document(_) => config,
+        })
+    }
+}
+
+impl FromStr for ConfigOverride {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        let (key, raw) = value
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected configuration in the form `key=value`"))?;
+        let key = key.trim();
+        if key.is_empty() || key.split('.').any(str::is_empty) {
+            bail!("invalid configuration key `{key}`");
+        }
+
+        let mut candidates = vec![];
+        if let Ok(parsed) = serde_yaml::from_str::<serde_yaml::Value>(raw) {
+            candidates.push(parsed);
+        }
+        candidates.push(serde_yaml::Value::String(raw.to_string()));
+
+        // environment variables and the working directory can be set per
+        // document and per testcase, but from the command line they must
+        // override those of the testcases
+        let testcase_first = matches!(key.split('.').next(), Some("environment" | "env" | "cwd"));
+
+        let mut error = None;
+        for candidate in candidates {
+            let candidate = key.rsplit('.').fold(candidate, |value, part| {
+                let mut mapping = serde_yaml::Mapping::new();
+                mapping.insert(serde_yaml::Value::String(part.to_string()), value);
+                serde_yaml::Value::Mapping(mapping)
+            });
+            let mut parsed = [
+                serde_yaml::from_value::<DocumentConfig>(candidate.clone())
+                    .map(|config| (!config.is_empty()).then_some(Self::Document(config))),
+                serde_yaml::from_value::<TestCaseConfig>(candidate)
+                    .map(|config| (!config.is_empty()).then_some(Self::TestCase(config))),
+            ];
+            if testcase_first {
+                parsed.reverse();
+            }
+            for result in parsed {
+                match result {
+                    // as any path on the command line, the env file is
+                    // relative to the current directory
+                    Ok(Some(Self::Document(config))) if config.env_file.is_some() => {
+                        return Ok(Self::Document(config.with_env_file_loaded(Path::new(""))?));
+                    }
+                    Ok(Some(config)) => return Ok(config),
+                    Ok(None) => {}
+                    Err(err) => error = Some(err),
+                }
+            }
+        }
+
+        match error {
+            Some(err) => Err(anyhow::Error::new(err).context(format!("invalid value for `{key}`"))),
+            None => Err(anyhow!("unsupported configuration key `{key}`")),
+        }
+    }
+}
+
+fn parse_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value: String = Deserialize::deserialize(deserializer)?;
+    let duration = humantime::parse_duration(&value).map_err(de::Error::custom)?;
+    Ok(duration)
+}
+
+fn parse_duration_opt<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value: String = Deserialize::deserialize(deserializer)?;
+    if value.is_empty() || value == "null" {
+        return Ok(None);
+    }
+    let duration = humantime::parse_duration(&value).map_err(de::Error::custom)?;
+    Ok(Some(duration))
+}
+
+fn render_duration<S>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let value = humantime::format_duration(*value).to_string();
+    serializer.serialize_str(&value)
+}
+
+fn render_duration_opt<S>(value: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let value = if let Some(value) = value {
+        humantime::format_duration(*value).to_string()
+    } else {
+        "null".to_string()
+    };
+    serializer.serialize_str(&value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::Path;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use super::ByteSize;
+    use super::ConfigOverride;
+    use super::CustomRule;
+    use super::DEFAULT_WAIT_TIMEOUT;
+    use super::Deprecation;
+    use super::DeprecationDate;
+    use super::DiffAlgorithm;
+    use super::DiffContext;
+    use super::DockerConfig;
+    use super::DocumentConfig;
+    use super::DocumentTemplate;
+    use super::EmptyTestsPolicy;
+    use super::ExecutorBackend;
+    use super::FileAssertions;
+    use super::Fixture;
+    use super::InvalidUtf8;
+    use super::KillSignal;
+    use super::OutputEncoding;
+    use super::OutputNormalizer;
+    use super::ParserType;
+    use super::Shell;
+    use super::TerminalSize;
+    use super::TestCaseStdin;
+    use super::TestCaseWait;
+    use super::UnicodeNormalization;
+    use super::WorkDirectoryConfig;
+    use super::WorkDirectoryKeep;
+    use super::WorkDirectoryScope;
+    use super::WorkingDirectory;
+    use super::interpolate;
+    use super::parse_env_file;
+    use crate::config::OutputStreamControl;
+    use crate::config::TestCaseConfig;
+    use crate::output::ProducedFile;
+
+    const FULL_DOCUMENT_CONFIG: &str = "
+after_each: the-after-each
+append:
+- app1
+- app2
+artifacts:
+- logs/*.log
+- core*
+before_each: the-before-each
+command_template: the-command {args}
+cwd: the-cwd
+defaults:
+  allow_empty_tests: warn
+  allow_recursion: true
+  annotations:
+    component: auth
+  anonymize_user: true
+  collect_all_failures: true
+  cwd:
+    document: the-default-cwd
+  detached: true
+  detached_kill_signal: quit
+  diff_algorithm: patience
+  diff_context: 10
+  fail_fast: true
+  environment:
+    BAZ: zoing
+    FOO: bar
+  expected_signal: quit
+  frozen: true
+  invalid_utf8: escape
+  keep_crlf: true
+  locales:
+  - de_DE.UTF-8
+  max_duration: 5s
+  max_output_size: 64MiB
+  max_rss: 256MiB
+  normalize_crlf: true
+  normalize_paths: true
+  output_encoding: bytes
+  output_stream: stdout
+  redact:
+  - API_TOKEN
+  - secret-[0-9]+
+  retry: 10s
+  skip_document_code: 123
+  stdin: the-stdin
+  strip_ansi_escaping: true
+  terminal_size:
+    columns: 120
+    rows: 40
+  timeout: 6m 4s
+  timeout_grace: 2s
+  timeout_signal: quit
+  tty: true
+  unicode_normalization: nfc
+  wait:
+    timeout: 2m 1s
+    path: the-wait-path
+  xfail: true
+deprecated:
+  after: 2025-06-30
+  fail_after: 2025-12-31
+  reason: replaced by the-new-suite
+docker:
+  image: the-image
+  mounts:
+  - the-volume:/the-volume
+  environment:
+    CONTAINER: the-container
+env_file: the-env-file
+env_passthrough:
+- SSH_AUTH_SOCK
+environment:
+  QUX: quux
+executor: docker
+extends: the-common.yaml
+fixtures:
+- source: the-fixture
+- source: the-template.toml
+  target: config/the.toml
+  template: jinja
+format: markdown
+hermetic_env: true
+locales:
+- en_US.UTF-8
+- de_DE.UTF-8
+macros:
+  the-macro: the-snippet (regex)
+normalizers:
+- pattern: '[0-9]+ms'
+  replacement: <duration>
+prepend:
+- prep1
+- prep2
+rules:
+  the-rule:
+    command: the-rule-command
+shell: the-shell
+template: jinja
+total_timeout: 5m 3s
+timeout_warning: 80
+vars:
+  flags:
+  - -a
+  - -b
+workdir:
+  scope: per-testcase
+  keep: on-failure
+";
+
+    #[test]
+    fn test_parse_full_document_config() {
+        let config: DocumentConfig =
+            serde_yaml::from_str(FULL_DOCUMENT_CONFIG).expect("parse full document config");
+        assert_eq!(
+            config,
+            DocumentConfig {
+                shell: Some("the-shell".into()),
+                template: Some(DocumentTemplate::Jinja),
+                vars: BTreeMap::from([(
+                    "flags".to_string(),
+                    serde_yaml::from_str("[-a, -b]").expect("parse vars"),
+                )]),
+                workdir: Some(WorkDirectoryConfig {
+                    scope: WorkDirectoryScope::PerTestcase,
+                    keep: WorkDirectoryKeep::OnFailure,
+                }),
+                total_timeout: Some(Duration::from_secs(5 * 60 + 3)),
+                timeout_warning: Some(80),
+                prepend: vec!["prep1".into(), "prep2".into()],
+                rules: BTreeMap::from([(
+                    "the-rule".to_string(),
+                    CustomRule {
+                        command: "the-rule-command".into(),
+                    },
+                )]),
+                append: vec!["app1".into(), "app2".into()],
+                artifacts: vec!["logs/*.log".into(), "core*".into()],
+                before_each: Some("the-before-each".into()),
+                command_template: Some("the-command {args}".into()),
+                cwd: Some(WorkingDirectory::Work("the-cwd".into())),
+                deprecated: Some(Deprecation {
+                    after: "2025-06-30".parse().expect("parse date"),
+                    fail_after: Some("2025-12-31".parse().expect("parse date")),
+                    reason: Some("replaced by the-new-suite".into()),
+                }),
+                docker: Some(DockerConfig {
+                    image: "the-image".into(),
+                    mounts: vec!["the-volume:/the-volume".into()],
+                    environment: BTreeMap::from([(
+                        "CONTAINER".to_string(),
+                        "the-container".to_string(),
+                    )]),
+                }),
+                executor: Some(ExecutorBackend::Docker),
+                after_each: Some("the-after-each".into()),
+                extends: Some("the-common.yaml".into()),
+                fixtures: vec![
+                    Fixture {
+                        source: "the-fixture".into(),
+                        target: None,
+                        template: None,
+                    },
+                    Fixture {
+                        source: "the-template.toml".into(),
+                        target: Some("config/the.toml".into()),
+                        template: Some(DocumentTemplate::Jinja),
+                    },
+                ],
+                format: Some(ParserType::Markdown),
+                hermetic_env: Some(true),
+                env_file: Some("the-env-file".into()),
+                env_passthrough: vec!["SSH_AUTH_SOCK".into()],
+                environment: BTreeMap::from([("QUX".to_string(), "quux".to_string())]),
+                locales: vec!["en_US.UTF-8".into(), "de_DE.UTF-8".into()],
+                macros: BTreeMap::from([(
+                    "the-macro".to_string(),
+                    "the-snippet (regex)".to_string(),
+                )]),
+                normalizers: vec![OutputNormalizer {
+                    pattern: "[0-9]+ms".into(),
+                    replacement: "<duration>".into(),
+                }],
+                defaults: TestCaseConfig {
+                    allow_empty_tests: Some(EmptyTestsPolicy::Warn),
+                    allow_recursion: Some(true),
+                    annotations: BTreeMap::from([("component".to_string(), "auth".to_string())]),
+                    anonymize_user: Some(true),
+                    assert_files: None,
+                    output_stream: Some(OutputStreamControl::Stdout),
+                    keep_crlf: Some(true),
+                    locales: vec!["de_DE.UTF-8".into()],
+                    timeout: Some(Duration::from_secs(6 * 60 + 4)),
+                    timeout_grace: Some(Duration::from_secs(2)),
+                    timeout_signal: Some(KillSignal::test_default()),
+                    environment: {
+                        let mut m = BTreeMap::new();
+                        m.insert("FOO".to_string(), "bar".to_string());
+                        m.insert("BAZ".to_string(), "zoing".to_string());
+                        m
+                    },
+                    detached: Some(true),
+                    detached_kill_signal: Some(KillSignal::test_default()),
+                    expected_signal: Some(KillSignal::test_default()),
+                    max_duration: Some(Duration::from_secs(5)),
+                    max_output_size: Some(ByteSize(64 << 20)),
+                    output_encoding: Some(OutputEncoding::Bytes),
+                    invalid_utf8: Some(InvalidUtf8::Escape),
+                    max_rss: Some(ByteSize(256 << 20)),
+                    xfail: Some(true),
+                    fail_fast: Some(true),
+                    diff_algorithm: Some(DiffAlgorithm::Patience),
+                    diff_context: Some(DiffContext::Lines(10)),
+                    frozen: Some(true),
+                    collect_all_failures: Some(true),
+                    cwd: Some(WorkingDirectory::Document {
+                        document: "the-default-cwd".into(),
+                    }),
+                    wait: Some(TestCaseWait {
+                        timeout: Duration::from_secs(2 * 60 + 1),
+                        path: Some(PathBuf::from("the-wait-path")),
+                        port: None,
+                        http: None,
+                        status: None,
+                    }),
+                    normalize_crlf: Some(true),
+                    normalize_paths: Some(true),
+                    redact: vec!["API_TOKEN".into(), "secret-[0-9]+".into()],
+                    retry: Some(Duration::from_secs(10)),
+                    skip_document_code: Some(123),
+                    shell: None,
+                    stdin: Some(TestCaseStdin::Content("the-stdin".into())),
+                    strip_ansi_escaping: Some(true),
+                    terminal_size: Some(TerminalSize {
+                        columns: 120,
+                        rows: 40,
+                    }),
+                    tty: Some(true),
+                    unicode_normalization: Some(UnicodeNormalization::Nfc),
+                }
+            }
+        )
+    }
+
+    #[test]
+    fn test_render_full_document_config() {
+        let config = DocumentConfig {
+            shell: Some("the-shell".into()),
+            template: Some(DocumentTemplate::Jinja),
+            vars: BTreeMap::from([(
+                "flags".to_string(),
+                serde_yaml::from_str("[-a, -b]").expect("parse vars"),
+            )]),
+            workdir: Some(WorkDirectoryConfig {
+                scope: WorkDirectoryScope::PerTestcase,
+                keep: WorkDirectoryKeep::OnFailure,
+            }),
+            timeout_warning: Some(80),
+            total_timeout: Some(Duration::from_secs(5 * 60 + 3)),
+            prepend: vec!["prep1".into(), "prep2".into()],
+            rules: BTreeMap::from([(
+                "the-rule".to_string(),
+                CustomRule {
+                    command: "the-rule-command".into(),
+                },
+            )]),
+            append: vec!["app1".into(), "app2".into()],
+            artifacts: vec!["logs/*.log".into(), "core*".into()],
+            before_each: Some("the-before-each".into()),
+            command_template: Some("the-command {args}".into()),
+            cwd: Some(WorkingDirectory::Work("the-cwd".into())),
+            deprecated: Some(Deprecation {
+                after: "2025-06-30".parse().expect("parse date"),
+                fail_after: Some("2025-12-31".parse().expect("parse date")),
+                reason: Some("replaced by the-new-suite".into()),
+            }),
+            docker: Some(DockerConfig {
+                image: "the-image".into(),
+                mounts: vec!["the-volume:/the-volume".into()],
+                environment: BTreeMap::from([(
+                    "CONTAINER".to_string(),
+                    "the-container".to_string(),
+                )]),
+            }),
+            executor: Some(ExecutorBackend::Docker),
+            after_each: Some("the-after-each".into()),
+            extends: Some("the-common.yaml".into()),
+            fixtures: vec![
+                Fixture {
+                    source: "the-fixture".into(),
+                    target: None,
+                    template: None,
+                },
+                Fixture {
+                    source: "the-template.toml".into(),
+                    target: Some("config/the.toml".into()),
+                    template: Some(DocumentTemplate::Jinja),
+                },
+            ],
+            format: Some(ParserType::Markdown),
+            hermetic_env: Some(true),
+            env_file: Some("the-env-file".into()),
+            env_passthrough: vec!["SSH_AUTH_SOCK".into()],
+            environment: BTreeMap::from([("QUX".to_string(), "quux".to_string())]),
+            locales: vec!["en_US.UTF-8".into(), "de_DE.UTF-8".into()],
+            macros: BTreeMap::from([("the-macro".to_string(), "the-snippet (regex)".to_string())]),
+            normalizers: vec![OutputNormalizer {
+                pattern: "[0-9]+ms".into(),
+                replacement: "<duration>".into(),
+            }],
+            defaults: TestCaseConfig {
+                allow_empty_tests: Some(EmptyTestsPolicy::Warn),
+                allow_recursion: Some(true),
+                annotations: BTreeMap::from([("component".to_string(), "auth".to_string())]),
+                anonymize_user: Some(true),
+                assert_files: None,
+                output_stream: Some(OutputStreamControl::Stdout),
+                keep_crlf: Some(true),
+                locales: vec!["de_DE.UTF-8".into()],
+                timeout: Some(Duration::from_secs(6 * 60 + 4)),
+                timeout_grace: Some(Duration::from_secs(2)),
+                timeout_signal: Some(KillSignal::test_default()),
+                environment: {
+                    let mut m = BTreeMap::new();
+                    m.insert("FOO".to_string(), "bar".to_string());
+                    m.insert("BAZ".to_string(), "zoing".to_string());
+                    m
+                },
+                detached: Some(true),
+                detached_kill_signal: Some(KillSignal::test_default()),
+                expected_signal: Some(KillSignal::test_default()),
+                max_duration: Some(Duration::from_secs(5)),
+                max_output_size: Some(ByteSize(64 << 20)),
+                output_encoding: Some(OutputEncoding::Bytes),
+                invalid_utf8: Some(InvalidUtf8::Escape),
+                max_rss: Some(ByteSize(256 << 20)),
+                xfail: Some(true),
+                fail_fast: Some(true),
+                diff_algorithm: Some(DiffAlgorithm::Patience),
+                diff_context: Some(DiffContext::Lines(10)),
+                frozen: Some(true),
+                collect_all_failures: Some(true),
+                cwd: Some(WorkingDirectory::Document {
+                    document: "the-default-cwd".into(),
+                }),
+                wait: Some(TestCaseWait {
+                    timeout: Duration::from_secs(2 * 60 + 1),
+                    path: Some(PathBuf::from("the-wait-path")),
+                    port: None,
+                    http: None,
+                    status: None,
+                }),
+                normalize_crlf: Some(true),
+                normalize_paths: Some(true),
+                redact: vec!["API_TOKEN".into(), "secret-[0-9]+".into()],
+                retry: Some(Duration::from_secs(10)),
+                skip_document_code: Some(123),
+                shell: None,
+                stdin: Some(TestCaseStdin::Content("the-stdin".into())),
+                strip_ansi_escaping: Some(true),
+                terminal_size: Some(TerminalSize {
+                    columns: 120,
+                    rows: 40,
+                }),
+                tty: Some(true),
+                unicode_normalization: Some(UnicodeNormalization::Nfc),
+            },
+        };
+        assert_eq!(
+            serde_yaml::to_string(&config).expect("render document config to YAML"),
+            FULL_DOCUMENT_CONFIG.to_string().trim_start(),
+        )
+    }
+
+    const FULL_TESTCASE_CONFIG: &str = "
+allow_empty_tests: warn
+allow_recursion: true
+annotations:
+  component: auth
+anonymize_user: true
+assert_files:
+  exists:
+  - out/
+  absent:
+  - tmp/
+  matches:
+    out/result.json: '@golden/result.json'
+collect_all_failures: true
+cwd:
+  document: the-cwd
+detached: true
+detached_kill_signal: quit
+diff_algorithm: patience
+diff_context: 10
+fail_fast: true
+environment:
+  BAZ: zoing
+  FOO: bar
+expected_signal: quit
+frozen: true
+invalid_utf8: escape
+keep_crlf: true
+locales:
+- de_DE.UTF-8
+max_duration: 5s
+max_output_size: 64MiB
+max_rss: 256MiB
+normalize_crlf: true
+normalize_paths: true
+output_encoding: bytes
+output_stream: stderr
+redact:
+- API_TOKEN
+- secret-[0-9]+
+retry: 10s
+skip_document_code: 123
+stdin: the-stdin
+strip_ansi_escaping: true
+terminal_size:
+  columns: 120
+  rows: 40
+timeout: 6m 4s
+timeout_grace: 2s
+timeout_signal: quit
+tty: true
+unicode_normalization: nfc
+wait:
+  timeout: 2m 1s
+  path: the-wait-path
+xfail: true
+";
+
+    #[test]
+    fn test_parse_full_testcase_config() {
+        let config: TestCaseConfig =
+            serde_yaml::from_str(FULL_TESTCASE_CONFIG).expect("parse full testcase config");
+        assert_eq!(
+            config,
+            TestCaseConfig {
+                allow_empty_tests: Some(EmptyTestsPolicy::Warn),
+                allow_recursion: Some(true),
+                annotations: BTreeMap::from([("component".to_string(), "auth".to_string())]),
+                anonymize_user: Some(true),
+                assert_files: Some(FileAssertions {
+                    exists: vec!["out/".into()],
+                    absent: vec!["tmp/".into()],
+                    matches: BTreeMap::from([(
+                        "out/result.json".to_string(),
+                        "@golden/result.json".to_string(),
+                    )]),
+                    ..Default::default()
+                }),
+                output_stream: Some(OutputStreamControl::Stderr),
+                keep_crlf: Some(true),
+                locales: vec!["de_DE.UTF-8".into()],
+                timeout: Some(Duration::from_secs(6 * 60 + 4)),
+                timeout_grace: Some(Duration::from_secs(2)),
+                timeout_signal: Some(KillSignal::test_default()),
+                environment: {
+                    let mut m = BTreeMap::new();
+                    m.insert("FOO".to_string(), "bar".to_string());
+                    m.insert("BAZ".to_string(), "zoing".to_string());
+                    m
+                },
+                detached: Some(true),
+                detached_kill_signal: Some(KillSignal::test_default()),
+                expected_signal: Some(KillSignal::test_default()),
+                max_duration: Some(Duration::from_secs(5)),
+                max_output_size: Some(ByteSize(64 << 20)),
+                output_encoding: Some(OutputEncoding::Bytes),
+                invalid_utf8: Some(InvalidUtf8::Escape),
+                max_rss: Some(ByteSize(256 << 20)),
+                xfail: Some(true),
+                fail_fast: Some(true),
+                diff_algorithm: Some(DiffAlgorithm::Patience),
+                diff_context: Some(DiffContext::Lines(10)),
+                frozen: Some(true),
+                collect_all_failures: Some(true),
+                cwd: Some(WorkingDirectory::Document {
+                    document: "the-cwd".into(),
+                }),
+                wait: Some(TestCaseWait {
+                    timeout: Duration::from_secs(2 * 60 + 1),
+                    path: Some(PathBuf::from("the-wait-path")),
+                    port: None,
+                    http: None,
+                    status: None,
+                }),
+                normalize_crlf: Some(true),
+                normalize_paths: Some(true),
+                redact: vec!["API_TOKEN".into(), "secret-[0-9]+".into()],
+                retry: Some(Duration::from_secs(10)),
+                skip_document_code: Some(123),
+                shell: None,
+                stdin: Some(TestCaseStdin::Content("the-stdin".into())),
+                strip_ansi_escaping: Some(true),
+                terminal_size: Some(TerminalSize {
+                    columns: 120,
+                    rows: 40,
+                }),
+                tty: Some(true),
+                unicode_normalization: Some(UnicodeNormalization::Nfc),
+            }
+        )
+    }
+
+    #[test]
+    fn test_render_full_testcase_config() {
+        let config = TestCaseConfig {
+            allow_empty_tests: Some(EmptyTestsPolicy::Warn),
+            allow_recursion: Some(true),
+            annotations: BTreeMap::from([("component".to_string(), "auth".to_string())]),
+            anonymize_user: Some(true),
+            assert_files: Some(FileAssertions {
+                exists: vec!["out/".into()],
+                absent: vec!["tmp/".into()],
+                matches: BTreeMap::from([(
+                    "out/result.json".to_string(),
+                    "@golden/result.json".to_string(),
+                )]),
+                ..Default::default()
+            }),
+            output_stream: Some(OutputStreamControl::Stderr),
+            keep_crlf: Some(true),
+            locales: vec!["de_DE.UTF-8".into()],
+            timeout: Some(Duration::from_secs(6 * 60 + 4)),
+            timeout_grace: Some(Duration::from_secs(2)),
+            timeout_signal: Some(KillSignal::test_default()),
+            environment: {
+                let mut m = BTreeMap::new();
+                m.insert("FOO".to_string(), "bar".to_string());
+                m.insert("BAZ".to_string(), "zoing".to_string());
+                m
+            },
+            detached: Some(true),
+            detached_kill_signal: Some(KillSignal::test_default()),
+            expected_signal: Some(KillSignal::test_default()),
+            max_duration: Some(Duration::from_secs(5)),
+            max_output_size: Some(ByteSize(64 << 20)),
+            output_encoding: Some(OutputEncoding::Bytes),
+            invalid_utf8: Some(InvalidUtf8::Escape),
+            max_rss: Some(ByteSize(256 << 20)),
+            xfail: Some(true),
+            fail_fast: Some(true),
+            diff_algorithm: Some(DiffAlgorithm::Patience),
+            diff_context: Some(DiffContext::Lines(10)),
+            frozen: Some(true),
+            collect_all_failures: Some(true),
+            cwd: Some(WorkingDirectory::Document {
+                document: "the-cwd".into(),
+            }),
+            wait: Some(TestCaseWait {
+                timeout: Duration::from_secs(2 * 60 + 1),
+                path: Some(PathBuf::from("the-wait-path")),
+                port: None,
+                http: None,
+                status: None,
+            }),
+            normalize_crlf: Some(true),
+            normalize_paths: Some(true),
+            redact: vec!["API_TOKEN".into(), "secret-[0-9]+".into()],
+            retry: Some(Duration::from_secs(10)),
+            skip_document_code: Some(123),
+            shell: None,
+            stdin: Some(TestCaseStdin::Content("the-stdin".into())),
+            strip_ansi_escaping: Some(true),
+            terminal_size: Some(TerminalSize {
+                columns: 120,
+                rows: 40,
+            }),
+            tty: Some(true),
+            unicode_normalization: Some(UnicodeNormalization::Nfc),
+        };
+        assert_eq!(
+            serde_yaml::to_string(&config).expect("render testcase config to YAML"),
+            FULL_TESTCASE_CONFIG.to_string().trim_start(),
+        )
+    }
+
+    #[test]
+    fn test_testcase_config_yaml_one_liner() {
+        let tests = [
+            (TestCaseConfig::empty(), "{}"),
+            (
+                TestCaseConfig {
+                    keep_crlf: Some(true),
+                    ..Default::default()
+                },
+                "{keep_crlf: true}",
+            ),
+            (
+                TestCaseConfig {
+                    wait: Some(TestCaseWait {
+                        timeout: Duration::from_secs(123),
+                        path: None,
+                        port: None,
+                        http: None,
+                        status: None,
+                    }),
+                    ..Default::default()
+                },
+                "{wait: 2m 3s}",
+            ),
+            (
+                TestCaseConfig {
+                    wait: Some(TestCaseWait {
+                        timeout: Duration::from_secs(5),
+                        path: None,
+                        port: Some(8080),
+                        http: Some("http://localhost:8080/".into()),
+                        status: Some(200),
+                    }),
+                    ..Default::default()
+                },
+                "{wait: {timeout: 5s, port: 8080, http: \"http://localhost:8080/\", status: 200}}",
+            ),
+            (
+                TestCaseConfig {
+                    environment: BTreeMap::from([("FOO".to_string(), "say \"hi\"\\n".to_string())]),
+                    ..Default::default()
+                },
+                "{environment: {FOO: \"say \\\"hi\\\"\\\\n\"}}",
+            ),
+            (
+                TestCaseConfig {
+                    output_stream: Some(OutputStreamControl::Stderr),
+                    keep_crlf: Some(true),
+                    locales: vec!["de_DE.UTF-8".into(), "fr_FR.UTF-8".into()],
+                    detached: Some(false),
+                    detached_kill_signal: Some(KillSignal::test_default()),
+                    expected_signal: Some(KillSignal::test_default()),
+                    max_duration: Some(Duration::from_secs(5)),
+                    max_output_size: Some(ByteSize(64 << 20)),
+                    output_encoding: Some(OutputEncoding::Bytes),
+                    invalid_utf8: Some(InvalidUtf8::Escape),
+                    max_rss: Some(ByteSize(256 << 20)),
+                    xfail: Some(true),
+                    fail_fast: Some(false),
+                    diff_algorithm: Some(DiffAlgorithm::Patience),
+                    diff_context: Some(DiffContext::Lines(10)),
+                    frozen: Some(true),
+                    collect_all_failures: Some(true),
+                    cwd: Some(WorkingDirectory::Document {
+                        document: "fixtures".into(),
+                    }),
+                    environment: BTreeMap::from([("foo".to_string(), "bar".to_string())]),
+                    allow_empty_tests: Some(EmptyTestsPolicy::Fail),
+                    allow_recursion: Some(true),
+                    annotations: BTreeMap::from([("severity".to_string(), "p1".to_string())]),
+                    anonymize_user: Some(true),
+                    assert_files: Some(FileAssertions {
+                        absent: vec!["tmp/".into()],
+                        ..Default::default()
+                    }),
+                    normalize_crlf: Some(true),
+                    normalize_paths: Some(true),
+                    redact: vec!["API_TOKEN".into(), "it's-[0-9]+".into()],
+                    retry: Some(Duration::from_secs(10)),
+                    skip_document_code: Some(123),
+                    shell: Some(Shell::Interpreter(vec!["python3".into(), "-c".into()])),
+                    stdin: Some(TestCaseStdin::File {
+                        file: PathBuf::from("input.txt"),
+                    }),
+                    strip_ansi_escaping: Some(true),
+                    terminal_size: Some(TerminalSize {
+                        columns: 120,
+                        rows: 40,
+                    }),
+                    tty: Some(true),
+                    unicode_normalization: Some(UnicodeNormalization::Nfc),
+                    timeout: Some(Duration::from_secs(234)),
+                    timeout_grace: Some(Duration::from_secs(2)),
+                    timeout_signal: Some(KillSignal::test_default()),
+                    wait: Some(TestCaseWait {
+                        timeout: Duration::from_secs(123),
+                        path: Some(PathBuf::from("/tmp/wait")),
+                        port: None,
+                        http: None,
+                        status: None,
+                    }),
+                },
+                "{output_stream: stderr, output_encoding: bytes, keep_crlf: true, normalize_crlf: true, invalid_utf8: escape, timeout: 3m 54s, timeout_grace: 2s, timeout_signal: quit, max_duration: 5s, max_output_size: 64MiB, max_rss: 256MiB, retry: 10s, detached: false, detached_kill_signal: quit, diff_algorithm: patience, diff_context: 10, expected_signal: quit, fail_fast: false, frozen: true, collect_all_failures: true, anonymize_user: true, assert_files: {absent: [\"tmp/\"]}, allow_empty_tests: false, allow_recursion: true, normalize_paths: true, skip_document_code: 123, stdin: {file: \"input.txt\"}, cwd: {document: \"fixtures\"}, shell: [\"python3\", \"-c\"], strip_ansi_escaping: true, terminal_size: {columns: 120, rows: 40}, tty: true, unicode_normalization: nfc, wait: {timeout: 2m 3s, path: \"/tmp/wait\"}, xfail: true, locales: [de_DE.UTF-8, fr_FR.UTF-8], redact: ['API_TOKEN', 'it''s-[0-9]+'], environment: {foo: \"bar\"}, annotations: {severity: \"p1\"}}",
+            ),
+        ];
+        for (idx, (config, expected)) in tests.iter().enumerate() {
+            let yaml = config.to_yaml_one_liner();
+            assert_eq!(
+                expected.to_string(),
+                yaml,
+                "test {idx}: for config {config:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_testcase_config_diff() {
+        let defaults = TestCaseConfig {
+            environment: BTreeMap::from([
+                ("FOO".to_string(), "default".to_string()),
+                ("BAR".to_string(), "default".to_string()),
+            ]),
+            ..TestCaseConfig::default_markdown()
+        };
+        let config = TestCaseConfig {
+            detached_kill_signal: Some(KillSignal::test_default()),
+            expected_signal: Some(KillSignal::test_default()),
+            max_duration: Some(Duration::from_secs(5)),
+            max_output_size: Some(ByteSize(64 << 20)),
+            output_encoding: Some(OutputEncoding::Bytes),
+            invalid_utf8: Some(InvalidUtf8::Escape),
+            max_rss: Some(ByteSize(256 << 20)),
+            normalize_crlf: Some(true),
+            xfail: Some(true),
+            environment: BTreeMap::from([
+                ("FOO".to_string(), "own".to_string()),
+                ("BAZ".to_string(), "own".to_string()),
+            ]),
+            ..Default::default()
+        }
+        .with_defaults_from(&defaults);
+        assert_eq!(
+            config.diff(&defaults),
+            TestCaseConfig {
+                detached_kill_signal: Some(KillSignal::test_default()),
+                expected_signal: Some(KillSignal::test_default()),
+                max_duration: Some(Duration::from_secs(5)),
+                max_output_size: Some(ByteSize(64 << 20)),
+                output_encoding: Some(OutputEncoding::Bytes),
+                invalid_utf8: Some(InvalidUtf8::Escape),
+                max_rss: Some(ByteSize(256 << 20)),
+                normalize_crlf: Some(true),
+                xfail: Some(true),
+                environment: BTreeMap::from([
+                    ("BAZ".to_string(), "own".to_string()),
+                    ("FOO".to_string(), "own".to_string()),
+                ]),
+                ..Default::default()
+            }
+        );
+        assert_eq!(defaults.diff(&defaults), TestCaseConfig::empty());
+    }
+
+    #[test]
+    fn test_testcase_config_with_defaults_from_keeps_own_environment() {
+        let config = TestCaseConfig {
+            environment: BTreeMap::from([("FOO".to_string(), "own".to_string())]),
+            ..Default::default()
+        };
+        let defaults = TestCaseConfig {
+            environment: BTreeMap::from([
+                ("FOO".to_string(), "default".to_string()),
+                ("BAR".to_string(), "default".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.with_defaults_from(&defaults).environment,
+            BTreeMap::from([
+                ("BAR".to_string(), "default".to_string()),
+                ("FOO".to_string(), "own".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_document_config_environment() {
+        let config: DocumentConfig =
+            serde_yaml::from_str("env:\n  FOO: document\n  BAR: document\ndefaults:\n  environment:\n    FOO: default\n    BAZ: default\n")
+                .expect("parse document config");
+        let testcase: TestCaseConfig =
+            serde_yaml::from_str("{env: {BAR: own}}").expect("parse testcase config");
+        assert_eq!(
+            testcase
+                .with_defaults_from(&config.testcase_defaults())
+                .environment,
+            BTreeMap::from([
+                ("BAR".to_string(), "own".to_string()),
+                ("BAZ".to_string(), "default".to_string()),
+                ("FOO".to_string(), "document".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_render_command_template() {
+        let config: DocumentConfig =
+            serde_yaml::from_str("command_template: cli --config {fixture}/cfg.yaml {args}\n")
+                .expect("parse document config");
+        assert_eq!(
+            config
+                .render_command_template("--flag '{fixture}'")
+                .expect("render template"),
+            "cli --config ${TESTDIR}/cfg.yaml --flag '{fixture}'"
+        );
+        assert!(
+            DocumentConfig::empty()
+                .render_command_template("--flag")
+                .is_err(),
+            "no template"
+        );
+    }
+
+    #[test]
+    fn test_deprecation() {
+        let date = |s: &str| s.parse::<DeprecationDate>().expect("parse date");
+        let deprecation: Deprecation =
+            serde_yaml::from_str("{after: 2025-06-30, reason: \"use foo\"}").expect("parse");
+        assert_eq!(
+            "deprecated after 2025-06-30: use foo",
+            deprecation.to_string()
+        );
+        assert!(!deprecation.is_expired(&date("2025-06-30")));
+        assert!(deprecation.is_expired(&date("2025-07-01")));
+        assert!(!deprecation.is_failing(&date("2099-01-01")));
+
+        let deprecation = Deprecation {
+            fail_after: Some(date("2025-12-31")),
+            ..deprecation
+        };
+        assert!(!deprecation.is_failing(&date("2025-12-31")));
+        assert!(deprecation.is_failing(&date("2026-01-01")));
+
+        assert!(date("2024-02-29") < date("2024-03-01"));
+        assert!(DeprecationDate::today() > date("2025-01-01"));
+        for invalid in [
+            "2025-6-30",
+            "2025-02-30",
+            "2023-02-29",
+            "tomorrow",
+            "2025-06-30x",
+        ] {
+            invalid
+                .parse::<DeprecationDate>()
+                .expect_err(&format!("invalid date `{invalid}`"));
+        }
+    }
+
+    #[test]
+    fn test_strip_ansi_alias() {
+        let config: DocumentConfig =
+            serde_yaml::from_str("defaults:\n  strip_ansi: true\n").expect("parse document config");
+        assert_eq!(config.testcase_defaults().strip_ansi_escaping, Some(true));
+        let testcase: TestCaseConfig =
+            serde_yaml::from_str("{strip_ansi: false}").expect("parse testcase config");
+        assert_eq!(testcase.strip_ansi_escaping, Some(false));
+    }
+
+    #[test]
+    fn test_allow_empty_tests_policy() {
+        for (yaml, expected) in [
+            ("{allow_empty_tests: true}", EmptyTestsPolicy::Allow),
+            ("{allow_empty_tests: false}", EmptyTestsPolicy::Fail),
+            ("{allow_empty_tests: warn}", EmptyTestsPolicy::Warn),
+        ] {
+            let config: TestCaseConfig = serde_yaml::from_str(yaml).expect("parse testcase config");
+            assert_eq!(config.allow_empty_tests, Some(expected), "{yaml}");
+            assert_eq!(config.to_yaml_one_liner(), yaml, "{yaml}");
+        }
+        assert!(serde_yaml::from_str::<TestCaseConfig>("{allow_empty_tests: fail}").is_err());
+    }
+
+    #[test]
+    fn test_max_rss() {
+        for (yaml, expected, rendered) in [
+            ("{max_rss: 1024}", 1024, "{max_rss: 1KiB}"),
+            ("{max_rss: 1000}", 1000, "{max_rss: 1000B}"),
+            ("{max_rss: 512MiB}", 512 << 20, "{max_rss: 512MiB}"),
+            ("{max_rss: 2 GiB}", 2 << 30, "{max_rss: 2GiB}"),
+            ("{max_rss: 3MB}", 3_000_000, "{max_rss: 3000000B}"),
+            ("{max_rss: 4KB}", 4_000, "{max_rss: 4000B}"),
+        ] {
+            let config: TestCaseConfig = serde_yaml::from_str(yaml).expect("parse testcase config");
+            assert_eq!(config.max_rss, Some(ByteSize(expected)), "{yaml}");
+            assert_eq!(config.to_yaml_one_liner(), rendered, "{yaml}");
+        }
+        assert!(serde_yaml::from_str::<TestCaseConfig>("{max_rss: 5 apples}").is_err());
+        assert!(serde_yaml::from_str::<TestCaseConfig>("{max_rss: -1}").is_err());
+    }
+
+    #[test]
+    fn test_stdin() {
+        let config: TestCaseConfig =
+            serde_yaml::from_str("{stdin: \"foo\\nbar\\n\"}").expect("parse content");
+        assert_eq!(
+            config.stdin,
+            Some(TestCaseStdin::Content("foo\nbar\n".into()))
+        );
+        assert_eq!(config.to_yaml_one_liner(), "{stdin: \"foo\\nbar\\n\"}");
+
+        let config: TestCaseConfig =
+            serde_yaml::from_str("{stdin: {file: input.txt}}").expect("parse file");
+        assert_eq!(
+            config.stdin,
+            Some(TestCaseStdin::File {
+                file: PathBuf::from("input.txt")
+            })
+        );
+        assert_eq!(config.to_yaml_one_liner(), "{stdin: {file: \"input.txt\"}}");
+
+        assert!(serde_yaml::from_str::<TestCaseConfig>("{stdin: {path: input.txt}}").is_err());
+    }
+
+    #[test]
+    fn test_fixtures() {
+        let config: DocumentConfig = serde_yaml::from_str(
+            "fixtures: [data, {source: app.toml, target: etc/app.toml, template: jinja}]",
+        )
+        .expect("parse fixtures");
+        assert_eq!(
+            config.fixtures,
+            vec![
+                Fixture {
+                    source: "data".into(),
+                    target: None,
+                    template: None,
+                },
+                Fixture {
+                    source: "app.toml".into(),
+                    target: Some("etc/app.toml".into()),
+                    template: Some(DocumentTemplate::Jinja),
+                },
+            ]
+        );
+        assert!(
+            serde_yaml::from_str::<DocumentConfig>("fixtures: [{path: data}]").is_err(),
+            "unknown key"
+        );
+    }
+
+    #[test]
+    fn test_stage_fixtures() {
+        let document = tempfile::TempDir::with_prefix("document.").expect("create directory");
+        let work = tempfile::TempDir::with_prefix("work.").expect("create directory");
+        fs::create_dir_all(document.path().join("data/sub")).expect("create fixture directory");
+        fs::write(document.path().join("data/sub/file.txt"), "{{ name }}").expect("write file");
+        fs::write(document.path().join("app.toml"), "name = \"{{ name }}\"\n").expect("write file");
+
+        let config: DocumentConfig = serde_yaml::from_str(
+            "{fixtures: [data, {source: app.toml, target: etc/app.toml, template: jinja}], vars: {name: scrut}}",
+        )
+        .expect("parse config");
+        config
+            .stage_fixtures(document.path(), work.path())
+            .expect("stage fixtures");
+
+        assert_eq!(
+            "{{ name }}",
+            fs::read_to_string(work.path().join("data/sub/file.txt")).expect("read copied file")
+        );
+        assert_eq!(
+            "name = \"scrut\"\n",
+            fs::read_to_string(work.path().join("etc/app.toml")).expect("read rendered file")
+        );
+
+        let config: DocumentConfig =
+            serde_yaml::from_str("{fixtures: [{source: app.toml, target: ../app.toml}]}")
+                .expect("parse config");
+        assert!(
+            config.stage_fixtures(document.path(), work.path()).is_err(),
+            "target outside of work directory"
+        );
+    }
+
+    #[test]
+    fn test_assert_files() {
+        let document = tempfile::TempDir::with_prefix("document.").expect("create directory");
+        fs::create_dir_all(document.path().join("golden")).expect("create golden directory");
+        fs::write(document.path().join("golden/result.json"), "{}").expect("write golden file");
+
+        let config: TestCaseConfig = serde_yaml::from_str(
+            "{assert_files: {exists: [out/], absent: [tmp/], matches: {out/result.json: '@golden/result.json', out/name.txt: scrut}}}",
+        )
+        .expect("parse assert_files");
+        assert_eq!(
+            config.to_yaml_one_liner(),
+            "{assert_files: {exists: [\"out/\"], absent: [\"tmp/\"], matches: {\"out/name.txt\": \"scrut\", \"out/result.json\": \"@golden/result.json\"}}}"
+        );
+        let mut assert_files = config.assert_files.expect("has assert_files");
+        assert_files.load_golden_files(document.path());
+
+        let produced_files = BTreeMap::from([
+            ("out/".to_string(), ProducedFile::Directory),
+            ("tmp/".to_string(), ProducedFile::Missing),
+            (
+                "out/result.json".to_string(),
+                ProducedFile::File(b"{}".to_vec()),
+            ),
+            (
+                "out/name.txt".to_string(),
+                ProducedFile::File(b"scrut".to_vec()),
+            ),
+        ]);
+        assert_eq!(assert_files.check(&produced_files), vec![]);
+        assert!(assert_files.golden_file_updates(&produced_files).is_empty());
+
+        let produced_files = BTreeMap::from([
+            ("out/".to_string(), ProducedFile::File(vec![])),
+            ("tmp/".to_string(), ProducedFile::Directory),
+            (
+                "out/result.json".to_string(),
+                ProducedFile::File(b"[]".to_vec()),
+            ),
+        ]);
+        assert_eq!(
+            assert_files.check(&produced_files),
+            vec![
+                (
+                    "assert_files.exists out/".to_string(),
+                    "out/ is not a directory".to_string()
+                ),
+                (
+                    "assert_files.absent tmp/".to_string(),
+                    "tmp/ exists".to_string()
+                ),
+                (
+                    "assert_files.matches out/name.txt: scrut".to_string(),
+                    "out/name.txt does not exist".to_string()
+                ),
+                (
+                    "assert_files.matches out/result.json: @golden/result.json".to_string(),
+                    "contents of out/result.json differ".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            assert_files.golden_file_updates(&produced_files),
+            vec![("golden/result.json".to_string(), b"[]".to_vec())]
+        );
+
+        assert!(
+            serde_yaml::from_str::<TestCaseConfig>("{assert_files: {present: [out]}}").is_err(),
+            "unknown key"
+        );
+    }
+
+    #[test]
+    fn test_assert_files_trees() {
+        let document = tempfile::TempDir::with_prefix("document.").expect("create directory");
+        let work = tempfile::TempDir::with_prefix("work.").expect("create directory");
+        for (root, files) in [
+            (
+                document.path().join("golden/app"),
+                ["src/main.rs", "README.md"],
+            ),
+            (work.path().join("app"), ["src/main.rs", "build.log"]),
+        ] {
+            for file in files {
+                let path = root.join(file);
+                fs::create_dir_all(path.parent().unwrap()).expect("create directory");
+                fs::write(&path, file).expect("write file");
+            }
+        }
+
+        let config: TestCaseConfig = serde_yaml::from_str(
+            "{assert_files: {trees: {app: {golden: golden/app, ignore: ['*.log']}, other: golden/other}}}",
+        )
+        .expect("parse trees");
+        assert_eq!(
+            config.to_yaml_one_liner(),
+            "{assert_files: {trees: {\"app\": {golden: \"golden/app\", ignore: [\"*.log\"]}, \"other\": \"golden/other\"}}}"
+        );
+        let mut assert_files = config.assert_files.expect("has assert_files");
+        assert_files.load_golden_files(document.path());
+
+        let produced_files = assert_files
+            .trees
+            .iter()
+            .map(|(path, tree)| (path.clone(), tree.read(&work.path().join(path))))
+            .collect();
+        assert_eq!(
+            assert_files.check(&produced_files),
+            vec![
+                (
+                    "assert_files.trees app: golden/app".to_string(),
+                    "app differs from golden/app: missing README.md".to_string()
+                ),
+                (
+                    "assert_files.trees other: golden/other".to_string(),
+                    "other does not exist".to_string()
+                ),
+            ]
+        );
+        let updates = assert_files.golden_tree_updates(&produced_files);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].0.golden, "golden/app");
+        assert_eq!(
+            updates[0].1.keys().collect::<Vec<_>>(),
+            vec!["src", "src/main.rs"]
+        );
+
+        assert!(
+            serde_yaml::from_str::<TestCaseConfig>(
+                "{assert_files: {trees: {app: {golden: golden/app, ignore: ['[']}}}}"
+            )
+            .is_err(),
+            "invalid ignore pattern"
+        );
+    }
+
+    #[test]
+    fn test_base_environment() {
+        let work_directory = Path::new("/the/work");
+        assert_eq!(
+            DocumentConfig::empty().base_environment(work_directory),
+            std::env::vars_os().collect::<Vec<_>>(),
+            "environment of scrut without hermetic_env"
+        );
+
+        let config: DocumentConfig = serde_yaml::from_str(
+            "{hermetic_env: true, env_passthrough: [CARGO, SCRUT_UNSET_VARIABLE]}",
+        )
+        .expect("parse config");
+        let environment = config
+            .base_environment(work_directory)
+            .into_iter()
+            .map(|(key, value)| {
+                (
+                    key.to_string_lossy().to_string(),
+                    value.to_string_lossy().to_string(),
+                )
+            })
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(
+            environment.get("HOME").map(String::as_str),
+            Some("/the/work")
+        );
+        assert_eq!(environment.get("TZ").map(String::as_str), Some("GMT"));
+        assert_eq!(environment.get("LC_ALL").map(String::as_str), Some("C"));
+        #[cfg(not(windows))]
+        assert_eq!(
+            environment.get("PATH").map(String::as_str),
+            Some(super::HERMETIC_PATH)
+        );
+        assert_eq!(
+            environment.get("CARGO"),
+            std::env::var("CARGO").ok().as_ref()
+        );
+        assert!(!environment.contains_key("SCRUT_UNSET_VARIABLE"));
+        assert!(
+            !environment.contains_key("CARGO_PKG_NAME"),
+            "not passed through"
+        );
+    }
+
+    #[test]
+    fn test_cwd() {
+        let config: TestCaseConfig = serde_yaml::from_str("{cwd: sub/dir}").expect("parse path");
+        assert_eq!(
+            config.cwd,
+            Some(WorkingDirectory::Work(PathBuf::from("sub/dir")))
+        );
+        assert_eq!(config.to_yaml_one_liner(), "{cwd: \"sub/dir\"}");
+        assert_eq!(
+            config
+                .cwd
+                .unwrap()
+                .resolve(Path::new("/work"), Path::new("/tests")),
+            PathBuf::from("/work/sub/dir")
+        );
+
+        let config: TestCaseConfig =
+            serde_yaml::from_str("{cwd: {document: fixtures}}").expect("parse document path");
+        assert_eq!(
+            config.to_yaml_one_liner(),
+            "{cwd: {document: \"fixtures\"}}"
+        );
+        assert_eq!(
+            config
+                .cwd
+                .unwrap()
+                .resolve(Path::new("/work"), Path::new("/tests")),
+            PathBuf::from("/tests/fixtures")
+        );
+        assert!(serde_yaml::from_str::<TestCaseConfig>("{cwd: {work: fixtures}}").is_err());
+
+        let config: DocumentConfig =
+            serde_yaml::from_str("cwd: sub\ndefaults:\n  cwd: other\n").expect("parse document");
+        assert_eq!(
+            config.testcase_defaults().cwd,
+            Some(WorkingDirectory::Work(PathBuf::from("sub")))
+        );
+    }
+
+    #[test]
+    fn test_workdir() {
+        let config: DocumentConfig =
+            serde_yaml::from_str("workdir: {scope: shared}").expect("parse workdir");
+        assert_eq!(
+            config.get_workdir(),
+            WorkDirectoryConfig {
+                scope: WorkDirectoryScope::Shared,
+                keep: WorkDirectoryKeep::Never,
+            }
+        );
+        assert_eq!(
+            DocumentConfig::empty().get_workdir().scope,
+            WorkDirectoryScope::PerDocument
+        );
+        assert!(serde_yaml::from_str::<DocumentConfig>("workdir: {keep: sometimes}").is_err());
+    }
+
+    #[test]
+    fn test_shell() {
+        let config: DocumentConfig = serde_yaml::from_str("shell: /bin/zsh").expect("parse path");
+        assert_eq!(config.shell, Some(Shell::Path("/bin/zsh".into())));
+        assert_eq!(serde_yaml::to_string(&config).unwrap(), "shell: /bin/zsh\n");
+
+        let config: TestCaseConfig =
+            serde_yaml::from_str("{shell: [python3, -c]}").expect("parse interpreter");
+        assert_eq!(
+            config.shell,
+            Some(Shell::Interpreter(vec!["python3".into(), "-c".into()]))
+        );
+        assert_eq!(config.to_yaml_one_liner(), "{shell: [\"python3\", \"-c\"]}");
+
+        assert!(serde_yaml::from_str::<TestCaseConfig>("{shell: []}").is_err());
+    }
+
+    #[test]
+    fn test_parse_env_file() {
+        let environment = parse_env_file(
+            "# comment\n\nFOO=bar\nexport BAZ = zoing # comment\nQUOTED=\"a \\\"b\\\"\\nc\"\nSINGLE='d # e'\nEMPTY=\n",
+        )
+        .expect("parse env file");
+        assert_eq!(
+            environment,
+            BTreeMap::from([
+                ("BAZ".to_string(), "zoing".to_string()),
+                ("EMPTY".to_string(), "".to_string()),
+                ("FOO".to_string(), "bar".to_string()),
+                ("QUOTED".to_string(), "a \"b\"\nc".to_string()),
+                ("SINGLE".to_string(), "d # e".to_string()),
+            ])
+        );
 
-    use super::DocumentConfig;
-    use super::KillSignal;
-    use super::TestCaseWait;
-    use crate::config::OutputStreamControl;
-    use crate::config::TestCaseConfig;
+        for invalid in ["FOO", "=bar", "FOO BAR=baz"] {
+            assert!(parse_env_file(invalid).is_err(), "{invalid:?} is rejected");
+        }
+    }
 
-    const FULL_DOCUMENT_CONFIG: &str = "
-append:
-- app1
-- app2
-defaults:
-  detached: true
-  detached_kill_signal: quit
-  fail_fast: true
-  environment:
-    BAZ: zoing
-    FOO: bar
-  keep_crlf: true
-  output_stream: stdout
-  skip_document_code: 123
-  strip_ansi_escaping: true
-  timeout: 6m 4s
-  wait:
-    timeout: 2m 1s
-    path: the-wait-path
-prepend:
-- prep1
-- prep2
-shell: the-shell
-total_timeout: 5m 3s
-";
+    #[test]
+    fn test_parse_env_file_escaped_backslash() {
+        let environment =
+            parse_env_file("NEWLINE=\"a\\\\nb\"\nQUOTE=\"a\\\\\\\"b\"\nOTHER=\"a\\tb\"\n")
+                .expect("parse env file");
+        assert_eq!(
+            environment,
+            BTreeMap::from([
+                ("NEWLINE".to_string(), "a\\nb".to_string()),
+                ("OTHER".to_string(), "a\\tb".to_string()),
+                ("QUOTE".to_string(), "a\\\"b".to_string()),
+            ])
+        );
+    }
 
     #[test]
-    fn test_parse_full_document_config() {
-        let config: DocumentConfig =
-            serde_yaml::from_str(FULL_DOCUMENT_CONFIG).expect("parse full document config");
+    fn test_document_config_with_env_file_loaded() {
+        let root = tempfile::TempDir::with_prefix("config.").expect("create temporary directory");
+        std::fs::write(root.path().join(".env"), "FOO=file\nBAR=file\n").expect("write env file");
+        let config = DocumentConfig {
+            env_file: Some(".env".into()),
+            environment: BTreeMap::from([("FOO".to_string(), "own".to_string())]),
+            ..DocumentConfig::empty()
+        }
+        .with_env_file_loaded(root.path())
+        .expect("load env file");
         assert_eq!(
             config,
             DocumentConfig {
-                shell: Some("the-shell".into()),
-                total_timeout: Some(Duration::from_secs(5 * 60 + 3)),
-                prepend: vec!["prep1".into(), "prep2".into()],
-                append: vec!["app1".into(), "app2".into()],
-                defaults: TestCaseConfig {
-                    output_stream: Some(OutputStreamControl::Stdout),
-                    keep_crlf: Some(true),
-                    timeout: Some(Duration::from_secs(6 * 60 + 4)),
-                    environment: {
-                        let mut m = BTreeMap::new();
-                        m.insert("FOO".to_string(), "bar".to_string());
-                        m.insert("BAZ".to_string(), "zoing".to_string());
-                        m
-                    },
-                    detached: Some(true),
-                    detached_kill_signal: Some(KillSignal::test_default()),
-                    fail_fast: Some(true),
-                    wait: Some(TestCaseWait {
-                        timeout: Duration::from_secs(2 * 60 + 1),
-                        path: Some(PathBuf::from("the-wait-path")),
-                    }),
-                    skip_document_code: Some(123),
-                    strip_ansi_escaping: Some(true),
-                }
+                environment: BTreeMap::from([
+                    ("BAR".to_string(), "file".to_string()),
+                    ("FOO".to_string(), "own".to_string()),
+                ]),
+                ..DocumentConfig::empty()
             }
-        )
+        );
     }
 
     #[test]
-    fn test_render_full_document_config() {
+    fn test_document_config_normalize_output() {
         let config = DocumentConfig {
-            shell: Some("the-shell".into()),
-            total_timeout: Some(Duration::from_secs(5 * 60 + 3)),
-            prepend: vec!["prep1".into(), "prep2".into()],
-            append: vec!["app1".into(), "app2".into()],
-            defaults: TestCaseConfig {
-                output_stream: Some(OutputStreamControl::Stdout),
-                keep_crlf: Some(true),
-                timeout: Some(Duration::from_secs(6 * 60 + 4)),
-                environment: {
-                    let mut m = BTreeMap::new();
-                    m.insert("FOO".to_string(), "bar".to_string());
-                    m.insert("BAZ".to_string(), "zoing".to_string());
-                    m
+            normalizers: vec![
+                OutputNormalizer {
+                    pattern: "[0-9]{4}-[0-9]{2}-[0-9]{2}".into(),
+                    replacement: "<date>".into(),
                 },
-                detached: Some(true),
-                detached_kill_signal: Some(KillSignal::test_default()),
-                fail_fast: Some(true),
-                wait: Some(TestCaseWait {
-                    timeout: Duration::from_secs(2 * 60 + 1),
-                    path: Some(PathBuf::from("the-wait-path")),
-                }),
-                skip_document_code: Some(123),
-                strip_ansi_escaping: Some(true),
-            },
+                OutputNormalizer {
+                    pattern: "^pid ([0-9]+)$".into(),
+                    replacement: "pid <pid>".into(),
+                },
+                OutputNormalizer {
+                    pattern: "(?<dir>/tmp)/[a-z]+".into(),
+                    replacement: "${dir}/<random>".into(),
+                },
+            ],
+            ..DocumentConfig::empty()
         };
+        let output = config
+            .normalize_output(
+                (
+                    "at 2024-01-31 in /tmp/abc\npid 123\nthe pid 123\n",
+                    "pid 456",
+                )
+                    .into(),
+            )
+            .expect("normalize output");
         assert_eq!(
-            serde_yaml::to_string(&config).expect("render document config to YAML"),
-            FULL_DOCUMENT_CONFIG.to_string().trim_start(),
-        )
-    }
+            output.stdout.to_bytes(),
+            b"at <date> in /tmp/<random>\npid <pid>\nthe pid 123\n"
+        );
+        assert_eq!(output.stderr.to_bytes(), b"pid <pid>");
 
-    const FULL_TESTCASE_CONFIG: &str = "
-detached: true
-detached_kill_signal: quit
-fail_fast: true
-environment:
-  BAZ: zoing
-  FOO: bar
-keep_crlf: true
-output_stream: stderr
-skip_document_code: 123
-strip_ansi_escaping: true
-timeout: 6m 4s
-wait:
-  timeout: 2m 1s
-  path: the-wait-path
-";
+        let config = DocumentConfig {
+            normalizers: vec![OutputNormalizer {
+                pattern: "foo(".into(),
+                replacement: "".into(),
+            }],
+            ..DocumentConfig::empty()
+        };
+        config
+            .normalize_output(("foo", "").into())
+            .expect_err("invalid pattern");
+    }
 
     #[test]
-    fn test_parse_full_testcase_config() {
-        let config: TestCaseConfig =
-            serde_yaml::from_str(FULL_TESTCASE_CONFIG).expect("parse full testcase config");
-        assert_eq!(
-            config,
-            TestCaseConfig {
-                output_stream: Some(OutputStreamControl::Stderr),
-                keep_crlf: Some(true),
-                timeout: Some(Duration::from_secs(6 * 60 + 4)),
-                environment: {
-                    let mut m = BTreeMap::new();
-                    m.insert("FOO".to_string(), "bar".to_string());
-                    m.insert("BAZ".to_string(), "zoing".to_string());
-                    m
-                },
-                detached: Some(true),
-                detached_kill_signal: Some(KillSignal::test_default()),
-                fail_fast: Some(true),
-                wait: Some(TestCaseWait {
-                    timeout: Duration::from_secs(2 * 60 + 1),
-                    path: Some(PathBuf::from("the-wait-path")),
-                }),
-                skip_document_code: Some(123),
-                strip_ansi_escaping: Some(true),
-            }
-        )
+    fn test_interpolate() {
+        let lookup = |name: &str| (name == "HOME").then(|| "/home/me".to_string());
+        assert_eq!(interpolate("plain", lookup), "plain");
+        assert_eq!(interpolate("${HOME}/bin", lookup), "/home/me/bin");
+        assert_eq!(interpolate("a${UNSET}b", lookup), "ab");
+        assert_eq!(interpolate("$${HOME}:${HOME}", lookup), "${HOME}:/home/me");
+        assert_eq!(interpolate("$HOME ${HOME", lookup), "$HOME ${HOME");
     }
 
     #[test]
-    fn test_render_full_testcase_config() {
+    fn test_interpolated_environment_with_terminal_size() {
         let config = TestCaseConfig {
-            output_stream: Some(OutputStreamControl::Stderr),
-            keep_crlf: Some(true),
-            timeout: Some(Duration::from_secs(6 * 60 + 4)),
-            environment: {
-                let mut m = BTreeMap::new();
-                m.insert("FOO".to_string(), "bar".to_string());
-                m.insert("BAZ".to_string(), "zoing".to_string());
-                m
-            },
-            detached: Some(true),
-            detached_kill_signal: Some(KillSignal::test_default()),
-            fail_fast: Some(true),
-            wait: Some(TestCaseWait {
-                timeout: Duration::from_secs(2 * 60 + 1),
-                path: Some(PathBuf::from("the-wait-path")),
+            terminal_size: Some(TerminalSize {
+                columns: 120,
+                rows: 40,
             }),
-            skip_document_code: Some(123),
-            strip_ansi_escaping: Some(true),
+            environment: BTreeMap::from([
+                ("COLUMNS".to_string(), "80".to_string()),
+                ("FOO".to_string(), "bar".to_string()),
+            ]),
+            ..Default::default()
         };
         assert_eq!(
-            serde_yaml::to_string(&config).expect("render testcase config to YAML"),
-            FULL_TESTCASE_CONFIG.to_string().trim_start(),
+            config.interpolated_environment(),
+            BTreeMap::from([
+                ("COLUMNS".to_string(), "120".to_string()),
+                ("FOO".to_string(), "bar".to_string()),
+                ("LINES".to_string(), "40".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_document_config_with_extends_resolved() {
+        let root = tempfile::TempDir::with_prefix("extends.").expect("create temporary directory");
+        let common = root.path().join("common");
+        std::fs::create_dir_all(&common).expect("create common directory");
+        std::fs::write(
+            common.join("base.yaml"),
+            "shell: base-shell\ntotal_timeout: 1m\ndefaults:\n  environment:\n    FOO: base\n    BAR: base\n",
+        )
+        .expect("write base configuration");
+        std::fs::write(
+            common.join("shared.yaml"),
+            "extends: base.yaml\nshell: shared-shell\nprepend:\n- setup.md\ndefaults:\n  environment:\n    FOO: shared\n",
         )
+        .expect("write shared configuration");
+
+        let config = DocumentConfig {
+            extends: Some(PathBuf::from("common/shared.yaml")),
+            total_timeout: Some(Duration::from_secs(5)),
+            ..DocumentConfig::empty()
+        }
+        .with_extends_resolved(root.path())
+        .expect("resolve extends");
+        assert_eq!(
+            config,
+            DocumentConfig {
+                shell: Some("shared-shell".into()),
+                total_timeout: Some(Duration::from_secs(5)),
+                prepend: vec![common.join("setup.md")],
+                defaults: TestCaseConfig {
+                    environment: BTreeMap::from([
+                        ("BAR".to_string(), "base".to_string()),
+                        ("FOO".to_string(), "shared".to_string()),
+                    ]),
+                    ..TestCaseConfig::empty()
+                },
+                ..DocumentConfig::empty()
+            }
+        );
+
+        std::fs::write(common.join("base.yaml"), "extends: shared.yaml\n")
+            .expect("write circular configuration");
+        let err = DocumentConfig {
+            extends: Some(PathBuf::from("common/shared.yaml")),
+            ..DocumentConfig::empty()
+        }
+        .with_extends_resolved(root.path())
+        .expect_err("circular extends are rejected");
+        assert!(
+            format!("{err:#}").contains("circular extends"),
+            "unexpected error: {err:#}"
+        );
     }
 
     #[test]
-    fn test_testcase_config_yaml_one_liner() {
-        let tests = [
-            (TestCaseConfig::empty(), "{}"),
+    fn test_parse_config_override() {
+        let tests = vec![
             (
-                TestCaseConfig {
-                    keep_crlf: Some(true),
-                    ..Default::default()
-                },
-                "{keep_crlf: true}",
+                "total_timeout=10m",
+                ConfigOverride::Document(DocumentConfig {
+                    total_timeout: Some(Duration::from_secs(600)),
+                    ..DocumentConfig::empty()
+                }),
             ),
             (
-                TestCaseConfig {
-                    wait: Some(TestCaseWait {
-                        timeout: Duration::from_secs(123),
-                        path: None,
-                    }),
-                    ..Default::default()
-                },
-                "{wait: 2m 3s}",
+                "shell=/bin/zsh",
+                ConfigOverride::Document(DocumentConfig {
+                    shell: Some("/bin/zsh".into()),
+                    ..DocumentConfig::empty()
+                }),
             ),
             (
-                TestCaseConfig {
-                    output_stream: Some(OutputStreamControl::Stderr),
+                "keep_crlf=true",
+                ConfigOverride::TestCase(TestCaseConfig {
                     keep_crlf: Some(true),
-                    detached: Some(false),
-                    detached_kill_signal: None,
-                    fail_fast: Some(false),
-                    environment: BTreeMap::from([("foo".to_string(), "bar".to_string())]),
-                    skip_document_code: Some(123),
-                    strip_ansi_escaping: Some(true),
-                    timeout: Some(Duration::from_secs(234)),
-                    wait: Some(TestCaseWait {
-                        timeout: Duration::from_secs(123),
-                        path: Some(PathBuf::from("/tmp/wait")),
-                    }),
-                },
-                "{output_stream: stderr, keep_crlf: true, timeout: 3m 54s, detached: false, fail_fast: false, skip_document_code: 123, strip_ansi_escaping: true, wait: {timeout: 2m 3s, path: /tmp/wait}, environment: {foo: \"bar\"}}",
+                    ..TestCaseConfig::empty()
+                }),
+            ),
+            (
+                "environment.FOO=123",
+                ConfigOverride::TestCase(TestCaseConfig {
+                    environment: BTreeMap::from([("FOO".to_string(), "123".to_string())]),
+                    ..TestCaseConfig::empty()
+                }),
+            ),
+            (
+                "env.FOO=123",
+                ConfigOverride::TestCase(TestCaseConfig {
+                    environment: BTreeMap::from([("FOO".to_string(), "123".to_string())]),
+                    ..TestCaseConfig::empty()
+                }),
+            ),
+            (
+                "cwd=b",
+                ConfigOverride::TestCase(TestCaseConfig {
+                    cwd: Some(WorkingDirectory::Work("b".into())),
+                    ..TestCaseConfig::empty()
+                }),
+            ),
+            (
+                "defaults.timeout=5s",
+                ConfigOverride::Document(DocumentConfig {
+                    defaults: TestCaseConfig {
+                        timeout: Some(Duration::from_secs(5)),
+                        ..TestCaseConfig::empty()
+                    },
+                    ..DocumentConfig::empty()
+                }),
             ),
         ];
-        for (idx, (config, expected)) in tests.iter().enumerate() {
-            let yaml = config.to_yaml_one_liner();
-            assert_eq!(
-                expected.to_string(),
-                yaml,
-                "test {idx}: for config {config:?}"
+        for (raw, expected) in tests {
+            let parsed: ConfigOverride = raw
+                .parse()
+                .unwrap_or_else(|err| panic!("parse {raw:?}: {err}"));
+            assert_eq!(parsed, expected, "for input {raw:?}");
+        }
+
+        for raw in ["no_equal_sign", "unknown_key=1", "keep_crlf=maybe", "=1"] {
+            assert!(
+                raw.parse::<ConfigOverride>().is_err(),
+                "{raw:?} is rejected"
             );
         }
     }
@@ -790,6 +4687,9 @@ wait:
                 Some(TestCaseWait {
                     timeout: Duration::from_secs(3 * 60 + 4),
                     path: None,
+                    port: None,
+                    http: None,
+                    status: None,
                 }),
             ),
             (
@@ -797,6 +4697,29 @@ wait:
                 Some(TestCaseWait {
                     timeout: Duration::from_secs(3 * 60 + 5),
                     path: Some(PathBuf::from("some/file/name")),
+                    port: None,
+                    http: None,
+                    status: None,
+                }),
+            ),
+            (
+                "wait: {port: 8080}",
+                Some(TestCaseWait {
+                    timeout: Duration::from_secs(DEFAULT_WAIT_TIMEOUT),
+                    path: None,
+                    port: Some(8080),
+                    http: None,
+                    status: None,
+                }),
+            ),
+            (
+                "wait: {timeout: 5s, http: \"http://localhost:8080/health\", status: 204}",
+                Some(TestCaseWait {
+                    timeout: Duration::from_secs(5),
+                    path: None,
+                    port: None,
+                    http: Some("http://localhost:8080/health".into()),
+                    status: Some(204),
                 }),
             ),
         ];
@@ -805,5 +4728,53 @@ wait:
                 serde_yaml::from_str(raw).unwrap_or_else(|err| panic!("parse {raw:?}: {err}"));
             assert_eq!(config.wait, expect, "for input {raw:?}");
         }
+
+        for raw in [
+            "wait: {http: \"https://localhost/health\"}",
+            "wait: {port: 8080, status: 200}",
+        ] {
+            assert!(
+                serde_yaml::from_str::<TestCaseConfig>(raw).is_err(),
+                "invalid {raw:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_unicode_normalization() {
+        let decomposed = "cafe\u{301}";
+        let composed = "caf\u{e9}";
+        let tests = [
+            (UnicodeNormalization::Nfc, decomposed, composed),
+            (UnicodeNormalization::Nfc, composed, composed),
+            (UnicodeNormalization::Nfd, composed, decomposed),
+            (UnicodeNormalization::Nfd, decomposed, decomposed),
+            (UnicodeNormalization::None, decomposed, decomposed),
+            (UnicodeNormalization::None, composed, composed),
+        ];
+        for (normalization, input, expect) in tests {
+            assert_eq!(
+                expect,
+                normalization.normalize(input),
+                "{normalization} of {input:?}"
+            );
+            assert_eq!(
+                expect.as_bytes(),
+                normalization.normalize_bytes(input.as_bytes()).as_ref(),
+                "{normalization} of bytes {input:?}"
+            );
+        }
+
+        let mut invalid = decomposed.as_bytes().to_vec();
+        invalid.extend_from_slice(b"\xff\n");
+        let mut expect = composed.as_bytes().to_vec();
+        expect.extend_from_slice(b"\xff\n");
+        assert_eq!(
+            expect,
+            UnicodeNormalization::Nfc
+                .normalize_bytes(&invalid)
+                .into_owned(),
+            "invalid bytes are kept"
+        );
     }
 }
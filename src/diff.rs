@@ -5,12 +5,18 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::collections::BTreeMap;
 use std::fmt::Debug;
+use std::ops::Range;
 
 use anyhow::Result;
 use serde::Serialize;
 use serde::ser::SerializeMap;
 
+use crate::diff_algorithm::DiffAlgorithm;
+use crate::diff_algorithm::Edit;
+use crate::diff_algorithm::MAX_ALIGNMENT_LENGTH;
+use crate::diff_algorithm::align;
 use crate::expectation::Expectation;
 use crate::lossy_string;
 use crate::newline::BytesNewline;
@@ -75,21 +81,197 @@ use crate::newline::SplitLinesByNewline;
 /// );
 /// ```
 pub struct DiffTool {
+    /// The expectations as they are aligned with lines of output
     expectations: Vec<Expectation>,
+
+    /// The expectations as they were given and the index of the given
+    /// expectation that each aligned expectation stems from, if they differ:
+    /// Expectations that assert absence are left out and expectations with a
+    /// line count are repeated as often as their count allows
+    originals: Option<(Vec<Expectation>, Vec<usize>)>,
+
+    /// Hex encoded SHA-256 digests of produced files, by the path that
+    /// checksum expectations refer to them with, or `None` for missing files
+    file_digests: BTreeMap<String, Option<String>>,
+
+    /// How expectations are aligned with lines of output
+    algorithm: DiffAlgorithm,
 }
 
 impl DiffTool {
-    /// Construct from list of expectations
+    /// Construct from list of expectations. Expectations that assert absence
+    /// (see [`crate::rules::rule::Rule::asserts_absence`]) are left out.
     pub fn new(expectations: Vec<Expectation>) -> Self {
-        Self { expectations }
+        let (expectations, originals) = if expectations
+            .iter()
+            .any(|expectation| expectation.rule.asserts_absence() || expectation.count.is_some())
+        {
+            let (aligned, indices) = expectations
+                .iter()
+                .enumerate()
+                .flat_map(|(index, expectation)| {
+                    aligned_expectations(expectation)
+                        .into_iter()
+                        .map(move |aligned| (aligned, index))
+                })
+                .unzip();
+            (aligned, Some((expectations, indices)))
+        } else {
+            (expectations, None)
+        };
+        Self {
+            expectations,
+            originals,
+            file_digests: BTreeMap::new(),
+            algorithm: DiffAlgorithm::default(),
+        }
+    }
+
+    /// Provide the algorithm that aligns expectations with lines of output.
+    /// Expectations of checksums and outputs that exceed
+    /// [`MAX_ALIGNMENT_LENGTH`] are always aligned with
+    /// [`DiffAlgorithm::Lookahead`].
+    pub fn with_algorithm(mut self, algorithm: DiffAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Provide the digests of produced files, that checksum expectations of
+    /// files are compared with
+    pub fn with_file_digests(mut self, file_digests: BTreeMap<String, Option<String>>) -> Self {
+        self.file_digests = file_digests;
+        self
     }
 
     /// Compares output with expectations and returns line-wise results that
     /// describe whether and which expectations matched, did not match, were
     /// not used and which lines were unexpected
     pub fn diff(&self, output: &[u8]) -> Result<Diff> {
-        let lines = output.split_at_newline();
-        let to_output_list = |i| -> (usize, Vec<u8>) { (i, lines[i].to_owned()) };
+        let (lines, origins) = self.ordered_lines(output.split_at_newline());
+        let diff = if self.is_aligned(&lines) {
+            self.diff_aligned(&lines)
+        } else {
+            self.to_diff(self.diff_lookahead(&lines), &lines)
+        };
+        Ok(with_origins(
+            diff,
+            origins.as_deref(),
+            self.originals.as_ref(),
+        ))
+    }
+
+    /// Compares output with expectations like [`DiffTool::diff`], but returns
+    /// the result only if there are differences. Output that matches is
+    /// compared without copying any of its lines, which keeps comparing huge
+    /// outputs cheap.
+    pub fn differences(&self, output: &[u8]) -> Result<Option<Diff>> {
+        let (lines, origins) = self.ordered_lines(output.split_at_newline());
+        if self.is_aligned(&lines) {
+            let diff = self.diff_aligned(&lines);
+            return Ok(diff
+                .has_differences()
+                .then(|| with_origins(diff, origins.as_deref(), self.originals.as_ref())));
+        }
+        let segments = self.diff_lookahead(&lines);
+        if segments
+            .iter()
+            .all(|segment| matches!(segment, Segment::Matched(..)))
+        {
+            return Ok(None);
+        }
+        Ok(Some(with_origins(
+            self.to_diff(segments, &lines),
+            origins.as_deref(),
+            self.originals.as_ref(),
+        )))
+    }
+
+    /// Returns the lines of output, in which the consecutive lines that each
+    /// group of unordered expectations matches are in the order of the
+    /// expectations, and the original index of each line, if there are groups
+    fn ordered_lines<'a>(&self, mut lines: Vec<&'a [u8]>) -> (Vec<&'a [u8]>, Option<Vec<usize>>) {
+        let groups = self.unordered_groups();
+        if groups.is_empty() {
+            return (lines, None);
+        }
+        let mut origins = (0..lines.len()).collect::<Vec<_>>();
+        let mut start_line_index = 0;
+        for group in groups {
+            let expectations = &self.expectations[group];
+            let Some((start, order)) = find_unordered_run(expectations, &lines, start_line_index)
+            else {
+                continue;
+            };
+            let run = order
+                .iter()
+                .map(|offset| (lines[start + offset], origins[start + offset]))
+                .collect::<Vec<_>>();
+            for (offset, (line, origin)) in run.into_iter().enumerate() {
+                lines[start + offset] = line;
+                origins[start + offset] = origin;
+            }
+            start_line_index = start + expectations.len();
+        }
+        (lines, Some(origins))
+    }
+
+    /// Returns the index ranges of the groups of consecutive unordered
+    /// expectations
+    fn unordered_groups(&self) -> Vec<Range<usize>> {
+        let mut groups: Vec<Range<usize>> = vec![];
+        for (index, expectation) in self.expectations.iter().enumerate() {
+            if !expectation.unordered {
+                continue;
+            }
+            match groups.last_mut() {
+                Some(group) if group.end == index => group.end += 1,
+                _ => groups.push(index..index + 1),
+            }
+        }
+        groups
+    }
+
+    /// Whether the lines are aligned with the configured [`DiffAlgorithm`],
+    /// instead of with [`DiffAlgorithm::Lookahead`]
+    fn is_aligned(&self, lines: &[&[u8]]) -> bool {
+        self.algorithm != DiffAlgorithm::Lookahead
+            && self.expectations.len() + lines.len() <= MAX_ALIGNMENT_LENGTH
+            && !self.expectations.iter().any(|expectation| {
+                expectation.rule.file_checksum().is_some()
+                    || expectation.rule.matches_remaining_output()
+            })
+    }
+
+    /// Returns the [`DiffLine`]s of the segments, with the lines of output
+    /// that they refer to
+    fn to_diff(&self, segments: Vec<Segment>, lines: &[&[u8]]) -> Diff {
+        let to_output_list = |range: Range<usize>| -> Vec<(usize, Vec<u8>)> {
+            range.map(|index| (index, lines[index].to_vec())).collect()
+        };
+        Diff::new(
+            segments
+                .into_iter()
+                .map(|segment| match segment {
+                    Segment::Matched(index, range) => DiffLine::MatchedExpectation {
+                        index,
+                        expectation: self.expectations[index].to_owned(),
+                        lines: to_output_list(range),
+                    },
+                    Segment::Unmatched(index) => DiffLine::UnmatchedExpectation {
+                        index,
+                        expectation: self.expectations[index].to_owned(),
+                    },
+                    Segment::Unexpected(range) => DiffLine::UnexpectedLines {
+                        lines: to_output_list(range),
+                    },
+                })
+                .collect(),
+        )
+    }
+
+    /// Compares the lines of output with expectations, by looking ahead for
+    /// the next matching expectation or line whenever they do not match
+    fn diff_lookahead(&self, lines: &[&[u8]]) -> Vec<Segment> {
         let mut expectation_index = 0;
         let mut line_index = 0;
         let mut diffs = vec![];
@@ -103,6 +285,18 @@ impl DiffTool {
             let next_expectation = self.expectations.get(expectation_index + 1);
             let line = lines[line_index];
 
+            // .. that asserts a produced file or all remaining output at once
+            if let Some(segment) = self.diff_checksum(expectation_index, lines, line_index) {
+                if matches!(segment, Segment::Matched(..))
+                    && expectation.rule.matches_remaining_output()
+                {
+                    line_index = lines.len();
+                }
+                diffs.push(segment);
+                expectation_index += 1;
+                continue;
+            }
+
             // .. that matches the line
             if expectation.matches(line) {
                 // .. and is multiline -> keep going to next line(s)
@@ -115,13 +309,10 @@ impl DiffTool {
                         {
                             // make sure to note the previous multiline expectation
                             if let Some(match_start_index) = match_start {
-                                diffs.push(DiffLine::MatchedExpectation {
-                                    index: expectation_index,
-                                    expectation: expectation.to_owned(),
-                                    lines: (match_start_index..line_index)
-                                        .map(to_output_list)
-                                        .collect(),
-                                });
+                                diffs.push(Segment::Matched(
+                                    expectation_index,
+                                    match_start_index..line_index,
+                                ));
                             }
 
                             // and then assure the next expectation is selected
@@ -142,11 +333,10 @@ impl DiffTool {
                 }
 
                 // .. so note the match and go to next line with next expectation
-                diffs.push(DiffLine::MatchedExpectation {
-                    index: expectation_index,
-                    expectation: expectation.to_owned(),
-                    lines: vec![(line_index, line.to_owned())],
-                });
+                diffs.push(Segment::Matched(
+                    expectation_index,
+                    line_index..line_index + 1,
+                ));
                 line_index += 1;
                 expectation_index += 1;
                 continue;
@@ -156,13 +346,10 @@ impl DiffTool {
             //    before: make sure to log those lines the match and attempt the
             //    next expectation for the current line
             if let Some(match_start_index) = match_start {
-                diffs.push(DiffLine::MatchedExpectation {
-                    index: expectation_index,
-                    expectation: expectation.to_owned(),
-                    lines: (match_start_index..line_index)
-                        .map(to_output_list)
-                        .collect(),
-                });
+                diffs.push(Segment::Matched(
+                    expectation_index,
+                    match_start_index..line_index,
+                ));
                 match_start = None;
                 expectation_index += 1;
                 continue;
@@ -171,19 +358,14 @@ impl DiffTool {
 
             // .. that does not match the current line, so ..
             //   .. let find whatever is closer (if any):
-            match self.peek_match(line_index, &lines, expectation_index) {
+            match self.peek_match(line_index, lines, expectation_index) {
                 //     .. the next matching expectation for the current line
                 PeekMatch::NextExpectation(next_expectation_index) => {
                     // .. note down not matching of all intermediate expectations
                     //    assuming it is not optional ..
                     (expectation_index..next_expectation_index)
                         .filter(|index| !self.expectations[*index].optional)
-                        .for_each(|index| {
-                            diffs.push(DiffLine::UnmatchedExpectation {
-                                index,
-                                expectation: self.expectations[index].clone(),
-                            });
-                        });
+                        .for_each(|index| diffs.push(Segment::Unmatched(index)));
 
                     // .. then continue with matching expectation
                     expectation_index = next_expectation_index;
@@ -192,9 +374,7 @@ impl DiffTool {
                 //     .. the next matching line for the current expectation
                 PeekMatch::NextLine(next_line_index) => {
                     // .. note down not matching of all intermediate lines ..
-                    diffs.push(DiffLine::UnexpectedLines {
-                        lines: (line_index..next_line_index).map(to_output_list).collect(),
-                    });
+                    diffs.push(Segment::Unexpected(line_index..next_line_index));
 
                     // .. then continue with matching line ..
                     line_index = next_line_index;
@@ -204,10 +384,7 @@ impl DiffTool {
                 PeekMatch::None => {
                     // .. give up and go to next expectation (for the next line) and try again
                     if !expectation.optional {
-                        diffs.push(DiffLine::UnmatchedExpectation {
-                            index: expectation_index,
-                            expectation: expectation.to_owned(),
-                        });
+                        diffs.push(Segment::Unmatched(expectation_index));
                     }
                     expectation_index += 1;
                 }
@@ -216,34 +393,140 @@ impl DiffTool {
 
         // .. ending in a multiline expectation?
         if let Some(match_start) = match_start {
-            diffs.push(DiffLine::MatchedExpectation {
-                index: expectation_index,
-                expectation: self.expectations[expectation_index].to_owned(),
-                lines: (match_start..line_index).map(to_output_list).collect(),
-            });
+            diffs.push(Segment::Matched(expectation_index, match_start..line_index));
             expectation_index += 1;
         }
 
         // .. having unused expectations?
         if expectation_index < self.expectations.len() {
-            (expectation_index..self.expectations.len())
-                .filter(|index| !self.expectations[*index].optional)
-                .for_each(|index| {
-                    diffs.push(DiffLine::UnmatchedExpectation {
-                        index,
-                        expectation: self.expectations[index].to_owned(),
-                    })
-                });
+            for index in expectation_index..self.expectations.len() {
+                if let Some(segment) = self.diff_checksum(index, lines, line_index) {
+                    diffs.push(segment);
+                } else if !self.expectations[index].optional {
+                    diffs.push(Segment::Unmatched(index))
+                }
+            }
         }
 
         // .. having any unvisited lines?
         if line_index < lines.len() {
-            diffs.push(DiffLine::UnexpectedLines {
-                lines: (line_index..lines.len()).map(to_output_list).collect(),
-            });
+            diffs.push(Segment::Unexpected(line_index..lines.len()));
         }
 
-        Ok(Diff::new(diffs))
+        diffs
+    }
+
+    /// Compares output with expectations like [`DiffTool::diff`], but aligns
+    /// them with the configured [`DiffAlgorithm`]. Multiline expectations
+    /// additionally match the adjacent lines that they match.
+    fn diff_aligned(&self, lines: &[&[u8]]) -> Diff {
+        let matching = self
+            .expectations
+            .iter()
+            .map(|expectation| {
+                lines
+                    .iter()
+                    .map(|line| expectation.matches(line))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let edits = align(
+            self.algorithm,
+            self.expectations.len(),
+            lines.len(),
+            &|index, line_index| matching[index][line_index],
+        );
+
+        let mut diffs = vec![];
+        let mut multiline_match = None;
+        for edit in edits {
+            match edit {
+                Edit::Match(index, line_index) => {
+                    let expectation = &self.expectations[index];
+                    let mut matched = vec![(line_index, lines[line_index].to_vec())];
+
+                    // .. a multiline expectation takes the unexpected lines
+                    //    directly before it, that it matches
+                    if expectation.multiline {
+                        if let Some(DiffLine::UnexpectedLines { lines: unexpected }) =
+                            diffs.last_mut()
+                        {
+                            while let Some((previous, _)) = unexpected.last() {
+                                if previous + 1 != matched[0].0 || !matching[index][*previous] {
+                                    break;
+                                }
+                                matched.insert(0, unexpected.pop().expect("last line"));
+                            }
+                            if unexpected.is_empty() {
+                                diffs.pop();
+                            }
+                        }
+                    }
+                    diffs.push(DiffLine::MatchedExpectation {
+                        index,
+                        expectation: expectation.to_owned(),
+                        lines: matched,
+                    });
+                    multiline_match = expectation.multiline.then_some(diffs.len() - 1);
+                }
+                Edit::Unmatched(index) => {
+                    if !self.expectations[index].optional {
+                        diffs.push(DiffLine::UnmatchedExpectation {
+                            index,
+                            expectation: self.expectations[index].to_owned(),
+                        });
+                    }
+                }
+                Edit::Unexpected(line_index) => {
+                    let line = (line_index, lines[line_index].to_vec());
+
+                    // .. and the unexpected lines directly after it
+                    if let Some(DiffLine::MatchedExpectation {
+                        index,
+                        lines: matched,
+                        ..
+                    }) = multiline_match.and_then(|position| diffs.get_mut(position))
+                    {
+                        if matched
+                            .last()
+                            .is_some_and(|(last, _)| last + 1 == line_index)
+                            && matching[*index][line_index]
+                        {
+                            matched.push(line);
+                            continue;
+                        }
+                    }
+                    multiline_match = None;
+                    if let Some(DiffLine::UnexpectedLines { lines: unexpected }) = diffs.last_mut()
+                    {
+                        unexpected.push(line);
+                    } else {
+                        diffs.push(DiffLine::UnexpectedLines { lines: vec![line] });
+                    }
+                }
+            }
+        }
+        Diff::new(diffs)
+    }
+
+    /// Compares a checksum expectation with the digest of the produced file it
+    /// refers to or with all lines from the given line index on, if it is one
+    fn diff_checksum(&self, index: usize, lines: &[&[u8]], line_index: usize) -> Option<Segment> {
+        let expectation = &self.expectations[index];
+        let matched = if let Some((path, digest)) = expectation.rule.file_checksum() {
+            self.file_digests.get(path).and_then(|d| d.as_deref()) == Some(digest)
+        } else if expectation.rule.matches_remaining_output() {
+            expectation.matches(&lines[line_index..].concat())
+        } else {
+            return None;
+        };
+        Some(if !matched {
+            Segment::Unmatched(index)
+        } else if expectation.rule.matches_remaining_output() {
+            Segment::Matched(index, line_index..lines.len())
+        } else {
+            Segment::Matched(index, line_index..line_index)
+        })
     }
 
     /// Returns either the index of the index of the next matching expectation
@@ -299,11 +582,233 @@ impl DiffTool {
         self.expectations
             .iter()
             .skip(start_expectation_index)
-            .position(|expectation| expectation.matches(line))
+            .position(|expectation| {
+                !expectation.rule.matches_remaining_output() && expectation.matches(line)
+            })
             .map(|position| position + start_expectation_index)
     }
 }
 
+/// Returns the index of the first of as many consecutive lines as there are
+/// unordered expectations, from the given line index on, that the expectations
+/// match in some order, and the offset of the line that each expectation matches
+fn find_unordered_run(
+    expectations: &[Expectation],
+    lines: &[&[u8]],
+    start_line_index: usize,
+) -> Option<(usize, Vec<usize>)> {
+    let size = expectations.len();
+    let candidates = lines.get(start_line_index..)?;
+    if candidates.len() < size {
+        return None;
+    }
+    let matching = expectations
+        .iter()
+        .map(|expectation| {
+            candidates
+                .iter()
+                .map(|line| expectation.matches(line))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let matched = (0..candidates.len())
+        .map(|line_index| matching.iter().any(|matches| matches[line_index]))
+        .collect::<Vec<_>>();
+    (0..=candidates.len() - size).find_map(|start| {
+        if !matched[start..start + size].iter().all(|matched| *matched) {
+            return None;
+        }
+        assign_lines(size, &|index, offset| matching[index][start + offset])
+            .map(|order| (start_line_index + start, order))
+    })
+}
+
+/// Assigns each of the expectations a distinct one of as many lines, which it
+/// matches, by finding augmenting paths, and returns the offset of the line
+/// that each expectation is assigned
+fn assign_lines(size: usize, matches: &dyn Fn(usize, usize) -> bool) -> Option<Vec<usize>> {
+    fn augment(
+        index: usize,
+        matches: &dyn Fn(usize, usize) -> bool,
+        owners: &mut [Option<usize>],
+        visited: &mut [bool],
+    ) -> bool {
+        for offset in 0..owners.len() {
+            if visited[offset] || !matches(index, offset) {
+                continue;
+            }
+            visited[offset] = true;
+            if owners[offset].is_none_or(|owner| augment(owner, matches, owners, visited)) {
+                owners[offset] = Some(index);
+                return true;
+            }
+        }
+        false
+    }
+
+    let mut owners = vec![None; size];
+    for index in 0..size {
+        if !augment(index, matches, &mut owners, &mut vec![false; size]) {
+            return None;
+        }
+    }
+    let mut order = vec![0; size];
+    for (offset, owner) in owners.into_iter().enumerate() {
+        order[owner?] = offset;
+    }
+    Some(order)
+}
+
+/// Returns the expectations that stand in for the given expectation when it
+/// is aligned with lines of output: None, if it asserts absence, or as many
+/// as its line count allows, of which those above the lower bound are optional
+fn aligned_expectations(expectation: &Expectation) -> Vec<Expectation> {
+    if expectation.rule.asserts_absence() {
+        return vec![];
+    }
+    let Some(count) = expectation.count else {
+        return vec![expectation.clone()];
+    };
+    let repeated = |optional: bool, multiline: bool| {
+        let mut repeated = expectation.clone();
+        repeated.optional = optional;
+        repeated.multiline = multiline;
+        repeated
+    };
+    let mut aligned = vec![repeated(false, false); count.min];
+    match count.max {
+        Some(max) => aligned.extend(vec![repeated(true, false); max - count.min]),
+        None => aligned.push(repeated(true, true)),
+    }
+    aligned
+}
+
+/// Returns the diff with the original indices of the lines of output, if they
+/// were compared in a different order, and with the original expectations, if
+/// the aligned expectations differ from them
+fn with_origins(
+    diff: Diff,
+    origins: Option<&[usize]>,
+    originals: Option<&(Vec<Expectation>, Vec<usize>)>,
+) -> Diff {
+    if origins.is_none() && originals.is_none() {
+        return diff;
+    }
+    let restore = |lines: Vec<(usize, Vec<u8>)>| match origins {
+        Some(origins) => lines
+            .into_iter()
+            .map(|(index, line)| (origins[index], line))
+            .collect(),
+        None => lines,
+    };
+    let lines = diff
+        .lines
+        .into_iter()
+        .map(|line| match line {
+            DiffLine::MatchedExpectation {
+                index,
+                expectation,
+                lines,
+            } => DiffLine::MatchedExpectation {
+                index,
+                expectation,
+                lines: restore(lines),
+            },
+            DiffLine::UnexpectedLines { lines } => DiffLine::UnexpectedLines {
+                lines: restore(lines),
+            },
+            unmatched => unmatched,
+        })
+        .collect();
+    Diff::new(match originals {
+        Some((expectations, indices)) => with_original_expectations(lines, expectations, indices),
+        None => lines,
+    })
+}
+
+/// Returns the diff lines with the original expectations that the aligned
+/// expectations stand in for. The lines that the repetitions of an expectation
+/// with a line count match are merged, if the repetitions match consecutively,
+/// otherwise the expectation is unmatched and the lines are unexpected.
+fn with_original_expectations(
+    lines: Vec<DiffLine>,
+    expectations: &[Expectation],
+    indices: &[usize],
+) -> Vec<DiffLine> {
+    let mut consecutive = vec![true; expectations.len()];
+    let mut last_positions: Vec<Option<usize>> = vec![None; expectations.len()];
+    for (position, line) in lines.iter().enumerate() {
+        let (index, matched) = match line {
+            DiffLine::MatchedExpectation { index, .. } => (indices[*index], true),
+            DiffLine::UnmatchedExpectation { index, .. } => (indices[*index], false),
+            DiffLine::UnexpectedLines { .. } => continue,
+        };
+        if !matched || last_positions[index].is_some_and(|last| last + 1 != position) {
+            consecutive[index] = false;
+        }
+        last_positions[index] = Some(position);
+    }
+
+    let mut restored: Vec<DiffLine> = vec![];
+    let mut seen = vec![false; expectations.len()];
+    for line in lines {
+        let (index, lines) = match line {
+            DiffLine::MatchedExpectation { index, lines, .. } => (indices[index], lines),
+            DiffLine::UnmatchedExpectation { index, .. } => (indices[index], vec![]),
+            DiffLine::UnexpectedLines { lines } => {
+                push_unexpected_lines(&mut restored, lines);
+                continue;
+            }
+        };
+        let first = !std::mem::replace(&mut seen[index], true);
+        if !consecutive[index] {
+            if first {
+                restored.push(DiffLine::UnmatchedExpectation {
+                    index,
+                    expectation: expectations[index].clone(),
+                });
+            }
+            push_unexpected_lines(&mut restored, lines);
+        } else if first {
+            restored.push(DiffLine::MatchedExpectation {
+                index,
+                expectation: expectations[index].clone(),
+                lines,
+            });
+        } else if let Some(DiffLine::MatchedExpectation { lines: matched, .. }) =
+            restored.last_mut()
+        {
+            matched.extend(lines);
+        }
+    }
+    restored
+}
+
+/// Appends the lines to the unexpected lines at the end of the diff lines, or
+/// as new unexpected lines, if there are any
+fn push_unexpected_lines(diff_lines: &mut Vec<DiffLine>, lines: Vec<(usize, Vec<u8>)>) {
+    if lines.is_empty() {
+        return;
+    }
+    match diff_lines.last_mut() {
+        Some(DiffLine::UnexpectedLines { lines: unexpected }) => unexpected.extend(lines),
+        _ => diff_lines.push(DiffLine::UnexpectedLines { lines }),
+    }
+}
+
+/// Part of the result of [`DiffTool::diff_lookahead`], which refers to the
+/// lines of output by their index, so that they are only copied if needed
+enum Segment {
+    /// The expectation with the index matches the lines in the range
+    Matched(usize, Range<usize>),
+
+    /// The expectation with the index does not match
+    Unmatched(usize),
+
+    /// The lines in the range are not matched by any expectation
+    Unexpected(Range<usize>),
+}
+
 /// Enumerate the kind of peeked (future) match that was found
 enum PeekMatch {
     /// A future expectation matchers the current line
@@ -361,6 +866,52 @@ impl Diff {
         }
     }
 
+    /// Returns all lines of output, that follow the line of the diff at the
+    /// given position, as they are compared with expectations of all
+    /// remaining output
+    pub fn remaining_output(&self, position: usize) -> Vec<u8> {
+        self.lines[position + 1..]
+            .iter()
+            .flat_map(|diff_line| match diff_line {
+                DiffLine::MatchedExpectation { lines, .. }
+                | DiffLine::UnexpectedLines { lines } => lines.clone(),
+                DiffLine::UnmatchedExpectation { .. } => vec![],
+            })
+            .flat_map(|(_, line)| line)
+            .collect()
+    }
+
+    /// Returns the paths of golden files (see
+    /// [`crate::rules::rule::Rule::golden_file`]), that did not match, and
+    /// the remaining output that they are updated with
+    pub fn golden_file_updates(&self) -> Vec<(String, Vec<u8>)> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter_map(|(position, diff_line)| match diff_line {
+                DiffLine::UnmatchedExpectation { expectation, .. } => expectation
+                    .rule
+                    .golden_file()
+                    .map(|path| (path.to_string(), self.remaining_output(position))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the messages of the expectations that did not match (see
+    /// [`crate::expectation::Expectation::message`])
+    pub fn failure_messages(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|diff_line| match diff_line {
+                DiffLine::UnmatchedExpectation { expectation, .. } => {
+                    expectation.message.as_deref()
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Whether there are any differences in the result, i.e. not all lines
     /// are [`DiffLine::MatchedExpectation`]s
     pub fn has_differences(&self) -> bool {
@@ -515,18 +1066,20 @@ fn lines_to_strings(lines: &[(usize, Vec<u8>)]) -> Vec<(usize, String)> {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+
     use super::DiffLine;
     use super::DiffTool;
     use crate::bformatln;
     use crate::blines;
     use crate::diff::Diff;
+    use crate::diff_algorithm::DiffAlgorithm;
+    use crate::expectation::tests::expectation_maker;
     use crate::test_expectation;
 
     #[test]
     fn test_exact_match() {
-        let differ = DiffTool {
-            expectations: vec![test_expectation!("equal", "foo")],
-        };
+        let differ = DiffTool::new(vec![test_expectation!("equal", "foo")]);
 
         let diffs = differ.diff(&bformatln!("foo")).expect("no error");
         insta::assert_debug_snapshot!(diffs);
@@ -534,9 +1087,7 @@ mod tests {
 
     #[test]
     fn test_exact_no_match() {
-        let differ = DiffTool {
-            expectations: vec![test_expectation!("equal", "bar")],
-        };
+        let differ = DiffTool::new(vec![test_expectation!("equal", "bar")]);
 
         let diffs = differ.diff(&bformatln!("foo")).expect("no error");
         insta::assert_debug_snapshot!(diffs);
@@ -546,96 +1097,76 @@ mod tests {
     fn test_quantifiers_optional() {
         let tests = &[
             (
-                DiffTool {
-                    expectations: vec![test_expectation!("equal", "foo", false, false)],
-                },
+                DiffTool::new(vec![test_expectation!("equal", "foo", false, false)]),
                 vec![],
                 false,
             ),
             (
-                DiffTool {
-                    expectations: vec![test_expectation!("equal", "foo", false, false)],
-                },
+                DiffTool::new(vec![test_expectation!("equal", "foo", false, false)]),
                 blines!("foo"),
                 true,
             ),
             (
-                DiffTool {
-                    expectations: vec![test_expectation!("equal", "foo", true, false)],
-                },
+                DiffTool::new(vec![test_expectation!("equal", "foo", true, false)]),
                 vec![],
                 true,
             ),
             (
-                DiffTool {
-                    expectations: vec![test_expectation!("equal", "foo", true, false)],
-                },
+                DiffTool::new(vec![test_expectation!("equal", "foo", true, false)]),
                 blines!("foo"),
                 true,
             ),
             (
-                DiffTool {
-                    expectations: vec![
-                        test_expectation!("equal", "foo", false, false),
-                        test_expectation!("equal", "bar", false, false),
-                        test_expectation!("equal", "baz", false, false),
-                    ],
-                },
+                DiffTool::new(vec![
+                    test_expectation!("equal", "foo", false, false),
+                    test_expectation!("equal", "bar", false, false),
+                    test_expectation!("equal", "baz", false, false),
+                ]),
                 blines!("foo", "bar", "baz"),
                 true,
             ),
             (
-                DiffTool {
-                    expectations: vec![
-                        test_expectation!("equal", "foo", true, false),
-                        test_expectation!("equal", "bar", true, false),
-                        test_expectation!("equal", "baz", true, false),
-                    ],
-                },
+                DiffTool::new(vec![
+                    test_expectation!("equal", "foo", true, false),
+                    test_expectation!("equal", "bar", true, false),
+                    test_expectation!("equal", "baz", true, false),
+                ]),
                 blines!("foo", "bar", "baz"),
                 true,
             ),
             (
-                DiffTool {
-                    expectations: vec![
-                        test_expectation!("equal", "foo", true, false),
-                        test_expectation!("equal", "bar", true, false),
-                        test_expectation!("equal", "baz", true, false),
-                    ],
-                },
+                DiffTool::new(vec![
+                    test_expectation!("equal", "foo", true, false),
+                    test_expectation!("equal", "bar", true, false),
+                    test_expectation!("equal", "baz", true, false),
+                ]),
                 blines!("bar", "baz"),
                 true,
             ),
             (
-                DiffTool {
-                    expectations: vec![
-                        test_expectation!("equal", "foo", true, false),
-                        test_expectation!("equal", "bar", true, false),
-                        test_expectation!("equal", "baz", true, false),
-                    ],
-                },
+                DiffTool::new(vec![
+                    test_expectation!("equal", "foo", true, false),
+                    test_expectation!("equal", "bar", true, false),
+                    test_expectation!("equal", "baz", true, false),
+                ]),
                 blines!("foo", "baz"),
                 true,
             ),
             (
-                DiffTool {
-                    expectations: vec![
-                        test_expectation!("equal", "foo", true, false),
-                        test_expectation!("equal", "bar", true, false),
-                        test_expectation!("equal", "baz", true, false),
-                    ],
-                },
+                DiffTool::new(vec![
+                    test_expectation!("equal", "foo", true, false),
+                    test_expectation!("equal", "bar", true, false),
+                    test_expectation!("equal", "baz", true, false),
+                ]),
                 blines!("foo", "bar"),
                 true,
             ),
             (
-                DiffTool {
-                    expectations: vec![
-                        test_expectation!("equal", "foo", true, false),
-                        test_expectation!("equal", "bar", true, false),
-                        test_expectation!("equal", "baz", true, false),
-                    ],
-                },
+                DiffTool::new(vec![
+                    test_expectation!("equal", "foo", true, false),
+                    test_expectation!("equal", "bar", true, false),
+                    test_expectation!("equal", "baz", true, false),
+                ]),
                 vec![],
                 true,
             ),
@@ -729,9 +1260,7 @@ mod tests {
 
     #[test]
     fn test_multiline_expectation() {
-        let differ = DiffTool {
-            expectations: vec![test_expectation!("glob", "f*", false, true)],
-        };
+        let differ = DiffTool::new(vec![test_expectation!("glob", "f*", false, true)]);
 
         let diffs = differ
             .diff(&blines!("foo", "fun", "fact"))
@@ -741,12 +1270,10 @@ mod tests {
 
     #[test]
     fn test_starting_multiline_expectation() {
-        let differ = DiffTool {
-            expectations: vec![
-                test_expectation!("glob", "f*", false, true),
-                test_expectation!("equal", "bar", false, true),
-            ],
-        };
+        let differ = DiffTool::new(vec![
+            test_expectation!("glob", "f*", false, true),
+            test_expectation!("equal", "bar", false, true),
+        ]);
 
         let diffs = differ
             .diff(&blines!("foo", "fun", "fact", "bar"))
@@ -756,13 +1283,11 @@ mod tests {
 
     #[test]
     fn test_middle_multiline_expectation() {
-        let differ = DiffTool {
-            expectations: vec![
-                test_expectation!("equal", "baz", false, true),
-                test_expectation!("glob", "f*", false, true),
-                test_expectation!("equal", "bar", false, true),
-            ],
-        };
+        let differ = DiffTool::new(vec![
+            test_expectation!("equal", "baz", false, true),
+            test_expectation!("glob", "f*", false, true),
+            test_expectation!("equal", "bar", false, true),
+        ]);
 
         let diffs = differ
             .diff(&blines!("baz", "foo", "fun", "fact", "bar"))
@@ -772,12 +1297,10 @@ mod tests {
 
     #[test]
     fn test_tailing_multiline_expectation() {
-        let differ = DiffTool {
-            expectations: vec![
-                test_expectation!("equal", "baz", false, true),
-                test_expectation!("glob", "f*", false, true),
-            ],
-        };
+        let differ = DiffTool::new(vec![
+            test_expectation!("equal", "baz", false, true),
+            test_expectation!("glob", "f*", false, true),
+        ]);
 
         let diffs = differ
             .diff(&blines!("baz", "foo", "fun", "fact"))
@@ -796,12 +1319,10 @@ mod tests {
     // ```
     #[test]
     fn test_regression_excess_expectations_after_multiline_fail() {
-        let differ = DiffTool {
-            expectations: vec![
-                test_expectation!("glob", "*", false, true),
-                test_expectation!("equal", "bar", false, true),
-            ],
-        };
+        let differ = DiffTool::new(vec![
+            test_expectation!("glob", "*", false, true),
+            test_expectation!("equal", "bar", false, true),
+        ]);
 
         let diffs = differ
             .diff(&blines!("foo", "foo", "baz"))
@@ -820,12 +1341,10 @@ mod tests {
     // ```
     #[test]
     fn test_matching_non_multiline_precedent_over_matching_multiline() {
-        let differ = DiffTool {
-            expectations: vec![
-                test_expectation!("glob", "*", false, true),
-                test_expectation!("equal", "bar", false, true),
-            ],
-        };
+        let differ = DiffTool::new(vec![
+            test_expectation!("glob", "*", false, true),
+            test_expectation!("equal", "bar", false, true),
+        ]);
 
         let diffs = differ
             .diff(&blines!("foo", "foo", "bar"))
@@ -835,17 +1354,15 @@ mod tests {
 
     #[test]
     fn test_next_expectation_is_used_first() {
-        let differ = DiffTool {
-            expectations: vec![
-                test_expectation!("equal", "foo"),
-                test_expectation!("equal", ""),
-                test_expectation!("equal", "bar"),
-                test_expectation!("equal", ""),
-                test_expectation!("equal", "baz"),
-                test_expectation!("equal", ""),
-                test_expectation!("equal", "zoing"),
-            ],
-        };
+        let differ = DiffTool::new(vec![
+            test_expectation!("equal", "foo"),
+            test_expectation!("equal", ""),
+            test_expectation!("equal", "bar"),
+            test_expectation!("equal", ""),
+            test_expectation!("equal", "baz"),
+            test_expectation!("equal", ""),
+            test_expectation!("equal", "zoing"),
+        ]);
 
         let diffs = differ
             .diff(&blines!("foo", "", "baz", "", "zoing"))
@@ -854,6 +1371,92 @@ mod tests {
         insta::assert_debug_snapshot!(diffs);
     }
 
+    #[test]
+    fn test_checksum_of_remaining_output() {
+        let digest = crate::rules::checksum::sha256_hex(b"bar\nbaz\n");
+        let differ = DiffTool::new(vec![
+            test_expectation!("equal", "foo"),
+            test_expectation!("sha256", &digest),
+        ]);
+
+        let diffs = differ
+            .diff(&blines!("foo", "bar", "baz"))
+            .expect("no error");
+        assert!(!diffs.has_differences(), "remaining output matches");
+
+        let diffs = differ
+            .diff(&blines!("foo", "bar", "zoing"))
+            .expect("no error");
+        assert!(diffs.has_differences(), "remaining output differs");
+    }
+
+    #[test]
+    fn test_golden_file_updates() {
+        let directory = tempfile::tempdir().expect("create temporary directory");
+        std::fs::write(directory.path().join("golden.txt"), "bar\nbaz\n").expect("write file");
+        let mut golden = test_expectation!("file", "golden.txt");
+        golden.rule = golden
+            .rule
+            .with_document_directory(directory.path())
+            .expect("golden file is loaded");
+        let differ = DiffTool::new(vec![test_expectation!("equal", "foo"), golden]);
+
+        let diffs = differ
+            .diff(&blines!("foo", "bar", "baz"))
+            .expect("no error");
+        assert!(!diffs.has_differences(), "remaining output matches");
+        assert!(diffs.golden_file_updates().is_empty());
+
+        let diffs = differ
+            .diff(&blines!("foo", "bar", "zoing"))
+            .expect("no error");
+        assert!(diffs.has_differences(), "remaining output differs");
+        assert_eq!(
+            vec![("golden.txt".to_string(), b"bar\nzoing\n".to_vec())],
+            diffs.golden_file_updates()
+        );
+    }
+
+    #[test]
+    fn test_failure_messages() {
+        let mut banner = test_expectation!("equal", "v1");
+        banner.message = Some("banner changed".to_string());
+        let mut footer = test_expectation!("equal", "bye");
+        footer.message = Some("footer changed".to_string());
+        let differ = DiffTool::new(vec![banner, test_expectation!("equal", "foo"), footer]);
+
+        let diffs = differ.diff(&blines!("v1", "foo", "bye")).expect("no error");
+        assert!(diffs.failure_messages().is_empty());
+
+        let diffs = differ.diff(&blines!("v2", "foo", "bye")).expect("no error");
+        assert_eq!(vec!["banner changed"], diffs.failure_messages());
+    }
+
+    #[test]
+    fn test_checksum_of_file() {
+        let digest = crate::rules::checksum::sha256_hex(b"content");
+        let differ = |file_digest: Option<String>| {
+            DiffTool::new(vec![
+                test_expectation!("equal", "foo"),
+                test_expectation!("sha256", &format!("out.bin {digest}")),
+            ])
+            .with_file_digests(BTreeMap::from([("out.bin".to_string(), file_digest)]))
+        };
+
+        let diffs = differ(Some(digest.clone()))
+            .diff(&blines!("foo"))
+            .expect("no error");
+        assert!(!diffs.has_differences(), "file digest matches");
+
+        let diffs = differ(Some("0".repeat(64)))
+            .diff(&blines!("foo"))
+            .expect("no error");
+        assert!(diffs.has_differences(), "file digest differs");
+
+        let diffs = differ(None).diff(&blines!("foo")).expect("no error");
+        assert!(diffs.has_differences(), "file is missing");
+    }
+
     #[test]
     fn test_serialize() {
         let diff = Diff::new(vec![
@@ -874,13 +1477,264 @@ mod tests {
         insta::assert_snapshot!(&rendered);
     }
 
-    fn make() -> DiffTool {
-        DiffTool {
-            expectations: vec![
-                test_expectation!("equal", "foo"),
-                test_expectation!("equal", "bar"),
-                test_expectation!("equal", "baz"),
+    #[test]
+    fn test_algorithms_match_quantified_expectations() {
+        let differ = || {
+            DiffTool::new(vec![
+                test_expectation!("equal", "baz", false, false),
+                test_expectation!("glob", "f*", false, true),
+                test_expectation!("equal", "opt", true, false),
+                test_expectation!("equal", "bar", false, false),
+            ])
+        };
+        let output = blines!("baz", "foo", "fun", "fact", "bar");
+        let expected = differ().diff(&output).expect("no error");
+        assert!(!expected.has_differences());
+        for algorithm in [
+            DiffAlgorithm::Myers,
+            DiffAlgorithm::Patience,
+            DiffAlgorithm::Histogram,
+        ] {
+            let diffs = differ()
+                .with_algorithm(algorithm)
+                .diff(&output)
+                .expect("no error");
+            assert_eq!(expected.lines, diffs.lines, "{algorithm}");
+        }
+    }
+
+    #[test]
+    fn test_algorithm_reordered_output() {
+        let differ = DiffTool::new(vec![
+            test_expectation!("equal", "foo"),
+            test_expectation!("equal", "bar"),
+            test_expectation!("equal", "baz"),
+            test_expectation!("equal", "zoing"),
+        ]);
+        let output = blines!("zoing", "foo", "bar", "baz");
+
+        // looking ahead for the first line skips all expectations up to it ..
+        let diffs = differ.diff(&output).expect("no error");
+        assert_eq!(3, diffs.count_unmatched);
+
+        // .. while aligning finds that only the last one moved
+        let diffs = differ
+            .with_algorithm(DiffAlgorithm::Myers)
+            .diff(&output)
+            .expect("no error");
+        insta::assert_debug_snapshot!(diffs);
+    }
+
+    #[test]
+    fn test_unordered_expectations() {
+        let unordered =
+            |kind: &str, expression: &str| test_expectation!(kind, expression).with_unordered(true);
+        let differ = || {
+            DiffTool::new(vec![
+                test_expectation!("equal", "start"),
+                unordered("glob", "worker *"),
+                unordered("equal", "worker 1"),
+                unordered("equal", "worker 2"),
+                test_expectation!("equal", "end"),
+            ])
+        };
+        let output = blines!("start", "worker 2", "worker 3", "worker 1", "end");
+        let diffs = differ().diff(&output).expect("no error");
+        assert!(!diffs.has_differences(), "lines match in any order");
+        assert_eq!(
+            DiffLine::MatchedExpectation {
+                index: 1,
+                expectation: unordered("glob", "worker *"),
+                lines: vec![(2, bformatln!("worker 3"))],
+            },
+            diffs.lines[1],
+            "original line index is kept"
+        );
+        assert_eq!(
+            None,
+            differ().differences(&output).expect("no error"),
+            "no differences"
+        );
+        for algorithm in [
+            DiffAlgorithm::Myers,
+            DiffAlgorithm::Patience,
+            DiffAlgorithm::Histogram,
+        ] {
+            let diffs = differ()
+                .with_algorithm(algorithm)
+                .diff(&output)
+                .expect("no error");
+            assert!(!diffs.has_differences(), "{algorithm}");
+        }
+
+        let diffs = differ()
+            .diff(&blines!("start", "worker 2", "worker 3", "worker 4", "end"))
+            .expect("no error");
+        assert!(diffs.has_differences(), "a line is missing");
+
+        let diffs = differ()
+            .diff(&blines!("start", "worker 2", "worker 1", "end"))
+            .expect("no error");
+        assert!(diffs.has_differences(), "too few lines");
+    }
+
+    #[test]
+    fn test_absent_expectations_are_left_out() {
+        let differ = DiffTool::new(vec![
+            test_expectation!("equal", "foo"),
+            test_expectation!("not", "ERROR"),
+            test_expectation!("equal", "bar"),
+        ]);
+        let diffs = differ
+            .diff(&blines!("foo", "ERROR", "bar"))
+            .expect("no error");
+        assert_eq!(
+            vec![
+                DiffLine::MatchedExpectation {
+                    index: 0,
+                    expectation: test_expectation!("equal", "foo"),
+                    lines: vec![(0, bformatln!("foo"))],
+                },
+                DiffLine::UnexpectedLines {
+                    lines: vec![(1, bformatln!("ERROR"))],
+                },
+                DiffLine::MatchedExpectation {
+                    index: 2,
+                    expectation: test_expectation!("equal", "bar"),
+                    lines: vec![(2, bformatln!("bar"))],
+                },
             ],
+            diffs.lines,
+            "original expectation index is kept"
+        );
+        assert_eq!(
+            None,
+            differ
+                .differences(&blines!("foo", "bar"))
+                .expect("no error"),
+            "absence is not asserted by the diff"
+        );
+    }
+
+    #[test]
+    fn test_line_count_expectations() {
+        let counted = |line: &str| {
+            expectation_maker()
+                .parse(line)
+                .expect("parse counted expectation")
+        };
+        let differ = |count: &str| {
+            DiffTool::new(vec![
+                test_expectation!("equal", "start"),
+                counted(&format!("worker * (glob{count})")),
+                test_expectation!("equal", "end"),
+            ])
+        };
+        let workers = |amount: usize| {
+            let mut output = bformatln!("start");
+            for worker in 1..=amount {
+                output.extend(bformatln!("worker {}", worker));
+            }
+            output.extend(bformatln!("end"));
+            output
+        };
+
+        let diffs = differ("{3}").diff(&workers(3)).expect("no error");
+        assert_eq!(
+            vec![
+                DiffLine::MatchedExpectation {
+                    index: 0,
+                    expectation: test_expectation!("equal", "start"),
+                    lines: vec![(0, bformatln!("start"))],
+                },
+                DiffLine::MatchedExpectation {
+                    index: 1,
+                    expectation: counted("worker * (glob{3})"),
+                    lines: vec![
+                        (1, bformatln!("worker 1")),
+                        (2, bformatln!("worker 2")),
+                        (3, bformatln!("worker 3")),
+                    ],
+                },
+                DiffLine::MatchedExpectation {
+                    index: 2,
+                    expectation: test_expectation!("equal", "end"),
+                    lines: vec![(4, bformatln!("end"))],
+                },
+            ],
+            diffs.lines,
+            "repetitions are merged"
+        );
+
+        let diffs = differ("{3}").diff(&workers(2)).expect("no error");
+        assert_eq!(
+            vec![
+                DiffLine::MatchedExpectation {
+                    index: 0,
+                    expectation: test_expectation!("equal", "start"),
+                    lines: vec![(0, bformatln!("start"))],
+                },
+                DiffLine::UnmatchedExpectation {
+                    index: 1,
+                    expectation: counted("worker * (glob{3})"),
+                },
+                DiffLine::UnexpectedLines {
+                    lines: vec![(1, bformatln!("worker 1")), (2, bformatln!("worker 2"))],
+                },
+                DiffLine::MatchedExpectation {
+                    index: 2,
+                    expectation: test_expectation!("equal", "end"),
+                    lines: vec![(3, bformatln!("end"))],
+                },
+            ],
+            diffs.lines,
+            "too few lines"
+        );
+
+        for (count, amount, matches) in [
+            ("{3}", 4, false),
+            ("{2,5}", 1, false),
+            ("{2,5}", 2, true),
+            ("{2,5}", 5, true),
+            ("{2,5}", 6, false),
+            ("{2,}", 9, true),
+            ("{0,2}", 0, true),
+            ("{1}", 1, true),
+        ] {
+            for algorithm in [DiffAlgorithm::Lookahead, DiffAlgorithm::Myers] {
+                let differ = differ(count).with_algorithm(algorithm);
+                assert_eq!(
+                    matches,
+                    differ
+                        .differences(&workers(amount))
+                        .expect("no error")
+                        .is_none(),
+                    "{count} with {amount} lines and {algorithm}"
+                );
+            }
         }
     }
+
+    #[test]
+    fn test_assign_lines() {
+        let matching = [[true, true], [true, false]];
+        assert_eq!(
+            Some(vec![1, 0]),
+            super::assign_lines(2, &|index, offset| matching[index][offset]),
+            "first assignment is reconsidered"
+        );
+        let matching = [[true, false], [true, false]];
+        assert_eq!(
+            None,
+            super::assign_lines(2, &|index, offset| matching[index][offset])
+        );
+    }
+
+    fn make() -> DiffTool {
+        DiffTool::new(vec![
+            test_expectation!("equal", "foo"),
+            test_expectation!("equal", "bar"),
+            test_expectation!("equal", "baz"),
+        ])
+    }
 }
@@ -0,0 +1,405 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::fmt;
+use std::fmt::Display;
+use std::ops::Range;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Maximum sum of expectations and lines of output that are aligned with an
+/// algorithm other than [`DiffAlgorithm::Lookahead`], which require time and
+/// memory that grow quadratically with it
+pub const MAX_ALIGNMENT_LENGTH: usize = 4_000;
+
+/// Maximum number of lines a single expectation may match, to be used as an
+/// anchor by [`DiffAlgorithm::Histogram`]
+const MAX_HISTOGRAM_OCCURRENCES: usize = 64;
+
+/// The algorithm that aligns the expectations of a testcase with the lines of
+/// its output, which determines how mismatches are presented
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffAlgorithm {
+    /// Walks expectations and lines in lock step and, on a mismatch, looks
+    /// ahead for the next expectation or line that matches
+    #[default]
+    Lookahead,
+
+    /// Finds the shortest sequence of unmatched expectations and unexpected
+    /// lines (Myers' algorithm)
+    Myers,
+
+    /// Anchors the alignment on expectations and lines that match each other
+    /// uniquely, which keeps reordered blocks of output together
+    Patience,
+
+    /// Like patience, but anchors on the expectations that match the fewest
+    /// lines, if none match uniquely
+    Histogram,
+}
+
+impl Display for DiffAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Lookahead => "lookahead",
+            Self::Myers => "myers",
+            Self::Patience => "patience",
+            Self::Histogram => "histogram",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// One step of the alignment of expectations (by index) with lines of output
+/// (by index)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Edit {
+    /// The expectation matches the line
+    Match(usize, usize),
+
+    /// The expectation is not matched by any line
+    Unmatched(usize),
+
+    /// The line is not matched by any expectation
+    Unexpected(usize),
+}
+
+/// Aligns expectations with lines of output, given whether an expectation
+/// matches a line, with one of the algorithms other than
+/// [`DiffAlgorithm::Lookahead`]
+pub(crate) fn align(
+    algorithm: DiffAlgorithm,
+    count_expectations: usize,
+    count_lines: usize,
+    matches: &dyn Fn(usize, usize) -> bool,
+) -> Vec<Edit> {
+    let aligner = Aligner { algorithm, matches };
+    let mut edits = vec![];
+    aligner.align(0..count_expectations, 0..count_lines, &mut edits);
+    edits
+}
+
+struct Aligner<'a> {
+    algorithm: DiffAlgorithm,
+    matches: &'a dyn Fn(usize, usize) -> bool,
+}
+
+impl Aligner<'_> {
+    fn align(&self, expectations: Range<usize>, lines: Range<usize>, edits: &mut Vec<Edit>) {
+        match self.algorithm {
+            DiffAlgorithm::Patience | DiffAlgorithm::Histogram => {
+                // a matching head needs no anchor, which also extends the
+                // matches that follow an anchor
+                let (mut expectations, mut lines) = (expectations, lines);
+                while !expectations.is_empty()
+                    && !lines.is_empty()
+                    && (self.matches)(expectations.start, lines.start)
+                {
+                    edits.push(Edit::Match(expectations.start, lines.start));
+                    expectations.start += 1;
+                    lines.start += 1;
+                }
+                if self.algorithm == DiffAlgorithm::Patience {
+                    self.patience(expectations, lines, edits);
+                } else {
+                    self.histogram(expectations, lines, edits);
+                }
+            }
+            _ => self.myers(expectations, lines, edits),
+        }
+    }
+
+    /// Aligns the ranges between and around the given anchors, which are
+    /// pairs of expectation and line that match
+    fn align_around(
+        &self,
+        expectations: Range<usize>,
+        lines: Range<usize>,
+        anchors: &[(usize, usize)],
+        edits: &mut Vec<Edit>,
+    ) {
+        let (mut expectation, mut line) = (expectations.start, lines.start);
+        for &(anchor_expectation, anchor_line) in anchors {
+            self.align(expectation..anchor_expectation, line..anchor_line, edits);
+            edits.push(Edit::Match(anchor_expectation, anchor_line));
+            (expectation, line) = (anchor_expectation + 1, anchor_line + 1);
+        }
+        self.align(expectation..expectations.end, line..lines.end, edits);
+    }
+
+    fn patience(&self, expectations: Range<usize>, lines: Range<usize>, edits: &mut Vec<Edit>) {
+        // pairs of expectation and line that match only each other ..
+        let unique = expectations
+            .clone()
+            .filter_map(|expectation| {
+                let mut matching = lines
+                    .clone()
+                    .filter(|line| (self.matches)(expectation, *line));
+                let line = matching.next()?;
+                if matching.next().is_some() {
+                    return None;
+                }
+                let matched_by = expectations
+                    .clone()
+                    .filter(|other| (self.matches)(*other, line))
+                    .count();
+                (matched_by == 1).then_some((expectation, line))
+            })
+            .collect::<Vec<_>>();
+
+        // .. of which the longest sequence in order of both are the anchors
+        let anchors = longest_increasing_lines(&unique);
+        if anchors.is_empty() {
+            self.myers(expectations, lines, edits);
+        } else {
+            self.align_around(expectations, lines, &anchors, edits);
+        }
+    }
+
+    fn histogram(&self, expectations: Range<usize>, lines: Range<usize>, edits: &mut Vec<Edit>) {
+        // the expectations that match the fewest (but any) lines are anchored
+        // on the longest run of expectations that match consecutive lines
+        let occurrences = expectations
+            .clone()
+            .map(|expectation| {
+                let matching = lines
+                    .clone()
+                    .filter(|line| (self.matches)(expectation, *line))
+                    .collect::<Vec<_>>();
+                (expectation, matching)
+            })
+            .filter(|(_, matching)| !matching.is_empty())
+            .collect::<Vec<_>>();
+        let Some(fewest) = occurrences.iter().map(|(_, matching)| matching.len()).min() else {
+            edits.extend(expectations.map(Edit::Unmatched));
+            edits.extend(lines.map(Edit::Unexpected));
+            return;
+        };
+        if fewest > MAX_HISTOGRAM_OCCURRENCES {
+            self.myers(expectations, lines, edits);
+            return;
+        }
+
+        let mut best: Option<(usize, usize, usize)> = None;
+        for (expectation, matching) in &occurrences {
+            if matching.len() != fewest {
+                continue;
+            }
+            for &line in matching {
+                let (mut start_expectation, mut start_line) = (*expectation, line);
+                while start_expectation > expectations.start
+                    && start_line > lines.start
+                    && (self.matches)(start_expectation - 1, start_line - 1)
+                {
+                    start_expectation -= 1;
+                    start_line -= 1;
+                }
+                let mut end = 1;
+                while expectation + end < expectations.end
+                    && line + end < lines.end
+                    && (self.matches)(expectation + end, line + end)
+                {
+                    end += 1;
+                }
+                let length = expectation - start_expectation + end;
+                if best.is_none_or(|(_, _, best_length)| length > best_length) {
+                    best = Some((start_expectation, start_line, length));
+                }
+            }
+        }
+
+        let (start_expectation, start_line, length) = best.expect("anchor among occurrences");
+        let anchors = (0..length)
+            .map(|offset| (start_expectation + offset, start_line + offset))
+            .collect::<Vec<_>>();
+        self.align_around(expectations, lines, &anchors, edits);
+    }
+
+    fn myers(&self, expectations: Range<usize>, lines: Range<usize>, edits: &mut Vec<Edit>) {
+        let (n, m) = (expectations.len() as isize, lines.len() as isize);
+        let max = n + m;
+        let offset = |k: isize| (k + max + 1) as usize;
+
+        // furthest reaching expectation index per diagonal, for each number
+        // of edits that are needed to reach them
+        let mut frontier = vec![0isize; 2 * max as usize + 3];
+        let mut trace = vec![];
+        'search: for d in 0..=max {
+            trace.push(frontier.clone());
+            for k in (-d..=d).step_by(2) {
+                let mut x =
+                    if k == -d || (k != d && frontier[offset(k - 1)] < frontier[offset(k + 1)]) {
+                        frontier[offset(k + 1)]
+                    } else {
+                        frontier[offset(k - 1)] + 1
+                    };
+                let mut y = x - k;
+                while x < n
+                    && y < m
+                    && (self.matches)(expectations.start + x as usize, lines.start + y as usize)
+                {
+                    x += 1;
+                    y += 1;
+                }
+                frontier[offset(k)] = x;
+                if x >= n && y >= m {
+                    break 'search;
+                }
+            }
+        }
+
+        // walk back from the end, to collect the edits in reverse
+        let mut reversed = vec![];
+        let (mut x, mut y) = (n, m);
+        for (d, frontier) in trace.iter().enumerate().rev() {
+            let (d, k) = (d as isize, x - y);
+            let previous_k =
+                if k == -d || (k != d && frontier[offset(k - 1)] < frontier[offset(k + 1)]) {
+                    k + 1
+                } else {
+                    k - 1
+                };
+            let previous_x = frontier[offset(previous_k)];
+            let previous_y = previous_x - previous_k;
+            while x > previous_x && y > previous_y {
+                x -= 1;
+                y -= 1;
+                reversed.push(Edit::Match(
+                    expectations.start + x as usize,
+                    lines.start + y as usize,
+                ));
+            }
+            if d > 0 {
+                if x == previous_x {
+                    reversed.push(Edit::Unexpected(lines.start + previous_y as usize));
+                } else {
+                    reversed.push(Edit::Unmatched(expectations.start + previous_x as usize));
+                }
+            }
+            (x, y) = (previous_x, previous_y);
+        }
+        edits.extend(reversed.into_iter().rev());
+    }
+}
+
+/// Returns the longest subsequence of the given pairs of expectation and line,
+/// which are ordered by expectation, in which the lines are increasing, too
+fn longest_increasing_lines(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // index of the last pair of the best subsequence of each length, and the
+    // predecessor of each pair in the subsequence it ends
+    let mut tails: Vec<usize> = vec![];
+    let mut predecessors = vec![None; pairs.len()];
+    for (index, (_, line)) in pairs.iter().enumerate() {
+        let length = tails.partition_point(|tail| pairs[*tail].1 < *line);
+        predecessors[index] = length.checked_sub(1).map(|length| tails[length]);
+        if length == tails.len() {
+            tails.push(index);
+        } else {
+            tails[length] = index;
+        }
+    }
+    let mut sequence = vec![];
+    let mut next = tails.last().copied();
+    while let Some(index) = next {
+        sequence.push(pairs[index]);
+        next = predecessors[index];
+    }
+    sequence.reverse();
+    sequence
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DiffAlgorithm;
+    use super::Edit;
+    use super::align;
+
+    fn align_lines(algorithm: DiffAlgorithm, expected: &[&str], actual: &[&str]) -> String {
+        align(algorithm, expected.len(), actual.len(), &|e, l| {
+            expected[e] == actual[l]
+        })
+        .into_iter()
+        .map(|edit| match edit {
+            Edit::Match(e, _) => format!(" {}", expected[e]),
+            Edit::Unmatched(e) => format!("-{}", expected[e]),
+            Edit::Unexpected(l) => format!("+{}", actual[l]),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+
+    #[test]
+    fn test_align_moved_line() {
+        let (expected, actual) = (&["a", "b", "c", "d"], &["d", "a", "b", "c"]);
+        for algorithm in [
+            DiffAlgorithm::Myers,
+            DiffAlgorithm::Patience,
+            DiffAlgorithm::Histogram,
+        ] {
+            assert_eq!(
+                "+d  a  b  c -d",
+                align_lines(algorithm, expected, actual),
+                "{algorithm}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_align_without_matches() {
+        for algorithm in [
+            DiffAlgorithm::Myers,
+            DiffAlgorithm::Patience,
+            DiffAlgorithm::Histogram,
+        ] {
+            assert_eq!(
+                "-a -b +c",
+                align_lines(algorithm, &["a", "b"], &["c"]),
+                "{algorithm}"
+            );
+            assert_eq!("-a", align_lines(algorithm, &["a"], &[]), "{algorithm}");
+            assert_eq!("+a", align_lines(algorithm, &[], &["a"]), "{algorithm}");
+            assert_eq!("", align_lines(algorithm, &[], &[]), "{algorithm}");
+        }
+    }
+
+    #[test]
+    fn test_align_reordered_blocks() {
+        let expected = &["start", "a", "}", "", "b", "}", "", "end"];
+        let actual = &["start", "b", "}", "", "a", "}", "", "end"];
+
+        // Myers matches the common lines of both blocks, which splits them ..
+        assert_eq!(
+            " start -a +b  }   -b +a  }    end",
+            align_lines(DiffAlgorithm::Myers, expected, actual)
+        );
+
+        // .. while patience and histogram anchor on the unique lines, which
+        // keeps the blocks together
+        assert_eq!(
+            " start -a -} -  b  }   +a +} +  end",
+            align_lines(DiffAlgorithm::Patience, expected, actual)
+        );
+        assert_eq!(
+            " start +b +} +  a  }   -b -} -  end",
+            align_lines(DiffAlgorithm::Histogram, expected, actual)
+        );
+    }
+
+    #[test]
+    fn test_align_histogram_anchors_on_rare_lines() {
+        // no line is unique, but `x` is rarer than `y`
+        let expected = &["x", "y", "y", "x", "y"];
+        let actual = &["y", "x", "y", "y", "x"];
+        assert_eq!(
+            "+y  x  y  y  x -y",
+            align_lines(DiffAlgorithm::Histogram, expected, actual)
+        );
+    }
+}
@@ -152,6 +152,50 @@ fn has_unprintable_unicode(bytes: &[u8]) -> bool {
         .unwrap_or(true)
 }
 
+/// Number of bytes that each line of a [`hex_dump`] shows
+const HEX_DUMP_WIDTH: usize = 16;
+
+/// Returns the bytes as hex dump in the canonical format of `hexdump -C`, but
+/// without collapsing repeated lines: one line per 16 bytes, with the offset,
+/// the bytes in hex and the printable bytes between pipes, e.g.
+/// `00000000  68 69 0a                                          |hi.|`
+pub fn hex_dump(bytes: &[u8]) -> Vec<u8> {
+    let mut dump = String::with_capacity(bytes.len().div_ceil(HEX_DUMP_WIDTH) * 79);
+    for (index, chunk) in bytes.chunks(HEX_DUMP_WIDTH).enumerate() {
+        dump.push_str(&format!("{:08x} ", index * HEX_DUMP_WIDTH));
+        for position in 0..HEX_DUMP_WIDTH {
+            if position % 8 == 0 {
+                dump.push(' ');
+            }
+            match chunk.get(position) {
+                Some(byte) => dump.push_str(&format!("{byte:02x} ")),
+                None => dump.push_str("   "),
+            }
+        }
+        dump.push_str(" |");
+        dump.extend(chunk.iter().map(|byte| match byte {
+            b'\x20'..=b'\x7e' => *byte as char,
+            _ => '.',
+        }));
+        dump.push_str("|\n");
+    }
+    dump.into_bytes()
+}
+
+/// Returns the bytes as text in which all bytes that are not valid UTF-8 are
+/// replaced with `\xNN` escape sequences and all backslashes with `\\`, so that
+/// the bytes can be restored from it, as from an `(escaped)` expectation
+pub fn escaped_invalid_utf8(bytes: &[u8]) -> Vec<u8> {
+    let mut escaped = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        escaped.push_str(&chunk.valid().replace('\\', "\\\\"));
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{byte:02x}"));
+        }
+    }
+    escaped.into_bytes()
+}
+
 pub fn strip_colors(input: &str) -> Result<String> {
     let stripped = strip_colors_bytes(input.as_bytes())?;
     String::from_utf8(stripped).context("decode stripped bytes back to utf8 string")
@@ -164,8 +208,51 @@ pub fn strip_colors_bytes(input: &[u8]) -> Result<Vec<u8>> {
 #[cfg(test)]
 mod tests {
 
+    use super::escaped_invalid_utf8;
     use super::escaped_printable_ascii;
     use super::escaped_printable_unicode;
+    use super::hex_dump;
+
+    #[test]
+    fn test_hex_dump() {
+        let tests: Vec<(&[u8], &str)> = vec![
+            (b"", ""),
+            (
+                b"hi\n",
+                "00000000  68 69 0a                                          |hi.|\n",
+            ),
+            (
+                b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR\x00 ",
+                "00000000  89 50 4e 47 0d 0a 1a 0a  00 00 00 0d 49 48 44 52  |.PNG........IHDR|\n\
+                 00000010  00 20                                             |. |\n",
+            ),
+        ];
+        for (from, expect) in tests {
+            assert_eq!(
+                expect,
+                String::from_utf8(hex_dump(from)).expect("hex dump is ASCII"),
+                "from {from:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_escaped_invalid_utf8() {
+        let tests: Vec<(&[u8], &str)> = vec![
+            (b"", ""),
+            ("foo 😂\n".as_bytes(), "foo 😂\n"),
+            (b"foo\xffbar\n", "foo\\xffbar\n"),
+            (b"\xc3\x28 \xf0\x9f\x98", "\\xc3( \\xf0\\x9f\\x98"),
+            (b"C:\\foo\\xff", "C:\\\\foo\\\\xff"),
+        ];
+        for (from, expect) in tests {
+            assert_eq!(
+                expect,
+                String::from_utf8(escaped_invalid_utf8(from)).expect("escaped is UTF-8"),
+                "from {from:?}"
+            );
+        }
+    }
 
     #[test]
     fn test_bytes_as_printable_unicode() {
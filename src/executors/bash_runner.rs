@@ -5,16 +5,22 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
 
+use anyhow::Context;
 use anyhow::Result;
+use tempfile::NamedTempFile;
 use tracing::trace;
 
 use super::context::Context as ExecutionContext;
 use super::runner::Runner;
 use super::stateful_executor::StatefulExecutorRunnerGenerator;
 use super::subprocess_runner::SubprocessRunner;
+use super::util::render_exports;
+use super::util::render_hook;
+use super::util::working_directory;
 use crate::output::Output;
 use crate::testcase::TestCase;
 
@@ -22,9 +28,11 @@ use crate::testcase::TestCase;
 pub const BASH_EXCLUDED_VARIABLES: &[&str] = &[
     // variables from Scrut internals
     "__SCRUT_DECLARE_VARS_CMD",
+    "__SCRUT_PREVIOUS_DIRECTORY",
     "__SCRUT_TEMP_STATE_PATH",
     // variables set by scrut in every execution
     "SCRUT_TEST",
+    "SCRUT_INSIDE_RUN",
     // variables from `man bash`
     "BASHOPTS",
     "BASH_ALIASES",
@@ -68,7 +76,8 @@ const BASH_TEMPLATE: &str = include_str!("bash_runner.template");
 /// This Runner is not concurrency-safe (the shared state directory with the `state` file mandates
 /// sequential, isolated execution).
 ///
-/// Underneath the [`SubprocessRunner`] is used, so timeout constraints are fully supported.
+/// Underneath the [`SubprocessRunner`] is used, so timeout constraints are fully supported. Test
+/// cases that are configured with `tty` are run with the `PtyRunner` instead.
 #[derive(Clone)]
 pub struct BashRunner {
     pub shell: PathBuf,
@@ -93,18 +102,32 @@ impl BashRunner {
             Box::new(shell_instance) as Box<dyn Runner>
         })
     }
-}
-
-impl Runner for BashRunner {
-    fn run(&self, name: &str, testcase: &TestCase, context: &ExecutionContext) -> Result<Output> {
-        let shell = self.shell.to_owned();
 
-        // render the bash script
+    /// Returns the bash script, that loads the state of the previous
+    /// execution, runs the given shell expression of the testcase and
+    /// persists the state for the next execution
+    pub(super) fn render_script(
+        &self,
+        name: &str,
+        shell_expression: &str,
+        testcase: &TestCase,
+        context: &ExecutionContext,
+    ) -> Result<String> {
         let state_directory_str = self.state_directory.to_string_lossy();
-        let expression = BASH_TEMPLATE
+        let script = BASH_TEMPLATE
             .replace("{state_directory}", &state_directory_str)
             .replace("{name}", name)
-            .replace("{shell_expression}", &testcase.shell_expression)
+            .replace("{before_each}", &render_hook(&context.config.before_each))
+            .replace("{after_each}", &render_hook(&context.config.after_each))
+            .replace(
+                "{environment}",
+                &render_exports(&testcase.config.environment)?,
+            )
+            .replace(
+                "{change_directory}",
+                &render_change_directory(&working_directory(testcase, context)?),
+            )
+            .replace("{shell_expression}", shell_expression)
             .replace("{excluded_variables}", &BASH_EXCLUDED_VARIABLES.join("|"))
             .replace(
                 "{persist_state}",
@@ -114,21 +137,96 @@ impl Runner for BashRunner {
                     "1"
                 },
             );
-        trace!("compiled expression {}", &expression);
+        trace!("compiled expression {}", &script);
+        Ok(script)
+    }
+}
+
+/// Returns the statements, that change into the working directory and
+/// remember the previous directory to return to, or nothing
+fn render_change_directory(directory: &Option<PathBuf>) -> String {
+    match directory {
+        Some(directory) => format!(
+            "__SCRUT_PREVIOUS_DIRECTORY=\"$PWD\"\ncd {} || exit 1",
+            shell_escape::unix::escape(directory.to_string_lossy())
+        ),
+        None => String::new(),
+    }
+}
+
+/// Returns the shell expression, that reads its standard input from the file
+/// at the given path
+pub(super) fn redirect_stdin(shell_expression: &str, path: &Path) -> String {
+    format!(
+        "{{\n{}\n}} < {}",
+        shell_expression,
+        shell_escape::unix::escape(path.to_string_lossy())
+    )
+}
+
+impl Runner for BashRunner {
+    fn run(&self, name: &str, testcase: &TestCase, context: &ExecutionContext) -> Result<Output> {
+        let shell = self.shell.to_owned();
+
+        // provide the configured standard input to the shell expression only,
+        // because the shell itself reads the script from its standard input
+        let shell_expression = match testcase.config.stdin {
+            Some(ref stdin) => {
+                let directory = context.file.parent().unwrap_or(Path::new(""));
+                let content = stdin.read(directory)?;
+                let mut file = NamedTempFile::with_prefix_in(".stdin.", &context.temp_directory)
+                    .context("create temporary stdin file")?;
+                file.write_all(&content).context("write stdin file")?;
+
+                // detached executions may read after this run ended, the file
+                // is removed together with the temporary directory
+                let path = file.into_temp_path().keep().context("keep stdin file")?;
+                redirect_stdin(&testcase.shell_expression, &path)
+            }
+            None => testcase.shell_expression.clone(),
+        };
 
         let mut testcase = testcase.clone();
-        testcase.shell_expression = expression;
+        testcase.shell_expression =
+            self.render_script(name, &shell_expression, &testcase, context)?;
 
-        SubprocessRunner(shell).run(name, &testcase, context)
+        if testcase.config.tty.unwrap_or(false) {
+            return run_in_terminal(shell, name, &testcase, context);
+        }
+        if !testcase.interaction.is_empty() {
+            anyhow::bail!("interaction requires tty");
+        }
+        SubprocessRunner::new(shell).run(name, &testcase, context)
     }
 }
 
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn run_in_terminal(
+    shell: PathBuf,
+    name: &str,
+    testcase: &TestCase,
+    context: &ExecutionContext,
+) -> Result<Output> {
+    super::pty_runner::PtyRunner::new(shell).run(name, testcase, context)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn run_in_terminal(
+    _shell: PathBuf,
+    _name: &str,
+    _testcase: &TestCase,
+    _context: &ExecutionContext,
+) -> Result<Output> {
+    anyhow::bail!("tty is not supported on this platform")
+}
+
 #[cfg(test)]
 mod tests {
     use tempfile::TempDir;
 
     use super::BashRunner;
     use super::Runner;
+    use crate::config::TestCaseStdin;
     use crate::executors::DEFAULT_SHELL;
     use crate::executors::context::Context as ExecutionContext;
     use crate::output::Output;
@@ -190,6 +288,23 @@ mod tests {
         assert_eq!(expect, output);
     }
 
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_execute_with_stdin() {
+        let temp_dir = TempDir::with_prefix("runner.").expect("create temporary directory");
+        let mut testcase = TestCase::from_expression("read -r FIRST && echo \"<$FIRST>\" && cat");
+        testcase.config.stdin = Some(TestCaseStdin::Content("foo\nbar\n".into()));
+        let output = BashRunner {
+            shell: DEFAULT_SHELL.to_owned(),
+            state_directory: temp_dir.path().into(),
+        }
+        .run("name", &testcase, &ExecutionContext::new_for_test())
+        .expect("execute without error");
+
+        let expect: Output = ("<foo>\nbar\n", "").into();
+        assert_eq!(expect, output);
+    }
+
     #[test]
     fn test_execute_persists_state_file_in_state_directory() {
         let temp_dir = TempDir::with_prefix("runner.").expect("create temporary directory");
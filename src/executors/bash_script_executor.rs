@@ -5,6 +5,7 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -25,8 +26,12 @@ use super::executor::Executor;
 use super::executor::Result;
 use super::runner::Runner;
 use super::subprocess_runner::SubprocessRunner;
+use super::util::render_exports;
+use super::util::render_hook;
+use crate::config::DocumentConfig;
 use crate::config::OutputStreamControl;
 use crate::config::TestCaseConfig;
+use crate::config::WorkDirectoryScope;
 use crate::lossy_string;
 use crate::newline::BytesNewline;
 use crate::newline::SplitLinesByNewline;
@@ -80,7 +85,7 @@ impl Executor for BashScriptExecutor {
         context: &ExecutionContext,
     ) -> Result<Vec<Output>> {
         let testcase = compile_testcase(testcases, context)?;
-        let runner = SubprocessRunner(self.0.to_owned());
+        let runner = SubprocessRunner::new(self.0.to_owned());
         let output = runner
             .run("script", &testcase, context)
             .map_err(|err| ExecutionError::from_execute(err, None, None))?;
@@ -90,14 +95,11 @@ impl Executor for BashScriptExecutor {
                 return Err(ExecutionError::Skipped(0));
             }
             ExitStatus::Timeout(_) => {
+                let output = sanitize_script_output(testcases, output, context)
+                    .map_err(|err| ExecutionError::failed(0, err))?;
                 return Err(ExecutionError::Timeout(
                     ExecutionTimeout::Total,
-                    vec![Output {
-                        exit_code: output.exit_code,
-                        stderr: remove_dividers_from_output(&output.stderr),
-                        stdout: remove_dividers_from_output(&output.stdout),
-                        detached_process: None,
-                    }],
+                    vec![output],
                 ));
             }
             ExitStatus::Unknown => {
@@ -109,6 +111,20 @@ impl Executor for BashScriptExecutor {
             _ => {}
         }
 
+        // output that exceeds the max output size was not captured entirely,
+        // so it cannot be assigned to the testcases, which all fail
+        if testcase
+            .config
+            .max_output_size
+            .is_some_and(|max_output_size| {
+                (output.stdout.len() + output.stderr.len()) as u64 > max_output_size.0
+            })
+        {
+            let output = sanitize_script_output(testcases, output, context)
+                .map_err(|err| ExecutionError::failed(0, err))?;
+            return Ok(vec![output; testcases.len()]);
+        }
+
         // iterate STDOUT and split by divider string
         let mut outputs = vec![];
         iterate_divided_output(
@@ -120,6 +136,13 @@ impl Executor for BashScriptExecutor {
                     stdout: out.to_vec().into(),
                     exit_code: ExitStatus::Code(exit_code),
                     detached_process: None,
+                    resource_usage: None,
+                    duration: None,
+                    attempts: None,
+                    file_digests: BTreeMap::new(),
+                    produced_files: BTreeMap::new(),
+                    work_directory: None,
+                    artifacts: vec![],
                 });
                 Ok(())
             },
@@ -166,16 +189,110 @@ impl Executor for BashScriptExecutor {
             )?;
         }
 
-        Ok(outputs)
+        // hide secrets and normalize before the output is validated or rendered
+        outputs
+            .into_iter()
+            .zip(testcases)
+            .enumerate()
+            .map(|(index, (output, testcase))| {
+                sanitize_output(testcase, output, context)
+                    .map_err(|err| ExecutionError::failed(index, err))
+            })
+            .collect()
     }
 }
 
+/// Returns the output of the testcase with its secrets, including those
+/// configured in the document-wide testcase defaults, hidden, its paths
+/// normalized and user anonymized (if configured) and with the document-wide
+/// normalizers applied
+fn sanitize_output(
+    testcase: &TestCase,
+    output: Output,
+    context: &ExecutionContext,
+) -> anyhow::Result<Output> {
+    let testcase = TestCase {
+        config: testcase
+            .config
+            .with_defaults_from(&context.config.testcase_defaults()),
+        ..testcase.clone()
+    };
+    let environment = testcase.config.interpolated_environment();
+    let mut output = testcase.redact_output(output, &environment)?;
+
+    // all testcases ran already, so files are as the last one left them
+    output.file_digests = testcase.produced_file_digests(&context.work_directory);
+    output.produced_files = testcase.produced_files(&context.work_directory);
+    let output = testcase.normalize_paths_in_output(output, &context.work_directory);
+    let output = testcase.anonymize_user_in_output(output, &environment);
+    context.config.normalize_output(output)
+}
+
+/// Returns the output of the whole script without dividers, which contains
+/// the output of all testcases, so all secrets of all testcases are hidden
+fn sanitize_script_output(
+    testcases: &[&TestCase],
+    output: Output,
+    context: &ExecutionContext,
+) -> anyhow::Result<Output> {
+    let all = TestCase {
+        config: TestCaseConfig {
+            redact: testcases
+                .iter()
+                .flat_map(|testcase| testcase.config.redact.clone())
+                .collect(),
+            environment: testcases
+                .iter()
+                .flat_map(|testcase| testcase.config.environment.clone())
+                .collect(),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    sanitize_output(
+        &all,
+        Output {
+            exit_code: output.exit_code,
+            stderr: remove_dividers_from_output(&output.stderr),
+            stdout: remove_dividers_from_output(&output.stdout),
+            detached_process: None,
+            resource_usage: None,
+            duration: None,
+            attempts: None,
+            file_digests: BTreeMap::new(),
+            produced_files: BTreeMap::new(),
+            work_directory: None,
+            artifacts: vec![],
+        },
+        context,
+    )
+}
+
 /// Reduce a list of [`TestCase`] into a single one that has as it's shell
 /// expression a compiled bash script that executes all expressions and that
 /// uses a shared configuration
 fn compile_testcase(testcases: &[&TestCase], context: &ExecutionContext) -> Result<TestCase> {
     let mut config = TestCaseConfig::empty();
 
+    // all testcases run in one bash script, not with an interpreter
+    if context
+        .config
+        .shell
+        .as_ref()
+        .is_some_and(|shell| shell.interpreter().is_some())
+    {
+        return Err(ExecutionError::aborted(
+            anyhow!("an interpreter as shell is not supported for Cram documents"),
+            None,
+        ));
+    }
+    if context.config.get_workdir().scope == WorkDirectoryScope::PerTestcase {
+        return Err(ExecutionError::aborted(
+            anyhow!("per-testcase work directories are not supported for Cram documents"),
+            None,
+        ));
+    }
+
     // iterate all test cases and make sure that they have a consistent configuration
     // as there is no support for a divergent, per-testcase config.
     for (index, testcase) in testcases.iter().enumerate() {
@@ -195,10 +312,21 @@ fn compile_testcase(testcases: &[&TestCase], context: &ExecutionContext) -> Resu
             };
         }
         set_consistent!(detached);
+        set_consistent!(invalid_utf8);
         set_consistent!(keep_crlf);
+        set_consistent!(max_output_size);
+        set_consistent!(normalize_crlf);
         set_consistent!(output_stream);
         set_consistent!(skip_document_code);
+        set_consistent!(terminal_size);
+        set_consistent!(unicode_normalization);
         set_consistent!(wait);
+        if testcase.config.shell.is_some() {
+            return Err(ExecutionError::failed(
+                index,
+                anyhow!("an interpreter as shell is not supported for Cram documents"),
+            ));
+        }
         if !config.environment.is_empty() && config.environment != testcase.config.environment {
             return Err(ExecutionError::failed(
                 index,
@@ -208,6 +336,15 @@ fn compile_testcase(testcases: &[&TestCase], context: &ExecutionContext) -> Resu
         config.environment = testcase.config.environment.clone();
     }
 
+    // the document-wide environment variables apply to all testcases
+    config.environment = context
+        .config
+        .testcase_defaults()
+        .environment
+        .into_iter()
+        .chain(config.environment)
+        .collect();
+
     let timeout = context
         .config
         .total_timeout
@@ -217,7 +354,7 @@ fn compile_testcase(testcases: &[&TestCase], context: &ExecutionContext) -> Resu
     }
 
     // create a bash script that executes all testcases
-    let script = compile_script(testcases, &config)?;
+    let script = compile_script(testcases, &config, &context.config)?;
 
     // the environment variables are already exported in the compiled script
     config.environment.clear();
@@ -244,11 +381,14 @@ fn remove_dividers_from_output(output: &OutputStream) -> OutputStream {
 }
 
 /// Compiles all shell expressions of a list of [`TestCase`]s into a single bash script
-fn compile_script(testcases: &[&TestCase], config: &TestCaseConfig) -> Result<String> {
-    use std::borrow::Cow;
-
+fn compile_script(
+    testcases: &[&TestCase],
+    config: &TestCaseConfig,
+    document_config: &DocumentConfig,
+) -> Result<String> {
     let mut expressions = vec![];
     let salt = random_string(SUFFIX_RANDOM_SIZE);
+    let defaults = document_config.testcase_defaults();
     for (index, testcase) in testcases.iter().enumerate() {
         if testcase.config.timeout.is_some() {
             return Err(ExecutionError::failed(
@@ -256,6 +396,30 @@ fn compile_script(testcases: &[&TestCase], config: &TestCaseConfig) -> Result<St
                 anyhow!("timeout per execution not supported in bash-script execution",),
             ));
         }
+        if testcase.config.tty.unwrap_or(false) {
+            return Err(ExecutionError::failed(
+                index,
+                anyhow!("tty not supported in bash-script execution"),
+            ));
+        }
+        if testcase.config.stdin.is_some() {
+            return Err(ExecutionError::failed(
+                index,
+                anyhow!("stdin not supported in bash-script execution"),
+            ));
+        }
+        if testcase.config.cwd.is_some() || defaults.cwd.is_some() {
+            return Err(ExecutionError::failed(
+                index,
+                anyhow!("cwd not supported in bash-script execution"),
+            ));
+        }
+        if testcase.config.retry.is_some() || defaults.retry.is_some() {
+            return Err(ExecutionError::failed(
+                index,
+                anyhow!("retry not supported in bash-script execution"),
+            ));
+        }
 
         // add exported environment variables before expression
         // note: this executor is only used for Cram `.t` execution, which does
@@ -263,24 +427,26 @@ fn compile_script(testcases: &[&TestCase], config: &TestCaseConfig) -> Result<St
         // same test file share the same, unmodified default Cram environment
         // variables. Hence they only need to be set once, at the start.
         if index == 0 {
-            for (key, value) in &testcase.config.environment {
-                // variable keys and values are assumed to be escaped in bash-like
-                // environments, that means even when executing in windows within
-                // a `bash.exe` process, the unix escaping is needed
-                let qkey = shell_escape::unix::escape(Cow::from(key)).to_string();
-                if qkey != *key {
-                    return Err(ExecutionError::failed(
-                        index,
-                        anyhow!("Environment variable {} contains invalid characters", &qkey),
-                    ));
-                }
-                let qval = shell_escape::unix::escape(Cow::from(value)).to_string();
-                expressions.push(format!("export {}={}", &qkey, &qval));
+            let environment = config.interpolated_environment();
+            if !environment.is_empty() {
+                expressions.push(
+                    render_exports(&environment)
+                        .map_err(|err| ExecutionError::failed(index, err))?,
+                );
             }
         }
 
-        // add actual expression
+        // add actual expression, surrounded by the hooks, making sure that the
+        // exit code of the expression is the one that ends up in the divider
+        if document_config.before_each.is_some() {
+            expressions.push(render_hook(&document_config.before_each));
+        }
         expressions.push(testcase.shell_expression.to_string());
+        if document_config.after_each.is_some() {
+            expressions.push("__SCRUT_EXIT_CODE=$?".to_string());
+            expressions.push(render_hook(&document_config.after_each));
+            expressions.push("(exit $__SCRUT_EXIT_CODE)".to_string());
+        }
 
         // add footer that divides from next execution and captures exit code
         let footer = generate_divider(&salt, index);
@@ -427,9 +593,12 @@ mod tests {
     use super::DIVIDER_PREFIX;
     use super::DividerSearch;
     use super::parse_divider_bytes;
+    use crate::config::DocumentConfig;
     use crate::config::TestCaseConfig;
+    use crate::executors::context::Context as ExecutionContext;
     use crate::executors::error::ExecutionError;
     use crate::executors::error::ExecutionTimeout;
+    use crate::executors::executor::Executor;
     use crate::executors::executor::tests::combined_output_test_suite;
     use crate::executors::executor::tests::run_executor_tests;
     use crate::executors::executor::tests::standard_output_test_suite;
@@ -529,6 +698,29 @@ mod tests {
         run_executor_tests(BashScriptExecutor::default(), tests);
     }
 
+    #[test]
+    fn test_does_not_support_retry() {
+        let tests = vec![(
+            "Retry is refused, as all testcases run in one script",
+            vec![TestCase {
+                title: "Test".into(),
+                shell_expression: "echo OK1".into(),
+                config: TestCaseConfig {
+                    retry: Some(Duration::from_secs(1)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }],
+            None,
+            Err(ExecutionError::failed(
+                0,
+                anyhow!("retry not supported in bash-script execution"),
+            )),
+        )];
+
+        run_executor_tests(BashScriptExecutor::default(), tests);
+    }
+
     #[test]
     fn test_skipped_test_returns_skipped_error() {
         let tests = vec![(
@@ -663,4 +855,27 @@ mod tests {
 
         run_executor_tests(BashScriptExecutor::default(), tests);
     }
+
+    #[test]
+    fn test_executes_hooks_around_each_testcase() {
+        let context = ExecutionContext::new_for_test_with_config(DocumentConfig {
+            before_each: Some("echo hidden\nCOUNT=$((COUNT + 1))".into()),
+            after_each: Some("echo hidden >&2\nLAST=done\nfalse".into()),
+            ..Default::default()
+        });
+        let testcases = [
+            TestCase::from_expression("echo \"$COUNT-${LAST:-none}\" && ( exit 3 )"),
+            TestCase::from_expression("echo \"$COUNT-${LAST:-none}\""),
+        ];
+        let outputs = BashScriptExecutor::default()
+            .execute_all(&testcases.iter().collect::<Vec<_>>(), &context)
+            .expect("execute with hooks");
+        assert_eq!(
+            vec![
+                Output::from(("1-none\n", "", Some(3))),
+                Output::from(("2-done\n", "", Some(0))),
+            ],
+            outputs
+        );
+    }
 }
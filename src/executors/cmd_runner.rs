@@ -0,0 +1,301 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use tracing::trace;
+
+use super::context::Context as ExecutionContext;
+use super::runner::Runner;
+use super::stateful_executor::StatefulExecutorRunnerGenerator;
+use super::subprocess_runner::SubprocessRunner;
+use super::util::read_stdin_input;
+use super::util::working_directory;
+use super::util::write_script_file;
+use crate::output::Output;
+use crate::testcase::TestCase;
+
+const CMD_TEMPLATE: &str = include_str!("cmd_runner.template");
+
+/// Whether the shell is the Windows command processor, as told by the name of
+/// its executable (e.g. `cmd` or `C:\Windows\System32\cmd.exe`)
+pub fn is_cmd(shell: &Path) -> bool {
+    shell
+        .file_stem()
+        .is_some_and(|stem| stem.eq_ignore_ascii_case("cmd"))
+}
+
+/// A [`Runner`], that runs the shell expressions of testcases with the Windows
+/// command processor (`cmd.exe`). The shell expression is translated into a
+/// temporary `.cmd` script, that persists the environment variables and the
+/// current directory in the state directory and loads them in the subsequent
+/// execution, like the [`super::bash_runner::BashRunner`] does.
+///
+/// The shell expression runs as a subroutine of the script, so its exit code
+/// is the `%ERRORLEVEL%` that the last command left, or that the shell
+/// expression ended with `exit /b <code>`. The configured
+/// [`crate::config::TestCaseConfig::stdin`] is provided as standard input of
+/// `cmd.exe`, from which the commands read.
+///
+/// Underneath the [`SubprocessRunner`] is used, so timeout constraints are
+/// fully supported. Running attached to a terminal (`tty`) is not.
+#[derive(Clone)]
+pub struct CmdRunner {
+    pub shell: PathBuf,
+    pub state_directory: PathBuf,
+}
+
+impl CmdRunner {
+    pub fn new(shell: &Path, state_directory: &Path) -> Self {
+        Self {
+            shell: shell.to_owned(),
+            state_directory: state_directory.to_owned(),
+        }
+    }
+
+    pub fn stateful_generator(shell: &Path) -> StatefulExecutorRunnerGenerator {
+        let shell = shell.to_owned();
+        Box::new(move |state_directory: &Path| -> Box<dyn Runner> {
+            Box::new(Self::new(&shell, state_directory)) as Box<dyn Runner>
+        })
+    }
+
+    /// Returns the script, that runs the shell expression of the testcase, with
+    /// the CRLF line endings that labels in batch scripts depend on
+    fn render_script(&self, testcase: &TestCase, context: &ExecutionContext) -> Result<String> {
+        let script = CMD_TEMPLATE
+            .replace(
+                "{state_directory}",
+                &escape_cmd(&self.state_directory.to_string_lossy()),
+            )
+            .replace(
+                "{environment}",
+                &render_cmd_environment(&testcase.config.environment)?,
+            )
+            .replace(
+                "{change_directory}",
+                &render_cmd_change_directory(&working_directory(testcase, context)?),
+            )
+            .replace(
+                "{before_each}",
+                context.config.before_each.as_deref().unwrap_or_default(),
+            )
+            .replace(
+                "{after_each}",
+                context.config.after_each.as_deref().unwrap_or_default(),
+            )
+            .replace(
+                "{persist_state}",
+                if testcase.config.detached.unwrap_or(false) {
+                    "0"
+                } else {
+                    "1"
+                },
+            )
+            .replace("{shell_expression}", &testcase.shell_expression);
+        Ok(script.replace("\r\n", "\n").replace('\n', "\r\n"))
+    }
+}
+
+impl Runner for CmdRunner {
+    fn run(&self, name: &str, testcase: &TestCase, context: &ExecutionContext) -> Result<Output> {
+        if testcase.config.tty.unwrap_or(false) {
+            bail!("tty is not supported with cmd");
+        }
+        if !testcase.interaction.is_empty() {
+            bail!("interaction requires tty");
+        }
+
+        let script = self.render_script(testcase, context)?;
+        trace!(name, "compiled script {}", &script);
+        let script_path = write_script_file(&context.temp_directory, ".cmd", script.as_bytes())?;
+
+        let mut testcase = testcase.clone();
+        testcase.shell_expression = read_stdin_input(&testcase, context)?;
+
+        // `/D` skips the AutoRun commands from the registry, `/Q` turns off echo
+        let runner = SubprocessRunner::new(self.shell.clone()).with_args(&[
+            "/D".into(),
+            "/Q".into(),
+            "/C".into(),
+            script_path.to_string_lossy().into(),
+        ]);
+        let output = runner.run(name, &testcase, context);
+
+        // detached executions read the script after this run ended, the file
+        // is removed together with the temporary directory
+        if testcase.config.detached.unwrap_or(false) {
+            script_path.keep().context("keep script file")?;
+        }
+        output
+    }
+}
+
+/// Renders the statements, that change into the working directory and
+/// remember the previous directory to return to, or nothing
+fn render_cmd_change_directory(directory: &Option<PathBuf>) -> String {
+    match directory {
+        Some(directory) => format!(
+            "set \"__SCRUT_PREVIOUS_DIRECTORY=%CD%\"\ncd /d \"{}\" || exit /b 1",
+            escape_cmd(&directory.to_string_lossy())
+        ),
+        None => String::new(),
+    }
+}
+
+/// Escapes `%` in text that is written quoted into a batch script, which
+/// would otherwise be read as the start of a variable expansion
+fn escape_cmd(text: &str) -> String {
+    text.replace('%', "%%")
+}
+
+/// Escapes text that is written unquoted into a batch script, so that
+/// characters that are special to cmd, like `&` or `>`, are literal
+fn escape_cmd_unquoted(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '%' => escaped.push_str("%%"),
+            '^' | '&' | '|' | '<' | '>' | '(' | ')' | '"' => {
+                escaped.push('^');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders the environment variables as one `set` statement each, whose
+/// values are escaped, so that they are set literally
+fn render_cmd_environment(environment: &BTreeMap<String, String>) -> Result<String> {
+    let mut statements = vec![];
+    for (key, value) in environment {
+        if key.is_empty() || key.contains(['=', '\r', '\n']) {
+            bail!("Environment variable {key:?} contains invalid characters");
+        }
+        if value.contains(['\r', '\n']) {
+            bail!("Environment variable {key} contains a line break, which cmd does not support");
+        }
+        statements.push(format!(
+            "set {}={}",
+            escape_cmd_unquoted(key),
+            escape_cmd_unquoted(value)
+        ));
+    }
+    Ok(statements.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    use super::CmdRunner;
+    use super::is_cmd;
+    use super::render_cmd_environment;
+    use crate::config::DocumentConfig;
+    use crate::executors::context::Context as ExecutionContext;
+    use crate::testcase::TestCase;
+
+    #[test]
+    fn test_is_cmd() {
+        for (shell, expected) in [
+            ("cmd", true),
+            ("C:/Windows/System32/cmd.exe", true),
+            ("CMD.EXE", true),
+            ("/bin/bash", false),
+            ("cmder", false),
+        ] {
+            assert_eq!(expected, is_cmd(Path::new(shell)), "{shell}");
+        }
+    }
+
+    #[test]
+    fn test_render_cmd_environment() {
+        assert_eq!(
+            "set FOO=100%% ^\"quoted^\" ^& ^^literal\nset with space=",
+            render_cmd_environment(&BTreeMap::from([
+                ("FOO".into(), "100% \"quoted\" & ^literal".into()),
+                ("with space".into(), "".into()),
+            ]))
+            .expect("render environment")
+        );
+        render_cmd_environment(&BTreeMap::from([("A=B".into(), "".into())]))
+            .expect_err("name must not contain =");
+        render_cmd_environment(&BTreeMap::from([("FOO".into(), "multi\nline".into())]))
+            .expect_err("value must not contain line breaks");
+    }
+
+    #[test]
+    fn test_render_script() {
+        let mut testcase = TestCase::from_expression("echo %FOO%\nexit /b 3");
+        testcase
+            .config
+            .environment
+            .insert("FOO".into(), "bar".into());
+        let context = ExecutionContext::new_for_test_with_config(DocumentConfig {
+            before_each: Some("set BEFORE=1".into()),
+            ..Default::default()
+        });
+        let script = CmdRunner::new(Path::new("cmd"), Path::new("C:/state/100%"))
+            .render_script(&testcase, &context)
+            .expect("render script");
+
+        assert!(!script.replace("\r\n", "").contains('\n'), "CRLF only");
+        let lines = script.lines().collect::<Vec<_>>();
+        for line in [
+            "set FOO=bar",
+            "set > \"C:/state/100%%\\state.env\"",
+            "if not \"1\"==\"1\" exit /b %__SCRUT_EXIT_CODE%",
+            ":__scrut_before_each",
+            "set BEFORE=1",
+        ] {
+            assert!(lines.contains(&line), "script contains `{line}`");
+        }
+        assert!(
+            script.ends_with(":__scrut_shell_expression\r\necho %FOO%\r\nexit /b 3\r\n"),
+            "shell expression is the last subroutine"
+        );
+    }
+
+    #[cfg(target_os = "windows")]
+    #[test]
+    fn test_execute_persists_state_and_exit_codes() {
+        use crate::executors::executor::Executor;
+        use crate::executors::stateful_executor::StatefulExecutor;
+        use crate::output::Output;
+
+        let testcases = [
+            TestCase::from_expression("set GREETING=Hello\ncd .."),
+            TestCase::from_expression("echo %GREETING%, %NAME%"),
+            TestCase::from_expression("dir does-not-exist >nul 2>&1"),
+            TestCase::from_expression("exit /b 3"),
+        ];
+        let mut testcases = testcases.to_vec();
+        testcases[1]
+            .config
+            .environment
+            .insert("NAME".into(), "World".into());
+        let context = ExecutionContext::new_for_test();
+        let outputs = StatefulExecutor::new(CmdRunner::stateful_generator(Path::new("cmd.exe")))
+            .execute_all(&testcases.iter().collect::<Vec<_>>(), &context)
+            .expect("execute without error");
+        let expected: Vec<Output> = vec![
+            ("", "", Some(0)).into(),
+            ("Hello, World\n", "", Some(0)).into(),
+            ("", "", Some(1)).into(),
+            ("", "", Some(3)).into(),
+        ];
+        assert_eq!(expected, outputs);
+    }
+}
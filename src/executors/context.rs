@@ -5,10 +5,15 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::fmt::Debug;
+use std::fmt::Display;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 use derive_builder::Builder;
 
+use super::detached::DetachedProcesses;
 use crate::config::DocumentConfig;
 
 /// Context that describes the environment in which one or multiple [`crate::testcase::TestCase`]s are executed in
@@ -26,8 +31,107 @@ pub struct Context {
     /// The configuration on per-document level
     #[builder(default)]
     pub config: DocumentConfig,
+
+    /// Receives notices about executions that are starting or still running
+    #[builder(default)]
+    pub notifier: ProgressNotifier,
+
+    /// The processes that testcases started detached, which are killed once
+    /// the context and all its clones are dropped, unless they are taken
+    #[builder(default)]
+    pub detached_processes: DetachedProcesses,
 }
 
+/// A notice about an execution that is starting or still running
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProgressNotice {
+    /// The execution of a testcase is about to start
+    TestCaseStarted { title: String, line_number: usize },
+
+    /// The execution of a testcase has been running for the elapsed time,
+    /// which is the configured percentage of its timeout (see
+    /// [`DocumentConfig::timeout_warning`])
+    TimeoutApproaching {
+        title: String,
+        line_number: usize,
+        elapsed: Duration,
+        timeout: Duration,
+    },
+}
+
+impl Display for ProgressNotice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TestCaseStarted { title, line_number } => {
+                write!(f, "testcase at line {}", line_number)?;
+                if !title.is_empty() {
+                    write!(f, " ({})", title.lines().next().unwrap_or_default())?;
+                }
+                Ok(())
+            }
+            Self::TimeoutApproaching {
+                title,
+                line_number,
+                elapsed,
+                timeout,
+            } => {
+                write!(f, "testcase at line {}", line_number)?;
+                if !title.is_empty() {
+                    write!(f, " ({})", title.lines().next().unwrap_or_default())?;
+                }
+                write!(
+                    f,
+                    " is running for {} of its {} timeout",
+                    humantime::format_duration(*elapsed),
+                    humantime::format_duration(*timeout)
+                )
+            }
+        }
+    }
+}
+
+/// Function that is called with every [`ProgressNotice`]
+pub type ProgressCallback = dyn Fn(&ProgressNotice) + Send + Sync;
+
+/// Callback that receives [`ProgressNotice`]s, possibly from another thread
+#[derive(Clone, Default)]
+pub struct ProgressNotifier(Option<Arc<ProgressCallback>>);
+
+impl ProgressNotifier {
+    pub fn new<F: Fn(&ProgressNotice) + Send + Sync + 'static>(callback: F) -> Self {
+        Self(Some(Arc::new(callback)))
+    }
+
+    /// Whether there is anybody to receive notices
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub fn notify(&self, notice: &ProgressNotice) {
+        if let Some(callback) = &self.0 {
+            callback(notice)
+        }
+    }
+}
+
+impl Debug for ProgressNotifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ProgressNotifier({})", self.is_enabled())
+    }
+}
+
+impl PartialEq for ProgressNotifier {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(left), Some(right)) => Arc::ptr_eq(left, right),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for ProgressNotifier {}
+
 #[cfg(test)]
 impl Context {
     /// Create a new context that is used by test. Both temporary and working directory
@@ -43,6 +147,8 @@ impl Context {
             temp_directory: test::create_testing_directory(),
             file: PathBuf::from("test.md"),
             config,
+            notifier: Default::default(),
+            detached_processes: Default::default(),
         }
     }
 }
@@ -166,6 +272,8 @@ mod test {
             work_directory: work_directory.path().to_path_buf(),
             file: PathBuf::from("test.md"),
             config: Default::default(),
+            notifier: Default::default(),
+            detached_processes: Default::default(),
         };
 
         assert!(temp_directory.path().exists(), "temp directory is created");
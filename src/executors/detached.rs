@@ -0,0 +1,194 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::fmt::Debug;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use tracing::debug;
+use tracing::warn;
+
+use crate::output::DetachedProcess;
+
+/// Directory within the work directory, that holds the files into which the
+/// output of detached testcases is written
+pub const DETACHED_LOGS_DIRECTORY: &str = ".detached-logs";
+
+/// Returns the paths of the files that the STDOUT and STDERR of the detached
+/// testcase at the given line are written to
+pub fn detached_log_paths(work_directory: &Path, line_number: usize) -> (PathBuf, PathBuf) {
+    let directory = work_directory.join(DETACHED_LOGS_DIRECTORY);
+    (
+        directory.join(format!("line-{line_number}.stdout")),
+        directory.join(format!("line-{line_number}.stderr")),
+    )
+}
+
+/// The processes that testcases started detached, which are shared between
+/// all clones. Processes that are still tracked when the last clone is
+/// dropped are killed, so that they do not outlive an execution that ended
+/// early (timeout, fail fast) or that panicked.
+#[derive(Clone, Default)]
+pub struct DetachedProcesses(Arc<Mutex<TrackedProcesses>>);
+
+impl DetachedProcesses {
+    /// Starts tracking the process
+    pub fn track(&self, process: DetachedProcess) {
+        self.lock().0.push(process);
+    }
+
+    /// Stops tracking and returns all processes, which makes the caller
+    /// responsible for killing them
+    pub fn take(&self) -> Vec<DetachedProcess> {
+        std::mem::take(&mut self.lock().0)
+    }
+
+    /// Amount of processes that are tracked
+    pub fn len(&self) -> usize {
+        self.lock().0.len()
+    }
+
+    /// Whether no process is tracked
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TrackedProcesses> {
+        // processes must be killed, even if another thread panicked
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Debug for DetachedProcesses {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DetachedProcesses({})", self.len())
+    }
+}
+
+impl PartialEq for DetachedProcesses {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for DetachedProcesses {}
+
+#[derive(Default)]
+struct TrackedProcesses(Vec<DetachedProcess>);
+
+impl Drop for TrackedProcesses {
+    fn drop(&mut self) {
+        for process in self.0.drain(..) {
+            if process.signal.is_off() {
+                continue;
+            }
+            debug!(pid = process.pid, "killing left over detached process");
+            if let Err(err) = process.kill() {
+                warn!(
+                    pid = process.pid,
+                    "failed to kill detached process: {err:#}"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::DetachedProcesses;
+    use super::detached_log_paths;
+    use crate::output::DetachedProcess;
+    use crate::signal::KillSignal;
+
+    /// Whether the process exists and is not a zombie
+    #[cfg(target_os = "linux")]
+    fn is_running(pid: u32) -> bool {
+        std::fs::read_to_string(format!("/proc/{pid}/stat")).is_ok_and(|stat| {
+            stat.rsplit_once(") ")
+                .is_some_and(|(_, rest)| !rest.starts_with('Z'))
+        })
+    }
+
+    #[test]
+    fn test_detached_log_paths() {
+        assert_eq!(
+            (
+                Path::new("work/.detached-logs/line-3.stdout").to_path_buf(),
+                Path::new("work/.detached-logs/line-3.stderr").to_path_buf(),
+            ),
+            detached_log_paths(Path::new("work"), 3)
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_dropping_kills_process_group() {
+        use std::time::Duration;
+        use std::time::Instant;
+
+        use subprocess::Popen;
+        use subprocess::PopenConfig;
+
+        let directory = tempfile::tempdir().expect("create temporary directory");
+        let pid_file = directory.path().join("pid");
+        let mut process = Popen::create(
+            &[
+                "sh",
+                "-c",
+                &format!("sleep 30 & echo $! > '{}'; wait", pid_file.display()),
+            ],
+            PopenConfig {
+                setpgid: true,
+                ..Default::default()
+            },
+        )
+        .expect("start process");
+        let pid = process.pid().expect("process is running");
+        let started = Instant::now();
+        while !pid_file.exists() && started.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let child: u32 = std::fs::read_to_string(&pid_file)
+            .expect("read child pid")
+            .trim()
+            .parse()
+            .expect("parse child pid");
+
+        let processes = DetachedProcesses::default();
+        processes.track(DetachedProcess {
+            pid,
+            signal: KillSignal::default(),
+        });
+        let clone = processes.clone();
+        drop(processes);
+        assert!(is_running(child), "clone keeps tracking");
+
+        drop(clone);
+        process.wait().expect("wait for process");
+        let started = Instant::now();
+        while is_running(child) && started.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!is_running(child), "child of detached process is killed");
+    }
+
+    #[test]
+    fn test_take_stops_tracking() {
+        let processes = DetachedProcesses::default();
+        processes.track(DetachedProcess {
+            pid: u32::MAX,
+            signal: KillSignal::Disabled,
+        });
+        assert_eq!(1, processes.len());
+        assert_eq!(1, processes.take().len());
+        assert!(processes.is_empty());
+    }
+}
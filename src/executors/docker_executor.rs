@@ -0,0 +1,450 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::rc::Rc;
+
+use anyhow::Context;
+use anyhow::anyhow;
+use anyhow::bail;
+use tracing::debug;
+use tracing::warn;
+
+use super::bash_runner::BashRunner;
+use super::bash_runner::redirect_stdin;
+use super::context::Context as ExecutionContext;
+use super::error::ExecutionError;
+use super::executor::Executor;
+use super::executor::Result;
+use super::runner::Runner;
+use super::stateful_executor::StatefulExecutor;
+use super::subprocess_runner::SubprocessRunner;
+use crate::config::DockerConfig;
+use crate::config::WorkDirectoryScope;
+use crate::output::Output;
+use crate::testcase::TestCase;
+
+/// The program, that containers are managed with
+const DOCKER: &str = "docker";
+
+/// An executor, that runs the testcases of a document with `bash` in a
+/// container, which is created from the image of the [`DockerConfig`] and
+/// removed after all testcases ran.
+///
+/// The work directory is copied into the container before the first testcase
+/// and copied back after each testcase, so that files the testcases create
+/// can be asserted on. It has the same path in the container as on the host,
+/// as has the temporary directory, so that `$TMPDIR` and paths in the output
+/// are the same. The directory of the test document is mounted read-only, so
+/// that `$TESTDIR` can be used.
+///
+/// Underneath the [`StatefulExecutor`] runs each testcase with `docker exec`,
+/// so state is shared in between testcases as with the
+/// [`super::bash_runner::BashRunner`].
+pub struct DockerExecutor {
+    shell: PathBuf,
+    config: DockerConfig,
+}
+
+impl DockerExecutor {
+    pub fn new(shell: &Path, config: &DockerConfig) -> Self {
+        Self {
+            shell: shell.to_owned(),
+            config: config.clone(),
+        }
+    }
+}
+
+impl Executor for DockerExecutor {
+    fn execute_all(
+        &self,
+        testcases: &[&TestCase],
+        context: &ExecutionContext,
+    ) -> Result<Vec<Output>> {
+        if context
+            .config
+            .shell
+            .as_ref()
+            .is_some_and(|shell| shell.interpreter().is_some())
+            || testcases
+                .iter()
+                .any(|testcase| testcase.config.shell.is_some())
+        {
+            return Err(ExecutionError::aborted(
+                anyhow!("an interpreter as shell is not supported with the docker executor"),
+                None,
+            ));
+        }
+
+        if context.config.get_workdir().scope == WorkDirectoryScope::PerTestcase {
+            return Err(ExecutionError::aborted(
+                anyhow!("per-testcase work directories are not supported with the docker executor"),
+                None,
+            ));
+        }
+
+        let container = Rc::new(
+            Container::start(&self.config, context)
+                .map_err(|err| ExecutionError::aborted(err, None))?,
+        );
+        let (shell, runner_container) = (self.shell.clone(), container.clone());
+        let outputs = StatefulExecutor::new(Box::new(move |state_directory: &Path| {
+            Box::new(DockerRunner {
+                bash: BashRunner::new(&shell, state_directory),
+                container: runner_container.clone(),
+            }) as Box<dyn Runner>
+        }))
+        .execute_all(testcases, context);
+
+        // detached processes may have written more files after the last
+        // testcase, which are copied back, however the execution ended
+        match (outputs, container.copy_out(&context.work_directory)) {
+            (Ok(outputs), Ok(())) => Ok(outputs),
+            (Ok(_), Err(err)) => Err(ExecutionError::aborted(err, None)),
+            (Err(err), copied) => {
+                if let Err(copy_err) = copied {
+                    warn!("{copy_err:#}");
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+/// A [`Runner`], that runs the bash script of the [`BashRunner`] in the
+/// container with `docker exec`
+struct DockerRunner {
+    bash: BashRunner,
+    container: Rc<Container>,
+}
+
+impl Runner for DockerRunner {
+    fn run(
+        &self,
+        name: &str,
+        testcase: &TestCase,
+        context: &ExecutionContext,
+    ) -> anyhow::Result<Output> {
+        if testcase.config.tty.unwrap_or(false) {
+            bail!("tty is not supported with the docker executor");
+        }
+        if !testcase.interaction.is_empty() {
+            bail!("interaction requires tty");
+        }
+
+        // the standard input is written into the temporary directory of the
+        // container, from where the shell expression reads it
+        let shell_expression = match testcase.config.stdin {
+            Some(ref stdin) => {
+                let directory = context.file.parent().unwrap_or(Path::new(""));
+                let content = stdin.read(directory)?;
+                let path = context.temp_directory.join(format!(".stdin.{name}"));
+                self.container.write_file(&path, &content)?;
+                redirect_stdin(&testcase.shell_expression, &path)
+            }
+            None => testcase.shell_expression.clone(),
+        };
+
+        let mut testcase = testcase.clone();
+        testcase.shell_expression =
+            self.bash
+                .render_script(name, &shell_expression, &testcase, context)?;
+        let output = SubprocessRunner::new(DOCKER.into())
+            .with_args(&[
+                "exec".into(),
+                "--interactive".into(),
+                "--workdir".into(),
+                context.work_directory.to_string_lossy().into(),
+                self.container.id.clone(),
+                self.bash.shell.to_string_lossy().into(),
+            ])
+            .run(name, &testcase, context)?;
+
+        if !testcase.config.detached.unwrap_or(false) {
+            self.container.copy_out(&context.work_directory)?;
+        }
+        Ok(output)
+    }
+}
+
+/// A running container, that is removed when it is dropped
+struct Container {
+    id: String,
+}
+
+impl Container {
+    /// Starts a container, that runs until it is removed, and copies the work
+    /// directory into it
+    fn start(config: &DockerConfig, context: &ExecutionContext) -> anyhow::Result<Self> {
+        if cfg!(windows) {
+            bail!("the docker executor is not supported on Windows");
+        }
+        let document_directory = std::path::absolute(&context.file)
+            .context("absolute path of test document")?
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let arguments = run_arguments(config, &document_directory)?;
+        let arguments = arguments
+            .iter()
+            .map(OsString::as_os_str)
+            .collect::<Vec<_>>();
+        let id = docker(&arguments, None, "start container")?;
+        let container = Self { id };
+        debug!("started container {}", container.id);
+
+        docker(
+            &[
+                "exec".as_ref(),
+                container.id.as_ref(),
+                "mkdir".as_ref(),
+                "-p".as_ref(),
+                context.work_directory.as_os_str(),
+                context.temp_directory.as_os_str(),
+            ],
+            None,
+            "create work and temporary directory in container",
+        )?;
+        let mut source = context.work_directory.clone().into_os_string();
+        source.push("/.");
+        docker(
+            &[
+                "cp".as_ref(),
+                source.as_os_str(),
+                container.path(&context.work_directory).as_os_str(),
+            ],
+            None,
+            "copy work directory into container",
+        )?;
+        Ok(container)
+    }
+
+    /// Returns the path in the container, as argument of `docker cp`
+    fn path(&self, path: &Path) -> OsString {
+        let mut container_path = OsString::from(format!("{}:", self.id));
+        container_path.push(path);
+        container_path
+    }
+
+    /// Copies the work directory from the container into the same directory
+    /// on the host
+    fn copy_out(&self, work_directory: &Path) -> anyhow::Result<()> {
+        let mut source = self.path(work_directory);
+        source.push("/.");
+        docker(
+            &[
+                "cp".as_ref(),
+                source.as_os_str(),
+                work_directory.as_os_str(),
+            ],
+            None,
+            "copy work directory from container",
+        )
+        .map(|_| ())
+    }
+
+    /// Writes the content into a file at the path in the container
+    fn write_file(&self, path: &Path, content: &[u8]) -> anyhow::Result<()> {
+        docker(
+            &[
+                "exec".as_ref(),
+                "--interactive".as_ref(),
+                self.id.as_ref(),
+                "sh".as_ref(),
+                "-c".as_ref(),
+                "cat > \"$0\"".as_ref(),
+                path.as_os_str(),
+            ],
+            Some(content),
+            &format!("write {} in container", path.display()),
+        )
+        .map(|_| ())
+    }
+}
+
+impl Drop for Container {
+    fn drop(&mut self) {
+        debug!("removing container {}", self.id);
+        if let Err(err) = docker(
+            &["rm".as_ref(), "--force".as_ref(), self.id.as_ref()],
+            None,
+            "remove container",
+        ) {
+            warn!("{err}");
+        }
+    }
+}
+
+/// Returns the arguments of `docker run`, that start a container from the
+/// configured image, which does nothing until it is removed
+fn run_arguments(
+    config: &DockerConfig,
+    document_directory: &Path,
+) -> anyhow::Result<Vec<OsString>> {
+    if config.image.is_empty() {
+        bail!("docker executor requires `docker.image`");
+    }
+    let mut arguments: Vec<OsString> = vec!["run".into(), "--detach".into(), "--init".into()];
+    let mut mount = |host: &Path, target: &str| {
+        let mut volume = std::path::absolute(document_directory.join(host))
+            .unwrap_or_else(|_| host.to_path_buf())
+            .into_os_string();
+        volume.push(":");
+        volume.push(target);
+        arguments.extend(["--volume".into(), volume]);
+    };
+    mount(
+        document_directory,
+        &format!("{}:ro", document_directory.to_string_lossy()),
+    );
+    for volume in &config.mounts {
+        let Some((host, target)) = volume.split_once(':') else {
+            bail!("mount `{volume}` is not of the form `<host path>:<container path>`");
+        };
+        mount(Path::new(host), target);
+    }
+    for (key, value) in &config.environment {
+        arguments.extend(["--env".into(), format!("{key}={value}").into()]);
+    }
+    arguments.extend([
+        "--entrypoint".into(),
+        "tail".into(),
+        config.image.clone().into(),
+        "-f".into(),
+        "/dev/null".into(),
+    ]);
+    Ok(arguments)
+}
+
+/// Runs `docker` with the arguments and optional standard input, and returns
+/// its trimmed standard output, or an error with the description of the action
+/// and the standard error
+fn docker(arguments: &[&OsStr], input: Option<&[u8]>, action: &str) -> anyhow::Result<String> {
+    let mut child = Command::new(DOCKER)
+        .args(arguments)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| anyhow!("{action}: start `{DOCKER}`: {err}"))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin
+            .write_all(input)
+            .map_err(|err| anyhow!("{action}: write standard input: {err}"))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|err| anyhow!("{action}: wait for `{DOCKER}`: {err}"))?;
+    if !output.status.success() {
+        bail!(
+            "{action}: `{DOCKER}` failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::ffi::OsString;
+    use std::path::Path;
+
+    use super::DockerExecutor;
+    use super::run_arguments;
+    use crate::config::DockerConfig;
+    use crate::executors::context::Context as ExecutionContext;
+    use crate::executors::executor::Executor;
+    use crate::output::Output;
+    use crate::testcase::TestCase;
+
+    #[test]
+    fn test_run_arguments() {
+        let arguments = run_arguments(
+            &DockerConfig {
+                image: "ubuntu:24.04".into(),
+                mounts: vec!["bin:/opt/bin:ro".into(), "/data:/data".into()],
+                environment: BTreeMap::from([("FOO".into(), "bar baz".into())]),
+            },
+            Path::new("/tests"),
+        )
+        .expect("render arguments");
+        assert_eq!(
+            [
+                "run",
+                "--detach",
+                "--init",
+                "--volume",
+                "/tests:/tests:ro",
+                "--volume",
+                "/tests/bin:/opt/bin:ro",
+                "--volume",
+                "/data:/data",
+                "--env",
+                "FOO=bar baz",
+                "--entrypoint",
+                "tail",
+                "ubuntu:24.04",
+                "-f",
+                "/dev/null",
+            ]
+            .map(OsString::from)
+            .to_vec(),
+            arguments
+        );
+
+        run_arguments(&DockerConfig::default(), Path::new("/tests")).expect_err("requires image");
+        run_arguments(
+            &DockerConfig {
+                image: "ubuntu:24.04".into(),
+                mounts: vec!["no-target".into()],
+                ..Default::default()
+            },
+            Path::new("/tests"),
+        )
+        .expect_err("mount requires target");
+    }
+
+    #[test]
+    fn test_execute_in_container() {
+        let image = match std::env::var("SCRUT_TEST_DOCKER_IMAGE") {
+            Ok(image) if super::docker(&["info".as_ref()], None, "get info").is_ok() => image,
+            _ => return,
+        };
+        let testcases = [
+            TestCase::from_expression("export GREETING=Hello; echo created > file.txt"),
+            TestCase::from_expression("echo \"$GREETING\"; cat file.txt; exit 3"),
+        ];
+        let context = ExecutionContext::new_for_test();
+        let outputs = DockerExecutor::new(
+            Path::new("bash"),
+            &DockerConfig {
+                image,
+                ..Default::default()
+            },
+        )
+        .execute_all(&testcases.iter().collect::<Vec<_>>(), &context)
+        .expect("execute in container");
+        let expected: Vec<Output> = vec![
+            ("", "", Some(0)).into(),
+            ("Hello\ncreated\n", "", Some(3)).into(),
+        ];
+        assert_eq!(expected, outputs);
+        assert!(context.work_directory.join("file.txt").exists());
+    }
+}
@@ -50,7 +50,7 @@ pub enum ExecutionError {
         error: anyhow::Error,
 
         /// Potentially the last output leading to the abort of execution
-        output: Option<Output>,
+        output: Option<Box<Output>>,
     },
 
     /// Returned if either a single [`crate::testcase::TestCase`] execution timed
@@ -76,7 +76,10 @@ impl ExecutionError {
     /// Construct a new error without an index (e.g. when failure in execute_all)
     /// happens before or after executions take place
     pub fn aborted(error: anyhow::Error, output: Option<Output>) -> Self {
-        Self::AbortedExecutions { error, output }
+        Self::AbortedExecutions {
+            error,
+            output: output.map(Box::new),
+        }
     }
 
     /// Construct a new error with an index, that denotes a specific execution
@@ -92,7 +95,7 @@ impl ExecutionError {
     ) -> Self {
         match index {
             Some(index) => Self::FailedExecution { index, error },
-            None => Self::AbortedExecutions { error, output },
+            None => Self::aborted(error, output),
         }
     }
 }
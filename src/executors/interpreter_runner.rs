@@ -0,0 +1,96 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use anyhow::Result;
+use anyhow::bail;
+
+use super::context::Context as ExecutionContext;
+use super::runner::Runner;
+use super::subprocess_runner::SubprocessRunner;
+use super::util::read_stdin_input;
+use super::util::working_directory;
+use crate::output::Output;
+use crate::testcase::TestCase;
+
+/// A [`Runner`], that runs the shell expression of a testcase with an
+/// arbitrary interpreter, as configured with
+/// [`crate::config::Shell::Interpreter`]. The interpreter is started with its
+/// arguments and the shell expression as the last argument (e.g.
+/// `python3 -c '<shell expression>'`).
+///
+/// Unlike the shells, an interpreter does not share state in between
+/// executions: it is provided with all configured environment variables and
+/// starts in the work directory, or the configured
+/// [`crate::config::TestCaseConfig::cwd`]. The `before_each` and `after_each` snippets
+/// of the document, which are written for the shell, are not run. The
+/// configured [`crate::config::TestCaseConfig::stdin`] is provided as its
+/// standard input.
+///
+/// Underneath the [`SubprocessRunner`] is used, so timeout constraints are
+/// fully supported. Running attached to a terminal (`tty`) is not.
+#[derive(Clone)]
+pub struct InterpreterRunner {
+    pub command: Vec<String>,
+}
+
+impl InterpreterRunner {
+    pub fn new(command: &[String]) -> Self {
+        Self {
+            command: command.to_vec(),
+        }
+    }
+}
+
+impl Runner for InterpreterRunner {
+    fn run(&self, name: &str, testcase: &TestCase, context: &ExecutionContext) -> Result<Output> {
+        if testcase.config.tty.unwrap_or(false) {
+            bail!("tty is not supported with an interpreter");
+        }
+        if !testcase.interaction.is_empty() {
+            bail!("interaction requires tty");
+        }
+        let Some((program, args)) = self.command.split_first() else {
+            bail!("interpreter must not be empty");
+        };
+
+        let mut args = args.to_vec();
+        args.push(testcase.shell_expression.clone());
+        let directory = working_directory(testcase, context)?;
+        let mut testcase = testcase.clone();
+        testcase.shell_expression = read_stdin_input(&testcase, context)?;
+
+        SubprocessRunner::new(program.into())
+            .with_args(&args)
+            .with_current_directory(directory)
+            .run(name, &testcase, context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InterpreterRunner;
+    use crate::config::TestCaseStdin;
+    use crate::executors::context::Context as ExecutionContext;
+    use crate::executors::runner::Runner;
+    use crate::output::Output;
+    use crate::testcase::TestCase;
+
+    #[test]
+    fn test_run_passes_expression_as_last_argument() {
+        let runner = InterpreterRunner::new(&["bash".into(), "-c".into()]);
+        let mut testcase = TestCase::from_expression("tr a-z A-Z; echo \"$GREETING\"; exit 3");
+        testcase.config.stdin = Some(TestCaseStdin::Content("hello\n".into()));
+        testcase
+            .config
+            .environment
+            .insert("GREETING".into(), "hi".into());
+        let output = runner
+            .run("exec1", &testcase, &ExecutionContext::new_for_test())
+            .expect("run interpreter");
+        assert_eq!(Output::from(("HELLO\nhi\n", "", Some(3))), output);
+    }
+}
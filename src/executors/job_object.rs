@@ -0,0 +1,163 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Windows has no process groups, that all processes a shell starts belong
+//! to. Instead processes are assigned to a job object, which all processes
+//! that they start belong to as well, so that the whole tree can be ended
+//! together.
+
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::ptr::null;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+use windows_sys::Win32::Foundation::CloseHandle;
+use windows_sys::Win32::Foundation::HANDLE;
+use windows_sys::Win32::System::JobObjects::AssignProcessToJobObject;
+use windows_sys::Win32::System::JobObjects::CreateJobObjectW;
+use windows_sys::Win32::System::JobObjects::JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+use windows_sys::Win32::System::JobObjects::JOBOBJECT_EXTENDED_LIMIT_INFORMATION;
+use windows_sys::Win32::System::JobObjects::JobObjectExtendedLimitInformation;
+use windows_sys::Win32::System::JobObjects::SetInformationJobObject;
+use windows_sys::Win32::System::JobObjects::TerminateJobObject;
+use windows_sys::Win32::System::Threading::OpenProcess;
+use windows_sys::Win32::System::Threading::PROCESS_SET_QUOTA;
+use windows_sys::Win32::System::Threading::PROCESS_TERMINATE;
+
+/// The exit code of processes that are ended with their job object
+const TERMINATED_EXIT_CODE: u32 = 1;
+
+lazy_static! {
+    /// Job objects of detached processes, by the PID of the process, which
+    /// are kept open until the processes are killed
+    static ref DETACHED_JOBS: Mutex<HashMap<u32, JobObject>> = Mutex::new(HashMap::new());
+}
+
+/// A job object that the processes of one execution are assigned to
+pub(crate) struct JobObject(HANDLE);
+
+// SAFETY: the handle of a job object can be used from any thread
+unsafe impl Send for JobObject {}
+
+impl JobObject {
+    /// Creates a job object, whose processes are killed once it is closed,
+    /// if `kill_on_close`, i.e. at the latest when Scrut ends
+    pub(crate) fn new(kill_on_close: bool) -> Result<Self> {
+        // SAFETY: both attributes and name are optional
+        let handle = unsafe { CreateJobObjectW(null(), null()) };
+        if handle.is_null() {
+            bail!("create job object: {}", std::io::Error::last_os_error());
+        }
+        let job = Self(handle);
+        if kill_on_close {
+            let mut limits = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+            limits.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            // SAFETY: the information matches its class and size
+            let result = unsafe {
+                SetInformationJobObject(
+                    job.0,
+                    JobObjectExtendedLimitInformation,
+                    &limits as *const _ as *const _,
+                    size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+                )
+            };
+            if result == 0 {
+                bail!("configure job object: {}", std::io::Error::last_os_error());
+            }
+        }
+        Ok(job)
+    }
+
+    /// Assigns the process with the given PID, and all processes it starts
+    /// from now on, to the job object
+    pub(crate) fn assign(&self, pid: u32) -> Result<()> {
+        // SAFETY: the returned handle is checked and closed below
+        let process = unsafe { OpenProcess(PROCESS_SET_QUOTA | PROCESS_TERMINATE, 0, pid) };
+        if process.is_null() {
+            bail!("open process {pid}: {}", std::io::Error::last_os_error());
+        }
+        // SAFETY: both handles are valid
+        let result = unsafe { AssignProcessToJobObject(self.0, process) };
+        let err = std::io::Error::last_os_error();
+        // SAFETY: the handle is valid and not used afterwards
+        unsafe { CloseHandle(process) };
+        if result == 0 {
+            bail!("assign process {pid} to job object: {err}");
+        }
+        Ok(())
+    }
+
+    /// Ends all processes of the job object
+    pub(crate) fn terminate(&self) -> Result<()> {
+        // SAFETY: the handle is valid
+        if unsafe { TerminateJobObject(self.0, TERMINATED_EXIT_CODE) } == 0 {
+            bail!("terminate job object: {}", std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for JobObject {
+    fn drop(&mut self) {
+        // SAFETY: the handle is valid and not used afterwards
+        unsafe { CloseHandle(self.0) };
+    }
+}
+
+/// Keeps the job object of the detached process with the given PID open,
+/// until the process is ended with [`terminate_detached`]
+pub(crate) fn track_detached(pid: u32, job: JobObject) {
+    DETACHED_JOBS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .insert(pid, job);
+}
+
+/// Ends the detached process with the given PID, and all processes it
+/// started, using the job object it was assigned to
+pub(crate) fn terminate_detached(pid: u32) -> Result<()> {
+    DETACHED_JOBS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .remove(&pid)
+        .ok_or_else(|| anyhow!("no job object for detached process {pid}"))?
+        .terminate()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use subprocess::ExitStatus;
+    use subprocess::Popen;
+    use subprocess::PopenConfig;
+
+    use super::JobObject;
+    use super::TERMINATED_EXIT_CODE;
+
+    #[test]
+    fn test_terminate_ends_assigned_process() {
+        let mut process = Popen::create(
+            &["cmd", "/C", "ping -n 30 127.0.0.1 > NUL"],
+            PopenConfig::default(),
+        )
+        .expect("start process");
+        let job = JobObject::new(false).expect("create job object");
+        job.assign(process.pid().expect("process is running"))
+            .expect("assign process");
+        job.terminate().expect("terminate job object");
+        assert_eq!(
+            Some(ExitStatus::Exited(TERMINATED_EXIT_CODE)),
+            process
+                .wait_timeout(Duration::from_secs(5))
+                .expect("wait for process")
+        );
+    }
+}
@@ -13,22 +13,39 @@
 //! The execution happens always in batches of all TestCases within a file, as
 //! described in the [`crate::executors::executor::Executor`] trait.
 //!
-//! Currently there are two implementations available:
+//! Currently there are three implementations available:
 //! - [`crate::executors::bash_script_executor::BashScriptExecutor`]
-//! - [`crate::executors::stateful_executor::StatefulExecutor`]
+//! - [`crate::executors::stateful_executor::StatefulExecutor`], which runs
+//!   each testcase with a [`crate::executors::bash_runner::BashRunner`] or,
+//!   for PowerShell, a [`crate::executors::powershell_runner::PowerShellRunner`]
+//!   and, for `cmd.exe`, a [`crate::executors::cmd_runner::CmdRunner`]. Testcases
+//!   that configure an interpreter are run with an
+//!   [`crate::executors::interpreter_runner::InterpreterRunner`]
+//! - [`crate::executors::docker_executor::DockerExecutor`], which runs the
+//!   testcases in a container
 
 use std::path::Path;
 
 pub mod bash_runner;
 pub mod bash_script_executor;
+pub mod cmd_runner;
 pub mod context;
+pub mod detached;
+pub mod docker_executor;
 pub mod error;
 pub mod execution;
 pub mod executor;
+pub mod interpreter_runner;
+#[cfg(windows)]
+pub(crate) mod job_object;
+pub mod powershell_runner;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub mod pty_runner;
 pub mod runner;
 pub mod stateful_executor;
 pub mod subprocess_runner;
 pub mod util;
+mod wait;
 
 lazy_static! {
     static ref SHELL_PATH: String = if let Ok(value) = std::env::var("SCRUT_DEFAULT_SHELL") {
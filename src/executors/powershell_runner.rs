@@ -0,0 +1,312 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use tracing::trace;
+
+use super::context::Context as ExecutionContext;
+use super::runner::Runner;
+use super::stateful_executor::StatefulExecutorRunnerGenerator;
+use super::subprocess_runner::SubprocessRunner;
+use super::util::read_stdin_input;
+use super::util::working_directory;
+use super::util::write_script_file;
+use crate::output::Output;
+use crate::testcase::TestCase;
+
+/// Automatic variables, that PowerShell sets depending on the context, and
+/// that are hence not persisted in between executions, in addition to all
+/// variables that exist before the state is loaded
+pub const POWERSHELL_EXCLUDED_VARIABLES: &[&str] = &[
+    "_",
+    "args",
+    "foreach",
+    "input",
+    "LASTEXITCODE",
+    "Matches",
+    "PSItem",
+    "StackTrace",
+    "switch",
+    "this",
+];
+
+/// The prompt, that PowerShell documents can use instead of `$` to start the
+/// shell expression of a testcase
+pub const POWERSHELL_PROMPT: &str = "PS>";
+
+/// Names of the executables of PowerShell (`pwsh`) and Windows PowerShell
+const POWERSHELL_NAMES: &[&str] = &["pwsh", "powershell"];
+
+/// Byte order mark, without which Windows PowerShell reads scripts in the
+/// legacy encoding of the system instead of UTF-8
+const UTF8_BOM: &str = "\u{feff}";
+
+const POWERSHELL_TEMPLATE: &str = include_str!("powershell_runner.template");
+
+/// Whether the shell is PowerShell, as told by the name of its executable
+/// (e.g. `pwsh`, `/usr/bin/pwsh` or `C:\...\powershell.exe`)
+pub fn is_powershell(shell: &Path) -> bool {
+    shell
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+        .is_some_and(|stem| POWERSHELL_NAMES.contains(&stem.as_str()))
+}
+
+/// A [`Runner`], that runs the shell expressions of testcases with PowerShell
+/// (`pwsh` or Windows `powershell`). Like the [`super::bash_runner::BashRunner`]
+/// it persists the state of each execution (environment and user variables,
+/// functions and the current location) in the state directory and loads it in
+/// the subsequent execution.
+///
+/// The rendered script is run from a file with `-File`, so that the standard
+/// input of PowerShell is available to provide the configured
+/// [`crate::config::TestCaseConfig::stdin`] to native commands. The exit code
+/// follows POSIX shells: it is the `$LASTEXITCODE` of a failing native
+/// command, or `1` if a cmdlet failed, unless the script ends with `exit`.
+///
+/// Underneath the [`SubprocessRunner`] is used, so timeout constraints are
+/// fully supported. Running attached to a terminal (`tty`) is not.
+#[derive(Clone)]
+pub struct PowerShellRunner {
+    pub shell: PathBuf,
+    pub state_directory: PathBuf,
+}
+
+impl PowerShellRunner {
+    pub fn new(shell: &Path, state_directory: &Path) -> Self {
+        Self {
+            shell: shell.to_owned(),
+            state_directory: state_directory.to_owned(),
+        }
+    }
+
+    pub fn stateful_generator(shell: &Path) -> StatefulExecutorRunnerGenerator {
+        let shell = shell.to_owned();
+        Box::new(move |state_directory: &Path| -> Box<dyn Runner> {
+            Box::new(Self::new(&shell, state_directory)) as Box<dyn Runner>
+        })
+    }
+}
+
+impl Runner for PowerShellRunner {
+    fn run(&self, name: &str, testcase: &TestCase, context: &ExecutionContext) -> Result<Output> {
+        if testcase.config.tty.unwrap_or(false) {
+            bail!("tty is not supported with PowerShell");
+        }
+        if !testcase.interaction.is_empty() {
+            bail!("interaction requires tty");
+        }
+        let detached = testcase.config.detached.unwrap_or(false);
+
+        // render the PowerShell script
+        let script = POWERSHELL_TEMPLATE
+            .replace(
+                "{state_directory}",
+                &quote_powershell(&self.state_directory.to_string_lossy()),
+            )
+            .replace(
+                "{before_each}",
+                &render_powershell_hook(&context.config.before_each),
+            )
+            .replace(
+                "{after_each}",
+                &render_powershell_hook(&context.config.after_each),
+            )
+            .replace(
+                "{environment}",
+                &render_powershell_environment(&testcase.config.environment),
+            )
+            .replace(
+                "{change_directory}",
+                &render_powershell_change_directory(&working_directory(testcase, context)?),
+            )
+            .replace("{shell_expression}", &testcase.shell_expression)
+            .replace(
+                "{excluded_variables}",
+                &POWERSHELL_EXCLUDED_VARIABLES
+                    .iter()
+                    .map(|name| quote_powershell(name))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )
+            .replace("{persist_state}", if detached { "$false" } else { "$true" });
+        trace!(name, "compiled script {}", &script);
+
+        let script_path = write_script_file(
+            &context.temp_directory,
+            ".ps1",
+            format!("{UTF8_BOM}{script}").as_bytes(),
+        )?;
+
+        // the standard input of PowerShell is provided to the native commands
+        // that the shell expression runs
+        let stdin = read_stdin_input(testcase, context)?;
+        let mut testcase = testcase.clone();
+        testcase.shell_expression = stdin;
+
+        let runner = SubprocessRunner::new(self.shell.clone()).with_args(&[
+            "-NoLogo".into(),
+            "-NoProfile".into(),
+            "-NonInteractive".into(),
+            "-ExecutionPolicy".into(),
+            "Bypass".into(),
+            "-File".into(),
+            script_path.to_string_lossy().into(),
+        ]);
+        let output = runner.run(name, &testcase, context);
+
+        // detached executions read the script after this run ended, the file
+        // is removed together with the temporary directory
+        if detached {
+            script_path.keep().context("keep script file")?;
+        }
+        output
+    }
+}
+
+/// Quotes the value as verbatim (single quoted) PowerShell string, in which
+/// single quotes, including the typographic ones that PowerShell treats alike,
+/// are escaped by doubling them
+pub fn quote_powershell(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('\'');
+    for c in value.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            quoted.push(c);
+        }
+        quoted.push(c);
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// Renders a `before_each` / `after_each` snippet as a script block, that runs
+/// in the scope of the script and discards all output, or nothing if no
+/// snippet is provided
+fn render_powershell_hook(hook: &Option<String>) -> String {
+    match hook {
+        Some(hook) => format!(". {{\n{hook}\n}} *> $null"),
+        None => String::new(),
+    }
+}
+
+/// Renders the statements, that change into the working directory and
+/// remember the previous location to return to, or nothing
+fn render_powershell_change_directory(directory: &Option<PathBuf>) -> String {
+    match directory {
+        Some(directory) => format!(
+            "$__ScrutPreviousLocation = (Get-Location).Path\nSet-Location -LiteralPath {} -ErrorAction Stop",
+            quote_powershell(&directory.to_string_lossy())
+        ),
+        None => String::new(),
+    }
+}
+
+/// Renders the environment variables as one `Set-Item` statement each, which
+/// supports names that cannot be written as `$env:NAME`
+fn render_powershell_environment(environment: &BTreeMap<String, String>) -> String {
+    environment
+        .iter()
+        .map(|(key, value)| {
+            format!(
+                "Set-Item -LiteralPath {} -Value {}",
+                quote_powershell(&format!("env:{key}")),
+                quote_powershell(value)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::Path;
+
+    use super::PowerShellRunner;
+    use super::is_powershell;
+    use super::quote_powershell;
+    use super::render_powershell_environment;
+    use crate::executors::context::Context as ExecutionContext;
+    use crate::executors::executor::Executor;
+    use crate::executors::stateful_executor::StatefulExecutor;
+    use crate::output::Output;
+    use crate::testcase::TestCase;
+
+    #[test]
+    fn test_is_powershell() {
+        for (shell, expected) in [
+            ("pwsh", true),
+            ("/usr/bin/pwsh", true),
+            (
+                "C:/Windows/System32/WindowsPowerShell/v1.0/powershell.exe",
+                true,
+            ),
+            ("PowerShell.EXE", true),
+            ("/bin/bash", false),
+            ("pwsh-wrapper", false),
+        ] {
+            assert_eq!(expected, is_powershell(Path::new(shell)), "{shell}");
+        }
+    }
+
+    #[test]
+    fn test_quote_powershell() {
+        assert_eq!("'plain'", quote_powershell("plain"));
+        assert_eq!(
+            "'$no ''`interpolation'''",
+            quote_powershell("$no '`interpolation'")
+        );
+        assert_eq!("'it\u{2019}\u{2019}s'", quote_powershell("it\u{2019}s"));
+    }
+
+    #[test]
+    fn test_render_powershell_environment() {
+        assert_eq!(
+            "Set-Item -LiteralPath 'env:FOO' -Value 'b''ar'\nSet-Item -LiteralPath 'env:with space' -Value ''",
+            render_powershell_environment(&BTreeMap::from([
+                ("FOO".into(), "b'ar".into()),
+                ("with space".into(), "".into()),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_execute_persists_state_and_exit_codes() {
+        let Ok(shell) = which::which("pwsh") else {
+            return;
+        };
+        let testcases = [
+            TestCase::from_expression("$greeting = 'Hello'; function Shout($s) { $s.ToUpper() }"),
+            TestCase::from_expression("Shout \"$greeting, $env:NAME\""),
+            TestCase::from_expression("Get-Item -LiteralPath 'does-not-exist' 2> $null"),
+            TestCase::from_expression("exit 3"),
+        ];
+        let mut testcases = testcases.to_vec();
+        testcases[1]
+            .config
+            .environment
+            .insert("NAME".into(), "World".into());
+        let context = ExecutionContext::new_for_test();
+        let outputs = StatefulExecutor::new(PowerShellRunner::stateful_generator(&shell))
+            .execute_all(&testcases.iter().collect::<Vec<_>>(), &context)
+            .expect("execute without error");
+        let expected: Vec<Output> = vec![
+            ("", "", Some(0)).into(),
+            ("HELLO, WORLD\n", "", Some(0)).into(),
+            ("", "", Some(1)).into(),
+            ("", "", Some(3)).into(),
+        ];
+        assert_eq!(expected, outputs);
+    }
+}
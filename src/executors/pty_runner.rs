@@ -0,0 +1,381 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use nix::pty::Winsize;
+use nix::pty::openpty;
+use nix::sys::signal::Signal;
+use nix::sys::signal::killpg;
+use nix::unistd::Pid;
+use nix::unistd::setsid;
+use regex::bytes::Regex;
+use tempfile::NamedTempFile;
+use tracing::debug;
+use tracing::debug_span;
+use tracing::trace;
+
+use super::DEFAULT_SHELL;
+use super::context::Context as ExecutionContext;
+use super::runner::Runner;
+use super::util::terminate_process_group;
+use super::util::wait_with_resource_usage;
+use crate::output::ExitStatus as OutputExitStatus;
+use crate::output::Output;
+use crate::testcase::InteractionStep;
+use crate::testcase::TestCase;
+
+/// Width of the pseudo-terminal, unless configured otherwise with
+/// [`crate::config::TestCaseConfig::terminal_size`]. Matches the `COLUMNS`
+/// that Scrut provides to all tests.
+pub const DEFAULT_TTY_COLUMNS: u16 = 80;
+
+/// Height of the pseudo-terminal, unless configured otherwise with
+/// [`crate::config::TestCaseConfig::terminal_size`]
+pub const DEFAULT_TTY_ROWS: u16 = 24;
+
+/// A runner that starts an interpreter (usually `bash`) in a sub-process that
+/// is attached to a pseudo-terminal, so that `isatty()` is true for STDIN,
+/// STDOUT and STDERR of the shell expression of a given
+/// [`crate::testcase::TestCase`].
+///
+/// As within a real terminal, STDOUT and STDERR are combined into STDOUT and
+/// line endings are written as CRLF (which are converted to LF, unless
+/// [`crate::config::TestCaseConfig::keep_crlf`] is set and
+/// [`crate::config::TestCaseConfig::normalize_crlf`] is not).
+///
+/// Constraining the max execution time is supported. Detached execution is not.
+/// Only available on Linux and macOS.
+#[derive(Clone)]
+pub struct PtyRunner(pub(super) PathBuf);
+
+impl PtyRunner {
+    pub fn new(p: PathBuf) -> Self {
+        Self(p)
+    }
+}
+
+impl Default for PtyRunner {
+    fn default() -> Self {
+        Self(DEFAULT_SHELL.to_owned())
+    }
+}
+
+impl Runner for PtyRunner {
+    fn run(&self, _name: &str, testcase: &TestCase, context: &ExecutionContext) -> Result<Output> {
+        if testcase.config.detached.unwrap_or(false) {
+            bail!("tty is not supported for detached execution");
+        }
+        let shell = &self.0;
+
+        // the expression is run from a file, because STDIN is the terminal
+        let mut script = NamedTempFile::with_prefix_in(".tty.", &context.temp_directory)
+            .context("create temporary script file")?;
+        script
+            .write_all(testcase.shell_expression.as_bytes())
+            .context("write script file")?;
+
+        let size = testcase.config.terminal_size;
+        let pty = openpty(
+            &Winsize {
+                ws_row: size.map_or(DEFAULT_TTY_ROWS, |s| s.rows),
+                ws_col: size.map_or(DEFAULT_TTY_COLUMNS, |s| s.columns),
+                ws_xpixel: 0,
+                ws_ypixel: 0,
+            },
+            None,
+        )
+        .context("open pseudo-terminal")?;
+
+        // apply environment variables (ensure SHELL is set)
+        let mut envs = testcase.config.environment.clone();
+        envs.insert("SHELL".into(), shell.to_string_lossy().to_string());
+
+        let mut command = Command::new(shell);
+        command
+            .arg(script.path())
+            .env_clear()
+            .envs(context.config.base_environment(&context.work_directory))
+            .envs(&envs)
+            .current_dir(&context.work_directory)
+            .stdin(pty.slave.try_clone().context("clone terminal")?)
+            .stdout(pty.slave.try_clone().context("clone terminal")?)
+            .stderr(pty.slave);
+
+        // SAFETY: only async-signal-safe functions are called between fork
+        // and exec, to make the terminal the controlling terminal of a new
+        // session (which also makes the shell the leader of a process group)
+        unsafe {
+            command.pre_exec(|| {
+                setsid()?;
+                if nix::libc::ioctl(0, nix::libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let pid = command.spawn().context("start process")?.id();
+
+        // the terminal is closed once all processes that are attached to it
+        // are done, so the command (which holds the other ends) must be gone
+        drop(command);
+
+        let span = debug_span!("process", pid = pid);
+        let _s = span.enter();
+        trace!(testcase = %&testcase, "running testcase in pseudo-terminal");
+
+        let master = File::from(pty.master);
+        let mut terminal = master.try_clone().context("clone terminal")?;
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let max_output_size = testcase.config.max_output_size.map(|size| size.0 as usize);
+        let reader = {
+            let buffer = buffer.clone();
+            let mut master = master;
+            thread::spawn(move || {
+                let mut chunk = [0u8; 4096];
+                // reading fails with EIO when the terminal is closed
+                while let Ok(size @ 1..) = master.read(&mut chunk) {
+                    let mut buffer = buffer.lock().expect("lock terminal output");
+                    buffer.extend_from_slice(&chunk[..size]);
+
+                    // stop reading output that exceeds the max output size
+                    if max_output_size.is_some_and(|max_output_size| buffer.len() > max_output_size)
+                    {
+                        break;
+                    }
+                }
+            })
+        };
+
+        let timeout = testcase.config.timeout;
+        if let Some(timeout) = timeout {
+            debug!(
+                "waiting for output (max {})",
+                humantime::format_duration(Duration::from_secs(timeout.as_secs()))
+            );
+        } else {
+            debug!("waiting for output (no timeout)");
+        }
+
+        let mut interaction = Interaction::new(&testcase.interaction)?;
+        let started = Instant::now();
+        let (exit_code, resource_usage) = loop {
+            // end the execution, if its output is too large to be captured
+            // entirely (see TestCase::validate)
+            let exceeded = max_output_size.is_some_and(|max_output_size| {
+                buffer.lock().expect("lock terminal output").len() > max_output_size
+            });
+            if exceeded {
+                debug!("output exceeds max output size, ending execution");
+                let _ = killpg(Pid::from_raw(pid as i32), Signal::SIGKILL);
+            }
+            interaction.advance(&buffer.lock().expect("lock terminal output"), &mut terminal);
+            if let Some((exit_code, usage)) =
+                wait_with_resource_usage(pid, exceeded).context("capture process exit")?
+            {
+                break (exit_code, Some(usage));
+            }
+            if let Some(timeout) = timeout {
+                if started.elapsed() >= timeout {
+                    terminate_process_group(
+                        pid,
+                        &testcase.config.get_timeout_signal(),
+                        testcase.config.get_timeout_grace(),
+                    );
+                    break (OutputExitStatus::Timeout(timeout), None);
+                }
+            }
+            sleep(Duration::from_millis(10));
+        };
+
+        // background processes may keep the terminal open, so do not wait
+        // for all of them, only for the output that is still in transit
+        let grace_until = Instant::now() + Duration::from_millis(100);
+        while !reader.is_finished() && Instant::now() < grace_until {
+            sleep(Duration::from_millis(5));
+        }
+        let output = std::mem::take(&mut *buffer.lock().expect("lock terminal output"));
+        if let Some(step) = interaction.pending() {
+            debug!("process ended before interaction step `{}`", step);
+        }
+
+        Ok(Output {
+            stderr: vec![].into(),
+            stdout: testcase.render_owned_output(output)?.into(),
+            exit_code,
+            detached_process: None,
+            resource_usage,
+            duration: None,
+            attempts: None,
+            file_digests: BTreeMap::new(),
+            produced_files: BTreeMap::new(),
+            work_directory: None,
+            artifacts: vec![],
+        })
+    }
+}
+
+/// Drives an interactive session along the [`InteractionStep`]s of a
+/// testcase: input is sent as soon as all preceding expected patterns were
+/// found in the output of the terminal, each in the output that follows the
+/// match of the previous.
+struct Interaction<'a> {
+    steps: &'a [InteractionStep],
+    patterns: Vec<Option<Regex>>,
+    next: usize,
+    offset: usize,
+}
+
+impl<'a> Interaction<'a> {
+    fn new(steps: &'a [InteractionStep]) -> Result<Self> {
+        let patterns = steps
+            .iter()
+            .map(|step| match step {
+                InteractionStep::Expect(pattern) => Regex::new(pattern)
+                    .map(Some)
+                    .with_context(|| format!("compile expect pattern `{}`", pattern)),
+                InteractionStep::Send(_) => Ok(None),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            steps,
+            patterns,
+            next: 0,
+            offset: 0,
+        })
+    }
+
+    /// Process all steps that can be processed with the output that was
+    /// received so far
+    fn advance(&mut self, output: &[u8], terminal: &mut File) {
+        while let Some(step) = self.steps.get(self.next) {
+            match step {
+                InteractionStep::Expect(_) => {
+                    let pattern = self.patterns[self.next]
+                        .as_ref()
+                        .expect("expect steps have compiled patterns");
+                    let Some(found) = pattern.find(&output[self.offset..]) else {
+                        return;
+                    };
+                    self.offset += found.end();
+                }
+                InteractionStep::Send(text) => {
+                    // Enter is sent as carriage return, as by a keyboard
+                    if let Err(err) = terminal
+                        .write_all(format!("{}\r", text).as_bytes())
+                        .and_then(|_| terminal.flush())
+                    {
+                        debug!("failed to send `{}` to terminal: {}", text, err);
+                        return;
+                    }
+                }
+            }
+            trace!("processed interaction step `{}`", step);
+            self.next += 1;
+        }
+    }
+
+    /// The next step that was not yet processed, if any
+    fn pending(&self) -> Option<&InteractionStep> {
+        self.steps.get(self.next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::PtyRunner;
+    use super::Runner;
+    use crate::config::TerminalSize;
+    use crate::executors::context::Context as ExecutionContext;
+    use crate::output::ExitStatus;
+    use crate::output::Output;
+    use crate::testcase::InteractionStep;
+    use crate::testcase::TestCase;
+
+    #[test]
+    fn test_execute_is_attached_to_terminal() {
+        let output = PtyRunner::default()
+            .run(
+                "name",
+                &TestCase::from_expression(
+                    "[ -t 0 ] && [ -t 1 ] && [ -t 2 ] && echo TTY && ( 1>&2 echo ERR ) && exit 3",
+                ),
+                &ExecutionContext::new_for_test(),
+            )
+            .expect("execute without error");
+        let expect: Output = ("TTY\nERR\n", "", Some(3)).into();
+        assert_eq!(expect, output);
+    }
+
+    #[test]
+    fn test_execute_uses_terminal_size() {
+        let mut testcase = TestCase::from_expression("stty size");
+        testcase.config.terminal_size = Some(TerminalSize {
+            columns: 120,
+            rows: 40,
+        });
+        let output = PtyRunner::default()
+            .run("name", &testcase, &ExecutionContext::new_for_test())
+            .expect("execute without error");
+        let expect: Output = ("40 120\n", "").into();
+        assert_eq!(expect, output);
+    }
+
+    #[test]
+    fn test_execute_with_interaction() {
+        let mut testcase = TestCase::from_expression(
+            "read -p 'Name? ' name && read -p 'Age? ' age && echo \"$name is $age\"",
+        );
+        testcase.interaction = vec![
+            InteractionStep::Expect("Name\\? $".into()),
+            InteractionStep::Send("Alice".into()),
+            InteractionStep::Expect("Age\\? $".into()),
+            InteractionStep::Send("42".into()),
+        ];
+        let output = PtyRunner::default()
+            .run("name", &testcase, &ExecutionContext::new_for_test())
+            .expect("execute without error");
+        let expect: Output = ("Name? Alice\nAge? 42\nAlice is 42\n", "").into();
+        assert_eq!(expect, output);
+    }
+
+    #[test]
+    fn test_execute_with_timeout() {
+        let output = PtyRunner::default()
+            .run(
+                "name",
+                &TestCase::from_expression_timed(
+                    "echo OK1 && sleep 5 && echo OK2",
+                    Some(Duration::from_millis(300)),
+                ),
+                &ExecutionContext::new_for_test(),
+            )
+            .expect("execute without error");
+        assert_eq!(
+            ExitStatus::Timeout(Duration::from_millis(300)),
+            output.exit_code
+        );
+        assert_eq!(output.stdout.to_bytes(), b"OK1\n".to_vec());
+    }
+}
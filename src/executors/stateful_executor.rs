@@ -5,27 +5,39 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::collections::BTreeMap;
 use std::ops::Add;
 use std::path::Path;
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
 use std::thread::sleep;
 use std::time::Duration;
 use std::time::Instant;
 
 use anyhow::Context;
+use anyhow::anyhow;
 use tempfile::TempDir;
 use tracing::debug;
 use tracing::trace;
 use tracing::trace_span;
 
 use super::context::Context as ExecutionContext;
+use super::context::ProgressNotice;
 use super::error::ExecutionError;
 use super::executor::DEFAULT_TOTAL_TIMEOUT;
 use super::executor::Executor;
 use super::executor::Result;
+use super::interpreter_runner::InterpreterRunner;
 use super::runner::Runner;
+use super::wait::wait_until_ready;
+use crate::config::Shell;
+use crate::config::WorkDirectoryScope;
+use crate::config::WorkingDirectory;
 use crate::executors::error::ExecutionTimeout;
 use crate::output::ExitStatus;
 use crate::output::Output;
+use crate::rules::variables::Variables;
 use crate::testcase::TestCase;
 
 /// A generator that creates a new instance of a [`super::runner::Runner`] that is provided with a
@@ -42,6 +54,10 @@ pub type StatefulExecutorRunnerGenerator = Box<dyn Fn(&Path) -> Box<dyn Runner>>
 /// The executors supports both timeouts per executions and timeouts over all executions.
 pub struct StatefulExecutor(StatefulExecutorRunnerGenerator);
 
+/// Time to wait in between the attempts of a testcase that is retried until
+/// its output matches (see [`crate::config::TestCaseConfig::retry`])
+const RETRY_INTERVAL: Duration = Duration::from_millis(200);
+
 /// A dataset to differentiate between occurance of global and per-execution timeout
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 struct Timeout {
@@ -55,6 +71,70 @@ impl StatefulExecutor {
     }
 }
 
+/// Runs the testcase and notifies the notifier of the context, if it is still
+/// running after the percentage of its timeout that is configured in
+/// [`crate::config::DocumentConfig::timeout_warning`]
+fn run_with_timeout_warning(
+    runner: &dyn Runner,
+    name: &str,
+    testcase: &TestCase,
+    context: &ExecutionContext,
+) -> anyhow::Result<Output> {
+    let warn_after = match (context.config.timeout_warning, testcase.config.timeout) {
+        (Some(percent @ 1..=99), Some(timeout))
+            if context.notifier.is_enabled() && !testcase.config.detached.unwrap_or(false) =>
+        {
+            Some((timeout.mul_f64(percent as f64 / 100.0), timeout))
+        }
+        _ => None,
+    };
+    let Some((warn_after, timeout)) = warn_after else {
+        return runner.run(name, testcase, context);
+    };
+
+    let notifier = &context.notifier;
+    let notice = ProgressNotice::TimeoutApproaching {
+        title: testcase.title.clone(),
+        line_number: testcase.line_number,
+        elapsed: warn_after,
+        timeout,
+    };
+    thread::scope(|scope| {
+        let (finished, running) = mpsc::channel::<()>();
+        scope.spawn(move || {
+            if running.recv_timeout(warn_after) == Err(RecvTimeoutError::Timeout) {
+                notifier.notify(&notice);
+            }
+        });
+        let result = runner.run(name, testcase, context);
+        drop(finished);
+        result
+    })
+}
+
+/// Returns the working directory of a testcase, that runs isolated from the
+/// other testcases, in a new directory within the work directory. Configured
+/// paths relative to the work directory are resolved against the new
+/// directory instead.
+fn isolated_working_directory(
+    index: usize,
+    cwd: &Option<WorkingDirectory>,
+    context: &ExecutionContext,
+) -> anyhow::Result<WorkingDirectory> {
+    if let Some(document @ WorkingDirectory::Document { .. }) = cwd {
+        return Ok(document.clone());
+    }
+    let directory = tempfile::Builder::new()
+        .prefix(&format!("testcase-{}.", index + 1))
+        .tempdir_in(&context.work_directory)
+        .context("create work directory of testcase")?
+        .keep();
+    Ok(WorkingDirectory::Work(match cwd {
+        Some(WorkingDirectory::Work(path)) => directory.join(path),
+        _ => directory,
+    }))
+}
+
 impl Executor for StatefulExecutor {
     /// Run all Executions in given order. Timeout over all Executions is supported. Timeout per
     /// Execution is not.
@@ -83,13 +163,62 @@ impl Executor for StatefulExecutor {
 
         // iterate all executions and run them in a bash process, then run
         // the next execution using the state of the previous
-        let mut outputs = vec![];
+        let (mut outputs, mut variables) = (vec![], Variables::new());
+        let mut persisted_environment = BTreeMap::new();
         for (index, testcase) in testcases.iter().enumerate() {
             let name = format!("exec{}", index + 1);
             let mut testcase = (*testcase).clone();
 
             // apply document-wide testcase defaults
-            testcase.config = testcase.config.with_defaults_from(&context.config.defaults);
+            testcase.config = testcase
+                .config
+                .with_defaults_from(&context.config.testcase_defaults());
+
+            // testcases of documents that isolate them run in a fresh
+            // directory of their own, within the work directory
+            if context.config.get_workdir().scope == WorkDirectoryScope::PerTestcase {
+                testcase.config.cwd = Some(
+                    isolated_working_directory(index, &testcase.config.cwd, context)
+                        .map_err(|err| ExecutionError::failed(index, err))?,
+                );
+            }
+
+            // testcases that configure an interpreter, or all testcases of a
+            // document that does, run without the state of the shell
+            let interpreter = match (&testcase.config.shell, &context.config.shell) {
+                (Some(Shell::Path(path)), _) => {
+                    return Err(ExecutionError::failed(
+                        index,
+                        anyhow!(
+                            "shell `{}` of a testcase must be an interpreter with arguments",
+                            path.display()
+                        ),
+                    ));
+                }
+                (Some(Shell::Interpreter(command)), _)
+                | (None, Some(Shell::Interpreter(command))) => Some(command),
+                _ => None,
+            };
+            let stateless = interpreter.is_some();
+            let runner: Box<dyn Runner> = match interpreter {
+                Some(command) => Box::new(InterpreterRunner::new(command)),
+                None => runner_gen(state_directory.path()),
+            };
+
+            // resolve references to variables of the parent environment, then
+            // pass on only variables that changed: those configured with the
+            // same value as for the previous execution are already part of the
+            // persisted state, which also keeps changes the shell made to them.
+            // Interpreters have no such state, so they are passed all of them
+            let environment = testcase.config.interpolated_environment();
+            testcase.config.environment = environment
+                .iter()
+                .filter(|(key, value)| stateless || persisted_environment.get(*key) != Some(*value))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            if !stateless && !testcase.config.detached.unwrap_or(false) {
+                persisted_environment = environment.clone();
+            }
 
             // timeout is whatever the lowest provided value of:
             // - global (over all executions) timeout
@@ -115,11 +244,7 @@ impl Executor for StatefulExecutor {
             // waiting on previous execution
             if let Some(ref wait) = testcase.config.wait {
                 debug!("waiting {}", wait);
-                if let Some(ref path) = wait.path {
-                    wait_until_path_or_time(&context.temp_directory.join(path), wait.timeout)
-                } else {
-                    sleep(wait.timeout);
-                }
+                wait_until_ready(wait, &context.temp_directory);
             }
 
             // set timeout and identifying environment variable
@@ -134,21 +259,71 @@ impl Executor for StatefulExecutor {
             );
 
             // run the execution, using the shared state directory
-            let context = context.to_owned();
-
             trace!("effective testcase configuration: {}", &testcase.config);
-            let mut output = runner_gen(state_directory.path())
-                .run(&name, &testcase, context)
-                .map_err(|err| ExecutionError::failed(index, err))?;
-            trace!("{output:?}");
+            context.notifier.notify(&ProgressNotice::TestCaseStarted {
+                title: testcase.title.clone(),
+                line_number: testcase.line_number,
+            });
+            let run = || -> Result<Output> {
+                let started = Instant::now();
+                let mut output =
+                    run_with_timeout_warning(runner.as_ref(), &name, &testcase, context)
+                        .map_err(|err| ExecutionError::failed(index, err))?;
+                output.duration = Some(started.elapsed());
+                output.file_digests = testcase.produced_file_digests(&context.work_directory);
+                output.produced_files = testcase.produced_files(&context.work_directory);
+                trace!("{output:?}");
+
+                // hide secrets and normalize before the output is validated or rendered
+                testcase
+                    .redact_output(output, &environment)
+                    .map(|output| {
+                        testcase.normalize_paths_in_output(output, &context.work_directory)
+                    })
+                    .map(|output| testcase.anonymize_user_in_output(output, &environment))
+                    .and_then(|output| context.config.normalize_output(output))
+                    .map_err(|err| ExecutionError::failed(index, err))
+            };
+
+            // re-execute until the output matches, if the testcase is retried
+            let skip_document_code = testcase.config.get_skip_document_code();
+            let retry_until = testcase
+                .config
+                .retry
+                .filter(|_| !testcase.config.detached.unwrap_or(false))
+                .map(|retry| {
+                    let until = Instant::now().add(retry);
+                    timeout_at.map_or(until, |at| until.min(at))
+                });
+            let mut attempts = 1;
+            let mut output = run()?;
+            if let Some(until) = retry_until {
+                while matches!(
+                    output.exit_code,
+                    ExitStatus::Code(_) | ExitStatus::Signal(_)
+                ) && output.exit_code != ExitStatus::Code(skip_document_code)
+                    && testcase
+                        .validate_with_variables(&output, &mut variables.clone())
+                        .is_err()
+                {
+                    let now = Instant::now();
+                    if now >= until {
+                        break;
+                    }
+                    debug!("retrying after attempt {attempts}");
+                    sleep(RETRY_INTERVAL.min(until - now));
+                    attempts += 1;
+                    output = run()?;
+                }
+                output.attempts = Some(attempts);
+            }
 
             // handle exit code
-            let skip_document_code = testcase.config.get_skip_document_code();
             match output.exit_code {
-                // having an actual numeric exit code ..
-                ExitStatus::Code(code) => {
+                // having an actual numeric exit code, or a terminating signal ..
+                ExitStatus::Code(_) | ExitStatus::Signal(_) => {
                     // .. ends collecting if user signals to skip
-                    if code == skip_document_code {
+                    if output.exit_code == ExitStatus::Code(skip_document_code) {
                         return Err(ExecutionError::Skipped(index));
                     }
 
@@ -156,10 +331,12 @@ impl Executor for StatefulExecutor {
                     outputs.push(output);
 
                     // check if fail_fast is enabled and validation fails
-                    if testcase.config.get_fail_fast() {
-                        if testcase.validate(outputs.last().unwrap()).is_err() {
-                            return Err(ExecutionError::Failed(index, outputs));
-                        }
+                    if testcase.config.get_fail_fast()
+                        && testcase
+                            .validate_with_variables(outputs.last().unwrap(), &mut variables)
+                            .is_err()
+                    {
+                        return Err(ExecutionError::Failed(index, outputs));
                     }
                 }
 
@@ -211,32 +388,36 @@ impl Executor for StatefulExecutor {
     }
 }
 
-fn wait_until_path_or_time(path: &Path, timeout: Duration) {
-    let end = Instant::now().add(timeout);
-    while end > Instant::now() {
-        if path.exists() {
-            return;
-        }
-        sleep(Duration::from_millis(50));
-    }
-}
-
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use std::sync::Mutex;
     use std::time::Duration;
 
     use regex::Regex;
 
     use super::StatefulExecutor;
+    use crate::config::DocumentConfig;
+    use crate::config::Shell;
+    use crate::config::TestCaseConfig;
+    use crate::config::WorkDirectoryConfig;
+    use crate::config::WorkDirectoryScope;
+    use crate::config::WorkingDirectory;
     use crate::executors::DEFAULT_SHELL;
     use crate::executors::bash_runner::BashRunner;
+    use crate::executors::context::Context as ExecutionContext;
+    use crate::executors::context::ProgressNotice;
+    use crate::executors::context::ProgressNotifier;
     use crate::executors::error::ExecutionError;
     use crate::executors::error::ExecutionTimeout;
+    use crate::executors::executor::Executor;
     use crate::executors::executor::tests::combined_output_test_suite;
     use crate::executors::executor::tests::run_executor_tests;
     use crate::executors::executor::tests::standard_output_test_suite;
     use crate::output::ExitStatus;
     use crate::output::Output;
+    use crate::test_expectation;
     use crate::testcase::TestCase;
 
     #[test]
@@ -525,4 +706,304 @@ mod tests {
             tests,
         );
     }
+
+    #[test]
+    fn test_executes_hooks_around_each_testcase() {
+        let context = ExecutionContext::new_for_test_with_config(DocumentConfig {
+            before_each: Some("echo hidden\nCOUNT=$((COUNT + 1))".into()),
+            after_each: Some("echo hidden >&2\nLAST=done\nfalse".into()),
+            ..Default::default()
+        });
+        let testcases = [
+            TestCase::from_expression("echo \"$COUNT-${LAST:-none}\" && ( exit 3 )"),
+            TestCase::from_expression("echo \"$COUNT-${LAST:-none}\""),
+        ];
+        let outputs = StatefulExecutor(BashRunner::stateful_generator(*DEFAULT_SHELL))
+            .execute_all(&testcases.iter().collect::<Vec<_>>(), &context)
+            .expect("execute with hooks");
+        assert_eq!(
+            vec![
+                Output::from(("1-none\n", "", Some(3))),
+                Output::from(("2-done\n", "", Some(0))),
+            ],
+            outputs
+        );
+    }
+
+    #[test]
+    fn test_executes_with_document_and_interpolated_environment() {
+        let context = ExecutionContext::new_for_test_with_config(DocumentConfig {
+            environment: BTreeMap::from([
+                ("SCRUT_DOCUMENT_VAR".into(), "document".into()),
+                ("SCRUT_SHARED_VAR".into(), "document".into()),
+            ]),
+            ..Default::default()
+        });
+        let testcases = [TestCase {
+            shell_expression: "echo \"$SCRUT_DOCUMENT_VAR $SCRUT_SHARED_VAR $SCRUT_PATH_VAR\""
+                .into(),
+            config: TestCaseConfig {
+                environment: BTreeMap::from([
+                    ("SCRUT_SHARED_VAR".into(), "testcase".into()),
+                    ("SCRUT_PATH_VAR".into(), "${PATH}".into()),
+                ]),
+                ..Default::default()
+            },
+            ..Default::default()
+        }];
+        let outputs = StatefulExecutor(BashRunner::stateful_generator(*DEFAULT_SHELL))
+            .execute_all(&testcases.iter().collect::<Vec<_>>(), &context)
+            .expect("execute with environment");
+        assert_eq!(
+            vec![Output::from((
+                format!(
+                    "document testcase {}\n",
+                    std::env::var("PATH").expect("PATH is set")
+                )
+                .as_str(),
+                "",
+                Some(0)
+            ))],
+            outputs
+        );
+    }
+
+    #[test]
+    fn test_runs_testcases_with_interpreter_without_state() {
+        let environment = BTreeMap::from([("SCRUT_CONFIGURED_VAR".into(), "configured".into())]);
+        let interpreter = Shell::Interpreter(vec!["bash".into(), "-c".into()]);
+        let testcases = [
+            TestCase {
+                shell_expression: "export SCRUT_SHELL_VAR=shell".into(),
+                config: TestCaseConfig {
+                    environment: environment.clone(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            TestCase {
+                shell_expression:
+                    "echo \"${SCRUT_SHELL_VAR:-unset} $SCRUT_CONFIGURED_VAR\"; exit 3".into(),
+                config: TestCaseConfig {
+                    environment,
+                    shell: Some(interpreter.clone()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            TestCase::from_expression("echo \"$SCRUT_SHELL_VAR\""),
+        ];
+        let outputs = StatefulExecutor(BashRunner::stateful_generator(*DEFAULT_SHELL))
+            .execute_all(
+                &testcases.iter().collect::<Vec<_>>(),
+                &ExecutionContext::new_for_test(),
+            )
+            .expect("execute with interpreter");
+        assert_eq!(
+            vec![
+                Output::from(("", "", Some(0))),
+                Output::from(("unset configured\n", "", Some(3))),
+                Output::from(("shell\n", "", Some(0))),
+            ],
+            outputs
+        );
+
+        let testcase = TestCase {
+            config: TestCaseConfig {
+                shell: Some("/bin/zsh".into()),
+                ..Default::default()
+            },
+            ..TestCase::from_expression("echo")
+        };
+        let result = StatefulExecutor(BashRunner::stateful_generator(*DEFAULT_SHELL))
+            .execute_all(&[&testcase], &ExecutionContext::new_for_test());
+        assert!(
+            matches!(
+                result,
+                Err(ExecutionError::FailedExecution { index: 0, .. })
+            ),
+            "shell of a testcase must be an interpreter"
+        );
+    }
+
+    #[test]
+    fn test_runs_testcases_in_working_directory() {
+        let in_subdirectory = |shell_expression: &str, shell: Option<Shell>| TestCase {
+            shell_expression: shell_expression.into(),
+            config: TestCaseConfig {
+                cwd: Some(WorkingDirectory::Work("sub".into())),
+                shell,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let testcases = [
+            TestCase::from_expression("mkdir sub"),
+            in_subdirectory(
+                "basename \"$PWD\"; touch here.txt; export SCRUT_IN_SUB=yes",
+                None,
+            ),
+            TestCase::from_expression("test -f sub/here.txt && echo \"$SCRUT_IN_SUB\""),
+            in_subdirectory(
+                "ls",
+                Some(Shell::Interpreter(vec!["bash".into(), "-c".into()])),
+            ),
+        ];
+        let outputs = StatefulExecutor(BashRunner::stateful_generator(*DEFAULT_SHELL))
+            .execute_all(
+                &testcases.iter().collect::<Vec<_>>(),
+                &ExecutionContext::new_for_test(),
+            )
+            .expect("execute in working directory");
+        assert_eq!(
+            vec![
+                Output::from(("", "", Some(0))),
+                Output::from(("sub\n", "", Some(0))),
+                Output::from(("yes\n", "", Some(0))),
+                Output::from(("here.txt\n", "", Some(0))),
+            ],
+            outputs
+        );
+
+        let result = StatefulExecutor(BashRunner::stateful_generator(*DEFAULT_SHELL)).execute_all(
+            &[&in_subdirectory("echo", None)],
+            &ExecutionContext::new_for_test(),
+        );
+        assert!(
+            matches!(
+                result,
+                Err(ExecutionError::FailedExecution { index: 0, .. })
+            ),
+            "working directory must exist"
+        );
+    }
+
+    #[test]
+    fn test_runs_testcases_isolated_in_directories_of_their_own() {
+        let testcases = [
+            TestCase::from_expression("touch created.txt; export SCRUT_SHARED=state"),
+            TestCase::from_expression(
+                "ls; echo \"$SCRUT_SHARED\"; basename \"$PWD\" | cut -d. -f1",
+            ),
+        ];
+        let context = ExecutionContext::new_for_test_with_config(DocumentConfig {
+            workdir: Some(WorkDirectoryConfig {
+                scope: WorkDirectoryScope::PerTestcase,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let outputs = StatefulExecutor(BashRunner::stateful_generator(*DEFAULT_SHELL))
+            .execute_all(&testcases.iter().collect::<Vec<_>>(), &context)
+            .expect("execute isolated");
+        assert_eq!(
+            vec![
+                Output::from(("", "", Some(0))),
+                Output::from(("state\ntestcase-2\n", "", Some(0))),
+            ],
+            outputs
+        );
+    }
+
+    #[test]
+    fn test_notifies_about_testcase_approaching_timeout() {
+        let notices = Arc::new(Mutex::new(vec![]));
+        let mut context = ExecutionContext::new_for_test_with_config(DocumentConfig {
+            timeout_warning: Some(20),
+            ..Default::default()
+        });
+        let received = notices.clone();
+        context.notifier = ProgressNotifier::new(move |notice| {
+            received.lock().expect("lock notices").push(notice.clone())
+        });
+        let testcases = [
+            TestCase {
+                title: "slow".into(),
+                shell_expression: "sleep 0.6".into(),
+                line_number: 3,
+                config: TestCaseConfig {
+                    timeout: Some(Duration::from_secs(2)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            TestCase {
+                title: "fast".into(),
+                shell_expression: "true".into(),
+                line_number: 7,
+                config: TestCaseConfig {
+                    timeout: Some(Duration::from_secs(10)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        ];
+        StatefulExecutor(BashRunner::stateful_generator(*DEFAULT_SHELL))
+            .execute_all(&testcases.iter().collect::<Vec<_>>(), &context)
+            .expect("execute with timeout warning");
+        assert_eq!(
+            vec![
+                ProgressNotice::TestCaseStarted {
+                    title: "slow".into(),
+                    line_number: 3,
+                },
+                ProgressNotice::TimeoutApproaching {
+                    title: "slow".into(),
+                    line_number: 3,
+                    elapsed: Duration::from_millis(400),
+                    timeout: Duration::from_secs(2),
+                },
+                ProgressNotice::TestCaseStarted {
+                    title: "fast".into(),
+                    line_number: 7,
+                },
+            ],
+            *notices.lock().expect("lock notices")
+        );
+    }
+
+    #[test]
+    fn test_retries_until_output_matches() {
+        let directory = tempfile::tempdir().expect("create temporary directory");
+        let counter = directory
+            .path()
+            .join("counter")
+            .to_string_lossy()
+            .to_string();
+        let context = ExecutionContext::new_for_test();
+        let testcases = [
+            TestCase {
+                shell_expression: format!(
+                    "n=$(( $(cat '{counter}' 2>/dev/null || echo 0) + 1 )); echo $n > '{counter}'; echo $n"
+                ),
+                expectations: vec![test_expectation!("equal", "3")],
+                config: TestCaseConfig {
+                    retry: Some(Duration::from_secs(10)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            TestCase {
+                shell_expression: "echo never".into(),
+                expectations: vec![test_expectation!("equal", "always")],
+                config: TestCaseConfig {
+                    retry: Some(Duration::from_millis(300)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        ];
+        let outputs = StatefulExecutor(BashRunner::stateful_generator(*DEFAULT_SHELL))
+            .execute_all(&testcases.iter().collect::<Vec<_>>(), &context)
+            .expect("execute with retries");
+        assert_eq!(
+            vec![Output::from(("3\n", "")), Output::from(("never\n", ""))],
+            outputs
+        );
+        assert_eq!(Some(3), outputs[0].attempts);
+        assert!(
+            outputs[1].attempts.is_some_and(|attempts| attempts > 1),
+            "retried until the time is up"
+        );
+    }
 }
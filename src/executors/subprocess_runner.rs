@@ -5,29 +5,47 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fs;
+use std::fs::File;
 use std::io::ErrorKind;
 use std::io::Seek;
 use std::io::Write;
+use std::path::Path;
 use std::path::PathBuf;
 use std::time::Duration;
 
 use anyhow::Context;
 use anyhow::Result;
-use subprocess::Exec;
 use subprocess::ExitStatus;
-use subprocess::NullFile;
+use subprocess::Popen;
+use subprocess::PopenConfig;
 use subprocess::Redirection;
 use tempfile::tempfile_in;
 use tracing::debug;
 use tracing::debug_span;
 use tracing::trace;
+#[cfg(windows)]
+use tracing::warn;
 
 use super::DEFAULT_SHELL;
 use super::context::Context as ExecutionContext;
+use super::detached::detached_log_paths;
+#[cfg(windows)]
+use super::job_object;
+#[cfg(windows)]
+use super::job_object::JobObject;
 use super::runner::Runner;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use super::util::terminate_process_group;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use super::util::wait_with_resource_usage;
 use crate::output::DetachedProcess;
 use crate::output::ExitStatus as OutputExitStatus;
 use crate::output::Output;
+use crate::output::ResourceUsage;
 use crate::testcase::TestCase;
 
 /// A runner that starts an interpreter (usually `bash`) in a sub-process and
@@ -36,77 +54,71 @@ use crate::testcase::TestCase;
 ///
 /// Constraining the max execution time is supported.
 #[derive(Clone)]
-pub struct SubprocessRunner(pub(super) PathBuf);
+pub struct SubprocessRunner(
+    pub(super) PathBuf,
+    pub(super) Vec<String>,
+    pub(super) Option<PathBuf>,
+);
 
 impl SubprocessRunner {
     pub fn new(p: PathBuf) -> Self {
-        Self(p)
+        Self(p, vec![], None)
+    }
+
+    /// Sets the arguments, that the interpreter is started with
+    pub fn with_args(mut self, args: &[String]) -> Self {
+        self.1 = args.to_vec();
+        self
+    }
+
+    /// Sets the directory, that the interpreter is started in, instead of
+    /// the work directory
+    pub fn with_current_directory(mut self, directory: Option<PathBuf>) -> Self {
+        self.2 = directory;
+        self
     }
 }
 
 impl Runner for SubprocessRunner {
     fn run(&self, _name: &str, testcase: &TestCase, context: &ExecutionContext) -> Result<Output> {
         let shell = &self.0;
+        let command = std::iter::once(shell.as_os_str())
+            .chain(self.1.iter().map(OsStr::new))
+            .collect::<Vec<_>>();
 
         // apply environment variables (ensure SHELL is set)
         let mut envs = testcase.config.environment.clone();
         envs.insert("SHELL".into(), shell.to_string_lossy().to_string());
 
-        let mut exec = Exec::cmd(shell)
-            .env_extend(&Vec::from_iter(envs.iter()))
-            .cwd(&context.work_directory);
-
         let input = &testcase.shell_expression as &str;
-        let is_detached = testcase.config.detached.unwrap_or(false);
-        if is_detached {
-            // Why is a temporary file created here? Because the subprocess crate closes the
-            // STDIN pipe when it goes out of scope, which will interrupt the detached child.
-            let mut tmp =
-                tempfile_in(&context.temp_directory).context("Create temporary STDIN file")?;
-            tmp.write(input.as_bytes()).context("write to STDIN file")?;
-            tmp.seek(std::io::SeekFrom::Start(0))
-                .context("reset STDIN file")?;
-            exec = exec
-                .stdout(NullFile)
-                .stderr(NullFile)
-                .stdin(Redirection::File(tmp));
-        } else {
-            exec = exec
-                .stdout(Redirection::Pipe)
-                .stderr(
-                    if testcase.config.output_stream
-                        == Some(crate::config::OutputStreamControl::Combined)
-                    {
-                        Redirection::Merge
-                    } else {
-                        Redirection::Pipe
-                    },
-                )
-                .stdin(Redirection::Pipe);
+        let directory = self.2.as_deref().unwrap_or(&context.work_directory);
+        if testcase.config.detached.unwrap_or(false) {
+            return start_detached(&command, &envs, directory, input, testcase, context);
         }
 
-        let mut process = exec.detached().popen().context("start process")?;
+        let mut process = Popen::create(
+            &command,
+            PopenConfig {
+                stdin: Redirection::Pipe,
+                stdout: Redirection::Pipe,
+                stderr: if testcase.config.output_stream
+                    == Some(crate::config::OutputStreamControl::Combined)
+                {
+                    Redirection::Merge
+                } else {
+                    Redirection::Pipe
+                },
+                ..popen_config(&envs, directory, context)
+            },
+        )
+        .context("start process")?;
         let span = debug_span!("process", pid = ?process.pid());
         let _s = span.enter();
         trace!(testcase = %&testcase, "running testcase in subprocess");
-
-        // when detaching, do not wait for the process to finish
-        if is_detached {
-            let detached_process =
-                match (process.pid(), testcase.config.detached_kill_signal.clone()) {
-                    (Some(pid), Some(signal)) => Some(DetachedProcess { pid, signal }),
-                    (_, _) => None,
-                };
-            debug!(
-                "detaching, not waiting for output, marking for kill = {}",
-                detached_process.is_some(),
-            );
-            return Ok(Output {
-                exit_code: OutputExitStatus::Detached,
-                detached_process,
-                ..Default::default()
-            });
-        }
+        // processes that outlive the shell are killed once the job is dropped,
+        // at the latest when the execution ends
+        #[cfg(windows)]
+        let job = assign_job_object(&process, true);
 
         // constraint max execution time?
         let mut comm = process.communicate_start(Some(input.as_bytes().to_vec()));
@@ -120,14 +132,30 @@ impl Runner for SubprocessRunner {
             debug!("waiting for output (no timeout)");
         }
 
+        // constraint max output size? read one byte more than allowed, to
+        // tell output that exceeds it apart from output that fills it up
+        let max_output_size = testcase.config.max_output_size;
+        if let Some(max_output_size) = max_output_size {
+            comm = comm.limit_size(max_output_size.0.saturating_add(1) as usize);
+        }
+
         // wait for the process to finish and handle the result
+        let mut resource_usage = None;
         let (stdout, stderr, exit_code) = match comm.read() {
             // successs! we are happy!
-            Ok((stdout, stderr)) => (
-                stdout,
-                stderr,
-                process.wait().context("capture process exit")?.into(),
-            ),
+            Ok((stdout, stderr)) => {
+                // .. unless the output is too large to be captured entirely,
+                //    then end the execution (see TestCase::validate)
+                if max_output_size.is_some_and(|max_output_size| {
+                    output_size(&stdout, &stderr) > max_output_size.0
+                }) {
+                    debug!("output exceeds max output size, ending execution");
+                    let _ = process.kill();
+                }
+                let (exit_code, usage) = wait_for_exit(&mut process)?;
+                resource_usage = usage;
+                (stdout, stderr, exit_code)
+            }
 
             // bummer, a sad thing happened
             Err(err) => {
@@ -137,6 +165,13 @@ impl Runner for SubprocessRunner {
                 // windows execution returns [`ErrorKind::BrokenPipe`] in case
                 // anything explicitly runs `exit <code>`
                 let exit = if cfg!(windows) {
+                    if kind == ErrorKind::TimedOut {
+                        #[cfg(windows)]
+                        if let Some(ref job) = job {
+                            let _ = job.terminate();
+                        }
+                        let _ = process.kill();
+                    }
                     let process_result = process.wait().unwrap_or(ExitStatus::Undetermined);
                     if kind == ErrorKind::TimedOut {
                         OutputExitStatus::Timeout(testcase.config.timeout.unwrap_or_default())
@@ -146,6 +181,15 @@ impl Runner for SubprocessRunner {
                         OutputExitStatus::Unknown
                     }
                 } else if kind == ErrorKind::TimedOut {
+                    #[cfg(any(target_os = "linux", target_os = "macos"))]
+                    if let Some(pid) = process.pid() {
+                        debug!("timed out, ending process group");
+                        terminate_process_group(
+                            pid,
+                            &testcase.config.get_timeout_signal(),
+                            testcase.config.get_timeout_grace(),
+                        );
+                    }
                     OutputExitStatus::Timeout(testcase.config.timeout.unwrap_or_default())
                 } else {
                     OutputExitStatus::Unknown
@@ -156,22 +200,166 @@ impl Runner for SubprocessRunner {
 
         Ok(Output {
             stderr: testcase
-                .render_output(&stderr.unwrap_or_default()[..])?
-                .to_vec()
+                .render_owned_output(stderr.unwrap_or_default())?
                 .into(),
             stdout: testcase
-                .render_output(&stdout.unwrap_or_default()[..])?
-                .to_vec()
+                .render_owned_output(stdout.unwrap_or_default())?
                 .into(),
             exit_code,
             detached_process: None,
+            resource_usage,
+            duration: None,
+            attempts: None,
+            file_digests: BTreeMap::new(),
+            produced_files: BTreeMap::new(),
+            work_directory: None,
+            artifacts: vec![],
         })
     }
 }
 
+/// Returns the configuration of a process, that runs in the given directory
+/// with the given environment variables (in addition to the base environment
+/// of the document, see [`crate::config::DocumentConfig::base_environment`]) and
+/// that is not waited for, when it is dropped. On unix the process runs in a
+/// process group of its own, so that all processes it starts can be ended
+/// together (see [`terminate_process_group`] and [`DetachedProcess::kill`]).
+fn popen_config(
+    envs: &BTreeMap<String, String>,
+    directory: &Path,
+    context: &ExecutionContext,
+) -> PopenConfig {
+    let mut env = context.config.base_environment(&context.work_directory);
+    env.extend(
+        envs.iter()
+            .map(|(key, value)| (OsString::from(key), OsString::from(value))),
+    );
+    PopenConfig {
+        detached: true,
+        env: Some(env),
+        cwd: Some(directory.as_os_str().to_owned()),
+        #[cfg(unix)]
+        setpgid: true,
+        ..Default::default()
+    }
+}
+
+/// Starts the shell expression of a detached testcase in a process group of
+/// its own, that is tracked in the context, and does not wait for it. Its
+/// STDOUT and STDERR are written into log files in the work directory (see
+/// [`detached_log_paths`]).
+fn start_detached(
+    command: &[&OsStr],
+    envs: &BTreeMap<String, String>,
+    directory: &Path,
+    input: &str,
+    testcase: &TestCase,
+    context: &ExecutionContext,
+) -> Result<Output> {
+    // Why is a temporary file created here? Because the subprocess crate closes the
+    // STDIN pipe when it goes out of scope, which will interrupt the detached child.
+    let mut stdin = tempfile_in(&context.temp_directory).context("Create temporary STDIN file")?;
+    stdin
+        .write_all(input.as_bytes())
+        .context("write to STDIN file")?;
+    stdin
+        .seek(std::io::SeekFrom::Start(0))
+        .context("reset STDIN file")?;
+
+    let (stdout_log, stderr_log) =
+        detached_log_paths(&context.work_directory, testcase.line_number);
+    if let Some(directory) = stdout_log.parent() {
+        fs::create_dir_all(directory).context("create directory of detached logs")?;
+    }
+    let stdout = File::create(&stdout_log).context("create STDOUT log of detached process")?;
+    let stderr =
+        if testcase.config.output_stream == Some(crate::config::OutputStreamControl::Combined) {
+            Redirection::Merge
+        } else {
+            Redirection::File(
+                File::create(&stderr_log).context("create STDERR log of detached process")?,
+            )
+        };
+
+    let process = Popen::create(
+        command,
+        PopenConfig {
+            stdin: Redirection::File(stdin),
+            stdout: Redirection::File(stdout),
+            stderr,
+            ..popen_config(envs, directory, context)
+        },
+    )
+    .context("start process")?;
+    let span = debug_span!("process", pid = ?process.pid());
+    let _s = span.enter();
+    trace!(testcase = %&testcase, "running testcase detached in subprocess");
+
+    let detached_process = match (process.pid(), testcase.config.detached_kill_signal.clone()) {
+        (Some(pid), Some(signal)) => Some(DetachedProcess { pid, signal }),
+        (_, _) => None,
+    };
+    if let Some(ref detached_process) = detached_process {
+        #[cfg(windows)]
+        if !detached_process.signal.is_off() {
+            if let Some(job) = assign_job_object(&process, true) {
+                job_object::track_detached(detached_process.pid, job);
+            }
+        }
+        context.detached_processes.track(detached_process.clone());
+    }
+    debug!(
+        stdout = %stdout_log.display(),
+        "detaching, not waiting for output, marking for kill = {}",
+        detached_process.is_some(),
+    );
+    Ok(Output {
+        exit_code: OutputExitStatus::Detached,
+        detached_process,
+        ..Default::default()
+    })
+}
+
+/// Returns a job object on Windows, that the process and all processes it
+/// starts are assigned to, so that they can be ended together. Processes that
+/// the process started before it was assigned do not belong to it.
+#[cfg(windows)]
+fn assign_job_object(process: &Popen, kill_on_close: bool) -> Option<JobObject> {
+    let pid = process.pid()?;
+    match JobObject::new(kill_on_close).and_then(|job| job.assign(pid).map(|_| job)) {
+        Ok(job) => Some(job),
+        Err(err) => {
+            warn!(pid, "failed to assign process to job object: {err:#}");
+            None
+        }
+    }
+}
+
+/// Returns the amount of bytes of the captured STDOUT and STDERR combined
+fn output_size(stdout: &Option<Vec<u8>>, stderr: &Option<Vec<u8>>) -> u64 {
+    [stdout, stderr]
+        .iter()
+        .map(|stream| stream.as_ref().map_or(0, Vec::len) as u64)
+        .sum()
+}
+
+/// Waits for the process to end and returns its exit status and, where the
+/// platform allows, the resources it consumed
+fn wait_for_exit(process: &mut Popen) -> Result<(OutputExitStatus, Option<ResourceUsage>)> {
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    if let Some(pid) = process.pid() {
+        if let Some((exit_code, usage)) =
+            wait_with_resource_usage(pid, true).context("capture process exit")?
+        {
+            return Ok((exit_code, Some(usage)));
+        }
+    }
+    Ok((process.wait().context("capture process exit")?.into(), None))
+}
+
 impl Default for SubprocessRunner {
     fn default() -> Self {
-        Self(DEFAULT_SHELL.to_owned())
+        Self::new(DEFAULT_SHELL.to_owned())
     }
 }
 
@@ -179,7 +367,7 @@ impl From<ExitStatus> for OutputExitStatus {
     fn from(value: ExitStatus) -> Self {
         match value {
             ExitStatus::Exited(code) => OutputExitStatus::Code(code as i32),
-            ExitStatus::Signaled(_) => OutputExitStatus::Unknown,
+            ExitStatus::Signaled(signal) => OutputExitStatus::Signal(signal as i32),
             ExitStatus::Other(code) => OutputExitStatus::Code(code),
             ExitStatus::Undetermined => OutputExitStatus::Unknown,
         }
@@ -192,9 +380,11 @@ mod tests {
 
     use super::Runner;
     use super::SubprocessRunner;
+    use crate::config::ByteSize;
     use crate::config::OutputStreamControl;
     use crate::config::TestCaseConfig;
     use crate::executors::context::Context as ExecutionContext;
+    use crate::executors::detached::detached_log_paths;
     use crate::output::ExitStatus;
     use crate::output::Output;
     use crate::testcase::TestCase;
@@ -214,6 +404,21 @@ mod tests {
         assert_eq!(expect, output);
     }
 
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_execute_captures_resource_usage() {
+        let output = SubprocessRunner::default()
+            .run(
+                "name",
+                &TestCase::from_expression("( exit 3 )"),
+                &ExecutionContext::new_for_test(),
+            )
+            .expect("execute without error");
+        assert_eq!(ExitStatus::Code(3), output.exit_code);
+        let usage = output.resource_usage.expect("resource usage is captured");
+        assert!(usage.max_rss_bytes > 0, "max RSS is captured: {usage}");
+    }
+
     #[test]
     fn test_execute_captures_stdout_and_stderr_separately() {
         let output = SubprocessRunner::default()
@@ -278,6 +483,105 @@ mod tests {
         assert_eq!(expect, output);
     }
 
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_execute_captures_terminating_signal() {
+        let output = SubprocessRunner::default()
+            .run(
+                "name",
+                &TestCase::from_expression("kill -TERM $$"),
+                &ExecutionContext::new_for_test(),
+            )
+            .expect("execute without error");
+        assert_eq!(ExitStatus::Signal(15), output.exit_code);
+        assert_eq!("signal:TERM", output.exit_code.to_string());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_execute_timeout_signals_before_kill() {
+        let directory = tempfile::tempdir().expect("create temporary directory");
+        let mut testcase = TestCase::from_expression_timed(
+            "trap 'echo cleaned > \"$DIRECTORY/signaled\"; exit' TERM; sleep 30 & wait",
+            Some(Duration::from_millis(200)),
+        );
+        testcase.config.environment.insert(
+            "DIRECTORY".into(),
+            directory.path().to_string_lossy().to_string(),
+        );
+        testcase.config.timeout_grace = Some(Duration::from_secs(10));
+        let start = std::time::Instant::now();
+        let output = SubprocessRunner::default()
+            .run("name", &testcase, &ExecutionContext::new_for_test())
+            .expect("execute without error");
+        assert_eq!(
+            ExitStatus::Timeout(Duration::from_millis(200)),
+            output.exit_code
+        );
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "processes ended before grace period"
+        );
+        assert_eq!(
+            "cleaned\n",
+            std::fs::read_to_string(directory.path().join("signaled")).expect("read file")
+        );
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_execute_timeout_kills_after_grace() {
+        let mut testcase = TestCase::from_expression_timed(
+            "trap '' TERM; sleep 30",
+            Some(Duration::from_millis(200)),
+        );
+        testcase.config.timeout_grace = Some(Duration::from_millis(300));
+        let start = std::time::Instant::now();
+        let output = SubprocessRunner::default()
+            .run("name", &testcase, &ExecutionContext::new_for_test())
+            .expect("execute without error");
+        assert_eq!(
+            ExitStatus::Timeout(Duration::from_millis(200)),
+            output.exit_code
+        );
+        let elapsed = start.elapsed();
+        assert!(
+            elapsed >= Duration::from_millis(500) && elapsed < Duration::from_secs(5),
+            "killed after grace period, not after {elapsed:?}"
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_execute_detached_tracks_process_and_writes_logs() {
+        let mut testcase = TestCase::from_expression("echo out; echo err >&2; sleep 30");
+        testcase.line_number = 7;
+        testcase.config.detached = Some(true);
+        testcase.config.detached_kill_signal = Some(Default::default());
+        let context = ExecutionContext::new_for_test();
+        let output = SubprocessRunner::default()
+            .run("name", &testcase, &context)
+            .expect("execute without error");
+        assert_eq!(ExitStatus::Detached, output.exit_code);
+        assert_eq!(1, context.detached_processes.len(), "process is tracked");
+
+        let (stdout_log, stderr_log) = detached_log_paths(&context.work_directory, 7);
+        let start = std::time::Instant::now();
+        while std::fs::read_to_string(&stderr_log).unwrap_or_default() != "err\n"
+            && start.elapsed() < Duration::from_secs(5)
+        {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(
+            "out\n",
+            std::fs::read_to_string(&stdout_log).expect("read STDOUT log")
+        );
+        assert_eq!(
+            "err\n",
+            std::fs::read_to_string(&stderr_log).expect("read STDERR log")
+        );
+    }
+
     #[test]
     fn test_execute_respects_timeout() {
         let start = std::time::SystemTime::now();
@@ -312,4 +616,35 @@ mod tests {
             "timeout reflected in exit code",
         );
     }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_execute_respects_max_output_size() {
+        let testcase = |expression: &str| TestCase {
+            shell_expression: expression.to_string(),
+            config: TestCaseConfig {
+                max_output_size: Some(ByteSize(10)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let output = SubprocessRunner::default()
+            .run(
+                "name",
+                &testcase("printf 12345; printf 67890 >&2"),
+                &ExecutionContext::new_for_test(),
+            )
+            .expect("execute without error");
+        assert_eq!(Output::from(("12345", "67890")), output);
+
+        let output = SubprocessRunner::default()
+            .run("name", &testcase("yes"), &ExecutionContext::new_for_test())
+            .expect("execute without error");
+        assert_eq!(
+            11,
+            output.stdout.len(),
+            "captured one byte more than allowed"
+        );
+        assert_eq!(ExitStatus::Signal(9), output.exit_code);
+    }
 }
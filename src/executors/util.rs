@@ -5,8 +5,31 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::Write;
 use std::num::NonZeroUsize;
+use std::path::Path;
+use std::path::PathBuf;
 use std::thread::{self};
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::time::Duration;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::time::Instant;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use tempfile::TempPath;
+
+use super::context::Context as ExecutionContext;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use crate::output::ExitStatus;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use crate::output::ResourceUsage;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use crate::signal::KillSignal;
+use crate::testcase::TestCase;
 
 /// Default amount of parallel executions. This number often corresponds to the
 /// amount of CPUs or computer has, but it may diverge in various cases.
@@ -15,3 +38,163 @@ pub fn default_parallel_count() -> usize {
         .unwrap_or(NonZeroUsize::new(1).expect("1 > 0"))
         .get()
 }
+
+/// Renders a `before_each` / `after_each` shell snippet as a command group that
+/// discards all output, or a no-op if no snippet is provided.
+pub(crate) fn render_hook(hook: &Option<String>) -> String {
+    match hook {
+        Some(hook) => format!("{{\n{hook}\n}} >/dev/null 2>&1"),
+        None => ":".to_string(),
+    }
+}
+
+/// Renders the environment variables as a single `export` statement, or a
+/// no-op if there are none.
+pub(crate) fn render_exports(environment: &BTreeMap<String, String>) -> Result<String> {
+    if environment.is_empty() {
+        return Ok(":".to_string());
+    }
+    let mut exports = vec![];
+    for (key, value) in environment {
+        // variable keys and values are assumed to be escaped in bash-like
+        // environments, that means even when executing in windows within
+        // a `bash.exe` process, the unix escaping is needed
+        let qkey = shell_escape::unix::escape(Cow::from(key)).to_string();
+        if qkey != *key {
+            bail!("Environment variable {} contains invalid characters", &qkey);
+        }
+        let qval = shell_escape::unix::escape(Cow::from(value)).to_string();
+        exports.push(format!("{}={}", &qkey, &qval));
+    }
+    Ok(format!("export {}", exports.join(" ")))
+}
+
+/// Writes the script into a temporary file in the directory, whose name ends
+/// with the suffix (e.g. `.ps1`), so that interpreters recognize it. The file
+/// is removed once the returned path is dropped.
+pub(crate) fn write_script_file(directory: &Path, suffix: &str, script: &[u8]) -> Result<TempPath> {
+    let mut file = tempfile::Builder::new()
+        .prefix(".script.")
+        .suffix(suffix)
+        .tempfile_in(directory)
+        .context("create temporary script file")?;
+    file.write_all(script).context("write script file")?;
+    Ok(file.into_temp_path())
+}
+
+/// Returns the configured standard input of the testcase, or nothing, for
+/// runners that run the shell expression from a script file, and can hence
+/// provide the standard input of the interpreter to it
+pub(crate) fn read_stdin_input(testcase: &TestCase, context: &ExecutionContext) -> Result<String> {
+    let Some(ref stdin) = testcase.config.stdin else {
+        return Ok(String::new());
+    };
+    let directory = context.file.parent().unwrap_or(Path::new(""));
+    String::from_utf8(stdin.read(directory)?).context("standard input must be valid UTF-8")
+}
+
+/// Returns the absolute path of the directory the testcase is configured to
+/// run in (see [`crate::config::TestCaseConfig::cwd`]), which must exist
+pub(crate) fn working_directory(
+    testcase: &TestCase,
+    context: &ExecutionContext,
+) -> Result<Option<PathBuf>> {
+    let Some(ref cwd) = testcase.config.cwd else {
+        return Ok(None);
+    };
+    let document_directory = context.file.parent().unwrap_or(Path::new(""));
+    let directory = std::path::absolute(cwd.resolve(&context.work_directory, document_directory))
+        .context("absolute path of working directory")?;
+    if !directory.is_dir() {
+        bail!("working directory {:?} does not exist", directory);
+    }
+    Ok(Some(directory))
+}
+
+/// Ends the process group, that the child process with the given PID leads,
+/// after it ran into its timeout: all processes of the group receive the
+/// signal first and are given the grace period to end, before those that are
+/// left are killed. The child process is reaped, so it cannot be waited for
+/// again. A disabled signal kills the processes right away.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub(crate) fn terminate_process_group(pid: u32, signal: &KillSignal, grace: Duration) {
+    use nix::sys::signal::Signal;
+    use nix::sys::signal::killpg;
+    use nix::unistd::Pid;
+
+    let group = Pid::from_raw(pid as i32);
+    let mut reaped = false;
+    if let Ok(signal) = signal.to_nix() {
+        let _ = killpg(group, signal);
+        let until = Instant::now() + grace;
+        while Instant::now() < until {
+            reaped = reaped || !matches!(wait_with_resource_usage(pid, false), Ok(None));
+
+            // the group is gone, once all of its processes ended
+            if reaped && killpg(group, None).is_err() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+    let _ = killpg(group, Signal::SIGKILL);
+    if !reaped {
+        let _ = wait_with_resource_usage(pid, true);
+    }
+}
+
+/// Waits for the child process with the given PID to end and returns its exit
+/// status together with the resources that it, and all the processes it waited
+/// for, consumed. If not `block`ing, returns `None` if the process is still
+/// running. The process is reaped, so it cannot be waited for again.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub(crate) fn wait_with_resource_usage(
+    pid: u32,
+    block: bool,
+) -> std::io::Result<Option<(ExitStatus, ResourceUsage)>> {
+    use nix::libc;
+
+    let mut status: libc::c_int = 0;
+    // SAFETY: rusage is a plain struct, for which all zeros is a valid value
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    let flags = if block { 0 } else { libc::WNOHANG };
+    let result = loop {
+        // SAFETY: both pointers reference valid, writable memory
+        let result = unsafe { libc::wait4(pid as libc::pid_t, &mut status, flags, &mut usage) };
+        if result != -1 || std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted
+        {
+            break result;
+        }
+    };
+    match result {
+        -1 => Err(std::io::Error::last_os_error()),
+        0 => Ok(None),
+        _ => {
+            let exit_code = if libc::WIFEXITED(status) {
+                ExitStatus::Code(libc::WEXITSTATUS(status))
+            } else if libc::WIFSIGNALED(status) {
+                ExitStatus::Signal(libc::WTERMSIG(status))
+            } else {
+                ExitStatus::Unknown
+            };
+            let duration = |time: libc::timeval| {
+                Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+            };
+
+            // Linux reports the maximum resident set size in kilobytes, macOS in bytes
+            let max_rss = usage.ru_maxrss.max(0) as u64;
+            Ok(Some((
+                exit_code,
+                ResourceUsage {
+                    max_rss_bytes: if cfg!(target_os = "macos") {
+                        max_rss
+                    } else {
+                        max_rss * 1024
+                    },
+                    user_time: duration(usage.ru_utime),
+                    system_time: duration(usage.ru_stime),
+                },
+            )))
+        }
+    }
+}
@@ -0,0 +1,212 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+
+use crate::config::TestCaseWait;
+
+/// Time in between two checks of the conditions of a wait
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Max time that connecting to a port, or a request to a URL, may take
+const CHECK_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Waits until all conditions of the wait are met, or until its timeout. A
+/// wait without conditions waits for its timeout. Paths are relative to the
+/// given directory.
+pub(super) fn wait_until_ready(wait: &TestCaseWait, directory: &Path) {
+    if wait.path.is_none() && wait.port.is_none() && wait.http.is_none() {
+        sleep(wait.timeout);
+        return;
+    }
+    let end = Instant::now() + wait.timeout;
+    while end > Instant::now() {
+        if is_ready(wait, directory) {
+            return;
+        }
+        sleep(POLL_INTERVAL);
+    }
+}
+
+/// Whether all conditions of the wait are met
+fn is_ready(wait: &TestCaseWait, directory: &Path) -> bool {
+    wait.path
+        .as_ref()
+        .is_none_or(|path| directory.join(path).exists())
+        && wait.port.is_none_or(accepts_connections)
+        && wait.http.as_ref().is_none_or(|url| {
+            http_status(url).is_ok_and(|status| match wait.status {
+                Some(expected) => status == expected,
+                None => (200..300).contains(&status),
+            })
+        })
+}
+
+/// Whether a TCP connection to the port on `localhost` can be established
+fn accepts_connections(port: u16) -> bool {
+    ("localhost", port)
+        .to_socket_addrs()
+        .is_ok_and(|mut addresses| {
+            addresses.any(|address| TcpStream::connect_timeout(&address, CHECK_TIMEOUT).is_ok())
+        })
+}
+
+/// Returns the status code of the response to a `GET` request to the
+/// (`http://`) URL
+fn http_status(url: &str) -> Result<u16> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| anyhow!("unsupported URL `{url}`, only `http://` URLs are"))?;
+    let (authority, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, "/"),
+    };
+    let address = if authority
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| port.parse::<u16>().is_ok())
+    {
+        authority.to_string()
+    } else {
+        format!("{authority}:80")
+    };
+
+    let mut stream = address
+        .to_socket_addrs()
+        .with_context(|| format!("resolve `{address}`"))?
+        .find_map(|address| TcpStream::connect_timeout(&address, CHECK_TIMEOUT).ok())
+        .ok_or_else(|| anyhow!("connect to `{address}`"))?;
+    stream.set_read_timeout(Some(CHECK_TIMEOUT))?;
+    stream.set_write_timeout(Some(CHECK_TIMEOUT))?;
+    let request = format!("GET {path} HTTP/1.1\r\nHost: {authority}\r\nConnection: close\r\n\r\n");
+    stream
+        .write_all(request.as_bytes())
+        .context("send request")?;
+
+    // only the status line of the response is of interest
+    let (mut response, mut buffer) = (vec![], [0; 512]);
+    while !response.contains(&b'\n') {
+        let read = stream.read(&mut buffer).context("read response")?;
+        if read == 0 {
+            break;
+        }
+        response.extend_from_slice(&buffer[..read]);
+    }
+    let response = String::from_utf8_lossy(&response);
+    let mut status_line = response.split_whitespace();
+    match (status_line.next(), status_line.next()) {
+        (Some(version), Some(status)) if version.starts_with("HTTP/") => status
+            .parse()
+            .with_context(|| format!("parse status `{status}` of `{url}`")),
+        _ => Err(anyhow!("invalid response from `{url}`")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::path::Path;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use super::http_status;
+    use super::is_ready;
+    use super::wait_until_ready;
+    use crate::config::TestCaseWait;
+
+    fn wait_for(path: Option<&str>, port: Option<u16>, http: Option<String>) -> TestCaseWait {
+        TestCaseWait {
+            timeout: Duration::from_millis(300),
+            path: path.map(Into::into),
+            port,
+            http,
+            status: None,
+        }
+    }
+
+    #[test]
+    fn test_wait_for_path() {
+        let directory = tempfile::tempdir().expect("create temporary directory");
+        std::fs::write(directory.path().join("ready"), "").expect("write file");
+
+        let started = Instant::now();
+        wait_until_ready(&wait_for(Some("ready"), None, None), directory.path());
+        assert!(
+            started.elapsed() < Duration::from_millis(300),
+            "path exists"
+        );
+
+        let started = Instant::now();
+        wait_until_ready(&wait_for(Some("missing"), None, None), directory.path());
+        assert!(
+            started.elapsed() >= Duration::from_millis(300),
+            "waited for timeout"
+        );
+    }
+
+    #[test]
+    fn test_wait_for_port() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind port");
+        let port = listener.local_addr().expect("local address").port();
+        assert!(is_ready(&wait_for(None, Some(port), None), Path::new("")));
+
+        drop(listener);
+        assert!(!is_ready(&wait_for(None, Some(port), None), Path::new("")));
+    }
+
+    #[test]
+    fn test_wait_for_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind port");
+        let url = format!(
+            "http://{}/health",
+            listener.local_addr().expect("local address")
+        );
+        let server = std::thread::spawn(move || {
+            for status in [
+                "204 No Content",
+                "204 No Content",
+                "503 Service Unavailable",
+            ] {
+                let (mut stream, _) = listener.accept().expect("accept connection");
+                let mut request = [0; 512];
+                let read = stream.read(&mut request).expect("read request");
+                assert!(
+                    request[..read].starts_with(b"GET /health HTTP/1.1\r\n"),
+                    "request of health"
+                );
+                write!(stream, "HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n")
+                    .expect("write response");
+            }
+        });
+
+        assert_eq!(204, http_status(&url).expect("status is returned"));
+        assert!(is_ready(
+            &wait_for(None, None, Some(url.clone())),
+            Path::new("")
+        ));
+        let wait = TestCaseWait {
+            status: Some(200),
+            ..wait_for(None, None, Some(url.clone()))
+        };
+        assert!(!is_ready(&wait, Path::new("")), "status is not 200");
+        server.join().expect("server ends");
+
+        http_status("https://localhost/").expect_err("only http is supported");
+    }
+}
@@ -5,15 +5,127 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
 
 use anyhow::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+use lazy_static::lazy_static;
+use regex::Captures;
+use regex::Regex;
 use serde::Serialize;
 
+use crate::config::CustomRule;
 use crate::escaping::Escaper;
 use crate::newline::StringNewline;
 use crate::rules::registry::RuleRegistry;
 use crate::rules::rule::Rule;
+use crate::rules::variables::Undefined;
+use crate::rules::variables::Variables;
+
+lazy_static! {
+    /// Checksum expectations, that are written as `(sha256:<hex>)` for the
+    /// remaining output or `<path> (sha256:<hex>)` for a produced file
+    static ref CHECKSUM_EXPECTATION: Regex =
+        Regex::new(r"^(?:(.*?)\s)?\(sha256:([0-9a-fA-F]+)\)$").expect("checksum regex");
+
+    /// Message that is shown when the expectation fails, which follows the
+    /// expectation as in `foo (regex) (msg: "banner changed")`
+    static ref MESSAGE_ANNOTATION: Regex =
+        Regex::new(r#"^(.*?)\s\(msg:\s*"((?:[^"\\]|\\.)*)"\)$"#).expect("message regex");
+
+    /// Reference to a macro of the document configuration, like `%ts%`
+    static ref MACRO_REFERENCE: Regex =
+        Regex::new(r"%([A-Za-z0-9_-]+)%").expect("macro reference regex");
+}
+
+/// Line that matches and ignores all remaining output
+pub const IGNORE_REMAINING: &str = "...";
+
+/// Line that starts a group of unordered expectations
+pub const UNORDERED_START: &str = "(unordered)";
+
+/// Line that ends a group of unordered expectations
+pub const UNORDERED_END: &str = "(ordered)";
+
+/// Line that separates alternative variants of the expectations of a testcase
+pub const VARIANT_SEPARATOR: &str = "(or)";
+
+/// Returns the line that starts or ends a group of unordered expectations,
+/// if the next expectation is not in the same kind of group as the previous
+pub(crate) fn unordered_marker(previous: bool, next: bool) -> Option<&'static str> {
+    match (previous, next) {
+        (false, true) => Some(UNORDERED_START),
+        (true, false) => Some(UNORDERED_END),
+        _ => None,
+    }
+}
+
+/// The number of consecutive lines of output that an [`Expectation`] matches,
+/// as given by a quantifier like `{3}`, `{2,5}` or `{2,}`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineCount {
+    /// The least number of lines
+    pub min: usize,
+
+    /// The most number of lines, if there is an upper bound
+    pub max: Option<usize>,
+}
+
+impl FromStr for LineCount {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let bounds = s
+            .strip_prefix('{')
+            .and_then(|bounds| bounds.strip_suffix('}'))
+            .ok_or_else(|| anyhow!("line count `{s}` must be enclosed in curly brackets"))?;
+        let parse = |bound: &str| {
+            bound
+                .trim()
+                .parse::<usize>()
+                .map_err(|err| anyhow!("invalid bound in line count `{s}`: {err}"))
+        };
+        let count = match bounds.split_once(',') {
+            None => {
+                let count = parse(bounds)?;
+                Self {
+                    min: count,
+                    max: Some(count),
+                }
+            }
+            Some((min, "")) => Self {
+                min: parse(min)?,
+                max: None,
+            },
+            Some((min, max)) => Self {
+                min: parse(min)?,
+                max: Some(parse(max)?),
+            },
+        };
+        match count.max {
+            Some(0) => bail!("line count `{s}` must allow at least one line"),
+            Some(max) if max < count.min => {
+                bail!("line count `{s}` must not have a lower bound above its upper bound")
+            }
+            _ => Ok(count),
+        }
+    }
+}
+
+impl Display for LineCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{{{max}}}"),
+            Some(max) => write!(f, "{{{},{max}}}", self.min),
+            None => write!(f, "{{{},}}", self.min),
+        }
+    }
+}
 
 /// An expectation about the content and / or form of one or multiple subsequent
 /// line(s) of output, that may be optional.
@@ -25,9 +137,22 @@ pub struct Expectation {
     /// Multiline Expectations (can) match multiple sequential lines of output
     pub multiline: bool,
 
+    /// Counted Expectations match a number of consecutive lines of output
+    /// that is within the bounds of the count, which then also determines
+    /// whether they are optional and multiline
+    pub count: Option<LineCount>,
+
+    /// Unordered Expectations match the lines of output that the consecutive
+    /// unordered expectations around them match in any order
+    pub unordered: bool,
+
     /// The actual algorithm that implements the Expectation
     pub rule: Box<dyn Rule>,
 
+    /// Message that explains why the Expectation matters, which is shown
+    /// when it fails
+    pub message: Option<String>,
+
     /// The original expression as it was written in the test file
     original: String,
 }
@@ -46,14 +171,64 @@ impl Expectation {
 
     /// Renders the Expectation into an expression from which it can be parsed
     pub fn to_expression_string(&self, escaper: &Escaper) -> String {
-        self.rule
-            .to_expression_string(self.optional, self.multiline, escaper)
+        self.with_message(self.rule.to_expression_string(&self.quantifier(), escaper))
+    }
+
+    /// Returns the rendered expression, followed by the message of the
+    /// Expectation (e.g. `(msg: "banner changed")`), if it has one
+    pub(crate) fn with_message(&self, expression: String) -> String {
+        match self.message {
+            Some(ref message) => format!(
+                "{expression} (msg: \"{}\")",
+                message.replace('\\', "\\\\").replace('"', "\\\"")
+            ),
+            None => expression,
+        }
+    }
+
+    /// The quantifier, as it is written after the kind of the Expectation
+    /// (e.g. `+` in `foo (glob+)`), which is empty if it has none
+    pub fn quantifier(&self) -> String {
+        match (self.count, self.optional, self.multiline) {
+            (Some(count), _, _) => count.to_string(),
+            (None, true, true) => "*".to_string(),
+            (None, true, false) => "?".to_string(),
+            (None, false, true) => "+".to_string(),
+            (None, false, false) => "".to_string(),
+        }
     }
 
     /// The original string as it was written in the test file
     pub fn original_string(&self) -> String {
         self.original.clone()
     }
+
+    /// Returns the Expectation, which is or is not part of a group of
+    /// unordered expectations
+    pub(crate) fn with_unordered(self, unordered: bool) -> Self {
+        Self { unordered, ..self }
+    }
+
+    /// Returns the Expectation, in which references to variables (`%{name}`)
+    /// are replaced with their values, if its rule has any
+    pub fn with_variables(&self, variables: &Variables, undefined: Undefined) -> Self {
+        match self.rule.with_variables(variables, undefined) {
+            Some(rule) => Self {
+                rule,
+                ..self.clone()
+            },
+            None => self.clone(),
+        }
+    }
+
+    /// Returns the Expectation with the given original string, if it was made
+    /// from a different (e.g. normalized) form of what is written in the test file
+    pub(crate) fn with_original(self, original: &str) -> Self {
+        Self {
+            original: original.to_string(),
+            ..self
+        }
+    }
 }
 
 impl Display for Expectation {
@@ -66,6 +241,9 @@ impl PartialEq for Expectation {
     fn eq(&self, other: &Self) -> bool {
         self.optional == other.optional
             && self.multiline == other.multiline
+            && self.count == other.count
+            && self.unordered == other.unordered
+            && self.message == other.message
             && self.rule.to_string() == other.rule.to_string()
     }
 }
@@ -81,26 +259,98 @@ impl Serialize for Expectation {
 
 /// Facade for [`Expectation`] creation from either line encoded representation
 /// or from components
-pub struct ExpectationMaker(RuleRegistry);
+pub struct ExpectationMaker {
+    registry: RuleRegistry,
+
+    /// Snippets of expectation lines, by name, which are referred to as
+    /// `%name%` and expanded before the line is parsed
+    macros: BTreeMap<String, String>,
+}
 
 impl ExpectationMaker {
     pub fn new(registry: RuleRegistry) -> Self {
-        Self(registry)
+        Self {
+            registry,
+            macros: BTreeMap::new(),
+        }
+    }
+
+    /// Returns a copy that also makes expectations of the given custom kinds,
+    /// whose commands run in the given shell
+    pub fn with_custom_rules(
+        &self,
+        rules: &BTreeMap<String, CustomRule>,
+        shell: &Path,
+    ) -> Result<Self> {
+        let mut registry = self.registry.clone();
+        for (name, rule) in rules {
+            registry.register_command(name, &rule.command, shell)?;
+        }
+        Ok(Self {
+            registry,
+            macros: self.macros.clone(),
+        })
+    }
+
+    /// Returns a copy that expands the given macros, which replace the
+    /// macros it expanded before
+    pub fn with_macros(&self, macros: &BTreeMap<String, String>) -> Result<Self> {
+        if let Some(name) = macros.keys().find(|name| {
+            name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        }) {
+            bail!(
+                "invalid name of macro `{name}`, which must consist of letters, digits, `-` or `_`"
+            );
+        }
+        Ok(Self {
+            registry: self.registry.clone(),
+            macros: macros.clone(),
+        })
+    }
+
+    /// Returns the line, in which all references to macros (`%name%`) are
+    /// replaced with their snippets. References to undefined macros are kept.
+    fn expand_macros<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        if self.macros.is_empty() {
+            return Cow::Borrowed(line);
+        }
+        MACRO_REFERENCE.replace_all(line, |captures: &Captures| {
+            self.macros
+                .get(&captures[1])
+                .cloned()
+                .unwrap_or_else(|| captures[0].to_string())
+        })
     }
 
     // TODO: T131320483 following sentence doesnt make sense
     /// Create an [`Expectation`] that from it's text encoding, with the BNF form:
     ///
     /// ```bnf
-    ///  <expectation> ::= <expression> | <expression> (<kind>) | <expression> (<quantifier>) | <expression> (<kind><quantifier>)
+    ///  <expectation> ::= <expression> | <expression> (<kind>) | <expression> (<quantifier>) | <expression> (<kind><quantifier>) | <checksum> | <ignore> | <expectation> <message>
     ///   <expression> ::= "arbitrary text"
-    ///         <kind> ::= <equal-kind> | <no-eol-kind> | <escaped-kind> | <glob-kind> | <regex-kind>
-    ///   <equal-kind> ::= "equal" | "eq"
+    ///         <kind> ::= <equal-kind> | <no-eol-kind> | <escaped-kind> | <glob-kind> | <regex-kind> | <file-kind> | <json-kind> | <yaml-kind> | <toml-kind> | <not-kind> | <approx-kind> | <template-kind> | <custom-kind>
+    ///   <equal-kind> ::= ("equal" | "eq") <modifiers>
     ///  <no-eol-kind> ::= "no-eol"
-    /// <escaped-kind> ::= "escaped" | "esc"
-    ///    <glob-kind> ::= "glob" | "gl"
-    ///   <regex-kind> ::= "regex" | "re"
-    ///   <quantifier> ::= "?" | "*" | "+"
+    /// <escaped-kind> ::= ("escaped" | "esc") <modifiers>
+    ///    <glob-kind> ::= ("glob" | "gl") <modifiers>
+    ///   <regex-kind> ::= ("regex" | "re") <modifiers>
+    ///    <file-kind> ::= "file"
+    ///    <json-kind> ::= "json"
+    ///    <yaml-kind> ::= "yaml" | "yml"
+    ///    <toml-kind> ::= "toml"
+    ///  <approx-kind> ::= "approx" <modifiers> | "approx" <modifiers> <tolerance>
+    ///    <tolerance> ::= "±" <number> | "±" <number> "%"
+    /// <template-kind> ::= "template" <modifiers>
+    ///     <not-kind> ::= "not" <modifiers>
+    ///  <custom-kind> ::= "name of a custom rule in the document configuration"
+    ///    <modifiers> ::= "" | ":i" <modifiers> | ":trim" <modifiers>
+    ///   <quantifier> ::= "?" | "*" | "+" | "{" <number> "}" | "{" <number> ",}" | "{" <number> "," <number> "}"
+    ///     <checksum> ::= (sha256:<hex-digest>) | <file-path> (sha256:<hex-digest>)
+    ///       <ignore> ::= "..."
+    ///      <message> ::= (msg: "arbitrary text")
     /// ```
     ///
     /// ```
@@ -113,17 +363,52 @@ impl ExpectationMaker {
     ///     .parse("^foo bar$ (regex)")
     ///     .expect("parses expectation");
     /// ```
+    ///
+    /// Expectations that span multiple lines, like JSON documents, are given
+    /// with the kind at the end of their first line, which is followed by the
+    /// remaining lines of the expression.
+    ///
+    /// References to macros of the document configuration (`%name%`) are
+    /// replaced with their snippet before the line is parsed.
+    ///
+    /// The first line can end in a message, that is shown when the
+    /// expectation fails, as in `foo (regex) (msg: "banner changed")`.
     pub fn parse(&self, line: &str) -> Result<Expectation> {
-        let (expression, kind, quantifier) = self.extract(line)?;
-        let multiline = quantifier == "*" || quantifier == "+";
-        let optional = quantifier == "*" || quantifier == "?";
-        self.make(
-            &kind,
-            &expression,
-            optional,
-            multiline,
-            &(&line).trim_newlines(),
-        )
+        let original = &(&line).trim_newlines();
+        let expanded = self.expand_macros(line);
+        let line: &str = &expanded;
+        let (first_line, continuation) = match line.split_once('\n') {
+            Some((first_line, continuation)) => (first_line, Some(continuation)),
+            None => (line, None),
+        };
+        let (first_line, message) = match MESSAGE_ANNOTATION.captures(first_line) {
+            Some(captures) => (
+                captures.get(1).map_or("", |m| m.as_str()),
+                Some(unescape_message(&captures[2])),
+            ),
+            None => (first_line, None),
+        };
+        let (mut expression, kind, quantifier) = self.extract(first_line)?;
+        if let Some(continuation) = continuation {
+            expression.push('\n');
+            expression.push_str(&(&continuation).trim_newlines());
+        }
+        let count = if quantifier.starts_with('{') {
+            Some(quantifier.parse::<LineCount>()?)
+        } else {
+            None
+        };
+        let multiline = quantifier == "*"
+            || quantifier == "+"
+            || count.is_some_and(|count| count.max != Some(1));
+        let optional =
+            quantifier == "*" || quantifier == "?" || count.is_some_and(|count| count.min == 0);
+        let expectation = self.make(&kind, &expression, optional, multiline, original)?;
+        Ok(Expectation {
+            count,
+            message,
+            ..expectation
+        })
     }
 
     /// Create an [`Expectation`] from the components that make it up
@@ -138,24 +423,37 @@ impl ExpectationMaker {
         Ok(Expectation {
             optional,
             multiline,
-            rule: self.0.make(kind, expression)?,
+            count: None,
+            unordered: false,
+            rule: self.registry.make(kind, expression)?,
+            message: None,
             original: original.into(),
         })
     }
 
     // TODO: rename return type so that people can understand
     fn extract(&self, line: &str) -> Result<(String, String, String)> {
-        let captures = self
-            .0
-            .to_expectation_regex()?
-            .captures(line)
-            .map_or(vec![], |captures| {
-                captures
-                    .iter()
-                    .skip(1)
-                    .filter_map(|m| m.map(|v| v.as_str()))
-                    .collect::<Vec<_>>()
-            });
+        if let Some(captures) = CHECKSUM_EXPECTATION.captures(line) {
+            let expression = match captures.get(1) {
+                Some(path) => format!("{} {}", path.as_str(), &captures[2]),
+                None => captures[2].to_string(),
+            };
+            return Ok((expression, "sha256".to_string(), "".to_string()));
+        }
+        if line == IGNORE_REMAINING {
+            return Ok(("".to_string(), "ignore".to_string(), "".to_string()));
+        }
+        let captures =
+            self.registry
+                .to_expectation_regex()?
+                .captures(line)
+                .map_or(vec![], |captures| {
+                    captures
+                        .iter()
+                        .skip(1)
+                        .filter_map(|m| m.map(|v| v.as_str()))
+                        .collect::<Vec<_>>()
+                });
         if captures.len() == 1 {
             Ok((line.to_string(), "equal".to_string(), "".to_string()))
         } else if captures.len() == 2 {
@@ -178,8 +476,25 @@ impl ExpectationMaker {
     }
 }
 
+/// Returns the message of a `(msg: "…")` annotation, in which `\"` and `\\`
+/// stand for a double quote and a backslash
+fn unescape_message(message: &str) -> String {
+    let mut unescaped = String::with_capacity(message.len());
+    let mut chars = message.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            unescaped.extend(chars.next());
+        } else {
+            unescaped.push(c);
+        }
+    }
+    unescaped
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
+    use std::collections::BTreeMap;
+
     use super::ExpectationMaker;
     use crate::escaping::Escaper;
     use crate::rules::registry::RuleRegistry;
@@ -210,6 +525,28 @@ pub(crate) mod tests {
             ("foo (glob*)", ("foo", "glob", "*")),
             ("foo (glob+)", ("foo", "glob", "+")),
             ("foo (glob+) (glob+)", ("foo (glob+)", "glob", "+")),
+            ("foo ({3})", ("foo", "equal", "{3}")),
+            ("foo (regex{3})", ("foo", "regex", "{3}")),
+            ("foo (glob{2,5})", ("foo", "glob", "{2,5}")),
+            ("foo (glob{2,})", ("foo", "glob", "{2,}")),
+            ("foo (glob{2)", ("foo (glob{2)", "equal", "")),
+            ("(sha256:abc123)", ("abc123", "sha256", "")),
+            ("out.bin (sha256:abc123)", ("out.bin abc123", "sha256", "")),
+            ("abc123 (sha256)", ("abc123", "sha256", "")),
+            ("...", ("", "ignore", "")),
+            ("took 1s (approx)", ("took 1s", "approx", "")),
+            ("took 1s (approx ±10%)", ("took 1s", "approx ±10%", "")),
+            ("took 1s (approx ±0.5+)", ("took 1s", "approx ±0.5", "+")),
+            ("foo (glob bar)", ("foo (glob bar)", "equal", "")),
+            ("... (equal)", ("...", "equal", "")),
+            ("foo ...", ("foo ...", "equal", "")),
+            ("foo (equal:i)", ("foo", "equal:i", "")),
+            ("foo (glob:i:trim+)", ("foo", "glob:i:trim", "+")),
+            (
+                "took 1s (approx:trim ±0.5)",
+                ("took 1s", "approx:trim ±0.5", ""),
+            ),
+            ("foo (equal:x)", ("foo (equal:x)", "equal", "")),
         ];
 
         tests.iter().for_each(
@@ -250,6 +587,28 @@ pub(crate) mod tests {
             ("foo (regex)", "foo (regex)"),
             ("foo (re)", "foo (regex)"),
             ("foo (regex*)", "foo (regex*)"),
+            ("foo (not)", "foo (not)"),
+            ("...", "..."),
+            ("took 1s (approx)", "took 1s (approx)"),
+            ("took 1s (approx +-10%)", "took 1s (approx ±10%)"),
+            ("id %{UUID} (template)", "id %{UUID} (template)"),
+            ("foo (eq:i)", "foo (equal:i)"),
+            ("foo (eq:trim:i+)", "foo (equal:i:trim+)"),
+            ("foo\\tbar (escaped:i)", "foo\\tbar (escaped:i)"),
+            ("foo (re:trim{2})", "foo (regex:trim{2})"),
+            ("took 1s (approx:i +-10%)", "took 1s (approx:i ±10%)"),
+            ("foo ({3})", "foo ({3})"),
+            ("foo (eq{0,2})", "foo ({0,2})"),
+            ("foo (regex{3})", "foo (regex{3})"),
+            ("foo (glob{2,})", "foo (glob{2,})"),
+            (
+                "(sha256:5891B5B522D5DF086D0FF0B110FBD9D21BB4FC7163AF34D08286A2E846F6BE03)",
+                "(sha256:5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03)",
+            ),
+            (
+                "out.txt (sha256:5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03)",
+                "out.txt (sha256:5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03)",
+            ),
         ];
         for (from, to) in tests {
             let expectation = expectation_maker()
@@ -260,6 +619,74 @@ pub(crate) mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_message() {
+        for (line, message, rendered) in [
+            (
+                r#"foo (msg: "foo matters")"#,
+                Some("foo matters"),
+                r#"foo (msg: "foo matters")"#,
+            ),
+            (
+                r#"fo+ (regex+) (msg:"a \"quoted\" \\ reason")"#,
+                Some(r#"a "quoted" \ reason"#),
+                r#"fo+ (regex+) (msg: "a \"quoted\" \\ reason")"#,
+            ),
+            (r#"foo (msg: "")"#, Some(""), r#"foo (msg: "")"#),
+            (r#"foo (msg: unquoted)"#, None, r#"foo (msg: unquoted)"#),
+            (r#"foo (msg: "a" "b")"#, None, r#"foo (msg: "a" "b")"#),
+        ] {
+            let expectation = expectation_maker()
+                .parse(line)
+                .unwrap_or_else(|err| panic!("parse `{line}`: {err}"));
+            assert_eq!(
+                message,
+                expectation.message.as_deref(),
+                "message of `{line}`"
+            );
+            assert_eq!(
+                rendered,
+                expectation.to_expression_string(&Escaper::default()),
+                "`{line}` is rendered"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_macros() {
+        let maker = expectation_maker()
+            .with_macros(&BTreeMap::from([
+                ("ts".to_string(), r"\d{4}-\d{2}-\d{2} (regex)".to_string()),
+                ("user".to_string(), "alice".to_string()),
+            ]))
+            .expect("register macros");
+        for (line, kind, expression) in [
+            ("%ts%", "regex", r"\d{4}-\d{2}-\d{2}"),
+            ("hello %user%", "equal", "hello alice"),
+            ("at %user%: %ts%", "regex", r"at alice: \d{4}-\d{2}-\d{2}"),
+            ("%other% and 100%", "equal", "%other% and 100%"),
+        ] {
+            let expectation = maker
+                .parse(line)
+                .unwrap_or_else(|err| panic!("parse `{line}`: {err}"));
+            assert_eq!(
+                (kind.to_string(), expression.as_bytes().to_vec()),
+                expectation.rule.unmake(),
+                "`{line}` is expanded"
+            );
+            assert_eq!(line, expectation.original, "`{line}` is kept as original");
+        }
+
+        for name in ["", "a b", "a%b"] {
+            assert!(
+                expectation_maker()
+                    .with_macros(&BTreeMap::from([(name.to_string(), "x".to_string())]))
+                    .is_err(),
+                "`{name}` is rejected"
+            );
+        }
+    }
+
     pub(crate) fn expectation_maker() -> ExpectationMaker {
         ExpectationMaker::new(RuleRegistry::default())
     }
@@ -141,6 +141,8 @@ mod tests {
                         testcase: TestCase {
                             title: "This is a test".to_string(),
                             shell_expression: "the command".to_string(),
+                            template_arguments: None,
+                            interaction: vec![],
                             expectations: vec![test_expectation!(
                                 "equal",
                                 "an expectation",
@@ -148,7 +150,9 @@ mod tests {
                                 false,
                                 "an expectation"
                             )],
+                            variants: vec![],
                             exit_code: None,
+                            assertions: vec![],
                             line_number: 234,
                             config: Default::default(),
                         },
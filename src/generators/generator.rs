@@ -7,7 +7,9 @@
 
 use anyhow::Result;
 
+use crate::config::DocumentConfig;
 use crate::outcome::Outcome;
+use crate::testcase::TestCase;
 
 /// Takes whole test documents, in the appropriate syntax of the implementation,
 /// and returns an updated document, for which all testcases blocks contain
@@ -22,6 +24,14 @@ pub trait TestCaseGenerator {
     fn generate_testcases(&self, outcomes: &[&Outcome]) -> Result<String>;
 }
 
+/// Renders a whole test document, in the appropriate syntax of the
+/// implementation, from its configuration and [`crate::testcase::TestCase`]s.
+/// Parsing the rendered document yields the same configuration and testcases
+/// (except for line numbers).
+pub trait DocumentGenerator {
+    fn generate_document(&self, config: &DocumentConfig, testcases: &[TestCase]) -> Result<String>;
+}
+
 #[cfg(test)]
 pub(super) mod tests {
     use super::TestCaseGenerator;
@@ -157,6 +167,51 @@ pub(super) mod tests {
                     format: ParserType::Markdown,
                 },
             ),
+            (
+                "heredoc_command",
+                Outcome {
+                    location: None,
+                    output: ("the output\n", "").into(),
+                    testcase: TestCase {
+                        title: "This is a test".to_string(),
+                        shell_expression: "cat <<EOF | \\\n  wc -l\n$ line\n\n> line\n[1]\nEOF"
+                            .into(),
+                        expectations: vec![],
+                        exit_code: None,
+                        line_number: 234,
+                        ..Default::default()
+                    },
+                    result: Err(TestCaseError::MalformedOutput(Diff::new(vec![
+                        DiffLine::UnexpectedLines {
+                            lines: vec![(0, formatln!("the output").as_bytes().to_vec())],
+                        },
+                    ]))),
+                    escaping: Escaper::default(),
+                    format: ParserType::Markdown,
+                },
+            ),
+            (
+                "exit_code_like_output",
+                Outcome {
+                    location: None,
+                    output: ("[1]\n", "").into(),
+                    testcase: TestCase {
+                        title: "This is a test".to_string(),
+                        shell_expression: "echo '[1]'".into(),
+                        expectations: vec![],
+                        exit_code: None,
+                        line_number: 234,
+                        ..Default::default()
+                    },
+                    result: Err(TestCaseError::MalformedOutput(Diff::new(vec![
+                        DiffLine::UnexpectedLines {
+                            lines: vec![(0, formatln!("[1]").as_bytes().to_vec())],
+                        },
+                    ]))),
+                    escaping: Escaper::default(),
+                    format: ParserType::Markdown,
+                },
+            ),
             (
                 "non-zero_exit_code",
                 Outcome {
@@ -166,7 +221,7 @@ pub(super) mod tests {
                         title: "This is a test".to_string(),
                         shell_expression: "the command".into(),
                         expectations: vec![],
-                        exit_code: Some(123),
+                        exit_code: Some(123.into()),
                         line_number: 234,
                         ..Default::default()
                     },
@@ -9,18 +9,34 @@ use std::fmt::Write;
 
 use anyhow::Context;
 use anyhow::Result;
+use anyhow::bail;
 
+use super::generator::DocumentGenerator;
 use super::generator::TestCaseGenerator;
 use super::generator::UpdateGenerator;
+use crate::config::DocumentConfig;
+use crate::config::Shell;
 use crate::config::TestCaseConfig;
+use crate::escaping::Escaper;
+use crate::executors::powershell_runner::POWERSHELL_PROMPT;
+use crate::executors::powershell_runner::is_powershell;
+use crate::expectation::Expectation;
+use crate::expectation::IGNORE_REMAINING;
+use crate::expectation::VARIANT_SEPARATOR;
+use crate::expectation::unordered_marker;
 use crate::formatln;
 use crate::generators::outcome::OutcomeTestGenerator;
 use crate::newline::StringNewline;
 use crate::outcome::Outcome;
+use crate::parsers::line_parser::extract_exit_code;
 use crate::parsers::markdown::DEFAULT_MARKDOWN_LANGUAGES;
 use crate::parsers::markdown::MarkdownIterator;
 use crate::parsers::markdown::MarkdownToken;
 use crate::parsers::markdown::NumberedLines;
+use crate::parsers::markdown::extract_title;
+use crate::testcase::ExpectedExitCode;
+use crate::testcase::InteractionStep;
+use crate::testcase::TestCase;
 
 /// Update [`crate::testcase::TestCase`]s in an existing Markdown document
 pub struct MarkdownUpdateGenerator(Vec<String>);
@@ -77,7 +93,7 @@ impl UpdateGenerator for MarkdownUpdateGenerator {
                     language,
                     config_lines,
                     comment_lines,
-                    code_lines: _,
+                    code_lines,
                 } => {
                     let config = if config_lines.is_empty() {
                         "".into()
@@ -87,6 +103,14 @@ impl UpdateGenerator for MarkdownUpdateGenerator {
                     let generated = outcomes[testcase_index]
                         .generate_testcase()
                         .with_context(|| format!("testcase number {}", testcase_index + 1))?;
+
+                    // keep the PowerShell prompt, if the testcase was written with it
+                    let generated = match code_lines.first() {
+                        Some((_, line)) if line.starts_with(&format!("{POWERSHELL_PROMPT} ")) => {
+                            with_prompt(generated, POWERSHELL_PROMPT)
+                        }
+                        _ => generated,
+                    };
                     let backticks = "`".repeat(max_backtick_size(&generated) + 1);
                     updated.push_str(&formatln!("{}{}{}", &backticks, &language, &config));
                     for (_, line) in &comment_lines {
@@ -104,11 +128,18 @@ impl UpdateGenerator for MarkdownUpdateGenerator {
 
 /// Generate a new Markdown [`crate::testcase::TestCase`] document from shell
 /// expression and it's [`crate::output::Output`]
-pub struct MarkdownTestCaseGenerator(String);
+pub struct MarkdownTestCaseGenerator(String, String);
 
 impl MarkdownTestCaseGenerator {
     pub fn new(language: &str) -> Self {
-        Self(language.to_string())
+        Self(language.to_string(), "$".to_string())
+    }
+
+    /// Sets the prompt that the shell expressions start with, like
+    /// [`POWERSHELL_PROMPT`] for documents that are run with PowerShell
+    pub fn with_prompt(mut self, prompt: &str) -> Self {
+        self.1 = prompt.to_string();
+        self
     }
 }
 
@@ -143,7 +174,7 @@ impl TestCaseGenerator for MarkdownTestCaseGenerator {
                 };
 
                 // start with shell expressions
-                let generated = outcome.generate_testcase()?;
+                let generated = with_prompt(outcome.generate_testcase()?, &self.1);
                 let backticks = "`".repeat(max_backtick_size(&generated) + 1);
                 rendered.push_str(&formatln!("{}{}{}", &backticks, self.0, config));
                 rendered.push_str(&generated);
@@ -155,11 +186,193 @@ impl TestCaseGenerator for MarkdownTestCaseGenerator {
     }
 }
 
+/// Render a whole Markdown document from the configuration and the
+/// [`crate::testcase::TestCase`]s that [`crate::parsers::markdown::MarkdownParser`]
+/// returns for it, in canonical form:
+/// - the document configuration as front-matter, unless it is the default
+/// - every testcase as a code block with its title as paragraph above it, its
+///   configuration as far as it differs from the document defaults and its
+///   expectations with their long rule names
+///
+/// Prose, comments and code blocks that are not tests are not part of the
+/// parsed testcases and are hence not rendered. Testcases that cannot be
+/// expressed in Markdown (e.g. multi-line titles with empty lines) result in
+/// an error, instead of a document that parses into something else.
+pub struct MarkdownDocumentGenerator(String);
+
+impl MarkdownDocumentGenerator {
+    pub fn new(language: &str) -> Self {
+        Self(language.to_string())
+    }
+
+    fn generate_testcase(
+        &self,
+        testcase: &TestCase,
+        defaults: &TestCaseConfig,
+        prompt: &str,
+    ) -> Result<String> {
+        let mut rendered = String::new();
+        for line in testcase.title.lines() {
+            rendered.push_str(&formatln!("{}", render_title_line(line)?));
+        }
+        if !rendered.is_empty() {
+            rendered.push('\n');
+        }
+
+        let mut code = String::new();
+        let (prefix, expression) = match &testcase.template_arguments {
+            Some(arguments) => ("%", arguments),
+            None => (prompt, &testcase.shell_expression),
+        };
+        for (index, line) in expression.split('\n').enumerate() {
+            code.push_str(&match (index, line) {
+                (0, line) => formatln!("{} {}", prefix, line),
+                (_, "") => formatln!(">"),
+                (_, line) => formatln!("> {}", line),
+            });
+        }
+        for step in &testcase.interaction {
+            code.push_str(&formatln!("{}", step));
+        }
+        let tty = testcase.config.tty.or(defaults.tty).unwrap_or(false);
+        let variants = std::iter::once(&testcase.expectations).chain(&testcase.variants);
+        for (variant, expectations) in variants.enumerate() {
+            if variant > 0 {
+                code.push_str(&formatln!("{}", VARIANT_SEPARATOR));
+            }
+            let mut unordered = false;
+            for (index, expectation) in expectations.iter().enumerate() {
+                if let Some(marker) = unordered_marker(unordered, expectation.unordered) {
+                    code.push_str(&formatln!("{}", marker));
+                }
+                unordered = expectation.unordered;
+                let line = render_expectation(expectation);
+                let first = variant == 0 && index == 0;
+                if first && (line == ">" || line.starts_with("> ")) {
+                    bail!(
+                        "first expectation `{}` would be read as continuation of the shell expression",
+                        line
+                    );
+                }
+                if first && tty && InteractionStep::parse(&line).is_some() {
+                    bail!(
+                        "first expectation `{}` would be read as interaction step",
+                        line
+                    );
+                }
+                code.push_str(&formatln!("{}", line));
+            }
+        }
+        match &testcase.exit_code {
+            Some(ExpectedExitCode::Code(code)) if *code < 0 => {
+                bail!("negative exit code {} is not supported", code)
+            }
+            Some(exit_code) => code.push_str(&formatln!("[{}]", exit_code)),
+            None => {}
+        }
+
+        let config_diff = testcase.config.diff(defaults);
+        let config = if config_diff.is_empty() {
+            "".into()
+        } else {
+            format!(" {}", config_diff.to_yaml_one_liner())
+        };
+        let backticks = "`".repeat(max_backtick_size(&code) + 1);
+        rendered.push_str(&formatln!("{}{}{}", &backticks, self.0, config));
+        rendered.push_str(&code);
+        rendered.push_str(&formatln!("{}", &backticks));
+        Ok(rendered)
+    }
+}
+
+impl Default for MarkdownDocumentGenerator {
+    fn default() -> Self {
+        Self::new(DEFAULT_MARKDOWN_LANGUAGES[0])
+    }
+}
+
+impl DocumentGenerator for MarkdownDocumentGenerator {
+    fn generate_document(
+        &self,
+        config: &DocumentConfig,
+        testcases: &[TestCase],
+    ) -> anyhow::Result<String> {
+        let mut blocks = vec![];
+        let front_matter =
+            serde_yaml::to_string(config).context("render document configuration")?;
+        if front_matter.trim() != "{}" {
+            blocks.push(format!("---\n{}---\n", front_matter.assure_newline()));
+        }
+
+        let defaults = config
+            .testcase_defaults()
+            .with_defaults_from(&TestCaseConfig::default_markdown());
+        let prompt = match config.shell.as_ref().and_then(Shell::path) {
+            Some(shell) if is_powershell(shell) => POWERSHELL_PROMPT,
+            _ => "$",
+        };
+        for testcase in testcases {
+            blocks.push(
+                self.generate_testcase(testcase, &defaults, prompt)
+                    .with_context(|| format!("testcase `{}`", testcase.shell_expression))?,
+            );
+        }
+        Ok(blocks.join("\n"))
+    }
+}
+
+/// Renders a line of a testcase title as paragraph, or as header if it does
+/// not start like a paragraph
+fn render_title_line(line: &str) -> Result<String> {
+    for rendered in [line.to_string(), format!("## {line}")] {
+        if extract_title(&rendered).is_some_and(|(_, title)| title == line) {
+            return Ok(rendered);
+        }
+    }
+    bail!("title line `{}` cannot be expressed in Markdown", line)
+}
+
+/// Replaces the `$` prompt of the shell expression, that the generated
+/// testcase starts with, with the given prompt
+fn with_prompt(generated: String, prompt: &str) -> String {
+    match generated.strip_prefix("$ ") {
+        Some(rest) if prompt != "$" => format!("{prompt} {rest}"),
+        _ => generated,
+    }
+}
+
+/// Renders an expectation as line of a test code block. Expressions are
+/// written as-is, so that they are parsed into the same rule, and only those
+/// that span lines or are stored unescaped (`escaped`) are written escaped.
+/// Equality rules are written without a kind, unless the line would then be
+/// read as something else. Messages are written after the kind.
+fn render_expectation(expectation: &Expectation) -> String {
+    let (kind, expression) = expectation.rule.unmake();
+    let raw = match String::from_utf8(expression) {
+        Ok(raw) if !kind.starts_with("escaped") && !raw.contains('\n') && !raw.ends_with('\r') => {
+            raw
+        }
+        _ => return expectation.to_expression_string(&Escaper::default()),
+    };
+    let quantifier = expectation.quantifier();
+    expectation.with_message(match kind.as_str() {
+        "equal" if !quantifier.is_empty() => format!("{raw} ({quantifier})"),
+        "equal"
+            if !raw.ends_with(')')
+                && raw != IGNORE_REMAINING
+                && extract_exit_code(&raw).is_none() =>
+        {
+            raw
+        }
+        _ => format!("{raw} ({kind}{quantifier})"),
+    })
+}
+
 /// returns the largest amount of backticks in a line that is found in the given
 /// code block. If no backtick prefix is found than 2 is return, so that an
 /// addition of one to the result always yields the minimal, correct amount of
 /// backticks tha are needed to guard the inner code
-fn max_backtick_size(code_block: &str) -> usize {
+pub(crate) fn max_backtick_size(code_block: &str) -> usize {
     let mut max = 2;
     for line in code_block.lines() {
         let mut count = 0;
@@ -178,25 +391,46 @@ fn max_backtick_size(code_block: &str) -> usize {
 mod tests {
 
     use std::path::PathBuf;
+    use std::sync::Arc;
     use std::time::Duration;
 
+    use super::MarkdownDocumentGenerator;
     use super::MarkdownTestCaseGenerator;
     use super::MarkdownUpdateGenerator;
+    use crate::assertions::assertion::Assertion;
+    use crate::assertions::registry::AssertionRegistry;
+    use crate::config::DocumentConfig;
     use crate::config::TestCaseConfig;
     use crate::config::TestCaseWait;
     use crate::diff::Diff;
     use crate::diff::DiffLine;
     use crate::escaping::Escaper;
+    use crate::executors::powershell_runner::POWERSHELL_PROMPT;
+    use crate::expectation::tests::expectation_maker;
     use crate::formatln;
+    use crate::generators::generator::DocumentGenerator;
+    use crate::generators::generator::TestCaseGenerator;
+    use crate::generators::generator::UpdateGenerator;
     use crate::generators::generator::tests::UpdateGeneratorTest;
     use crate::generators::generator::tests::run_update_generator_tests;
     use crate::generators::generator::tests::standard_testcase_generator_test_suite;
     use crate::outcome::Outcome;
+    use crate::parsers::markdown::DEFAULT_MARKDOWN_LANGUAGES;
+    use crate::parsers::markdown::MarkdownParser;
+    use crate::parsers::parser::Parser;
     use crate::parsers::parser::ParserType;
     use crate::test_expectation;
+    use crate::testcase::ExpectedExitCode;
     use crate::testcase::TestCase;
     use crate::testcase::TestCaseError;
 
+    fn assertion(line: &str) -> Box<dyn Assertion> {
+        AssertionRegistry::default()
+            .parse(line)
+            .expect("line is an assertion")
+            .expect("make assertion")
+    }
+
     #[test]
     fn test_update_generator() {
         let tests: &[(&str, UpdateGeneratorTest)] = &[
@@ -272,6 +506,51 @@ mod tests {
                     }],
                 },
             ),
+            (
+                "frozen_expectations",
+                UpdateGeneratorTest {
+                    original_document: &([
+                        "This is a test",
+                        "",
+                        "```scrut {frozen: true}",
+                        "$ the command",
+                        "an expectation",
+                        "[3]",
+                        "```",
+                    ]
+                    .join("\n")
+                        + "\n"),
+
+                    outcomes: vec![Outcome {
+                        location: None,
+                        output: ("new output\n", "", Some(0)).into(),
+                        testcase: TestCase {
+                            title: "This is a test".to_string(),
+                            shell_expression: "the command".to_string(),
+                            expectations: vec![test_expectation!(
+                                "equal",
+                                "an expectation",
+                                false,
+                                false,
+                                "an expectation"
+                            )],
+                            exit_code: Some(ExpectedExitCode::Code(3)),
+                            line_number: 234,
+                            config: TestCaseConfig {
+                                frozen: Some(true),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        result: Err(TestCaseError::InvalidExitCode {
+                            actual: 0,
+                            expected: ExpectedExitCode::Code(3),
+                        }),
+                        escaping: Escaper::default(),
+                        format: ParserType::Markdown,
+                    }],
+                },
+            ),
             (
                 "updated_output_expectations",
                 UpdateGeneratorTest {
@@ -311,6 +590,96 @@ mod tests {
                     }],
                 },
             ),
+            (
+                "updated_output_keeps_variants",
+                UpdateGeneratorTest {
+                    original_document: &([
+                        "This is a test",
+                        "",
+                        "```scrut",
+                        "$ the command",
+                        "an expectation",
+                        "(or)",
+                        "another expectation",
+                        "```",
+                    ]
+                    .join("\n")
+                        + "\n"),
+
+                    outcomes: vec![Outcome {
+                        location: None,
+                        output: ("new output\n", "").into(),
+                        testcase: TestCase {
+                            title: "This is a test".to_string(),
+                            shell_expression: "the command".to_string(),
+                            expectations: vec![test_expectation!("equal", "an expectation")],
+                            variants: vec![vec![test_expectation!("another expectation")]],
+                            exit_code: None,
+                            line_number: 234,
+                            ..Default::default()
+                        },
+                        result: Err(TestCaseError::MalformedOutput(Diff::new(vec![
+                            DiffLine::UnmatchedExpectation {
+                                index: 0,
+                                expectation: test_expectation!("equal", "an expectation"),
+                            },
+                            DiffLine::UnexpectedLines {
+                                lines: vec![(0, formatln!("new output").as_bytes().to_vec())],
+                            },
+                        ]))),
+                        escaping: Escaper::default(),
+                        format: ParserType::Markdown,
+                    }],
+                },
+            ),
+            (
+                "updated_output_keeps_assertions",
+                UpdateGeneratorTest {
+                    original_document: &([
+                        "This is a test",
+                        "",
+                        "```scrut",
+                        "$ the command",
+                        "an expectation",
+                        "@assert exit_code in 0..2",
+                        "@assert file_exists out.txt",
+                        "```",
+                    ]
+                    .join("\n")
+                        + "\n"),
+
+                    outcomes: vec![Outcome {
+                        location: None,
+                        output: ("new output\n@assert new output\n", "", Some(1)).into(),
+                        testcase: TestCase {
+                            title: "This is a test".to_string(),
+                            shell_expression: "the command".to_string(),
+                            expectations: vec![test_expectation!("equal", "an expectation")],
+                            exit_code: None,
+                            assertions: vec![
+                                assertion("@assert exit_code in 0..2"),
+                                assertion("@assert file_exists out.txt"),
+                            ],
+                            line_number: 234,
+                            ..Default::default()
+                        },
+                        result: Err(TestCaseError::MalformedOutput(Diff::new(vec![
+                            DiffLine::UnmatchedExpectation {
+                                index: 0,
+                                expectation: test_expectation!("equal", "an expectation"),
+                            },
+                            DiffLine::UnexpectedLines {
+                                lines: vec![
+                                    (0, formatln!("new output").as_bytes().to_vec()),
+                                    (1, formatln!("@assert new output").as_bytes().to_vec()),
+                                ],
+                            },
+                        ]))),
+                        escaping: Escaper::default(),
+                        format: ParserType::Markdown,
+                    }],
+                },
+            ),
             (
                 "updated_output_none_zero_exit_code",
                 UpdateGeneratorTest {
@@ -338,7 +707,7 @@ mod tests {
                         },
                         result: Err(TestCaseError::InvalidExitCode {
                             actual: 10,
-                            expected: 0,
+                            expected: 0.into(),
                         }),
                         escaping: Escaper::default(),
                         format: ParserType::Markdown,
@@ -371,7 +740,7 @@ mod tests {
                         },
                         result: Err(TestCaseError::InvalidExitCode {
                             actual: 10,
-                            expected: 0,
+                            expected: 0.into(),
                         }),
                         escaping: Escaper::default(),
                         format: ParserType::Markdown,
@@ -406,7 +775,7 @@ mod tests {
                         },
                         result: Err(TestCaseError::InvalidExitCode {
                             actual: 20,
-                            expected: 10,
+                            expected: 10.into(),
                         }),
                         escaping: Escaper::default(),
                         format: ParserType::Markdown,
@@ -521,14 +890,21 @@ mod tests {
                         testcase: TestCase {
                             title: "This is a test".to_string(),
                             shell_expression: "the command".to_string(),
+                            template_arguments: None,
+                            interaction: vec![],
                             expectations: vec![test_expectation!("equal", "an expectation")],
+                            variants: vec![],
                             exit_code: None,
+                            assertions: vec![],
                             line_number: 234,
                             config: TestCaseConfig {
                                 timeout: Some(Duration::from_secs(3 * 60 + 4)),
                                 wait: Some(TestCaseWait {
                                     timeout: Duration::from_secs(4 * 60 + 5),
                                     path: Some(PathBuf::from("some-path")),
+                                    port: None,
+                                    http: None,
+                                    status: None,
                                 }),
                                 ..Default::default()
                             },
@@ -567,14 +943,21 @@ mod tests {
                         testcase: TestCase {
                             title: "This is a test".to_string(),
                             shell_expression: "the command".to_string(),
+                            template_arguments: None,
+                            interaction: vec![],
                             expectations: vec![test_expectation!("equal", "an expectation")],
+                            variants: vec![],
                             exit_code: None,
+                            assertions: vec![],
                             line_number: 234,
                             config: TestCaseConfig {
                                 timeout: Some(Duration::from_secs(3 * 60 + 4)),
                                 wait: Some(TestCaseWait {
                                     timeout: Duration::from_secs(4 * 60 + 5),
                                     path: Some(PathBuf::from("some-path")),
+                                    port: None,
+                                    http: None,
+                                    status: None,
                                 }),
                                 ..Default::default()
                             },
@@ -734,4 +1117,255 @@ mod tests {
         let generator = MarkdownTestCaseGenerator::default();
         standard_testcase_generator_test_suite(generator, "markdown")
     }
+
+    fn parse_markdown(document: &str) -> (DocumentConfig, Vec<TestCase>) {
+        let (config, testcases) = MarkdownParser::new(
+            Arc::new(expectation_maker()),
+            DEFAULT_MARKDOWN_LANGUAGES,
+            None,
+        )
+        .parse(document)
+        .expect("parse markdown document");
+        let testcases = testcases
+            .into_iter()
+            .map(|testcase| TestCase {
+                line_number: 0,
+                ..testcase
+            })
+            .collect();
+        (config, testcases)
+    }
+
+    #[test]
+    fn test_document_generator_round_trip() {
+        let document = r#"---
+total_timeout: 1m
+command_template: cli {args}
+environment:
+  SHARED: value
+defaults:
+  timeout: 10s
+---
+
+# Some document
+
+With prose that is not part of any test.
+
+## Simple title
+
+```scrut
+$ echo hello
+hello
+```
+
+```scrut
+% --flag \
+>   value
+```
+
+A paragraph title
+### with a header
+
+````scrut {timeout: 20s, environment: {OWN: "it's \"quoted\""}, detached_kill_signal: quit}
+# a comment
+$ printf 'a\nb\n' &&
+>
+> echo ```
+[ab] (regex)
+b* (glob+)
+``` (?)
+[0]
+````
+
+```bash
+echo not a test
+```
+
+```scrut
+$ cat <<EOF
+$ raw body
+
+[1]
+EOF
+$ raw body
+
+[1] (equal)
+```
+
+```scrut {output_stream: stderr}
+$ echo 'foo (regex)' 1>&2; exit 3
+foo (regex) (equal)
+[3] (equal)
+tab\there (escaped)
+	no eol (no-eol)
+[3]
+```
+
+```scrut {tty: true}
+$ read -p 'Name? ' name && echo "Hello $name"
+? Name\? $
+! Alice
+!
+Name? Alice
+Hello Alice
+```
+"#;
+        let (config, testcases) = parse_markdown(document);
+        let generated = MarkdownDocumentGenerator::default()
+            .generate_document(&config, &testcases)
+            .expect("generate document");
+        assert_eq!(
+            generated,
+            r#"---
+command_template: cli {args}
+defaults:
+  timeout: 10s
+environment:
+  SHARED: value
+total_timeout: 1m
+---
+
+Simple title
+
+```scrut
+$ echo hello
+hello
+```
+
+```scrut
+% --flag \
+>   value
+```
+
+A paragraph title
+with a header
+
+````scrut {timeout: 20s, detached_kill_signal: quit, environment: {OWN: "it's \"quoted\""}}
+$ printf 'a\nb\n' &&
+>
+> echo ```
+[ab] (regex)
+b* (glob+)
+``` (?)
+[0]
+````
+
+```scrut
+$ cat <<EOF
+> $ raw body
+>
+> [1]
+> EOF
+$ raw body
+
+[1] (equal)
+```
+
+```scrut {output_stream: stderr}
+$ echo 'foo (regex)' 1>&2; exit 3
+foo (regex) (equal)
+[3] (equal)
+tab\there (escaped)
+	no eol (no-eol)
+[3]
+```
+
+```scrut {tty: true}
+$ read -p 'Name? ' name && echo "Hello $name"
+? Name\? $
+! Alice
+!
+Name? Alice
+Hello Alice
+```
+"#
+        );
+        assert_eq!(parse_markdown(&generated), (config, testcases));
+    }
+
+    #[test]
+    fn test_powershell_prompt() {
+        let outcome = Outcome {
+            location: None,
+            output: ("hello\n", "").into(),
+            testcase: TestCase {
+                title: "".into(),
+                expectations: vec![test_expectation!("hello")],
+                ..TestCase::from_expression("Write-Output hello\n| Write-Output")
+            },
+            result: Ok(()),
+            escaping: Escaper::default(),
+            format: ParserType::Markdown,
+        };
+        let expected = "PS> Write-Output hello\n> | Write-Output\nhello\n";
+
+        let updated = MarkdownUpdateGenerator::default()
+            .generate_update(
+                "```scrut\nPS> Write-Output hello\n> | Write-Output\n```\n",
+                &[&outcome],
+            )
+            .expect("update document");
+        assert_eq!(format!("```scrut\n{expected}```\n"), updated);
+
+        let generated = MarkdownTestCaseGenerator::default()
+            .with_prompt(POWERSHELL_PROMPT)
+            .generate_testcases(&[&outcome])
+            .expect("generate testcase");
+        assert_eq!(format!("```scrut\n{expected}```\n"), generated);
+
+        let config = DocumentConfig {
+            shell: Some("pwsh".into()),
+            ..Default::default()
+        };
+        let testcases = vec![TestCase {
+            expectations: vec![test_expectation!("hello")],
+            ..TestCase::from_expression("Write-Output hello")
+        }];
+        let generated = MarkdownDocumentGenerator::default()
+            .generate_document(&config, &testcases)
+            .expect("generate document");
+        assert_eq!(
+            "---\nshell: pwsh\n---\n\nTest\n\n```scrut\nPS> Write-Output hello\nhello\n```\n",
+            generated
+        );
+        let (_, parsed) = parse_markdown(&generated);
+        assert_eq!(testcases[0].shell_expression, parsed[0].shell_expression);
+    }
+
+    #[test]
+    fn test_document_generator_fails_on_inexpressible_testcases() {
+        let generator = MarkdownDocumentGenerator::default();
+        for testcase in [
+            TestCase {
+                title: "A title\n\nwith an empty line".to_string(),
+                ..TestCase::from_expression("echo hello")
+            },
+            TestCase {
+                expectations: vec![test_expectation!("> continued")],
+                ..TestCase::from_expression("echo hello")
+            },
+            TestCase {
+                exit_code: Some((-1).into()),
+                ..TestCase::from_expression("echo hello")
+            },
+            TestCase {
+                expectations: vec![test_expectation!("? prompt")],
+                config: TestCaseConfig {
+                    tty: Some(true),
+                    ..Default::default()
+                },
+                ..TestCase::from_expression("echo hello")
+            },
+        ] {
+            assert!(
+                generator
+                    .generate_document(
+                        &DocumentConfig::default_markdown(),
+                        std::slice::from_ref(&testcase)
+                    )
+                    .is_err(),
+                "testcase {testcase:?} must not be rendered"
+            );
+        }
+    }
 }
@@ -13,10 +13,13 @@
 //!   used in creation of new test files
 //! - [`generator::UpdateGenerator`], updating all [`crate::testcase::TestCase`]s
 //!   that are found in an existing test file
+//! - [`generator::DocumentGenerator`], rendering a whole test file from its
+//!   parsed configuration and [`crate::testcase::TestCase`]s, so that tools can
+//!   rewrite test files programmatically
 //!
 //! These traits are implemented as
 //! - Markdown syntax: [`markdown::MarkdownTestCaseGenerator`],
-//!   [`markdown::MarkdownUpdateGenerator`]
+//!   [`markdown::MarkdownUpdateGenerator`], [`markdown::MarkdownDocumentGenerator`]
 //! - Cram syntax: [`cram::CramTestCaseGenerator`], [`cram::CramUpdateGenerator`]
 
 pub mod cram;
@@ -8,7 +8,15 @@
 use anyhow::Result;
 use anyhow::bail;
 
+use crate::assertions::assertion::ASSERTION_PREFIX;
+use crate::config::OutputEncoding;
 use crate::diff::DiffLine;
+use crate::expectation::Expectation;
+use crate::expectation::IGNORE_REMAINING;
+use crate::expectation::UNORDERED_END;
+use crate::expectation::UNORDERED_START;
+use crate::expectation::VARIANT_SEPARATOR;
+use crate::expectation::unordered_marker;
 use crate::formatln;
 use crate::lossy_string;
 use crate::newline::BytesNewline;
@@ -16,6 +24,9 @@ use crate::newline::SplitLinesByNewline;
 use crate::newline::StringNewline;
 use crate::outcome::Outcome;
 use crate::output::ExitStatus;
+use crate::parsers::line_parser::extract_exit_code;
+use crate::rules::checksum::sha256_hex;
+use crate::testcase::ExpectedExitCode;
 use crate::testcase::TestCaseError;
 
 pub(super) trait OutcomeTestGenerator {
@@ -24,101 +35,361 @@ pub(super) trait OutcomeTestGenerator {
 
 impl Outcome {
     fn generate_testcase_expression(&self) -> String {
-        // prepend by command
-        let expression_lines = self.testcase.shell_expression.as_bytes();
+        // prepend by command, or by the shorthand it was rendered from
+        let (prefix, expression) = match &self.testcase.template_arguments {
+            Some(arguments) => ("%", arguments),
+            None => ("$", &self.testcase.shell_expression),
+        };
+        let expression_lines = expression.as_bytes();
         let expression_lines = expression_lines.split_at_newline();
-        let mut generated = format!("$ {}", lossy_string!(&expression_lines[0].assure_newline()));
+        let mut generated = format!(
+            "{} {}",
+            prefix,
+            lossy_string!(&expression_lines[0].assure_newline())
+        );
         expression_lines.iter().skip(1).for_each(|line| {
-            generated.push_str(&format!(
-                "> {}",
-                lossy_string!(&(&line[..]).assure_newline())
-            ))
+            // empty lines, as in here-documents, without trailing whitespace
+            let line = lossy_string!(&(&line[..]).assure_newline());
+            if line.trim_newlines().is_empty() {
+                generated.push_str(&">".assure_newline())
+            } else {
+                generated.push_str(&format!("> {}", line))
+            }
         });
+        self.testcase
+            .interaction
+            .iter()
+            .for_each(|step| generated.push_str(&formatln!("{}", step)));
         generated
     }
 
+    /// Returns the exit code line, which keeps an expected exit code that is
+    /// not exact (e.g. `[!0]`), as long as the actual exit code matches it.
+    /// There is none if the matching exit code is an expected signal from
+    /// the configuration.
     fn generate_testcase_exit_code(&self) -> Option<String> {
-        match &self.output.exit_code {
-            ExitStatus::Code(code) if *code != 0 => Some(formatln!("[{}]", code)),
-            _ => None,
+        let code = match self.output.exit_code {
+            ExitStatus::Code(_) | ExitStatus::Signal(_) => self.output.exit_code.as_code(),
+            _ => return None,
+        };
+        if self.testcase.has_exit_code_assertion() {
+            return None;
+        }
+        let expected = self.testcase.expected_exit_code();
+        if !expected.matches(&self.output.exit_code) {
+            return match self.output.exit_code {
+                ExitStatus::Code(0) => None,
+                ref status => Some(formatln!("[{}]", status)),
+            };
+        }
+        match &self.testcase.exit_code {
+            _ if self.testcase.config.expected_signal.is_some() => None,
+            Some(ExpectedExitCode::Code(_)) | None if code == 0 => None,
+            Some(ExpectedExitCode::Code(_)) | None => Some(formatln!("[{}]", code)),
+            Some(expected) => Some(formatln!("[{}]", expected)),
         }
     }
-}
 
-impl OutcomeTestGenerator for Outcome {
-    fn generate_testcase(&self) -> Result<String> {
-        match &self.result {
-            Ok(_) => {
+    /// Returns the checksum expectation of all output, for testcases whose
+    /// output is compared by its digest
+    fn generate_testcase_digest(&self) -> String {
+        formatln!(
+            "(sha256:{})",
+            sha256_hex(&self.testcase.encoded_output(&self.output))
+        )
+    }
+
+    /// Returns the alternative variants of the expectations, each following a
+    /// line `(or)`, which are kept as they are written
+    fn generate_testcase_variants(&self) -> String {
+        self.testcase
+            .variants
+            .iter()
+            .map(|variant| formatln!("{}", VARIANT_SEPARATOR) + &generate_expectations(variant))
+            .collect()
+    }
+
+    /// Returns the assertion lines, which follow the exit code
+    fn generate_testcase_assertions(&self) -> String {
+        self.testcase
+            .assertions
+            .iter()
+            .map(|assertion| formatln!("{}", assertion))
+            .collect()
+    }
+
+    /// Returns the testcase with its original expectations, which are valid
+    fn generate_valid_testcase(&self) -> String {
+        let mut generated = self.generate_testcase_expression();
+        generated.push_str(&generate_expectations(&self.testcase.expectations));
+        generated.push_str(&self.generate_testcase_variants());
+        if let Some(exit_code) = self.generate_testcase_exit_code() {
+            generated.push_str(&exit_code)
+        }
+        generated.push_str(&self.generate_testcase_assertions());
+        generated
+    }
+
+    /// Returns the testcase as it is written, whatever the outcome
+    fn generate_frozen_testcase(&self) -> String {
+        let mut generated = self.generate_testcase_expression();
+        generated.push_str(&generate_expectations(&self.testcase.expectations));
+        generated.push_str(&self.generate_testcase_variants());
+        match &self.testcase.exit_code {
+            Some(ExpectedExitCode::Code(0)) | None => {}
+            Some(expected) => generated.push_str(&formatln!("[{}]", expected)),
+        }
+        generated.push_str(&self.generate_testcase_assertions());
+        generated
+    }
+
+    fn generate_testcase_from_error(&self, err: &TestCaseError) -> Result<String> {
+        match err {
+            // output that is compared by digest is written as such
+            TestCaseError::MalformedOutput(_)
+                if self.testcase.config.output_encoding == Some(OutputEncoding::Hash) =>
+            {
                 let mut generated = self.generate_testcase_expression();
-                self.testcase.expectations.iter().for_each(|expectation| {
-                    generated.push_str(&expectation.original_string().assure_newline())
-                });
+                generated.push_str(&self.generate_testcase_digest());
+                generated.push_str(&self.generate_testcase_variants());
                 if let Some(exit_code) = self.generate_testcase_exit_code() {
                     generated.push_str(&exit_code)
                 }
+                generated.push_str(&self.generate_testcase_assertions());
                 Ok(generated)
             }
-            Err(err) => match err {
-                TestCaseError::MalformedOutput(diff) => {
-                    let mut generated = self.generate_testcase_expression();
-
-                    // output the actual recorded output lines
-                    for diff_line in diff.lines.iter() {
-                        match diff_line {
-                            DiffLine::MatchedExpectation {
-                                index: _,
-                                expectation,
-                                lines: _,
-                            } => {
-                                generated.push_str(&expectation.original_string().assure_newline())
+            TestCaseError::MalformedOutput(diff) => {
+                let mut generated = self.generate_testcase_expression();
+                let mut unordered = false;
+
+                // expectations that assert absence are not part of the diff, so
+                // they are kept in front of the expectations that followed them
+                let mut absences = self
+                    .testcase
+                    .expectations
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, expectation)| expectation.rule.asserts_absence())
+                    .peekable();
+
+                // output the actual recorded output lines
+                for (position, diff_line) in diff.lines.iter().enumerate() {
+                    if let DiffLine::MatchedExpectation { index, .. }
+                    | DiffLine::UnmatchedExpectation { index, .. } = diff_line
+                    {
+                        while let Some((_, absence)) =
+                            absences.next_if(|(absence_index, _)| absence_index < index)
+                        {
+                            push_unordered_marker(&mut generated, &mut unordered, false);
+                            generated.push_str(&absence.original_string().assure_newline());
+                        }
+                    }
+                    match diff_line {
+                        // expectations of all remaining output, like checksums, are
+                        // updated to match it, if they can be
+                        DiffLine::UnmatchedExpectation {
+                            index: _,
+                            expectation,
+                        } if expectation.rule.matches_remaining_output() => {
+                            let remaining = diff.remaining_output(position);
+                            if let Some(updated) = expectation.rule.updated_expression(&remaining) {
+                                push_unordered_marker(&mut generated, &mut unordered, false);
+                                generated.push_str(&updated.assure_newline());
+                                break;
                             }
-                            DiffLine::UnexpectedLines { lines } => {
-                                for (_, line) in lines {
-                                    let suffix = if line.ends_with(b"\n") {
-                                        ""
-                                    } else {
-                                        " (no-eol)"
-                                    };
-                                    let line = formatln!(
-                                        "{}{}",
-                                        self.escaping
-                                            .escaped_expectation((&line[..]).trim_newlines()),
-                                        suffix
-                                    );
-                                    generated.push_str(&line)
+                        }
+                        DiffLine::UnmatchedExpectation {
+                            index: _,
+                            expectation,
+                        } => {
+                            if let Some((path, _)) = expectation.rule.file_checksum() {
+                                if let Some(Some(digest)) = self.output.file_digests.get(path) {
+                                    push_unordered_marker(&mut generated, &mut unordered, false);
+                                    generated.push_str(&formatln!("{} (sha256:{})", path, digest));
                                 }
                             }
-                            _ => continue,
+                        }
+                        DiffLine::MatchedExpectation {
+                            index: _,
+                            expectation,
+                            lines: _,
+                        } => {
+                            push_unordered_marker(
+                                &mut generated,
+                                &mut unordered,
+                                expectation.unordered,
+                            );
+                            generated.push_str(&expectation.original_string().assure_newline())
+                        }
+                        DiffLine::UnexpectedLines { lines } => {
+                            push_unordered_marker(&mut generated, &mut unordered, false);
+                            for (_, line) in lines {
+                                let suffix = if line.ends_with(b"\n") {
+                                    ""
+                                } else {
+                                    " (no-eol)"
+                                };
+                                let line = formatln!(
+                                    "{}{}",
+                                    unambiguous_expectation(
+                                        self.escaping
+                                            .escaped_expectation((&line[..]).trim_newlines())
+                                    ),
+                                    suffix
+                                );
+                                generated.push_str(&line)
+                            }
                         }
                     }
-                    if let Some(exit_code) = self.generate_testcase_exit_code() {
-                        generated.push_str(&exit_code)
-                    }
-                    Ok(generated)
                 }
-                TestCaseError::InvalidExitCode {
-                    actual,
-                    expected: _,
-                } => {
-                    let mut generated = self.generate_testcase_expression();
-                    let mut output = self.output.stdout.to_output_string(None, &self.escaping);
-                    if !output.is_empty() && !output.ends_with('\n') {
-                        output.push_str(" (no-eol)\n")
-                    }
-                    generated.push_str(&output);
-                    generated.push_str(&formatln!("[{}]", *actual));
-                    Ok(generated)
+                for (_, absence) in absences {
+                    push_unordered_marker(&mut generated, &mut unordered, false);
+                    generated.push_str(&absence.original_string().assure_newline());
+                }
+                generated.push_str(&self.generate_testcase_variants());
+                if let Some(exit_code) = self.generate_testcase_exit_code() {
+                    generated.push_str(&exit_code)
                 }
-                TestCaseError::InternalError(err) => {
-                    bail!("cannot generate testcase from internal error: {}", err)
+                generated.push_str(&self.generate_testcase_assertions());
+                Ok(generated)
+            }
+            TestCaseError::InvalidExitCode {
+                actual,
+                expected: _,
+            } => {
+                let mut generated = self.generate_testcase_expression();
+                let mut output = match self.testcase.config.output_encoding {
+                    Some(OutputEncoding::Hash) => self.generate_testcase_digest(),
+                    Some(OutputEncoding::Bytes) => {
+                        lossy_string!(&self.testcase.encoded_output(&self.output))
+                    }
+                    _ => self
+                        .output
+                        .stdout
+                        .to_output_string(None, &self.escaping)
+                        .split_inclusive('\n')
+                        .map(|line| match line.strip_suffix('\n') {
+                            Some(line) => formatln!("{}", unambiguous_expectation(line.into())),
+                            None => line.into(),
+                        })
+                        .collect::<String>(),
+                };
+                if !output.is_empty() && !output.ends_with('\n') {
+                    output.push_str(" (no-eol)\n")
                 }
-                TestCaseError::Timeout => {
-                    bail!("cannot generate timed out testcase")
+                generated.push_str(&output);
+                self.testcase
+                    .expectations
+                    .iter()
+                    .filter(|expectation| expectation.rule.asserts_absence())
+                    .for_each(|absence| {
+                        generated.push_str(&absence.original_string().assure_newline())
+                    });
+                generated.push_str(&self.generate_testcase_variants());
+                match self.output.exit_code {
+                    ExitStatus::Signal(_) => {
+                        generated.push_str(&formatln!("[{}]", self.output.exit_code))
+                    }
+                    _ => generated.push_str(&formatln!("[{}]", *actual)),
                 }
-                TestCaseError::Skipped => {
-                    bail!("cannot generate skipped testcase")
+                generated.push_str(&self.generate_testcase_assertions());
+                Ok(generated)
+            }
+            TestCaseError::InternalError(err) => {
+                bail!("cannot generate testcase from internal error: {}", err)
+            }
+            TestCaseError::Timeout => {
+                bail!("cannot generate timed out testcase")
+            }
+            TestCaseError::Skipped => {
+                bail!("cannot generate skipped testcase")
+            }
+            TestCaseError::NonDeterministic(_) => {
+                bail!("cannot generate testcase with non-deterministic output")
+            }
+            TestCaseError::NoAssertions => {
+                bail!("cannot generate testcase without assertions")
+            }
+            TestCaseError::ExceededMaxOutputSize { .. } => {
+                bail!("cannot generate testcase with output exceeding its max output size")
+            }
+            TestCaseError::InvalidUtf8Output { .. } => {
+                bail!("cannot generate testcase with output that is not valid UTF-8")
+            }
+
+            // the output is valid, only its duration, memory usage or the side
+            // effects that assertions check are not
+            TestCaseError::ExceededMaxDuration { .. }
+            | TestCaseError::ExceededMaxRss { .. }
+            | TestCaseError::FailedAssertion { .. } => Ok(self.generate_valid_testcase()),
+            TestCaseError::MultipleFailures(failures) => {
+                // the output is generated with the actual exit code
+                let failure = failures
+                    .iter()
+                    .find(|failure| matches!(failure, TestCaseError::MalformedOutput(_)))
+                    .or(failures.first());
+                match failure {
+                    Some(failure) => self.generate_testcase_from_error(failure),
+                    None => bail!("cannot generate testcase without failures"),
                 }
-            },
+            }
+        }
+    }
+}
+
+/// Output lines that would be read as exit code (e.g. when a here-document
+/// with such a line is printed), as start or end of a group of unordered
+/// expectations, as separator of variants or as ignoring remaining output are
+/// written with an explicit
+/// equality rule,
+/// output lines that would be read as assertion with an anchored regex rule
+fn unambiguous_expectation(line: String) -> String {
+    if line.starts_with(ASSERTION_PREFIX) {
+        format!("^{}$ (regex)", regex::escape(&line))
+    } else if extract_exit_code(&line).is_some()
+        || [
+            UNORDERED_START,
+            UNORDERED_END,
+            VARIANT_SEPARATOR,
+            IGNORE_REMAINING,
+        ]
+        .contains(&line.as_str())
+    {
+        format!("{line} (equal)")
+    } else {
+        line
+    }
+}
+
+/// Returns the lines of the expectations as they are written, in between the
+/// lines that group unordered expectations
+fn generate_expectations(expectations: &[Expectation]) -> String {
+    let mut generated = String::new();
+    let mut unordered = false;
+    for expectation in expectations {
+        push_unordered_marker(&mut generated, &mut unordered, expectation.unordered);
+        generated.push_str(&expectation.original_string().assure_newline());
+    }
+    generated
+}
+
+/// Appends the line that starts or ends a group of unordered expectations, if
+/// the next line is not in the same kind of group as the previous line
+fn push_unordered_marker(generated: &mut String, unordered: &mut bool, next: bool) {
+    if let Some(marker) = unordered_marker(*unordered, next) {
+        generated.push_str(&formatln!("{}", marker));
+    }
+    *unordered = next;
+}
+
+impl OutcomeTestGenerator for Outcome {
+    fn generate_testcase(&self) -> Result<String> {
+        if self.testcase.config.is_frozen() {
+            return Ok(self.generate_frozen_testcase());
+        }
+        match &self.result {
+            Ok(_) => Ok(self.generate_valid_testcase()),
+            Err(err) => self.generate_testcase_from_error(err),
         }
     }
 }
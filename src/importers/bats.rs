@@ -0,0 +1,674 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use regex::Regex;
+
+use super::importer::ANY_LINE;
+use super::importer::ANY_LINES;
+use super::importer::Attention;
+use super::importer::ImportedBlock;
+use super::importer::ImportedDocument;
+use super::importer::ImportedSection;
+use super::importer::ImportedTestCase;
+use super::importer::Importer;
+use super::importer::literal_expectation;
+use super::shell::Command;
+use super::shell::Word;
+use super::shell::logical_commands;
+use super::shell::parse_words;
+use crate::config::DocumentConfig;
+use crate::config::OutputStreamControl;
+use crate::config::TestCaseConfig;
+
+lazy_static! {
+    static ref TEST_HEADER: Regex =
+        Regex::new(r#"^@test\s+(?:"((?:[^"\\]|\\.)*)"|'([^']*)')\s*\{\s*$"#)
+            .expect("bats test header regex must compile");
+    static ref HOOK_DEFINITION: Regex = Regex::new(
+        r"^(?:function\s+)?(setup|teardown|setup_file|teardown_file)\s*(?:\(\s*\))?\s*\{"
+    )
+    .expect("bats hook definition regex must compile");
+    static ref RUN: Regex = Regex::new(r"^run(?:\s|$)").expect("bats run regex must compile");
+    static ref NESTED_RUN: Regex =
+        Regex::new(r"(?:^|[;&|{(]\s*|\n\s*)run\s").expect("bats nested run regex must compile");
+    static ref SKIP: Regex =
+        Regex::new(r"(?:^|[;&|]\s*)skip(?:\s|$)").expect("bats skip regex must compile");
+    static ref LOAD: Regex = Regex::new(r"^load\s+").expect("bats load regex must compile");
+    static ref RUN_VARIABLES: Regex =
+        Regex::new(r"\$\{?#?(?:output|status|lines|stderr|stderr_lines)\b")
+            .expect("bats run variables regex must compile");
+    static ref TEMPORARY_DIRECTORY: Regex = Regex::new(r"\bBATS_(?:TEST_|FILE_|RUN_)?TMPDIR\b")
+        .expect("bats temporary directory regex must compile");
+    static ref UNSUPPORTED_VARIABLE: Regex =
+        Regex::new(r"\bBATS_[A-Z_]+\b").expect("bats variable regex must compile");
+    static ref LINE_VARIABLE: Regex =
+        Regex::new(r"^\$\{lines\[([0-9]+)\]\}$").expect("bats line variable regex must compile");
+}
+
+/// Converts test files of the Bash Automated Testing System (bats) into Scrut
+/// documents:
+/// - every `@test` becomes a section, in which every `run` becomes a testcase
+///   with output expectations and exit code from the subsequent assertions on
+///   `$output`, `${lines[N]}` and `$status` (including the `bats-assert`
+///   helpers `assert_success`, `assert_failure N`, `assert_output` and
+///   `assert_line --index N`)
+/// - all other commands become testcases that must succeed, but whose output
+///   is ignored, as in bats
+/// - code outside of tests (helpers, `load`, hook functions) is executed at
+///   the start of the document and hook functions are called where bats calls
+///   them
+///
+/// Anything that cannot be converted faithfully is kept as [`Attention`].
+#[derive(Default)]
+pub struct BatsImporter;
+
+impl Importer for BatsImporter {
+    fn import(&self, content: &str) -> Result<ImportedDocument> {
+        let mut setup_code = vec![];
+        let mut setup_attentions = vec![];
+        let mut hooks = vec![];
+        let mut tests = vec![];
+
+        for command in logical_commands(content, 1) {
+            if command.line_number == 1 && command.text.starts_with("#!") {
+                continue;
+            }
+            if command.text.starts_with("@test") {
+                tests.push(command);
+                continue;
+            }
+            if let Some(captures) = HOOK_DEFINITION.captures(&command.text) {
+                hooks.push(captures[1].to_string());
+            }
+            let text = rewrite_variables(&command.text);
+            if text.starts_with("bats_require_minimum_version") {
+                continue;
+            }
+            if let Some(attention) = unsupported(&command.line_number, &text) {
+                setup_attentions.push(attention);
+            } else if text.starts_with("bats_load_library") {
+                setup_attentions.push(Attention {
+                    line_number: command.line_number,
+                    reason: "libraries cannot be loaded by name, source them from their path"
+                        .into(),
+                    code: Some(command.text.clone()),
+                });
+            } else if LOAD.is_match(&text) {
+                setup_code.push(convert_load(&text));
+            } else {
+                setup_code.push(text);
+            }
+        }
+        let has_hook = |name: &str| hooks.iter().any(|hook| hook == name);
+
+        let mut sections = vec![];
+        if !setup_code.is_empty() || !setup_attentions.is_empty() {
+            let mut blocks = vec![];
+            if !setup_code.is_empty() {
+                blocks.push(ImportedBlock::TestCase(ImportedTestCase::any_output(
+                    &setup_code.join("\n"),
+                )));
+            }
+            blocks.extend(setup_attentions.into_iter().map(ImportedBlock::Attention));
+            if has_hook("setup_file") {
+                blocks.push(ImportedBlock::TestCase(ImportedTestCase::any_output(
+                    "setup_file",
+                )));
+            }
+            sections.push(ImportedSection {
+                title: "Setup".into(),
+                blocks,
+            });
+        }
+
+        for test in tests {
+            let mut section = convert_test(&test);
+            if has_hook("setup") {
+                section.blocks.insert(
+                    0,
+                    ImportedBlock::TestCase(ImportedTestCase::any_output("setup")),
+                );
+            }
+            if has_hook("teardown") {
+                section
+                    .blocks
+                    .push(ImportedBlock::TestCase(ImportedTestCase::any_output(
+                        "teardown",
+                    )));
+            }
+            sections.push(section);
+        }
+
+        if has_hook("teardown_file") {
+            sections.push(ImportedSection {
+                title: "Teardown".into(),
+                blocks: vec![ImportedBlock::TestCase(ImportedTestCase::any_output(
+                    "teardown_file",
+                ))],
+            });
+        }
+
+        Ok(ImportedDocument {
+            // bats captures STDOUT and STDERR combined in `$output`
+            config: DocumentConfig {
+                defaults: TestCaseConfig {
+                    output_stream: Some(OutputStreamControl::Combined),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            sections,
+        })
+    }
+}
+
+/// Converts a whole `@test "name" { .. }` block into a section
+fn convert_test(test: &Command) -> ImportedSection {
+    let lines = test.text.lines().collect::<Vec<_>>();
+    let header = TEST_HEADER.captures(lines[0]);
+    let (Some(header), true) = (
+        header,
+        lines.len() > 1 && lines[lines.len() - 1].trim() == "}",
+    ) else {
+        return ImportedSection {
+            title: format!("Test in line {}", test.line_number),
+            blocks: vec![ImportedBlock::Attention(Attention {
+                line_number: test.line_number,
+                reason: "unrecognized test definition".into(),
+                code: Some(test.text.clone()),
+            })],
+        };
+    };
+    let title = header
+        .get(1)
+        .map(|title| title.as_str().replace("\\\"", "\"").replace("\\\\", "\\"))
+        .or_else(|| header.get(2).map(|title| title.as_str().to_string()))
+        .unwrap_or_default();
+
+    let mut converter = TestConverter::default();
+    let body = lines[1..lines.len() - 1].join("\n");
+    for command in logical_commands(&body, test.line_number + 1) {
+        converter.convert(&command);
+    }
+    converter.finish_run();
+
+    ImportedSection {
+        title,
+        blocks: converter.blocks,
+    }
+}
+
+/// Collects the testcases of a single `@test`
+#[derive(Default)]
+struct TestConverter {
+    blocks: Vec<ImportedBlock>,
+    run: Option<PendingRun>,
+}
+
+/// A `run` command, whose assertions are still being collected
+#[derive(Default)]
+struct PendingRun {
+    line_number: usize,
+    shell_expression: String,
+    exit_code: Option<i32>,
+    output: Option<String>,
+    lines: BTreeMap<usize, String>,
+    line_count: Option<usize>,
+    attentions: Vec<Attention>,
+}
+
+/// An assertion on the result of a `run` command
+#[derive(Debug, PartialEq)]
+enum Assertion {
+    ExitCode(i32),
+    Output(String),
+    Line(usize, String),
+    LineCount(usize),
+}
+
+impl TestConverter {
+    fn convert(&mut self, command: &Command) {
+        let text = rewrite_variables(&command.text);
+        let attention = |reason: &str| Attention {
+            line_number: command.line_number,
+            reason: reason.into(),
+            code: Some(command.text.clone()),
+        };
+
+        if let Some(attention) = unsupported(&command.line_number, &text) {
+            self.attend(attention);
+        } else if SKIP.is_match(&text) {
+            self.attend(attention(
+                "single tests cannot be skipped, exit with code 80 to skip the whole document",
+            ));
+        } else if RUN.is_match(&text) {
+            self.finish_run();
+            self.start_run(command.line_number, &text);
+        } else if let Some(assertion) = (!text.contains('\n'))
+            .then(|| parse_assertion(&parse_words(&text).0))
+            .flatten()
+        {
+            match self.run {
+                Some(ref mut run) => run.assert(assertion),
+                None => self.attend(attention("assertion without a preceding `run`")),
+            }
+        } else if RUN_VARIABLES.is_match(&text) {
+            self.attend(attention(
+                "the variables of `run` are not available, use output expectations instead",
+            ));
+        } else if NESTED_RUN.is_match(&text) {
+            self.attend(attention(
+                "`run` within a compound command cannot be converted",
+            ));
+        } else {
+            self.finish_run();
+            self.blocks
+                .push(ImportedBlock::TestCase(ImportedTestCase::any_output(&text)));
+        }
+    }
+
+    /// Adds the attention after the current `run`, if any
+    fn attend(&mut self, attention: Attention) {
+        match self.run {
+            Some(ref mut run) => run.attentions.push(attention),
+            None => self.blocks.push(ImportedBlock::Attention(attention)),
+        }
+    }
+
+    fn start_run(&mut self, line_number: usize, text: &str) {
+        let mut run = PendingRun {
+            line_number,
+            ..Default::default()
+        };
+        let (words, _) = parse_words(text);
+        let mut rest = text.trim_start();
+        for (index, word) in words.iter().enumerate() {
+            let is_flag = match word.raw.as_str() {
+                _ if index == 0 => true,
+                "--keep-empty-lines" => true,
+                "--separate-stderr" => {
+                    run.attentions.push(Attention {
+                        line_number,
+                        reason: "`$stderr` is not available, STDOUT and STDERR are combined".into(),
+                        code: None,
+                    });
+                    true
+                }
+                "!" => {
+                    run.attentions.push(Attention {
+                        line_number,
+                        reason: "`run !` expects any failure, but an exact exit code is needed"
+                            .into(),
+                        code: None,
+                    });
+                    true
+                }
+                flag if flag.len() > 1 && flag.starts_with('-') => match flag[1..].parse::<i32>() {
+                    Ok(code) => {
+                        run.exit_code = Some(code);
+                        true
+                    }
+                    Err(_) => false,
+                },
+                _ => false,
+            };
+            if !is_flag {
+                break;
+            }
+            rest = rest[word.raw.len()..].trim_start();
+            if word.raw == "--" {
+                break;
+            }
+        }
+        run.shell_expression = rest.to_string();
+        self.run = Some(run);
+    }
+
+    fn finish_run(&mut self) {
+        let Some(run) = self.run.take() else {
+            return;
+        };
+        if run.exit_code.is_none() {
+            self.blocks.push(ImportedBlock::Attention(Attention {
+                line_number: run.line_number,
+                reason: "the exit status of `run` is not asserted, the test expects exit code 0"
+                    .into(),
+                code: None,
+            }));
+        }
+        let expectations = run.expectations();
+        self.blocks.push(ImportedBlock::TestCase(ImportedTestCase {
+            shell_expression: run.shell_expression,
+            expectations,
+            exit_code: run.exit_code.filter(|code| *code != 0),
+            ..Default::default()
+        }));
+        self.blocks
+            .extend(run.attentions.into_iter().map(ImportedBlock::Attention));
+    }
+}
+
+impl PendingRun {
+    fn assert(&mut self, assertion: Assertion) {
+        match assertion {
+            Assertion::ExitCode(code) => self.exit_code = Some(code),
+            Assertion::Output(output) => self.output = Some(output),
+            Assertion::Line(index, line) => {
+                self.lines.insert(index, line);
+            }
+            Assertion::LineCount(count) => self.line_count = Some(count),
+        }
+    }
+
+    /// Returns the expectations of the asserted output: the exact `$output`,
+    /// or all asserted `${lines[N]}`, with arbitrary lines in between
+    fn expectations(&self) -> Vec<String> {
+        if let Some(ref output) = self.output {
+            if output.is_empty() {
+                return vec![];
+            }
+            return output.split('\n').map(literal_expectation).collect();
+        }
+
+        let mut expectations = vec![];
+        let count = self
+            .lines
+            .keys()
+            .last()
+            .map(|index| index + 1)
+            .unwrap_or(0)
+            .max(self.line_count.unwrap_or(0));
+        for index in 0..count {
+            expectations.push(
+                self.lines
+                    .get(&index)
+                    .map(|line| literal_expectation(line))
+                    .unwrap_or_else(|| ANY_LINE.into()),
+            );
+        }
+        if self.line_count != Some(count) {
+            expectations.push(ANY_LINES.into());
+        }
+        expectations
+    }
+}
+
+/// Returns the assertion on the result of `run` that the command is, if any
+fn parse_assertion(words: &[Word]) -> Option<Assertion> {
+    let first = words.first()?.raw.as_str();
+    let last = words.last()?.raw.as_str();
+    match (first, words.len()) {
+        ("[", 5) if last == "]" => compare(&words[1], &words[2].value, &words[3], false),
+        ("[[", 5) if last == "]]" => compare(&words[1], &words[2].value, &words[3], true),
+        ("test", 4) => compare(&words[1], &words[2].value, &words[3], false),
+        ("assert_success", 1) => Some(Assertion::ExitCode(0)),
+        ("assert_failure", 2) => literal(&words[1])?.parse().ok().map(Assertion::ExitCode),
+        ("assert_output", 2) => literal(&words[1])
+            .filter(|output| !output.starts_with('-'))
+            .map(Assertion::Output),
+        ("refute_output", 1) => Some(Assertion::Output("".into())),
+        ("assert_line", 4) if ["--index", "-n"].contains(&words[1].raw.as_str()) => Some(
+            Assertion::Line(literal(&words[2])?.parse().ok()?, literal(&words[3])?),
+        ),
+        ("assert_equal", 3) => compare(&words[1], "=", &words[2], false),
+        _ => None,
+    }
+}
+
+/// Returns the assertion that compares a variable of `run` with a literal
+fn compare(left: &Word, operator: &str, right: &Word, is_pattern: bool) -> Option<Assertion> {
+    let (variable, value) = match (literal(left), literal(right)) {
+        (None, Some(value)) => (left, value),
+        (Some(value), None) => (right, value),
+        _ => return None,
+    };
+
+    // unquoted right-hand values in `[[ .. ]]` are patterns
+    if is_pattern && !right.raw.starts_with(['"', '\'']) && value.contains(['*', '?', '[']) {
+        return None;
+    }
+
+    let is_equal = ["=", "=="].contains(&operator);
+    let is_numeric_equal = is_equal || operator == "-eq";
+    match variable.value.as_str() {
+        "$status" | "${status}" if is_numeric_equal => value.parse().ok().map(Assertion::ExitCode),
+        "$output" | "${output}" if is_equal => Some(Assertion::Output(value)),
+        "${#lines[@]}" | "${#lines[*]}" if is_numeric_equal => {
+            value.parse().ok().map(Assertion::LineCount)
+        }
+        other if is_equal => {
+            let index = LINE_VARIABLE.captures(other)?[1].parse().ok()?;
+            Some(Assertion::Line(index, value))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the value of the word, if it does not contain any expansion
+fn literal(word: &Word) -> Option<String> {
+    (!word.dynamic).then(|| word.value.clone())
+}
+
+/// Replaces variables that bats provides with their Scrut equivalent
+fn rewrite_variables(text: &str) -> String {
+    TEMPORARY_DIRECTORY
+        .replace_all(&text.replace("BATS_TEST_DIRNAME", "TESTDIR"), "TMPDIR")
+        .into_owned()
+}
+
+/// Returns an attention, if the code uses variables of bats that have no Scrut
+/// equivalent
+fn unsupported(line_number: &usize, text: &str) -> Option<Attention> {
+    let variable = UNSUPPORTED_VARIABLE.find(text)?;
+    Some(Attention {
+        line_number: *line_number,
+        reason: format!("the bats variable `{}` is not available", variable.as_str()),
+        code: Some(text.to_string()),
+    })
+}
+
+/// Converts `load helper` into sourcing `helper.bash` from the test directory
+fn convert_load(text: &str) -> String {
+    let (words, _) = parse_words(text);
+    let mut path = words
+        .get(1)
+        .map(|word| word.value.clone())
+        .unwrap_or_default();
+    if !path.ends_with(".bash") {
+        path.push_str(".bash");
+    }
+    if path.starts_with('/') {
+        format!("source \"{path}\"")
+    } else {
+        format!("source \"$TESTDIR/{path}\"")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Assertion;
+    use super::BatsImporter;
+    use super::parse_assertion;
+    use crate::importers::importer::Importer;
+    use crate::importers::shell::parse_words;
+
+    #[test]
+    fn test_parse_assertion() {
+        let tests = [
+            (r#"[ "$status" -eq 0 ]"#, Some(Assertion::ExitCode(0))),
+            ("[ $status -eq 3 ]", Some(Assertion::ExitCode(3))),
+            ("[[ 2 == ${status} ]]", Some(Assertion::ExitCode(2))),
+            (r#"[ "$status" -ne 0 ]"#, None),
+            (
+                r#"[ "$output" = "foo bar" ]"#,
+                Some(Assertion::Output("foo bar".into())),
+            ),
+            (
+                r#"[ "$output" == $'foo\nbar' ]"#,
+                Some(Assertion::Output("foo\nbar".into())),
+            ),
+            (r#"[ "$output" = "$expected" ]"#, None),
+            (r#"[[ "$output" == foo* ]]"#, None),
+            (
+                r#"[[ "$output" == "foo*" ]]"#,
+                Some(Assertion::Output("foo*".into())),
+            ),
+            (
+                r#"[ "${lines[1]}" = 'second' ]"#,
+                Some(Assertion::Line(1, "second".into())),
+            ),
+            (r#"[ "${#lines[@]}" -eq 2 ]"#, Some(Assertion::LineCount(2))),
+            ("assert_success", Some(Assertion::ExitCode(0))),
+            ("assert_failure 2", Some(Assertion::ExitCode(2))),
+            ("assert_failure", None),
+            ("assert_output 'foo'", Some(Assertion::Output("foo".into()))),
+            ("assert_output --partial 'foo'", None),
+            ("refute_output", Some(Assertion::Output("".into()))),
+            (
+                "assert_line --index 0 foo",
+                Some(Assertion::Line(0, "foo".into())),
+            ),
+            ("assert_line foo", None),
+            (
+                r#"assert_equal "$output" "foo""#,
+                Some(Assertion::Output("foo".into())),
+            ),
+            ("[ -f foo ]", None),
+        ];
+        for (command, expected) in tests {
+            assert_eq!(
+                expected,
+                parse_assertion(&parse_words(command).0),
+                "for {command}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_import() {
+        let document = BatsImporter
+            .import(
+                r#"#!/usr/bin/env bats
+
+load test_helper
+
+setup() {
+  cd "$BATS_TEST_TMPDIR"
+}
+
+@test "prints \"hello\"" {
+  run echo hello
+  [ "$status" -eq 0 ]
+  [ "$output" = "hello" ]
+}
+
+@test "fails with lines" {
+  touch file
+  run -3 bash -c 'echo one; echo two; echo three; exit 3'
+  [ "${lines[0]}" = "one" ]
+  [ "${lines[2]}" = "three" ]
+  [[ "$output" =~ two ]]
+}
+
+@test "cannot skip" {
+  skip "not yet"
+  run true
+}
+"#,
+            )
+            .expect("import bats file");
+        assert_eq!(
+            document
+                .to_markdown("example.bats")
+                .expect("render markdown"),
+            r#"---
+defaults:
+  output_stream: combined
+---
+
+# example.bats
+
+## Setup
+
+```scrut
+$ source "$TESTDIR/test_helper.bash"
+> setup() {
+>   cd "$TMPDIR"
+> }
+.* (regex*)
+```
+
+## prints "hello"
+
+```scrut
+$ setup
+.* (regex*)
+```
+
+```scrut
+$ echo hello
+hello
+```
+
+## fails with lines
+
+```scrut
+$ setup
+.* (regex*)
+```
+
+```scrut
+$ touch file
+.* (regex*)
+```
+
+```scrut
+$ bash -c 'echo one; echo two; echo three; exit 3'
+one
+.* (regex)
+three
+.* (regex*)
+[3]
+```
+
+> [!WARNING]
+> Needs manual attention (line 20): the variables of `run` are not available, use output expectations instead
+
+```bash
+[[ "$output" =~ two ]]
+```
+
+## cannot skip
+
+```scrut
+$ setup
+.* (regex*)
+```
+
+> [!WARNING]
+> Needs manual attention (line 24): single tests cannot be skipped, exit with code 80 to skip the whole document
+
+```bash
+skip "not yet"
+```
+
+> [!WARNING]
+> Needs manual attention (line 25): the exit status of `run` is not asserted, the test expects exit code 0
+
+```scrut
+$ true
+.* (regex*)
+```
+"#
+        );
+        assert_eq!(document.testcase_count(), 8);
+        assert_eq!(document.attentions().len(), 3);
+    }
+}
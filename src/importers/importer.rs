@@ -0,0 +1,340 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::fmt::Display;
+
+use anyhow::Context;
+use anyhow::Result;
+use clap::ValueEnum;
+
+use super::bats::BatsImporter;
+use super::sharness::SharnessImporter;
+use super::shelltestrunner::ShelltestrunnerImporter;
+use crate::config::DocumentConfig;
+use crate::config::TestCaseConfig;
+use crate::escaping::Escaper;
+use crate::generators::markdown::max_backtick_size;
+use crate::parsers::markdown::DEFAULT_MARKDOWN_LANGUAGES;
+
+/// An Importer converts a test file of another test framework into a Scrut
+/// [`ImportedDocument`]
+pub trait Importer {
+    /// Returns the document that is converted from the provided test file
+    fn import(&self, content: &str) -> Result<ImportedDocument>;
+}
+
+/// The test frameworks from which test files can be imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImporterType {
+    /// Bash Automated Testing System (`.bats` files)
+    Bats,
+
+    /// Sharness, the test library of Git (`.t` files)
+    Sharness,
+
+    /// shelltestrunner (`.test` files)
+    Shelltestrunner,
+}
+
+impl ImporterType {
+    /// Returns the file extension of test files of the framework
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            Self::Bats => "bats",
+            Self::Sharness => "t",
+            Self::Shelltestrunner => "test",
+        }
+    }
+
+    /// Returns the importer for test files of the framework
+    pub fn importer(&self) -> Box<dyn Importer> {
+        match self {
+            Self::Bats => Box::<BatsImporter>::default(),
+            Self::Sharness => Box::<SharnessImporter>::default(),
+            Self::Shelltestrunner => Box::<ShelltestrunnerImporter>::default(),
+        }
+    }
+}
+
+impl Display for ImporterType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Bats => "bats",
+                Self::Sharness => "sharness",
+                Self::Shelltestrunner => "shelltestrunner",
+            }
+        )
+    }
+}
+
+/// A Scrut document that was converted from a test file of another framework
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportedDocument {
+    /// Document-wide configuration, rendered as front-matter
+    pub config: DocumentConfig,
+
+    /// The tests of the document, in order
+    pub sections: Vec<ImportedSection>,
+}
+
+/// A titled group of testcases, usually converted from a single test
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportedSection {
+    pub title: String,
+    pub blocks: Vec<ImportedBlock>,
+}
+
+#[derive(Debug, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum ImportedBlock {
+    /// A testcase that could be converted
+    TestCase(ImportedTestCase),
+
+    /// A construct that could not (or not faithfully) be converted
+    Attention(Attention),
+}
+
+/// A converted testcase
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportedTestCase {
+    /// The (possibly multi-line) shell expression
+    pub shell_expression: String,
+
+    /// The output expectations, in their text encoding (e.g. `foo (regex)`)
+    pub expectations: Vec<String>,
+
+    /// The expected exit code, if not zero
+    pub exit_code: Option<i32>,
+
+    /// Configuration of the testcase, rendered inline
+    pub config: TestCaseConfig,
+}
+
+/// A construct in the imported test file that needs manual attention
+#[derive(Debug, PartialEq)]
+pub struct Attention {
+    /// The line number in the imported test file (starting at 1)
+    pub line_number: usize,
+
+    /// What needs attention and why
+    pub reason: String,
+
+    /// The original code that was not converted, if any
+    pub code: Option<String>,
+}
+
+impl ImportedTestCase {
+    /// Returns a testcase that asserts a successful exit, but ignores all output
+    pub fn any_output(shell_expression: &str) -> Self {
+        Self {
+            shell_expression: shell_expression.into(),
+            expectations: vec![ANY_LINES.into()],
+            ..Default::default()
+        }
+    }
+}
+
+/// Expectation that matches any (or no) output
+pub const ANY_LINES: &str = ".* (regex*)";
+
+/// Expectation that matches exactly one arbitrary line
+pub const ANY_LINE: &str = ".* (regex)";
+
+/// Returns the expectation that matches exactly the given output line
+pub fn literal_expectation(line: &str) -> String {
+    let expectation = Escaper::Unicode.escaped_expectation(line.as_bytes());
+    if expectation.ends_with(" (escaped)") {
+        expectation
+    } else if line.starts_with("$ ") || line.starts_with("> ") {
+        format!("{} (regex)", regex::escape(line))
+    } else if line.ends_with(')') && line.contains(" (") {
+        format!("{line} (equal)")
+    } else {
+        expectation
+    }
+}
+
+impl ImportedDocument {
+    /// Returns all constructs that need manual attention
+    pub fn attentions(&self) -> Vec<&Attention> {
+        self.blocks()
+            .filter_map(|block| match block {
+                ImportedBlock::Attention(attention) => Some(attention),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the amount of converted testcases
+    pub fn testcase_count(&self) -> usize {
+        self.blocks()
+            .filter(|block| matches!(block, ImportedBlock::TestCase(_)))
+            .count()
+    }
+
+    fn blocks(&self) -> impl Iterator<Item = &ImportedBlock> {
+        self.sections
+            .iter()
+            .flat_map(|section| section.blocks.iter())
+    }
+
+    /// Renders the document in Markdown format, with the given title
+    pub fn to_markdown(&self, title: &str) -> Result<String> {
+        let mut rendered = String::new();
+        if !self.config.is_empty() {
+            rendered.push_str("---\n");
+            rendered.push_str(
+                &serde_yaml::to_string(&self.config).context("render document configuration")?,
+            );
+            rendered.push_str("---\n\n");
+        }
+        rendered.push_str(&format!("# {title}\n"));
+        for section in &self.sections {
+            rendered.push_str(&format!("\n## {}\n", section.title));
+            for block in &section.blocks {
+                rendered.push('\n');
+                match block {
+                    ImportedBlock::TestCase(testcase) => {
+                        let mut code = String::new();
+                        for (index, line) in testcase.shell_expression.lines().enumerate() {
+                            code.push_str(if index == 0 { "$ " } else { "> " });
+                            code.push_str(line);
+                            code.push('\n');
+                        }
+                        for expectation in &testcase.expectations {
+                            code.push_str(expectation);
+                            code.push('\n');
+                        }
+                        if let Some(exit_code) = testcase.exit_code {
+                            code.push_str(&format!("[{exit_code}]\n"));
+                        }
+                        let mut language = DEFAULT_MARKDOWN_LANGUAGES[0].to_string();
+                        if !testcase.config.is_empty() {
+                            language.push(' ');
+                            language.push_str(&testcase.config.to_yaml_one_liner());
+                        }
+                        rendered.push_str(&code_block(&language, &code));
+                    }
+                    ImportedBlock::Attention(attention) => {
+                        rendered.push_str(&format!(
+                            "> [!WARNING]\n> Needs manual attention (line {}): {}\n",
+                            attention.line_number, attention.reason
+                        ));
+                        if let Some(ref code) = attention.code {
+                            rendered.push('\n');
+                            rendered.push_str(&code_block("bash", &format!("{code}\n")));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(rendered)
+    }
+}
+
+fn code_block(language: &str, code: &str) -> String {
+    let backticks = "`".repeat(max_backtick_size(code) + 1);
+    format!("{backticks}{language}\n{code}{backticks}\n")
+}
+
+impl Display for Attention {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line_number, self.reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ImportedBlock;
+    use super::ImportedDocument;
+    use super::ImportedSection;
+    use super::ImportedTestCase;
+    use super::literal_expectation;
+    use crate::config::DocumentConfig;
+    use crate::config::OutputStreamControl;
+    use crate::config::TestCaseConfig;
+    use crate::importers::importer::Attention;
+
+    #[test]
+    fn test_literal_expectation() {
+        let tests = [
+            ("foo", "foo"),
+            ("foo\tbar", "foo\\tbar (escaped)"),
+            ("foo (regex)", "foo (regex) (equal)"),
+            ("$ foo", "\\$ foo (regex)"),
+            ("> foo", "> foo (regex)"),
+        ];
+        for (line, expected) in tests {
+            assert_eq!(expected, literal_expectation(line), "for {line:?}");
+        }
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let document = ImportedDocument {
+            config: DocumentConfig {
+                defaults: TestCaseConfig {
+                    output_stream: Some(OutputStreamControl::Combined),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            sections: vec![ImportedSection {
+                title: "the test".into(),
+                blocks: vec![
+                    ImportedBlock::TestCase(ImportedTestCase {
+                        shell_expression: "echo foo &&\n  echo bar".into(),
+                        expectations: vec!["foo".into(), "bar".into()],
+                        exit_code: Some(3),
+                        config: TestCaseConfig {
+                            output_stream: Some(OutputStreamControl::Stderr),
+                            ..Default::default()
+                        },
+                    }),
+                    ImportedBlock::Attention(Attention {
+                        line_number: 12,
+                        reason: "cannot convert".into(),
+                        code: Some("```\nfoo".into()),
+                    }),
+                ],
+            }],
+        };
+        assert_eq!(
+            document.to_markdown("Title").expect("render markdown"),
+            "---
+defaults:
+  output_stream: combined
+---
+
+# Title
+
+## the test
+
+```scrut {output_stream: stderr}
+$ echo foo &&
+>   echo bar
+foo
+bar
+[3]
+```
+
+> [!WARNING]
+> Needs manual attention (line 12): cannot convert
+
+````bash
+```
+foo
+````
+"
+        );
+        assert_eq!(document.testcase_count(), 1);
+        assert_eq!(document.attentions().len(), 1);
+    }
+}
@@ -0,0 +1,18 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Importers convert test files of other test frameworks into Scrut
+//! documents. Currently supported:
+//! - Bash Automated Testing System: [`bats::BatsImporter`]
+//! - Sharness: [`sharness::SharnessImporter`]
+//! - shelltestrunner: [`shelltestrunner::ShelltestrunnerImporter`]
+
+pub mod bats;
+pub mod importer;
+pub mod sharness;
+pub(crate) mod shell;
+pub mod shelltestrunner;
@@ -0,0 +1,342 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use anyhow::Result;
+use regex::Regex;
+
+use super::importer::Attention;
+use super::importer::ImportedBlock;
+use super::importer::ImportedDocument;
+use super::importer::ImportedSection;
+use super::importer::ImportedTestCase;
+use super::importer::Importer;
+use super::shell::Command;
+use super::shell::dedent;
+use super::shell::logical_commands;
+use super::shell::parse_words;
+
+lazy_static! {
+    static ref FUNCTION_DEFINITION: Regex =
+        Regex::new(r"^(?:function\s+)?([A-Za-z_][A-Za-z0-9_]*)\s*\(\s*\)")
+            .expect("sharness function definition regex must compile");
+    static ref SOURCE_LIBRARY: Regex =
+        Regex::new(r#"^(?:\.|source)\s+.*\b(?:sharness|test-lib)\.sh\b"#)
+            .expect("sharness source regex must compile");
+    static ref HELPER: Regex =
+        Regex::new(r"\btest_[a-z_]+\b").expect("sharness helper regex must compile");
+    static ref UNSUPPORTED_VARIABLE: Regex =
+        Regex::new(r"\bSHARNESS_[A-Z_]+\b").expect("sharness variable regex must compile");
+}
+
+/// Helpers of sharness and the equivalent shell code they are replaced with
+const HELPER_REPLACEMENTS: &[(&str, &str)] = &[
+    ("test_must_fail", "!"),
+    ("test_cmp", "diff -u"),
+    ("test_path_is_file", "test -f"),
+    ("test_path_is_dir", "test -d"),
+    ("test_path_is_missing", "test ! -e"),
+    ("test_must_be_empty", "test ! -s"),
+];
+
+/// Converts sharness test scripts (as used by Git) into Scrut documents:
+/// - every `test_expect_success` becomes a section with a testcase that runs
+///   the test body, which must succeed, but whose output is ignored, as in
+///   sharness
+/// - common helpers (`test_cmp`, `test_must_fail`, `test_path_is_file` ..) are
+///   replaced with equivalent shell code
+/// - all other code (helpers, setup) is executed where it is in the script
+///
+/// Known breakages (`test_expect_failure`), prerequisites and anything else
+/// that cannot be converted faithfully is kept as [`Attention`].
+#[derive(Default)]
+pub struct SharnessImporter;
+
+impl Importer for SharnessImporter {
+    fn import(&self, content: &str) -> Result<ImportedDocument> {
+        let commands = logical_commands(content, 1);
+        let functions = commands
+            .iter()
+            .filter_map(|command| FUNCTION_DEFINITION.captures(&command.text))
+            .map(|captures| captures[1].to_string())
+            .collect::<Vec<_>>();
+        let converter = Converter { functions };
+
+        let mut sections: Vec<ImportedSection> = vec![];
+        let mut setup_code = vec![];
+        for command in commands {
+            let (words, _) = parse_words(&command.text);
+            let name = words.first().map(|word| word.raw.as_str()).unwrap_or("");
+            if (command.line_number == 1 && command.text.starts_with("#!"))
+                || command.text.starts_with("test_description=")
+                || SOURCE_LIBRARY.is_match(&command.text)
+                || name == "test_done"
+            {
+                continue;
+            }
+            if !["test_expect_success", "test_expect_failure"].contains(&name) {
+                match converter.convert_code(&command) {
+                    Ok(code) => setup_code.push(code),
+                    Err(attention) => {
+                        push_setup(&mut sections, &mut setup_code);
+                        push_blocks(&mut sections, vec![ImportedBlock::Attention(attention)]);
+                    }
+                }
+                continue;
+            }
+            push_setup(&mut sections, &mut setup_code);
+            sections.push(converter.convert_test(&command, name == "test_expect_failure"));
+        }
+        push_setup(&mut sections, &mut setup_code);
+
+        Ok(ImportedDocument {
+            sections,
+            ..Default::default()
+        })
+    }
+}
+
+/// Adds the collected code outside of tests as a single testcase
+fn push_setup(sections: &mut Vec<ImportedSection>, setup_code: &mut Vec<String>) {
+    if setup_code.is_empty() {
+        return;
+    }
+    let testcase = ImportedTestCase::any_output(&setup_code.join("\n"));
+    setup_code.clear();
+    push_blocks(sections, vec![ImportedBlock::TestCase(testcase)]);
+}
+
+/// Adds blocks outside of tests to the current setup section, or to a new one
+fn push_blocks(sections: &mut Vec<ImportedSection>, blocks: Vec<ImportedBlock>) {
+    match sections.last_mut() {
+        Some(section) if section.title == "Setup" => section.blocks.extend(blocks),
+        _ => sections.push(ImportedSection {
+            title: "Setup".into(),
+            blocks,
+        }),
+    }
+}
+
+struct Converter {
+    /// Functions that are defined in the script, which are not mistaken for
+    /// helpers of sharness
+    functions: Vec<String>,
+}
+
+impl Converter {
+    /// Converts a `test_expect_success` or `test_expect_failure` call
+    fn convert_test(&self, command: &Command, is_known_breakage: bool) -> ImportedSection {
+        let (words, has_operators) = parse_words(&command.text);
+        let attention = |reason: &str, code: String| Attention {
+            line_number: command.line_number,
+            reason: reason.into(),
+            code: Some(code),
+        };
+        let (prerequisite, title, body) = match (&words[1..], has_operators) {
+            ([title, body], false) => (None, title, body),
+            ([prerequisite, title, body], false) => (Some(prerequisite), title, body),
+            _ => {
+                return ImportedSection {
+                    title: format!("Test in line {}", command.line_number),
+                    blocks: vec![ImportedBlock::Attention(attention(
+                        "unrecognized test definition",
+                        command.text.clone(),
+                    ))],
+                };
+            }
+        };
+
+        let lines = body
+            .value
+            .trim_end()
+            .lines()
+            .skip_while(|line| line.trim().is_empty())
+            .collect::<Vec<_>>();
+        let code = if lines.is_empty() {
+            "true".to_string()
+        } else {
+            dedent(&lines)
+        };
+
+        let mut blocks = vec![];
+        if is_known_breakage {
+            blocks.push(ImportedBlock::Attention(attention(
+                "known breakage (`test_expect_failure`), the test is not converted",
+                code,
+            )));
+        } else {
+            match self.convert_code(&Command {
+                line_number: command.line_number,
+                text: code,
+            }) {
+                Ok(code) => {
+                    blocks.push(ImportedBlock::TestCase(ImportedTestCase::any_output(&code)))
+                }
+                Err(attention) => blocks.push(ImportedBlock::Attention(attention)),
+            }
+            if let Some(prerequisite) = prerequisite {
+                blocks.push(ImportedBlock::Attention(Attention {
+                    line_number: command.line_number,
+                    reason: format!(
+                        "the prerequisite `{}` is not checked, the test always runs",
+                        prerequisite.value
+                    ),
+                    code: None,
+                }));
+            }
+        }
+
+        ImportedSection {
+            title: title.value.clone(),
+            blocks,
+        }
+    }
+
+    /// Returns the code with helpers of sharness replaced, or an attention if
+    /// it uses helpers or variables of sharness that have no equivalent
+    fn convert_code(&self, command: &Command) -> Result<String, Attention> {
+        let mut code = command.text.replace("SHARNESS_TEST_DIRECTORY", "TESTDIR");
+        for (helper, replacement) in HELPER_REPLACEMENTS {
+            code = Regex::new(&format!(r"\b{helper}\b"))
+                .expect("helper regex must compile")
+                .replace_all(&code, *replacement)
+                .into_owned();
+        }
+
+        let reason = if let Some(variable) = UNSUPPORTED_VARIABLE.find(&code) {
+            format!(
+                "the sharness variable `{}` is not available",
+                variable.as_str()
+            )
+        } else if let Some(helper) = HELPER
+            .find_iter(&code)
+            .find(|helper| !self.functions.iter().any(|name| name == helper.as_str()))
+        {
+            format!("the sharness helper `{}` is not available", helper.as_str())
+        } else {
+            return Ok(code);
+        };
+        Err(Attention {
+            line_number: command.line_number,
+            reason,
+            code: Some(command.text.clone()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharnessImporter;
+    use crate::importers::importer::Importer;
+
+    #[test]
+    fn test_import() {
+        let document = SharnessImporter
+            .import(
+                r#"#!/bin/sh
+
+test_description="Show basic features"
+
+. ./sharness.sh
+
+test_expect_success 'creates a file' '
+	echo foo >file &&
+	test_path_is_file file
+'
+
+write_expected() {
+	echo foo >expected
+}
+
+test_expect_success PERL 'compares files' "
+	write_expected &&
+	test_cmp expected file &&
+	test_must_fail false
+"
+
+test_expect_failure 'known breakage' '
+	false
+'
+
+test_expect_success 'uses helpers' '
+	test_when_finished "rm file" &&
+	cat "$SHARNESS_TEST_DIRECTORY/data"
+'
+
+test_expect_success 'uses the trash directory' '
+	cd "$SHARNESS_TRASH_DIRECTORY"
+'
+
+test_done
+"#,
+            )
+            .expect("import sharness file");
+        assert_eq!(
+            document
+                .to_markdown("t0000-basic.t")
+                .expect("render markdown"),
+            r#"# t0000-basic.t
+
+## creates a file
+
+```scrut
+$ echo foo >file &&
+> test -f file
+.* (regex*)
+```
+
+## Setup
+
+```scrut
+$ write_expected() {
+> 	echo foo >expected
+> }
+.* (regex*)
+```
+
+## compares files
+
+```scrut
+$ write_expected &&
+> diff -u expected file &&
+> ! false
+.* (regex*)
+```
+
+> [!WARNING]
+> Needs manual attention (line 16): the prerequisite `PERL` is not checked, the test always runs
+
+## known breakage
+
+> [!WARNING]
+> Needs manual attention (line 22): known breakage (`test_expect_failure`), the test is not converted
+
+```bash
+false
+```
+
+## uses helpers
+
+> [!WARNING]
+> Needs manual attention (line 26): the sharness helper `test_when_finished` is not available
+
+```bash
+test_when_finished "rm file" &&
+cat "$SHARNESS_TEST_DIRECTORY/data"
+```
+
+## uses the trash directory
+
+> [!WARNING]
+> Needs manual attention (line 31): the sharness variable `SHARNESS_TRASH_DIRECTORY` is not available
+
+```bash
+cd "$SHARNESS_TRASH_DIRECTORY"
+```
+"#
+        );
+    }
+}
@@ -0,0 +1,385 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Best-effort lexing of shell code, just enough to find where commands start
+//! and end and to extract the values of simple words.
+
+/// A complete shell command, which may span multiple lines (e.g. a compound
+/// command, a line continuation or a heredoc)
+#[derive(Debug, PartialEq)]
+pub(crate) struct Command {
+    /// The line number in which the command starts (starting at 1)
+    pub line_number: usize,
+
+    /// The command, with the indentation of its first line removed from all
+    /// of its lines
+    pub text: String,
+}
+
+/// A word of a shell command
+#[derive(Debug, PartialEq)]
+pub(crate) struct Word {
+    /// The word as it was written, including quotes
+    pub raw: String,
+
+    /// The word with quotes and escapes removed
+    pub value: String,
+
+    /// Whether the word contains an expansion (`$..`, `` `..` ``), in which
+    /// case the value is not the final value
+    pub dynamic: bool,
+}
+
+/// Splits shell code into complete commands, skipping empty and comment lines
+/// between them
+pub(crate) fn logical_commands(content: &str, first_line_number: usize) -> Vec<Command> {
+    let mut commands = vec![];
+    let mut scanner = Scanner::default();
+    let mut current: Option<(usize, Vec<&str>)> = None;
+    for (index, line) in content.lines().enumerate() {
+        let mut continues = false;
+        if let Some((ref delimiter, strip_tabs)) = scanner.heredoc {
+            let end = if strip_tabs {
+                line.trim_start_matches('\t')
+            } else {
+                line
+            };
+            if end == delimiter {
+                scanner.heredoc = None;
+            }
+        } else {
+            let trimmed = line.trim();
+            if current.is_none() && (trimmed.is_empty() || trimmed.starts_with('#')) {
+                continue;
+            }
+            continues = scanner.scan_line(line);
+        }
+        current
+            .get_or_insert_with(|| (first_line_number + index, vec![]))
+            .1
+            .push(line);
+        if !continues && scanner.is_complete() {
+            scanner.depth = 0;
+            if let Some((line_number, lines)) = current.take() {
+                commands.push(Command {
+                    line_number,
+                    text: dedent(&lines),
+                });
+            }
+        }
+    }
+    if let Some((line_number, lines)) = current {
+        commands.push(Command {
+            line_number,
+            text: dedent(&lines),
+        });
+    }
+    commands
+}
+
+/// Removes the indentation of the first line from all lines
+pub(crate) fn dedent(lines: &[&str]) -> String {
+    let indentation = &lines[0][..lines[0].len() - lines[0].trim_start().len()];
+    lines
+        .iter()
+        .map(|line| line.strip_prefix(indentation).unwrap_or(line).trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Tracks the state of shell code across lines
+#[derive(Default)]
+struct Scanner {
+    /// The currently open quote: `'`, `"` or `$` (for `$'..'`)
+    quote: Option<char>,
+
+    /// The amount of open compound commands
+    depth: i32,
+
+    /// The delimiter of an open heredoc and whether tabs are stripped
+    heredoc: Option<(String, bool)>,
+}
+
+impl Scanner {
+    fn is_complete(&self) -> bool {
+        self.quote.is_none() && self.depth <= 0 && self.heredoc.is_none()
+    }
+
+    /// Scans a single line and returns whether it ends in a line continuation
+    fn scan_line(&mut self, line: &str) -> bool {
+        let chars = line.chars().collect::<Vec<_>>();
+        let mut token = String::new();
+        let mut index = 0;
+        while index < chars.len() {
+            let (current, next) = (chars[index], chars.get(index + 1).copied());
+            match self.quote {
+                Some('\'') if current == '\'' => self.quote = None,
+                Some('"' | '$') if current == '\\' => index += 1,
+                Some('"') if current == '"' => self.quote = None,
+                Some('$') if current == '\'' => self.quote = None,
+                Some(_) => {}
+                None => match current {
+                    '\\' if next.is_none() => {
+                        self.count(&token);
+                        return true;
+                    }
+                    '\\' => {
+                        token.push(current);
+                        index += 1;
+                    }
+                    '\'' | '"' => {
+                        token.push(current);
+                        self.quote = Some(current);
+                    }
+                    '$' if next == Some('\'') => {
+                        token.push(current);
+                        self.quote = Some('$');
+                        index += 1;
+                    }
+                    '#' if token.is_empty() => break,
+                    '<' if next == Some('<') && chars.get(index + 2) != Some(&'<') => {
+                        index = self.open_heredoc(&chars, index + 2);
+                        continue;
+                    }
+                    c if c.is_whitespace() || ";&|()".contains(c) => {
+                        self.count(&token);
+                        token.clear();
+                    }
+                    c => token.push(c),
+                },
+            }
+            index += 1;
+        }
+        self.count(&token);
+        false
+    }
+
+    /// Updates the depth of compound commands from a complete, unquoted token
+    fn count(&mut self, token: &str) {
+        match token {
+            "if" | "case" | "for" | "while" | "until" | "select" | "{" => self.depth += 1,
+            "fi" | "esac" | "done" | "}" => self.depth -= 1,
+            _ => {}
+        }
+    }
+
+    /// Reads the delimiter of a heredoc that starts at the given index, right
+    /// after `<<`, and returns the index after it
+    fn open_heredoc(&mut self, chars: &[char], mut index: usize) -> usize {
+        let strip_tabs = chars.get(index) == Some(&'-');
+        if strip_tabs {
+            index += 1;
+        }
+        while chars.get(index).is_some_and(|c| c.is_whitespace()) {
+            index += 1;
+        }
+        let mut delimiter = String::new();
+        while let Some(&c) = chars.get(index) {
+            if c.is_whitespace() || ";&|<>()".contains(c) {
+                break;
+            }
+            if !['\'', '"', '\\'].contains(&c) {
+                delimiter.push(c);
+            }
+            index += 1;
+        }
+        if !delimiter.is_empty() {
+            self.heredoc = Some((delimiter, strip_tabs));
+        }
+        index
+    }
+}
+
+/// Splits a single shell command into words and returns whether it contains
+/// any operators (`;`, `&&`, `|`, redirections, subshells ..), in which case
+/// it is not a simple command
+pub(crate) fn parse_words(command: &str) -> (Vec<Word>, bool) {
+    let mut words = vec![];
+    let mut has_operators = false;
+    let mut quote = None;
+    let mut start = None;
+    let mut value = String::new();
+    let mut dynamic = false;
+    let mut chars = command.char_indices().peekable();
+
+    macro_rules! finish_word {
+        ($end:expr) => {
+            if let Some(from) = start.take() {
+                words.push(Word {
+                    raw: command[from..$end].to_string(),
+                    value: std::mem::take(&mut value),
+                    dynamic,
+                });
+                dynamic = false;
+            }
+        };
+    }
+
+    while let Some((index, current)) = chars.next() {
+        match quote {
+            Some('\'') => match current {
+                '\'' => quote = None,
+                c => value.push(c),
+            },
+            Some('"') => match current {
+                '"' => quote = None,
+                '\\' if chars
+                    .peek()
+                    .is_some_and(|(_, c)| ['$', '`', '"', '\\'].contains(c)) =>
+                {
+                    value.push(chars.next().expect("peeked").1);
+                }
+                c => {
+                    dynamic |= c == '$' || c == '`';
+                    value.push(c)
+                }
+            },
+            Some(_) => match current {
+                '\'' => quote = None,
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('r') => value.push('\r'),
+                    Some('e' | 'E') => value.push('\x1b'),
+                    Some(c @ ('\\' | '\'' | '"')) => value.push(c),
+                    Some(c) => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                    None => value.push('\\'),
+                },
+                c => value.push(c),
+            },
+            None => {
+                if current.is_whitespace() {
+                    finish_word!(index);
+                    continue;
+                }
+                if ";&|<>()`".contains(current) {
+                    finish_word!(index);
+                    has_operators = true;
+                    continue;
+                }
+                start.get_or_insert(index);
+                match current {
+                    '\\' => {
+                        if let Some((_, c)) = chars.next() {
+                            value.push(c);
+                        }
+                    }
+                    '\'' | '"' => quote = Some(current),
+                    '$' if chars.peek().is_some_and(|(_, c)| *c == '\'') => {
+                        chars.next();
+                        quote = Some('$');
+                    }
+                    c => {
+                        dynamic |= c == '$';
+                        value.push(c)
+                    }
+                }
+            }
+        }
+    }
+    if let Some(from) = start {
+        words.push(Word {
+            raw: command[from..].to_string(),
+            value,
+            dynamic,
+        });
+    }
+    (words, has_operators)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Command;
+    use super::Word;
+    use super::logical_commands;
+    use super::parse_words;
+
+    #[test]
+    fn test_logical_commands() {
+        let commands = logical_commands(
+            r#"# a comment
+  echo one
+
+  if true; then
+    echo "two
+three"
+  fi
+  echo four \
+    five
+  cat <<-EOF
+	if
+	EOF
+  echo 'done' # done
+"#,
+            10,
+        );
+        assert_eq!(
+            commands,
+            vec![
+                Command {
+                    line_number: 11,
+                    text: "echo one".into(),
+                },
+                Command {
+                    line_number: 13,
+                    text: "if true; then\n  echo \"two\nthree\"\nfi".into(),
+                },
+                Command {
+                    line_number: 17,
+                    text: "echo four \\\n  five".into(),
+                },
+                Command {
+                    line_number: 19,
+                    text: "cat <<-EOF\n\tif\n\tEOF".into(),
+                },
+                Command {
+                    line_number: 22,
+                    text: "echo 'done' # done".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_words() {
+        let word = |raw: &str, value: &str, dynamic: bool| Word {
+            raw: raw.into(),
+            value: value.into(),
+            dynamic,
+        };
+        assert_eq!(
+            parse_words(r#"[ "$output" = 'a b' ]"#),
+            (
+                vec![
+                    word("[", "[", false),
+                    word("\"$output\"", "$output", true),
+                    word("=", "=", false),
+                    word("'a b'", "a b", false),
+                    word("]", "]", false),
+                ],
+                false
+            )
+        );
+        assert_eq!(
+            parse_words(r#"echo $'a\tb' "\"q\"" x\ y"#),
+            (
+                vec![
+                    word("echo", "echo", false),
+                    word("$'a\\tb'", "a\tb", false),
+                    word("\"\\\"q\\\"\"", "\"q\"", false),
+                    word("x\\ y", "x y", false),
+                ],
+                false
+            )
+        );
+        assert!(parse_words("echo foo && echo bar").1);
+        assert!(parse_words("echo foo > file").1);
+    }
+}
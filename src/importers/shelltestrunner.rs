@@ -0,0 +1,606 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use anyhow::Result;
+
+use super::importer::ANY_LINES;
+use super::importer::Attention;
+use super::importer::ImportedBlock;
+use super::importer::ImportedDocument;
+use super::importer::ImportedSection;
+use super::importer::ImportedTestCase;
+use super::importer::Importer;
+use super::importer::literal_expectation;
+use super::shell::parse_words;
+use crate::config::OutputStreamControl;
+use crate::config::TestCaseConfig;
+
+/// Where the expected STDERR is written to, when both STDOUT and STDERR of a
+/// test are asserted
+const STDERR_FILE: &str = "\"$TMPDIR/stderr\"";
+
+/// Converts test files of shelltestrunner into Scrut documents. All three
+/// formats are supported, detected from the first line that is not empty or a
+/// comment:
+/// - format 1: a command line, followed by `<<<` (input), `>>>` (STDOUT),
+///   `>>>2` (STDERR) and `>>>=` (exit status) sections
+/// - format 2: like format 1, but commands start with `$$$ ` and input
+///   precedes the command and applies to all subsequent tests
+/// - format 3: like format 2, but with the short markers `$ `, `<`, `>`,
+///   `>2` and `>=`
+///
+/// Every test becomes a section with a single testcase (two, if STDOUT and
+/// STDERR are asserted). Output that is not asserted is ignored and the exit
+/// status defaults to zero, as in shelltestrunner. Regular expression matchers
+/// (`/regex/`) and negations (`!`) are kept as [`Attention`].
+#[derive(Default)]
+pub struct ShelltestrunnerImporter;
+
+impl Importer for ShelltestrunnerImporter {
+    fn import(&self, content: &str) -> Result<ImportedDocument> {
+        let mut parser = Parser::new(Format::detect(content));
+        for (index, line) in content.lines().enumerate() {
+            parser.parse_line(index + 1, line);
+        }
+        parser.finish_test();
+
+        Ok(ImportedDocument {
+            sections: parser.sections,
+            ..Default::default()
+        })
+    }
+}
+
+/// The formats of shelltestrunner test files
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    One,
+    Two,
+    Three,
+}
+
+impl Format {
+    /// Detects the format from the first line that is not empty or a comment
+    fn detect(content: &str) -> Self {
+        let first = content
+            .lines()
+            .map(str::trim_end)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .unwrap_or_default();
+        if first.starts_with("$$$") || first == "<<<" {
+            Self::Two
+        } else if first.starts_with("$ ") || first == "<" {
+            Self::Three
+        } else {
+            Self::One
+        }
+    }
+
+    /// Returns the markers of input, STDOUT, STDERR and exit status sections
+    fn markers(&self) -> (&'static str, &'static str, &'static str, &'static str) {
+        match self {
+            Self::One | Self::Two => ("<<<", ">>>", ">>>2", ">>>="),
+            Self::Three => ("<", ">", ">2", ">="),
+        }
+    }
+
+    /// Returns the command of the line, if the line starts a test
+    fn command<'a>(&self, line: &'a str) -> Option<&'a str> {
+        match self {
+            Self::One => None,
+            Self::Two => line.strip_prefix("$$$"),
+            Self::Three => line.strip_prefix("$ "),
+        }
+        .map(str::trim)
+    }
+}
+
+/// A line of a test file, classified by its marker
+#[derive(Debug, PartialEq)]
+enum Line<'a> {
+    Command(&'a str),
+    Input,
+    Stdout(Option<&'a str>),
+    Stderr(Option<&'a str>),
+    ExitStatus(&'a str),
+    Other,
+}
+
+impl<'a> Line<'a> {
+    fn classify(format: Format, line: &'a str) -> Self {
+        if let Some(command) = format.command(line) {
+            return Self::Command(command);
+        }
+        let trimmed = line.trim_end();
+        let (input, stdout, stderr, exit_status) = format.markers();
+        if let Some(status) = trimmed.strip_prefix(exit_status) {
+            return Self::ExitStatus(status.trim());
+        }
+        if trimmed == input {
+            return Self::Input;
+        }
+        if let Some(matcher) = output_marker(trimmed, stderr) {
+            return Self::Stderr(matcher);
+        }
+        if let Some(matcher) = output_marker(trimmed, stdout) {
+            return Self::Stdout(matcher);
+        }
+        Self::Other
+    }
+}
+
+/// Returns whether the line is the given output marker, with the optional
+/// regular expression matcher (`/regex/` or `!/regex/`) that follows it
+fn output_marker<'a>(line: &'a str, marker: &str) -> Option<Option<&'a str>> {
+    let rest = line.strip_prefix(marker)?;
+    if rest.is_empty() {
+        return Some(None);
+    }
+    let matcher = rest.strip_prefix(' ')?.trim();
+    (matcher.starts_with('/') || matcher.starts_with("!/")).then_some(Some(matcher))
+}
+
+/// The section of a test whose lines are currently collected
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Section {
+    None,
+    Input,
+    SharedInput,
+    Stdout,
+    Stderr,
+}
+
+/// What output a test expects on STDOUT or STDERR
+#[derive(Debug, PartialEq)]
+enum Expected {
+    Lines(Vec<String>),
+    Matcher(usize, String),
+}
+
+/// A test, whose sections are still being collected
+#[derive(Default)]
+struct PendingTest {
+    title: Option<String>,
+    command: String,
+    input: Option<Vec<String>>,
+    stdout: Option<Expected>,
+    stderr: Option<Expected>,
+    exit_status: Option<(usize, String)>,
+}
+
+struct Parser {
+    format: Format,
+    sections: Vec<ImportedSection>,
+    test: Option<PendingTest>,
+    comments: Vec<String>,
+    shared_input: Option<Vec<String>>,
+    section: Section,
+    lines: Vec<String>,
+}
+
+impl Parser {
+    fn new(format: Format) -> Self {
+        Self {
+            format,
+            sections: vec![],
+            test: None,
+            comments: vec![],
+            shared_input: None,
+            section: Section::None,
+            lines: vec![],
+        }
+    }
+
+    fn parse_line(&mut self, line_number: usize, line: &str) {
+        let classified = Line::classify(self.format, line);
+        if self.section != Section::None && classified == Line::Other {
+            self.lines.push(line.to_string());
+            return;
+        }
+
+        match classified {
+            Line::Command(command) => self.start_test(command),
+            Line::Other => {
+                let trimmed = line.trim();
+                if trimmed.is_empty() {
+                    return;
+                }
+                if let Some(comment) = trimmed.strip_prefix('#') {
+                    self.comments.push(comment.trim().to_string());
+                } else if self.format == Format::One {
+                    self.start_test(trimmed);
+                } else {
+                    self.attend(line_number, "unrecognized line", Some(line.to_string()));
+                }
+            }
+            Line::Input => {
+                self.close_section(false);
+                self.section = if self.format == Format::One && self.test.is_some() {
+                    Section::Input
+                } else {
+                    Section::SharedInput
+                };
+            }
+            Line::Stdout(matcher) | Line::Stderr(matcher) => {
+                self.close_section(false);
+                let is_stdout = matches!(classified, Line::Stdout(_));
+                let Some(ref mut test) = self.test else {
+                    self.attend(line_number, "output section without a test", None);
+                    return;
+                };
+                match matcher {
+                    Some(matcher) => {
+                        let expected = Some(Expected::Matcher(line_number, matcher.to_string()));
+                        if is_stdout {
+                            test.stdout = expected;
+                        } else {
+                            test.stderr = expected;
+                        }
+                    }
+                    None if is_stdout => self.section = Section::Stdout,
+                    None => self.section = Section::Stderr,
+                }
+            }
+            Line::ExitStatus(status) => {
+                self.close_section(false);
+                match self.test {
+                    Some(ref mut test) => test.exit_status = Some((line_number, status.into())),
+                    None => self.attend(line_number, "exit status without a test", None),
+                }
+            }
+        }
+    }
+
+    fn start_test(&mut self, command: &str) {
+        self.finish_test();
+        self.test = Some(PendingTest {
+            title: (!self.comments.is_empty()).then(|| self.comments.join(" ")),
+            command: command.to_string(),
+            input: self.shared_input.clone(),
+            ..Default::default()
+        });
+        self.comments.clear();
+    }
+
+    /// Assigns the collected lines to the section they were collected for.
+    /// Trailing empty lines are removed if the section ends with the test, as
+    /// they separate tests rather than being expected.
+    fn close_section(&mut self, ends_test: bool) {
+        let mut lines = std::mem::take(&mut self.lines);
+        if ends_test {
+            while lines.last().is_some_and(|line| line.trim().is_empty()) {
+                lines.pop();
+            }
+        }
+        let section = std::mem::replace(&mut self.section, Section::None);
+        if section == Section::SharedInput {
+            self.shared_input = Some(lines);
+            return;
+        }
+        let Some(ref mut test) = self.test else {
+            return;
+        };
+        match section {
+            Section::Input => test.input = Some(lines),
+            Section::Stdout => test.stdout = Some(Expected::Lines(lines)),
+            Section::Stderr => test.stderr = Some(Expected::Lines(lines)),
+            Section::None | Section::SharedInput => {}
+        }
+    }
+
+    fn finish_test(&mut self) {
+        self.close_section(true);
+        if let Some(test) = self.test.take() {
+            self.sections.push(test.convert());
+        }
+    }
+
+    /// Adds the attention to the current test, or as a section of its own
+    fn attend(&mut self, line_number: usize, reason: &str, code: Option<String>) {
+        let attention = ImportedBlock::Attention(Attention {
+            line_number,
+            reason: reason.into(),
+            code,
+        });
+        match self.sections.last_mut() {
+            Some(section) if self.test.is_none() => section.blocks.push(attention),
+            _ if self.test.is_none() => self.sections.push(ImportedSection {
+                title: format!("Line {line_number}"),
+                blocks: vec![attention],
+            }),
+            _ => {
+                self.finish_test();
+                if let Some(section) = self.sections.last_mut() {
+                    section.blocks.push(attention)
+                }
+            }
+        }
+    }
+}
+
+impl PendingTest {
+    fn convert(mut self) -> ImportedSection {
+        let mut attentions = vec![];
+        let mut matcher = |expected: Option<Expected>, stream: &str| match expected {
+            Some(Expected::Lines(lines)) => Some(
+                lines
+                    .iter()
+                    .map(|line| literal_expectation(line))
+                    .collect::<Vec<_>>(),
+            ),
+            Some(Expected::Matcher(line_number, matcher)) => {
+                attentions.push(Attention {
+                    line_number,
+                    reason: format!(
+                        "the {stream} matcher `{matcher}` cannot be converted, the output is ignored"
+                    ),
+                    code: None,
+                });
+                None
+            }
+            None => None,
+        };
+        let stdout = matcher(self.stdout.take(), "STDOUT");
+        let stderr = matcher(self.stderr.take(), "STDERR");
+
+        let exit_code = match self.exit_status.take() {
+            Some((line_number, status)) => match status.parse::<i32>() {
+                Ok(code) => Some(code).filter(|code| *code != 0),
+                Err(_) => {
+                    attentions.push(Attention {
+                        line_number,
+                        reason: format!(
+                            "the exit status matcher `{status}` cannot be converted, the test expects exit code 0"
+                        ),
+                        code: None,
+                    });
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let redirect_stderr = stdout.is_some() && stderr.is_some();
+        let mut blocks = vec![ImportedBlock::TestCase(ImportedTestCase {
+            shell_expression: self.shell_expression(redirect_stderr),
+            expectations: match (&stdout, &stderr) {
+                (Some(_), _) => stdout.clone().unwrap_or_default(),
+                (None, Some(stderr)) => stderr.clone(),
+                (None, None) => vec![ANY_LINES.into()],
+            },
+            exit_code,
+            config: TestCaseConfig {
+                output_stream: (stdout.is_none() && stderr.is_some())
+                    .then_some(OutputStreamControl::Stderr),
+                ..Default::default()
+            },
+        })];
+        if redirect_stderr {
+            blocks.push(ImportedBlock::TestCase(ImportedTestCase {
+                shell_expression: format!("cat {STDERR_FILE}"),
+                expectations: stderr.unwrap_or_default(),
+                ..Default::default()
+            }));
+        }
+        blocks.extend(attentions.into_iter().map(ImportedBlock::Attention));
+
+        ImportedSection {
+            title: self.title.unwrap_or_else(|| self.command.clone()),
+            blocks,
+        }
+    }
+
+    /// Returns the command with the input provided as heredoc and, if
+    /// requested, with STDERR written to [`STDERR_FILE`]
+    fn shell_expression(&self, redirect_stderr: bool) -> String {
+        let input = self.input.as_ref().filter(|input| !input.is_empty());
+        let mut expression = if (input.is_some() || redirect_stderr) && parse_words(&self.command).1
+        {
+            format!("({})", self.command)
+        } else {
+            self.command.clone()
+        };
+        if redirect_stderr {
+            expression.push_str(&format!(" 2>{STDERR_FILE}"));
+        }
+        if let Some(input) = input {
+            let mut delimiter = "EOF".to_string();
+            while input.contains(&delimiter) {
+                delimiter.push('_');
+            }
+            expression.push_str(&format!(
+                " <<'{delimiter}'\n{}\n{delimiter}",
+                input.join("\n")
+            ));
+        }
+        expression
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Format;
+    use super::Line;
+    use super::ShelltestrunnerImporter;
+    use crate::importers::importer::Importer;
+
+    #[test]
+    fn test_classify_line() {
+        let tests = [
+            (Format::One, "echo foo", Line::Other),
+            (Format::One, "<<<", Line::Input),
+            (Format::One, ">>>", Line::Stdout(None)),
+            (Format::One, ">>> /fo+/", Line::Stdout(Some("/fo+/"))),
+            (Format::One, ">>> foo", Line::Other),
+            (Format::One, ">>>2", Line::Stderr(None)),
+            (Format::One, ">>>2 !/bar/", Line::Stderr(Some("!/bar/"))),
+            (Format::One, ">>>= 1", Line::ExitStatus("1")),
+            (Format::Two, "$$$ echo foo", Line::Command("echo foo")),
+            (Format::Two, "$ echo foo", Line::Other),
+            (Format::Three, "$ echo foo", Line::Command("echo foo")),
+            (Format::Three, "<", Line::Input),
+            (Format::Three, ">", Line::Stdout(None)),
+            (Format::Three, ">2", Line::Stderr(None)),
+            (Format::Three, ">=!0", Line::ExitStatus("!0")),
+            (Format::Three, "> foo", Line::Other),
+        ];
+        for (format, line, expected) in tests {
+            assert_eq!(expected, Line::classify(format, line), "for {line:?}");
+        }
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let tests = [
+            ("# comment\necho foo\n>>>\nfoo", Format::One),
+            ("$$$ echo foo\n>>>\nfoo", Format::Two),
+            ("<<<\nfoo\n$$$ cat\n>>>\nfoo", Format::Two),
+            ("\n$ echo foo\n>\nfoo", Format::Three),
+            ("<\nfoo\n$ cat\n>\nfoo", Format::Three),
+        ];
+        for (content, expected) in tests {
+            assert_eq!(expected, Format::detect(content), "for {content:?}");
+        }
+    }
+
+    #[test]
+    fn test_import_format_one() {
+        let document = ShelltestrunnerImporter
+            .import(
+                r#"# prints the input
+cat
+<<<
+foo
+bar
+>>>
+foo
+bar
+>>>= 0
+
+# writes to both streams
+echo out; echo err >&2; exit 3
+>>>
+out
+>>>2
+err
+>>>= 3
+
+grep -c foo
+<<<
+foo
+>>> /[0-9]+/
+>>>= !1
+"#,
+            )
+            .expect("import shelltestrunner file");
+        assert_eq!(
+            document
+                .to_markdown("example.test")
+                .expect("render markdown"),
+            r#"# example.test
+
+## prints the input
+
+```scrut
+$ cat <<'EOF'
+> foo
+> bar
+> EOF
+foo
+bar
+```
+
+## writes to both streams
+
+```scrut
+$ (echo out; echo err >&2; exit 3) 2>"$TMPDIR/stderr"
+out
+[3]
+```
+
+```scrut
+$ cat "$TMPDIR/stderr"
+err
+```
+
+## grep -c foo
+
+```scrut
+$ grep -c foo <<'EOF'
+> foo
+> EOF
+.* (regex*)
+```
+
+> [!WARNING]
+> Needs manual attention (line 22): the STDOUT matcher `/[0-9]+/` cannot be converted, the output is ignored
+
+> [!WARNING]
+> Needs manual attention (line 23): the exit status matcher `!1` cannot be converted, the test expects exit code 0
+"#
+        );
+        assert_eq!(document.testcase_count(), 4);
+        assert_eq!(document.attentions().len(), 2);
+    }
+
+    #[test]
+    fn test_import_format_three() {
+        let document = ShelltestrunnerImporter
+            .import(
+                r#"<
+shared
+$ cat
+>
+shared
+
+$ cat >&2
+>2
+shared
+>= 0
+
+$ false
+>= 1
+"#,
+            )
+            .expect("import shelltestrunner file");
+        assert_eq!(
+            document
+                .to_markdown("example.test")
+                .expect("render markdown"),
+            r#"# example.test
+
+## cat
+
+```scrut
+$ cat <<'EOF'
+> shared
+> EOF
+shared
+```
+
+## cat >&2
+
+```scrut {output_stream: stderr}
+$ (cat >&2) <<'EOF'
+> shared
+> EOF
+shared
+```
+
+## false
+
+```scrut
+$ false <<'EOF'
+> shared
+> EOF
+.* (regex*)
+[1]
+```
+"#
+        );
+    }
+}
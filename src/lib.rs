@@ -11,13 +11,16 @@ extern crate derivative;
 #[macro_use]
 extern crate lazy_static;
 
+pub mod assertions;
 pub mod config;
 pub mod debug;
 pub mod diff;
+pub mod diff_algorithm;
 pub mod escaping;
 pub mod executors;
 pub mod expectation;
 pub mod generators;
+pub mod importers;
 pub mod newline;
 pub mod outcome;
 pub mod output;
@@ -148,6 +148,22 @@ pub fn replace_crlf<'a>(bytes: &'a [u8]) -> Cow<'a, [u8]> {
     }
 }
 
+/// Returns the number of lines that end in CRLF and the number of lines that
+/// end in LF only, to detect text that mixes both
+pub fn count_line_endings(bytes: &[u8]) -> (usize, usize) {
+    bytes
+        .iter()
+        .enumerate()
+        .filter(|(_, byte)| **byte == b'\n')
+        .fold((0, 0), |(crlf, lf), (index, _)| {
+            if index > 0 && bytes[index - 1] == b'\r' {
+                (crlf + 1, lf)
+            } else {
+                (crlf, lf + 1)
+            }
+        })
+}
+
 /// Like the [`format`] with an added new line character
 #[macro_export]
 macro_rules! formatln {
@@ -185,6 +201,7 @@ mod tests {
     use super::BytesNewline;
     use super::SplitLinesByNewline;
     use super::StringNewline;
+    use super::count_line_endings;
     use super::replace_crlf;
     use crate::newline::assure_newline;
 
@@ -272,6 +289,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_count_line_endings() {
+        let tests = vec![
+            ("", (0, 0)),
+            ("foo", (0, 0)),
+            ("\n", (0, 1)),
+            ("foo\nbar\n", (0, 2)),
+            ("foo\r\nbar\r\n", (2, 0)),
+            ("foo\r\nbar\nbaz\r\n", (2, 1)),
+            ("foo\rbar\n", (0, 1)),
+        ];
+        for (from, expect) in tests {
+            assert_eq!(
+                expect,
+                count_line_endings(from.as_bytes()),
+                "from `{from:?}`"
+            );
+        }
+    }
+
     #[test]
     fn test_split_at_newline() {
         let tests = vec![
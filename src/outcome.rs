@@ -10,11 +10,14 @@ use std::collections::HashMap;
 use serde::Serialize;
 use serde::ser::SerializeMap;
 
+use crate::config::TreeAssertion;
 use crate::escaping::Escaper;
+use crate::output::DirectoryTree;
 use crate::output::Output;
 use crate::parsers::parser::ParserType;
 use crate::testcase::Result as TestCaseResult;
 use crate::testcase::TestCase;
+use crate::testcase::TestCaseError;
 
 /// Aggregation of all that a renderer could possibly need to build a readable,
 /// understandable output
@@ -38,6 +41,44 @@ pub struct Outcome {
     pub result: TestCaseResult<()>,
 }
 
+impl Outcome {
+    /// Returns the paths of golden files, relative to the test document, that
+    /// the output or the files checked by `assert_files` did not match, and
+    /// the contents they are updated with
+    pub fn golden_file_updates(&self) -> Vec<(String, Vec<u8>)> {
+        let failures = match &self.result {
+            Err(TestCaseError::MultipleFailures(failures)) => failures.iter().collect(),
+            Err(failure) => vec![failure],
+            Ok(_) => vec![],
+        };
+        let mut updates = failures
+            .iter()
+            .flat_map(|failure| match failure {
+                TestCaseError::MalformedOutput(diff) => diff.golden_file_updates(),
+                _ => vec![],
+            })
+            .collect::<Vec<_>>();
+        if let (false, Some(assert_files)) =
+            (failures.is_empty(), &self.testcase.config.assert_files)
+        {
+            updates.extend(assert_files.golden_file_updates(&self.output.produced_files));
+        }
+        updates
+    }
+
+    /// Returns the golden directories of `assert_files` that the produced
+    /// directories did not match, and the produced directories they are
+    /// updated with
+    pub fn golden_tree_updates(&self) -> Vec<(&TreeAssertion, &DirectoryTree)> {
+        match (&self.result, &self.testcase.config.assert_files) {
+            (Err(_), Some(assert_files)) => {
+                assert_files.golden_tree_updates(&self.output.produced_files)
+            }
+            _ => vec![],
+        }
+    }
+}
+
 impl Serialize for Outcome {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
@@ -47,7 +88,13 @@ impl Serialize for Outcome {
         if self.location.is_some() {
             count += 1;
         }
-        if self.result.is_err() {
+        if self.result.is_err() || !self.testcase.config.annotations.is_empty() {
+            count += 1;
+        }
+        if self.result.is_ok() && self.output.resource_usage.is_some() {
+            count += 1;
+        }
+        if self.result.is_ok() && self.output.attempts.is_some() {
             count += 1;
         }
         let mut outcome = serializer.serialize_map(Some(count))?;
@@ -62,6 +109,15 @@ impl Serialize for Outcome {
             }
             Ok(_) => {
                 outcome.serialize_entry("title", &self.testcase.title)?;
+                if !self.testcase.config.annotations.is_empty() {
+                    outcome.serialize_entry("annotations", &self.testcase.config.annotations)?;
+                }
+                if let Some(ref resource_usage) = self.output.resource_usage {
+                    outcome.serialize_entry("resource_usage", resource_usage)?;
+                }
+                if let Some(attempts) = self.output.attempts {
+                    outcome.serialize_entry("attempts", &attempts)?;
+                }
                 let mut map = HashMap::new();
                 map.insert("kind", "success");
                 outcome.serialize_entry("result", &map)?;
@@ -73,8 +129,14 @@ impl Serialize for Outcome {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
     use super::Outcome;
+    use crate::config::TestCaseConfig;
     use crate::escaping::Escaper;
+    use crate::output::Output;
+    use crate::output::ResourceUsage;
     use crate::parsers::parser::ParserType;
     use crate::testcase::TestCaseError;
 
@@ -93,13 +155,13 @@ mod tests {
                         title: "the title".to_string(),
                         shell_expression: "the command".to_string(),
                         expectations: vec![test_expectation!("equal", "foo")],
-                        exit_code: Some(234),
+                        exit_code: Some(234.into()),
                         line_number: 234,
                         ..Default::default()
                     },
                     result: Err(TestCaseError::InvalidExitCode {
                         actual: 123,
-                        expected: 234,
+                        expected: 234.into(),
                     }),
                     escaping: Escaper::default(),
                     format: ParserType::Markdown,
@@ -114,7 +176,7 @@ mod tests {
                         title: "the title".to_string(),
                         shell_expression: "the command".to_string(),
                         expectations: vec![test_expectation!("equal", "foo")],
-                        exit_code: Some(123),
+                        exit_code: Some(123.into()),
                         line_number: 234,
                         ..Default::default()
                     },
@@ -123,6 +185,120 @@ mod tests {
                     format: ParserType::Markdown,
                 },
             ),
+            (
+                "annotated_success",
+                Outcome {
+                    location: Some("path/file.md".to_string()),
+                    output: ("stdout", "stderr", Some(0)).into(),
+                    testcase: TestCase {
+                        title: "the title".to_string(),
+                        shell_expression: "the command".to_string(),
+                        config: TestCaseConfig {
+                            annotations: BTreeMap::from([
+                                ("component".to_string(), "auth".to_string()),
+                                ("severity".to_string(), "p1".to_string()),
+                            ]),
+                            ..Default::default()
+                        },
+                        line_number: 234,
+                        ..Default::default()
+                    },
+                    result: Ok(()),
+                    escaping: Escaper::default(),
+                    format: ParserType::Markdown,
+                },
+            ),
+            (
+                "success_with_resource_usage",
+                Outcome {
+                    location: Some("path/file.md".to_string()),
+                    output: Output {
+                        resource_usage: Some(ResourceUsage {
+                            max_rss_bytes: 4 * 1024 * 1024,
+                            user_time: Duration::from_millis(120),
+                            system_time: Duration::from_millis(30),
+                        }),
+                        ..("stdout", "stderr", Some(0)).into()
+                    },
+                    testcase: TestCase {
+                        title: "the title".to_string(),
+                        shell_expression: "the command".to_string(),
+                        line_number: 234,
+                        ..Default::default()
+                    },
+                    result: Ok(()),
+                    escaping: Escaper::default(),
+                    format: ParserType::Markdown,
+                },
+            ),
+            (
+                "retried_success",
+                Outcome {
+                    location: Some("path/file.md".to_string()),
+                    output: Output {
+                        attempts: Some(3),
+                        ..("stdout", "stderr", Some(0)).into()
+                    },
+                    testcase: TestCase {
+                        title: "the title".to_string(),
+                        shell_expression: "the command".to_string(),
+                        line_number: 234,
+                        ..Default::default()
+                    },
+                    result: Ok(()),
+                    escaping: Escaper::default(),
+                    format: ParserType::Markdown,
+                },
+            ),
+            (
+                "error_with_resource_usage",
+                Outcome {
+                    location: Some("path/file.md".to_string()),
+                    output: Output {
+                        resource_usage: Some(ResourceUsage {
+                            max_rss_bytes: 4 * 1024 * 1024,
+                            user_time: Duration::from_millis(120),
+                            system_time: Duration::from_millis(30),
+                        }),
+                        ..("stdout", "stderr", Some(1)).into()
+                    },
+                    testcase: TestCase {
+                        title: "the title".to_string(),
+                        shell_expression: "the command".to_string(),
+                        line_number: 234,
+                        ..Default::default()
+                    },
+                    result: Err(TestCaseError::InvalidExitCode {
+                        actual: 1,
+                        expected: 0.into(),
+                    }),
+                    escaping: Escaper::default(),
+                    format: ParserType::Markdown,
+                },
+            ),
+            (
+                "error_with_artifacts",
+                Outcome {
+                    location: Some("path/file.md".to_string()),
+                    output: Output {
+                        work_directory: Some("/tmp/work".into()),
+                        artifacts: vec![".scrut/artifacts/path/file.md/logs/app.log".into()],
+                        ..("stdout", "stderr", Some(1)).into()
+                    },
+                    testcase: TestCase {
+                        title: "the title".to_string(),
+                        shell_expression: "the command".to_string(),
+                        line_number: 234,
+                        ..Default::default()
+                    },
+                    result: Err(TestCaseError::InvalidExitCode {
+                        actual: 1,
+                        expected: 0.into(),
+                    }),
+                    escaping: Escaper::default(),
+                    format: ParserType::Markdown,
+                },
+            ),
         ];
 
         for (name, outcome) in outcomes {
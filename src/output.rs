@@ -5,10 +5,14 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::fmt::Display;
+use std::path::Path;
+use std::path::PathBuf;
 use std::time::Duration;
 
+use globset::GlobSet;
 use serde::Serialize;
 use serde::ser::SerializeMap;
 
@@ -18,6 +22,7 @@ use crate::formatln;
 use crate::lossy_string;
 use crate::newline::SplitLinesByNewline;
 use crate::signal::KillSignal;
+use crate::signal::signal_name;
 
 #[derive(Clone, PartialEq, Eq)]
 pub struct DetachedProcess {
@@ -28,8 +33,154 @@ pub struct DetachedProcess {
     pub signal: KillSignal,
 }
 
-/// Product of a single execution that captures output and status
-#[derive(Clone, PartialEq, Eq)]
+impl DetachedProcess {
+    /// Sends the kill signal to the process group that the detached process
+    /// leads, so that all processes it started end with it
+    #[cfg(unix)]
+    pub fn kill(&self) -> anyhow::Result<()> {
+        let signal = self.signal.to_nix()?;
+        nix::sys::signal::killpg(nix::unistd::Pid::from_raw(self.pid as i32), signal)?;
+        Ok(())
+    }
+
+    /// Terminates the job object that the detached process was assigned to,
+    /// so that all processes it started end with it
+    #[cfg(windows)]
+    pub fn kill(&self) -> anyhow::Result<()> {
+        crate::executors::job_object::terminate_detached(self.pid)
+    }
+}
+
+/// Resources that were consumed by the execution of a single testcase,
+/// including all processes it started and waited for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ResourceUsage {
+    /// The maximum resident set size, in bytes, of the largest process
+    pub max_rss_bytes: u64,
+
+    /// The CPU time spent in user mode
+    #[serde(rename = "user_time_ms", serialize_with = "serialize_millis")]
+    pub user_time: Duration,
+
+    /// The CPU time spent in kernel mode
+    #[serde(rename = "system_time_ms", serialize_with = "serialize_millis")]
+    pub system_time: Duration,
+}
+
+impl Display for ResourceUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "max RSS {:.1} MiB, user {:.3}s, system {:.3}s",
+            self.max_rss_bytes as f64 / (1024.0 * 1024.0),
+            self.user_time.as_secs_f64(),
+            self.system_time.as_secs_f64(),
+        )
+    }
+}
+
+fn serialize_millis<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_u128(duration.as_millis())
+}
+
+/// The files and directories within a directory, by their path relative to
+/// it (with `/` as separator), as [`ProducedFile::Directory`] and
+/// [`ProducedFile::File`]
+pub type DirectoryTree = BTreeMap<String, ProducedFile>;
+
+/// The state of a path in the work directory after an execution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProducedFile {
+    /// Nothing exists at the path
+    Missing,
+
+    /// A directory exists at the path
+    Directory,
+
+    /// A (readable) file with the contents exists at the path
+    File(Vec<u8>),
+
+    /// A directory with the (not ignored) files and directories within
+    /// exists at the path
+    Tree(DirectoryTree),
+}
+
+impl ProducedFile {
+    /// Returns the state of the path, following symbolic links
+    pub fn read(path: &Path) -> Self {
+        if path.is_dir() {
+            Self::Directory
+        } else {
+            std::fs::read(path).map_or(Self::Missing, Self::File)
+        }
+    }
+
+    /// Returns the state of the path like [`ProducedFile::read`], but with
+    /// all files and directories within, if it is a directory. Those whose
+    /// relative path or name matches the ignore patterns are left out, as are
+    /// files that cannot be read.
+    pub fn read_tree(path: &Path, ignore: &GlobSet) -> Self {
+        if !path.is_dir() {
+            return Self::read(path);
+        }
+        let mut tree = DirectoryTree::new();
+        read_directory_tree(path, "", ignore, &mut tree);
+        Self::Tree(tree)
+    }
+}
+
+fn read_directory_tree(directory: &Path, prefix: &str, ignore: &GlobSet, tree: &mut DirectoryTree) {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let relative = format!("{prefix}{name}");
+        if ignore.is_match(&relative) || ignore.is_match(&name) {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            tree.insert(relative.clone(), ProducedFile::Directory);
+            read_directory_tree(&path, &format!("{relative}/"), ignore, tree);
+        } else if let Ok(contents) = std::fs::read(&path) {
+            tree.insert(relative, ProducedFile::File(contents));
+        }
+    }
+}
+
+/// Returns the changes that turn the `from` tree into the `to` tree, by the
+/// path relative to the trees: first the paths that only exist in `from`, as
+/// [`ProducedFile::Missing`], deepest first, and then all paths that differ
+/// or only exist in `to`, with their state in `to`, parents first
+pub fn directory_tree_changes(
+    from: &DirectoryTree,
+    to: &DirectoryTree,
+) -> Vec<(String, ProducedFile)> {
+    let removed = from
+        .keys()
+        .rev()
+        .filter(|path| !to.contains_key(*path))
+        .map(|path| (path.clone(), ProducedFile::Missing));
+    let changed = to
+        .iter()
+        .filter(|(path, state)| from.get(*path) != Some(state))
+        .map(|(path, state)| (path.clone(), state.clone()));
+    removed.chain(changed).collect()
+}
+
+/// Product of a single execution that captures output and status.
+///
+/// Only the output streams, the exit code and the detached process are
+/// considered in comparisons. The metadata of the execution (resource usage,
+/// duration, attempts, file digests, produced files, work directory and
+/// artifacts) differs between any two executions or is not output, so it is
+/// ignored.
+#[derive(Clone, Derivative)]
+#[derivative(PartialEq, Eq)]
 pub struct Output {
     /// The STDERR output of the execution
     pub stderr: OutputStream,
@@ -44,6 +195,44 @@ pub struct Output {
     /// The process PID and the configured kill signal, if the execution was
     /// intentionally detached.
     pub detached_process: Option<DetachedProcess>,
+
+    /// The resources the execution consumed, where the platform and the
+    /// executor allow to capture them
+    #[derivative(PartialEq = "ignore")]
+    pub resource_usage: Option<ResourceUsage>,
+
+    /// How long the execution took (wall clock), where the executor measures
+    /// it
+    #[derivative(PartialEq = "ignore")]
+    pub duration: Option<Duration>,
+
+    /// How often the shell expression was executed, if it was re-executed
+    /// until its output matched the expectations (see
+    /// [`crate::config::TestCaseConfig::retry`])
+    #[derivative(PartialEq = "ignore")]
+    pub attempts: Option<usize>,
+
+    /// Hex encoded SHA-256 digests of the files that the checksum
+    /// expectations of the testcase refer to, as they were right after the
+    /// execution, or `None` for files that did not exist
+    #[derivative(PartialEq = "ignore")]
+    pub file_digests: BTreeMap<String, Option<String>>,
+
+    /// The files and directories that the `assert_files` of the testcase
+    /// refer to, as they were right after the execution (see
+    /// [`crate::config::TestCaseConfig::assert_files`])
+    #[derivative(PartialEq = "ignore")]
+    pub produced_files: BTreeMap<String, ProducedFile>,
+
+    /// The work directory that was preserved after the execution failed, so
+    /// that the files it produced can be inspected
+    #[derivative(PartialEq = "ignore")]
+    pub work_directory: Option<PathBuf>,
+
+    /// The files that were collected from the work directory after the
+    /// execution failed (see [`crate::config::DocumentConfig::artifacts`])
+    #[derivative(PartialEq = "ignore")]
+    pub artifacts: Vec<PathBuf>,
 }
 
 impl Output {
@@ -84,6 +273,13 @@ impl Default for Output {
             stderr: vec![].into(),
             exit_code: ExitStatus::Unknown,
             detached_process: None,
+            resource_usage: None,
+            duration: None,
+            attempts: None,
+            file_digests: BTreeMap::new(),
+            produced_files: BTreeMap::new(),
+            work_directory: None,
+            artifacts: vec![],
         }
     }
 }
@@ -93,11 +289,23 @@ impl Serialize for Output {
     where
         S: serde::Serializer,
     {
-        let count = if self.detached_process.is_some() {
+        let mut count = if self.detached_process.is_some() {
             5
         } else {
             3
         };
+        if self.resource_usage.is_some() {
+            count += 1;
+        }
+        if self.attempts.is_some() {
+            count += 1;
+        }
+        if self.work_directory.is_some() {
+            count += 1;
+        }
+        if !self.artifacts.is_empty() {
+            count += 1;
+        }
         let mut map = serializer.serialize_map(Some(count))?;
         map.serialize_entry("exit_code", &self.exit_code.to_string())?;
         map.serialize_entry("stdout", &lossy_string!((&self.stdout).into()))?;
@@ -106,6 +314,18 @@ impl Serialize for Output {
             map.serialize_entry("detached_process_pid", &detached_process.pid)?;
             map.serialize_entry("detached_process_signal", &detached_process.signal)?;
         }
+        if let Some(ref resource_usage) = self.resource_usage {
+            map.serialize_entry("resource_usage", resource_usage)?;
+        }
+        if let Some(attempts) = self.attempts {
+            map.serialize_entry("attempts", &attempts)?;
+        }
+        if let Some(ref work_directory) = self.work_directory {
+            map.serialize_entry("work_directory", work_directory)?;
+        }
+        if !self.artifacts.is_empty() {
+            map.serialize_entry("artifacts", &self.artifacts)?;
+        }
         map.end()
     }
 }
@@ -120,6 +340,13 @@ impl<T: ToString, U: ToString> From<(T, U, Option<i32>)> for Output {
                 Some(code) => ExitStatus::Code(code),
             },
             detached_process: None,
+            resource_usage: None,
+            duration: None,
+            attempts: None,
+            file_digests: BTreeMap::new(),
+            produced_files: BTreeMap::new(),
+            work_directory: None,
+            artifacts: vec![],
         }
     }
 }
@@ -137,6 +364,13 @@ impl From<Duration> for Output {
             stderr: vec![].into(),
             exit_code: ExitStatus::Timeout(timeout),
             detached_process: None,
+            resource_usage: None,
+            duration: None,
+            attempts: None,
+            file_digests: BTreeMap::new(),
+            produced_files: BTreeMap::new(),
+            work_directory: None,
+            artifacts: vec![],
         }
     }
 }
@@ -148,6 +382,13 @@ impl From<ExitStatus> for Output {
             stderr: vec![].into(),
             exit_code: status,
             detached_process: None,
+            resource_usage: None,
+            duration: None,
+            attempts: None,
+            file_digests: BTreeMap::new(),
+            produced_files: BTreeMap::new(),
+            work_directory: None,
+            artifacts: vec![],
         }
     }
 }
@@ -158,6 +399,9 @@ pub enum ExitStatus {
     /// Execution resulted in exit code
     Code(i32),
 
+    /// Execution was terminated by the signal with the number
+    Signal(i32),
+
     /// Execution never finished due to timeout
     Timeout(Duration),
 
@@ -176,10 +420,12 @@ impl ExitStatus {
     /// Exit code 0 denotes success
     pub const SUCCESS: Self = Self::Code(0);
 
-    /// Returns exit code as integer with -1 for timeout and -255 for unknown
+    /// Returns exit code as integer with -1 for timeout and -255 for unknown.
+    /// Signals are returned as `128 + N`, as shells report them.
     pub fn as_code(&self) -> i32 {
         match self {
             Self::Code(code) => *code,
+            Self::Signal(signal) => 128 + signal,
             Self::Skipped => DEFAULT_SKIP_DOCUMENT_CODE,
             Self::Timeout(_) => -1,
             Self::Detached => -100,
@@ -192,6 +438,10 @@ impl Display for ExitStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Code(code) => write!(f, "{}", code),
+            Self::Signal(signal) => match signal_name(*signal) {
+                Some(name) => write!(f, "signal:{}", name),
+                None => write!(f, "signal:{}", signal),
+            },
             Self::Timeout(duration) => write!(f, "timeout[{:.2}ms]", duration.as_millis()),
             Self::Skipped => write!(f, "skipped"),
             Self::Detached => write!(f, "detached"),
@@ -240,6 +490,15 @@ impl OutputStream {
     pub fn to_bytes(&self) -> Vec<u8> {
         self.0.clone()
     }
+
+    /// Returns the amount of bytes of the stream
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 impl From<Vec<u8>> for OutputStream {
@@ -274,9 +533,78 @@ impl<'a> From<&'a OutputStream> for &'a [u8] {
 
 #[cfg(test)]
 mod tests {
+    use std::fs;
+
+    use globset::Glob;
+    use globset::GlobSet;
+    use globset::GlobSetBuilder;
+
+    use super::DirectoryTree;
     use super::OutputStream;
+    use super::ProducedFile;
+    use super::directory_tree_changes;
     use crate::escaping::Escaper;
 
+    #[test]
+    fn test_read_tree() {
+        let directory = tempfile::TempDir::new().expect("create directory");
+        for path in ["a/b/file.txt", "a/debug.log", "target/out.bin"] {
+            let path = directory.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).expect("create directory");
+            fs::write(&path, "contents").expect("write file");
+        }
+        fs::create_dir(directory.path().join("empty")).expect("create directory");
+        let ignore = GlobSetBuilder::new()
+            .add(Glob::new("*.log").unwrap())
+            .add(Glob::new("target").unwrap())
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            ProducedFile::Tree(DirectoryTree::from([
+                ("a".to_string(), ProducedFile::Directory),
+                ("a/b".to_string(), ProducedFile::Directory),
+                (
+                    "a/b/file.txt".to_string(),
+                    ProducedFile::File(b"contents".to_vec())
+                ),
+                ("empty".to_string(), ProducedFile::Directory),
+            ])),
+            ProducedFile::read_tree(directory.path(), &ignore)
+        );
+        assert_eq!(
+            ProducedFile::Missing,
+            ProducedFile::read_tree(&directory.path().join("missing"), &GlobSet::empty())
+        );
+    }
+
+    #[test]
+    fn test_directory_tree_changes() {
+        let from = DirectoryTree::from([
+            ("a".to_string(), ProducedFile::Directory),
+            ("a/old.txt".to_string(), ProducedFile::File(b"old".to_vec())),
+            ("same.txt".to_string(), ProducedFile::File(b"same".to_vec())),
+            ("changed.txt".to_string(), ProducedFile::File(b"1".to_vec())),
+        ]);
+        let to = DirectoryTree::from([
+            ("b".to_string(), ProducedFile::Directory),
+            ("b/new.txt".to_string(), ProducedFile::File(b"new".to_vec())),
+            ("same.txt".to_string(), ProducedFile::File(b"same".to_vec())),
+            ("changed.txt".to_string(), ProducedFile::File(b"2".to_vec())),
+        ]);
+        assert_eq!(
+            vec![
+                ("a/old.txt".to_string(), ProducedFile::Missing),
+                ("a".to_string(), ProducedFile::Missing),
+                ("b".to_string(), ProducedFile::Directory),
+                ("b/new.txt".to_string(), ProducedFile::File(b"new".to_vec())),
+                ("changed.txt".to_string(), ProducedFile::File(b"2".to_vec())),
+            ],
+            directory_tree_changes(&from, &to)
+        );
+        assert!(directory_tree_changes(&to, &to).is_empty());
+    }
+
     #[test]
     fn test_output_stream_appends_no_eol() {
         let tests = vec![
@@ -31,6 +31,7 @@ pub const DEFAULT_CRAM_INDENTION: usize = 2;
 pub struct CramParser {
     expectation_maker: Arc<ExpectationMaker>,
     indention: usize,
+    base_document_config: DocumentConfig,
 }
 
 impl CramParser {
@@ -38,8 +39,22 @@ impl CramParser {
         CramParser {
             expectation_maker,
             indention,
+            base_document_config: DocumentConfig::default_cram(),
         }
     }
+
+    /// Builder setter for the document configuration that is returned for all
+    /// parsed documents, which also provides the defaults for all testcases
+    pub fn with_base_document_config(mut self, config: DocumentConfig) -> Self {
+        self.base_document_config = config;
+        self
+    }
+
+    fn testcase_config(&self) -> TestCaseConfig {
+        self.base_document_config
+            .testcase_defaults()
+            .with_defaults_from(&TestCaseConfig::default_cram())
+    }
 }
 
 /* impl Default for CramParser {
@@ -52,6 +67,7 @@ impl Parser for CramParser {
     /// See [`super::parser::Parser::parse`]
     fn parse(&self, text: &str) -> Result<(DocumentConfig, Vec<TestCase>)> {
         let mut engine = LineParser::new(self.expectation_maker.clone(), true);
+        engine.set_document_config(self.base_document_config.clone())?;
         let lines = text.lines().collect::<Vec<_>>();
         let indent = " ".repeat(self.indention);
         debug!("parsing {} lines of cram file", lines.len());
@@ -73,7 +89,7 @@ impl Parser for CramParser {
             // shell expression or testcase output expectations:
             if let Some(line) = line.strip_prefix(&indent) {
                 engine.add_testcase_body(line, index)?;
-                engine.set_testcase_config(TestCaseConfig::default_cram());
+                engine.set_testcase_config(self.testcase_config());
                 continue;
             }
 
@@ -85,12 +101,12 @@ impl Parser for CramParser {
         }
 
         if engine.has_testcase_body() {
-            engine.set_testcase_config(TestCaseConfig::default_cram());
+            engine.set_testcase_config(self.testcase_config());
             engine.end_testcase(lines.len())?
         }
         debug!("found {} testcases in cram file", engine.testcases.len());
 
-        Ok((DocumentConfig::default_cram(), engine.testcases.clone()))
+        Ok((self.base_document_config.clone(), engine.testcases.clone()))
     }
 }
 
@@ -123,9 +139,13 @@ mod tests {
         assert_eq!(
             TestCase {
                 shell_expression: "echo hello".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("equal", "hello", false, false)],
+                variants: vec![],
                 title: "This is a title".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 2,
                 config: TestCaseConfig::default_cram(),
             },
@@ -152,9 +172,13 @@ This is a title
         assert_eq!(
             TestCase {
                 shell_expression: "echo hello".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("equal", "hello", false, false)],
+                variants: vec![],
                 title: "This is a title".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 7,
                 config: TestCaseConfig::default_cram(),
             },
@@ -182,9 +206,13 @@ Title 2
         assert_eq!(
             TestCase {
                 shell_expression: "echo hello".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("equal", "hello", false, false)],
+                variants: vec![],
                 title: "Title 2".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 8,
                 config: TestCaseConfig::default_cram(),
             },
@@ -214,9 +242,13 @@ This is the yet more title
         assert_eq!(
             TestCase {
                 shell_expression: "echo hello".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("equal", "hello", false, false)],
+                variants: vec![],
                 title: "This is a title".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 3,
                 config: TestCaseConfig::default_cram(),
             },
@@ -226,9 +258,13 @@ This is the yet more title
         assert_eq!(
             TestCase {
                 shell_expression: "echo something".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("equal", "something", false, false)],
+                variants: vec![],
                 title: "This is the next title".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 9,
                 config: TestCaseConfig::default_cram(),
             },
@@ -238,9 +274,13 @@ This is the yet more title
         assert_eq!(
             TestCase {
                 shell_expression: "echo lastly".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("equal", "lastly", false, false)],
+                variants: vec![],
                 title: "This is the yet more title".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 12,
                 config: TestCaseConfig::default_cram(),
             },
@@ -266,13 +306,17 @@ The title
         assert_eq!(
             TestCase {
                 shell_expression: ["echo hello && \\", "echo more && \\", "echo most"].join("\n"),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![
                     test_expectation!("equal", "hello", false, false),
                     test_expectation!("equal", "more", false, false),
                     test_expectation!("equal", "most", false, false),
                 ],
+                variants: vec![],
                 title: "The title".into(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 3,
                 config: TestCaseConfig::default_cram(),
             },
@@ -280,6 +324,42 @@ The title
         );
     }
 
+    #[test]
+    fn test_heredoc_body_is_part_of_command() {
+        let cram_test = "This is a title\n  $ cat <<EOF\n  $ not a command\n  \n  [1]\n  EOF\n  $ not a command\n  [1]\n";
+        let parser = parser();
+        let (_, testcases) = parser.parse(cram_test).expect("must parse");
+        assert_eq!(
+            vec![
+                TestCase {
+                    shell_expression: "cat <<EOF\n$ not a command\n\n[1]\nEOF".to_string(),
+                    template_arguments: None,
+                    interaction: vec![],
+                    expectations: vec![],
+                    variants: vec![],
+                    title: "This is a title".to_string(),
+                    exit_code: None,
+                    assertions: vec![],
+                    line_number: 2,
+                    config: TestCaseConfig::default_cram(),
+                },
+                TestCase {
+                    shell_expression: "not a command".to_string(),
+                    template_arguments: None,
+                    interaction: vec![],
+                    expectations: vec![],
+                    variants: vec![],
+                    title: "".to_string(),
+                    exit_code: Some(1.into()),
+                    assertions: vec![],
+                    line_number: 7,
+                    config: TestCaseConfig::default_cram(),
+                },
+            ],
+            testcases
+        );
+    }
+
     #[test]
     fn test_exit_code_is_extracted() {
         let cram_test = r#"
@@ -304,9 +384,13 @@ This has an exit code 3
         assert_eq!(
             TestCase {
                 shell_expression: "command1".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("equal", "output", false, false)],
+                variants: vec![],
                 title: "This has an exit code 1".to_string(),
-                exit_code: Some(4),
+                exit_code: Some(4.into()),
+                assertions: vec![],
                 line_number: 3,
                 config: TestCaseConfig::default_cram(),
             },
@@ -315,9 +399,13 @@ This has an exit code 3
         assert_eq!(
             TestCase {
                 shell_expression: "command2".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![],
+                variants: vec![],
                 title: "This has an exit code 2".to_string(),
-                exit_code: Some(15),
+                exit_code: Some(15.into()),
+                assertions: vec![],
                 line_number: 8,
                 config: TestCaseConfig::default_cram(),
             },
@@ -326,12 +414,16 @@ This has an exit code 3
         assert_eq!(
             TestCase {
                 shell_expression: "command3".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![
                     test_expectation!("equal", "output1", false, false),
                     test_expectation!("equal", "output2", false, false)
                 ],
+                variants: vec![],
                 title: "This has an exit code 3".to_string(),
-                exit_code: Some(106),
+                exit_code: Some(106.into()),
+                assertions: vec![],
                 line_number: 12,
                 config: TestCaseConfig::default_cram(),
             },
@@ -376,6 +468,8 @@ This is a title
         assert_eq!(
             TestCase {
                 shell_expression: "echo hello".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![
                     test_expectation!(
                         "equal",
@@ -385,8 +479,10 @@ This is a title
                     ),
                     test_expectation!("equal", "hello", false, false),
                 ],
+                variants: vec![],
                 title: "This is a title".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 6,
                 config: TestCaseConfig::default_cram(),
             },
@@ -413,9 +509,13 @@ This is a title
         assert_eq!(
             TestCase {
                 shell_expression: "source $TESTDIR/setup.sh".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![],
+                variants: vec![],
                 title: "Setup a buck dir with a mock visibility list".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 2,
                 config: TestCaseConfig::default_cram(),
             },
@@ -424,9 +524,13 @@ This is a title
         assert_eq!(
             TestCase {
                 shell_expression: "mkdir -p path/to".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![],
+                variants: vec![],
                 title: "".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 3,
                 config: TestCaseConfig::default_cram(),
             },
@@ -443,9 +547,13 @@ This is a title
                     "EOF",
                 ]
                 .join("\n"),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![],
+                variants: vec![],
                 title: "".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 4,
                 config: TestCaseConfig::default_cram(),
             },
@@ -454,9 +562,13 @@ This is a title
         assert_eq!(
             TestCase {
                 shell_expression: ["bla"].join("\n"),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![],
+                variants: vec![],
                 title: "".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 10,
                 config: TestCaseConfig::default_cram(),
             },
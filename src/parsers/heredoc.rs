@@ -0,0 +1,210 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::VecDeque;
+
+/// A here-document that is opened in a shell expression, like `<<EOF` or
+/// `<<-'EOF'`
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Heredoc {
+    /// The word that ends the body of the here-document, without quotes
+    pub(crate) delimiter: String,
+
+    /// Whether leading tabs are stripped from the lines of the body (`<<-`)
+    pub(crate) strip_tabs: bool,
+}
+
+impl Heredoc {
+    /// Whether the given line of the body ends the here-document
+    fn is_closed_by(&self, line: &str) -> bool {
+        let line = if self.strip_tabs {
+            line.trim_start_matches('\t')
+        } else {
+            line
+        };
+        line == self.delimiter
+    }
+}
+
+/// Returns the here-documents of the given shell expression, whose body is
+/// not yet ended by its delimiter, in the order their bodies are read.
+///
+/// This is not a shell parser: it only knows enough about quoting, comments,
+/// here-strings (`<<<`) and arithmetic (`$((1 << 2))`) to not mistake them
+/// for here-documents.
+pub(crate) fn open_heredocs(expression: &str) -> Vec<Heredoc> {
+    let mut scanner = Scanner::default();
+    let mut bodies: VecDeque<Heredoc> = VecDeque::new();
+    for line in expression.split('\n') {
+        if let Some(heredoc) = bodies.front() {
+            if heredoc.is_closed_by(line) {
+                bodies.pop_front();
+            }
+            continue;
+        }
+        scanner.scan_line(line);
+
+        // bodies start in the line after the command, which can span multiple
+        // lines with quotes or escaped newlines
+        if scanner.quote.is_none() && !scanner.escaped_newline {
+            bodies.extend(scanner.opened.drain(..));
+        }
+    }
+    bodies.extend(scanner.opened);
+    bodies.into()
+}
+
+#[derive(Default)]
+struct Scanner {
+    quote: Option<char>,
+    arithmetic: usize,
+    escaped_newline: bool,
+    opened: Vec<Heredoc>,
+}
+
+impl Scanner {
+    fn scan_line(&mut self, line: &str) {
+        self.escaped_newline = false;
+        let chars = line.chars().collect::<Vec<_>>();
+        let mut index = 0;
+        while index < chars.len() {
+            let current = chars[index];
+            let next = chars.get(index + 1).copied();
+            match (self.quote, current) {
+                (Some('\''), '\'') => self.quote = None,
+                (Some('\''), _) => {}
+                (Some(_), '\\') => index += 1,
+                (Some(quote), _) if current == quote => self.quote = None,
+                (Some(_), _) => {}
+                (None, '\\') if next.is_none() => self.escaped_newline = true,
+                (None, '\\') => index += 1,
+                (None, '\'' | '"') => self.quote = Some(current),
+                (None, '#') if index == 0 || is_separator(chars[index - 1]) => break,
+                (None, '(') if next == Some('(') => {
+                    self.arithmetic += 1;
+                    index += 1;
+                }
+                (None, ')') if next == Some(')') && self.arithmetic > 0 => {
+                    self.arithmetic -= 1;
+                    index += 1;
+                }
+                (None, '<') if next == Some('<') && self.arithmetic == 0 => {
+                    index += 2;
+                    if chars.get(index) == Some(&'<') {
+                        // here-string
+                        index += 1;
+                        continue;
+                    }
+                    let strip_tabs = chars.get(index) == Some(&'-');
+                    if strip_tabs {
+                        index += 1;
+                    }
+                    while chars.get(index).is_some_and(|c| *c == ' ' || *c == '\t') {
+                        index += 1;
+                    }
+                    let (delimiter, end) = read_word(&chars, index);
+                    if !delimiter.is_empty() {
+                        self.opened.push(Heredoc {
+                            delimiter,
+                            strip_tabs,
+                        });
+                    }
+                    index = end;
+                    continue;
+                }
+                _ => {}
+            }
+            index += 1;
+        }
+    }
+}
+
+/// Reads a shell word from the given position and returns it with quotes
+/// removed, along with the position after it
+fn read_word(chars: &[char], mut index: usize) -> (String, usize) {
+    let mut word = String::new();
+    let mut quote = None;
+    while let Some(current) = chars.get(index).copied() {
+        match (quote, current) {
+            (Some(q), _) if current == q => quote = None,
+            (Some(_), _) => word.push(current),
+            (None, '\'' | '"') => quote = Some(current),
+            (None, '\\') => {
+                index += 1;
+                if let Some(escaped) = chars.get(index) {
+                    word.push(*escaped);
+                }
+            }
+            (None, _) if is_separator(current) || current == '<' || current == '>' => break,
+            (None, _) => word.push(current),
+        }
+        index += 1;
+    }
+    (word, index)
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || matches!(c, ';' | '|' | '&' | '(' | ')')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Heredoc;
+    use super::open_heredocs;
+
+    fn heredoc(delimiter: &str, strip_tabs: bool) -> Heredoc {
+        Heredoc {
+            delimiter: delimiter.into(),
+            strip_tabs,
+        }
+    }
+
+    #[test]
+    fn test_open_heredocs() {
+        let tests = vec![
+            ("echo hello", vec![]),
+            ("cat <<EOF", vec![heredoc("EOF", false)]),
+            ("cat << EOF", vec![heredoc("EOF", false)]),
+            ("cat <<-EOF", vec![heredoc("EOF", true)]),
+            ("cat <<'EOF'", vec![heredoc("EOF", false)]),
+            ("cat <<\"EOF\"", vec![heredoc("EOF", false)]),
+            ("cat <<\\EOF", vec![heredoc("EOF", false)]),
+            ("cat <<E'O'F", vec![heredoc("EOF", false)]),
+            ("cat <<EOF>out", vec![heredoc("EOF", false)]),
+            ("cat <<EOF | wc -l", vec![heredoc("EOF", false)]),
+            (
+                "cat <<ONE; cat <<-TWO",
+                vec![heredoc("ONE", false), heredoc("TWO", true)],
+            ),
+            ("cat <<EOF\nline\nEOF", vec![]),
+            ("cat <<EOF\nline\n  EOF", vec![heredoc("EOF", false)]),
+            ("cat <<-EOF\n\tline\n\tEOF", vec![]),
+            (
+                "cat <<ONE; cat <<TWO\none\nONE\ntwo",
+                vec![heredoc("TWO", false)],
+            ),
+            ("cat <<ONE; cat <<TWO\none\nONE\ntwo\nTWO", vec![]),
+            ("cat <<EOF \\\n  | wc -l", vec![heredoc("EOF", false)]),
+            ("cat <<EOF\n$(echo <<NOT)\nEOF", vec![]),
+            ("cat <<<word", vec![]),
+            ("echo $((1 << 2))", vec![]),
+            ("echo '<<EOF'", vec![]),
+            ("echo \"<<EOF\"", vec![]),
+            ("echo \\<<EOF", vec![]),
+            ("echo foo # <<EOF", vec![]),
+            ("echo foo#<<EOF", vec![heredoc("EOF", false)]),
+            ("echo 'multi\nline' <<EOF", vec![heredoc("EOF", false)]),
+        ];
+        for (expression, expected) in tests {
+            assert_eq!(
+                expected,
+                open_heredocs(expression),
+                "expression {expression:?}"
+            );
+        }
+    }
+}
@@ -5,6 +5,7 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::borrow::Cow;
 use std::sync::Arc;
 
 use anyhow::Context;
@@ -12,26 +13,44 @@ use anyhow::Result;
 use anyhow::bail;
 use regex::Regex;
 
+use crate::assertions::assertion::Assertion;
+use crate::assertions::registry::AssertionRegistry;
+use crate::config::DocumentConfig;
+use crate::config::Shell;
 use crate::config::TestCaseConfig;
+use crate::executors::DEFAULT_SHELL;
+use crate::executors::powershell_runner::POWERSHELL_PROMPT;
 use crate::expectation::Expectation;
 use crate::expectation::ExpectationMaker;
+use crate::expectation::UNORDERED_END;
+use crate::expectation::UNORDERED_START;
+use crate::expectation::VARIANT_SEPARATOR;
+use crate::newline::StringNewline;
+use crate::parsers::heredoc::open_heredocs;
+use crate::rules::rule::IncompleteExpression;
+use crate::testcase::ExpectedExitCode;
+use crate::testcase::InteractionStep;
 use crate::testcase::TestCase;
 
 lazy_static! {
     /// Exit code expression matches an output line of the form:
     ///
     /// ```bnf
-    /// <exit-code-expression> ::= "[" <integer> "]"
+    /// <exit-code-expression> ::= "[" <exit-code> "]"
+    /// <exit-code> ::= <integer> | <integer> "-" <integer> | "!" <integer> | "signal:" <signal>
     /// ```
     static ref EXIT_CODE_EXPRESSION: Regex =
-        Regex::new("^\\[([0-9]+)\\]$").expect("exit code expression must compile");
+        Regex::new("^\\[([0-9]+|[0-9]+-[0-9]+|![0-9]+|exit:[0-9]+|signal:[A-Za-z0-9]+)\\]$")
+            .expect("exit code expression must compile");
 }
 
 pub(super) enum CodeType {
     CommandStart,
     CommandContinue,
+    Interaction,
     Expectation,
     ExitCode,
+    Assertion,
 }
 
 /// A meta parser engine, that can be used for any line-by-line test file format
@@ -43,17 +62,59 @@ pub(super) enum CodeType {
 /// other lines are
 /// all output
 /// ```
+///
+/// Testcases that are attached to a pseudo-terminal (see
+/// [`crate::config::TestCaseConfig::tty`]) can additionally have interaction
+/// steps in between the shell expression and the output expectations:
+///
+/// ```txt
+/// $ a-command
+/// ? expected prompt
+/// ! sent input
+/// output of the whole session
+/// ```
+///
+/// Markdown documents can start the shell expression with the PowerShell
+/// prompt `PS> ` instead of `$ `.
+///
+/// Documents with a [`DocumentConfig::command_template`] can start testcases
+/// with a shorthand line `% <args>` instead, from which the shell expression
+/// is rendered.
+///
+/// Lines of the form `@assert <kind> <arguments>` in between the output
+/// expectations are assertions (see [`crate::assertions::assertion::Assertion`]),
+/// that are evaluated after the execution.
+///
+/// Expectations that are incomplete, like the first line of a JSON document
+/// (see [`crate::rules::json::JsonRule`]), are continued by the following lines
+/// until they are complete. Expectations of documents that cannot tell when
+/// they are complete, like YAML (see [`crate::rules::yaml::YamlRule`]), are
+/// continued up to an exit code or an assertion.
+///
+/// Expectations in between the lines `(unordered)` and `(ordered)` (or the end
+/// of the expectations) match the lines of output in any order.
+///
+/// Lines `(or)` separate alternative variants of the expectations, of which
+/// the output must match any one, as when it differs by platform.
 pub(super) struct LineParser {
     pub(super) testcases: Vec<TestCase>,
     expectation_maker: Arc<ExpectationMaker>,
+    assertion_registry: AssertionRegistry,
     title: Option<String>,
     command: Vec<String>,
-    exit_code: Option<i32>,
+    shorthand: bool,
+    interaction: Vec<InteractionStep>,
+    exit_code: Option<ExpectedExitCode>,
     expectations: Vec<Expectation>,
+    variants: Vec<Vec<Expectation>>,
+    open_expectation: Option<(String, usize)>,
+    unordered: bool,
+    assertions: Vec<Box<dyn Assertion>>,
     in_command: bool,
     allow_multiple_commands: bool,
     output_start_index: Option<usize>,
     config: Option<TestCaseConfig>,
+    document_config: DocumentConfig,
 }
 
 impl LineParser {
@@ -63,23 +124,54 @@ impl LineParser {
     ) -> Self {
         Self {
             expectation_maker,
+            assertion_registry: AssertionRegistry::default(),
             title: None,
             command: vec![],
+            shorthand: false,
+            interaction: vec![],
             expectations: vec![],
+            variants: vec![],
+            open_expectation: None,
+            unordered: false,
+            assertions: vec![],
             exit_code: None,
             testcases: vec![],
             in_command: false,
             allow_multiple_commands,
             output_start_index: None,
             config: None,
+            document_config: DocumentConfig::empty(),
         }
     }
 
     /// Add a line that is either a command or an expectation
     pub(super) fn add_testcase_body(&mut self, line: &str, index: usize) -> Result<CodeType> {
-        // start of command
+        // body of a here-document, that is not written as continuation of the
+        // command, is still part of the command up until its delimiter
+        let is_continuation = self.in_command && (line == ">" || line.starts_with("> "));
+        if !is_continuation
+            && !self.command.is_empty()
+            && !open_heredocs(&self.command.join("\n")).is_empty()
+        {
+            self.in_command = false;
+            self.command.push(line.into());
+            return Ok(CodeType::CommandContinue);
+        }
+
+        // start of command, or of shorthand for the command template
         if self.allow_multiple_commands || self.command.is_empty() {
-            if let Some(line) = line.strip_prefix("$ ") {
+            let start = line
+                .strip_prefix("$ ")
+                .or_else(|| self.strip_powershell_prompt(line))
+                .map(|line| (line, false))
+                .or_else(|| {
+                    self.document_config
+                        .command_template
+                        .as_ref()
+                        .and_then(|_| line.strip_prefix("% "))
+                        .map(|line| (line, true))
+                });
+            if let Some((line, shorthand)) = start {
                 self.in_command = true;
                 if !self.command.is_empty() {
                     self.end_testcase(index)?;
@@ -87,6 +179,7 @@ impl LineParser {
                 if self.output_start_index.is_none() {
                     self.output_start_index = Some(index);
                 }
+                self.shorthand = shorthand;
                 self.command.push(line.into());
                 return Ok(CodeType::CommandStart);
             }
@@ -106,27 +199,180 @@ impl LineParser {
         }
 
         self.in_command = false;
+        let is_unordered_marker = line == UNORDERED_START || line == UNORDERED_END;
+        let is_variant_separator = line == VARIANT_SEPARATOR;
+        if let Some((open, start_index)) = self.open_expectation.take() {
+            if !is_unordered_marker
+                && !is_variant_separator
+                && extract_exit_code(line).is_none()
+                && self.assertion_registry.parse(line).is_none()
+            {
+                return self.add_expectation(format!("{open}\n{line}"), start_index);
+            }
+            self.close_expectation(&open, start_index)?;
+        }
+
+        if self.accepts_interaction() {
+            if let Some(step) = InteractionStep::parse(line) {
+                self.interaction
+                    .push(step.with_context(|| format!("parsing line {}", index + 1))?);
+                return Ok(CodeType::Interaction);
+            }
+        }
+
+        if is_unordered_marker {
+            self.unordered = line == UNORDERED_START;
+            return Ok(CodeType::Expectation);
+        }
+
+        if is_variant_separator {
+            if self.exit_code.is_some() || !self.assertions.is_empty() {
+                bail!(
+                    "line {}: variants of expectations must precede exit code and assertions",
+                    index + 1
+                )
+            }
+            self.variants.push(std::mem::take(&mut self.expectations));
+            self.unordered = false;
+            return Ok(CodeType::Expectation);
+        }
+
         if let Some(exit_code) = extract_exit_code(line) {
             if self.exit_code.is_some() {
                 bail!("line {}: exit code provided multiple times", index + 1)
             }
-            self.exit_code = Some(exit_code);
+            self.exit_code =
+                Some(exit_code.with_context(|| format!("parsing line {}", index + 1))?);
             return Ok(CodeType::ExitCode);
         }
 
-        self.expectations.push(
-            self.expectation_maker
-                .parse(line)
-                .with_context(|| format!("parsing line {}", index + 1))?,
-        );
+        if let Some(assertion) = self.assertion_registry.parse(line) {
+            self.assertions
+                .push(assertion.with_context(|| format!("parsing line {}", index + 1))?);
+            return Ok(CodeType::Assertion);
+        }
+
+        self.add_expectation(line.to_string(), index)
+    }
+
+    /// Returns the shell expression of a line that starts with the PowerShell
+    /// prompt, which Markdown documents can use instead of `$`
+    fn strip_powershell_prompt<'a>(&self, line: &'a str) -> Option<&'a str> {
+        if self.allow_multiple_commands {
+            return None;
+        }
+        line.strip_prefix(POWERSHELL_PROMPT)?.strip_prefix(' ')
+    }
+
+    /// Add an expectation from the given line(s), starting at the line index,
+    /// or keep them open if they are incomplete or continue
+    fn add_expectation(&mut self, text: String, index: usize) -> Result<CodeType> {
+        match self.parse_expectation(&text) {
+            Ok(expectation) if expectation.rule.continues_expression() => {
+                self.open_expectation = Some((text, index));
+            }
+            Err(err) if err.downcast_ref::<IncompleteExpression>().is_some() => {
+                self.open_expectation = Some((text, index));
+            }
+            expectation => self.push_expectation(
+                expectation.with_context(|| format!("parsing line {}", index + 1))?,
+                index,
+            )?,
+        }
         Ok(CodeType::Expectation)
     }
 
+    /// Add the open expectation from the given line(s), starting at the line
+    /// index, which must be complete now
+    fn close_expectation(&mut self, text: &str, index: usize) -> Result<()> {
+        let expectation = self
+            .parse_expectation(text)
+            .with_context(|| format!("parsing line {}", index + 1))?;
+        self.push_expectation(expectation, index)
+    }
+
+    /// Add the expectation from the given line index, as part of the current
+    /// group of unordered expectations, if there is one
+    fn push_expectation(&mut self, expectation: Expectation, index: usize) -> Result<()> {
+        if expectation.rule.asserts_absence() && (expectation.optional || expectation.multiline) {
+            bail!(
+                "line {}: expectations that assert absence apply to all lines of output, so they cannot have quantifiers",
+                index + 1
+            )
+        }
+        if expectation.count.is_some()
+            && (expectation.rule.matches_remaining_output()
+                || expectation.rule.file_checksum().is_some())
+        {
+            bail!(
+                "line {}: expectations of remaining output or files cannot have a line count",
+                index + 1
+            )
+        }
+        if self.unordered
+            && (expectation.optional
+                || expectation.multiline
+                || expectation.rule.matches_remaining_output()
+                || expectation.rule.file_checksum().is_some()
+                || expectation.rule.asserts_absence())
+        {
+            bail!(
+                "line {}: unordered expectations must match exactly one line of output, so they cannot have quantifiers or assert remaining output, files or absence",
+                index + 1
+            )
+        }
+        self.expectations
+            .push(expectation.with_unordered(self.unordered));
+        Ok(())
+    }
+
+    fn parse_expectation(&self, text: &str) -> Result<Expectation> {
+        // expectations are compared in the same normalization form as the output
+        let normalization = self
+            .config
+            .as_ref()
+            .and_then(|config| config.unicode_normalization);
+        match normalization.map(|normalization| normalization.normalize(text)) {
+            Some(Cow::Owned(normalized)) => self
+                .expectation_maker
+                .parse(&normalized)
+                .map(|expectation| expectation.with_original(&(&text).trim_newlines())),
+            _ => self.expectation_maker.parse(text),
+        }
+    }
+
     /// Add a line of title
     pub(super) fn set_testcase_title(&mut self, line: &str) {
         self.title = Some(line.to_string())
     }
 
+    /// Set the configuration of the document, that provides the command
+    /// template for shorthand lines, the custom kinds of expectations and the
+    /// macros that expectations refer to
+    pub(super) fn set_document_config(&mut self, config: DocumentConfig) -> Result<()> {
+        if !config.rules.is_empty() {
+            let shell = config
+                .shell
+                .as_ref()
+                .and_then(Shell::path)
+                .unwrap_or(&DEFAULT_SHELL);
+            self.expectation_maker = Arc::new(
+                self.expectation_maker
+                    .with_custom_rules(&config.rules, shell)
+                    .context("register custom rules of document config")?,
+            );
+        }
+        if !config.macros.is_empty() {
+            self.expectation_maker = Arc::new(
+                self.expectation_maker
+                    .with_macros(&config.macros)
+                    .context("register macros of document config")?,
+            );
+        }
+        self.document_config = config;
+        Ok(())
+    }
+
     /// Add a line of title
     pub(super) fn set_testcase_config(&mut self, config: TestCaseConfig) {
         self.config = Some(config)
@@ -136,8 +382,15 @@ impl LineParser {
     /// validity of the testcase, add it to the stack and flush the state
     /// so that the next testcase(s) can be processed.
     pub(super) fn end_testcase(&mut self, line_index: usize) -> Result<()> {
-        let (has_commands, has_expectations) =
-            (!self.command.is_empty(), !self.expectations.is_empty());
+        if let Some((open, start_index)) = self.open_expectation.take() {
+            self.close_expectation(&open, start_index)?;
+        }
+        let (has_commands, has_expectations) = (
+            !self.command.is_empty(),
+            !self.expectations.is_empty()
+                || !self.variants.is_empty()
+                || !self.assertions.is_empty(),
+        );
         if !has_commands {
             if has_expectations {
                 bail!(
@@ -147,11 +400,34 @@ impl LineParser {
             }
             return Ok(());
         }
+        let command = self.command.join("\n");
+        let (shell_expression, template_arguments) = if self.shorthand {
+            let rendered = self
+                .document_config
+                .render_command_template(&command)
+                .with_context(|| {
+                    format!("line {}", self.output_start_index.unwrap_or(line_index) + 1)
+                })?;
+            (rendered, Some(command))
+        } else {
+            (command, None)
+        };
+        let (expectations, variants) = if self.variants.is_empty() {
+            (self.expectations.clone(), vec![])
+        } else {
+            let mut variants = std::mem::take(&mut self.variants);
+            variants.push(self.expectations.clone());
+            (variants.remove(0), variants)
+        };
         self.testcases.push(TestCase {
             title: self.title.to_owned().unwrap_or_default(),
-            shell_expression: self.command.join("\n"),
-            exit_code: self.exit_code,
-            expectations: self.expectations.clone(),
+            shell_expression,
+            template_arguments,
+            interaction: self.interaction.clone(),
+            exit_code: self.exit_code.take(),
+            expectations,
+            variants,
+            assertions: self.assertions.clone(),
             line_number: self.output_start_index.unwrap_or(line_index) + 1,
             config: self.config.clone().unwrap_or_default(),
         });
@@ -161,13 +437,37 @@ impl LineParser {
 
     // whether shell expression(s) or expectation(s) are given
     pub(super) fn has_testcase_body(&self) -> bool {
-        !self.command.is_empty() || !self.expectations.is_empty()
+        !self.command.is_empty()
+            || !self.expectations.is_empty()
+            || !self.variants.is_empty()
+            || self.open_expectation.is_some()
+            || !self.assertions.is_empty()
+    }
+
+    // whether the next line can be an interaction step, which must follow the
+    // shell expression of a testcase that runs in a pseudo-terminal
+    fn accepts_interaction(&self) -> bool {
+        !self.command.is_empty()
+            && self.expectations.is_empty()
+            && self.variants.is_empty()
+            && self.assertions.is_empty()
+            && self.exit_code.is_none()
+            && self
+                .config
+                .as_ref()
+                .is_some_and(|config| config.tty.unwrap_or(false))
     }
 
     fn flush(&mut self) {
         self.title = None;
         self.command = vec![];
+        self.shorthand = false;
+        self.interaction = vec![];
         self.expectations = vec![];
+        self.variants = vec![];
+        self.open_expectation = None;
+        self.unordered = false;
+        self.assertions = vec![];
         self.exit_code = None;
         self.output_start_index = None;
         self.config = None;
@@ -175,8 +475,9 @@ impl LineParser {
 }
 
 /// Parse a line of output for whether it contains an exit code of
-/// the form `[<numeric code>]` and return the numeric value if it does
-pub(super) fn extract_exit_code(line: &str) -> Option<i32> {
+/// the form `[<exit code>]` and return the expected exit code if it does,
+/// see [`ExpectedExitCode`]
+pub(crate) fn extract_exit_code(line: &str) -> Option<anyhow::Result<ExpectedExitCode>> {
     // map. and then? map! and then?? map!!1!1!!!1 and ... then? ERRRR
     EXIT_CODE_EXPRESSION
         .captures(line)
@@ -186,7 +487,7 @@ pub(super) fn extract_exit_code(line: &str) -> Option<i32> {
                 .nth(1)
                 .and_then(|matching| matching.map(|matching| matching.as_str()))
         })
-        .and_then(|s| s.parse::<i32>().ok())
+        .map(|s| s.parse::<ExpectedExitCode>())
 }
 
 /// Lines starting with "#" are considered comments
@@ -196,12 +497,19 @@ pub(super) fn is_comment(line: &str) -> bool {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
     use std::sync::Arc;
 
     use super::LineParser;
     use super::extract_exit_code;
+    use crate::config::CustomRule;
+    use crate::config::DocumentConfig;
+    use crate::config::TestCaseConfig;
+    use crate::config::UnicodeNormalization;
     use crate::expectation::tests::expectation_maker;
     use crate::test_expectation;
+    use crate::testcase::ExpectedExitCode;
+    use crate::testcase::InteractionStep;
     use crate::testcase::TestCase;
 
     fn engine(allow_multiple_commands: bool) -> LineParser {
@@ -220,7 +528,7 @@ mod tests {
         assert_eq!(
             vec![TestCase {
                 title: "foo".to_string(),
-                exit_code: Some(5),
+                exit_code: Some(5.into()),
                 expectations: vec![test_expectation!("equal", "baz"),],
                 shell_expression: "bar".to_string(),
                 line_number: 2,
@@ -243,7 +551,7 @@ mod tests {
         assert_eq!(
             vec![TestCase {
                 title: "foo3".to_string(),
-                exit_code: Some(5),
+                exit_code: Some(5.into()),
                 expectations: vec![test_expectation!("equal", "baz"),],
                 shell_expression: "bar".to_string(),
                 line_number: 2,
@@ -267,7 +575,7 @@ mod tests {
         assert_eq!(
             vec![TestCase {
                 title: "foo".to_string(),
-                exit_code: Some(5),
+                exit_code: Some(5.into()),
                 expectations: vec![test_expectation!("equal", "baz"),],
                 shell_expression: "bar1\n\nbar2\nbar3".to_string(),
                 line_number: 2,
@@ -277,6 +585,461 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_powershell_prompt_starts_command() {
+        let mut markdown_engine = engine(false);
+        markdown_engine
+            .add_testcase_body("PS> Write-Output foo", 1)
+            .expect("add command");
+        markdown_engine
+            .add_testcase_body("> bar", 2)
+            .expect("add command");
+        markdown_engine
+            .add_testcase_body("PS> not a command", 3)
+            .expect("add expectation");
+        markdown_engine.end_testcase(4).expect("testcase ending");
+        assert_eq!(
+            vec![TestCase {
+                expectations: vec![test_expectation!("equal", "PS> not a command")],
+                shell_expression: "Write-Output foo\nbar".to_string(),
+                line_number: 2,
+                ..Default::default()
+            },],
+            markdown_engine.testcases,
+        );
+
+        let mut cram_engine = engine(true);
+        cram_engine
+            .add_testcase_body("$ foo", 1)
+            .expect("add command");
+        cram_engine
+            .add_testcase_body("PS> bar", 2)
+            .expect("add expectation");
+        cram_engine.end_testcase(3).expect("testcase ending");
+        assert_eq!(
+            vec![test_expectation!("equal", "PS> bar")],
+            cram_engine.testcases[0].expectations,
+            "only Markdown documents use the PowerShell prompt"
+        );
+    }
+
+    #[test]
+    fn test_interaction_requires_tty() {
+        for tty in [false, true] {
+            let mut engine = engine(false);
+            engine.set_testcase_config(TestCaseConfig {
+                tty: Some(tty),
+                ..Default::default()
+            });
+            engine.add_testcase_body("$ bar", 1).expect("add command");
+            engine.add_testcase_body("? prompt$", 2).expect("add line");
+            engine.add_testcase_body("! input", 3).expect("add line");
+            engine.add_testcase_body("!", 4).expect("add line");
+            engine.add_testcase_body("? output", 5).expect("add line");
+            engine.add_testcase_body("output", 6).expect("add line");
+            engine.add_testcase_body("? output", 7).expect("add line");
+            engine.end_testcase(8).expect("testcase ending");
+            let (interaction, expectations) = if tty {
+                (
+                    vec![
+                        InteractionStep::Expect("prompt$".into()),
+                        InteractionStep::Send("input".into()),
+                        InteractionStep::Send("".into()),
+                        InteractionStep::Expect("output".into()),
+                    ],
+                    vec![
+                        test_expectation!("equal", "output"),
+                        test_expectation!("equal", "? output"),
+                    ],
+                )
+            } else {
+                (
+                    vec![],
+                    vec![
+                        test_expectation!("equal", "? prompt$"),
+                        test_expectation!("equal", "! input"),
+                        test_expectation!("equal", "!"),
+                        test_expectation!("equal", "? output"),
+                        test_expectation!("equal", "output"),
+                        test_expectation!("equal", "? output"),
+                    ],
+                )
+            };
+            assert_eq!(interaction, engine.testcases[0].interaction, "tty: {tty}");
+            assert_eq!(expectations, engine.testcases[0].expectations, "tty: {tty}");
+        }
+    }
+
+    #[test]
+    fn test_expectation_unicode_normalization() {
+        let mut engine = engine(false);
+        engine.set_testcase_config(TestCaseConfig {
+            unicode_normalization: Some(UnicodeNormalization::Nfd),
+            ..Default::default()
+        });
+        engine.set_testcase_title("foo");
+        engine.add_testcase_body("$ bar", 1).expect("add command");
+        engine
+            .add_testcase_body("caf\u{e9}", 2)
+            .expect("add expectation");
+        engine.end_testcase(3).expect("testcase ending");
+        let expectation = &engine.testcases[0].expectations[0];
+        assert_eq!(
+            test_expectation!("equal", "cafe\u{301}"),
+            expectation.clone().with_original("cafe\u{301}"),
+            "expectation is normalized"
+        );
+        assert_eq!(
+            "caf\u{e9}",
+            expectation.original_string(),
+            "original is kept"
+        );
+    }
+
+    #[test]
+    fn test_expectation_continued_until_complete() {
+        let mut engine = engine(false);
+        engine.set_testcase_title("foo");
+        engine.add_testcase_body("$ bar", 1).expect("add command");
+        for (index, line) in ["{ (json)", "  \"foo\": [", "    1, 2", "  ]", "}"]
+            .iter()
+            .enumerate()
+        {
+            engine
+                .add_testcase_body(line, index + 2)
+                .expect("add expectation");
+        }
+        engine.add_testcase_body("[1]", 7).expect("add exit code");
+        engine.end_testcase(8).expect("testcase ending");
+        assert_eq!(
+            vec![test_expectation!(
+                "json",
+                "{\n  \"foo\": [\n    1, 2\n  ]\n}"
+            )],
+            engine.testcases[0].expectations,
+        );
+        assert_eq!(
+            "{ (json)\n  \"foo\": [\n    1, 2\n  ]\n}",
+            engine.testcases[0].expectations[0].original_string(),
+        );
+
+        let mut incomplete = self::engine(false);
+        incomplete
+            .add_testcase_body("$ bar", 1)
+            .expect("add command");
+        incomplete
+            .add_testcase_body("{ (json)", 2)
+            .expect("add expectation");
+        incomplete
+            .add_testcase_body("  \"foo\": 1", 3)
+            .expect("add expectation");
+        let err = incomplete
+            .end_testcase(4)
+            .expect_err("expectation is incomplete");
+        assert_eq!("parsing line 3", err.to_string());
+    }
+
+    #[test]
+    fn test_expectation_continued_up_to_exit_code() {
+        let mut engine = engine(false);
+        engine.set_testcase_title("foo");
+        engine.add_testcase_body("$ bar", 1).expect("add command");
+        for (index, line) in ["foo: (yaml)", "  - [1,", "     2]", "bar: baz", "[1]"]
+            .iter()
+            .enumerate()
+        {
+            engine
+                .add_testcase_body(line, index + 2)
+                .expect("add expectation");
+        }
+        engine.end_testcase(7).expect("testcase ending");
+        assert_eq!(
+            vec![test_expectation!(
+                "yaml",
+                "foo:\n  - [1,\n     2]\nbar: baz"
+            )],
+            engine.testcases[0].expectations,
+        );
+        assert_eq!(Some(1.into()), engine.testcases[0].exit_code);
+
+        let mut invalid = self::engine(false);
+        invalid.add_testcase_body("$ bar", 1).expect("add command");
+        invalid
+            .add_testcase_body("foo: [1, (yaml)", 2)
+            .expect("add expectation");
+        let err = invalid.end_testcase(3).expect_err("expectation is invalid");
+        assert_eq!("parsing line 3", err.to_string());
+    }
+
+    #[test]
+    fn test_unordered_expectations() {
+        let mut engine = engine(false);
+        engine.set_testcase_title("foo");
+        engine.add_testcase_body("$ bar", 1).expect("add command");
+        for (index, line) in ["first", "(unordered)", "a", "b (glob)", "(ordered)", "last"]
+            .iter()
+            .enumerate()
+        {
+            engine
+                .add_testcase_body(line, index + 2)
+                .expect("add expectation");
+        }
+        engine.end_testcase(8).expect("testcase ending");
+        assert_eq!(
+            vec![
+                test_expectation!("equal", "first"),
+                test_expectation!("equal", "a").with_unordered(true),
+                test_expectation!("glob", "b").with_unordered(true),
+                test_expectation!("equal", "last"),
+            ],
+            engine.testcases[0].expectations,
+        );
+
+        let mut quantified = self::engine(false);
+        quantified
+            .add_testcase_body("$ bar", 1)
+            .expect("add command");
+        quantified
+            .add_testcase_body("(unordered)", 2)
+            .expect("start group");
+        assert!(
+            quantified.add_testcase_body("a (?)", 3).is_err(),
+            "quantifiers are not supported"
+        );
+        assert!(
+            quantified.add_testcase_body("ERROR (not)", 4).is_err(),
+            "absence is not asserted in groups"
+        );
+    }
+
+    #[test]
+    fn test_variant_expectations() {
+        let mut engine = engine(false);
+        engine.set_testcase_title("foo");
+        engine.add_testcase_body("$ bar", 1).expect("add command");
+        for (index, line) in ["(unordered)", "a", "b", "(or)", "c", "(or)", "(or)", "[1]"]
+            .iter()
+            .enumerate()
+        {
+            engine
+                .add_testcase_body(line, index + 2)
+                .expect("add expectation");
+        }
+        engine.end_testcase(10).expect("testcase ending");
+        assert_eq!(
+            vec![
+                test_expectation!("equal", "a").with_unordered(true),
+                test_expectation!("equal", "b").with_unordered(true),
+            ],
+            engine.testcases[0].expectations,
+        );
+        assert_eq!(
+            vec![vec![test_expectation!("equal", "c")], vec![], vec![]],
+            engine.testcases[0].variants,
+        );
+
+        let mut late = self::engine(false);
+        late.add_testcase_body("$ bar", 1).expect("add command");
+        late.add_testcase_body("[1]", 2).expect("add exit code");
+        assert!(
+            late.add_testcase_body("(or)", 3).is_err(),
+            "variants precede the exit code"
+        );
+    }
+
+    #[test]
+    fn test_line_count_expectations() {
+        let mut engine = engine(false);
+        engine.add_testcase_body("$ bar", 1).expect("add command");
+        engine
+            .add_testcase_body("worker * (glob{2,5})", 2)
+            .expect("add expectation");
+        engine.end_testcase(3).expect("testcase ending");
+        assert_eq!(
+            "worker * (glob{2,5})",
+            engine.testcases[0].expectations[0].to_string()
+        );
+
+        let mut invalid = self::engine(false);
+        invalid.add_testcase_body("$ bar", 1).expect("add command");
+        assert!(
+            invalid.add_testcase_body("foo (glob{5,2})", 2).is_err(),
+            "lower bound above upper bound"
+        );
+        assert!(
+            invalid.add_testcase_body("1 (json{2})", 3).is_err(),
+            "remaining output has no line count"
+        );
+    }
+
+    #[test]
+    fn test_absent_expectation_without_quantifier() {
+        let mut engine = engine(false);
+        engine.add_testcase_body("$ bar", 1).expect("add command");
+        engine
+            .add_testcase_body("ERROR (not)", 2)
+            .expect("add expectation");
+        assert!(
+            engine.add_testcase_body("WARN (not*)", 3).is_err(),
+            "quantifiers are not supported"
+        );
+    }
+
+    #[test]
+    fn test_invalid_expect_pattern_fails() {
+        let mut engine = engine(false);
+        engine.set_testcase_config(TestCaseConfig {
+            tty: Some(true),
+            ..Default::default()
+        });
+        engine.add_testcase_body("$ bar", 1).expect("add command");
+        assert!(engine.add_testcase_body("? (unclosed", 2).is_err());
+    }
+
+    #[test]
+    fn test_shorthand_renders_command_template() {
+        let mut engine = engine(true);
+        engine
+            .set_document_config(DocumentConfig {
+                command_template: Some("cli --config {fixture}/cfg.yaml {args}".into()),
+                ..Default::default()
+            })
+            .expect("set document config");
+        engine
+            .add_testcase_body("% foo \\", 1)
+            .expect("add shorthand");
+        engine.add_testcase_body(">   bar", 2).expect("add command");
+        engine.add_testcase_body("baz", 3).expect("add expectation");
+        engine.add_testcase_body("$ qux", 4).expect("add command");
+        engine.end_testcase(5).expect("testcase ending");
+        assert_eq!(
+            vec![
+                TestCase {
+                    shell_expression: "cli --config ${TESTDIR}/cfg.yaml foo \\\n  bar".into(),
+                    template_arguments: Some("foo \\\n  bar".into()),
+                    expectations: vec![test_expectation!("equal", "baz")],
+                    line_number: 2,
+                    ..Default::default()
+                },
+                TestCase {
+                    shell_expression: "qux".into(),
+                    line_number: 5,
+                    ..Default::default()
+                },
+            ],
+            engine.testcases,
+        )
+    }
+
+    #[test]
+    fn test_shorthand_requires_command_template() {
+        let mut plain = engine(false);
+        let result = plain
+            .add_testcase_body("% foo", 1)
+            .and_then(|_| plain.end_testcase(2));
+        assert!(
+            result.is_err(),
+            "shorthand is an expectation without command"
+        );
+
+        let mut templated = engine(false);
+        templated
+            .set_document_config(DocumentConfig {
+                command_template: Some("cli".into()),
+                ..Default::default()
+            })
+            .expect("set document config");
+        templated
+            .add_testcase_body("% foo", 1)
+            .expect("add shorthand");
+        assert!(
+            templated.end_testcase(2).is_err(),
+            "template without arguments"
+        );
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let rules = |name: &str| DocumentConfig {
+            rules: BTreeMap::from([(
+                name.to_string(),
+                CustomRule {
+                    command: "./check.sh".into(),
+                },
+            )]),
+            ..Default::default()
+        };
+
+        let mut plain = engine(false);
+        plain.add_testcase_body("$ foo", 1).expect("add command");
+        plain
+            .add_testcase_body("bar (mycheck)", 2)
+            .expect("add expectation");
+        plain.end_testcase(3).expect("testcase ending");
+        assert_eq!(
+            vec![test_expectation!("equal", "bar (mycheck)")],
+            plain.testcases[0].expectations,
+            "unknown kind is part of the expression"
+        );
+
+        let mut engine = engine(false);
+        engine
+            .set_document_config(rules("mycheck"))
+            .expect("set document config");
+        engine.add_testcase_body("$ foo", 1).expect("add command");
+        engine
+            .add_testcase_body("bar (mycheck+)", 2)
+            .expect("add expectation");
+        engine.end_testcase(3).expect("testcase ending");
+        let expectation = &engine.testcases[0].expectations[0];
+        assert_eq!(
+            ("mycheck".to_string(), b"bar".to_vec()),
+            expectation.rule.unmake()
+        );
+        assert!(expectation.multiline);
+
+        engine
+            .set_document_config(rules("regex"))
+            .expect_err("built-in kind");
+    }
+
+    #[test]
+    fn test_assertions() {
+        let mut engine = engine(false);
+        engine.add_testcase_body("$ bar", 1).expect("add command");
+        engine.add_testcase_body("baz", 2).expect("add expectation");
+        engine
+            .add_testcase_body("@assert file_exists out.txt", 3)
+            .expect("add assertion");
+        engine
+            .add_testcase_body("@assert exit_code in 0..2", 4)
+            .expect("add assertion");
+        engine.end_testcase(5).expect("testcase ending");
+        assert_eq!(1, engine.testcases.len());
+        assert_eq!(
+            vec![test_expectation!("equal", "baz")],
+            engine.testcases[0].expectations
+        );
+        assert_eq!(
+            vec![
+                "@assert file_exists out.txt".to_string(),
+                "@assert exit_code in 0..2".to_string(),
+            ],
+            engine.testcases[0]
+                .assertions
+                .iter()
+                .map(|assertion| assertion.to_string())
+                .collect::<Vec<_>>()
+        );
+
+        engine.add_testcase_body("$ bar", 6).expect("add command");
+        let err = engine
+            .add_testcase_body("@assert unknown foo", 7)
+            .err()
+            .expect("unknown assertion");
+        assert_eq!("parsing line 8", err.to_string());
+    }
+
     #[test]
     fn test_expectations_are_stacked() {
         let mut engine = engine(false);
@@ -296,7 +1059,7 @@ mod tests {
         assert_eq!(
             vec![TestCase {
                 title: "foo".to_string(),
-                exit_code: Some(5),
+                exit_code: Some(5.into()),
                 expectations: vec![
                     test_expectation!("equal", "baz1"),
                     test_expectation!("equal", "baz2"),
@@ -411,7 +1174,7 @@ mod tests {
             vec![
                 TestCase {
                     title: "foo1".to_string(),
-                    exit_code: Some(1),
+                    exit_code: Some(1.into()),
                     expectations: vec![test_expectation!("equal", "baz1"),],
                     shell_expression: "bar1".to_string(),
                     line_number: 2,
@@ -419,7 +1182,7 @@ mod tests {
                 },
                 TestCase {
                     title: "foo2".to_string(),
-                    exit_code: Some(2),
+                    exit_code: Some(2.into()),
                     expectations: vec![test_expectation!("equal", "baz2"),],
                     shell_expression: "bar2".to_string(),
                     line_number: 5,
@@ -427,7 +1190,7 @@ mod tests {
                 },
                 TestCase {
                     title: "foo3".to_string(),
-                    exit_code: Some(3),
+                    exit_code: Some(3.into()),
                     expectations: vec![test_expectation!("equal", "baz3"),],
                     shell_expression: "bar3".to_string(),
                     line_number: 8,
@@ -451,7 +1214,7 @@ mod tests {
             assert_eq!(
                 vec![TestCase {
                     title: "foo1".to_string(),
-                    exit_code: if provided { Some(0) } else { None },
+                    exit_code: if provided { Some(0.into()) } else { None },
                     expectations: vec![],
                     shell_expression: "bar".to_string(),
                     line_number: 2,
@@ -465,17 +1228,43 @@ mod tests {
 
     #[test]
     fn test_extract_exit_code() {
-        let tests: Vec<(&str, Option<i32>)> = vec![
+        let tests: Vec<(&str, Option<ExpectedExitCode>)> = vec![
             ("foo", None),
             ("[]", None),
-            ("[0]", Some(0)),
-            ("[1]", Some(1)),
-            ("[99]", Some(99)),
+            ("[0]", Some(ExpectedExitCode::Code(0))),
+            ("[1]", Some(ExpectedExitCode::Code(1))),
+            ("[99]", Some(ExpectedExitCode::Code(99))),
             ("[a]", None),
+            ("[1-127]", Some(ExpectedExitCode::Range(1, 127))),
+            ("[!0]", Some(ExpectedExitCode::Not(0))),
+            (
+                "[signal:TERM]",
+                Some(ExpectedExitCode::Signal("TERM".into())),
+            ),
+            (
+                "[signal:sigkill]",
+                Some(ExpectedExitCode::Signal("KILL".into())),
+            ),
+            ("[signal:9]", Some(ExpectedExitCode::Signal("9".into()))),
+            ("[exit:137]", Some(ExpectedExitCode::Exit(137))),
+            (
+                "[signal:SIGSEGV]",
+                Some(ExpectedExitCode::Signal("SEGV".into())),
+            ),
+            ("[1-]", None),
+            ("[!]", None),
+            ("[exit:]", None),
         ];
         tests.iter().for_each(|(line, expect)| {
-            let result = extract_exit_code(line);
+            let result = extract_exit_code(line).map(|result| result.expect("valid exit code"));
             assert_eq!(*expect, result, "parsed '{}'", line);
         });
+        for line in ["[5-1]", "[signal:NOPE]", "[signal:200]"] {
+            assert!(
+                matches!(extract_exit_code(line), Some(Err(_))),
+                "parsed '{}' as invalid",
+                line
+            );
+        }
     }
 }
@@ -5,6 +5,9 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
 use std::str::Lines;
 use std::sync::Arc;
 
@@ -15,6 +18,7 @@ use tracing::debug;
 
 use super::line_parser::is_comment;
 use super::parser::Parser;
+use super::parser::front_matter;
 use crate::config::DocumentConfig;
 use crate::config::TestCaseConfig;
 use crate::expectation::ExpectationMaker;
@@ -26,6 +30,8 @@ lazy_static! {
         Regex::new(r"^\p{L}+").expect("paragraph start expression must compile");
     static ref HEADER_LINE: Regex =
         Regex::new(r"^(#+\s+)(.+)$").expect("header start expression must compile");
+    static ref TYPOGRAPHIC_QUOTE: Regex = Regex::new(r"(?:^|[:\[{,])\s*[‘“]|[’”]\s*(?:$|[,\]}])")
+        .expect("typographic quote expression must compile");
 }
 
 pub const DEFAULT_MARKDOWN_LANGUAGES: &[&str] = &["scrut"];
@@ -36,6 +42,23 @@ pub enum MarkdownParserError {
         "Code block starting at line {line} is missing language specifier. Use ```scrut to make this block a Scrut test, or any other language to make Scrut skip this block."
     )]
     MissingLanguageSpecifier { line: usize },
+
+    #[error(
+        "Code block starting at line {line} is indented with tabs, so it is not recognized as a Scrut test. Remove the indentation:\n{fixed}"
+    )]
+    TabIndentedCodeBlock { line: usize, fixed: String },
+
+    #[error(
+        "Configuration in line {line} encloses values in typographic quotes, which YAML does not recognize as quotes. Use straight quotes instead:\n{fixed}"
+    )]
+    TypographicQuotes { line: usize, fixed: String },
+
+    #[error("Command in line {line} {reason}, so it is not recognized as a command. Use:\n{fixed}")]
+    MalformedCommand {
+        line: usize,
+        reason: &'static str,
+        fixed: String,
+    },
 }
 
 /// A parser for Cram `.t` files, which reads [`crate::testcase::TestCase`]s
@@ -53,6 +76,9 @@ pub struct MarkdownParser {
     expectation_maker: Arc<ExpectationMaker>,
     languages: Vec<String>,
     base_testcase_config: TestCaseConfig,
+    base_document_config: DocumentConfig,
+    document_directory: PathBuf,
+    template_vars: BTreeMap<String, serde_yaml::Value>,
 }
 
 impl MarkdownParser {
@@ -66,7 +92,50 @@ impl MarkdownParser {
             languages: languages.iter().map(|lang| lang.to_string()).collect(),
             base_testcase_config: base_testcase_config
                 .unwrap_or_else(TestCaseConfig::default_markdown),
+            base_document_config: DocumentConfig::default_markdown(),
+            document_directory: PathBuf::new(),
+            template_vars: BTreeMap::new(),
+        }
+    }
+
+    /// Builder setter for the document configuration that the front-matter of
+    /// parsed documents is applied on top of
+    pub fn with_base_document_config(mut self, config: DocumentConfig) -> Self {
+        self.base_document_config = config;
+        self
+    }
+
+    /// Builder setter for the directory of the parsed document, that relative
+    /// paths in `extends` and `env_file` of the front-matter are resolved against
+    pub fn with_document_directory(mut self, directory: &Path) -> Self {
+        self.document_directory = directory.into();
+        self
+    }
+
+    /// Builder setter for variables of document templates, that take
+    /// precedence over the `vars` of the front-matter
+    pub fn with_template_vars(mut self, vars: BTreeMap<String, serde_yaml::Value>) -> Self {
+        self.template_vars = vars;
+        self
+    }
+
+    /// Returns the document rendered with the `template` that is configured
+    /// in its front-matter (or the base document configuration), if any.
+    /// Invalid front-matter is reported when the document is parsed.
+    fn render_template(&self, text: &str) -> Result<Option<String>> {
+        let mut config = self.base_document_config.clone();
+        let parsed_config = front_matter(text)
+            .and_then(|front_matter| serde_yaml::from_str::<DocumentConfig>(&front_matter).ok())
+            .and_then(|parsed| parsed.with_extends_resolved(&self.document_directory).ok());
+        if let Some(parsed_config) = parsed_config {
+            config = config.with_overrides_from(&parsed_config);
         }
+        let Some(template) = config.template else {
+            return Ok(None);
+        };
+        let mut vars = config.vars;
+        vars.extend(self.template_vars.clone());
+        template.render(text, &vars).map(Some)
     }
 }
 
@@ -78,25 +147,38 @@ impl Parser for MarkdownParser {
             &self.languages.join("` or `")
         );
 
+        let rendered = self.render_template(text)?;
+        let text = rendered.as_deref().unwrap_or(text);
         let languages: &[&str] = &self.languages.iter().map(|s| s as &str).collect::<Vec<_>>();
         let iterator = MarkdownIterator::new(languages, text.lines());
         let mut line_parser = LineParser::new(self.expectation_maker.clone(), false);
         let mut title_paragraph = vec![];
-        let mut config = DocumentConfig::default_markdown();
+        let mut config = self.base_document_config.clone();
+        line_parser.set_document_config(config.clone())?;
 
         for token in iterator {
             match token {
                 MarkdownToken::DocumentConfig(config_lines) => {
-                    let parsed_config = serde_yaml::from_str(&config_lines.join_newline())
-                        .with_context(|| {
+                    for (index, line) in &config_lines {
+                        check_typographic_quotes(*index, line)?;
+                    }
+                    let parsed_config: DocumentConfig =
+                        serde_yaml::from_str(&config_lines.join_newline()).with_context(|| {
                             format!(
                                 "parse document config from front-matter:\n{:?}",
                                 config_lines.join_newline()
                             )
                         })?;
+                    let parsed_config = parsed_config
+                        .with_extends_resolved(&self.document_directory)
+                        .context("resolve extends of document config")?
+                        .with_env_file_loaded(&self.document_directory)
+                        .context("load env file of document config")?;
                     config = config.with_overrides_from(&parsed_config);
+                    line_parser.set_document_config(config.clone())?;
                 }
-                MarkdownToken::Line(_, line) => {
+                MarkdownToken::Line(index, line) => {
+                    check_tab_indented_code_block(index, &line, languages)?;
                     if let Some((_, title)) = extract_title(&line) {
                         title_paragraph.push(title);
                         line_parser.set_testcase_title(&title_paragraph.join("\n"));
@@ -121,6 +203,10 @@ impl Parser for MarkdownParser {
                     comment_lines: _,
                     code_lines,
                 } => {
+                    for (index, line) in &config_lines {
+                        check_typographic_quotes(*index, line)?;
+                    }
+                    check_command_lines(&code_lines)?;
                     let parsed_config = if config_lines.is_empty() {
                         TestCaseConfig::empty()
                     } else {
@@ -129,7 +215,7 @@ impl Parser for MarkdownParser {
                     };
                     line_parser.set_testcase_config(
                         parsed_config
-                            .with_defaults_from(&config.defaults)
+                            .with_defaults_from(&config.testcase_defaults())
                             .with_defaults_from(&self.base_testcase_config),
                     );
                     for (index, line) in &code_lines {
@@ -154,7 +240,7 @@ impl Parser for MarkdownParser {
 #[derive(Debug)]
 pub(crate) enum MarkdownToken {
     /// An arbitrary line; basically any line of markdown we do not care about
-    Line(#[allow(dead_code)] usize, String),
+    Line(usize, String),
 
     /// Raw configuration that is prepending the document
     DocumentConfig(Vec<(usize, String)>),
@@ -335,6 +421,71 @@ pub(crate) fn extract_title(line: &str) -> Option<(String, String)> {
     }
 }
 
+/// Fails if the line is the start of a test code block that is not recognized,
+/// because it is indented with tabs
+fn check_tab_indented_code_block(index: usize, line: &str, languages: &[&str]) -> Result<()> {
+    let code = line.trim_start();
+    if !line[..line.len() - code.len()].contains('\t') {
+        return Ok(());
+    }
+    match extract_code_block_start(code) {
+        Some((_, language, _)) if languages.contains(&language) => {
+            anyhow::bail!(MarkdownParserError::TabIndentedCodeBlock {
+                line: index + 1,
+                fixed: code.into(),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Fails if the configuration line contains values that are enclosed in
+/// typographic quotes (e.g. `“foo”`), as inserted by many text editors
+fn check_typographic_quotes(index: usize, line: &str) -> Result<()> {
+    if !TYPOGRAPHIC_QUOTE.is_match(line) {
+        return Ok(());
+    }
+    anyhow::bail!(MarkdownParserError::TypographicQuotes {
+        line: index + 1,
+        fixed: line.replace(['‘', '’'], "'").replace(['“', '”'], "\""),
+    })
+}
+
+/// Fails if the command in the first line of the code of a test is not
+/// recognized because it is indented, or if it or a line that continues it is
+/// not recognized because of an exotic space (e.g. non-breaking space) after
+/// the `$` or `>` prefix
+fn check_command_lines(code_lines: &[(usize, String)]) -> Result<()> {
+    for (position, (index, line)) in code_lines.iter().enumerate() {
+        let prefix = if position == 0 { '$' } else { '>' };
+        if line.starts_with(&format!("{prefix} ")) || (prefix == '>' && line == ">") {
+            continue;
+        }
+        let code = line.trim_start();
+        let mut chars = code.chars();
+        let (reason, fixed) = match (chars.next(), chars.next()) {
+            (Some('$'), Some(' ')) if position == 0 && line.len() > code.len() => {
+                ("is indented", code.to_string())
+            }
+            (Some(first), Some(space))
+                if first == prefix && space.is_whitespace() && !space.is_ascii() =>
+            {
+                (
+                    "uses an exotic space (e.g. non-breaking space) after the prefix",
+                    format!("{prefix} {}", chars.as_str()),
+                )
+            }
+            _ => return Ok(()),
+        };
+        anyhow::bail!(MarkdownParserError::MalformedCommand {
+            line: index + 1,
+            reason,
+            fixed,
+        })
+    }
+    Ok(())
+}
+
 /// Parses a markdown code block starting line of three (or more) backticks
 /// that may be followed by a language.
 ///
@@ -354,7 +505,7 @@ pub(crate) fn extract_code_block_start(line: &str) -> Option<(&str, &str, &str)>
     }
 
     let mut language_start = None;
-    for (index, ch) in line.chars().enumerate() {
+    for (index, ch) in line.char_indices() {
         if let Some(language_start) = language_start {
             if ch == '{' {
                 return Some((
@@ -389,7 +540,7 @@ impl NumberedLines for Vec<(usize, String)> {
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::collections::BTreeMap;
     use std::sync::Arc;
     use std::time::Duration;
 
@@ -430,9 +581,13 @@ hello
         assert_eq!(
             TestCase {
                 shell_expression: "echo hello".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("equal", "hello", false, false)],
+                variants: vec![],
                 title: "This is a title".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 5,
                 config: TestCaseConfig::default_markdown(),
             },
@@ -460,7 +615,7 @@ hello
         assert_eq!(
             config,
             DocumentConfig {
-                shell: Some(PathBuf::from("some-shell")),
+                shell: Some("some-shell".into()),
                 total_timeout: Some(Duration::from_secs(3 * 60 + 3)),
                 ..DocumentConfig::empty()
             },
@@ -470,9 +625,13 @@ hello
         assert_eq!(
             TestCase {
                 shell_expression: "echo hello".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("equal", "hello", false, false)],
+                variants: vec![],
                 title: "This is a title".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 10,
                 config: TestCaseConfig::default_markdown(),
             },
@@ -480,6 +639,42 @@ hello
         );
     }
 
+    #[test]
+    fn test_document_template() {
+        let markdown_test = r#"---
+template: jinja
+vars:
+  names: [foo, bar]
+---
+
+{% for name in names %}
+# Say {{ name }}
+
+```scrut
+$ echo {{ name }}
+{{ name }}
+```
+
+{% endfor %}
+"#;
+        let parser = parser().with_template_vars(BTreeMap::from([(
+            "names".to_string(),
+            serde_yaml::from_str("[baz]").expect("parse vars"),
+        )]));
+        let (_, testcases) = parser.parse(markdown_test).expect("must parse");
+        assert_eq!(
+            vec![("Say baz", "echo baz", 10)],
+            testcases
+                .iter()
+                .map(|testcase| (
+                    testcase.title.as_str(),
+                    testcase.shell_expression.as_str(),
+                    testcase.line_number
+                ))
+                .collect::<Vec<_>>(),
+        );
+    }
+
     #[test]
     fn test_testcase_config() {
         let cram_test = r#"
@@ -501,15 +696,22 @@ hello
         assert_eq!(
             TestCase {
                 shell_expression: "echo hello".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("equal", "hello", false, false)],
+                variants: vec![],
                 title: "This is a title".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 5,
                 config: TestCaseConfig::default_markdown().with_overrides_from(&TestCaseConfig {
                     timeout: Some(Duration::from_secs(3 * 60 + 3)),
                     wait: Some(TestCaseWait {
                         timeout: Duration::from_secs(4 * 60 + 4),
                         path: None,
+                        port: None,
+                        http: None,
+                        status: None,
                     }),
                     ..TestCaseConfig::default_markdown()
                 })
@@ -538,9 +740,13 @@ hello
         assert_eq!(
             TestCase {
                 shell_expression: "echo hello".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("equal", "hello", false, false)],
+                variants: vec![],
                 title: "This is a title".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 9,
                 config: TestCaseConfig::default_markdown(),
             },
@@ -568,10 +774,14 @@ hello
         assert_eq!(
             TestCase {
                 shell_expression: "echo hello".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("equal", "hello", false, false)],
+                variants: vec![],
                 title: "This is a title\nThis is still part of it\nAnd another part of the title"
                     .to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 9,
                 config: TestCaseConfig::default_markdown(),
             },
@@ -597,9 +807,13 @@ hello
         assert_eq!(
             TestCase {
                 shell_expression: "echo hello".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("equal", "hello", false, false)],
+                variants: vec![],
                 title: "This is a title".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 7,
                 config: TestCaseConfig::default_markdown(),
             },
@@ -625,9 +839,13 @@ hello
         assert_eq!(
             TestCase {
                 shell_expression: "echo hello".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("equal", "hello", false, false)],
+                variants: vec![],
                 title: "This is a title".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 7,
                 config: TestCaseConfig::default_markdown(),
             },
@@ -672,9 +890,13 @@ world
         assert_eq!(
             TestCase {
                 shell_expression: "echo hello".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("equal", "hello", false, false)],
+                variants: vec![],
                 title: "This is a title".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 12,
                 config: TestCaseConfig::default_markdown(),
             },
@@ -683,9 +905,13 @@ world
         assert_eq!(
             TestCase {
                 shell_expression: "echo world".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("equal", "world", false, false)],
+                variants: vec![],
                 title: "This is another title".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 26,
                 config: TestCaseConfig::default_markdown(),
             },
@@ -714,13 +940,17 @@ i am output 3
         assert_eq!(
             TestCase {
                 shell_expression: "i am command 1\ni am command 2".into(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![
                     test_expectation!("equal", "i am output 1", false, false),
                     test_expectation!("equal", "> i am output 2", false, false),
                     test_expectation!("equal", "i am output 3", false, false),
                 ],
+                variants: vec![],
                 title: "This is a title".to_string(),
                 exit_code: None,
+                assertions: vec![],
                 line_number: 7,
                 config: TestCaseConfig::default_markdown(),
             },
@@ -761,19 +991,25 @@ Hello World
             vec![
                 TestCase {
                     shell_expression: "echo hello".to_string(),
+                    template_arguments: None,
+                    interaction: vec![],
                     expectations: vec![
                         test_expectation!("equal", "```scrut"),
                         test_expectation!("equal", "inner"),
                         test_expectation!("equal", "```"),
                         test_expectation!("equal", "text"),
                     ],
+                    variants: vec![],
                     title: "This is a title".to_string(),
                     exit_code: None,
+                    assertions: vec![],
                     line_number: 5,
                     config: TestCaseConfig::default_markdown(),
                 },
                 TestCase {
                     shell_expression: "cat test.md".to_string(),
+                    template_arguments: None,
+                    interaction: vec![],
                     expectations: vec![
                         test_expectation!("equal", "# Command executes successfully"),
                         test_expectation!("equal", ""),
@@ -782,8 +1018,10 @@ Hello World
                         test_expectation!("equal", "Hello World"),
                         test_expectation!("equal", "```"),
                     ],
+                    variants: vec![],
                     title: "And another title".to_string(),
                     exit_code: None,
+                    assertions: vec![],
                     line_number: 15,
                     config: TestCaseConfig::default_markdown(),
                 },
@@ -809,12 +1047,55 @@ world
         assert_eq!(
             vec![TestCase {
                 shell_expression: "echo -e '$ hello\\nworld'".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![
                     test_expectation!("equal", "$ hello"),
                     test_expectation!("equal", "world"),
                 ],
+                variants: vec![],
                 title: "This is a title".to_string(),
                 exit_code: None,
+                assertions: vec![],
+                line_number: 5,
+                config: TestCaseConfig::default_markdown(),
+            },],
+            testcases
+        );
+    }
+
+    #[test]
+    fn test_heredoc_body_is_part_of_command() {
+        let markdown_test = r#"
+This is a title
+
+```scrut
+$ cat <<'EOF' && \
+> cat <<-EOF2
+$ not a command
+> not a continuation
+
+[1]
+foo (regex)
+EOF
+	tab indented
+	EOF2
+$ not a command
+[1]
+```
+"#;
+        let parser = parser();
+        let (_, testcases) = parser.parse(markdown_test).expect("must parse");
+        assert_eq!(
+            vec![TestCase {
+                shell_expression: "cat <<'EOF' && \\\ncat <<-EOF2\n$ not a command\n> not a continuation\n\n[1]\nfoo (regex)\nEOF\n\ttab indented\n\tEOF2".to_string(),
+                template_arguments: None,
+                interaction: vec![],
+                expectations: vec![test_expectation!("equal", "$ not a command")],
+                variants: vec![],
+                title: "This is a title".to_string(),
+                exit_code: Some(1.into()),
+                assertions: vec![],
                 line_number: 5,
                 config: TestCaseConfig::default_markdown(),
             },],
@@ -846,4 +1127,59 @@ world
     fn test_extract_code_block_start_without_language() {
         assert_eq!(Some(("```", "", "")), extract_code_block_start("```"));
     }
+
+    #[test]
+    fn test_authoring_mistakes_are_diagnosed() {
+        let tests = [
+            (
+                "tab indented code block",
+                "A title\n\n\t```scrut\n\t$ echo hello\n\thello\n\t```\n",
+                "Code block starting at line 3 is indented with tabs",
+                "```scrut",
+            ),
+            (
+                "typographic quotes in testcase config",
+                "```scrut {output_stream: ‘stderr’}\n$ echo hello\n```\n",
+                "Configuration in line 1 encloses values in typographic quotes",
+                "output_stream: 'stderr'",
+            ),
+            (
+                "typographic quotes in front-matter",
+                "---\nenvironment:\n  FOO: “bar”\n---\n",
+                "Configuration in line 3 encloses values in typographic quotes",
+                "  FOO: \"bar\"",
+            ),
+            (
+                "indented command",
+                "```scrut\n  $ echo hello\nhello\n```\n",
+                "Command in line 2 is indented",
+                "$ echo hello",
+            ),
+            (
+                "non-breaking space after command prefix",
+                "```scrut\n$\u{a0}echo hello\nhello\n```\n",
+                "Command in line 2 uses an exotic space",
+                "$ echo hello",
+            ),
+            (
+                "non-breaking space after continuation prefix",
+                "```scrut\n$ echo hello &&\n>\u{a0}echo world\nhello\n```\n",
+                "Command in line 3 uses an exotic space",
+                "> echo world",
+            ),
+        ];
+        for (name, document, message, fixed) in tests {
+            let error = parser()
+                .parse(document)
+                .expect_err(&format!("{name}: must fail"))
+                .to_string();
+            assert!(error.starts_with(message), "{name}: {error}");
+            assert!(error.ends_with(&format!("\n{fixed}")), "{name}: {error}");
+        }
+
+        let (_, testcases) = parser()
+            .parse("```scrut {environment: {FOO: \"say “hi”\"}}\n$ echo hello\n  > quoted\n```\n")
+            .expect("typographic quotes within values and indented output are fine");
+        assert_eq!(1, testcases.len());
+    }
 }
@@ -15,6 +15,8 @@
 //! - Cram file syntax: [`cram::CramParser`]
 
 pub mod cram;
-pub(super) mod line_parser;
+pub(crate) mod heredoc;
+pub(crate) mod line_parser;
 pub mod markdown;
 pub mod parser;
+pub mod template;
@@ -9,6 +9,8 @@ use std::fmt::Display;
 
 use anyhow::Result;
 use clap::ValueEnum;
+use serde::Deserialize;
+use serde::Serialize;
 
 use crate::config::DocumentConfig;
 use crate::testcase::TestCase;
@@ -19,8 +21,24 @@ pub trait Parser {
     fn parse(&self, tests: &str) -> Result<(DocumentConfig, Vec<TestCase>)>;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+/// Returns the YAML of the front-matter of the document, if it has any
+pub(crate) fn front_matter(content: &str) -> Option<String> {
+    let mut lines = content.lines();
+    if lines.next()? != "---" {
+        return None;
+    }
+    Some(
+        lines
+            .take_while(|line| *line != "---")
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ParserType {
+    #[serde(alias = "md")]
     Markdown,
     Cram,
 }
@@ -32,6 +50,35 @@ impl ParserType {
             Self::Markdown => "md",
         }
     }
+
+    /// Returns the format that is explicitly set with `format` in the
+    /// front-matter of the document, if any
+    pub fn from_front_matter(content: &str) -> Option<Self> {
+        serde_yaml::from_str::<DocumentConfig>(&front_matter(content)?)
+            .ok()?
+            .format
+    }
+
+    /// Guesses the format of a document from its content: Markdown, if it
+    /// contains a code block in any of the given languages, or Cram, if it
+    /// contains an indented shell expression
+    pub fn detect(content: &str, markdown_languages: &[&str]) -> Option<Self> {
+        let mut is_cram = false;
+        for line in content.lines() {
+            if let Some(info) = line.trim_start().strip_prefix("```") {
+                let language = info
+                    .split(|c: char| c.is_whitespace() || c == '{')
+                    .next()
+                    .unwrap_or_default();
+                if markdown_languages.contains(&language) {
+                    return Some(Self::Markdown);
+                }
+            } else if line.starts_with("  $ ") {
+                is_cram = true;
+            }
+        }
+        is_cram.then_some(Self::Cram)
+    }
 }
 
 impl Display for ParserType {
@@ -58,3 +105,57 @@ impl TryFrom<String> for ParserType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ParserType;
+
+    #[test]
+    fn test_from_front_matter() {
+        let tests = vec![
+            ("---\nformat: cram\n---\n", Some(ParserType::Cram)),
+            (
+                "---\nformat: md\nshell: bash\n---\n",
+                Some(ParserType::Markdown),
+            ),
+            ("---\nshell: bash\n---\n", None),
+            ("format: cram\n", None),
+            ("", None),
+        ];
+        for (content, expected) in tests {
+            assert_eq!(
+                ParserType::from_front_matter(content),
+                expected,
+                "{content:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_detect() {
+        let tests = vec![
+            (
+                "# Title\n\n```scrut\n$ echo\n```\n",
+                Some(ParserType::Markdown),
+            ),
+            (
+                "```scrut {timeout: 1s}\n$ echo\n```\n",
+                Some(ParserType::Markdown),
+            ),
+            ("```bash\n$ echo\n```\n", None),
+            ("A test\n\n  $ echo foo\n  foo\n", Some(ParserType::Cram)),
+            (
+                "  $ echo\n```scrut\n$ echo\n```\n",
+                Some(ParserType::Markdown),
+            ),
+            ("just some text\n", None),
+        ];
+        for (content, expected) in tests {
+            assert_eq!(
+                ParserType::detect(content, &["scrut"]),
+                expected,
+                "{content:?}"
+            );
+        }
+    }
+}
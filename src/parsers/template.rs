@@ -0,0 +1,87 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use anyhow::Result;
+use minijinja::Environment;
+use minijinja::UndefinedBehavior;
+use minijinja::Value;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Upper bound of the instructions that rendering a template may execute,
+/// which stops runaway loops long before they exhaust memory
+const TEMPLATE_FUEL: u64 = 1_000_000;
+
+/// Template engines that a document can be rendered with before it is parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentTemplate {
+    /// Render the document with a restricted MiniJinja environment, that
+    /// supports expressions, conditions and loops, but no includes or imports
+    Jinja,
+}
+
+impl DocumentTemplate {
+    /// Returns the rendered document, with the provided variables available
+    pub fn render(&self, text: &str, vars: &BTreeMap<String, serde_yaml::Value>) -> Result<String> {
+        match self {
+            Self::Jinja => render_jinja(text, vars),
+        }
+    }
+}
+
+fn render_jinja(text: &str, vars: &BTreeMap<String, serde_yaml::Value>) -> Result<String> {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.set_fuel(Some(TEMPLATE_FUEL));
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env.set_keep_trailing_newline(true);
+    env.render_str(text, Value::from_serialize(vars))
+        .map_err(|err| anyhow::anyhow!("{:#}", err))
+        .context("render document template")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::DocumentTemplate;
+
+    fn vars(yaml: &str) -> BTreeMap<String, serde_yaml::Value> {
+        serde_yaml::from_str(yaml).expect("parse vars")
+    }
+
+    #[test]
+    fn test_render_jinja() {
+        let rendered = DocumentTemplate::Jinja
+            .render(
+                "{% for flag in flags %}\n$ cmd {{ flag }} {{ name }}\n{% endfor %}\n",
+                &vars("{flags: [-a, -b], name: foo}"),
+            )
+            .expect("render template");
+        assert_eq!("$ cmd -a foo\n$ cmd -b foo\n", rendered);
+    }
+
+    #[test]
+    fn test_render_jinja_undefined_variable() {
+        let err = DocumentTemplate::Jinja
+            .render("{{ missing }}\n", &BTreeMap::new())
+            .expect_err("undefined variable");
+        assert!(format!("{:#}", err).contains("undefined value"), "{err:#}");
+    }
+
+    #[test]
+    fn test_render_jinja_no_includes() {
+        DocumentTemplate::Jinja
+            .render("{% include 'other.md' %}\n", &BTreeMap::new())
+            .expect_err("includes are not supported");
+    }
+}
@@ -7,6 +7,7 @@
 
 use std::cmp::Ordering;
 use std::fmt::Display;
+use std::time::Duration;
 
 use anyhow::Result;
 use anyhow::bail;
@@ -18,7 +19,10 @@ use crate::diff::DiffLine;
 use crate::formatln;
 use crate::newline::BytesNewline;
 use crate::outcome::Outcome;
+use crate::output::Output;
 use crate::parsers::parser::ParserType;
+use crate::testcase::ExpectedExitCode;
+use crate::testcase::TestCaseError;
 
 /// Renderer that uses the traditional Diff render format
 /// See: <https://en.wikipedia.org/wiki/Diff>
@@ -89,7 +93,7 @@ impl ErrorRenderer for DiffRenderer {
         &self,
         outcome: &Outcome,
         actual: i32,
-        _expected: i32,
+        _expected: &ExpectedExitCode,
     ) -> Result<String> {
         let line_number = outcome.testcase.line_number
             + outcome.testcase.shell_expression_lines()
@@ -100,7 +104,7 @@ impl ErrorRenderer for DiffRenderer {
         output.push_str(
             &DiffHeader {
                 old_start: line_number,
-                old_length: outcome.testcase.exit_code.map_or(0, |_| 1),
+                old_length: outcome.testcase.exit_code.as_ref().map_or(0, |_| 1),
                 new_start: line_number,
                 new_length: 1,
                 kind: DiffHeaderKind::InvalidExitCode,
@@ -109,7 +113,7 @@ impl ErrorRenderer for DiffRenderer {
             .to_string(),
         );
 
-        if let Some(exit_code) = outcome.testcase.exit_code {
+        if let Some(exit_code) = &outcome.testcase.exit_code {
             output.push_str(&format!("-{prefix}[{exit_code}]\n"));
         }
         output.push_str(&format!("+{prefix}[{actual}]\n"));
@@ -149,6 +153,141 @@ impl ErrorRenderer for DiffRenderer {
     fn render_skipped(&self, _outcome: &Outcome) -> Result<String> {
         Ok("".into())
     }
+
+    fn render_non_deterministic(&self, outcome: &Outcome, _repeated: &Output) -> Result<String> {
+        let title = join_multiline(&outcome.testcase.title, " * ");
+        let mut output = String::new();
+        output.push_str("# ---- NON-DETERMINISTIC OUTPUT ----\n");
+        if let Some(ref location) = outcome.location {
+            output.push_str(&format!("# PATH:  {location}\n"));
+        }
+        output.push_str(&format!("# TITLE: {title}\n"));
+        output.push_str(&format!("# LINE:  {}\n", outcome.testcase.line_number));
+        output.push_str("# ---- NON-DETERMINISTIC OUTPUT ----\n");
+        Ok(output)
+    }
+
+    fn render_no_assertions(&self, outcome: &Outcome) -> Result<String> {
+        let title = join_multiline(&outcome.testcase.title, " * ");
+        let mut output = String::new();
+        output.push_str("# ---- NO ASSERTIONS ----\n");
+        if let Some(ref location) = outcome.location {
+            output.push_str(&format!("# PATH:  {location}\n"));
+        }
+        output.push_str(&format!("# TITLE: {title}\n"));
+        output.push_str(&format!("# LINE:  {}\n", outcome.testcase.line_number));
+        output.push_str("# ---- NO ASSERTIONS ----\n");
+        Ok(output)
+    }
+
+    fn render_exceeded_max_duration(
+        &self,
+        outcome: &Outcome,
+        duration: Duration,
+        max_duration: Duration,
+    ) -> Result<String> {
+        let title = join_multiline(&outcome.testcase.title, " * ");
+        let mut output = String::new();
+        output.push_str("# ---- EXCEEDED MAX DURATION ----\n");
+        if let Some(ref location) = outcome.location {
+            output.push_str(&format!("# PATH:  {location}\n"));
+        }
+        output.push_str(&format!("# TITLE: {title}\n"));
+        output.push_str(&format!("# LINE:  {}\n", outcome.testcase.line_number));
+        output.push_str(&format!(
+            "# TOOK:  {}ms (max {}ms)\n",
+            duration.as_millis(),
+            max_duration.as_millis()
+        ));
+        output.push_str("# ---- EXCEEDED MAX DURATION ----\n");
+        Ok(output)
+    }
+
+    fn render_exceeded_max_rss(
+        &self,
+        outcome: &Outcome,
+        rss_bytes: u64,
+        max_rss_bytes: u64,
+    ) -> Result<String> {
+        let title = join_multiline(&outcome.testcase.title, " * ");
+        let mut output = String::new();
+        output.push_str("# ---- EXCEEDED MAX RSS ----\n");
+        if let Some(ref location) = outcome.location {
+            output.push_str(&format!("# PATH:  {location}\n"));
+        }
+        output.push_str(&format!("# TITLE: {title}\n"));
+        output.push_str(&format!("# LINE:  {}\n", outcome.testcase.line_number));
+        output.push_str(&format!(
+            "# USED:  {rss_bytes} bytes (max {max_rss_bytes} bytes)\n"
+        ));
+        output.push_str("# ---- EXCEEDED MAX RSS ----\n");
+        Ok(output)
+    }
+
+    fn render_exceeded_max_output_size(
+        &self,
+        outcome: &Outcome,
+        max_output_size_bytes: u64,
+    ) -> Result<String> {
+        let title = join_multiline(&outcome.testcase.title, " * ");
+        let mut output = String::new();
+        output.push_str("# ---- EXCEEDED MAX OUTPUT SIZE ----\n");
+        if let Some(ref location) = outcome.location {
+            output.push_str(&format!("# PATH:  {location}\n"));
+        }
+        output.push_str(&format!("# TITLE: {title}\n"));
+        output.push_str(&format!("# LINE:  {}\n", outcome.testcase.line_number));
+        output.push_str(&format!("# MAX:   {max_output_size_bytes} bytes\n"));
+        output.push_str("# ---- EXCEEDED MAX OUTPUT SIZE ----\n");
+        Ok(output)
+    }
+
+    fn render_invalid_utf8_output(&self, outcome: &Outcome, offset: usize) -> Result<String> {
+        let title = join_multiline(&outcome.testcase.title, " * ");
+        let mut output = String::new();
+        output.push_str("# ---- INVALID UTF-8 OUTPUT ----\n");
+        if let Some(ref location) = outcome.location {
+            output.push_str(&format!("# PATH:  {location}\n"));
+        }
+        output.push_str(&format!("# TITLE: {title}\n"));
+        output.push_str(&format!("# LINE:  {}\n", outcome.testcase.line_number));
+        output.push_str(&format!("# AT:    byte offset {offset}\n"));
+        output.push_str("# ---- INVALID UTF-8 OUTPUT ----\n");
+        Ok(output)
+    }
+
+    fn render_failed_assertion(
+        &self,
+        outcome: &Outcome,
+        assertion: &str,
+        reason: &str,
+    ) -> Result<String> {
+        let title = join_multiline(&outcome.testcase.title, " * ");
+        let mut output = String::new();
+        output.push_str("# ---- FAILED ASSERTION ----\n");
+        if let Some(ref location) = outcome.location {
+            output.push_str(&format!("# PATH:      {location}\n"));
+        }
+        output.push_str(&format!("# TITLE:     {title}\n"));
+        output.push_str(&format!("# LINE:      {}\n", outcome.testcase.line_number));
+        output.push_str(&format!("# ASSERTION: {assertion}\n"));
+        output.push_str(&format!("# REASON:    {reason}\n"));
+        output.push_str("# ---- FAILED ASSERTION ----\n");
+        Ok(output)
+    }
+
+    fn render_multiple_failures(
+        &self,
+        outcome: &Outcome,
+        failures: &[TestCaseError],
+    ) -> Result<String> {
+        // failures are in document order, so are the hunks
+        let mut output = String::new();
+        for failure in failures {
+            output.push_str(&self.render_error(failure, outcome)?);
+        }
+        Ok(output)
+    }
 }
 
 #[derive(Default)]
@@ -224,7 +363,12 @@ impl UnifiedDiff {
                     if self.unmatched_start.is_none() {
                         self.unmatched_start = Some(*index);
                     }
-                    self.unmatched_lines.push(expectation.original_string())
+                    self.unmatched_lines.extend(
+                        expectation
+                            .original_string()
+                            .lines()
+                            .map(|line| line.to_string()),
+                    )
                 }
                 DiffLine::UnexpectedLines { lines } => {
                     if self.unexpected_start.is_none() {
@@ -360,7 +504,7 @@ mod tests {
                     title: "the title".into(),
                     shell_expression: "the command".into(),
                     expectations: vec![],
-                    exit_code: Some(111),
+                    exit_code: Some(111.into()),
                     line_number: 234,
                     ..Default::default()
                 },
@@ -389,14 +533,14 @@ mod tests {
                             title: "the title".into(),
                             shell_expression: "the command".into(),
                             expectations: vec![test_expectation!("the stdout")],
-                            exit_code: Some(111),
+                            exit_code: Some(111.into()),
                             line_number: 234,
                             ..Default::default()
                         },
                         location: Some("the location".into()),
                         result: Err(TestCaseError::InvalidExitCode {
                             actual: 222,
-                            expected: 111,
+                            expected: 111.into(),
                         }),
                         escaping: Escaper::default(),
                         format: *parser_type,
@@ -542,7 +686,7 @@ mod tests {
                     testcase1.line_number = 10;
                     let mut testcase2 = testcase.clone();
                     testcase2.line_number = 20;
-                    let outcomes = vec![
+                    let outcomes = [
                         Outcome {
                             output: (
                                 "expected line 1\nexpected line FAIL\nexpected line 3\n",
@@ -0,0 +1,376 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::time::Duration;
+
+use anyhow::Result;
+
+use super::renderer::ErrorRenderer;
+use super::renderer::Renderer;
+use crate::config::ByteSize;
+use crate::diff::Diff;
+use crate::diff::DiffLine;
+use crate::newline::BytesNewline;
+use crate::outcome::Outcome;
+use crate::output::Output;
+use crate::testcase::ExpectedExitCode;
+use crate::testcase::TestCaseError;
+
+/// Renders an `error` workflow command for each failed expectation, so that
+/// GitHub Actions shows the failures inline in the test documents, followed
+/// by the output of another renderer for the job log.
+/// See: <https://docs.github.com/en/actions/reference/workflow-commands-for-github-actions>
+pub struct GithubRenderer(Box<dyn Renderer>);
+
+impl GithubRenderer {
+    pub fn new(log_renderer: Box<dyn Renderer>) -> Self {
+        Self(log_renderer)
+    }
+}
+
+impl Renderer for GithubRenderer {
+    fn render(&self, outcomes: &[&Outcome]) -> Result<String> {
+        let mut output = String::new();
+        for outcome in outcomes {
+            match &outcome.result {
+                Err(TestCaseError::Skipped) | Ok(_) => continue,
+                Err(err) => output.push_str(&self.render_error(err, outcome)?),
+            }
+        }
+        output.push_str(&self.0.render(outcomes)?);
+        Ok(output)
+    }
+}
+
+impl ErrorRenderer for GithubRenderer {
+    fn render_invalid_exit_code(
+        &self,
+        outcome: &Outcome,
+        actual: i32,
+        expected: &ExpectedExitCode,
+    ) -> Result<String> {
+        let line_number = outcome.testcase.line_number
+            + outcome.testcase.shell_expression_lines()
+            + outcome.testcase.expectations_lines();
+        Ok(annotation(
+            outcome,
+            line_number,
+            &format!("unexpected exit code: expected {expected}, but got {actual}"),
+        ))
+    }
+
+    fn render_delegated_error(&self, outcome: &Outcome, err: &anyhow::Error) -> Result<String> {
+        Ok(annotation(
+            outcome,
+            outcome.testcase.line_number,
+            &format!("error: {err}"),
+        ))
+    }
+
+    /// Renders one annotation per expectation that is not matched, at the
+    /// line of the expectation, and one per run of output lines that are not
+    /// expected, at the line after which they were printed
+    fn render_malformed_output(&self, outcome: &Outcome, diff: &Diff) -> Result<String> {
+        let line_number = outcome.testcase.line_number + outcome.testcase.shell_expression_lines();
+        let mut output = String::new();
+        let mut expectation_index = 0;
+        for line in &diff.lines {
+            match line {
+                DiffLine::MatchedExpectation { index, .. } => expectation_index = *index,
+                DiffLine::UnmatchedExpectation { index, expectation } => {
+                    expectation_index = *index;
+                    let missing = format!(
+                        "expected output is missing: {}",
+                        expectation.original_string()
+                    );
+                    output.push_str(&annotation(
+                        outcome,
+                        line_number + index,
+                        &match expectation.message {
+                            Some(ref message) => format!("{message}\n{missing}"),
+                            None => missing,
+                        },
+                    ));
+                }
+                DiffLine::UnexpectedLines { lines } => {
+                    let lines = lines
+                        .iter()
+                        .map(|(_, line)| {
+                            String::from_utf8_lossy((line as &[u8]).trim_newlines()).into_owned()
+                        })
+                        .collect::<Vec<_>>();
+                    output.push_str(&annotation(
+                        outcome,
+                        line_number + expectation_index,
+                        &format!("unexpected output:\n{}", lines.join("\n")),
+                    ));
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    fn render_timeout(&self, outcome: &Outcome) -> Result<String> {
+        Ok(annotation(
+            outcome,
+            outcome.testcase.line_number,
+            "timeout in execution",
+        ))
+    }
+
+    fn render_skipped(&self, _outcome: &Outcome) -> Result<String> {
+        Ok("".into())
+    }
+
+    fn render_non_deterministic(&self, outcome: &Outcome, _repeated: &Output) -> Result<String> {
+        Ok(annotation(
+            outcome,
+            outcome.testcase.line_number,
+            "non-deterministic output in repeated execution",
+        ))
+    }
+
+    fn render_no_assertions(&self, outcome: &Outcome) -> Result<String> {
+        Ok(annotation(
+            outcome,
+            outcome.testcase.line_number,
+            "testcase asserts nothing",
+        ))
+    }
+
+    fn render_exceeded_max_duration(
+        &self,
+        outcome: &Outcome,
+        duration: Duration,
+        max_duration: Duration,
+    ) -> Result<String> {
+        Ok(annotation(
+            outcome,
+            outcome.testcase.line_number,
+            &format!(
+                "testcase took {}ms, longer than its max duration of {}ms",
+                duration.as_millis(),
+                max_duration.as_millis()
+            ),
+        ))
+    }
+
+    fn render_exceeded_max_rss(
+        &self,
+        outcome: &Outcome,
+        rss_bytes: u64,
+        max_rss_bytes: u64,
+    ) -> Result<String> {
+        Ok(annotation(
+            outcome,
+            outcome.testcase.line_number,
+            &format!(
+                "testcase used {:.1} MiB of memory, more than its max RSS of {}",
+                rss_bytes as f64 / (1024.0 * 1024.0),
+                ByteSize(max_rss_bytes)
+            ),
+        ))
+    }
+
+    fn render_exceeded_max_output_size(
+        &self,
+        outcome: &Outcome,
+        max_output_size_bytes: u64,
+    ) -> Result<String> {
+        Ok(annotation(
+            outcome,
+            outcome.testcase.line_number,
+            &format!(
+                "testcase produced more output than its max output size of {}",
+                ByteSize(max_output_size_bytes)
+            ),
+        ))
+    }
+
+    fn render_invalid_utf8_output(&self, outcome: &Outcome, offset: usize) -> Result<String> {
+        Ok(annotation(
+            outcome,
+            outcome.testcase.line_number,
+            &format!("output is not valid UTF-8, starting at byte offset {offset}"),
+        ))
+    }
+
+    fn render_failed_assertion(
+        &self,
+        outcome: &Outcome,
+        assertion: &str,
+        reason: &str,
+    ) -> Result<String> {
+        Ok(annotation(
+            outcome,
+            outcome.testcase.line_number,
+            &format!("assertion failed: {assertion}: {reason}"),
+        ))
+    }
+
+    fn render_multiple_failures(
+        &self,
+        outcome: &Outcome,
+        failures: &[TestCaseError],
+    ) -> Result<String> {
+        failures
+            .iter()
+            .map(|failure| self.render_error(failure, outcome))
+            .collect()
+    }
+}
+
+/// Returns an `error` workflow command for the given line of the document of
+/// the outcome, that is titled with the title of the testcase
+fn annotation(outcome: &Outcome, line_number: usize, message: &str) -> String {
+    let mut properties = vec![];
+    if let Some(ref location) = outcome.location {
+        properties.push(format!("file={}", escape_property(location)));
+    }
+    properties.push(format!("line={line_number}"));
+    let title = outcome
+        .testcase
+        .title
+        .lines()
+        .collect::<Vec<_>>()
+        .join(" * ");
+    if !title.is_empty() {
+        properties.push(format!("title={}", escape_property(&title)));
+    }
+    format!(
+        "::error {}::{}\n",
+        properties.join(","),
+        escape_data(message)
+    )
+}
+
+/// Escapes the message of a workflow command, which must be a single line
+fn escape_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes the value of a property of a workflow command
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GithubRenderer;
+    use crate::diff::Diff;
+    use crate::diff::DiffLine;
+    use crate::escaping::Escaper;
+    use crate::outcome::Outcome;
+    use crate::parsers::parser::ParserType;
+    use crate::renderers::diff::DiffRenderer;
+    use crate::renderers::renderer::Renderer;
+    use crate::test_expectation;
+    use crate::testcase::ExpectedExitCode;
+    use crate::testcase::TestCase;
+    use crate::testcase::TestCaseError;
+
+    fn renderer() -> GithubRenderer {
+        GithubRenderer::new(Box::<DiffRenderer>::default())
+    }
+
+    fn outcome(result: Result<(), TestCaseError>) -> Outcome {
+        Outcome {
+            output: ("foo\nbaz\n", "").into(),
+            testcase: TestCase {
+                title: "the title, with: colon".to_string(),
+                shell_expression: "the command".to_string(),
+                expectations: vec![test_expectation!("foo"), test_expectation!("bar")],
+                exit_code: None,
+                line_number: 10,
+                ..Default::default()
+            },
+            location: Some("the/location.md".to_string()),
+            result,
+            escaping: Escaper::default(),
+            format: ParserType::Markdown,
+        }
+    }
+
+    #[test]
+    fn test_render_success() {
+        let rendered = renderer()
+            .render(&[&outcome(Ok(()))])
+            .expect("render succeeds");
+        assert_eq!("", &rendered, "success results are not rendered");
+    }
+
+    #[test]
+    fn test_render_malformed_output() {
+        let outcome = outcome(Err(TestCaseError::MalformedOutput(Diff::new(vec![
+            DiffLine::MatchedExpectation {
+                index: 0,
+                expectation: test_expectation!("foo"),
+                lines: vec![(0, b"foo\n".to_vec())],
+            },
+            DiffLine::UnmatchedExpectation {
+                index: 1,
+                expectation: test_expectation!("bar"),
+            },
+            DiffLine::UnexpectedLines {
+                lines: vec![(1, b"baz\n".to_vec())],
+            },
+        ]))));
+        let rendered = renderer().render(&[&outcome]).expect("render succeeds");
+        let annotations = rendered
+            .lines()
+            .filter(|line| line.starts_with("::"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "::error file=the/location.md,line=12,title=the title%2C with%3A colon::expected output is missing: bar",
+                "::error file=the/location.md,line=12,title=the title%2C with%3A colon::unexpected output:%0Abaz",
+            ],
+            annotations
+        );
+        assert!(
+            rendered.contains("--- the/location.md\n"),
+            "output of the log renderer follows: {rendered}"
+        );
+    }
+
+    #[test]
+    fn test_render_malformed_output_with_message() {
+        let mut expectation = test_expectation!("bar");
+        expectation.message = Some("bar matters".to_string());
+        let outcome = outcome(Err(TestCaseError::MalformedOutput(Diff::new(vec![
+            DiffLine::UnmatchedExpectation {
+                index: 0,
+                expectation,
+            },
+        ]))));
+        let rendered = renderer().render(&[&outcome]).expect("render succeeds");
+        assert_eq!(
+            Some(
+                "::error file=the/location.md,line=11,title=the title%2C with%3A colon::bar matters%0Aexpected output is missing: bar"
+            ),
+            rendered.lines().next()
+        );
+    }
+
+    #[test]
+    fn test_render_invalid_exit_code() {
+        let outcome = outcome(Err(TestCaseError::InvalidExitCode {
+            actual: 2,
+            expected: ExpectedExitCode::Code(0),
+        }));
+        let rendered = renderer().render(&[&outcome]).expect("render succeeds");
+        assert_eq!(
+            Some(
+                "::error file=the/location.md,line=13,title=the title%2C with%3A colon::unexpected exit code: expected 0, but got 2"
+            ),
+            rendered.lines().next()
+        );
+    }
+}
@@ -9,6 +9,7 @@
 //! executions.
 
 pub mod diff;
+pub mod github;
 pub mod outcome;
 pub mod pretty;
 pub mod renderer;
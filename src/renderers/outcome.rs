@@ -39,6 +39,33 @@ impl OutcomeHeader for Outcome {
                 style(s).bright().cyan()
             }));
         }
+        if !self.testcase.config.annotations.is_empty() {
+            let annotations = self
+                .testcase
+                .config
+                .annotations
+                .iter()
+                .map(|(key, value)| format!("{key}: {value}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            headers.push(header_to_title("%", &annotations, |s| {
+                style(s).bright().magenta()
+            }));
+        }
+        if let Some(attempts) = self.output.attempts {
+            headers.push(header_to_title(
+                "~",
+                &format!("retried: {attempts} attempt(s)"),
+                |s| style(s).bright().magenta(),
+            ));
+        }
+        if let Some(ref work_directory) = self.output.work_directory {
+            headers.push(header_to_title(
+                "&",
+                &format!("work directory: {}", work_directory.display()),
+                |s| style(s).bright().magenta(),
+            ));
+        }
         headers.push(header_to_title("$", &self.testcase.shell_expression, |s| {
             style(s).bold().bright().yellow()
         }));
@@ -6,13 +6,19 @@
  */
 
 use std::collections::HashMap;
+use std::time::Duration;
 
 use anyhow::Result;
+use clap::ValueEnum;
+use console::measure_text_width;
 use console::style;
+use humantime::format_duration;
 
 use super::outcome::OutcomeHeader;
 use super::renderer::ErrorRenderer;
 use super::renderer::Renderer;
+use crate::config::ByteSize;
+use crate::config::DiffContext;
 use crate::diff::Diff;
 use crate::diff::DiffLine;
 use crate::escaping::strip_colors;
@@ -20,6 +26,10 @@ use crate::formatln;
 use crate::newline::BytesNewline;
 use crate::newline::StringNewline;
 use crate::outcome::Outcome;
+use crate::output::ExitStatus;
+use crate::output::Output;
+use crate::signal::signal_name;
+use crate::testcase::ExpectedExitCode;
 use crate::testcase::TestCaseError;
 
 pub const DEFAULT_SURROUNDING_LINES: usize = 5;
@@ -27,6 +37,37 @@ pub const DEFAULT_ABSOLUTE_LINE_NUMBERS: bool = false;
 pub const DEFAULT_SUMMARIZE: bool = true;
 pub const DEFAULT_MULTILINE_MATCHED_LINES: usize = 100;
 
+/// How the differences between expected and actual output are presented
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DiffStyle {
+    /// Expectations and output lines below each other, with mismatching
+    /// expectations prefixed by `-` and unexpected output lines by `+`
+    #[default]
+    Unified,
+
+    /// Expectations and output lines next to each other in two columns
+    SideBySide,
+}
+
+/// Which parts of a mismatching expectation and the output line that it is
+/// paired up with are highlighted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DiffGranularity {
+    /// The whole lines
+    Line,
+
+    /// The words (and other characters in between) that differ
+    #[default]
+    Word,
+
+    /// The characters that differ
+    Char,
+}
+
+/// Up to which product of the token counts of two lines the differing tokens
+/// are determined, beyond which the lines are highlighted as a whole
+const MAX_HIGHLIGHT_COMPLEXITY: usize = 250_000;
+
 /// Renders errors in a human readable way, that higlights the differences eper
 /// test case.
 #[derive(Default)]
@@ -52,9 +93,24 @@ pub struct PrettyColorRenderer {
     pub absolute_line_numbers: bool,
     pub summarize: bool,
     pub max_multiline_matched_lines: usize,
+    pub diff_style: DiffStyle,
+    pub diff_granularity: DiffGranularity,
 }
 
 impl PrettyColorRenderer {
+    /// Returns how many matching lines surround the mismatches of the given
+    /// outcome, from its [`crate::config::TestCaseConfig::diff_context`] or
+    /// from [`PrettyColorRenderer::max_surrounding_lines`], or `None` if all
+    /// lines are shown
+    fn surrounding_lines(&self, outcome: &Outcome) -> Option<usize> {
+        match outcome.testcase.config.diff_context {
+            Some(DiffContext::Lines(lines)) => Some(lines),
+            Some(DiffContext::Full) => None,
+            None if self.max_surrounding_lines == 0 => None,
+            None => Some(self.max_surrounding_lines),
+        }
+    }
+
     fn render_summary(&self, files: usize, ok: usize, errors: usize, ignored: usize) -> String {
         let summary = style("Result").underlined();
         let total = ok + errors + ignored;
@@ -76,6 +132,414 @@ impl PrettyColorRenderer {
             summary, files, tests, succeeded, failed, skipped,
         )
     }
+
+    /// Returns the highlighted contents of the mismatching expectations and of
+    /// the unexpected output lines that they are paired up with, keyed by the
+    /// index of the diff line and the position of the output line in it
+    fn highlight_mismatches(
+        &self,
+        outcome: &Outcome,
+        diff: &Diff,
+    ) -> HashMap<(usize, usize), String> {
+        let mut highlighted = HashMap::new();
+        if self.diff_granularity == DiffGranularity::Line {
+            return highlighted;
+        }
+        let mut diff_index = 0;
+        while diff_index < diff.lines.len() {
+            // pair up a run of mismatching expectations and unexpected lines
+            let (mut expected, mut actual) = (vec![], vec![]);
+            while let Some(line) = diff.lines.get(diff_index) {
+                match line {
+                    DiffLine::MatchedExpectation { .. } => break,
+                    DiffLine::UnmatchedExpectation { expectation, .. } => expected.push((
+                        (diff_index, 0),
+                        expectation.to_expression_string(&outcome.escaping),
+                    )),
+                    DiffLine::UnexpectedLines { lines } => {
+                        actual.extend(lines.iter().enumerate().map(|(position, (_, line))| {
+                            (
+                                (diff_index, position),
+                                render_unexpected_line(outcome, line),
+                            )
+                        }))
+                    }
+                }
+                diff_index += 1;
+            }
+            for ((expected_key, expected), (actual_key, actual)) in expected.into_iter().zip(actual)
+            {
+                let (expected, actual) =
+                    highlight_changes(&expected, &actual, self.diff_granularity);
+                highlighted.insert(expected_key, expected);
+                highlighted.insert(actual_key, actual);
+            }
+            diff_index += 1;
+        }
+        highlighted
+    }
+
+    /// Renders the expectations and the output lines in two columns, with
+    /// mismatching expectations paired up with the unexpected output lines
+    /// that follow them
+    fn render_side_by_side(&self, outcome: &Outcome, diff: &Diff) -> String {
+        let line_base = if self.absolute_line_numbers {
+            outcome.testcase.line_number + outcome.testcase.shell_expression_lines() - 1
+        } else {
+            0
+        };
+        let error_indices = diff
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| !matches!(line, DiffLine::MatchedExpectation { .. }))
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let surrounding_lines = self.surrounding_lines(outcome);
+        let visible = |index: usize| match surrounding_lines {
+            Some(lines) => error_indices
+                .iter()
+                .any(|error_index| error_index.abs_diff(index) <= lines),
+            None => true,
+        };
+
+        let mut rows = vec![];
+        let mut diff_index = 0;
+        while diff_index < diff.lines.len() {
+            if let DiffLine::MatchedExpectation {
+                index,
+                expectation,
+                lines,
+            } = &diff.lines[diff_index]
+            {
+                if visible(diff_index) {
+                    let expectation = expectation.to_expression_string(&outcome.escaping);
+                    let max_lines = self.max_multiline_matched_lines.max(1);
+                    for (line_offset, (line_index, line)) in
+                        lines.iter().take(max_lines).enumerate()
+                    {
+                        rows.push(SideBySideRow::Matched {
+                            expectation: (line_offset == 0)
+                                .then(|| (line_base + index + 1, expectation.clone())),
+                            output: (
+                                line_base + line_index + 1,
+                                outcome
+                                    .escaping
+                                    .escaped_printable((line as &[u8]).trim_newlines()),
+                            ),
+                        });
+                    }
+                    if lines.len() > max_lines {
+                        rows.push(SideBySideRow::Skipped);
+                    }
+                } else if !matches!(rows.last(), Some(SideBySideRow::Skipped)) {
+                    rows.push(SideBySideRow::Skipped);
+                }
+                diff_index += 1;
+                continue;
+            }
+
+            // pair up a run of mismatching expectations and unexpected lines
+            let (mut expected, mut actual) = (vec![], vec![]);
+            while let Some(line) = diff.lines.get(diff_index) {
+                match line {
+                    DiffLine::MatchedExpectation { .. } => break,
+                    DiffLine::UnmatchedExpectation { index, expectation } => expected.push((
+                        line_base + index + 1,
+                        expectation.to_expression_string(&outcome.escaping),
+                    )),
+                    DiffLine::UnexpectedLines { lines } => {
+                        actual.extend(lines.iter().map(|(line_index, line)| {
+                            (
+                                line_base + line_index + 1,
+                                render_unexpected_line(outcome, line),
+                            )
+                        }))
+                    }
+                }
+                diff_index += 1;
+            }
+            for row_index in 0..expected.len().max(actual.len()) {
+                rows.push(SideBySideRow::Mismatched {
+                    expectation: expected.get(row_index).cloned(),
+                    output: actual.get(row_index).cloned(),
+                });
+            }
+        }
+
+        let number_width = diff
+            .count_output_lines
+            .max(outcome.testcase.expectations.len())
+            + line_base;
+        let number_width = number_width.to_string().len();
+        let column_width = rows
+            .iter()
+            .filter_map(|row| match row {
+                SideBySideRow::Matched { expectation, .. }
+                | SideBySideRow::Mismatched { expectation, .. } => expectation.as_ref(),
+                SideBySideRow::Skipped => None,
+            })
+            .map(|(_, content)| measure_text_width(content))
+            .max()
+            .unwrap_or(0)
+            .max("expected".len());
+
+        let mut output = style(formatln!(
+            "{} {:<column_width$}   {} actual",
+            " ".repeat(number_width + 2),
+            "expected",
+            " ".repeat(number_width + 2),
+        ))
+        .bright()
+        .black()
+        .to_string();
+        for row in rows {
+            let (expectation, output_line, left, right) = match row {
+                SideBySideRow::Skipped => {
+                    output.push_str(&"...".assure_newline());
+                    continue;
+                }
+                SideBySideRow::Matched {
+                    expectation,
+                    output: output_line,
+                } => {
+                    let left = expectation
+                        .as_ref()
+                        .map(|(_, content)| style(content.to_string()).white().to_string());
+                    let right = style(output_line.1.to_string()).white().to_string();
+                    (
+                        expectation.map(|(number, content)| (number, content, " ")),
+                        Some((output_line.0, output_line.1, " ")),
+                        left,
+                        Some(right),
+                    )
+                }
+                SideBySideRow::Mismatched {
+                    expectation,
+                    output: output_line,
+                } => {
+                    let (left, right) = match (&expectation, &output_line) {
+                        (Some((_, expected)), Some((_, actual))) => {
+                            let (left, right) =
+                                highlight_changes(expected, actual, self.diff_granularity);
+                            (Some(left), Some(right))
+                        }
+                        _ => (
+                            expectation.as_ref().map(|(_, content)| {
+                                style(content.higlight_tailing_spaces()).red().to_string()
+                            }),
+                            output_line.as_ref().map(|(_, content)| {
+                                style(content.higlight_tailing_spaces()).green().to_string()
+                            }),
+                        ),
+                    };
+                    (
+                        expectation.map(|(number, content)| (number, content, "-")),
+                        output_line.map(|(number, content)| (number, content, "+")),
+                        left,
+                        right,
+                    )
+                }
+            };
+            let number = |number: Option<usize>| match number {
+                Some(number) => format!("{number:>number_width$}"),
+                None => " ".repeat(number_width),
+            };
+            let symbol = |symbol: Option<&str>| match symbol {
+                Some("-") => style("-").red().bold().to_string(),
+                Some("+") => style("+").green().bold().to_string(),
+                _ => " ".to_string(),
+            };
+            let padding = column_width
+                - expectation
+                    .as_ref()
+                    .map(|(_, content, _)| measure_text_width(content))
+                    .unwrap_or(0);
+            output.push_str(&format!(
+                "{} {} {}{} {}",
+                style(number(expectation.as_ref().map(|(number, _, _)| *number)))
+                    .bright()
+                    .black(),
+                symbol(expectation.as_ref().map(|(_, _, symbol)| *symbol)),
+                left.unwrap_or_default(),
+                " ".repeat(padding),
+                style("|").bright().black(),
+            ));
+            if let Some(right) = right {
+                output.push_str(&format!(
+                    " {} {} {}",
+                    style(number(output_line.as_ref().map(|(number, _, _)| *number)))
+                        .bright()
+                        .black(),
+                    symbol(output_line.as_ref().map(|(_, _, symbol)| *symbol)),
+                    right,
+                ));
+            }
+            output.push('\n');
+        }
+        output
+    }
+}
+
+/// A row of the side-by-side presentation of a diff, with the line numbers
+/// of the expectation and the output line
+enum SideBySideRow {
+    Matched {
+        expectation: Option<(usize, String)>,
+        output: (usize, String),
+    },
+    Mismatched {
+        expectation: Option<(usize, String)>,
+        output: Option<(usize, String)>,
+    },
+    Skipped,
+}
+
+/// Returns the expected and the actual line, in which the parts that differ
+/// between both, in the given granularity, are highlighted
+fn highlight_changes(
+    expected: &str,
+    actual: &str,
+    granularity: DiffGranularity,
+) -> (String, String) {
+    let highlight =
+        |segments: Vec<Segment>, color: fn(String) -> String, marked: fn(String) -> String| {
+            segments
+                .into_iter()
+                .map(|(segment, changed)| match changed {
+                    true if segment.trim().is_empty() => marked(render_spaces(&segment)),
+                    true => marked(segment),
+                    false => color(segment),
+                })
+                .collect::<String>()
+        };
+    let (expected, actual) = match changed_segments(expected, actual, granularity) {
+        Some(segments) => segments,
+        None => {
+            return (
+                style(expected.higlight_tailing_spaces()).red().to_string(),
+                style(actual.higlight_tailing_spaces()).green().to_string(),
+            );
+        }
+    };
+    (
+        highlight(
+            expected,
+            |s| style(s).red().to_string(),
+            |s| style(s).red().bold().underlined().to_string(),
+        ),
+        highlight(
+            actual,
+            |s| style(s).green().to_string(),
+            |s| style(s).green().bold().underlined().to_string(),
+        ),
+    )
+}
+
+/// A part of a line, that is flagged whether it differs from the line that
+/// it is compared with
+type Segment = (String, bool);
+
+/// Splits the expected and the actual line into segments, that are flagged
+/// whether they differ between both, based on the longest common sequence of
+/// their tokens in the given granularity. Returns `None` if the lines are to
+/// be highlighted as a whole.
+fn changed_segments(
+    expected: &str,
+    actual: &str,
+    granularity: DiffGranularity,
+) -> Option<(Vec<Segment>, Vec<Segment>)> {
+    // expressions that span lines, like JSON documents, do not align with a line
+    if expected.contains('\n') {
+        return None;
+    }
+    let (expected, actual) = match granularity {
+        DiffGranularity::Line => return None,
+        DiffGranularity::Word => (word_tokens(expected), word_tokens(actual)),
+        DiffGranularity::Char => (char_tokens(expected), char_tokens(actual)),
+    };
+    if expected.len() * actual.len() > MAX_HIGHLIGHT_COMPLEXITY {
+        return None;
+    }
+
+    // length of the longest common sequence of the tokens from the indices on
+    let mut common = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for left in (0..expected.len()).rev() {
+        for right in (0..actual.len()).rev() {
+            common[left][right] = if expected[left] == actual[right] {
+                common[left + 1][right + 1] + 1
+            } else {
+                common[left + 1][right].max(common[left][right + 1])
+            };
+        }
+    }
+    let (mut expected_changed, mut actual_changed) =
+        (vec![true; expected.len()], vec![true; actual.len()]);
+    let (mut left, mut right) = (0, 0);
+    while left < expected.len() && right < actual.len() {
+        if expected[left] == actual[right] {
+            expected_changed[left] = false;
+            actual_changed[right] = false;
+            left += 1;
+            right += 1;
+        } else if common[left + 1][right] >= common[left][right + 1] {
+            left += 1;
+        } else {
+            right += 1;
+        }
+    }
+
+    let segments = |tokens: Vec<&str>, changed: Vec<bool>| {
+        let mut segments: Vec<Segment> = vec![];
+        for (token, changed) in tokens.into_iter().zip(changed) {
+            match segments.last_mut() {
+                Some((segment, last_changed)) if *last_changed == changed => {
+                    segment.push_str(token)
+                }
+                _ => segments.push((token.to_string(), changed)),
+            }
+        }
+        segments
+    };
+    Some((
+        segments(expected, expected_changed),
+        segments(actual, actual_changed),
+    ))
+}
+
+/// Splits the line into words, runs of whitespace and single other characters
+fn word_tokens(line: &str) -> Vec<&str> {
+    let class = |ch: char| {
+        if ch.is_alphanumeric() || ch == '_' {
+            1
+        } else if ch.is_whitespace() {
+            2
+        } else {
+            0
+        }
+    };
+    let mut tokens = vec![];
+    let mut start = 0;
+    let mut previous = None;
+    for (index, ch) in line.char_indices() {
+        let current = class(ch);
+        if index > start && (current == 0 || previous != Some(current)) {
+            tokens.push(&line[start..index]);
+            start = index;
+        }
+        previous = Some(current);
+    }
+    if start < line.len() {
+        tokens.push(&line[start..]);
+    }
+    tokens
+}
+
+/// Splits the line into its characters
+fn char_tokens(line: &str) -> Vec<&str> {
+    line.char_indices()
+        .map(|(index, ch)| &line[index..index + ch.len_utf8()])
+        .collect()
 }
 
 impl Default for PrettyColorRenderer {
@@ -85,6 +549,8 @@ impl Default for PrettyColorRenderer {
             absolute_line_numbers: DEFAULT_ABSOLUTE_LINE_NUMBERS,
             summarize: DEFAULT_SUMMARIZE,
             max_multiline_matched_lines: DEFAULT_MULTILINE_MATCHED_LINES,
+            diff_style: DiffStyle::default(),
+            diff_granularity: DiffGranularity::default(),
         }
     }
 }
@@ -132,12 +598,15 @@ impl ErrorRenderer for PrettyColorRenderer {
         &self,
         outcome: &Outcome,
         actual: i32,
-        expected: i32,
+        expected: &ExpectedExitCode,
     ) -> Result<String> {
         let mut out = String::new();
         out.push_str(&formatln!("unexpected exit code"));
         out.push_str(&formatln!("  expected: {}", expected));
-        out.push_str(&formatln!("  actual:   {}", actual));
+        out.push_str(&formatln!(
+            "  actual:   {}",
+            render_actual_exit_code(outcome, actual)
+        ));
         out.push_str(&formatln!(""));
         out.push_str(&outcome.output.to_error_string(&outcome.escaping));
         Ok(out)
@@ -148,7 +617,11 @@ impl ErrorRenderer for PrettyColorRenderer {
     }
 
     fn render_malformed_output(&self, outcome: &Outcome, diff: &Diff) -> Result<String> {
-        let mut output = String::new();
+        let mut output = render_failure_messages(diff);
+        if self.diff_style == DiffStyle::SideBySide {
+            output.push_str(&self.render_side_by_side(outcome, diff));
+            return Ok(output);
+        }
         let line_base = if self.absolute_line_numbers {
             outcome.testcase.line_number + outcome.testcase.shell_expression_lines() - 1
         } else {
@@ -160,6 +633,8 @@ impl ErrorRenderer for PrettyColorRenderer {
                     .count_output_lines
                     .max(outcome.testcase.expectations.len()),
         );
+        let highlighted = self.highlight_mismatches(outcome, diff);
+        let surrounding_lines = self.surrounding_lines(outcome);
         let mut last_error_index = None;
         let next_error_index = |index: usize| {
             diff.lines
@@ -187,18 +662,16 @@ impl ErrorRenderer for PrettyColorRenderer {
                 } => {
                     let mut skip = true;
                     let mut first_skip = false;
-                    if self.max_surrounding_lines > 0 {
+                    if let Some(surrounding_lines) = surrounding_lines {
                         if let Some(last_error_index) = last_error_index {
-                            if last_error_index + self.max_surrounding_lines >= diff_index {
+                            if last_error_index + surrounding_lines >= diff_index {
                                 skip = false;
-                            } else if last_error_index + self.max_surrounding_lines + 1
-                                == diff_index
-                            {
+                            } else if last_error_index + surrounding_lines + 1 == diff_index {
                                 first_skip = true;
                             }
                         }
                         if let Some(next_error_index) = next_error_index(diff_index + 1) {
-                            if diff_index + self.max_surrounding_lines >= next_error_index {
+                            if diff_index + surrounding_lines >= next_error_index {
                                 skip = false;
                             }
                         }
@@ -306,42 +779,48 @@ impl ErrorRenderer for PrettyColorRenderer {
                 }
                 DiffLine::UnmatchedExpectation { index, expectation } => {
                     last_error_index = Some(diff_index);
-                    let content = expectation
-                        .to_expression_string(&outcome.escaping)
-                        .higlight_tailing_spaces();
-                    output.push_str(
-                        &decorator
-                            .line(
-                                None,
-                                Some(line_base + index + 1),
-                                expectation.multiline,
-                                "-",
-                                &content,
-                            )
-                            .assure_newline(),
-                    )
+                    let line = match highlighted.get(&(diff_index, 0)) {
+                        Some(content) => decorator.styled_line(
+                            None,
+                            Some(line_base + index + 1),
+                            expectation.multiline,
+                            "-",
+                            content,
+                        ),
+                        None => decorator.line(
+                            None,
+                            Some(line_base + index + 1),
+                            expectation.multiline,
+                            "-",
+                            &expectation
+                                .to_expression_string(&outcome.escaping)
+                                .higlight_tailing_spaces(),
+                        ),
+                    };
+                    output.push_str(&line.assure_newline())
                 }
                 DiffLine::UnexpectedLines { lines } => {
-                    lines.iter().for_each(|(line_index, line)| {
-                        let eol = (line.as_ref() as &[u8]).ends_in_newline();
-                        let line = if !eol {
-                            let mut line = line.clone();
-                            line.extend(b" (no-eol)");
-                            line
-                        } else {
-                            line.to_owned()
-                        };
-                        let line = outcome
-                            .escaping
-                            .escaped_expectation(&line)
-                            .higlight_tailing_spaces();
-                        last_error_index = Some(diff_index);
-                        output.push_str(
-                            &decorator
-                                .line(Some(line_base + line_index + 1), None, false, "+", &line)
-                                .assure_newline(),
-                        )
-                    })
+                    lines
+                        .iter()
+                        .enumerate()
+                        .for_each(|(position, (line_index, line))| {
+                            last_error_index = Some(diff_index);
+                            let line_number = Some(line_base + line_index + 1);
+                            let line = match highlighted.get(&(diff_index, position)) {
+                                Some(content) => {
+                                    decorator.styled_line(line_number, None, false, "+", content)
+                                }
+                                None => decorator.line(
+                                    line_number,
+                                    None,
+                                    false,
+                                    "+",
+                                    &render_unexpected_line(outcome, line)
+                                        .higlight_tailing_spaces(),
+                                ),
+                            };
+                            output.push_str(&line.assure_newline())
+                        })
                 }
             }
         }
@@ -360,6 +839,125 @@ impl ErrorRenderer for PrettyColorRenderer {
     fn render_skipped(&self, _outcome: &Outcome) -> Result<String> {
         Ok("".into())
     }
+
+    fn render_non_deterministic(&self, outcome: &Outcome, repeated: &Output) -> Result<String> {
+        let mut out = String::new();
+        out.push_str(&formatln!("non-deterministic output in repeated execution"));
+        out.push_str(&formatln!(""));
+        out.push_str(&formatln!("# FIRST EXECUTION"));
+        out.push_str(&formatln!("exit code: {}", outcome.output.exit_code));
+        out.push_str(&outcome.output.to_error_string(&outcome.escaping));
+        out.push_str(&formatln!("# REPEATED EXECUTION"));
+        out.push_str(&formatln!("exit code: {}", repeated.exit_code));
+        out.push_str(&repeated.to_error_string(&outcome.escaping));
+        Ok(out)
+    }
+
+    fn render_no_assertions(&self, _outcome: &Outcome) -> Result<String> {
+        let mut out = String::new();
+        out.push_str(&formatln!("testcase asserts nothing"));
+        out.push_str(&formatln!(""));
+        out.push_str(&formatln!(
+            "Add output expectations or an exit code, or allow it with `allow_empty_tests: true`"
+        ));
+        Ok(out)
+    }
+
+    fn render_exceeded_max_duration(
+        &self,
+        _outcome: &Outcome,
+        duration: Duration,
+        max_duration: Duration,
+    ) -> Result<String> {
+        let mut out = String::new();
+        out.push_str(&formatln!("testcase took longer than its max duration"));
+        out.push_str(&formatln!(
+            "  max duration: {}",
+            format_duration(max_duration)
+        ));
+        out.push_str(&formatln!(
+            "  actual:       {}",
+            format_duration(Duration::from_millis(duration.as_millis() as u64))
+        ));
+        Ok(out)
+    }
+
+    fn render_exceeded_max_rss(
+        &self,
+        _outcome: &Outcome,
+        rss_bytes: u64,
+        max_rss_bytes: u64,
+    ) -> Result<String> {
+        let mut out = String::new();
+        out.push_str(&formatln!("testcase used more memory than its max RSS"));
+        out.push_str(&formatln!("  max RSS: {}", ByteSize(max_rss_bytes)));
+        out.push_str(&formatln!(
+            "  actual:  {:.1} MiB",
+            rss_bytes as f64 / (1024.0 * 1024.0)
+        ));
+        Ok(out)
+    }
+
+    fn render_exceeded_max_output_size(
+        &self,
+        _outcome: &Outcome,
+        max_output_size_bytes: u64,
+    ) -> Result<String> {
+        Ok(formatln!(
+            "testcase produced more output than its max output size of {}, so it was ended early",
+            ByteSize(max_output_size_bytes)
+        ))
+    }
+
+    fn render_invalid_utf8_output(&self, _outcome: &Outcome, offset: usize) -> Result<String> {
+        Ok(formatln!(
+            "output is not valid UTF-8, starting at byte offset {}",
+            offset
+        ))
+    }
+
+    fn render_failed_assertion(
+        &self,
+        _outcome: &Outcome,
+        assertion: &str,
+        reason: &str,
+    ) -> Result<String> {
+        let mut out = String::new();
+        out.push_str(&formatln!("assertion failed"));
+        out.push_str(&formatln!("  assertion: {}", assertion));
+        out.push_str(&formatln!("  reason:    {}", reason));
+        Ok(out)
+    }
+
+    fn render_multiple_failures(
+        &self,
+        outcome: &Outcome,
+        failures: &[TestCaseError],
+    ) -> Result<String> {
+        let mut out = String::new();
+        for (index, failure) in failures.iter().enumerate() {
+            if index > 0 {
+                out.push_str(&formatln!(""));
+            }
+            out.push_str(&formatln!(
+                "{}",
+                style(format!("failure {} of {}:", index + 1, failures.len())).bold()
+            ));
+            match failure {
+                // the output is already part of the other failures
+                TestCaseError::InvalidExitCode { actual, expected } => {
+                    out.push_str(&formatln!("unexpected exit code"));
+                    out.push_str(&formatln!("  expected: {}", expected));
+                    out.push_str(&formatln!(
+                        "  actual:   {}",
+                        render_actual_exit_code(outcome, *actual)
+                    ));
+                }
+                _ => out.push_str(&self.render_error(failure, outcome)?),
+            }
+        }
+        Ok(out)
+    }
 }
 
 trait TailingSpacesHighlighter {
@@ -380,6 +978,49 @@ impl<T: AsRef<str>> TailingSpacesHighlighter for T {
     }
 }
 
+/// Returns an output line that no expectation matches, as it would be written
+/// as expectation
+/// Renders the messages of the expectations that did not match, so that they
+/// stand out above the differences
+fn render_failure_messages(diff: &Diff) -> String {
+    let messages = diff.failure_messages();
+    if messages.is_empty() {
+        return String::new();
+    }
+    let mut output = messages
+        .iter()
+        .map(|message| formatln!("{}", style(format!("! {message}")).red().bold()))
+        .collect::<String>();
+    output.push_str(&formatln!(""));
+    output
+}
+
+fn render_unexpected_line(outcome: &Outcome, line: &[u8]) -> String {
+    if line.ends_in_newline() {
+        outcome.escaping.escaped_expectation(line)
+    } else {
+        let mut line = line.to_vec();
+        line.extend(b" (no-eol)");
+        outcome.escaping.escaped_expectation(&line)
+    }
+}
+
+/// Returns the actual exit code, prefixed with the signal that terminated the
+/// execution, if any
+/// Renders the actual exit code, so that terminations by signal can be told
+/// apart from exit codes, including those the shell reports for commands it
+/// ran, that were terminated by a signal (`128 + N`)
+fn render_actual_exit_code(outcome: &Outcome, actual: i32) -> String {
+    match outcome.output.exit_code {
+        ExitStatus::Signal(_) => format!("{} (exit code {})", outcome.output.exit_code, actual),
+        ExitStatus::Code(code) if code > 128 => match signal_name(code - 128) {
+            Some(name) => format!("{} (128 + signal:{})", actual, name),
+            None => actual.to_string(),
+        },
+        _ => actual.to_string(),
+    }
+}
+
 fn space_start_index(input: &str) -> usize {
     for (i, ch) in input.chars().rev().enumerate() {
         if !ch.is_whitespace() {
@@ -435,6 +1076,29 @@ impl Decorator {
         multiline: bool,
         symbol: &str,
         content: &str,
+    ) -> String {
+        let color = match symbol {
+            "+" => |s: &str| style(s).green().bold().to_string(),
+            "-" => |s: &str| style(s).red().bold().to_string(),
+            _ => |s: &str| style(s).white().to_string(),
+        };
+        self.styled_line(
+            line_number,
+            expectation_number,
+            multiline,
+            symbol,
+            &color(content),
+        )
+    }
+
+    /// Like [`Decorator::line`], but with content that is already styled
+    fn styled_line(
+        &self,
+        line_number: Option<usize>,
+        expectation_number: Option<usize>,
+        multiline: bool,
+        symbol: &str,
+        content: &str,
     ) -> String {
         let color = match symbol {
             "+" => |s: &str| style(s).green().bold().to_string(),
@@ -446,37 +1110,117 @@ impl Decorator {
             "-" => |s: &str| style(s).red().to_string(),
             _ => |s: &str| s.to_string(),
         };
-        style(format!(
-            "{} {}  | {} {}",
-            line_color(&self.expectation_line_number(expectation_number, multiline)),
-            line_color(&self.output_line_number(line_number)),
-            color(symbol),
-            color(content)
-        ))
-        .bright()
-        .black()
-        .to_string()
+        // expressions that span lines, like JSON documents, continue without numbers
+        content
+            .split('\n')
+            .enumerate()
+            .map(|(offset, content)| {
+                let (expectation_number, line_number, multiline) = match offset {
+                    0 => (expectation_number, line_number, multiline),
+                    _ => (None, None, false),
+                };
+                style(format!(
+                    "{} {}  | {} {}",
+                    line_color(&self.expectation_line_number(expectation_number, multiline)),
+                    line_color(&self.output_line_number(line_number)),
+                    color(symbol),
+                    content
+                ))
+                .bright()
+                .black()
+                .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
     use anyhow::anyhow;
 
+    use super::DiffGranularity;
+    use super::DiffStyle;
     use super::PrettyColorRenderer;
     use super::PrettyMonochromeRenderer;
     use crate::bformatln;
+    use crate::config::DiffContext;
+    use crate::config::TestCaseConfig;
     use crate::diff::Diff;
     use crate::diff::DiffLine;
     use crate::escaping::Escaper;
     use crate::formatln;
     use crate::outcome::Outcome;
+    use crate::output::Output;
     use crate::parsers::parser::ParserType;
     use crate::renderers::renderer::Renderer;
     use crate::test_expectation;
     use crate::testcase::TestCase;
     use crate::testcase::TestCaseError;
 
+    #[test]
+    fn test_changed_segments() {
+        let segments = |granularity| {
+            super::changed_segments(
+                "copied 12 files to /tmp/a",
+                "copied 13 files to /tmp/b",
+                granularity,
+            )
+        };
+        let owned = |segments: &[(&str, bool)]| {
+            segments
+                .iter()
+                .map(|(segment, changed)| (segment.to_string(), *changed))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(None, segments(DiffGranularity::Line));
+        assert_eq!(
+            Some((
+                owned(&[
+                    ("copied ", false),
+                    ("12", true),
+                    (" files to /tmp/", false),
+                    ("a", true),
+                ]),
+                owned(&[
+                    ("copied ", false),
+                    ("13", true),
+                    (" files to /tmp/", false),
+                    ("b", true),
+                ]),
+            )),
+            segments(DiffGranularity::Word)
+        );
+        assert_eq!(
+            Some((
+                owned(&[
+                    ("copied 1", false),
+                    ("2", true),
+                    (" files to /tmp/", false),
+                    ("a", true),
+                ]),
+                owned(&[
+                    ("copied 1", false),
+                    ("3", true),
+                    (" files to /tmp/", false),
+                    ("b", true),
+                ]),
+            )),
+            segments(DiffGranularity::Char)
+        );
+    }
+
+    #[test]
+    fn test_word_tokens() {
+        assert_eq!(
+            vec!["foo_bar", "  ", "=", ">", " ", "ünï", " ", "1", ".", "5"],
+            super::word_tokens("foo_bar  => ünï 1.5")
+        );
+    }
+
     fn new_test_renderer() -> PrettyMonochromeRenderer {
         PrettyMonochromeRenderer::new(PrettyColorRenderer {
             max_surrounding_lines: 0,
@@ -530,7 +1274,7 @@ mod tests {
                 location: Some("the location \\\nnext line \\\nlast line".into()),
                 result: Err(TestCaseError::InvalidExitCode {
                     actual: 123,
-                    expected: 234,
+                    expected: 234.into(),
                 }),
                 escaping: Escaper::default(),
                 format: ParserType::Markdown,
@@ -556,7 +1300,7 @@ mod tests {
                 location: Some("the location".to_string()),
                 result: Err(TestCaseError::InvalidExitCode {
                     actual: 123,
-                    expected: 234,
+                    expected: 234.into(),
                 }),
                 escaping: Escaper::default(),
                 format: ParserType::Markdown,
@@ -610,7 +1354,7 @@ mod tests {
                     location: test.location.clone(),
                     result: Err(TestCaseError::InvalidExitCode {
                         actual: 123,
-                        expected: 234,
+                        expected: 234.into(),
                     }),
                     escaping: Escaper::default(),
                     format: ParserType::Markdown,
@@ -627,6 +1371,95 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_render_annotations() {
+        let renderer = new_test_renderer();
+        let rendered = renderer
+            .render(&[&Outcome {
+                location: None,
+                output: ("the stdout", "the stderr").into(),
+                testcase: TestCase {
+                    title: "the title".to_string(),
+                    shell_expression: "the command".to_string(),
+                    config: TestCaseConfig {
+                        annotations: BTreeMap::from([
+                            ("component".to_string(), "auth".to_string()),
+                            ("severity".to_string(), "p1".to_string()),
+                        ]),
+                        ..Default::default()
+                    },
+                    line_number: 234,
+                    ..Default::default()
+                },
+                result: Err(TestCaseError::InvalidExitCode {
+                    actual: 1,
+                    expected: 0.into(),
+                }),
+                escaping: Escaper::default(),
+                format: ParserType::Markdown,
+            }])
+            .expect("render succeeds");
+        insta::assert_snapshot!(rendered);
+    }
+
+    #[test]
+    fn test_render_retried() {
+        let renderer = new_test_renderer();
+        let rendered = renderer
+            .render(&[&Outcome {
+                location: None,
+                output: Output {
+                    attempts: Some(5),
+                    ..("the stdout", "the stderr", Some(1)).into()
+                },
+                testcase: TestCase {
+                    title: "the title".to_string(),
+                    shell_expression: "the command".to_string(),
+                    config: TestCaseConfig {
+                        retry: Some(Duration::from_secs(10)),
+                        ..Default::default()
+                    },
+                    line_number: 234,
+                    ..Default::default()
+                },
+                result: Err(TestCaseError::InvalidExitCode {
+                    actual: 1,
+                    expected: 0.into(),
+                }),
+                escaping: Escaper::default(),
+                format: ParserType::Markdown,
+            }])
+            .expect("render succeeds");
+        insta::assert_snapshot!(rendered);
+    }
+
+    #[test]
+    fn test_render_kept_work_directory() {
+        let renderer = new_test_renderer();
+        let rendered = renderer
+            .render(&[&Outcome {
+                location: None,
+                output: Output {
+                    work_directory: Some("/tmp/work.123".into()),
+                    ..("the stdout", "the stderr", Some(1)).into()
+                },
+                testcase: TestCase {
+                    title: "the title".to_string(),
+                    shell_expression: "the command".to_string(),
+                    line_number: 234,
+                    ..Default::default()
+                },
+                result: Err(TestCaseError::InvalidExitCode {
+                    actual: 1,
+                    expected: 0.into(),
+                }),
+                escaping: Escaper::default(),
+                format: ParserType::Markdown,
+            }])
+            .expect("render succeeds");
+        insta::assert_snapshot!(rendered);
+    }
+
     #[test]
     fn test_render_internal_error() {
         let renderer = new_test_renderer();
@@ -650,6 +1483,31 @@ mod tests {
         insta::assert_snapshot!(rendered);
     }
 
+    #[test]
+    fn test_render_non_deterministic() {
+        let renderer = new_test_renderer();
+        let rendered = renderer
+            .render(&[&Outcome {
+                location: None,
+                output: ("the stdout", "the stderr", Some(0)).into(),
+                testcase: TestCase {
+                    title: "the title".to_string(),
+                    shell_expression: "the command".to_string(),
+                    expectations: vec![],
+                    exit_code: None,
+                    line_number: 234,
+                    ..Default::default()
+                },
+                result: Err(TestCaseError::NonDeterministic(Box::new(
+                    ("the other stdout", "the stderr", Some(1)).into(),
+                ))),
+                escaping: Escaper::default(),
+                format: ParserType::Markdown,
+            }])
+            .expect("render does not fail");
+        insta::assert_snapshot!(rendered);
+    }
+
     #[test]
     fn test_render_malformed_output() {
         let renderer = new_test_renderer();
@@ -692,6 +1550,138 @@ mod tests {
         insta::assert_snapshot!(rendered);
     }
 
+    #[test]
+    fn test_render_malformed_output_with_message() {
+        let renderer = new_test_renderer();
+        let mut banner = test_expectation!("equal", "version 1", false, false);
+        banner.message = Some("API version banner changed".to_string());
+        let testcase = TestCase {
+            title: "the title".to_string(),
+            shell_expression: "the command".to_string(),
+            expectations: vec![banner, test_expectation!("equal", "ready", false, false)],
+            exit_code: None,
+            line_number: 234,
+            ..Default::default()
+        };
+        let rendered = renderer
+            .render(&[&Outcome {
+                location: None,
+                output: ("version 2\nready\n", "", Some(0)).into(),
+                testcase: testcase.clone(),
+                result: Err(TestCaseError::MalformedOutput(Diff::new(vec![
+                    DiffLine::UnmatchedExpectation {
+                        index: 0,
+                        expectation: testcase.expectations[0].clone(),
+                    },
+                    DiffLine::UnexpectedLines {
+                        lines: vec![(0, bformatln!("version 2"))],
+                    },
+                    DiffLine::MatchedExpectation {
+                        index: 1,
+                        expectation: testcase.expectations[1].clone(),
+                        lines: vec![(1, bformatln!("ready"))],
+                    },
+                ]))),
+                escaping: Escaper::default(),
+                format: ParserType::Markdown,
+            }])
+            .expect("render does not fail");
+        insta::assert_snapshot!(rendered);
+    }
+
+    #[test]
+    fn test_render_malformed_output_side_by_side() {
+        let renderer = PrettyMonochromeRenderer::new(PrettyColorRenderer {
+            max_surrounding_lines: 0,
+            diff_style: DiffStyle::SideBySide,
+            ..Default::default()
+        });
+        let testcase = TestCase {
+            title: "the title".to_string(),
+            shell_expression: "the command".to_string(),
+            expectations: vec![
+                test_expectation!("equal", "matched", false, false),
+                test_expectation!("equal", "unmatched", false, false),
+                test_expectation!("equal", "matched again", false, false),
+            ],
+            exit_code: None,
+            line_number: 234,
+            ..Default::default()
+        };
+        let rendered = renderer
+            .render(&[&Outcome {
+                location: None,
+                output: (
+                    "matched\nno match 1\nno match 2\nmatched again\n",
+                    "",
+                    Some(0),
+                )
+                    .into(),
+                testcase: testcase.clone(),
+                result: Err(TestCaseError::MalformedOutput(Diff::new(vec![
+                    DiffLine::MatchedExpectation {
+                        index: 0,
+                        expectation: testcase.expectations[0].clone(),
+                        lines: vec![(0, bformatln!("matched"))],
+                    },
+                    DiffLine::UnmatchedExpectation {
+                        index: 1,
+                        expectation: testcase.expectations[1].clone(),
+                    },
+                    DiffLine::UnexpectedLines {
+                        lines: vec![(1, bformatln!("no match 1")), (2, bformatln!("no match 2"))],
+                    },
+                    DiffLine::MatchedExpectation {
+                        index: 2,
+                        expectation: testcase.expectations[2].clone(),
+                        lines: vec![(3, bformatln!("matched again"))],
+                    },
+                ]))),
+                escaping: Escaper::default(),
+                format: ParserType::Markdown,
+            }])
+            .expect("render does not fail");
+        insta::assert_snapshot!(rendered);
+    }
+
+    #[test]
+    fn test_render_multiple_failures() {
+        let renderer = new_test_renderer();
+        let testcase = TestCase {
+            title: "the title".to_string(),
+            shell_expression: "the command".to_string(),
+            expectations: vec![test_expectation!("equal", "expected", false, false)],
+            exit_code: None,
+            line_number: 234,
+            ..Default::default()
+        };
+        let rendered = renderer
+            .render(&[&Outcome {
+                location: None,
+                output: ("actual\n", "", Some(123)).into(),
+                testcase: testcase.clone(),
+                result: Err(TestCaseError::MultipleFailures(vec![
+                    TestCaseError::MalformedOutput(Diff::new(vec![
+                        DiffLine::UnmatchedExpectation {
+                            index: 0,
+                            expectation: testcase.expectations[0].clone(),
+                        },
+                        DiffLine::UnexpectedLines {
+                            lines: vec![(0, bformatln!("actual"))],
+                        },
+                    ])),
+                    TestCaseError::InvalidExitCode {
+                        actual: 123,
+                        expected: 0.into(),
+                    },
+                ])),
+                escaping: Escaper::default(),
+                format: ParserType::Markdown,
+            }])
+            .expect("render does not fail");
+        insta::assert_snapshot!(rendered);
+    }
+
     #[test]
     fn test_render_malformed_output_multiple_lines() {
         let renderer = PrettyMonochromeRenderer::new(PrettyColorRenderer {
@@ -839,6 +1829,36 @@ mod tests {
                 rendered
             );
         }
+
+        // the configuration of the testcase takes precedence
+        for diff_context in [DiffContext::Lines(0), DiffContext::Full] {
+            let testcase = TestCase {
+                title: "the title".to_string(),
+                shell_expression: "the command".to_string(),
+                expectations: expectations.clone(),
+                exit_code: None,
+                line_number: 234,
+                config: TestCaseConfig {
+                    diff_context: Some(diff_context),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let rendered = new_test_renderer()
+                .render(&[&Outcome {
+                    location: None,
+                    output: ("matched\nno match 1\nno match 2\n", "", Some(123)).into(),
+                    testcase,
+                    result: Err(TestCaseError::MalformedOutput(Diff::new(diff.clone()))),
+                    escaping: Escaper::default(),
+                    format: ParserType::Markdown,
+                }])
+                .expect("render does not fail");
+            insta::assert_snapshot!(
+                format!("test_render_configured_diff_context={}", diff_context),
+                rendered
+            );
+        }
     }
 
     #[test]
@@ -984,6 +2004,7 @@ mod tests {
                     absolute_line_numbers: false,
                     summarize: false,
                     max_multiline_matched_lines: max_multiline_matched_lines as usize,
+                    ..Default::default()
                 });
                 let rendered = renderer
                     .render(&[&Outcome {
@@ -5,10 +5,14 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::time::Duration;
+
 use anyhow::Result;
 
 use crate::diff::Diff;
 use crate::outcome::Outcome;
+use crate::output::Output;
+use crate::testcase::ExpectedExitCode;
 use crate::testcase::TestCaseError;
 
 /// Renderer translate errors from validating [`crate::testcase::TestCase`]s into
@@ -24,11 +28,35 @@ pub(super) trait ErrorRenderer: Renderer {
         match err {
             TestCaseError::MalformedOutput(diff) => self.render_malformed_output(outcome, diff),
             TestCaseError::InvalidExitCode { actual, expected } => {
-                self.render_invalid_exit_code(outcome, *actual, *expected)
+                self.render_invalid_exit_code(outcome, *actual, expected)
             }
             TestCaseError::InternalError(err) => self.render_delegated_error(outcome, err),
             TestCaseError::Timeout => self.render_timeout(outcome),
             TestCaseError::Skipped => self.render_skipped(outcome),
+            TestCaseError::NonDeterministic(repeated) => {
+                self.render_non_deterministic(outcome, repeated)
+            }
+            TestCaseError::NoAssertions => self.render_no_assertions(outcome),
+            TestCaseError::ExceededMaxDuration {
+                duration,
+                max_duration,
+            } => self.render_exceeded_max_duration(outcome, *duration, *max_duration),
+            TestCaseError::ExceededMaxRss {
+                rss_bytes,
+                max_rss_bytes,
+            } => self.render_exceeded_max_rss(outcome, *rss_bytes, *max_rss_bytes),
+            TestCaseError::ExceededMaxOutputSize {
+                max_output_size_bytes,
+            } => self.render_exceeded_max_output_size(outcome, *max_output_size_bytes),
+            TestCaseError::InvalidUtf8Output { offset } => {
+                self.render_invalid_utf8_output(outcome, *offset)
+            }
+            TestCaseError::FailedAssertion { assertion, reason } => {
+                self.render_failed_assertion(outcome, assertion, reason)
+            }
+            TestCaseError::MultipleFailures(failures) => {
+                self.render_multiple_failures(outcome, failures)
+            }
         }
     }
 
@@ -36,7 +64,7 @@ pub(super) trait ErrorRenderer: Renderer {
         &self,
         outcome: &Outcome,
         actual: i32,
-        expected: i32,
+        expected: &ExpectedExitCode,
     ) -> Result<String>;
 
     fn render_delegated_error(&self, outcome: &Outcome, err: &anyhow::Error) -> Result<String>;
@@ -46,4 +74,43 @@ pub(super) trait ErrorRenderer: Renderer {
     fn render_timeout(&self, outcome: &Outcome) -> Result<String>;
 
     fn render_skipped(&self, outcome: &Outcome) -> Result<String>;
+
+    fn render_non_deterministic(&self, outcome: &Outcome, repeated: &Output) -> Result<String>;
+
+    fn render_no_assertions(&self, outcome: &Outcome) -> Result<String>;
+
+    fn render_exceeded_max_duration(
+        &self,
+        outcome: &Outcome,
+        duration: Duration,
+        max_duration: Duration,
+    ) -> Result<String>;
+
+    fn render_exceeded_max_rss(
+        &self,
+        outcome: &Outcome,
+        rss_bytes: u64,
+        max_rss_bytes: u64,
+    ) -> Result<String>;
+
+    fn render_exceeded_max_output_size(
+        &self,
+        outcome: &Outcome,
+        max_output_size_bytes: u64,
+    ) -> Result<String>;
+
+    fn render_invalid_utf8_output(&self, outcome: &Outcome, offset: usize) -> Result<String>;
+
+    fn render_failed_assertion(
+        &self,
+        outcome: &Outcome,
+        assertion: &str,
+        reason: &str,
+    ) -> Result<String>;
+
+    fn render_multiple_failures(
+        &self,
+        outcome: &Outcome,
+        failures: &[TestCaseError],
+    ) -> Result<String>;
 }
@@ -132,7 +132,7 @@ mod tests {
                 location: Some("the location 2".to_string()),
                 result: Err(TestCaseError::InvalidExitCode {
                     actual: 123,
-                    expected: 234,
+                    expected: 234.into(),
                 }),
                 escaping: Escaper::default(),
                 format: ParserType::Markdown,
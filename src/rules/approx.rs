@@ -0,0 +1,265 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::fmt::Display;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use lazy_static::lazy_static;
+use regex::Regex;
+use regex::bytes::Regex as ByteRegex;
+
+use super::modifiers::Modifiers;
+use super::rule::ArgumentRuleMaker;
+use super::rule::Rule;
+
+lazy_static! {
+    /// Numbers in the expression of the rule, which are compared with tolerance
+    static ref NUMBER: Regex = Regex::new(r"\d+(?:\.\d+)?").expect("number regex");
+}
+
+/// The pattern that matches a number in the output
+const NUMBER_PATTERN: &str = r"(\d+(?:\.\d+)?)";
+
+/// The tolerance of an [`ApproxRule`] without argument
+const DEFAULT_TOLERANCE: Tolerance = Tolerance::Relative(10.0);
+
+/// How much a number in the output may deviate from the expected number
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tolerance {
+    /// Deviation in percent of the expected number, written as `±10%`
+    Relative(f64),
+
+    /// Absolute deviation, written as `±0.5`
+    Absolute(f64),
+}
+
+impl Tolerance {
+    /// Whether the actual number is within the tolerance of the expected
+    pub fn allows(&self, expected: f64, actual: f64) -> bool {
+        let bound = match self {
+            Self::Relative(percent) => expected.abs() * percent / 100.0,
+            Self::Absolute(bound) => *bound,
+        };
+        // compensate for the imprecision of floating point arithmetic
+        (actual - expected).abs() - bound <= 1e-9
+    }
+}
+
+impl TryFrom<&str> for Tolerance {
+    type Error = anyhow::Error;
+
+    fn try_from(argument: &str) -> Result<Self> {
+        let value = argument
+            .strip_prefix('±')
+            .or_else(|| argument.strip_prefix("+-"))
+            .unwrap_or(argument)
+            .trim();
+        let (value, relative) = match value.strip_suffix('%') {
+            Some(value) => (value.trim(), true),
+            None => (value, false),
+        };
+        let value = value
+            .parse::<f64>()
+            .with_context(|| format!("tolerance `{argument}` is not like `±10%` or `±0.5`"))?;
+        if !value.is_finite() || value < 0.0 {
+            bail!("tolerance `{argument}` must be a positive number")
+        }
+        Ok(if relative {
+            Self::Relative(value)
+        } else {
+            Self::Absolute(value)
+        })
+    }
+}
+
+impl Display for Tolerance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Relative(percent) => write!(f, "±{percent}%"),
+            Self::Absolute(bound) => write!(f, "±{bound}"),
+        }
+    }
+}
+
+/// Match of lines that equal the expression, except for the numbers in them,
+/// which may deviate within a tolerance, as in `took 1.52s (approx ±10%)`
+#[derive(Clone, Debug)]
+pub struct ApproxRule {
+    expression: String,
+    tolerance: Option<Tolerance>,
+    regex: ByteRegex,
+    numbers: Vec<f64>,
+    modifiers: Modifiers,
+}
+
+impl Display for ApproxRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl PartialEq for ApproxRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.expression == other.expression
+            && self.tolerance == other.tolerance
+            && self.modifiers == other.modifiers
+    }
+}
+
+impl Rule for ApproxRule {
+    fn kind(&self) -> &'static str {
+        "approx"
+    }
+
+    fn matches(&self, line: &[u8]) -> bool {
+        let Some(captures) = self.regex.captures(self.modifiers.line(line)) else {
+            return false;
+        };
+        let tolerance = self.tolerance.unwrap_or(DEFAULT_TOLERANCE);
+        captures
+            .iter()
+            .skip(1)
+            .zip(&self.numbers)
+            .all(|(actual, expected)| {
+                actual
+                    .and_then(|actual| std::str::from_utf8(actual.as_bytes()).ok())
+                    .and_then(|actual| actual.parse::<f64>().ok())
+                    .is_some_and(|actual| tolerance.allows(*expected, actual))
+            })
+    }
+
+    /// The kind includes the tolerance, if it was given
+    fn unmake(&self) -> (String, Vec<u8>) {
+        let kind = self.modifiers.kind_with_modifiers(self.kind());
+        let kind = match self.tolerance {
+            Some(tolerance) => format!("{kind} {tolerance}"),
+            None => kind,
+        };
+        (kind, self.expression.as_bytes().to_vec())
+    }
+
+    fn with_modifiers(&self, modifiers: Modifiers) -> Result<Box<dyn Rule>> {
+        Ok(Box::new(Self::build(
+            &self.expression,
+            self.tolerance,
+            modifiers,
+        )?))
+    }
+}
+
+impl ApproxRule {
+    /// Create the rule, of which the regular expression captures each number
+    /// of the expression
+    fn build(expression: &str, tolerance: Option<Tolerance>, modifiers: Modifiers) -> Result<Self> {
+        let compared = if modifiers.trim {
+            expression.trim()
+        } else {
+            expression
+        };
+        let mut pattern = format!("{}^", modifiers.regex_flags());
+        let mut numbers = vec![];
+        let mut end = 0;
+        for number in NUMBER.find_iter(compared) {
+            pattern.push_str(&regex::escape(&compared[end..number.start()]));
+            pattern.push_str(NUMBER_PATTERN);
+            numbers.push(number.as_str().parse::<f64>()?);
+            end = number.end();
+        }
+        pattern.push_str(&regex::escape(&compared[end..]));
+        pattern.push('$');
+        Ok(Self {
+            expression: expression.to_string(),
+            tolerance,
+            regex: ByteRegex::new(&pattern)?,
+            numbers,
+            modifiers,
+        })
+    }
+}
+
+impl ArgumentRuleMaker for ApproxRule {
+    fn make_with_argument(argument: &str, expression: &str) -> Result<Box<dyn Rule>> {
+        let tolerance = if argument.is_empty() {
+            None
+        } else {
+            Some(Tolerance::try_from(argument)?)
+        };
+        Ok(Box::new(Self::build(
+            expression,
+            tolerance,
+            Modifiers::default(),
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ApproxRule;
+    use super::Tolerance;
+    use crate::lossy_string;
+    use crate::rules::rule::ArgumentRuleMaker;
+
+    #[test]
+    fn test_tolerance() {
+        for (argument, expected) in [
+            ("±10%", Tolerance::Relative(10.0)),
+            ("+-10%", Tolerance::Relative(10.0)),
+            ("2.5%", Tolerance::Relative(2.5)),
+            ("±0.5", Tolerance::Absolute(0.5)),
+            ("3", Tolerance::Absolute(3.0)),
+        ] {
+            assert_eq!(
+                expected,
+                Tolerance::try_from(argument).expect("parse tolerance"),
+                "{argument}"
+            );
+        }
+        for argument in ["±", "±-1%", "±ten"] {
+            Tolerance::try_from(argument).expect_err(argument);
+        }
+    }
+
+    #[test]
+    fn test_make_unmake() {
+        for (argument, expected_kind) in [("", "approx"), ("+-10%", "approx ±10%")] {
+            let rule =
+                ApproxRule::make_with_argument(argument, "took 1.5s").expect("rule is created");
+            let (kind, expression) = rule.unmake();
+            assert_eq!(expected_kind, kind);
+            assert_eq!("took 1.5s", lossy_string!(&expression));
+        }
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let tests = vec![
+            (true, "±10%", "took 1.52s", "took 1.52s"),
+            (true, "±10%", "took 1.52s", "took 1.6s"),
+            (true, "±10%", "took 1.52s", "took 1.4s"),
+            (false, "±10%", "took 1.52s", "took 1.7s"),
+            (false, "±10%", "took 1.52s", "took 1.52 s"),
+            (false, "±10%", "took 1.52s", "took 1.52ms"),
+            (true, "", "took 100ms", "took 110ms"),
+            (false, "", "took 100ms", "took 111ms"),
+            (true, "±0.5", "3 files, 2.0 MB", "3 files, 2.5 MB"),
+            (false, "±0.5", "3 files, 2.0 MB", "4 files, 2.0 MB"),
+            (true, "±1", "[a.b]", "[a.b]"),
+            (false, "±1", "[a.b]", "[axb]"),
+        ];
+        for (expect, argument, expression, line) in tests {
+            let rule = ApproxRule::make_with_argument(argument, expression)
+                .unwrap_or_else(|_| panic!("create rule from `{expression}`"));
+            assert_eq!(
+                expect,
+                rule.matches(format!("{line}\n").as_bytes()),
+                "`{expression} (approx {argument})` matches `{line}`"
+            );
+        }
+    }
+}
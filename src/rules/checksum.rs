@@ -0,0 +1,186 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::fmt::Display;
+use std::fmt::Write;
+use std::path::Path;
+
+use anyhow::Result;
+use anyhow::bail;
+use sha2::Digest;
+use sha2::Sha256;
+
+use super::rule::Rule;
+use super::rule::RuleMaker;
+use crate::escaping::Escaper;
+
+/// Returns the hex encoded SHA-256 digest of the given data
+pub fn sha256_hex(data: &[u8]) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+/// Returns the hex encoded SHA-256 digest of the contents of the file, or
+/// `None` if it cannot be read
+pub fn sha256_file_hex(path: &Path) -> Option<String> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).ok()?;
+    Some(to_hex(&hasher.finalize()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+/// Checksum match of either all remaining output, which is written as
+/// `(sha256:<hex>)`, or of a file that the execution produced, which is written
+/// as `<path> (sha256:<hex>)`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sha256Rule {
+    path: Option<String>,
+    digest: String,
+}
+
+impl Display for Sha256Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.path {
+            Some(ref path) => write!(f, "{path} (sha256:{})", self.digest),
+            None => write!(f, "(sha256:{})", self.digest),
+        }
+    }
+}
+
+impl Rule for Sha256Rule {
+    fn kind(&self) -> &'static str {
+        "sha256"
+    }
+
+    /// Whether the digest of the given (remaining) output equals the digest
+    fn matches(&self, line: &[u8]) -> bool {
+        self.path.is_none() && sha256_hex(line) == self.digest
+    }
+
+    fn unmake(&self) -> (String, Vec<u8>) {
+        let expression = match self.path {
+            Some(ref path) => format!("{path} {}", self.digest),
+            None => self.digest.clone(),
+        };
+        (self.kind().to_string(), expression.into_bytes())
+    }
+
+    fn to_expression_string(&self, _quantifier: &str, _: &Escaper) -> String {
+        self.to_string()
+    }
+
+    fn matches_remaining_output(&self) -> bool {
+        self.path.is_none()
+    }
+
+    fn updated_expression(&self, output: &[u8]) -> Option<String> {
+        self.path
+            .is_none()
+            .then(|| format!("(sha256:{})", sha256_hex(output)))
+    }
+
+    fn file_checksum(&self) -> Option<(&str, &str)> {
+        self.path
+            .as_ref()
+            .map(|path| (path.as_str(), self.digest.as_str()))
+    }
+}
+
+impl RuleMaker for Sha256Rule {
+    /// Creates the rule from the hex encoded digest, that is optionally
+    /// preceded by the path of a file and a space
+    fn make(expression: &str) -> Result<Box<dyn Rule>> {
+        let (path, digest) = match expression.rsplit_once(' ') {
+            Some((path, digest)) => (Some(path.trim().to_string()), digest),
+            None => (None, expression),
+        };
+        if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!("`{digest}` is not a hex encoded SHA-256 digest");
+        }
+        if path.as_ref().is_some_and(|path| path.is_empty()) {
+            bail!("missing path of file to compute SHA-256 digest of");
+        }
+        Ok(Box::new(Sha256Rule {
+            path,
+            digest: digest.to_lowercase(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sha256Rule;
+    use super::sha256_hex;
+    use crate::escaping::Escaper;
+    use crate::lossy_string;
+    use crate::rules::rule::RuleMaker;
+
+    const HELLO: &str = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(HELLO, sha256_hex(b"hello\n"));
+    }
+
+    #[test]
+    fn test_make_unmake() {
+        let rule = Sha256Rule::make(HELLO).expect("rule is created");
+        let (kind, expression) = rule.unmake();
+        assert_eq!("sha256", kind);
+        assert_eq!(HELLO, lossy_string!(&expression));
+        assert_eq!(None, rule.file_checksum());
+        assert!(rule.matches_remaining_output());
+
+        let rule = Sha256Rule::make(&format!("out/some file.bin {}", HELLO.to_uppercase()))
+            .expect("rule with path is created");
+        let (_, expression) = rule.unmake();
+        assert_eq!(
+            format!("out/some file.bin {HELLO}"),
+            lossy_string!(&expression)
+        );
+        assert_eq!(Some(("out/some file.bin", HELLO)), rule.file_checksum());
+        assert!(!rule.matches_remaining_output());
+
+        assert!(Sha256Rule::make("abc").is_err(), "too short digest");
+        assert!(
+            Sha256Rule::make(&HELLO.replace('5', "x")).is_err(),
+            "not hex encoded"
+        );
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let rule = Sha256Rule::make(HELLO).expect("rule is created");
+        assert!(rule.matches(b"hello\n"));
+        assert!(!rule.matches(b"hello"));
+
+        let rule = Sha256Rule::make(&format!("file {HELLO}")).expect("rule is created");
+        assert!(!rule.matches(b"hello\n"), "file rules do not match output");
+    }
+
+    #[test]
+    fn test_to_expression_string() {
+        let rule = Sha256Rule::make(HELLO).expect("rule is created");
+        assert_eq!(
+            format!("(sha256:{HELLO})"),
+            rule.to_expression_string("", &Escaper::default())
+        );
+        let rule = Sha256Rule::make(&format!("file.bin {HELLO}")).expect("rule is created");
+        assert_eq!(
+            format!("file.bin (sha256:{HELLO})"),
+            rule.to_expression_string("", &Escaper::default())
+        );
+    }
+}
@@ -0,0 +1,175 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use anyhow::Result;
+use tracing::warn;
+
+use super::rule::Rule;
+
+/// Match of lines by an external command, that is registered as a custom kind
+/// in the `rules` of the document configuration, as in `foo (mycheck)`. The
+/// command receives the line of output on STDIN and the expression as its
+/// argument, and the line matches if the command exits with code 0.
+#[derive(Clone, Debug)]
+pub struct CommandRule {
+    name: String,
+    command: String,
+    shell: PathBuf,
+    expression: String,
+
+    /// The directory that the command is run in, once the rule was resolved
+    /// against the directory of the test document
+    directory: Option<PathBuf>,
+
+    /// Whether the command matched a line, by the line, because diffing may
+    /// ask for the same line multiple times
+    matched: Arc<Mutex<HashMap<Vec<u8>, bool>>>,
+}
+
+impl CommandRule {
+    /// Creates a rule of the given custom kind, which runs the command in
+    /// the given shell
+    pub fn new(name: &str, command: &str, shell: &Path, expression: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            command: command.to_string(),
+            shell: shell.to_path_buf(),
+            expression: expression.to_string(),
+            directory: None,
+            matched: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Runs the command with the given line on STDIN, and returns whether it
+    /// succeeded
+    fn run(&self, line: &[u8]) -> Result<bool> {
+        let mut command = Command::new(&self.shell);
+        command
+            .arg("-c")
+            .arg(format!("{} \"$@\"", self.command))
+            .arg(&self.name)
+            .arg(&self.expression)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        if let Some(ref directory) = self.directory {
+            command.current_dir(directory);
+        }
+        let mut child = command.spawn().context("execute command")?;
+
+        // the command may not read its input, which is fine
+        let mut stdin = child.stdin.take().context("open STDIN of command")?;
+        match stdin.write_all(line) {
+            Err(err) if err.kind() != ErrorKind::BrokenPipe => {
+                return Err(err).context("write line to command");
+            }
+            _ => drop(stdin),
+        }
+        Ok(child.wait().context("wait for command")?.success())
+    }
+}
+
+impl Rule for CommandRule {
+    fn kind(&self) -> &'static str {
+        "command"
+    }
+
+    fn matches(&self, line: &[u8]) -> bool {
+        let mut matched = self
+            .matched
+            .lock()
+            .expect("custom rule cache must not be poisoned");
+        if let Some(matches) = matched.get(line) {
+            return *matches;
+        }
+        let matches = self.run(line).unwrap_or_else(|err| {
+            warn!(rule = %self.name, command = %self.command, "custom rule failed: {err:#}");
+            false
+        });
+        matched.insert(line.to_vec(), matches);
+        matches
+    }
+
+    fn unmake(&self) -> (String, Vec<u8>) {
+        (self.name.clone(), self.expression.as_bytes().to_vec())
+    }
+
+    fn with_document_directory(&self, directory: &Path) -> Option<Box<dyn Rule>> {
+        Some(Box::new(CommandRule {
+            directory: Some(directory)
+                .filter(|directory| !directory.as_os_str().is_empty())
+                .map(Path::to_path_buf),
+            matched: Arc::new(Mutex::new(HashMap::new())),
+            ..self.clone()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::CommandRule;
+    use crate::escaping::Escaper;
+    use crate::rules::rule::Rule;
+
+    #[test]
+    fn test_make_unmake() {
+        let rule = CommandRule::new("mycheck", "true", Path::new("sh"), "foo bar");
+        let (kind, expression) = rule.unmake();
+        assert_eq!("mycheck", kind);
+        assert_eq!(b"foo bar".to_vec(), expression);
+        assert_eq!(
+            "foo bar (mycheck+)",
+            rule.to_expression_string("+", &Escaper::default())
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_rule_matches() {
+        let rule = CommandRule::new(
+            "length",
+            r#"f() { read -r line; test "${#line}" -eq "$1"; }; f"#,
+            Path::new("sh"),
+            "3",
+        );
+        assert!(rule.matches(b"foo\n"));
+        assert!(!rule.matches(b"fooo\n"));
+        assert!(!rule.matches(b""));
+
+        let rule = CommandRule::new("missing", "./does-not-exist", Path::new("sh"), "");
+        assert!(!rule.matches(b"foo\n"));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_rule_runs_in_document_directory() {
+        let directory = tempfile::tempdir().expect("create temporary directory");
+        std::fs::write(directory.path().join("check.sh"), "grep -qx \"$1\"\n").expect("write");
+
+        let rule = CommandRule::new("check", "sh ./check.sh", Path::new("sh"), "foo");
+        assert!(!rule.matches(b"foo\n"), "script is not found");
+
+        let rule = rule
+            .with_document_directory(directory.path())
+            .expect("directory is resolved");
+        assert!(rule.matches(b"foo\n"));
+        assert!(!rule.matches(b"bar\n"));
+    }
+}
@@ -11,13 +11,15 @@ use std::fmt::Display;
 use anyhow::Result;
 use serde::Serialize;
 
+use super::modifiers::Modifiers;
 use super::rule::Rule;
 use super::rule::RuleMaker;
+use crate::lossy_string;
 use crate::newline::BytesNewline;
 
 /// Simple equality match for lines that end in a new-line character
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
-pub struct EqualRule(String);
+pub struct EqualRule(String, #[serde(skip)] Modifiers);
 
 impl Display for EqualRule {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -31,17 +33,29 @@ impl Rule for EqualRule {
     }
 
     fn matches(&self, line: &[u8]) -> bool {
-        self.0.as_bytes().assure_newline() == line
+        if self.1.is_empty() {
+            return self.0.as_bytes().assure_newline() == line;
+        }
+        let content = line.trim_newlines();
+        content.len() < line.len()
+            && self.1.normalize(&lossy_string!(content)) == self.1.normalize(&self.0)
     }
 
     fn unmake(&self) -> (String, Vec<u8>) {
-        (self.kind().to_string(), self.0.as_bytes().to_vec())
+        (
+            self.1.kind_with_modifiers(self.kind()),
+            self.0.as_bytes().to_vec(),
+        )
+    }
+
+    fn with_modifiers(&self, modifiers: Modifiers) -> Result<Box<dyn Rule>> {
+        Ok(Box::new(EqualRule(self.0.clone(), modifiers)))
     }
 }
 
 impl RuleMaker for EqualRule {
     fn make(expression: &str) -> Result<Box<dyn Rule>> {
-        Ok(Box::new(EqualRule(expression.into())))
+        Ok(Box::new(EqualRule(expression.into(), Modifiers::default())))
     }
 }
 
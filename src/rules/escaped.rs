@@ -9,13 +9,14 @@ use anyhow::Result;
 use serde::Serialize;
 
 use super::escaped_filter::apply_escaped_filter_bytes;
+use super::modifiers::Modifiers;
 use super::rule::Rule;
 use super::rule::RuleMaker;
-use crate::newline::BytesNewline;
+use crate::lossy_string;
 
 /// Simple equality match for lines that end in a new-line character
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
-pub struct EscapedRule(String, Vec<u8>);
+pub struct EscapedRule(String, Vec<u8>, #[serde(skip)] Modifiers);
 
 impl Rule for EscapedRule {
     fn kind(&self) -> &'static str {
@@ -23,11 +24,28 @@ impl Rule for EscapedRule {
     }
 
     fn matches(&self, line: &[u8]) -> bool {
-        &self.1[..] == line.trim_newlines()
+        let line = self.2.line(line);
+        let expected = if self.2.trim {
+            self.1.trim_ascii()
+        } else {
+            &self.1[..]
+        };
+        if !self.2.ignore_case {
+            return expected == line;
+        }
+        self.2.normalize(&lossy_string!(expected)) == self.2.normalize(&lossy_string!(line))
     }
 
     fn unmake(&self) -> (String, Vec<u8>) {
-        (self.kind().to_string(), self.1.to_owned())
+        (self.2.kind_with_modifiers(self.kind()), self.1.to_owned())
+    }
+
+    fn with_modifiers(&self, modifiers: Modifiers) -> Result<Box<dyn Rule>> {
+        Ok(Box::new(EscapedRule(
+            self.0.clone(),
+            self.1.clone(),
+            modifiers,
+        )))
     }
 }
 
@@ -42,7 +60,11 @@ impl RuleMaker for EscapedRule {
         };
 
         let bytes = apply_escaped_filter_bytes(expression)?;
-        Ok(Box::new(EscapedRule(expression.to_string(), bytes)))
+        Ok(Box::new(EscapedRule(
+            expression.to_string(),
+            bytes,
+            Modifiers::default(),
+        )))
     }
 }
 
@@ -0,0 +1,131 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::fmt::Display;
+use std::path::Path;
+
+use anyhow::Result;
+use anyhow::bail;
+
+use super::rule::Rule;
+use super::rule::RuleMaker;
+use crate::escaping::Escaper;
+
+/// Match of all remaining output with the contents of a golden file, which is
+/// written as `<path> (file)`, with the path relative to the test document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileRule {
+    path: String,
+
+    /// The contents of the golden file, once it was loaded from the directory
+    /// of the test document, or `None` if it does not exist
+    contents: Option<Vec<u8>>,
+}
+
+impl Display for FileRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (file)", self.path)
+    }
+}
+
+impl Rule for FileRule {
+    fn kind(&self) -> &'static str {
+        "file"
+    }
+
+    /// Whether the (remaining) output equals the contents of the golden file
+    fn matches(&self, output: &[u8]) -> bool {
+        self.contents.as_deref() == Some(output)
+    }
+
+    fn unmake(&self) -> (String, Vec<u8>) {
+        (self.kind().to_string(), self.path.as_bytes().to_vec())
+    }
+
+    fn to_expression_string(&self, _quantifier: &str, _: &Escaper) -> String {
+        self.to_string()
+    }
+
+    fn matches_remaining_output(&self) -> bool {
+        true
+    }
+
+    /// The expression is kept, because the golden file is updated instead
+    fn updated_expression(&self, _output: &[u8]) -> Option<String> {
+        Some(self.to_string())
+    }
+
+    fn golden_file(&self) -> Option<&str> {
+        Some(&self.path)
+    }
+
+    fn with_document_directory(&self, directory: &Path) -> Option<Box<dyn Rule>> {
+        Some(Box::new(FileRule {
+            path: self.path.clone(),
+            contents: std::fs::read(directory.join(&self.path)).ok(),
+        }))
+    }
+}
+
+impl RuleMaker for FileRule {
+    /// Creates the rule from the path of the golden file
+    fn make(expression: &str) -> Result<Box<dyn Rule>> {
+        let path = expression.trim();
+        if path.is_empty() {
+            bail!("missing path of golden file to compare output with");
+        }
+        Ok(Box::new(FileRule {
+            path: path.to_string(),
+            contents: None,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileRule;
+    use crate::escaping::Escaper;
+    use crate::lossy_string;
+    use crate::rules::rule::RuleMaker;
+
+    #[test]
+    fn test_make_unmake() {
+        let rule = FileRule::make("golden/out.txt").expect("rule is created");
+        let (kind, expression) = rule.unmake();
+        assert_eq!("file", kind);
+        assert_eq!("golden/out.txt", lossy_string!(&expression));
+        assert_eq!(Some("golden/out.txt"), rule.golden_file());
+        assert!(rule.matches_remaining_output());
+        assert_eq!(
+            "golden/out.txt (file)",
+            rule.to_expression_string("", &Escaper::default())
+        );
+        FileRule::make(" ").expect_err("path is missing");
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let directory = tempfile::tempdir().expect("create temporary directory");
+        std::fs::write(directory.path().join("out.txt"), "hello\nworld\n").expect("write file");
+
+        let rule = FileRule::make("out.txt").expect("rule is created");
+        assert!(!rule.matches(b"hello\nworld\n"), "file is not loaded");
+
+        let rule = rule
+            .with_document_directory(directory.path())
+            .expect("file is loaded");
+        assert!(rule.matches(b"hello\nworld\n"));
+        assert!(!rule.matches(b"hello\nworld"));
+        assert!(!rule.matches(b"hello\n"));
+
+        let rule = FileRule::make("missing.txt")
+            .expect("rule is created")
+            .with_document_directory(directory.path())
+            .expect("missing file is loaded");
+        assert!(!rule.matches(b""));
+    }
+}
@@ -21,14 +21,14 @@ use wildmatch::WildMatch;
 
 use super::escaped_filter::apply_escaped_filter_utf8;
 use super::escaped_filter::expression_as_escaped;
+use super::modifiers::Modifiers;
 use super::rule::Rule;
 use super::rule::RuleMaker;
 use crate::lossy_string;
-use crate::newline::BytesNewline;
 
 /// Simple equality match for lines that end in a new-line character
 #[derive(Clone, Debug, PartialEq)]
-pub struct GlobRule(WildMatch);
+pub struct GlobRule(WildMatch, Modifiers);
 
 impl Display for GlobRule {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -42,15 +42,30 @@ impl Rule for GlobRule {
     }
 
     fn matches(&self, line: &[u8]) -> bool {
-        self.0.matches(&lossy_string!(line.trim_newlines()))
+        self.0.matches(&lossy_string!(self.1.line(line)))
     }
 
     fn unmake(&self) -> (String, Vec<u8>) {
         (
-            self.kind().to_string(),
+            self.1.kind_with_modifiers(self.kind()),
             self.0.to_string().as_bytes().to_vec(),
         )
     }
+
+    fn with_modifiers(&self, modifiers: Modifiers) -> Result<Box<dyn Rule>> {
+        let pattern = self.0.to_string();
+        let pattern = if modifiers.trim {
+            pattern.trim()
+        } else {
+            &pattern
+        };
+        let wildmatch = if modifiers.ignore_case {
+            WildMatch::new_case_insensitive(pattern)
+        } else {
+            WildMatch::new(pattern)
+        };
+        Ok(Box::new(Self(wildmatch, modifiers)))
+    }
 }
 
 impl RuleMaker for GlobRule {
@@ -60,7 +75,10 @@ impl RuleMaker for GlobRule {
         } else {
             expression.to_string()
         };
-        Ok(Box::new(Self(WildMatch::new(&expression))))
+        Ok(Box::new(Self(
+            WildMatch::new(&expression),
+            Modifiers::default(),
+        )))
     }
 }
 
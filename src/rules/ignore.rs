@@ -0,0 +1,77 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::fmt::Display;
+
+use anyhow::Result;
+use anyhow::bail;
+
+use super::rule::Rule;
+use super::rule::RuleMaker;
+use crate::escaping::Escaper;
+use crate::expectation::IGNORE_REMAINING;
+
+/// Match of all remaining output, whatever it is, which is written as a lone
+/// `...` line
+#[derive(Clone, Debug, PartialEq)]
+pub struct IgnoreRule;
+
+impl Display for IgnoreRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{IGNORE_REMAINING}")
+    }
+}
+
+impl Rule for IgnoreRule {
+    fn kind(&self) -> &'static str {
+        "ignore"
+    }
+
+    fn matches(&self, _output: &[u8]) -> bool {
+        true
+    }
+
+    fn unmake(&self) -> (String, Vec<u8>) {
+        (self.kind().to_string(), vec![])
+    }
+
+    fn to_expression_string(&self, _quantifier: &str, _: &Escaper) -> String {
+        self.to_string()
+    }
+
+    fn matches_remaining_output(&self) -> bool {
+        true
+    }
+}
+
+impl RuleMaker for IgnoreRule {
+    fn make(expression: &str) -> Result<Box<dyn Rule>> {
+        if !expression.is_empty() {
+            bail!(
+                "remaining output is ignored with a lone `{IGNORE_REMAINING}` line, not with `{expression}`"
+            )
+        }
+        Ok(Box::new(IgnoreRule))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IgnoreRule;
+    use crate::escaping::Escaper;
+    use crate::rules::rule::RuleMaker;
+
+    #[test]
+    fn test_rule_matches() {
+        let rule = IgnoreRule::make("").expect("rule is created");
+        assert!(rule.matches_remaining_output());
+        assert!(rule.matches(b""));
+        assert!(rule.matches(b"any\nremaining\noutput"));
+        assert_eq!("...", rule.to_expression_string("", &Escaper::default()));
+        IgnoreRule::make("foo").expect_err("expression must be empty");
+    }
+}
@@ -0,0 +1,268 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! The JSON rule compares all remaining output structurally with a JSON
+//! document, so that neither the order of keys in objects nor whitespace are
+//! relevant. The document is written on the line that ends in `(json)` and
+//! continues on the following lines until it is complete:
+//!
+//! ```txt
+//! { (json)
+//!   "name": "foo",
+//!   "created": ...,
+//!   "tags": ["a", "b"]
+//! }
+//! ```
+//!
+//! A `...` in place of a value matches any value, a `...` in place of a member
+//! of an object allows the object to have any further members.
+
+use std::fmt::Display;
+
+use anyhow::Result;
+use anyhow::anyhow;
+use serde_json::Value;
+
+use super::rule::IncompleteExpression;
+use super::rule::Rule;
+use super::rule::RuleMaker;
+use super::structured::expression_with_kind;
+use super::structured::matches_value;
+use crate::escaping::Escaper;
+
+/// JSON encoding of [`super::structured::IGNORED`]
+const IGNORED_JSON: &[u8] = br#""\u0000...""#;
+
+/// Structural match of all remaining output with a JSON document
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonRule {
+    expression: String,
+    expected: Value,
+}
+
+impl Display for JsonRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl Rule for JsonRule {
+    fn kind(&self) -> &'static str {
+        "json"
+    }
+
+    /// Whether the given (remaining) output is a JSON document that matches
+    fn matches(&self, line: &[u8]) -> bool {
+        serde_json::from_slice::<Value>(line)
+            .is_ok_and(|actual| matches_value(&self.expected, &actual))
+    }
+
+    fn unmake(&self) -> (String, Vec<u8>) {
+        (self.kind().to_string(), self.expression.as_bytes().to_vec())
+    }
+
+    /// Renders the document with the kind at the end of its first line
+    fn to_expression_string(&self, _quantifier: &str, _: &Escaper) -> String {
+        expression_with_kind(&self.expression, self.kind())
+    }
+
+    fn matches_remaining_output(&self) -> bool {
+        true
+    }
+
+    /// Renders the output as pretty printed document, if it is JSON
+    fn updated_expression(&self, output: &[u8]) -> Option<String> {
+        let actual = serde_json::from_slice::<Value>(output).ok()?;
+        let expression = serde_json::to_string_pretty(&actual).ok()?;
+        Some(expression_with_kind(&expression, self.kind()))
+    }
+}
+
+impl RuleMaker for JsonRule {
+    /// Creates the rule from a JSON document, that may contain `...`
+    /// placeholders. Returns an [`IncompleteExpression`] error if the
+    /// document ends before it is complete.
+    fn make(expression: &str) -> Result<Box<dyn Rule>> {
+        let expected = serde_json::from_slice(&with_placeholders(expression)).map_err(|err| {
+            if err.is_eof() {
+                anyhow::Error::new(IncompleteExpression(format!(
+                    "JSON document ends before it is complete: {err}"
+                )))
+            } else {
+                anyhow!("invalid JSON document: {err}")
+            }
+        })?;
+        Ok(Box::new(Self {
+            expression: expression.to_string(),
+            expected,
+        }))
+    }
+}
+
+/// Replaces the `...` placeholders outside of strings with the
+/// [`super::structured::IGNORED`] value, or with a member of that name within
+/// objects
+fn with_placeholders(expression: &str) -> Vec<u8> {
+    let bytes = expression.as_bytes();
+    let mut replaced = Vec::with_capacity(bytes.len());
+    let mut containers = vec![];
+    let mut expects_key = false;
+    let (mut in_string, mut escaped) = (false, false);
+    let mut index = 0;
+    while index < bytes.len() {
+        let byte = bytes[index];
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+        } else {
+            match byte {
+                b'"' => in_string = true,
+                b'{' | b'[' => {
+                    containers.push(byte);
+                    expects_key = byte == b'{';
+                }
+                b'}' | b']' => {
+                    containers.pop();
+                    expects_key = false;
+                }
+                b',' => expects_key = containers.last() == Some(&b'{'),
+                b':' => expects_key = false,
+                b'.' if bytes[index..].starts_with(b"...") => {
+                    replaced.extend_from_slice(IGNORED_JSON);
+                    if expects_key {
+                        replaced.extend_from_slice(b":null");
+                    }
+                    index += 3;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        replaced.push(byte);
+        index += 1;
+    }
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsonRule;
+    use super::with_placeholders;
+    use crate::escaping::Escaper;
+    use crate::lossy_string;
+    use crate::rules::rule::IncompleteExpression;
+    use crate::rules::rule::RuleMaker;
+
+    #[test]
+    fn test_make_unmake() {
+        let expression = "{\n  \"foo\": [1, ...]\n}";
+        let rule = JsonRule::make(expression).expect("rule is created");
+        let (kind, unmade) = rule.unmake();
+        assert_eq!("json", kind);
+        assert_eq!(expression, lossy_string!(&unmade));
+        assert!(rule.matches_remaining_output());
+    }
+
+    #[test]
+    fn test_make_incomplete_or_invalid() {
+        let err = JsonRule::make("{\n  \"foo\": [1,").expect_err("document is incomplete");
+        assert!(err.downcast_ref::<IncompleteExpression>().is_some());
+        let err = JsonRule::make("{\"foo\" 1}").expect_err("document is invalid");
+        assert!(err.downcast_ref::<IncompleteExpression>().is_none());
+    }
+
+    #[test]
+    fn test_with_placeholders() {
+        let tests = [
+            ("[1, ...]", r#"[1, "\u0000..."]"#),
+            (r#"{"a": ...}"#, r#"{"a": "\u0000..."}"#),
+            (r#"{"a": 1, ...}"#, r#"{"a": 1, "\u0000...":null}"#),
+            (r#"{...}"#, r#"{"\u0000...":null}"#),
+            (
+                r#"{"a": [...], ...}"#,
+                r#"{"a": ["\u0000..."], "\u0000...":null}"#,
+            ),
+            (r#"{"a...": "...\"..."}"#, r#"{"a...": "...\"..."}"#),
+        ];
+        for (expression, expect) in tests {
+            assert_eq!(
+                expect,
+                lossy_string!(&with_placeholders(expression)),
+                "from {expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let tests = [
+            (
+                true,
+                r#"{"a": 1, "b": [true, null]}"#,
+                "{\n  \"b\": [true, null],\n  \"a\": 1\n}\n",
+            ),
+            (
+                false,
+                r#"{"a": 1, "b": [true, null]}"#,
+                r#"{"a": 1, "b": [null, true]}"#,
+            ),
+            (false, r#"{"a": 1}"#, r#"{"a": 1, "b": 2}"#),
+            (false, r#"{"a": 1, "b": 2}"#, r#"{"a": 1}"#),
+            (true, r#"{"a": 1, ...}"#, r#"{"a": 1, "b": 2}"#),
+            (false, r#"{"a": 1, ...}"#, r#"{"a": 2, "b": 2}"#),
+            (
+                true,
+                r#"{"a": ..., "b": 2}"#,
+                r#"{"a": {"c": [1]}, "b": 2}"#,
+            ),
+            (false, r#"{"a": ..., "b": 2}"#, r#"{"b": 2}"#),
+            (true, r#"[1, ..., 3]"#, r#"[1, "two", 3]"#),
+            (false, r#"[1, ...]"#, r#"[1, 2, 3]"#),
+            (true, r#"{"a": 1.0}"#, r#"{"a": 1}"#),
+            (false, r#"{"a": "..."}"#, r#"{"a": "b"}"#),
+            (true, r#""foo""#, "\"foo\"\n"),
+            (false, r#"{"a": 1}"#, "{\"a\": 1}\n{\"a\": 1}\n"),
+            (false, r#"{"a": 1}"#, "not json\n"),
+        ];
+        for (expect, expression, output) in tests {
+            let rule = JsonRule::make(expression)
+                .unwrap_or_else(|err| panic!("create rule from {expression}: {err}"));
+            assert_eq!(
+                expect,
+                rule.matches(output.as_bytes()),
+                "{expression} matching {output:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_to_expression_string() {
+        let tests = [
+            (r#"{"a": 1}"#, r#"{"a": 1} (json)"#),
+            ("{\n  \"a\": 1\n}", "{ (json)\n  \"a\": 1\n}"),
+        ];
+        for (expression, expect) in tests {
+            let rule = JsonRule::make(expression).expect("rule is created");
+            assert_eq!(expect, rule.to_expression_string("", &Escaper::default()));
+        }
+    }
+
+    #[test]
+    fn test_updated_expression() {
+        let rule = JsonRule::make(r#"{"a": ...}"#).expect("rule is created");
+        assert_eq!(
+            Some("{ (json)\n  \"a\": [\n    1\n  ]\n}".to_string()),
+            rule.updated_expression(b"{\"a\":[1]}\n")
+        );
+        assert_eq!(None, rule.updated_expression(b"not json\n"));
+    }
+}
@@ -5,13 +5,26 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+pub mod approx;
+pub mod checksum;
+pub mod command;
 pub mod equal;
 pub mod escaped;
 pub mod escaped_filter;
+pub mod file;
 pub mod glob;
 pub mod glob_cram;
+pub mod ignore;
+pub mod json;
+pub mod modifiers;
 pub mod no_eol;
+pub mod not;
 pub mod prelude;
 pub mod regex;
 pub mod registry;
 pub mod rule;
+pub mod structured;
+pub mod template;
+pub mod toml;
+pub mod variables;
+pub mod yaml;
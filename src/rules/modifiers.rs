@@ -0,0 +1,137 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::bail;
+
+use crate::newline::BytesNewline;
+
+/// Name of the modifier that makes comparisons case-insensitive
+pub const IGNORE_CASE: &str = "i";
+
+/// Name of the modifier that ignores leading and trailing whitespace
+pub const TRIM: &str = "trim";
+
+/// Modifiers that change how a [`super::rule::Rule`] compares lines, which
+/// follow the kind of the expectation, each prefixed with a colon, as in
+/// `(equal:i:trim)`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    /// Whether letters are compared case-insensitive
+    pub ignore_case: bool,
+
+    /// Whether leading and trailing whitespace of lines is ignored
+    pub trim: bool,
+}
+
+impl Modifiers {
+    /// Whether no modifier is set
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The inline flags, that a regular expression must start with to
+    /// apply the modifiers
+    pub fn regex_flags(&self) -> &'static str {
+        if self.ignore_case { "(?i)" } else { "" }
+    }
+
+    /// Returns the line without its newline and, if trimmed, without its
+    /// leading and trailing whitespace
+    pub fn line<'a>(&self, line: &'a [u8]) -> &'a [u8] {
+        let line = &line[..line.trim_newlines().len()];
+        if self.trim { line.trim_ascii() } else { line }
+    }
+
+    /// Returns the text, as it is compared: trimmed and in lowercase, if
+    /// the modifiers say so
+    pub fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let text = if self.trim { text.trim() } else { text };
+        if self.ignore_case {
+            Cow::Owned(text.to_lowercase())
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+
+    /// Returns the kind, followed by the modifiers
+    pub fn kind_with_modifiers(&self, kind: &str) -> String {
+        format!("{kind}{self}")
+    }
+}
+
+impl FromStr for Modifiers {
+    type Err = anyhow::Error;
+
+    /// Parse modifiers from their colon separated names, like `i:trim`
+    fn from_str(modifiers: &str) -> anyhow::Result<Self> {
+        let mut parsed = Self::default();
+        for name in modifiers.split(':') {
+            match name {
+                IGNORE_CASE => parsed.ignore_case = true,
+                TRIM => parsed.trim = true,
+                _ => bail!("unknown modifier `:{name}`, supported are: :{IGNORE_CASE}, :{TRIM}"),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+impl Display for Modifiers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.ignore_case {
+            write!(f, ":{IGNORE_CASE}")?;
+        }
+        if self.trim {
+            write!(f, ":{TRIM}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Modifiers;
+
+    #[test]
+    fn test_parse_display() {
+        for (modifiers, expected) in [
+            ("i", ":i"),
+            ("trim", ":trim"),
+            ("i:trim", ":i:trim"),
+            ("trim:i", ":i:trim"),
+        ] {
+            assert_eq!(
+                expected,
+                modifiers
+                    .parse::<Modifiers>()
+                    .expect("parse modifiers")
+                    .to_string()
+            );
+        }
+        for modifiers in ["", "x", "i:", "I"] {
+            modifiers.parse::<Modifiers>().expect_err(modifiers);
+        }
+    }
+
+    #[test]
+    fn test_line_and_normalize() {
+        let modifiers = Modifiers {
+            ignore_case: true,
+            trim: true,
+        };
+        assert_eq!(b"Foo  Bar", modifiers.line(b"  Foo  Bar \t\n"));
+        assert_eq!(b"  Foo", Modifiers::default().line(b"  Foo\n"));
+        assert_eq!("foo  bar", modifiers.normalize("  Foo  BAR "));
+        assert_eq!(" Foo ", Modifiers::default().normalize(" Foo "));
+        assert_eq!("(?i)", modifiers.regex_flags());
+        assert_eq!("", Modifiers::default().regex_flags());
+    }
+}
@@ -0,0 +1,111 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::fmt::Display;
+
+use anyhow::Result;
+use regex::bytes::Regex as ByteRegex;
+
+use super::modifiers::Modifiers;
+use super::regex::cleanup_unrecognized_escape_sequences;
+use super::regex::escape_misused_character_class;
+use super::regex::escape_misused_repetition_quantifier;
+use super::rule::Rule;
+use super::rule::RuleMaker;
+
+/// Negative match, that asserts that no line of output contains a match of
+/// an (unanchored) regular expression, as in `ERROR (not)`
+#[derive(Clone, Debug)]
+pub struct NotRule(String, ByteRegex, Modifiers);
+
+impl Display for NotRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl PartialEq for NotRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0 && self.2 == other.2
+    }
+}
+
+impl Rule for NotRule {
+    fn kind(&self) -> &'static str {
+        "not"
+    }
+
+    /// Whether the line contains what must not be in the output
+    fn matches(&self, line: &[u8]) -> bool {
+        self.1.is_match(self.2.line(line))
+    }
+
+    fn unmake(&self) -> (String, Vec<u8>) {
+        (
+            self.2.kind_with_modifiers(self.kind()),
+            self.0.as_bytes().to_vec(),
+        )
+    }
+
+    fn with_modifiers(&self, modifiers: Modifiers) -> Result<Box<dyn Rule>> {
+        let regex = ByteRegex::new(&format!("{}{}", modifiers.regex_flags(), self.0))?;
+        Ok(Box::new(NotRule(self.0.clone(), regex, modifiers)))
+    }
+
+    fn asserts_absence(&self) -> bool {
+        true
+    }
+}
+
+impl RuleMaker for NotRule {
+    fn make(expression: &str) -> Result<Box<dyn Rule>> {
+        let expression = cleanup_unrecognized_escape_sequences(expression);
+        let expression = escape_misused_repetition_quantifier(&expression);
+        let expression = escape_misused_character_class(&expression);
+        let regex = ByteRegex::new(&expression)?;
+        Ok(Box::new(NotRule(expression, regex, Modifiers::default())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NotRule;
+    use crate::lossy_string;
+    use crate::newline::StringNewline;
+    use crate::rules::rule::RuleMaker;
+
+    #[test]
+    fn test_make_unmake() {
+        let rule = NotRule::make("ERROR").expect("rule is created");
+        let (kind, expression) = rule.unmake();
+        assert_eq!("not", kind);
+        assert_eq!("ERROR", lossy_string!(&expression));
+        assert!(rule.asserts_absence());
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let tests = vec![
+            (true, "ERROR", "ERROR"),
+            (true, "ERROR", "an ERROR occurred"),
+            (false, "ERROR", "an error occurred"),
+            (true, "(?i)error", "an Error occurred"),
+            (true, "^ERROR", "ERROR: foo"),
+            (false, "^ERROR", "found ERROR"),
+            (true, "fail(ed|ure)$", "it failed"),
+        ];
+        for (expect, expression, line) in tests {
+            let rule = NotRule::make(expression)
+                .unwrap_or_else(|_| panic!("create rule from `{expression}`"));
+            assert_eq!(
+                expect,
+                rule.matches(line.assure_newline().as_bytes()),
+                "`{expression}` in `{line}`"
+            );
+        }
+    }
+}
@@ -12,13 +12,19 @@ use regex::Captures;
 use regex::Regex;
 use regex::bytes::Regex as ByteRegex;
 
+use super::modifiers::Modifiers;
 use super::rule::Rule;
 use super::rule::RuleMaker;
-use crate::newline::BytesNewline;
+use super::variables::Undefined;
+use super::variables::Variables;
+use super::variables::capture_variables;
+use super::variables::has_references;
+use super::variables::resolve_references;
+use super::variables::unescape_references;
 
 /// Simple equality match for lines that end in a new-line character
 #[derive(Clone, Debug)]
-pub struct RegexRule(String, ByteRegex);
+pub struct RegexRule(String, ByteRegex, Modifiers);
 
 impl Display for RegexRule {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -28,7 +34,7 @@ impl Display for RegexRule {
 
 impl PartialEq for RegexRule {
     fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
+        self.0 == other.0 && self.2 == other.2
     }
 }
 
@@ -38,11 +44,35 @@ impl Rule for RegexRule {
     }
 
     fn matches(&self, line: &[u8]) -> bool {
-        self.1.is_match(line.trim_newlines())
+        self.1.is_match(self.2.line(line))
     }
 
     fn unmake(&self) -> (String, Vec<u8>) {
-        (self.kind().to_string(), self.0.as_bytes().to_vec())
+        (
+            self.2.kind_with_modifiers(self.kind()),
+            self.0.as_bytes().to_vec(),
+        )
+    }
+
+    fn with_modifiers(&self, modifiers: Modifiers) -> Result<Box<dyn Rule>> {
+        let regex = compile(&self.0, modifiers, &Variables::new(), Undefined::Nothing)?;
+        Ok(Box::new(RegexRule(self.0.clone(), regex, modifiers)))
+    }
+
+    fn captures_variables(&self) -> bool {
+        self.1.capture_names().flatten().next().is_some()
+    }
+
+    fn captures(&self, line: &[u8]) -> Vec<(String, String)> {
+        capture_variables(&self.1, self.2.line(line))
+    }
+
+    fn with_variables(&self, variables: &Variables, undefined: Undefined) -> Option<Box<dyn Rule>> {
+        if !has_references(&self.0) {
+            return None;
+        }
+        let regex = compile(&self.0, self.2, variables, undefined).ok()?;
+        Some(Box::new(RegexRule(self.0.clone(), regex, self.2)))
     }
 }
 
@@ -51,11 +81,33 @@ impl RuleMaker for RegexRule {
         let expression = cleanup_unrecognized_escape_sequences(expression);
         let expression = escape_misused_repetition_quantifier(&expression);
         let expression = escape_misused_character_class(&expression);
-        let regex = ByteRegex::new(&format!("^{}$", expression))?;
-        Ok(Box::new(RegexRule(expression, regex)))
+        let expression = unescape_references(&expression);
+        let modifiers = Modifiers::default();
+        let regex = compile(
+            &expression,
+            modifiers,
+            &Variables::new(),
+            Undefined::Nothing,
+        )?;
+        Ok(Box::new(RegexRule(expression, regex, modifiers)))
     }
 }
 
+/// Compiles the expression into a regular expression, that matches whole
+/// lines, with the given modifiers and references to variables resolved
+fn compile(
+    expression: &str,
+    modifiers: Modifiers,
+    variables: &Variables,
+    undefined: Undefined,
+) -> Result<ByteRegex> {
+    Ok(ByteRegex::new(&format!(
+        "{}^{}$",
+        modifiers.regex_flags(),
+        resolve_references(expression, variables, undefined)
+    ))?)
+}
+
 lazy_static! {
     static ref VALID_REPETITION_QUANTIFIER: Regex = Regex::new("\\{([0-9]+(?:,[0-9]+)?)\\}")
         .expect("valid repetition quantifier regex must compile");
@@ -230,6 +282,8 @@ mod tests {
     use crate::lossy_string;
     use crate::newline::StringNewline;
     use crate::rules::rule::RuleMaker;
+    use crate::rules::variables::Undefined;
+    use crate::rules::variables::Variables;
 
     #[test]
     fn test_make_unmake() {
@@ -305,6 +359,45 @@ mod tests {
         assert_eq!("{\"kind\":\"regex\",\"expression\":\"abc\"}", serialized);
     }
 
+    #[test]
+    fn test_rule_variables() {
+        let rule = RegexRule::make(r"job (?P<job>\d+) started").expect("rule is created");
+        assert!(rule.captures_variables());
+        assert_eq!(
+            vec![("job".to_string(), "12".to_string())],
+            rule.captures(b"job 12 started\n")
+        );
+
+        let rule = RegexRule::make(r"job %{job} (done|failed)").expect("rule is created");
+        assert!(!rule.captures_variables());
+        assert_eq!(
+            "job %{job} (done|failed)",
+            lossy_string!(&rule.unmake().1),
+            "reference is not escaped"
+        );
+        assert!(!rule.matches(b"job 12 done\n"), "undefined matches nothing");
+
+        let variables = Variables::from([("job".to_string(), "12".to_string())]);
+        let resolved = rule
+            .with_variables(&variables, Undefined::Nothing)
+            .expect("rule has references");
+        assert!(resolved.matches(b"job 12 done\n"));
+        assert!(!resolved.matches(b"job 13 done\n"));
+        assert_eq!(rule.unmake(), resolved.unmake());
+
+        let resolved = rule
+            .with_variables(&Variables::new(), Undefined::Anything)
+            .expect("rule has references");
+        assert!(resolved.matches(b"job 13 done\n"));
+
+        assert!(
+            RegexRule::make("foo")
+                .expect("rule is created")
+                .with_variables(&variables, Undefined::Nothing)
+                .is_none()
+        );
+    }
+
     #[test]
     fn test_escape_misused_repetition_quantifier() {
         let tests = vec![
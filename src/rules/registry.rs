@@ -6,29 +6,54 @@
  */
 
 use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
 
 use anyhow::Result;
 use anyhow::anyhow;
+use anyhow::bail;
 use regex::Regex;
 
+use super::approx::ApproxRule;
+use super::checksum::Sha256Rule;
+use super::command::CommandRule;
 use super::equal::EqualRule;
 use super::escaped::EscapedRule;
+use super::file::FileRule;
 use super::glob::GlobRule;
+use super::ignore::IgnoreRule;
+use super::json::JsonRule;
+use super::modifiers::IGNORE_CASE;
+use super::modifiers::Modifiers;
+use super::modifiers::TRIM;
 use super::no_eol::EqualNoEolRule;
+use super::not::NotRule;
 use super::regex::RegexRule;
+use super::rule::ArgumentRuleMaker;
+use super::rule::MakeArgumentRule;
 use super::rule::MakeRule;
 use super::rule::Rule;
 use super::rule::RuleMaker;
+use super::template::TemplateRule;
+use super::toml::TomlRule;
+use super::yaml::YamlRule;
 
 /// Registry for [`Rule`] constructors, that is used by the [`crate::expectation::ExpectationMaker`]
+#[derive(Clone)]
 pub struct RuleRegistry {
     makers: HashMap<String, MakeRule>,
+    argument_makers: HashMap<String, MakeArgumentRule>,
+
+    /// Commands of custom kinds, and the shell they run in, by name
+    commands: HashMap<String, (String, PathBuf)>,
 }
 
 impl RuleRegistry {
     pub fn new() -> Self {
         Self {
             makers: HashMap::new(),
+            argument_makers: HashMap::new(),
+            commands: HashMap::new(),
         }
     }
 
@@ -36,13 +61,23 @@ impl RuleRegistry {
     /// [`crate::expectation::Expectation`], based on all registered rules
     /// matchers and their aliases
     pub(crate) fn to_expectation_regex(&self) -> Result<Regex> {
-        let mut names = self.makers.keys().collect::<Vec<_>>();
-        names.sort();
-        let names = names
-            .iter()
-            .map(|name| regex::escape(name))
-            .collect::<Vec<_>>()
-            .join("|");
+        let alternation = |names: Vec<&String>| {
+            let mut names = names
+                .iter()
+                .map(|name| regex::escape(name))
+                .collect::<Vec<_>>();
+            names.sort();
+            names.join("|")
+        };
+        let modifiers = format!("(?::(?:{IGNORE_CASE}|{TRIM}))*");
+        let mut names = format!(
+            "(?:{}){modifiers}",
+            alternation(self.makers.keys().chain(self.commands.keys()).collect())
+        );
+        if !self.argument_makers.is_empty() {
+            let argument_names = alternation(self.argument_makers.keys().collect());
+            names.push_str(&format!(r"|(?:{argument_names}){modifiers}(?:\s[^()]*?)?"));
+        }
         let expression = format!(
             r"(?x)
             ^
@@ -53,7 +88,7 @@ impl RuleRegistry {
                     (
                         {names}|
                     )?
-                    ([*+?])?
+                    ([*+?]|\{{\d+(?:,\d*)?\}})?
                 \)
             )?
             $
@@ -70,12 +105,61 @@ impl RuleRegistry {
         self
     }
 
-    /// Construct a [`Rule`] of the given kind (=name)
+    /// File a [`Rule`] constructor, that takes the argument that follows the
+    /// kind, under given names
+    pub fn register_with_argument(&mut self, maker: MakeArgumentRule, names: &[&str]) -> &mut Self {
+        for name in names {
+            self.argument_makers.insert(name.to_string(), maker);
+        }
+        self
+    }
+
+    /// File a custom kind under the given name, whose [`CommandRule`]s run
+    /// the command in the given shell. Names of built-in kinds cannot be used.
+    pub fn register_command(
+        &mut self,
+        name: &str,
+        command: &str,
+        shell: &Path,
+    ) -> Result<&mut Self> {
+        if !name.starts_with(|c: char| c.is_ascii_lowercase())
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        {
+            bail!(
+                "invalid name of custom rule `{name}`, which must start with a lowercase letter, followed by lowercase letters, digits, `-` or `_`"
+            );
+        }
+        if self.makers.contains_key(name) || self.argument_makers.contains_key(name) {
+            bail!("custom rule `{name}` conflicts with the built-in rule of the same name");
+        }
+        self.commands
+            .insert(name.to_string(), (command.to_string(), shell.to_path_buf()));
+        Ok(self)
+    }
+
+    /// Construct a [`Rule`] of the given kind (=name), which may be followed
+    /// by modifiers (e.g. `equal:i`) and by an argument for rules that take
+    /// one (e.g. `approx ±10%`)
     pub fn make(&self, kind: &str, expression: &str) -> Result<Box<dyn Rule>> {
-        if let Some(ref maker) = self.makers.get(kind) {
+        let (name, argument) = kind.split_once(char::is_whitespace).unwrap_or((kind, ""));
+        let (name, modifiers) = match name.split_once(':') {
+            Some((name, modifiers)) => (name, Some(modifiers.parse::<Modifiers>()?)),
+            None => (name, None),
+        };
+        let rule = if let (Some((command, shell)), "") = (self.commands.get(name), argument) {
+            Ok(Box::new(CommandRule::new(name, command, shell, expression)) as Box<dyn Rule>)
+        } else if let Some(ref maker) = self.argument_makers.get(name) {
+            maker(argument.trim(), expression)
+        } else if let (Some(maker), "") = (self.makers.get(name), argument) {
             maker(expression)
         } else {
             Err(anyhow!("no rule maker for {} registered", kind))
+        }?;
+        match modifiers {
+            Some(modifiers) => rule.with_modifiers(modifiers),
+            None => Ok(rule),
         }
     }
 }
@@ -84,17 +168,27 @@ impl Default for RuleRegistry {
     fn default() -> Self {
         let mut registry = Self::new();
         registry
+            .register_with_argument(ApproxRule::make_with_argument, &["approx"])
             .register(EqualRule::make, &["equal", "eq"])
             .register(EqualNoEolRule::make, &["no-eol"])
             .register(EscapedRule::make, &["escaped", "esc"])
+            .register(FileRule::make, &["file"])
             .register(GlobRule::make, &["glob", "gl"])
-            .register(RegexRule::make, &["regex", "re"]);
+            .register(IgnoreRule::make, &["ignore"])
+            .register(JsonRule::make, &["json"])
+            .register(NotRule::make, &["not"])
+            .register(RegexRule::make, &["regex", "re"])
+            .register(Sha256Rule::make, &["sha256"])
+            .register(TemplateRule::make, &["template"])
+            .register(TomlRule::make, &["toml"])
+            .register(YamlRule::make, &["yaml", "yml"]);
         registry
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
 
     use super::RuleRegistry;
     use crate::lossy_string;
@@ -107,6 +201,20 @@ mod tests {
             (vec!["escaped", "esc"], "foo"),
             (vec!["glob", "gl"], "foo"),
             (vec!["regex", "re"], "foo"),
+            (vec!["not"], "foo"),
+            (vec!["ignore"], ""),
+            (vec!["file"], "golden.txt"),
+            (vec!["template"], "id %{UUID}"),
+            (vec!["approx"], "took 1.5s"),
+            (vec!["approx ±10%"], "took 1.5s"),
+            (vec!["approx ±0.5"], "took 1.5s"),
+            (vec!["json"], "\"foo\""),
+            (vec!["yaml", "yml"], "foo: bar"),
+            (vec!["toml"], "foo = \"bar\""),
+            (vec!["equal:i", "eq:i"], "foo"),
+            (vec!["glob:i:trim", "gl:trim:i"], "foo*"),
+            (vec!["regex:trim", "re:trim"], "fo+"),
+            (vec!["approx:i ±10%"], "took 1.5s"),
         ];
         let registry = RuleRegistry::default();
         for (kinds, expression) in tests {
@@ -125,4 +233,74 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_modifiers() {
+        let registry = RuleRegistry::default();
+        for (kind, expression, line) in [
+            ("equal:i", "Hello World", "hello WORLD\n"),
+            ("equal:trim", "Hello World", "  Hello World \t\n"),
+            ("equal:i:trim", "Hello World", " HELLO world\n"),
+            ("escaped:i", "Hello\\tWorld", "hello\tworld\n"),
+            ("glob:i", "hello *", "HELLO World\n"),
+            ("glob:trim", "hello *", "  hello World  \n"),
+            ("regex:i", r"hello \w+", "HELLO World\n"),
+            ("regex:trim", r"hello \w+", "\thello World \n"),
+            ("template:i", "id %{HEX:4}", "ID BEEF\n"),
+            ("template:trim", "id %{INT}", " id 42 \n"),
+            ("approx:i:trim ±10%", "took 1.5S", " TOOK 1.6s\n"),
+            ("not:i", "error", "an ERROR occurred\n"),
+        ] {
+            let rule = registry
+                .make(kind, expression)
+                .unwrap_or_else(|err| panic!("make from `{kind}` `{expression}`: {err}"));
+            assert!(rule.matches(line.as_bytes()), "`{expression} ({kind})`");
+        }
+        for (kind, expression, line) in [
+            ("equal", "Hello World", "hello world\n"),
+            ("equal:i", "Hello World", "hello world"),
+            ("equal:i", "Hello World", " hello world\n"),
+            ("glob:trim", "hello *", "HELLO World\n"),
+            ("regex:i", r"hello \d", "HELLO W\n"),
+        ] {
+            let rule = registry
+                .make(kind, expression)
+                .unwrap_or_else(|err| panic!("make from `{kind}` `{expression}`: {err}"));
+            assert!(!rule.matches(line.as_bytes()), "`{expression} ({kind})`");
+        }
+        for kind in ["equal:x", "json:i", "sha256:trim", "ignore:i"] {
+            registry.make(kind, "foo").expect_err(kind);
+        }
+    }
+
+    #[test]
+    fn test_register_command() {
+        let mut registry = RuleRegistry::default();
+        registry
+            .register_command("my-check_2", "true", Path::new("sh"))
+            .expect("register custom rule");
+        let rule = registry
+            .make("my-check_2", "foo")
+            .expect("make custom rule");
+        assert_eq!(("my-check_2".to_string(), b"foo".to_vec()), rule.unmake());
+        registry
+            .make("my-check_2:i", "foo")
+            .expect_err("no modifiers");
+        registry
+            .make("my-check_2 arg", "foo")
+            .expect_err("no argument");
+
+        let regex = registry.to_expectation_regex().expect("compile regex");
+        let captures = regex.captures("foo (my-check_2+)").expect("matches");
+        assert_eq!("my-check_2", &captures[2]);
+
+        for name in ["equal", "re", "approx", "MyCheck", "1check", "my check", ""] {
+            assert!(
+                registry
+                    .register_command(name, "true", Path::new("sh"))
+                    .is_err(),
+                "`{name}` is rejected"
+            );
+        }
+    }
 }
@@ -7,11 +7,16 @@
 
 use std::fmt::Debug;
 use std::fmt::Display;
+use std::path::Path;
 
 use anyhow::Result;
+use anyhow::bail;
 use serde::Serialize;
 use serde::ser::SerializeMap;
 
+use super::modifiers::Modifiers;
+use super::variables::Undefined;
+use super::variables::Variables;
 use crate::escaping::Escaper;
 
 /// Rule implements the line-level comparisons of [`crate::expectation::Expectation`]s
@@ -25,27 +30,94 @@ pub trait Rule: RuleClone + Debug + Send {
     /// Decompose the rule into components from which it can be re-made
     fn unmake(&self) -> (String, Vec<u8>);
 
+    /// Whether the rule matches all remaining output at once, which is then
+    /// given to [`Rule::matches`] as a whole, instead of a single line
+    fn matches_remaining_output(&self) -> bool {
+        false
+    }
+
+    /// Whether the expression continues on all following lines of
+    /// expectations, up to an exit code or an assertion, because it cannot
+    /// tell by itself when it is complete
+    fn continues_expression(&self) -> bool {
+        false
+    }
+
+    /// Whether the rule asserts that no line of output matches it, which is
+    /// checked against all lines of output, instead of being aligned with them
+    fn asserts_absence(&self) -> bool {
+        false
+    }
+
+    /// A copy of the rule, that applies the given modifiers (e.g. `:i` in
+    /// `(equal:i)`), or an error, if the rule does not support modifiers
+    fn with_modifiers(&self, _modifiers: Modifiers) -> Result<Box<dyn Rule>> {
+        bail!("`{}` expectations do not support modifiers", self.kind())
+    }
+
+    /// Whether the rule captures values of variables from the line it
+    /// matches, which following expectations can refer to as `%{name}`
+    fn captures_variables(&self) -> bool {
+        false
+    }
+
+    /// The names and values of the variables that the rule captures from the
+    /// given line, that it matches
+    fn captures(&self, _line: &[u8]) -> Vec<(String, String)> {
+        vec![]
+    }
+
+    /// A copy of the rule, in which references to variables (`%{name}`) are
+    /// replaced with their values, if the rule has any such references
+    fn with_variables(
+        &self,
+        _variables: &Variables,
+        _undefined: Undefined,
+    ) -> Option<Box<dyn Rule>> {
+        None
+    }
+
+    /// The expression that the rule is updated to, so that it matches the
+    /// given (remaining) output, if it matches all remaining output at once
+    fn updated_expression(&self, _output: &[u8]) -> Option<String> {
+        None
+    }
+
+    /// The path of a file the execution produced and its expected (hex
+    /// encoded) SHA-256 digest, if the rule asserts a file instead of output
+    fn file_checksum(&self) -> Option<(&str, &str)> {
+        None
+    }
+
+    /// The path of a golden file, relative to the test document, whose
+    /// contents all remaining output is compared with
+    fn golden_file(&self) -> Option<&str> {
+        None
+    }
+
+    /// A copy of the rule, that resolves paths relative to the directory of
+    /// the test document, if the rule refers to any
+    fn with_document_directory(&self, _directory: &Path) -> Option<Box<dyn Rule>> {
+        None
+    }
+
     /// The string representation of the Rule as it would be written in
-    /// a test document
-    fn to_expression_string(&self, optional: bool, multiline: bool, escaper: &Escaper) -> String {
-        let (quantifier, equal_quantifier) = if optional {
-            if multiline {
-                ("*", " (*)")
-            } else {
-                ("?", " (?)")
-            }
-        } else if multiline {
-            ("+", " (+)")
-        } else {
-            ("", "")
-        };
+    /// a test document, with the given quantifier (e.g. `+` or `{3}`)
+    fn to_expression_string(&self, quantifier: &str, escaper: &Escaper) -> String {
         let (kind, expression) = self.unmake();
         let rendered = escaper.escaped_printable(&expression);
-        if kind == "equal" {
+        if let Some(modifiers) = kind
+            .strip_prefix("equal")
+            .filter(|modifiers| modifiers.is_empty() || modifiers.starts_with(':'))
+        {
             if escaper.has_unprintable(&expression) {
-                format!("{rendered} (escaped{quantifier})")
+                format!("{rendered} (escaped{modifiers}{quantifier})")
+            } else if !modifiers.is_empty() {
+                format!("{rendered} ({kind}{quantifier})")
+            } else if quantifier.is_empty() {
+                rendered
             } else {
-                format!("{rendered}{equal_quantifier}")
+                format!("{rendered} ({quantifier})")
             }
         } else {
             format!("{rendered} ({kind}{quantifier})")
@@ -106,3 +178,19 @@ pub trait RuleMaker {
 
 /// Constructor function for [`Rule`] implementations
 pub type MakeRule = fn(&str) -> Result<Box<dyn Rule>>;
+
+/// Trait of [`Rule`] implementations, whose kind can be followed by an
+/// argument, as in `(approx ±10%)`
+pub trait ArgumentRuleMaker {
+    /// Create the rule from the (possibly empty) argument and the expression
+    fn make_with_argument(argument: &str, expression: &str) -> Result<Box<dyn Rule>>;
+}
+
+/// Constructor function for [`Rule`] implementations that take an argument
+pub type MakeArgumentRule = fn(&str, &str) -> Result<Box<dyn Rule>>;
+
+/// Error of a [`RuleMaker`] for an expression that is not complete, but may be
+/// completed by the following line(s) of the test document
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct IncompleteExpression(pub String);
@@ -0,0 +1,153 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Shared implementation of the rules that compare all remaining output
+//! structurally with a document, like [`super::json::JsonRule`],
+//! [`super::yaml::YamlRule`] and [`super::toml::TomlRule`]. Documents are
+//! compared as [`Value`]s, in which placeholders are replaced with
+//! [`IGNORED`]: a member that is named so allows an object to have further
+//! members, a value that is so matches any value.
+
+use serde_json::Value;
+
+/// Placeholder for ignored values and further members of objects
+pub(super) const PLACEHOLDER: &str = "...";
+
+/// String value that placeholders are replaced with before comparison
+pub(super) const IGNORED: &str = "\u{0}...";
+
+/// Returns the expression with the kind at the end of its first line, which
+/// is how documents that span multiple lines are written
+pub(super) fn expression_with_kind(expression: &str, kind: &str) -> String {
+    match expression.split_once('\n') {
+        Some((first, rest)) => format!("{first} ({kind})\n{rest}"),
+        None => format!("{expression} ({kind})"),
+    }
+}
+
+/// Replaces all [`PLACEHOLDER`] strings and members of objects with
+/// [`IGNORED`], for documents that do not have a syntax for placeholders
+pub(super) fn with_ignored(value: Value) -> Value {
+    match value {
+        Value::String(value) if value == PLACEHOLDER => Value::String(IGNORED.to_string()),
+        Value::Array(values) => Value::Array(values.into_iter().map(with_ignored).collect()),
+        Value::Object(members) => Value::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| match key.as_str() {
+                    PLACEHOLDER => (IGNORED.to_string(), Value::Null),
+                    _ => (key, with_ignored(value)),
+                })
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+/// Whether the actual value matches the expected value, in which objects
+/// match regardless of the order of their members
+pub(super) fn matches_value(expected: &Value, actual: &Value) -> bool {
+    match (expected, actual) {
+        (Value::String(expected), _) if expected == IGNORED => true,
+        (Value::Object(expected), Value::Object(actual)) => {
+            (expected.contains_key(IGNORED) || expected.len() == actual.len())
+                && expected
+                    .iter()
+                    .filter(|(key, _)| *key != IGNORED)
+                    .all(|(key, expected)| {
+                        actual
+                            .get(key)
+                            .is_some_and(|actual| matches_value(expected, actual))
+                    })
+        }
+        (Value::Array(expected), Value::Array(actual)) => {
+            expected.len() == actual.len()
+                && expected
+                    .iter()
+                    .zip(actual)
+                    .all(|(expected, actual)| matches_value(expected, actual))
+        }
+        (Value::Number(expected), Value::Number(actual))
+            if expected.is_f64() || actual.is_f64() =>
+        {
+            expected.as_f64() == actual.as_f64()
+        }
+        _ => expected == actual,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::IGNORED;
+    use super::expression_with_kind;
+    use super::matches_value;
+    use super::with_ignored;
+
+    #[test]
+    fn test_expression_with_kind() {
+        assert_eq!("a: 1 (yaml)", expression_with_kind("a: 1", "yaml"));
+        assert_eq!(
+            "a: (yaml)\n  b: 1",
+            expression_with_kind("a:\n  b: 1", "yaml")
+        );
+    }
+
+    #[test]
+    fn test_with_ignored() {
+        assert_eq!(
+            json!({"a": IGNORED, "b": [1, IGNORED], "c": {"d": "....", IGNORED: null}}),
+            with_ignored(json!({"a": "...", "b": [1, "..."], "c": {"d": "....", "...": 2}}))
+        );
+    }
+
+    #[test]
+    fn test_matches_value() {
+        let tests = [
+            (
+                true,
+                json!({"a": 1, "b": [true, null]}),
+                json!({"b": [true, null], "a": 1}),
+            ),
+            (
+                false,
+                json!({"a": 1, "b": [true, null]}),
+                json!({"a": 1, "b": [null, true]}),
+            ),
+            (false, json!({"a": 1}), json!({"a": 1, "b": 2})),
+            (false, json!({"a": 1, "b": 2}), json!({"a": 1})),
+            (
+                true,
+                json!({"a": 1, IGNORED: null}),
+                json!({"a": 1, "b": 2}),
+            ),
+            (
+                false,
+                json!({"a": 1, IGNORED: null}),
+                json!({"a": 2, "b": 2}),
+            ),
+            (
+                true,
+                json!({"a": IGNORED, "b": 2}),
+                json!({"a": {"c": [1]}, "b": 2}),
+            ),
+            (false, json!({"a": IGNORED, "b": 2}), json!({"b": 2})),
+            (true, json!([1, IGNORED, 3]), json!([1, "two", 3])),
+            (false, json!([1, IGNORED]), json!([1, 2, 3])),
+            (true, json!({"a": 1.0}), json!({"a": 1})),
+            (false, json!({"a": "..."}), json!({"a": "b"})),
+        ];
+        for (expect, expected, actual) in tests {
+            assert_eq!(
+                expect,
+                matches_value(&expected, &actual),
+                "{expected} matching {actual}"
+            );
+        }
+    }
+}
@@ -0,0 +1,285 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::fmt::Display;
+
+use anyhow::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+use regex::bytes::Regex as ByteRegex;
+
+use super::modifiers::Modifiers;
+use super::rule::Rule;
+use super::rule::RuleMaker;
+use super::variables::Undefined;
+use super::variables::Variables;
+use super::variables::has_references;
+use super::variables::resolve_references;
+
+/// Regular expressions of the placeholders, by name
+const PLACEHOLDERS: &[(&str, &str)] = &[
+    (
+        "UUID",
+        r"[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}",
+    ),
+    (
+        "IPV4",
+        r"(?:(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\.){3}(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)",
+    ),
+    (
+        "ISO8601",
+        r"\d{4}-\d{2}-\d{2}(?:[T ]\d{2}:\d{2}(?::\d{2}(?:[.,]\d+)?)?(?:Z|[+-]\d{2}(?::?\d{2})?)?)?",
+    ),
+    (
+        "SEMVER",
+        r"(?:0|[1-9]\d*)\.(?:0|[1-9]\d*)\.(?:0|[1-9]\d*)(?:-[0-9A-Za-z-]+(?:\.[0-9A-Za-z-]+)*)?(?:\+[0-9A-Za-z-]+(?:\.[0-9A-Za-z-]+)*)?",
+    ),
+    ("HEX", r"[0-9a-fA-F]+"),
+    ("INT", r"[-+]?\d+"),
+];
+
+/// Match of lines that equal the expression, in which placeholders like
+/// `%{UUID}` or `%{HEX:8}` stand for any value of their kind, as in
+/// `request %{UUID} took %{INT}ms (template)`. A `%%` stands for a `%`.
+/// Lowercase names, as in `%{job}`, refer to captured variables instead.
+#[derive(Clone, Debug)]
+pub struct TemplateRule {
+    template: String,
+    pattern: String,
+    regex: ByteRegex,
+    modifiers: Modifiers,
+}
+
+impl Display for TemplateRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.template)
+    }
+}
+
+impl PartialEq for TemplateRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.template == other.template && self.modifiers == other.modifiers
+    }
+}
+
+impl Rule for TemplateRule {
+    fn kind(&self) -> &'static str {
+        "template"
+    }
+
+    fn matches(&self, line: &[u8]) -> bool {
+        self.regex.is_match(self.modifiers.line(line))
+    }
+
+    fn unmake(&self) -> (String, Vec<u8>) {
+        (
+            self.modifiers.kind_with_modifiers(self.kind()),
+            self.template.as_bytes().to_vec(),
+        )
+    }
+
+    fn with_modifiers(&self, modifiers: Modifiers) -> Result<Box<dyn Rule>> {
+        let template = if modifiers.trim {
+            self.template.trim()
+        } else {
+            &self.template
+        };
+        let pattern = template_to_regex(template)?;
+        Ok(Box::new(TemplateRule {
+            template: self.template.clone(),
+            regex: compile(&pattern, modifiers, &Variables::new(), Undefined::Nothing)?,
+            pattern,
+            modifiers,
+        }))
+    }
+
+    fn with_variables(&self, variables: &Variables, undefined: Undefined) -> Option<Box<dyn Rule>> {
+        if !has_references(&self.pattern) {
+            return None;
+        }
+        Some(Box::new(TemplateRule {
+            regex: compile(&self.pattern, self.modifiers, variables, undefined).ok()?,
+            ..self.clone()
+        }))
+    }
+}
+
+impl RuleMaker for TemplateRule {
+    fn make(expression: &str) -> Result<Box<dyn Rule>> {
+        let pattern = template_to_regex(expression)?;
+        let modifiers = Modifiers::default();
+        Ok(Box::new(TemplateRule {
+            template: expression.to_string(),
+            regex: compile(&pattern, modifiers, &Variables::new(), Undefined::Nothing)?,
+            pattern,
+            modifiers,
+        }))
+    }
+}
+
+/// Compiles the pattern into a regular expression, that matches whole lines,
+/// with the given modifiers and references to variables resolved
+fn compile(
+    pattern: &str,
+    modifiers: Modifiers,
+    variables: &Variables,
+    undefined: Undefined,
+) -> Result<ByteRegex> {
+    Ok(ByteRegex::new(&format!(
+        "{}^{}$",
+        modifiers.regex_flags(),
+        resolve_references(pattern, variables, undefined)
+    ))?)
+}
+
+/// Returns the regular expression, that matches what the template describes,
+/// in which references to variables are kept as they are
+fn template_to_regex(template: &str) -> Result<String> {
+    let mut regex = String::new();
+    let mut rest = template;
+    while let Some(position) = rest.find('%') {
+        regex.push_str(&regex::escape(&rest[..position]));
+        rest = &rest[position..];
+        if let Some(after) = rest.strip_prefix("%%") {
+            regex.push('%');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("%{") {
+            let end = after
+                .find('}')
+                .ok_or_else(|| anyhow!("unclosed placeholder in template `{template}`"))?;
+            let placeholder = &after[..end];
+            if has_references(&rest[..end + 3]) {
+                regex.push_str(&rest[..end + 3]);
+            } else {
+                regex.push_str(&placeholder_to_regex(placeholder)?);
+            }
+            rest = &after[end + 1..];
+        } else {
+            regex.push('%');
+            rest = &rest[1..];
+        }
+    }
+    regex.push_str(&regex::escape(rest));
+    Ok(regex)
+}
+
+/// Returns the (non-capturing) regular expression of a placeholder, given its
+/// name and its optional argument, as in `HEX:8`
+fn placeholder_to_regex(placeholder: &str) -> Result<String> {
+    let (name, argument) = match placeholder.split_once(':') {
+        Some((name, argument)) => (name, Some(argument)),
+        None => (placeholder, None),
+    };
+    let Some((_, regex)) = PLACEHOLDERS.iter().find(|(known, _)| *known == name) else {
+        bail!(
+            "unknown placeholder `%{{{placeholder}}}`, supported are: {}",
+            PLACEHOLDERS
+                .iter()
+                .map(|(name, _)| format!("%{{{name}}}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    };
+    match (name, argument) {
+        (_, None) => Ok(format!("(?:{regex})")),
+        ("HEX", Some(length)) => {
+            let length = length
+                .parse::<usize>()
+                .map_err(|_| anyhow!("length of `%{{{placeholder}}}` must be a number"))?;
+            Ok(format!("[0-9a-fA-F]{{{length}}}"))
+        }
+        (name, Some(_)) => bail!("placeholder `%{{{name}}}` takes no argument"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TemplateRule;
+    use crate::lossy_string;
+    use crate::rules::rule::RuleMaker;
+    use crate::rules::variables::Undefined;
+    use crate::rules::variables::Variables;
+
+    #[test]
+    fn test_make_unmake() {
+        let rule = TemplateRule::make("id %{UUID}").expect("rule is created");
+        let (kind, expression) = rule.unmake();
+        assert_eq!("template", kind);
+        assert_eq!("id %{UUID}", lossy_string!(&expression));
+    }
+
+    #[test]
+    fn test_make_invalid() {
+        for expression in ["%{FOO}", "%{UUID", "%{INT:3}", "%{HEX:many}"] {
+            TemplateRule::make(expression).expect_err(expression);
+        }
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let tests = vec![
+            (
+                true,
+                "id %{UUID}",
+                "id 0b8e9bb1-7f2c-4bb4-a1a3-2d6a2ff5b4c9",
+            ),
+            (false, "id %{UUID}", "id 0b8e9bb1-7f2c-4bb4-a1a3"),
+            (true, "from %{IPV4}:80", "from 192.168.0.1:80"),
+            (false, "from %{IPV4}:80", "from 192.168.0.256:80"),
+            (true, "at %{ISO8601}", "at 2024-01-02"),
+            (true, "at %{ISO8601}", "at 2024-01-02T03:04:05Z"),
+            (true, "at %{ISO8601}", "at 2024-01-02T03:04:05.123+01:00"),
+            (false, "at %{ISO8601}", "at yesterday"),
+            (true, "v%{SEMVER}", "v1.2.3"),
+            (true, "v%{SEMVER}", "v1.2.3-rc.1+build.5"),
+            (false, "v%{SEMVER}", "v1.2"),
+            (true, "commit %{HEX:8}", "commit 0123abcd"),
+            (false, "commit %{HEX:8}", "commit 0123abcde"),
+            (true, "sum %{HEX}", "sum deadbeef"),
+            (true, "took %{INT}ms", "took 42ms"),
+            (true, "took %{INT}ms", "took -42ms"),
+            (false, "took %{INT}ms", "took 4.2ms"),
+            (true, "100%% done (%{INT})", "100% done (3)"),
+            (true, "50% done", "50% done"),
+            (false, "a.c", "abc"),
+        ];
+        for (expect, expression, line) in tests {
+            let rule = TemplateRule::make(expression)
+                .unwrap_or_else(|err| panic!("create rule from `{expression}`: {err}"));
+            assert_eq!(
+                expect,
+                rule.matches(format!("{line}\n").as_bytes()),
+                "`{expression}` matches `{line}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_rule_variables() {
+        let rule = TemplateRule::make("job %{job} took %{INT}ms").expect("rule is created");
+        assert!(
+            !rule.matches(b"job 12 took 3ms\n"),
+            "undefined matches nothing"
+        );
+
+        let variables = Variables::from([("job".to_string(), "a.b".to_string())]);
+        let resolved = rule
+            .with_variables(&variables, Undefined::Nothing)
+            .expect("rule has references");
+        assert!(resolved.matches(b"job a.b took 3ms\n"));
+        assert!(!resolved.matches(b"job axb took 3ms\n"));
+        assert_eq!(rule.unmake(), resolved.unmake());
+
+        assert!(
+            TemplateRule::make("100%%{job}")
+                .expect("rule is created")
+                .with_variables(&variables, Undefined::Nothing)
+                .is_none(),
+            "escaped percent is no reference"
+        );
+    }
+}
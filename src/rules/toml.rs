@@ -0,0 +1,216 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! The TOML rule compares all remaining output structurally with a TOML
+//! document, so that neither the order of keys in tables nor the style in
+//! which the document is written are relevant. The document is written on the
+//! line that ends in `(toml)` and continues on all following lines, up to an
+//! exit code or an assertion:
+//!
+//! ```txt
+//! name = "foo" (toml)
+//! created = "..."
+//!
+//! [owner]
+//! name = "bar"
+//! "..." = "..."
+//! ```
+//!
+//! A `"..."` value matches any value, a `"..."` key allows the table to have
+//! any further keys. Date and time values are compared as strings.
+
+use std::fmt::Display;
+
+use anyhow::Result;
+use serde_json::Value;
+
+use super::rule::IncompleteExpression;
+use super::rule::Rule;
+use super::rule::RuleMaker;
+use super::structured::expression_with_kind;
+use super::structured::matches_value;
+use super::structured::with_ignored;
+use crate::escaping::Escaper;
+
+/// Structural match of all remaining output with a TOML document
+#[derive(Clone, Debug, PartialEq)]
+pub struct TomlRule {
+    expression: String,
+    expected: Value,
+}
+
+impl Display for TomlRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl Rule for TomlRule {
+    fn kind(&self) -> &'static str {
+        "toml"
+    }
+
+    /// Whether the given (remaining) output is a TOML document that matches
+    fn matches(&self, line: &[u8]) -> bool {
+        parse_toml(&String::from_utf8_lossy(line))
+            .is_ok_and(|actual| matches_value(&self.expected, &to_value(actual)))
+    }
+
+    fn unmake(&self) -> (String, Vec<u8>) {
+        (self.kind().to_string(), self.expression.as_bytes().to_vec())
+    }
+
+    /// Renders the document with the kind at the end of its first line
+    fn to_expression_string(&self, _quantifier: &str, _: &Escaper) -> String {
+        expression_with_kind(&self.expression, self.kind())
+    }
+
+    fn matches_remaining_output(&self) -> bool {
+        true
+    }
+
+    fn continues_expression(&self) -> bool {
+        true
+    }
+
+    /// Renders the output as document, if it is TOML
+    fn updated_expression(&self, output: &[u8]) -> Option<String> {
+        let actual = parse_toml(&String::from_utf8_lossy(output)).ok()?;
+        let expression = ::toml::to_string_pretty(&actual).ok()?;
+        Some(expression_with_kind(
+            expression.trim_end_matches('\n'),
+            self.kind(),
+        ))
+    }
+}
+
+impl RuleMaker for TomlRule {
+    /// Creates the rule from a TOML document, that may contain `"..."`
+    /// placeholders. Returns an [`IncompleteExpression`] error if the document
+    /// is invalid, as it may become valid with the following lines.
+    fn make(expression: &str) -> Result<Box<dyn Rule>> {
+        let expected = parse_toml(expression).map_err(|err| {
+            anyhow::Error::new(IncompleteExpression(format!(
+                "invalid TOML document: {}",
+                err.message()
+            )))
+        })?;
+        Ok(Box::new(Self {
+            expression: expression.to_string(),
+            expected: with_ignored(to_value(expected)),
+        }))
+    }
+}
+
+fn parse_toml(document: &str) -> Result<::toml::Table, ::toml::de::Error> {
+    document.parse::<::toml::Table>()
+}
+
+/// Converts a TOML table into a JSON value, so that it can be compared like
+/// other documents
+fn to_value(table: ::toml::Table) -> Value {
+    Value::Object(
+        table
+            .into_iter()
+            .map(|(key, value)| (key, to_json(value)))
+            .collect(),
+    )
+}
+
+fn to_json(value: ::toml::Value) -> Value {
+    match value {
+        ::toml::Value::String(value) => Value::String(value),
+        ::toml::Value::Integer(value) => Value::from(value),
+        ::toml::Value::Float(value) => Value::from(value),
+        ::toml::Value::Boolean(value) => Value::Bool(value),
+        ::toml::Value::Datetime(value) => Value::String(value.to_string()),
+        ::toml::Value::Array(values) => Value::Array(values.into_iter().map(to_json).collect()),
+        ::toml::Value::Table(table) => to_value(table),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TomlRule;
+    use crate::escaping::Escaper;
+    use crate::lossy_string;
+    use crate::rules::rule::IncompleteExpression;
+    use crate::rules::rule::RuleMaker;
+
+    #[test]
+    fn test_make_unmake() {
+        let expression = "foo = [1, \"...\"]\n\n[bar]\nbaz = true";
+        let rule = TomlRule::make(expression).expect("rule is created");
+        let (kind, unmade) = rule.unmake();
+        assert_eq!("toml", kind);
+        assert_eq!(expression, lossy_string!(&unmade));
+        assert!(rule.matches_remaining_output());
+        assert!(rule.continues_expression());
+    }
+
+    #[test]
+    fn test_make_invalid() {
+        let err = TomlRule::make("foo = [1,").expect_err("document is invalid");
+        assert!(err.downcast_ref::<IncompleteExpression>().is_some());
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let tests = [
+            (true, "a = 1\nb = [true]", "b = [ true ]\na = 1\n"),
+            (false, "a = 1\nb = [true]", "a = 1\nb = [false]\n"),
+            (false, "a = 1", "a = 1\nb = 2\n"),
+            (true, "a = 1\n\"...\" = \"...\"", "a = 1\nb = 2\n"),
+            (
+                true,
+                "c = 3\n[a]\nb = \"...\"\n\"...\" = \"...\"",
+                "c = 3\n\n[a]\nd = 4\nb = [1]\n",
+            ),
+            (true, "a.b = 1", "[a]\nb = 1\n"),
+            (
+                true,
+                "a = 1979-05-27T07:32:00Z",
+                "a = 1979-05-27T07:32:00Z\n",
+            ),
+            (
+                false,
+                "a = 1979-05-27T07:32:00Z",
+                "a = 1979-05-27T07:32:01Z\n",
+            ),
+            (true, "a = 1.0", "a = 1\n"),
+            (false, "a = 1", "a = [\n"),
+        ];
+        for (expect, expression, output) in tests {
+            let rule = TomlRule::make(expression)
+                .unwrap_or_else(|err| panic!("create rule from {expression}: {err}"));
+            assert_eq!(
+                expect,
+                rule.matches(output.as_bytes()),
+                "{expression:?} matching {output:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_to_expression_string() {
+        let rule = TomlRule::make("[a]\nb = 1").expect("rule is created");
+        assert_eq!(
+            "[a] (toml)\nb = 1",
+            rule.to_expression_string("", &Escaper::default())
+        );
+    }
+
+    #[test]
+    fn test_updated_expression() {
+        let rule = TomlRule::make("a = \"...\"").expect("rule is created");
+        assert_eq!(
+            Some("b = 2 (toml)\n\n[a]\nc = [1]".to_string()),
+            rule.updated_expression(b"a.c = [1]\nb = 2\n")
+        );
+        assert_eq!(None, rule.updated_expression(b"a = [\n"));
+    }
+}
@@ -0,0 +1,143 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::collections::BTreeMap;
+
+use lazy_static::lazy_static;
+use regex::Captures;
+use regex::Regex;
+use regex::bytes::Regex as ByteRegex;
+
+use crate::lossy_string;
+use crate::newline::BytesNewline;
+
+/// Values that named groups of regular expressions captured from the output,
+/// by the name of the group, which expectations refer to as `%{name}`
+pub type Variables = BTreeMap<String, String>;
+
+lazy_static! {
+    /// A reference to a variable in a pattern, like `%{job}`
+    static ref REFERENCE: Regex =
+        Regex::new(r"%\{([a-z_][A-Za-z0-9_]*)\}").expect("reference regex must compile");
+
+    /// A reference, of which the curly brackets were escaped as misused
+    /// repetition quantifiers, like `%\{job\}`
+    static ref ESCAPED_REFERENCE: Regex = Regex::new(r"%\\\{([a-z_][A-Za-z0-9_]*)\\\}")
+        .expect("escaped reference regex must compile");
+}
+
+/// What a reference to a variable, that has no value (yet), matches
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Undefined {
+    /// Match any text, so that values can be captured before they are known
+    Anything,
+
+    /// Match no text at all
+    Nothing,
+}
+
+impl Undefined {
+    fn pattern(&self) -> &'static str {
+        match self {
+            Self::Anything => ".*?",
+            Self::Nothing => r"\b\B",
+        }
+    }
+}
+
+/// Whether the (regular expression) pattern refers to any variable
+pub fn has_references(pattern: &str) -> bool {
+    REFERENCE.is_match(pattern)
+}
+
+/// Returns the pattern, in which references that were escaped as misused
+/// repetition quantifiers (`%\{job\}`) are restored (`%{job}`)
+pub(super) fn unescape_references(pattern: &str) -> String {
+    ESCAPED_REFERENCE.replace_all(pattern, "%{$1}").to_string()
+}
+
+/// Returns the (regular expression) pattern, in which the references to
+/// variables are replaced with their (escaped) values
+pub fn resolve_references(pattern: &str, variables: &Variables, undefined: Undefined) -> String {
+    REFERENCE
+        .replace_all(pattern, |captures: &Captures| {
+            variables.get(&captures[1]).map_or_else(
+                || undefined.pattern().to_string(),
+                |value| regex::escape(value),
+            )
+        })
+        .to_string()
+}
+
+/// Returns the values of all named groups of the regular expression, that
+/// participate in its match of the given line
+pub fn capture_variables(regex: &ByteRegex, line: &[u8]) -> Vec<(String, String)> {
+    let Some(captures) = regex.captures(line.trim_newlines()) else {
+        return vec![];
+    };
+    regex
+        .capture_names()
+        .flatten()
+        .filter_map(|name| {
+            captures
+                .name(name)
+                .map(|value| (name.to_string(), lossy_string!(value.as_bytes())))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::bytes::Regex as ByteRegex;
+
+    use super::Undefined;
+    use super::Variables;
+    use super::capture_variables;
+    use super::has_references;
+    use super::resolve_references;
+    use super::unescape_references;
+
+    #[test]
+    fn test_resolve_references() {
+        let variables = Variables::from([("job".to_string(), "1.2".to_string())]);
+        let tests = vec![
+            ("job %{job}", Undefined::Nothing, r"job 1\.2"),
+            ("%{job}-%{job}", Undefined::Nothing, r"1\.2-1\.2"),
+            ("job %{other}", Undefined::Anything, "job .*?"),
+            ("job %{other}", Undefined::Nothing, r"job \b\B"),
+            ("job %{UUID}", Undefined::Nothing, "job %{UUID}"),
+            ("job %%{job}", Undefined::Nothing, r"job %1\.2"),
+        ];
+        for (pattern, undefined, expected) in tests {
+            assert_eq!(
+                expected,
+                resolve_references(pattern, &variables, undefined),
+                "{pattern}"
+            );
+        }
+        assert!(has_references("job %{job}"));
+        assert!(!has_references("job %{JOB} \\{job\\}"));
+        assert_eq!("a %{job} b", unescape_references(r"a %\{job\} b"));
+    }
+
+    #[test]
+    fn test_capture_variables() {
+        let regex = ByteRegex::new(r"^job (?P<job>\d+)(?: on (?P<host>\w+))?$").expect("regex");
+        assert_eq!(
+            vec![("job".to_string(), "123".to_string())],
+            capture_variables(&regex, b"job 123\n")
+        );
+        assert_eq!(
+            vec![
+                ("job".to_string(), "123".to_string()),
+                ("host".to_string(), "foo".to_string())
+            ],
+            capture_variables(&regex, b"job 123 on foo\n")
+        );
+        assert!(capture_variables(&regex, b"no job\n").is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! The YAML rule compares all remaining output structurally with a YAML
+//! document, so that neither the order of keys in mappings nor the style in
+//! which the document is written are relevant. The document is written on the
+//! line that ends in `(yaml)` and continues on all following lines, up to an
+//! exit code or an assertion:
+//!
+//! ```txt
+//! name: foo (yaml)
+//! created: ...
+//! tags: [a, b]
+//! owner:
+//!   name: bar
+//!   ...: ...
+//! ```
+//!
+//! A `...` value matches any value, a `...` key allows the mapping to have any
+//! further keys.
+
+use std::fmt::Display;
+
+use anyhow::Result;
+use serde_json::Value;
+
+use super::rule::IncompleteExpression;
+use super::rule::Rule;
+use super::rule::RuleMaker;
+use super::structured::expression_with_kind;
+use super::structured::matches_value;
+use super::structured::with_ignored;
+use crate::escaping::Escaper;
+
+/// Structural match of all remaining output with a YAML document
+#[derive(Clone, Debug, PartialEq)]
+pub struct YamlRule {
+    expression: String,
+    expected: Value,
+}
+
+impl Display for YamlRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl Rule for YamlRule {
+    fn kind(&self) -> &'static str {
+        "yaml"
+    }
+
+    /// Whether the given (remaining) output is a YAML document that matches
+    fn matches(&self, line: &[u8]) -> bool {
+        serde_yaml::from_slice::<Value>(line)
+            .is_ok_and(|actual| matches_value(&self.expected, &actual))
+    }
+
+    fn unmake(&self) -> (String, Vec<u8>) {
+        (self.kind().to_string(), self.expression.as_bytes().to_vec())
+    }
+
+    /// Renders the document with the kind at the end of its first line
+    fn to_expression_string(&self, _quantifier: &str, _: &Escaper) -> String {
+        expression_with_kind(&self.expression, self.kind())
+    }
+
+    fn matches_remaining_output(&self) -> bool {
+        true
+    }
+
+    fn continues_expression(&self) -> bool {
+        true
+    }
+
+    /// Renders the output as document, if it is YAML
+    fn updated_expression(&self, output: &[u8]) -> Option<String> {
+        let actual = serde_yaml::from_slice::<Value>(output).ok()?;
+        let expression = serde_yaml::to_string(&actual).ok()?;
+        Some(expression_with_kind(
+            expression.trim_end_matches('\n'),
+            self.kind(),
+        ))
+    }
+}
+
+impl RuleMaker for YamlRule {
+    /// Creates the rule from a YAML document, that may contain `...`
+    /// placeholders. Returns an [`IncompleteExpression`] error if the document
+    /// is invalid, as it may become valid with the following lines.
+    fn make(expression: &str) -> Result<Box<dyn Rule>> {
+        let expected = serde_yaml::from_str(expression).map_err(|err| {
+            anyhow::Error::new(IncompleteExpression(format!(
+                "invalid YAML document: {err}"
+            )))
+        })?;
+        Ok(Box::new(Self {
+            expression: expression.to_string(),
+            expected: with_ignored(expected),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::YamlRule;
+    use crate::escaping::Escaper;
+    use crate::lossy_string;
+    use crate::rules::rule::IncompleteExpression;
+    use crate::rules::rule::RuleMaker;
+
+    #[test]
+    fn test_make_unmake() {
+        let expression = "foo:\n  - 1\n  - ...";
+        let rule = YamlRule::make(expression).expect("rule is created");
+        let (kind, unmade) = rule.unmake();
+        assert_eq!("yaml", kind);
+        assert_eq!(expression, lossy_string!(&unmade));
+        assert!(rule.matches_remaining_output());
+        assert!(rule.continues_expression());
+    }
+
+    #[test]
+    fn test_make_invalid() {
+        let err = YamlRule::make("foo: [1,").expect_err("document is invalid");
+        assert!(err.downcast_ref::<IncompleteExpression>().is_some());
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let tests = [
+            (
+                true,
+                "a: 1\nb: [true, null]",
+                "b:\n  - true\n  - null\na: 1\n",
+            ),
+            (
+                true,
+                "a: 1\nb: [true, null]",
+                "{\"a\": 1, \"b\": [true, null]}\n",
+            ),
+            (false, "a: 1\nb: [true, null]", "a: 1\nb: [null, true]\n"),
+            (false, "a: 1", "a: 1\nb: 2\n"),
+            (true, "a: 1\n...: ...", "a: 1\nb: 2\n"),
+            (
+                true,
+                "a:\n  b: ...\n  ...: ...\nc: 3",
+                "c: 3\na:\n  b: [1]\n  d: 4\n",
+            ),
+            (
+                false,
+                "a:\n  b: ...\n  ...: ...\nc: 3",
+                "c: 3\na:\n  d: 4\n",
+            ),
+            (true, "- 1\n- ...", "[1, two]\n"),
+            (true, "a: '1'", "a: \"1\"\n"),
+            (false, "a: '1'", "a: 1\n"),
+            (false, "a: 1", "a: [\n"),
+        ];
+        for (expect, expression, output) in tests {
+            let rule = YamlRule::make(expression)
+                .unwrap_or_else(|err| panic!("create rule from {expression}: {err}"));
+            assert_eq!(
+                expect,
+                rule.matches(output.as_bytes()),
+                "{expression:?} matching {output:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_to_expression_string() {
+        let rule = YamlRule::make("a:\n  b: 1").expect("rule is created");
+        assert_eq!(
+            "a: (yaml)\n  b: 1",
+            rule.to_expression_string("", &Escaper::default())
+        );
+    }
+
+    #[test]
+    fn test_updated_expression() {
+        let rule = YamlRule::make("a: ...").expect("rule is created");
+        assert_eq!(
+            Some("a: (yaml)\n- 1".to_string()),
+            rule.updated_expression(b"{\"a\": [1]}\n")
+        );
+        assert_eq!(None, rule.updated_expression(b"a: [\n"));
+    }
+}
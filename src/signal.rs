@@ -7,8 +7,16 @@
 
 #[cfg(unix)]
 pub use nix_signal::KillSignal;
+#[cfg(unix)]
+pub use nix_signal::signal_name;
+#[cfg(unix)]
+pub use nix_signal::signal_number;
 #[cfg(windows)]
 pub use win_signal::KillSignal;
+#[cfg(windows)]
+pub use win_signal::signal_name;
+#[cfg(windows)]
+pub use win_signal::signal_number;
 
 #[cfg(unix)]
 mod nix_signal {
@@ -42,6 +50,26 @@ mod nix_signal {
         }
     }
 
+    /// Returns the number of the signal with the given name, which can be
+    /// provided in any case and with or without the "SIG" prefix
+    /// ("term" -> 15, "SIGTERM" -> 15)
+    pub fn signal_number(name: &str) -> Option<i32> {
+        let name = name.to_uppercase();
+        let name = match name.strip_prefix("SIG") {
+            Some(_) => name,
+            None => format!("SIG{name}"),
+        };
+        signal::Signal::from_str(&name).ok().map(|s| s as i32)
+    }
+
+    /// Returns the upper case name of the signal with the given number,
+    /// without the "SIG" prefix (15 -> "TERM")
+    pub fn signal_name(number: i32) -> Option<String> {
+        signal::Signal::try_from(number)
+            .ok()
+            .map(|s| s.short_name().to_uppercase())
+    }
+
     /// Nix signal container that capture whether and which OS signal to send to
     /// executions of shell expressions that are marked as detached.
     #[derive(Clone, Debug, PartialEq, Eq)]
@@ -203,12 +231,23 @@ mod nix_signal {
                 ("\"disabled\"", KillSignal::Disabled),
             ];
             for (input, expected) in cases {
-                let value: KillSignal =
-                    serde_json::from_str(input).expect(&format!("deserialize from '{input}'"));
+                let value: KillSignal = serde_json::from_str(input)
+                    .unwrap_or_else(|_| panic!("deserialize from '{input}'"));
                 assert_eq!(value, expected);
             }
         }
 
+        #[test]
+        fn test_signal_number() {
+            assert_eq!(signal_number("term"), Some(15));
+            assert_eq!(signal_number("SIGKILL"), Some(9));
+            assert_eq!(signal_number("Segv"), Some(11));
+            assert_eq!(signal_number("nope"), None);
+            assert_eq!(signal_name(15), Some("TERM".to_string()));
+            assert_eq!(signal_name(11), Some("SEGV".to_string()));
+            assert_eq!(signal_name(0), None);
+        }
+
         #[test]
         fn test_serialize_json() {
             let cases = [
@@ -218,7 +257,7 @@ mod nix_signal {
             ];
             for (input, expected) in cases {
                 let value = serde_json::to_string(&input)
-                    .expect(&format!("serialize '{}'", input.to_string()));
+                    .unwrap_or_else(|_| panic!("serialize '{}'", input));
                 assert_eq!(&value as &str, expected);
             }
         }
@@ -244,6 +283,58 @@ mod win_signal {
         "xfsz", "vtalrm", "prof", "winch", "sys",
     ];
 
+    /// Returns the number that the signal with the given name has on Linux,
+    /// which can be provided in any case and with or without the "SIG" prefix
+    pub fn signal_number(name: &str) -> Option<i32> {
+        let name = name.to_lowercase();
+        let name = name.strip_prefix("sig").unwrap_or(&name);
+        SIGNAL_NUMBERS
+            .iter()
+            .find(|(signal, _)| *signal == name)
+            .map(|(_, number)| *number)
+    }
+
+    /// Returns the upper case name that the signal with the given number has
+    /// on Linux, without the "SIG" prefix
+    pub fn signal_name(number: i32) -> Option<String> {
+        SIGNAL_NUMBERS
+            .iter()
+            .find(|(_, signal)| *signal == number)
+            .map(|(name, _)| name.to_uppercase())
+    }
+
+    /// Numbers of the supported signals on Linux
+    const SIGNAL_NUMBERS: &[(&str, i32)] = &[
+        ("hup", 1),
+        ("int", 2),
+        ("quit", 3),
+        ("ill", 4),
+        ("trap", 5),
+        ("abrt", 6),
+        ("bus", 7),
+        ("fpe", 8),
+        ("kill", 9),
+        ("usr1", 10),
+        ("segv", 11),
+        ("usr2", 12),
+        ("pipe", 13),
+        ("alrm", 14),
+        ("term", 15),
+        ("chld", 17),
+        ("cont", 18),
+        ("stop", 19),
+        ("tstp", 20),
+        ("ttin", 21),
+        ("ttou", 22),
+        ("urg", 23),
+        ("xcpu", 24),
+        ("xfsz", 25),
+        ("vtalrm", 26),
+        ("prof", 27),
+        ("winch", 28),
+        ("sys", 31),
+    ];
+
     /// Windows "fake signal" container. It is currently only used to support the configuration
     /// type, so that updating or running tests on Windows does not fail with an error about
     /// invalid configuration.
@@ -6,28 +6,71 @@
  */
 
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fmt::Display;
-#[cfg(test)]
+use std::path::Path;
+use std::str::FromStr;
 use std::time::Duration;
 
+use anyhow::Context;
+use anyhow::bail;
+use lazy_static::lazy_static;
+use regex::bytes::Captures;
+use regex::bytes::NoExpand;
+use regex::bytes::Regex;
 use serde::Serialize;
 use serde::Serializer;
 use serde::ser::SerializeMap;
 use serde_json::Value;
 use serde_json::json;
 
+use crate::assertions::assertion::Assertion;
+use crate::config::FileAssertions;
+use crate::config::InvalidUtf8;
+use crate::config::OutputEncoding;
 use crate::config::OutputStreamControl;
 use crate::config::TestCaseConfig;
 use crate::diff::Diff;
+use crate::diff::DiffLine;
 use crate::diff::DiffTool;
+use crate::escaping::escaped_invalid_utf8;
+use crate::escaping::hex_dump;
 use crate::escaping::strip_colors_bytes;
 use crate::expectation::Expectation;
+use crate::formatln;
+use crate::lossy_string;
+use crate::newline::BytesNewline;
+use crate::newline::SplitLinesByNewline;
 use crate::newline::replace_crlf;
 use crate::output::ExitStatus;
 use crate::output::Output;
+use crate::output::ProducedFile;
+use crate::rules::checksum::sha256_file_hex;
+use crate::rules::checksum::sha256_hex;
+use crate::rules::variables::Undefined;
+use crate::rules::variables::Variables;
+use crate::signal::signal_number;
 
 pub type Result<T> = anyhow::Result<T, TestCaseError>;
 
+/// What secrets in the output are replaced with, see [`TestCaseConfig::redact`]
+pub const REDACTED: &str = "[REDACTED]";
+
+/// What the home directory in the output is replaced with, see
+/// [`TestCaseConfig::anonymize_user`]
+pub const HOME_PLACEHOLDER: &str = "~";
+
+/// What the name of the user in the output is replaced with, see
+/// [`TestCaseConfig::anonymize_user`]
+pub const USER_PLACEHOLDER: &str = "$USER";
+
+lazy_static! {
+    /// Matches a drive letter at the start of an absolute path, like `C:/`,
+    /// after backslashes have been replaced with forward slashes
+    static ref DRIVE_LETTER_EXPRESSION: Regex =
+        Regex::new("(^|[^A-Za-z0-9_])[A-Za-z]:/").expect("drive letter expression must compile");
+}
+
 /// An aggregate that unifies all ingredients for a test: a title
 /// of the expected and intended state of the world; what a specific
 /// command line should output and why
@@ -39,12 +82,34 @@ pub struct TestCase {
     /// The valid shell expression that is to be executed
     pub shell_expression: String,
 
+    /// The arguments of the shorthand line (`% <args>`) that the shell
+    /// expression was rendered from, using the command template of the
+    /// document (see [`crate::config::DocumentConfig::command_template`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template_arguments: Option<String>,
+
+    /// Steps that interactively drive the execution, which requires it to be
+    /// attached to a pseudo-terminal (see [`TestCaseConfig::tty`])
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub interaction: Vec<InteractionStep>,
+
     /// The expectations that describe the output of the execution
     pub expectations: Vec<Expectation>,
 
+    /// Alternative expectations, each of which the output is accepted with
+    /// instead of the `expectations`, as when it differs by platform. They
+    /// are written after lines `(or)`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<Vec<Expectation>>,
+
     /// The expected exit code of the execution
     #[serde(serialize_with = "serialize_always_as_value")]
-    pub exit_code: Option<i32>,
+    pub exit_code: Option<ExpectedExitCode>,
+
+    /// Checks that are evaluated after the execution and that are not about
+    /// its output, written as `@assert <kind> <arguments>` lines
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub assertions: Vec<Box<dyn Assertion>>,
 
     /// The line number of this test in the original file (starting at 1)
     pub line_number: usize,
@@ -59,48 +124,629 @@ impl TestCase {
     /// outcome in regards to exit code and (STDOUT) output, or return an
     /// [`TestCaseError`]
     pub fn validate(&self, output: &Output) -> Result<()> {
-        if let ExitStatus::Code(exit_code) = output.exit_code {
-            let expected = self.exit_code.unwrap_or(0);
-            if exit_code != expected {
-                return Err(TestCaseError::InvalidExitCode {
-                    actual: exit_code,
+        self.validate_with_variables(output, &mut Variables::new())
+    }
+
+    /// Validate like [`TestCase::validate`], with the variables that the
+    /// preceding testcases of the document captured, to which the variables
+    /// that the expectations of this testcase capture are added
+    pub fn validate_with_variables(
+        &self,
+        output: &Output,
+        variables: &mut Variables,
+    ) -> Result<()> {
+        // output that exceeds the max output size was not captured entirely
+        self.validate_output_size(output)?;
+        self.validate_utf8(output)?;
+
+        let mut failures = vec![];
+        if let (ExitStatus::Code(_) | ExitStatus::Signal(_), false) =
+            (&output.exit_code, self.has_exit_code_assertion())
+        {
+            let expected = self.expected_exit_code();
+            if !expected.matches(&output.exit_code) {
+                let failure = TestCaseError::InvalidExitCode {
+                    actual: output.exit_code.as_code(),
                     expected,
-                });
+                };
+                if self.config.collect_all_failures != Some(true) {
+                    return Err(failure);
+                }
+                failures.push(failure);
+            }
+        }
+        let stream = self.encoded_output(output);
+        let (diff, expectations) = self.validate_variants(output, &stream, variables)?;
+        if let Some(diff) = diff {
+            // the output precedes the exit code in the document
+            failures.insert(0, TestCaseError::MalformedOutput(diff));
+        }
+        if failures.is_empty() || self.config.collect_all_failures == Some(true) {
+            failures.extend(self.validate_absences(expectations, &stream));
+            failures.extend(self.validate_assertions(output));
+            failures.extend(self.validate_file_assertions(output));
+        }
+        match failures.len() {
+            0 => self
+                .validate_duration(output)
+                .and_then(|_| self.validate_rss(output)),
+            1 => Err(failures.remove(0)),
+            _ => Err(TestCaseError::MultipleFailures(failures)),
+        }
+    }
+
+    /// Returns the differences of the output to the first variant of the
+    /// expectations that it matches, starting with the `expectations`, or
+    /// to the `expectations`, if it matches none, along with that variant
+    fn validate_variants(
+        &self,
+        output: &Output,
+        stream: &[u8],
+        variables: &mut Variables,
+    ) -> Result<(Option<Diff>, &[Expectation])> {
+        let mut first = None;
+        for expectations in std::iter::once(&self.expectations).chain(&self.variants) {
+            let mut captured = variables.clone();
+            let diff = self.validate_expectations(expectations, output, stream, &mut captured)?;
+            if diff.is_none() {
+                *variables = captured;
+                return Ok((None, expectations));
+            }
+            first.get_or_insert((diff, captured, expectations));
+        }
+        let (diff, captured, expectations) = first.expect("expectations are validated");
+        *variables = captured;
+        Ok((diff, expectations))
+    }
+
+    /// Returns the differences of the output to the given expectations, if
+    /// there are any
+    fn validate_expectations(
+        &self,
+        expectations: &[Expectation],
+        output: &Output,
+        stream: &[u8],
+        variables: &mut Variables,
+    ) -> Result<Option<Diff>> {
+        let diff_tool =
+            DiffTool::new(self.resolved_expectations(expectations, output, stream, variables))
+                .with_file_digests(output.file_digests.clone())
+                .with_algorithm(self.config.diff_algorithm.unwrap_or_default());
+        let diff = diff_tool
+            .differences(stream)
+            .map_err(TestCaseError::InternalError)?;
+        if diff.is_some() && self.config.output_encoding == Some(OutputEncoding::Hash) {
+            // the digest of the output is shown, instead of the raw bytes
+            return Ok(Some(
+                diff_tool
+                    .diff(formatln!("(sha256:{})", sha256_hex(stream)).as_bytes())
+                    .map_err(TestCaseError::InternalError)?,
+            ));
+        }
+        Ok(diff)
+    }
+
+    /// Returns the expectations, in which references to variables (`%{name}`)
+    /// are replaced with their values, after the values that expectations
+    /// capture from the lines of output they match are added to the variables
+    fn resolved_expectations(
+        &self,
+        expectations: &[Expectation],
+        output: &Output,
+        stream: &[u8],
+        variables: &mut Variables,
+    ) -> Vec<Expectation> {
+        if expectations
+            .iter()
+            .any(|expectation| expectation.rule.captures_variables())
+        {
+            // align with references that match anything, to find the lines
+            // from which values are captured before they are known
+            let diff = DiffTool::new(
+                expectations
+                    .iter()
+                    .map(|expectation| expectation.with_variables(variables, Undefined::Anything))
+                    .collect(),
+            )
+            .with_file_digests(output.file_digests.clone())
+            .with_algorithm(self.config.diff_algorithm.unwrap_or_default())
+            .diff(stream);
+            for line in diff.map(|diff| diff.lines).unwrap_or_default() {
+                if let DiffLine::MatchedExpectation {
+                    expectation, lines, ..
+                } = line
+                {
+                    if let Some((_, line)) = lines.first() {
+                        variables.extend(expectation.rule.captures(line));
+                    }
+                }
+            }
+        }
+        expectations
+            .iter()
+            .map(|expectation| expectation.with_variables(variables, Undefined::Nothing))
+            .collect()
+    }
+
+    /// Returns a failure for each of the given expectations that asserts
+    /// absence (e.g. `ERROR (not)`), which a line of the output matches
+    /// nonetheless
+    fn validate_absences(&self, expectations: &[Expectation], stream: &[u8]) -> Vec<TestCaseError> {
+        let lines = stream.split_at_newline();
+        expectations
+            .iter()
+            .filter(|expectation| expectation.rule.asserts_absence())
+            .filter_map(|expectation| {
+                let mut matching = lines
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, line)| expectation.matches(line));
+                let (index, line) = matching.next()?;
+                let mut reason = format!(
+                    "line {} of output matches: {}",
+                    index + 1,
+                    lossy_string!((&line[..]).trim_newlines())
+                );
+                let more = matching.count();
+                if more > 0 {
+                    reason.push_str(&format!(" (and {more} more)"));
+                }
+                Some(TestCaseError::FailedAssertion {
+                    assertion: expectation.original_string(),
+                    reason,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns a failure for each assertion that the execution does not satisfy
+    fn validate_assertions(&self, output: &Output) -> Vec<TestCaseError> {
+        self.assertions
+            .iter()
+            .filter_map(|assertion| {
+                assertion
+                    .check(output)
+                    .err()
+                    .map(|reason| TestCaseError::FailedAssertion {
+                        assertion: assertion.to_string(),
+                        reason,
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns a failure for each check of the configured
+    /// [`TestCaseConfig::assert_files`] that the produced files do not satisfy
+    fn validate_file_assertions(&self, output: &Output) -> Vec<TestCaseError> {
+        self.config
+            .assert_files
+            .iter()
+            .flat_map(|assert_files| assert_files.check(&output.produced_files))
+            .map(|(assertion, reason)| TestCaseError::FailedAssertion { assertion, reason })
+            .collect()
+    }
+
+    /// Whether an assertion checks the exit code, in which case the exit code
+    /// is not expected to be zero, unless it is explicitly given
+    pub fn has_exit_code_assertion(&self) -> bool {
+        self.exit_code.is_none()
+            && self.config.expected_signal.is_none()
+            && self
+                .assertions
+                .iter()
+                .any(|assertion| assertion.asserts_exit_code())
+    }
+
+    /// Validate that the otherwise valid execution did not take longer than
+    /// the configured [`TestCaseConfig::max_duration`]
+    fn validate_duration(&self, output: &Output) -> Result<()> {
+        match (output.duration, self.config.max_duration) {
+            (Some(duration), Some(max_duration)) if duration > max_duration => {
+                Err(TestCaseError::ExceededMaxDuration {
+                    duration,
+                    max_duration,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Validate that the execution did not produce more output than the
+    /// configured [`TestCaseConfig::max_output_size`]
+    fn validate_output_size(&self, output: &Output) -> Result<()> {
+        match self.config.max_output_size {
+            Some(max_output_size)
+                if (output.stdout.len() + output.stderr.len()) as u64 > max_output_size.0 =>
+            {
+                Err(TestCaseError::ExceededMaxOutputSize {
+                    max_output_size_bytes: max_output_size.0,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Validate that the output is valid UTF-8, if configured so with
+    /// [`TestCaseConfig::invalid_utf8`]
+    fn validate_utf8(&self, output: &Output) -> Result<()> {
+        if self.config.invalid_utf8 != Some(InvalidUtf8::Error)
+            || matches!(
+                self.config.output_encoding,
+                Some(OutputEncoding::Bytes | OutputEncoding::Hash)
+            )
+        {
+            return Ok(());
+        }
+        match std::str::from_utf8(&self.encoded_output(output)) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(TestCaseError::InvalidUtf8Output {
+                offset: err.valid_up_to(),
+            }),
+        }
+    }
+
+    /// Validate that the otherwise valid execution did not use more memory
+    /// than the configured [`TestCaseConfig::max_rss`]
+    fn validate_rss(&self, output: &Output) -> Result<()> {
+        match (output.resource_usage, self.config.max_rss) {
+            (Some(usage), Some(max_rss)) if usage.max_rss_bytes > max_rss.0 => {
+                Err(TestCaseError::ExceededMaxRss {
+                    rss_bytes: usage.max_rss_bytes,
+                    max_rss_bytes: max_rss.0,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the exit code that the execution is expected to end with, which
+    /// is the signal from [`TestCaseConfig::expected_signal`], if configured
+    pub fn expected_exit_code(&self) -> ExpectedExitCode {
+        match &self.config.expected_signal {
+            Some(signal) if !signal.is_off() => {
+                ExpectedExitCode::Signal(signal.to_string().to_uppercase())
+            }
+            _ => self.exit_code.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Resolves the paths that expectations refer to, like golden files to
+    /// compare output with or commands of custom rules, against the directory
+    /// of the test document
+    pub fn resolve_document_paths(&mut self, document_directory: &Path) {
+        for expectation in self
+            .variants
+            .iter_mut()
+            .flatten()
+            .chain(&mut self.expectations)
+        {
+            if let Some(rule) = expectation.rule.with_document_directory(document_directory) {
+                expectation.rule = rule;
             }
         }
-        let diff_tool = DiffTool::new(self.expectations.clone());
+        if let Some(ref mut assert_files) = self.config.assert_files {
+            assert_files.load_golden_files(document_directory);
+        }
+    }
+
+    /// Returns the (hex encoded) SHA-256 digests of the files that checksum
+    /// expectations and assertions refer to, with relative paths being
+    /// resolved from the given work directory, or `None` for files that
+    /// cannot be read
+    pub fn produced_file_digests(&self, work_directory: &Path) -> BTreeMap<String, Option<String>> {
+        self.expectations
+            .iter()
+            .chain(self.variants.iter().flatten())
+            .filter_map(|expectation| expectation.rule.file_checksum())
+            .map(|(path, _)| path)
+            .chain(
+                self.assertions
+                    .iter()
+                    .filter_map(|assertion| assertion.file_path()),
+            )
+            .map(|path| {
+                (
+                    path.to_string(),
+                    sha256_file_hex(&work_directory.join(path)),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the state of the files and directories that the configured
+    /// [`TestCaseConfig::assert_files`] refer to, with relative paths being
+    /// resolved from the given work directory
+    pub fn produced_files(&self, work_directory: &Path) -> BTreeMap<String, ProducedFile> {
+        let Some(ref assert_files) = self.config.assert_files else {
+            return BTreeMap::new();
+        };
+        let mut produced_files = assert_files
+            .paths()
+            .map(|path| {
+                (
+                    path.to_string(),
+                    ProducedFile::read(&work_directory.join(path)),
+                )
+            })
+            .collect::<BTreeMap<_, _>>();
+        for (path, tree) in &assert_files.trees {
+            produced_files.insert(path.clone(), tree.read(&work_directory.join(path)));
+        }
+        produced_files
+    }
+
+    /// Returns the output stream that the expectations apply to, in the
+    /// configured [`TestCaseConfig::output_encoding`]
+    pub fn encoded_output<'a>(&self, output: &'a Output) -> Cow<'a, [u8]> {
         let stream = if self.config.output_stream == Some(OutputStreamControl::Stderr) {
             &output.stderr
         } else {
             &output.stdout
         };
-        let diff = diff_tool
-            .diff(stream.into())
-            .map_err(TestCaseError::InternalError)?;
-        if diff.has_differences() {
-            Err(TestCaseError::MalformedOutput(diff))
-        } else {
-            Ok(())
+        match self.config.output_encoding {
+            Some(OutputEncoding::Bytes) => Cow::Owned(hex_dump(stream.into())),
+            _ => Cow::Borrowed(stream.into()),
         }
     }
 
-    /// Returns output with configured transformations applied:
+    /// Returns output with configured transformations applied, unless it is
+    /// compared as raw bytes (see [`TestCaseConfig::output_encoding`]):
     /// - Remove CRLF?
     /// - Strip ANSI escaping?
+    /// - Replace, escape or reject bytes that are not valid UTF-8?
+    /// - Normalize Unicode?
     pub fn render_output<'a>(&self, output: &'a [u8]) -> anyhow::Result<Cow<'a, [u8]>> {
-        let processed_output = if self.config.keep_crlf != Some(true) {
-            replace_crlf(output)
+        if matches!(
+            self.config.output_encoding,
+            Some(OutputEncoding::Bytes | OutputEncoding::Hash)
+        ) {
+            return Ok(Cow::Borrowed(output));
+        }
+        let processed_output =
+            if self.config.keep_crlf != Some(true) || self.config.normalize_crlf == Some(true) {
+                replace_crlf(output)
+            } else {
+                Cow::Borrowed(output)
+            };
+
+        let processed_output = if self.config.strip_ansi_escaping == Some(true) {
+            Cow::Owned(strip_colors_bytes(&processed_output)?)
         } else {
-            Cow::Borrowed(output)
+            processed_output
         };
 
-        if self.config.strip_ansi_escaping == Some(true) {
-            Ok(Cow::Owned(strip_colors_bytes(&processed_output)?))
-        } else {
-            Ok(processed_output)
+        let processed_output = match self.config.invalid_utf8 {
+            Some(InvalidUtf8::Escape) => Cow::Owned(escaped_invalid_utf8(&processed_output)),
+            Some(InvalidUtf8::Replace) => match std::str::from_utf8(&processed_output) {
+                Ok(_) => processed_output,
+                Err(_) => Cow::Owned(
+                    String::from_utf8_lossy(&processed_output)
+                        .into_owned()
+                        .into_bytes(),
+                ),
+            },
+
+            // invalid bytes fail the validation, see `TestCase::validate_utf8`
+            Some(InvalidUtf8::Error) | None => processed_output,
+        };
+
+        match self.config.unicode_normalization {
+            Some(normalization) => Ok(match normalization.normalize_bytes(&processed_output) {
+                Cow::Owned(normalized) => Cow::Owned(normalized),
+                Cow::Borrowed(_) => processed_output,
+            }),
+            None => Ok(processed_output),
         }
     }
 
+    /// Returns output like [`TestCase::render_output`], but takes ownership
+    /// of it, so that it is not copied if no transformation applies
+    pub fn render_owned_output(&self, output: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        let rendered = match self.render_output(&output)? {
+            Cow::Owned(rendered) => Some(rendered),
+            Cow::Borrowed(_) => None,
+        };
+        Ok(rendered.unwrap_or(output))
+    }
+
+    /// Returns output with all secrets that are configured in
+    /// [`TestCaseConfig::redact`] replaced with [`REDACTED`]. Values of
+    /// environment variables are looked up in the given environment first and
+    /// then in the environment Scrut is run in.
+    pub fn redact_output(
+        &self,
+        output: Output,
+        environment: &BTreeMap<String, String>,
+    ) -> anyhow::Result<Output> {
+        if self.config.redact.is_empty() {
+            return Ok(output);
+        }
+
+        let mut values = vec![];
+        let mut patterns = vec![];
+        for entry in &self.config.redact {
+            if is_environment_variable_name(entry) {
+                if let Some(value) = environment
+                    .get(entry)
+                    .cloned()
+                    .or_else(|| std::env::var(entry).ok())
+                    .filter(|value| !value.is_empty())
+                {
+                    values.push(value);
+                }
+            } else {
+                patterns.push(
+                    Regex::new(entry)
+                        .with_context(|| format!("invalid redact expression `{entry}`"))?,
+                );
+            }
+        }
+
+        // redact longer values first, so that values that contain other values
+        // are not redacted only partially
+        values.sort_by_key(|value| std::cmp::Reverse(value.len()));
+        let patterns = values
+            .iter()
+            .map(|value| Regex::new(&regex::escape(value)).expect("escaped value is valid"))
+            .chain(patterns)
+            .collect::<Vec<_>>();
+        let redact = |stream: &[u8]| {
+            patterns
+                .iter()
+                .fold(stream.to_vec(), |redacted, pattern| {
+                    pattern
+                        .replace_all(&redacted, REDACTED.as_bytes())
+                        .into_owned()
+                })
+                .into()
+        };
+
+        Ok(Output {
+            stdout: redact(&output.stdout.to_bytes()),
+            stderr: redact(&output.stderr.to_bytes()),
+            ..output
+        })
+    }
+
+    /// Returns output in which paths are normalized, if
+    /// [`TestCaseConfig::normalize_paths`] is enabled: backslashes are
+    /// replaced with forward slashes, drive letters are removed and paths
+    /// within the given work directory are made relative to it.
+    pub fn normalize_paths_in_output(&self, output: Output, work_directory: &Path) -> Output {
+        if self.config.normalize_paths != Some(true) {
+            return output;
+        }
+
+        // the work directory may be provided in a different form than the
+        // shell prints it (e.g. symlinked temporary directories on macOS)
+        let mut prefixes = vec![normalize_path_string(&work_directory.to_string_lossy())];
+        if let Ok(canonical) = dunce::canonicalize(work_directory) {
+            let canonical = normalize_path_string(&canonical.to_string_lossy());
+            if !prefixes.contains(&canonical) {
+                prefixes.push(canonical);
+            }
+        }
+        prefixes.retain(|prefix| !prefix.is_empty());
+        prefixes.sort_by_key(|prefix| std::cmp::Reverse(prefix.len()));
+        let prefixes = prefixes
+            .iter()
+            .map(|prefix| {
+                Regex::new(&format!(r"(?m){}(/|$|[^\w./-])", regex::escape(prefix)))
+                    .expect("escaped prefix is valid")
+            })
+            .collect::<Vec<_>>();
+
+        let normalize = |stream: &[u8]| {
+            let slashed = stream
+                .iter()
+                .map(|&c| if c == b'\\' { b'/' } else { c })
+                .collect::<Vec<_>>();
+            let normalized = DRIVE_LETTER_EXPRESSION
+                .replace_all(&slashed, b"${1}/".as_slice())
+                .into_owned();
+
+            // the work directory itself becomes `.`, paths within it relative
+            prefixes
+                .iter()
+                .fold(normalized, |normalized, prefix| {
+                    prefix
+                        .replace_all(&normalized, |captures: &Captures| match &captures[1] {
+                            b"/" => vec![],
+                            suffix => [b".".as_slice(), suffix].concat(),
+                        })
+                        .into_owned()
+                })
+                .into()
+        };
+
+        Output {
+            stdout: normalize(&output.stdout.to_bytes()),
+            stderr: normalize(&output.stderr.to_bytes()),
+            ..output
+        }
+    }
+
+    /// Returns output in which the home directory and the name of the user
+    /// are replaced with [`HOME_PLACEHOLDER`] and [`USER_PLACEHOLDER`], if
+    /// [`TestCaseConfig::anonymize_user`] is enabled. Both are looked up in the
+    /// given environment first and then in the environment Scrut is run in.
+    pub fn anonymize_user_in_output(
+        &self,
+        output: Output,
+        environment: &BTreeMap<String, String>,
+    ) -> Output {
+        if self.config.anonymize_user != Some(true) {
+            return output;
+        }
+
+        let lookup = |names: &[&str]| {
+            names.iter().find_map(|name| {
+                environment
+                    .get(*name)
+                    .cloned()
+                    .or_else(|| std::env::var(name).ok())
+                    .filter(|value| !value.is_empty())
+            })
+        };
+
+        // paths are already normalized at this point, if configured
+        let home = lookup(&["HOME", "USERPROFILE"])
+            .map(|home| {
+                if self.config.normalize_paths == Some(true) {
+                    normalize_path_string(&home)
+                } else {
+                    home.trim_end_matches(['/', '\\']).to_string()
+                }
+            })
+            .filter(|home| !home.is_empty())
+            .map(|home| {
+                Regex::new(&format!(r"(?m){}([/\\]|$|[^\w.-])", regex::escape(&home)))
+                    .expect("escaped home directory is valid")
+            });
+        let user = lookup(&["USER", "USERNAME", "LOGNAME"]).map(|user| {
+            Regex::new(&format!(r"\b{}\b", regex::escape(&user)))
+                .expect("escaped user name is valid")
+        });
+
+        let anonymize = |stream: &[u8]| {
+            let mut anonymized = stream.to_vec();
+            if let Some(ref home) = home {
+                anonymized = home
+                    .replace_all(&anonymized, |captures: &Captures| {
+                        [HOME_PLACEHOLDER.as_bytes(), &captures[1]].concat()
+                    })
+                    .into_owned();
+            }
+            if let Some(ref user) = user {
+                anonymized = user
+                    .replace_all(&anonymized, NoExpand(USER_PLACEHOLDER.as_bytes()))
+                    .into_owned();
+            }
+            anonymized.into()
+        };
+
+        Output {
+            stdout: anonymize(&output.stdout.to_bytes()),
+            stderr: anonymize(&output.stderr.to_bytes()),
+            ..output
+        }
+    }
+
+    /// Whether the testcase asserts nothing, because it has neither output
+    /// expectations (or variants of them), nor an expected exit code, nor
+    /// assertions (of files)
+    pub fn asserts_nothing(&self) -> bool {
+        self.expectations.is_empty()
+            && self.variants.is_empty()
+            && self.exit_code.is_none()
+            && self.assertions.is_empty()
+            && self
+                .config
+                .assert_files
+                .as_ref()
+                .is_none_or(FileAssertions::is_empty)
+    }
+
     #[cfg(test)]
     pub fn from_expression(expression: &str) -> Self {
         Self {
@@ -129,9 +775,33 @@ impl TestCase {
 
     pub(crate) fn expectations_lines(&self) -> usize {
         self.expectations.len()
+            + self
+                .variants
+                .iter()
+                .map(|variant| variant.len() + 1)
+                .sum::<usize>()
     }
 }
 
+/// Whether the given entry of [`TestCaseConfig::redact`] names an environment
+/// variable, as opposed to being a regular expression
+fn is_environment_variable_name(entry: &str) -> bool {
+    let mut chars = entry.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Returns the given path with backslashes replaced with forward slashes and
+/// without a leading drive letter or trailing slashes
+fn normalize_path_string(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    String::from_utf8_lossy(&DRIVE_LETTER_EXPRESSION.replace(path.as_bytes(), b"${1}/".as_slice()))
+        .trim_end_matches('/')
+        .to_string()
+}
+
 impl Display for TestCase {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let map = json!({
@@ -141,7 +811,7 @@ impl Display for TestCase {
                 .iter()
                 .map(|e| Value::String(e.to_expression_string(&Default::default())))
                 .collect::<Vec<_>>(),
-            "exit_code": self.exit_code.unwrap_or(0),
+            "exit_code": self.exit_code.clone().unwrap_or_default(),
             "line_number": self.line_number,
             "config": &self.config,
         });
@@ -150,17 +820,202 @@ impl Display for TestCase {
     }
 }
 
-fn serialize_always_as_value<S>(x: &Option<i32>, s: S) -> std::result::Result<S::Ok, S::Error>
+/// A step of an interactive session with an execution that is attached to a
+/// pseudo-terminal, as in:
+///
+/// ```txt
+/// $ python3 -i
+/// ? >>> $
+/// ! print(1 + 2)
+/// ? >>> $
+/// ! exit()
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InteractionStep {
+    /// Wait until the output that was received since the previous match
+    /// matches the regular expression (line `? <regex>`)
+    Expect(String),
+
+    /// Write the text, followed by Enter, into the terminal (line `! <text>`)
+    Send(String),
+}
+
+impl InteractionStep {
+    /// Parse a line of a testcase body into an interaction step, if it is one
+    pub fn parse(line: &str) -> Option<anyhow::Result<Self>> {
+        if let Some(pattern) = line.strip_prefix("? ") {
+            return Some(
+                Regex::new(pattern)
+                    .map(|_| Self::Expect(pattern.into()))
+                    .with_context(|| format!("invalid expect pattern `{}`", pattern)),
+            );
+        }
+        if line == "!" {
+            return Some(Ok(Self::Send("".into())));
+        }
+        line.strip_prefix("! ")
+            .map(|text| Ok(Self::Send(text.into())))
+    }
+}
+
+impl Display for InteractionStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Expect(pattern) => write!(f, "? {}", pattern),
+            Self::Send(text) if text.is_empty() => write!(f, "!"),
+            Self::Send(text) => write!(f, "! {}", text),
+        }
+    }
+}
+
+/// The exit code that an execution is expected to end with, as written in
+/// the last line of a testcase body (`[<exit code>]`):
+///
+/// ```txt
+/// [1]             exactly 1
+/// [1-127]         any of 1 to 127 (inclusive)
+/// [!0]            anything but 0
+/// [exit:137]      exactly 137, but not from a termination by signal 9
+/// [signal:TERM]   terminated by the signal, which the shell reports as 128+N
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExpectedExitCode {
+    /// Exactly this exit code
+    Code(i32),
+
+    /// Any exit code in the inclusive range
+    Range(i32, i32),
+
+    /// Any exit code but this one
+    Not(i32),
+
+    /// Exactly this exit code, which the execution exited with, as opposed to
+    /// the shell being terminated by a signal that is reported as this code
+    Exit(i32),
+
+    /// Exit code of a command that was terminated by the signal (`128 + N`),
+    /// with the name of the signal (without "SIG" prefix) or its number
+    Signal(String),
+}
+
+impl ExpectedExitCode {
+    /// Whether the actual exit status is what is expected. Signals that
+    /// terminated the shell count as exit code `128 + N`, unless an exit is
+    /// expected explicitly (see [`ExpectedExitCode::Exit`]).
+    pub fn matches(&self, status: &ExitStatus) -> bool {
+        let actual = status.as_code();
+        match self {
+            Self::Code(code) => actual == *code,
+            Self::Exit(code) => *status == ExitStatus::Code(*code),
+            Self::Range(from, to) => (*from..=*to).contains(&actual),
+            Self::Not(code) => actual != *code,
+            Self::Signal(name) => {
+                signal_number_of(name).is_some_and(|number| actual == 128 + number)
+            }
+        }
+    }
+}
+
+/// Returns the number of a signal that is given by name or number
+fn signal_number_of(name: &str) -> Option<i32> {
+    name.parse::<i32>().ok().or_else(|| signal_number(name))
+}
+
+impl Default for ExpectedExitCode {
+    fn default() -> Self {
+        Self::Code(0)
+    }
+}
+
+impl From<i32> for ExpectedExitCode {
+    fn from(value: i32) -> Self {
+        Self::Code(value)
+    }
+}
+
+impl FromStr for ExpectedExitCode {
+    type Err = anyhow::Error;
+
+    /// Parse the exit code from within the square brackets (`1-127` of `[1-127]`)
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        if let Some(name) = value.strip_prefix("signal:") {
+            let number = signal_number_of(name)
+                .with_context(|| format!("unknown signal `{}` in exit code", name))?;
+            if !(1..128).contains(&number) {
+                bail!("signal number `{}` in exit code is out of range", number);
+            }
+            let name = match name.parse::<i32>() {
+                Ok(_) => name.to_string(),
+                Err(_) => {
+                    let name = name.to_uppercase();
+                    name.strip_prefix("SIG").unwrap_or(&name).to_string()
+                }
+            };
+            return Ok(Self::Signal(name));
+        }
+        if let Some(code) = value.strip_prefix("exit:") {
+            return Ok(Self::Exit(parse_exit_code(code)?));
+        }
+        if let Some(code) = value.strip_prefix('!') {
+            return Ok(Self::Not(parse_exit_code(code)?));
+        }
+        if let Some((from, to)) = value.split_once('-') {
+            let (from, to) = (parse_exit_code(from)?, parse_exit_code(to)?);
+            if from > to {
+                bail!("exit code range `{}` is empty", value);
+            }
+            return Ok(Self::Range(from, to));
+        }
+        Ok(Self::Code(parse_exit_code(value)?))
+    }
+}
+
+fn parse_exit_code(value: &str) -> anyhow::Result<i32> {
+    value
+        .parse::<i32>()
+        .with_context(|| format!("invalid exit code `{}`", value))
+}
+
+impl Display for ExpectedExitCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Code(code) => write!(f, "{}", code),
+            Self::Range(from, to) => write!(f, "{}-{}", from, to),
+            Self::Not(code) => write!(f, "!{}", code),
+            Self::Exit(code) => write!(f, "exit:{}", code),
+            Self::Signal(name) => write!(f, "signal:{}", name),
+        }
+    }
+}
+
+impl Serialize for ExpectedExitCode {
+    /// Exact exit codes are serialized as number, all others as string
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Code(code) => serializer.serialize_i32(*code),
+            _ => serializer.serialize_str(&self.to_string()),
+        }
+    }
+}
+
+fn serialize_always_as_value<S>(
+    x: &Option<ExpectedExitCode>,
+    s: S,
+) -> std::result::Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    s.serialize_i32(x.unwrap_or(0))
+    x.clone().unwrap_or_default().serialize(s)
 }
 
 /// An error that occurs when the actual output of an execution does not
 /// match with the expectations.
 ///
-/// There are four causes why an error can be raised:
+/// These are the causes why an error can be raised:
 /// 1) MalformedOutput: A line of output does not match the expected content or form
 /// 2) UnexpectedOutput: There are more lines of output than there are
 ///    expectations to validate the output. Hence the additional output is
@@ -169,13 +1024,26 @@ where
 ///    means some of the expectations could never be applied and must be
 ///    considered failed (assuming they are non-optional)
 /// 4) InternalError: An error occurred during processing, e.g. invalid UTF8
+/// 5) NonDeterministic: A repeated execution yielded a different output
+/// 6) NoAssertions: The testcase asserts nothing, which is not allowed (see
+///    [`TestCaseConfig::allow_empty_tests`])
+/// 7) FailedAssertion: The execution does not satisfy an `@assert` line
+/// 8) MultipleFailures: Both the output and the exit code do not match (see
+///    [`TestCaseConfig::collect_all_failures`])
+/// 9) ExceededMaxOutputSize: The execution produced more output than allowed
+///    (see [`TestCaseConfig::max_output_size`])
+/// 10) InvalidUtf8Output: The output is not valid UTF-8, which is not allowed
+///     (see [`TestCaseConfig::invalid_utf8`])
 #[derive(Debug)]
 pub enum TestCaseError {
     /// The validation of the expectation for the given line failed (invalid input)
     MalformedOutput(Diff),
 
     /// An execution ends in an unexpected exit code
-    InvalidExitCode { actual: i32, expected: i32 },
+    InvalidExitCode {
+        actual: i32,
+        expected: ExpectedExitCode,
+    },
 
     /// Delegated internal errors, e.g. relating to decoding
     InternalError(anyhow::Error),
@@ -185,6 +1053,40 @@ pub enum TestCaseError {
 
     /// Whether this test was skipped intentionally
     Skipped,
+
+    /// A repeated execution of the test case (see `--audit-determinism`)
+    /// resulted in the contained output, which differs from the first one
+    NonDeterministic(Box<Output>),
+
+    /// The testcase has neither output expectations nor an expected exit
+    /// code, which is not allowed by its configuration
+    NoAssertions,
+
+    /// The execution succeeded, but took longer than the configured
+    /// [`TestCaseConfig::max_duration`]
+    ExceededMaxDuration {
+        duration: Duration,
+        max_duration: Duration,
+    },
+
+    /// The execution succeeded, but its largest process reached a higher
+    /// resident set size than the configured [`TestCaseConfig::max_rss`]
+    ExceededMaxRss { rss_bytes: u64, max_rss_bytes: u64 },
+
+    /// The execution produced more output than the configured
+    /// [`TestCaseConfig::max_output_size`], so it was ended early
+    ExceededMaxOutputSize { max_output_size_bytes: u64 },
+
+    /// The output contains bytes that are not valid UTF-8, starting at the
+    /// given offset, which is not allowed by [`TestCaseConfig::invalid_utf8`]
+    InvalidUtf8Output { offset: usize },
+
+    /// The execution does not satisfy an `@assert` line of the testcase
+    FailedAssertion { assertion: String, reason: String },
+
+    /// More than one of the above, in the order they appear in the testcase,
+    /// which are only collected if configured so
+    MultipleFailures(Vec<TestCaseError>),
 }
 
 impl PartialEq for TestCaseError {
@@ -202,6 +1104,51 @@ impl PartialEq for TestCaseError {
                 },
             ) => l_actual == r_actual && l_expected == r_expected,
             (Self::InternalError(l0), Self::InternalError(r0)) => l0.to_string() == r0.to_string(),
+            (Self::NonDeterministic(l0), Self::NonDeterministic(r0)) => l0 == r0,
+            (Self::NoAssertions, Self::NoAssertions) => true,
+            (
+                Self::ExceededMaxDuration {
+                    duration: l_duration,
+                    max_duration: l_max_duration,
+                },
+                Self::ExceededMaxDuration {
+                    duration: r_duration,
+                    max_duration: r_max_duration,
+                },
+            ) => l_duration == r_duration && l_max_duration == r_max_duration,
+            (
+                Self::ExceededMaxRss {
+                    rss_bytes: l_rss_bytes,
+                    max_rss_bytes: l_max_rss_bytes,
+                },
+                Self::ExceededMaxRss {
+                    rss_bytes: r_rss_bytes,
+                    max_rss_bytes: r_max_rss_bytes,
+                },
+            ) => l_rss_bytes == r_rss_bytes && l_max_rss_bytes == r_max_rss_bytes,
+            (
+                Self::ExceededMaxOutputSize {
+                    max_output_size_bytes: l_max_output_size_bytes,
+                },
+                Self::ExceededMaxOutputSize {
+                    max_output_size_bytes: r_max_output_size_bytes,
+                },
+            ) => l_max_output_size_bytes == r_max_output_size_bytes,
+            (
+                Self::InvalidUtf8Output { offset: l_offset },
+                Self::InvalidUtf8Output { offset: r_offset },
+            ) => l_offset == r_offset,
+            (
+                Self::FailedAssertion {
+                    assertion: l_assertion,
+                    reason: l_reason,
+                },
+                Self::FailedAssertion {
+                    assertion: r_assertion,
+                    reason: r_reason,
+                },
+            ) => l_assertion == r_assertion && l_reason == r_reason,
+            (Self::MultipleFailures(l0), Self::MultipleFailures(r0)) => l0 == r0,
             (_, _) => false,
         }
     }
@@ -242,18 +1189,91 @@ impl Serialize for TestCaseError {
                 variant.serialize_entry("kind", "skipped")?;
                 variant.end()
             }
+            Self::NonDeterministic(output) => {
+                let mut variant = serializer.serialize_map(Some(2))?;
+                variant.serialize_entry("kind", "non_deterministic")?;
+                variant.serialize_entry("repeated_output", output)?;
+                variant.end()
+            }
+            Self::NoAssertions => {
+                let mut variant = serializer.serialize_map(Some(1))?;
+                variant.serialize_entry("kind", "no_assertions")?;
+                variant.end()
+            }
+            Self::ExceededMaxDuration {
+                duration,
+                max_duration,
+            } => {
+                let mut variant = serializer.serialize_map(Some(3))?;
+                variant.serialize_entry("kind", "exceeded_max_duration")?;
+                variant.serialize_entry("duration_ms", &duration.as_millis())?;
+                variant.serialize_entry("max_duration_ms", &max_duration.as_millis())?;
+                variant.end()
+            }
+            Self::ExceededMaxRss {
+                rss_bytes,
+                max_rss_bytes,
+            } => {
+                let mut variant = serializer.serialize_map(Some(3))?;
+                variant.serialize_entry("kind", "exceeded_max_rss")?;
+                variant.serialize_entry("rss_bytes", rss_bytes)?;
+                variant.serialize_entry("max_rss_bytes", max_rss_bytes)?;
+                variant.end()
+            }
+            Self::ExceededMaxOutputSize {
+                max_output_size_bytes,
+            } => {
+                let mut variant = serializer.serialize_map(Some(2))?;
+                variant.serialize_entry("kind", "exceeded_max_output_size")?;
+                variant.serialize_entry("max_output_size_bytes", max_output_size_bytes)?;
+                variant.end()
+            }
+            Self::InvalidUtf8Output { offset } => {
+                let mut variant = serializer.serialize_map(Some(2))?;
+                variant.serialize_entry("kind", "invalid_utf8_output")?;
+                variant.serialize_entry("offset", offset)?;
+                variant.end()
+            }
+            Self::FailedAssertion { assertion, reason } => {
+                let mut variant = serializer.serialize_map(Some(3))?;
+                variant.serialize_entry("kind", "failed_assertion")?;
+                variant.serialize_entry("assertion", assertion)?;
+                variant.serialize_entry("reason", reason)?;
+                variant.end()
+            }
+            Self::MultipleFailures(failures) => {
+                let mut variant = serializer.serialize_map(Some(2))?;
+                variant.serialize_entry("kind", "multiple_failures")?;
+                variant.serialize_entry("failures", failures)?;
+                variant.end()
+            }
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+    use std::path::Path;
+    use std::time::Duration;
+
+    use super::ExpectedExitCode;
     use super::TestCase;
     use super::TestCaseError;
+    use crate::assertions::registry::AssertionRegistry;
+    use crate::config::ByteSize;
+    use crate::config::InvalidUtf8;
+    use crate::config::OutputEncoding;
     use crate::config::TestCaseConfig;
     use crate::diff::Diff;
     use crate::diff::DiffLine;
+    use crate::diff::DiffTool;
     use crate::lossy_string;
+    use crate::output::ExitStatus;
+    use crate::output::Output;
+    use crate::output::ResourceUsage;
+    use crate::rules::checksum::sha256_hex;
+    use crate::rules::variables::Variables;
     use crate::test_expectation;
 
     #[test]
@@ -262,7 +1282,7 @@ mod tests {
             title: "an testcase".to_string(),
             shell_expression: "a command".to_string(),
             expectations: vec![test_expectation!("no-eol", "the stdout")],
-            exit_code: Some(123),
+            exit_code: Some(123.into()),
             line_number: 234,
             ..Default::default()
         };
@@ -271,13 +1291,379 @@ mod tests {
             .expect("no error");
     }
 
+    #[test]
+    fn test_validate_expected_exit_code_classes() {
+        let cases = [
+            ("1-127", 1, true),
+            ("1-127", 127, true),
+            ("1-127", 0, false),
+            ("1-127", 128, false),
+            ("!0", 2, true),
+            ("!0", 0, false),
+            ("signal:TERM", 143, true),
+            ("signal:SIGKILL", 137, true),
+            ("signal:9", 137, true),
+            ("signal:TERM", 15, false),
+        ];
+        for (expected, actual, valid) in cases {
+            let testcase = TestCase {
+                shell_expression: "a command".to_string(),
+                exit_code: Some(expected.parse().expect("valid exit code")),
+                ..Default::default()
+            };
+            assert_eq!(
+                valid,
+                testcase.validate(&("", "", Some(actual)).into()).is_ok(),
+                "exit code {} against [{}]",
+                actual,
+                expected,
+            );
+        }
+        assert!("5-1".parse::<ExpectedExitCode>().is_err(), "empty range");
+        assert!(
+            "signal:NOPE".parse::<ExpectedExitCode>().is_err(),
+            "unknown signal"
+        );
+    }
+
+    #[test]
+    fn test_validate_expected_exit_distinct_from_signal() {
+        let cases = [
+            ("exit:137", ExitStatus::Code(137), true),
+            ("exit:137", ExitStatus::Signal(9), false),
+            ("137", ExitStatus::Signal(9), true),
+            ("signal:SIGKILL", ExitStatus::Signal(9), true),
+            ("signal:SIGKILL", ExitStatus::Code(137), true),
+        ];
+        for (expected, status, valid) in cases {
+            let testcase = TestCase {
+                shell_expression: "a command".to_string(),
+                exit_code: Some(expected.parse().expect("valid exit code")),
+                ..Default::default()
+            };
+            let output = Output {
+                exit_code: status.clone(),
+                ..("", "").into()
+            };
+            assert_eq!(
+                valid,
+                testcase.validate(&output).is_ok(),
+                "status {:?} against [{}]",
+                status,
+                expected,
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_expected_signal() {
+        let testcase = TestCase {
+            shell_expression: "a command".to_string(),
+            exit_code: Some(0.into()),
+            config: TestCaseConfig {
+                expected_signal: Some(serde_yaml::from_str("segv").expect("valid signal")),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            ExpectedExitCode::Signal("SEGV".into()),
+            testcase.expected_exit_code()
+        );
+        let cases = [
+            (ExitStatus::Signal(11), true),
+            (ExitStatus::Code(139), true),
+            (ExitStatus::Signal(15), false),
+            (ExitStatus::Code(0), false),
+        ];
+        for (status, valid) in cases {
+            let mut output: Output = ("", "").into();
+            output.exit_code = status.clone();
+            assert_eq!(
+                valid,
+                testcase.validate(&output).is_ok(),
+                "status {:?}",
+                status,
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_max_duration() {
+        let testcase = TestCase {
+            shell_expression: "a command".to_string(),
+            expectations: vec![test_expectation!("equal", "the stdout")],
+            config: TestCaseConfig {
+                max_duration: Some(Duration::from_millis(500)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let output = |stdout: &str, millis: u64| Output {
+            duration: Some(Duration::from_millis(millis)),
+            ..(stdout, "").into()
+        };
+        testcase
+            .validate(&output("the stdout\n", 500))
+            .expect("within max duration");
+        assert_eq!(
+            Err(TestCaseError::ExceededMaxDuration {
+                duration: Duration::from_millis(501),
+                max_duration: Duration::from_millis(500),
+            }),
+            testcase.validate(&output("the stdout\n", 501)),
+        );
+        assert!(
+            matches!(
+                testcase.validate(&output("other stdout\n", 501)),
+                Err(TestCaseError::MalformedOutput(_))
+            ),
+            "invalid output takes precedence"
+        );
+    }
+
+    #[test]
+    fn test_validate_absences() {
+        let testcase = TestCase {
+            shell_expression: "a command".to_string(),
+            expectations: vec![
+                test_expectation!("not", "ERROR"),
+                test_expectation!("glob", "line *"),
+            ],
+            ..Default::default()
+        };
+        testcase
+            .validate(&("line 1\n", "", Some(0)).into())
+            .expect("no line matches");
+        assert_eq!(
+            Err(TestCaseError::FailedAssertion {
+                assertion: "ERROR (not)".to_string(),
+                reason: "line 1 of output matches: line 1 ERROR".to_string(),
+            }),
+            testcase.validate(&("line 1 ERROR\n", "", Some(0)).into()),
+        );
+
+        let testcase = TestCase {
+            config: TestCaseConfig {
+                collect_all_failures: Some(true),
+                ..Default::default()
+            },
+            ..testcase
+        };
+        assert_eq!(
+            Err(TestCaseError::MultipleFailures(vec![
+                TestCaseError::MalformedOutput(
+                    DiffTool::new(testcase.expectations.clone())
+                        .diff(b"ERROR\nline 2 ERROR\n")
+                        .expect("no error")
+                ),
+                TestCaseError::FailedAssertion {
+                    assertion: "ERROR (not)".to_string(),
+                    reason: "line 1 of output matches: ERROR (and 1 more)".to_string(),
+                },
+            ])),
+            testcase.validate(&("ERROR\nline 2 ERROR\n", "", Some(0)).into()),
+        );
+    }
+
+    #[test]
+    fn test_validate_with_variables() {
+        let testcase = TestCase {
+            shell_expression: "a command".to_string(),
+            expectations: vec![
+                test_expectation!("regex", r"job (?P<job>\d+) started"),
+                test_expectation!("glob", "*"),
+                test_expectation!("template", "job %{job} done"),
+            ],
+            ..Default::default()
+        };
+        let mut variables = Variables::new();
+        testcase
+            .validate_with_variables(
+                &("job 12 started\nworking\njob 12 done\n", "", Some(0)).into(),
+                &mut variables,
+            )
+            .expect("captured job matches");
+        assert_eq!(Some(&"12".to_string()), variables.get("job"));
+        assert!(matches!(
+            testcase.validate(&("job 12 started\nworking\njob 13 done\n", "", Some(0)).into()),
+            Err(TestCaseError::MalformedOutput(_))
+        ));
+
+        let later = TestCase {
+            shell_expression: "another command".to_string(),
+            expectations: vec![test_expectation!("regex", r"stopped %{job}")],
+            ..Default::default()
+        };
+        later
+            .validate_with_variables(&("stopped 12\n", "", Some(0)).into(), &mut variables)
+            .expect("job of preceding testcase matches");
+        later
+            .validate(&("stopped 12\n", "", Some(0)).into())
+            .expect_err("undefined job matches nothing");
+    }
+
+    #[test]
+    fn test_validate_variants() {
+        let testcase = TestCase {
+            shell_expression: "a command".to_string(),
+            expectations: vec![test_expectation!("equal", "linux")],
+            variants: vec![
+                vec![test_expectation!("regex", r"darwin (?P<version>\d+)")],
+                vec![test_expectation!("equal", "windows")],
+            ],
+            ..Default::default()
+        };
+        for output in ["linux\n", "windows\n"] {
+            testcase
+                .validate(&(output, "", Some(0)).into())
+                .unwrap_or_else(|err| panic!("variant matches {output:?}: {err:?}"));
+        }
+
+        let mut variables = Variables::new();
+        testcase
+            .validate_with_variables(&("darwin 23\n", "", Some(0)).into(), &mut variables)
+            .expect("variant matches");
+        assert_eq!(Some(&"23".to_string()), variables.get("version"));
+
+        assert_eq!(
+            Err(TestCaseError::MalformedOutput(
+                DiffTool::new(testcase.expectations.clone())
+                    .diff(b"freebsd\n")
+                    .expect("no error")
+            )),
+            testcase.validate(&("freebsd\n", "", Some(0)).into()),
+            "differences to the first variant are shown"
+        );
+    }
+
+    #[test]
+    fn test_validate_assertions() {
+        let registry = AssertionRegistry::default();
+        let testcase = TestCase {
+            shell_expression: "a command".to_string(),
+            expectations: vec![test_expectation!("equal", "the stdout")],
+            assertions: ["@assert exit_code in 0..2", "@assert file_exists out.txt"]
+                .iter()
+                .map(|line| registry.parse(line).unwrap().expect("make assertion"))
+                .collect(),
+            ..Default::default()
+        };
+        let output = |exit_code: i32, file_digest: Option<&str>| Output {
+            file_digests: BTreeMap::from([("out.txt".to_string(), file_digest.map(String::from))]),
+            ..("the stdout\n", "", Some(exit_code)).into()
+        };
+        testcase
+            .validate(&output(1, Some("abc")))
+            .expect("assertions replace the default exit code");
+        assert_eq!(
+            Err(TestCaseError::MultipleFailures(vec![
+                TestCaseError::FailedAssertion {
+                    assertion: "@assert exit_code in 0..2".to_string(),
+                    reason: "exit code is 2".to_string(),
+                },
+                TestCaseError::FailedAssertion {
+                    assertion: "@assert file_exists out.txt".to_string(),
+                    reason: "file out.txt does not exist".to_string(),
+                },
+            ])),
+            testcase.validate(&output(2, None)),
+        );
+
+        let testcase = TestCase {
+            exit_code: Some(0.into()),
+            ..testcase
+        };
+        assert_eq!(
+            Err(TestCaseError::InvalidExitCode {
+                actual: 1,
+                expected: 0.into(),
+            }),
+            testcase.validate(&output(1, Some("abc"))),
+            "explicit exit code is still validated"
+        );
+    }
+
+    #[test]
+    fn test_validate_file_assertions() {
+        let testcase = TestCase {
+            shell_expression: "a command".to_string(),
+            config: serde_yaml::from_str("{assert_files: {exists: [out.txt], absent: [tmp/]}}")
+                .expect("parse config"),
+            ..Default::default()
+        };
+        assert!(!testcase.asserts_nothing());
+
+        let work_directory = tempfile::TempDir::new().expect("create work directory");
+        std::fs::write(work_directory.path().join("out.txt"), "").expect("write file");
+        let output = Output {
+            produced_files: testcase.produced_files(work_directory.path()),
+            ..("", "", Some(0)).into()
+        };
+        testcase.validate(&output).expect("files are as asserted");
+
+        std::fs::create_dir(work_directory.path().join("tmp")).expect("create directory");
+        let output = Output {
+            produced_files: testcase.produced_files(work_directory.path()),
+            ..("", "", Some(0)).into()
+        };
+        assert_eq!(
+            Err(TestCaseError::FailedAssertion {
+                assertion: "assert_files.absent tmp/".to_string(),
+                reason: "tmp/ exists".to_string(),
+            }),
+            testcase.validate(&output),
+        );
+    }
+
+    #[test]
+    fn test_validate_max_rss() {
+        let testcase = TestCase {
+            shell_expression: "a command".to_string(),
+            expectations: vec![test_expectation!("equal", "the stdout")],
+            config: TestCaseConfig {
+                max_rss: Some(ByteSize(1024)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let output = |stdout: &str, max_rss_bytes: u64| Output {
+            resource_usage: Some(ResourceUsage {
+                max_rss_bytes,
+                user_time: Duration::ZERO,
+                system_time: Duration::ZERO,
+            }),
+            ..(stdout, "").into()
+        };
+        testcase
+            .validate(&output("the stdout\n", 1024))
+            .expect("within max RSS");
+        testcase
+            .validate(&("the stdout\n", "").into())
+            .expect("without resource usage");
+        assert_eq!(
+            Err(TestCaseError::ExceededMaxRss {
+                rss_bytes: 1025,
+                max_rss_bytes: 1024,
+            }),
+            testcase.validate(&output("the stdout\n", 1025)),
+        );
+        assert!(
+            matches!(
+                testcase.validate(&output("other stdout\n", 1025)),
+                Err(TestCaseError::MalformedOutput(_))
+            ),
+            "invalid output takes precedence"
+        );
+    }
+
     #[test]
     fn test_validate_fails_on_invalid_exit_code() {
         let testcase = TestCase {
             title: "an testcase".to_string(),
             shell_expression: "a command".to_string(),
             expectations: vec![test_expectation!("no-eol", "the stdout", false, false)],
-            exit_code: Some(234),
+            exit_code: Some(234.into()),
             line_number: 123,
             ..Default::default()
         };
@@ -292,7 +1678,11 @@ mod tests {
                         actual,
                         "asserted output is delegated"
                     );
-                    assert_eq!(234, expected, "expected exit code is delegated");
+                    assert_eq!(
+                        ExpectedExitCode::Code(234),
+                        expected,
+                        "expected exit code is delegated"
+                    );
                 }
                 _ => panic!("unexpected error: {:?}", err),
             },
@@ -310,7 +1700,7 @@ mod tests {
                 false,
                 false
             )],
-            exit_code: Some(123),
+            exit_code: Some(123.into()),
             line_number: 234,
             ..Default::default()
         };
@@ -336,6 +1726,142 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_validate_collects_all_failures() {
+        let mut testcase = TestCase {
+            title: "an testcase".to_string(),
+            shell_expression: "a command".to_string(),
+            expectations: vec![test_expectation!("no-eol", "something not matching")],
+            exit_code: Some(234.into()),
+            line_number: 234,
+            ..Default::default()
+        };
+        let asserted_output = ("the stdout", "the stderr", Some(123)).into();
+        assert!(matches!(
+            testcase.validate(&asserted_output),
+            Err(TestCaseError::InvalidExitCode { .. })
+        ));
+
+        testcase.config.collect_all_failures = Some(true);
+        assert_eq!(
+            Err(TestCaseError::MultipleFailures(vec![
+                TestCaseError::MalformedOutput(Diff::new(vec![
+                    DiffLine::UnmatchedExpectation {
+                        index: 0,
+                        expectation: testcase.expectations[0].clone()
+                    },
+                    DiffLine::UnexpectedLines {
+                        lines: vec![(0, b"the stdout".to_vec())]
+                    },
+                ])),
+                TestCaseError::InvalidExitCode {
+                    actual: 123,
+                    expected: 234.into()
+                },
+            ])),
+            testcase.validate(&asserted_output),
+        );
+
+        testcase.exit_code = Some(123.into());
+        assert!(matches!(
+            testcase.validate(&asserted_output),
+            Err(TestCaseError::MalformedOutput(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_output_encoding() {
+        let output = Output {
+            stdout: b"\x89PNG\r\n".as_slice().into(),
+            ..("", "", Some(0)).into()
+        };
+        let testcase = |encoding, expectations| TestCase {
+            shell_expression: "a command".to_string(),
+            expectations,
+            config: TestCaseConfig {
+                output_encoding: Some(encoding),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let bytes = testcase(
+            OutputEncoding::Bytes,
+            vec![test_expectation!(
+                "equal",
+                "00000000  89 50 4e 47 0d 0a                                 |.PNG..|"
+            )],
+        );
+        bytes.validate(&output).expect("hex dump matches");
+        assert_eq!(
+            b"\x89PNG\r\n".as_slice(),
+            bytes
+                .render_output(b"\x89PNG\r\n")
+                .expect("render output")
+                .as_ref(),
+            "raw bytes are kept"
+        );
+
+        let digest = "5df2e1e81e6e0b5b6b37d2dc1b3d2bd8b5fc8e9bbfbc0ccbbb4e6c24b8bbdd03";
+        let hash = testcase(
+            OutputEncoding::Hash,
+            vec![test_expectation!("sha256", digest)],
+        );
+        assert_eq!(
+            Err(TestCaseError::MalformedOutput(Diff::new(vec![
+                DiffLine::UnmatchedExpectation {
+                    index: 0,
+                    expectation: hash.expectations[0].clone()
+                },
+                DiffLine::UnexpectedLines {
+                    lines: vec![(
+                        0,
+                        format!("(sha256:{})\n", sha256_hex(b"\x89PNG\r\n")).into_bytes()
+                    )]
+                },
+            ]))),
+            hash.validate(&output),
+            "digest is shown instead of the output"
+        );
+    }
+
+    #[test]
+    fn test_validate_invalid_utf8() {
+        let tc = TestCase {
+            expectations: vec![test_expectation!("glob", "foo*")],
+            config: TestCaseConfig {
+                invalid_utf8: Some(InvalidUtf8::Error),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(Ok(()), tc.validate(&("foo\n", "", Some(0)).into()));
+        let output = Output {
+            stdout: b"foo\xff\n".as_slice().into(),
+            ..("", "", Some(0)).into()
+        };
+        assert_eq!(
+            Err(TestCaseError::InvalidUtf8Output { offset: 3 }),
+            tc.validate(&output)
+        );
+
+        // invalid bytes of raw output are expected
+        let tc = TestCase {
+            config: TestCaseConfig {
+                output_encoding: Some(OutputEncoding::Hash),
+                ..tc.config
+            },
+            ..tc
+        };
+        assert!(
+            !matches!(
+                tc.validate(&output),
+                Err(TestCaseError::InvalidUtf8Output { .. })
+            ),
+            "hashed output is not validated as UTF-8"
+        );
+    }
+
     #[test]
     fn test_render_output_crlf_support() {
         let tests = &[
@@ -350,8 +1876,12 @@ mod tests {
             let tc = TestCase {
                 title: "an testcase".to_string(),
                 shell_expression: "a command".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("no-eol", "the stdout")],
-                exit_code: Some(123),
+                variants: vec![],
+                exit_code: Some(123.into()),
+                assertions: vec![],
                 line_number: 234,
                 config: TestCaseConfig {
                     keep_crlf: Some(*crlf_support),
@@ -371,6 +1901,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_render_output_normalize_crlf() {
+        let tests = &[
+            (None, None, "foo\nbar\n"),
+            (Some(true), None, "foo\r\nbar\r\n"),
+            (Some(true), Some(false), "foo\r\nbar\r\n"),
+            (Some(true), Some(true), "foo\nbar\n"),
+            (Some(false), Some(true), "foo\nbar\n"),
+        ];
+        for (keep_crlf, normalize_crlf, expect) in tests {
+            let tc = TestCase {
+                config: TestCaseConfig {
+                    keep_crlf: *keep_crlf,
+                    normalize_crlf: *normalize_crlf,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let output = tc
+                .render_output(b"foo\r\nbar\r\n")
+                .expect("rendering should succeed");
+            assert_eq!(
+                *expect,
+                lossy_string!(&output),
+                "keep_crlf = {keep_crlf:?}, normalize_crlf = {normalize_crlf:?}"
+            );
+        }
+    }
+
     #[test]
     fn test_render_output_strip_ansi_escaping() {
         let tests = &[
@@ -384,13 +1943,18 @@ mod tests {
                 "foo\n\x1b[1mbar\x1b[0m\nbaz",
             ),
             (true, "foo\n\x1b[1mbar\x1b[0m\nbaz", "foo\nbar\nbaz"),
+            (true, "\x1b[2K\x1b[1Gfoo\x1b[3A\x1b[?25l", "foo"),
         ];
         for (strip_ansi_escaping, from, expect) in tests {
             let tc = TestCase {
                 title: "an testcase".to_string(),
                 shell_expression: "a command".to_string(),
+                template_arguments: None,
+                interaction: vec![],
                 expectations: vec![test_expectation!("no-eol", "the stdout")],
-                exit_code: Some(123),
+                variants: vec![],
+                exit_code: Some(123.into()),
+                assertions: vec![],
                 line_number: 234,
                 config: TestCaseConfig {
                     strip_ansi_escaping: Some(*strip_ansi_escaping),
@@ -409,4 +1973,174 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_render_output_invalid_utf8() {
+        let tests: &[(Option<InvalidUtf8>, &[u8], Option<&str>)] = &[
+            (None, b"f\xffo\\o", None),
+            (Some(InvalidUtf8::Replace), b"foo", Some("foo")),
+            (
+                Some(InvalidUtf8::Replace),
+                b"f\xffo\\o",
+                Some("f\u{fffd}o\\o"),
+            ),
+            (Some(InvalidUtf8::Escape), "f😂o".as_bytes(), Some("f😂o")),
+            (
+                Some(InvalidUtf8::Escape),
+                b"f\xffo\\o",
+                Some("f\\xffo\\\\o"),
+            ),
+            (Some(InvalidUtf8::Error), b"f\xffo\\o", None),
+        ];
+        for (invalid_utf8, from, expect) in tests {
+            let tc = TestCase {
+                config: TestCaseConfig {
+                    invalid_utf8: *invalid_utf8,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let output = tc.render_output(from).expect("rendering should succeed");
+            match expect {
+                Some(expect) => assert_eq!(
+                    *expect,
+                    std::str::from_utf8(&output).expect("rendered output is UTF-8"),
+                    "from {from:?} ({invalid_utf8:?})"
+                ),
+                None => assert_eq!(from, &output.as_ref(), "from {from:?} ({invalid_utf8:?})"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_redact_output() {
+        let environment = BTreeMap::from([
+            ("TOKEN".to_string(), "s3cr3t".to_string()),
+            ("LONG_TOKEN".to_string(), "s3cr3t-and-more".to_string()),
+            ("EMPTY".to_string(), "".to_string()),
+        ]);
+        let tests = [
+            (vec![], "token s3cr3t", "token s3cr3t"),
+            (vec!["TOKEN"], "token s3cr3t", "token [REDACTED]"),
+            (
+                vec!["TOKEN", "LONG_TOKEN"],
+                "s3cr3t-and-more and s3cr3t",
+                "[REDACTED] and [REDACTED]",
+            ),
+            (vec!["EMPTY", "SCRUT_UNSET_TOKEN"], "empty", "empty"),
+            (vec!["key-[0-9]+"], "key-123 key-abc", "[REDACTED] key-abc"),
+            (vec!["(?:TOKEN)"], "TOKEN s3cr3t", "[REDACTED] s3cr3t"),
+        ];
+        for (redact, stdout, expected) in tests {
+            let testcase = TestCase {
+                config: TestCaseConfig {
+                    redact: redact.iter().map(|entry| entry.to_string()).collect(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let output = testcase
+                .redact_output((stdout, stdout).into(), &environment)
+                .expect("redact output");
+            assert_eq!(
+                expected,
+                lossy_string!(&output.stdout.to_bytes()),
+                "stdout for {redact:?}"
+            );
+            assert_eq!(
+                expected,
+                lossy_string!(&output.stderr.to_bytes()),
+                "stderr for {redact:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_redact_output_fails_on_invalid_expression() {
+        let testcase = TestCase {
+            config: TestCaseConfig {
+                redact: vec!["foo(".into()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        testcase
+            .redact_output(("foo", "").into(), &BTreeMap::new())
+            .expect_err("invalid expression");
+    }
+
+    #[test]
+    fn test_normalize_paths_in_output() {
+        let tests = [
+            (None, "C:\\Users\\me", "C:\\Users\\me"),
+            (Some(false), "C:\\Users\\me", "C:\\Users\\me"),
+            (Some(true), "C:\\Users\\me", "/Users/me"),
+            (Some(true), "at d:\\foo, e:/bar", "at /foo, /bar"),
+            (Some(true), "/work/dir/foo/bar.txt", "foo/bar.txt"),
+            (Some(true), "in /work/dir\n(/work/dir)", "in .\n(.)"),
+            (Some(true), "/work/dir.bak/foo", "/work/dir.bak/foo"),
+            (Some(true), "/work/directory/foo", "/work/directory/foo"),
+            (Some(true), "C:\\work\\dir\\foo", "foo"),
+        ];
+        for (normalize_paths, stdout, expected) in tests {
+            let testcase = TestCase {
+                config: TestCaseConfig {
+                    normalize_paths,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let output = testcase
+                .normalize_paths_in_output((stdout, stdout).into(), Path::new("/work/dir/"));
+            assert_eq!(
+                expected,
+                lossy_string!(&output.stdout.to_bytes()),
+                "stdout for {stdout:?} with {normalize_paths:?}"
+            );
+            assert_eq!(
+                expected,
+                lossy_string!(&output.stderr.to_bytes()),
+                "stderr for {stdout:?} with {normalize_paths:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_anonymize_user_in_output() {
+        let environment = BTreeMap::from([
+            ("HOME".to_string(), "/home/jdoe/".to_string()),
+            ("USER".to_string(), "jdoe".to_string()),
+        ]);
+        let tests = [
+            (None, None, "/home/jdoe/foo", "/home/jdoe/foo"),
+            (Some(true), None, "/home/jdoe/foo", "~/foo"),
+            (Some(true), None, "in /home/jdoe\nby jdoe", "in ~\nby $USER"),
+            (Some(true), None, "/home/jdoe2 jdoes", "/home/jdoe2 jdoes"),
+            (Some(true), None, "owner: jdoe:staff", "owner: $USER:staff"),
+            (Some(true), Some(true), "C:\\home\\jdoe\\foo", "~/foo"),
+        ];
+        for (anonymize_user, normalize_paths, stdout, expected) in tests {
+            let testcase = TestCase {
+                config: TestCaseConfig {
+                    anonymize_user,
+                    normalize_paths,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let output =
+                testcase.normalize_paths_in_output((stdout, stdout).into(), Path::new("/work"));
+            let output = testcase.anonymize_user_in_output(output, &environment);
+            assert_eq!(
+                expected,
+                lossy_string!(&output.stdout.to_bytes()),
+                "stdout for {stdout:?} with {anonymize_user:?}"
+            );
+            assert_eq!(
+                expected,
+                lossy_string!(&output.stderr.to_bytes()),
+                "stderr for {stdout:?} with {anonymize_user:?}"
+            );
+        }
+    }
 }